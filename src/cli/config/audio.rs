@@ -9,6 +9,9 @@ pub struct AudioConfig {
 	pub channels: Option<String>,
 	pub sample_rate: Option<String>,
 	pub volume: Option<String>,
+	pub compression_level: Option<String>,
+	pub bitrate: Option<String>,
+	pub quality: Option<String>,
 }
 
 pub fn parse_audio(tokens: Vec<String>) -> Result<AudioConfig> {
@@ -21,5 +24,8 @@ pub fn parse_audio(tokens: Vec<String>) -> Result<AudioConfig> {
 		channels: map.get("channels").cloned(),
 		sample_rate: map.get("sample_rate").cloned(),
 		volume: map.get("volume").cloned(),
+		compression_level: map.get("compression_level").cloned(),
+		bitrate: map.get("bitrate").cloned(),
+		quality: map.get("quality").cloned(),
 	})
 }
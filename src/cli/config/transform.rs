@@ -1,6 +1,15 @@
 use super::track::parse_track_id;
 use crate::cli::config::parse_flags;
-use crate::message::Result;
+use crate::{error, message::Result};
+
+pub const TRANSFORM_NAMES: &[&str] =
+	&["gain", "volume", "normalize", "trim", "fade", "reverse", "speed", "rotate", "filter_chain"];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransformSpec {
+	pub name: String,
+	pub value: String,
+}
 
 #[derive(Debug, Default)]
 pub struct TransformConfig {
@@ -12,9 +21,12 @@ pub struct TransformConfig {
 	pub speed: Option<String>,
 	pub rotate: Option<String>,
 	pub filter_chain: Option<String>,
+	// transforms in the order they were given on the command line
+	pub chain: Vec<TransformSpec>,
 }
 
 pub fn parse_transform(tokens: Vec<String>) -> Result<TransformConfig> {
+	let chain = parse_chain(&tokens)?;
 	let map = parse_flags(tokens, true);
 	let track = parse_track_id(&map)?;
 
@@ -27,5 +39,45 @@ pub fn parse_transform(tokens: Vec<String>) -> Result<TransformConfig> {
 		speed: map.get("speed").cloned(),
 		rotate: map.get("rotate").cloned(),
 		filter_chain: map.get("filter_chain").cloned(),
+		chain,
 	})
 }
+
+fn parse_chain(tokens: &[String]) -> Result<Vec<TransformSpec>> {
+	let mut chain = Vec::new();
+	for token in tokens {
+		let spec = parse_spec(token)?;
+		match spec.name.as_str() {
+			"track" => continue,
+			"filter_chain" => {
+				// filter_chain=gain=2.0,normalize expands in place
+				for inner in spec.value.split(',').filter(|s| !s.trim().is_empty()) {
+					let inner = parse_spec(inner.trim())?;
+					if inner.name == "filter_chain" || inner.name == "track" {
+						return Err(error!("'{}' is not allowed inside filter_chain", inner.name));
+					}
+					chain.push(inner);
+				}
+			}
+			_ => chain.push(spec),
+		}
+	}
+	Ok(chain)
+}
+
+fn parse_spec(token: &str) -> Result<TransformSpec> {
+	let (name, value) = match token.split_once('=') {
+		Some((name, value)) => (name, value),
+		None => (token, "true"),
+	};
+
+	if name != "track" && !TRANSFORM_NAMES.contains(&name) {
+		return Err(error!(
+			"unknown transform '{}' (expected one of: {})",
+			name,
+			TRANSFORM_NAMES.join(", ")
+		));
+	}
+
+	Ok(TransformSpec { name: name.to_string(), value: value.to_string() })
+}
@@ -1,6 +1,8 @@
+use crate::cli::pipeline::containers;
 use crate::cli::{config, pipeline, utils};
-use crate::container;
-use crate::core::compatible;
+use crate::codecs;
+use crate::container::probe::PROBE_SIZE;
+use crate::io::stdio::StdioSource;
 use crate::{cli, error, message};
 
 pub fn execute(cli: cli::Cli) -> message::Result<()> {
@@ -12,40 +14,37 @@ pub fn execute(cli: cli::Cli) -> message::Result<()> {
 	let transform = config::parse_transform(cli.apply)?;
 	pipe.with_transform(transform);
 
-	let input_ext = utils::get_extension(&cli.input)?;
-	let output_ext = utils::get_extension(&cli.output)?;
+	let output = containers::find(&utils::get_extension(&cli.output)?)?;
 
-	let compat = compatible::Compatible::new();
-	compat.assert_container_supported(&input_ext)?;
-	compat.assert_container_supported(&output_ext)?;
+	// the content tells the input format, the name only when it cannot
+	let mut source = StdioSource::open(&cli.input)?;
+	let probe_data = source.peek(PROBE_SIZE)?;
+	let extension = utils::get_extension(&cli.input).ok();
+	let input = containers::detect(&probe_data, extension.as_deref());
+	let input = input.ok_or_else(|| error!("cannot tell the format of '{}'", cli.input))?;
+	pipe.with_input_container(input.name);
 
-	if let Some(codec) = &audio.codec {
-		compat.assert_audio_supported(&input_ext, codec)?;
-		pipe.with_audio(audio);
+	// encoded streams must fit the output, copied ones are checked once
+	// the stream map picks them
+	if let Some(codec) = encoded(&audio.codec) {
+		output.assert_audio(codec)?;
 	}
+	pipe.with_audio(audio);
 
-	if let Some(codec) = &video.codec {
-		compat.assert_video_supported(&input_ext, codec)?;
-		pipe.with_video(video);
+	if let Some(codec) = encoded(&video.codec) {
+		output.assert_video(codec)?;
 	}
+	pipe.with_video(video);
 
-	if let Some(codec) = &subtitle.codec {
-		compat.assert_subtitle_supported(&input_ext, codec)?;
-		pipe.with_subtitle(subtitle);
+	if let Some(codec) = encoded(&subtitle.codec) {
+		output.assert_subtitle(codec)?;
 	}
+	pipe.with_subtitle(subtitle);
 
-	// Route based on output format first for clarity
-	match output_ext.as_str() {
-		container::WAV => pipeline::wav::run(pipe),
-		container::RAW | container::PCM => pipeline::raw::run(pipe),
-		_ => {
-			// Fall back to input-based routing
-			match input_ext.as_str() {
-				container::WAV => pipeline::wav::run(pipe),
-				container::RAW | container::PCM => pipeline::raw::run(pipe),
-				container::MOV => pipeline::webm::run(pipe),
-				_ => Err(error!("unsupported '{}' format", input_ext)),
-			}
-		}
-	}
+	pipeline::engine::run(pipe, source)
+}
+
+/// The codec a stream is encoded to, `None` when it is copied.
+fn encoded(codec: &Option<String>) -> Option<&str> {
+	codec.as_deref().filter(|codec| *codec != codecs::COPY)
 }
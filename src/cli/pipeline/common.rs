@@ -1,9 +1,24 @@
 use crate::cli::config;
+use crate::codecs;
+use crate::codecs::audio::aac::AacDecoder;
+use crate::codecs::audio::adpcm::AdpcmDecoder;
+use crate::codecs::audio::flac::FlacDecoder;
+use crate::codecs::audio::g711::{G711Decoder, G711Encoder, G711Law};
+use crate::codecs::audio::mp3::Mp3Decoder;
+use crate::codecs::audio::opus::OpusDecoder;
+use crate::codecs::audio::pcm::{PcmDecoder, PcmEncoder, layout};
+use crate::container::wav;
+use crate::core::frame::AudioFormat;
+use crate::core::stream::Stream;
+use crate::core::{Decoder, Encoder};
+use crate::message::Result;
 
 #[derive(Debug, Default)]
 pub struct Pipeline {
 	pub input: String,
 	pub output: String,
+	/// Container of the input, as probed from its content.
+	pub input_container: String,
 	pub audio: config::AudioConfig,
 	pub video: config::VideoConfig,
 	pub subtitle: config::SubtitleConfig,
@@ -15,6 +30,10 @@ impl Pipeline {
 		Self { input: input.to_string(), output: output.to_string(), ..Default::default() }
 	}
 
+	pub fn with_input_container(&mut self, container: &str) {
+		self.input_container = container.to_string();
+	}
+
 	pub fn with_audio(&mut self, audio: config::AudioConfig) {
 		self.audio = audio;
 	}
@@ -31,3 +50,52 @@ impl Pipeline {
 		self.transform = transform;
 	}
 }
+
+pub fn create_audio_decoder(stream: &Stream, format: &wav::WavFormat) -> Result<Box<dyn Decoder>> {
+	match stream.codec.as_str() {
+		codecs::audio::FLAC => {
+			let decoder = FlacDecoder::new_from_codec_private(&stream.codec_private)?;
+			Ok(Box::new(decoder))
+		}
+		codecs::audio::MP3 => Ok(Box::new(Mp3Decoder::new())),
+		codecs::audio::AAC => {
+			let decoder = AacDecoder::new_from_codec_private(&stream.codec_private)?;
+			Ok(Box::new(decoder))
+		}
+		codecs::audio::OPUS => {
+			let decoder = OpusDecoder::new_from_codec_private(&stream.codec_private)?;
+			Ok(Box::new(decoder))
+		}
+		codecs::audio::ADPCM_IMA_WAV | codecs::audio::ADPCM_MS => {
+			Ok(Box::new(AdpcmDecoder::new_from_metadata(format)?))
+		}
+		codecs::audio::PCM_MULAW | codecs::audio::PCM_ALAW => {
+			Ok(Box::new(G711Decoder::new_from_metadata(format)?))
+		}
+		codecs::audio::PCM_S8
+		| codecs::audio::PCM_S16BE
+		| codecs::audio::PCM_S24BE
+		| codecs::audio::PCM_S32BE
+		| codecs::audio::PCM_F32BE
+		| codecs::audio::PCM_F64BE => {
+			let layout = layout::format_for_codec(&stream.codec).unwrap_or(format.audio_format());
+			Ok(Box::new(PcmDecoder::new(format.sample_rate, format.channels, layout)))
+		}
+		_ => Ok(Box::new(PcmDecoder::new_from_metadata(format))),
+	}
+}
+
+/// Encoder of uncompressed or G.711 samples stored as `format`.
+pub fn create_pcm_encoder(sample_rate: u32, format: AudioFormat) -> Box<dyn Encoder> {
+	let law = match format {
+		AudioFormat::MULAW => Some(G711Law::MuLaw),
+		AudioFormat::ALAW => Some(G711Law::ALaw),
+		_ => None,
+	};
+	if let Some(law) = law {
+		return Box::new(G711Encoder::new(law, sample_rate));
+	}
+
+	// the decoders hand out native samples, the encoder lays them out
+	Box::new(PcmEncoder::new(sample_rate).with_target_format(format))
+}
@@ -0,0 +1,155 @@
+use super::common::Pipeline;
+use super::demuxers::{self, Input};
+use super::muxers::{self, MuxerFactory, Output, Source};
+use crate::container::{self, registry};
+use crate::io::stdio::StdioSource;
+use crate::{error, message::Result};
+
+/// How the pipeline reads and writes a container of the registry.
+struct Io {
+	name: &'static str,
+	/// Opens an input; `None` when the container cannot be read.
+	open: Option<fn(StdioSource) -> Result<Input>>,
+	/// `None` when the container cannot be written.
+	muxer: Option<MuxerFactory>,
+}
+
+/// A container of the registry, with how the pipeline reads and writes it.
+#[derive(Clone, Copy)]
+pub struct Container {
+	pub name: &'static str,
+	pub registered: &'static registry::Container,
+	io: Option<&'static Io>,
+}
+
+impl Container {
+	fn new(registered: &'static registry::Container) -> Self {
+		let io = IO.iter().find(|io| io.name == registered.name);
+		Self { name: registered.name, registered, io }
+	}
+
+	pub fn open(&self, source: StdioSource) -> Result<Input> {
+		let open = self.io.and_then(|io| io.open);
+		let open = open.ok_or_else(|| error!("reading '{}' is not supported", self.name))?;
+		open(source)
+	}
+
+	pub fn muxer(&self) -> Result<&'static MuxerFactory> {
+		let muxer = self.io.and_then(|io| io.muxer.as_ref());
+		muxer.ok_or_else(|| error!("writing '{}' is not supported", self.name))
+	}
+
+	pub fn assert_audio(&self, codec: &str) -> Result<()> {
+		self.registered.assert_audio(codec)
+	}
+
+	pub fn assert_video(&self, codec: &str) -> Result<()> {
+		self.registered.assert_video(codec)
+	}
+
+	pub fn assert_subtitle(&self, format: &str) -> Result<()> {
+		self.registered.assert_subtitle(format)
+	}
+}
+
+type Create = fn(&Pipeline, &Input, &[Source]) -> Result<Output>;
+
+const fn muxer(multi_stream: bool, create: Create) -> Option<MuxerFactory> {
+	Some(MuxerFactory { multi_stream, create })
+}
+
+const IO: &[Io] = &[
+	Io {
+		name: container::WAV,
+		open: Some(demuxers::open_wav),
+		muxer: muxer(false, muxers::create_wav),
+	},
+	Io {
+		name: container::AIFF,
+		open: Some(demuxers::open_aiff),
+		muxer: muxer(false, muxers::create_aiff),
+	},
+	Io {
+		name: container::AIF,
+		open: Some(demuxers::open_aiff),
+		muxer: muxer(false, muxers::create_aiff),
+	},
+	Io {
+		name: container::AIFC,
+		open: Some(demuxers::open_aiff),
+		muxer: muxer(false, muxers::create_aiff),
+	},
+	Io { name: container::AU, open: Some(demuxers::open_au), muxer: muxer(false, muxers::create_au) },
+	Io {
+		name: container::SND,
+		open: Some(demuxers::open_au),
+		muxer: muxer(false, muxers::create_au),
+	},
+	Io {
+		name: container::VOC,
+		open: Some(demuxers::open_voc),
+		muxer: muxer(false, muxers::create_voc),
+	},
+	Io {
+		name: container::FLAC,
+		open: Some(demuxers::open_flac),
+		muxer: muxer(false, muxers::create_flac),
+	},
+	Io {
+		name: container::OGG,
+		open: Some(demuxers::open_ogg),
+		muxer: muxer(true, muxers::create_ogg),
+	},
+	Io {
+		name: container::OPUS,
+		open: Some(demuxers::open_ogg),
+		muxer: muxer(false, muxers::create_ogg),
+	},
+	Io {
+		name: container::MKV,
+		open: Some(demuxers::open_mkv),
+		muxer: muxer(true, muxers::create_mkv),
+	},
+	Io {
+		name: container::WEBM,
+		open: Some(demuxers::open_mkv),
+		muxer: muxer(true, muxers::create_webm),
+	},
+	Io {
+		name: container::MP4,
+		open: Some(demuxers::open_mp4),
+		muxer: muxer(true, muxers::create_mp4),
+	},
+	Io {
+		name: container::M4A,
+		open: Some(demuxers::open_mp4),
+		muxer: muxer(true, muxers::create_m4a),
+	},
+	Io { name: container::MOV, open: Some(demuxers::open_mp4), muxer: None },
+	Io { name: container::MP3, open: Some(demuxers::open_mp3), muxer: None },
+	Io {
+		name: container::AAC,
+		open: Some(demuxers::open_aac),
+		muxer: muxer(false, muxers::create_adts),
+	},
+	Io {
+		name: container::RAW,
+		open: Some(demuxers::open_raw),
+		muxer: muxer(false, muxers::create_raw),
+	},
+	Io {
+		name: container::PCM,
+		open: Some(demuxers::open_raw),
+		muxer: muxer(false, muxers::create_raw),
+	},
+];
+
+pub fn find(name: &str) -> Result<Container> {
+	registry::find(name).map(Container::new)
+}
+
+/// The container of an input starting with `data`, from its content first
+/// and the `extension` of its name after.
+pub fn detect(data: &[u8], extension: Option<&str>) -> Option<Container> {
+	registry::detect(data, extension).map(Container::new)
+}
@@ -0,0 +1,221 @@
+use crate::codecs;
+use crate::codecs::audio::aac::AudioSpecificConfig;
+use crate::codecs::audio::flac::StreamInfo;
+use crate::codecs::audio::opus::packet::OpusHead;
+use crate::container::{aac, aiff, au, flac, mkv, mp3, mp4, ogg, raw, voc, wav};
+use crate::core::Demuxer;
+use crate::core::frame::{AudioFormat, Channels};
+use crate::core::stream::Stream;
+use crate::core::time::Time;
+use crate::io::stdio::StdioSource;
+use crate::{error, message::Result};
+
+/// What an input keeps beyond its samples, for an output of the same
+/// container to carry over.
+#[derive(Debug, Clone, Default)]
+pub enum Metadata {
+	#[default]
+	None,
+	Wav(Box<wav::WavMetadata>),
+	Flac(flac::FlacMetadata),
+	Aiff(aiff::AiffFormat, aiff::AiffMetadata),
+	Au(au::AuFormat, String),
+	Voc(voc::VocFormat),
+}
+
+/// What the tracks of a container tell about a stream beyond `Stream`.
+#[derive(Debug, Clone, Default)]
+pub struct Details {
+	pub language: Option<String>,
+	pub width: u32,
+	pub height: u32,
+	/// Samples the decoder drops before the first one presented, such as
+	/// the aac encoder delay, in the time units of the stream.
+	pub delay: i64,
+}
+
+/// An opened input.
+pub struct Input {
+	pub demuxer: Box<dyn Demuxer>,
+	/// How each stream is decoded, by stream index; `None` for streams
+	/// that cannot be.
+	pub formats: Vec<Option<wav::WavFormat>>,
+	/// Track details by stream index, for containers that have them.
+	pub details: Vec<Details>,
+	pub metadata: Metadata,
+}
+
+impl Input {
+	fn new(demuxer: Box<dyn Demuxer>, formats: Vec<Option<wav::WavFormat>>) -> Self {
+		Self { demuxer, formats, details: Vec::new(), metadata: Metadata::None }
+	}
+
+	fn with_metadata(mut self, metadata: Metadata) -> Self {
+		self.metadata = metadata;
+		self
+	}
+
+	fn with_details(mut self, details: Vec<Details>) -> Self {
+		self.details = details;
+		self
+	}
+
+	pub fn details(&self, stream: &Stream) -> Details {
+		self.details.get(stream.index).cloned().unwrap_or_default()
+	}
+
+	/// The PCM layout `stream` is decoded with.
+	pub fn format(&self, stream: &Stream) -> Result<wav::WavFormat> {
+		match self.formats.get(stream.index) {
+			Some(Some(format)) => Ok(*format),
+			_ => Err(error!("decoding '{}' is not supported", stream.codec)),
+		}
+	}
+}
+
+pub fn open_wav(file: StdioSource) -> Result<Input> {
+	// a pipe is read through, so chunks after the audio reach the muxer at
+	// its end rather than here
+	let demuxer = if file.is_seekable() {
+		wav::WavDemuxer::new(file)?
+	} else {
+		wav::WavDemuxer::new_stream(file)?
+	};
+	let format = demuxer.format();
+	let metadata = Metadata::Wav(Box::new(demuxer.metadata().clone()));
+	Ok(Input::new(Box::new(demuxer), vec![Some(format)]).with_metadata(metadata))
+}
+
+pub fn open_aiff(file: StdioSource) -> Result<Input> {
+	let demuxer = if file.is_seekable() {
+		aiff::AiffDemuxer::new(file)?
+	} else {
+		aiff::AiffDemuxer::new_stream(file)?
+	};
+	let format = demuxer.format();
+	let metadata = Metadata::Aiff(format, demuxer.metadata().clone());
+	Ok(Input::new(Box::new(demuxer), vec![Some(format.to_wav_format())]).with_metadata(metadata))
+}
+
+pub fn open_au(file: StdioSource) -> Result<Input> {
+	let demuxer = au::AuDemuxer::new(file)?;
+	let format = demuxer.format();
+	let metadata = Metadata::Au(format, demuxer.annotation().to_string());
+	Ok(Input::new(Box::new(demuxer), vec![Some(format.to_wav_format())]).with_metadata(metadata))
+}
+
+pub fn open_voc(file: StdioSource) -> Result<Input> {
+	let demuxer = if file.is_seekable() {
+		voc::VocDemuxer::new(file)?
+	} else {
+		voc::VocDemuxer::new_stream(file)?
+	};
+	let format = demuxer.format();
+	let formats = vec![Some(format.to_wav_format())];
+	Ok(Input::new(Box::new(demuxer), formats).with_metadata(Metadata::Voc(format)))
+}
+
+pub fn open_flac(file: StdioSource) -> Result<Input> {
+	let demuxer = flac::FlacDemuxer::new(file)?;
+	let info = demuxer.stream_info();
+	let format =
+		wav::WavFormat::from_audio_format(info.audio_format(), info.channel_layout(), info.sample_rate);
+	let metadata = Metadata::Flac(demuxer.metadata().clone());
+	Ok(Input::new(Box::new(demuxer), vec![Some(format)]).with_metadata(metadata))
+}
+
+pub fn open_mp3(file: StdioSource) -> Result<Input> {
+	let demuxer = mp3::Mp3Demuxer::new(file)?;
+	let header = demuxer.first_header();
+	let channels = Channels::from_count(header.channels());
+	let format = wav::WavFormat::from_audio_format(AudioFormat::PCM16, channels, header.sample_rate);
+	Ok(Input::new(Box::new(demuxer), vec![Some(format)]))
+}
+
+pub fn open_aac(file: StdioSource) -> Result<Input> {
+	let demuxer = aac::AdtsDemuxer::new(file)?;
+	let format = aac_format(&demuxer.first_header().config())?;
+	Ok(Input::new(Box::new(demuxer), vec![Some(format)]))
+}
+
+pub fn open_ogg(file: StdioSource) -> Result<Input> {
+	let demuxer = ogg::OggDemuxer::new(file)?;
+	let formats = demuxer.streams().all().iter().map(|stream| ogg_format(stream).ok()).collect();
+	Ok(Input::new(Box::new(demuxer), formats))
+}
+
+pub fn open_mkv(file: StdioSource) -> Result<Input> {
+	let demuxer = mkv::MkvDemuxer::new(file)?;
+	let (mut formats, mut details) = (Vec::new(), Vec::new());
+	for stream in demuxer.streams().all() {
+		let track = &demuxer.tracks()[stream.index];
+		let channels = Channels::from_count(track.channels);
+		formats.push(track_format(stream, channels, track.sample_rate as u32).ok());
+		let language = track.language.clone();
+		let delay = stream.time.round_pts(track.codec_delay as i64, Time::new(1, 1_000_000_000));
+		details.push(Details { language, width: track.width, height: track.height, delay });
+	}
+	Ok(Input::new(Box::new(demuxer), formats).with_details(details))
+}
+
+pub fn open_mp4(file: StdioSource) -> Result<Input> {
+	let demuxer = mp4::Mp4Demuxer::new(file)?;
+	let (mut formats, mut details) = (Vec::new(), Vec::new());
+	for stream in demuxer.streams().all() {
+		let track = &demuxer.tracks()[stream.index];
+		let channels = Channels::from_count(track.channels as u8);
+		formats.push(track_format(stream, channels, track.sample_rate).ok());
+		let (width, height) = (track.width as u32, track.height as u32);
+		let (language, delay) = (track.language.clone(), (-track.edit_shift).max(0));
+		details.push(Details { language, width, height, delay });
+	}
+	Ok(Input::new(Box::new(demuxer), formats).with_details(details))
+}
+
+// raw input carries no layout, it is read as the default one
+pub fn open_raw(file: StdioSource) -> Result<Input> {
+	let format = wav::WavFormat::default();
+	let demuxer = raw::RawPcmDemuxer::new(file, format.to_raw_format())?;
+	Ok(Input::new(Box::new(demuxer), vec![Some(format)]))
+}
+
+fn ogg_format(stream: &Stream) -> Result<wav::WavFormat> {
+	if stream.codec != codecs::audio::OPUS {
+		return Err(error!("decoding '{}' is not supported", stream.codec));
+	}
+	let head = OpusHead::parse(&stream.codec_private)?;
+	let channels = Channels::from_count(head.channels);
+	Ok(wav::WavFormat::from_audio_format(AudioFormat::PCM16, channels, 48000))
+}
+
+/// PCM layout the decoder of an audio track of a matroska or mp4 input
+/// produces.
+fn track_format(stream: &Stream, channels: Channels, sample_rate: u32) -> Result<wav::WavFormat> {
+	if !stream.audio_kind() {
+		return Err(error!("stream {} is not audio", stream.index));
+	}
+	let format = match stream.codec.as_str() {
+		codecs::audio::OPUS => {
+			let head = OpusHead::parse(&stream.codec_private)?;
+			(AudioFormat::PCM16, Channels::from_count(head.channels), 48000)
+		}
+		codecs::audio::FLAC => {
+			let info = StreamInfo::parse(&stream.codec_private)?;
+			(info.audio_format(), info.channel_layout(), info.sample_rate)
+		}
+		codecs::audio::MP3 | codecs::audio::PCM_S16LE => (AudioFormat::PCM16, channels, sample_rate),
+		codecs::audio::PCM_S24LE => (AudioFormat::PCM24, channels, sample_rate),
+		codecs::audio::PCM_F32LE => (AudioFormat::PCM32, channels, sample_rate),
+		codecs::audio::AAC => return aac_format(&AudioSpecificConfig::parse(&stream.codec_private)?),
+		codec => return Err(error!("decoding '{}' is not supported", codec)),
+	};
+	Ok(wav::WavFormat::from_audio_format(format.0, format.1, format.2))
+}
+
+fn aac_format(config: &AudioSpecificConfig) -> Result<wav::WavFormat> {
+	if config.channels() == 0 {
+		return Err(error!("aac channel layouts from a program config are not supported"));
+	}
+	let channels = Channels::from_count(config.channels());
+	Ok(wav::WavFormat::from_audio_format(AudioFormat::PCM16, channels, config.sample_rate))
+}
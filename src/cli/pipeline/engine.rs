@@ -0,0 +1,112 @@
+use super::common::{self, Pipeline};
+use super::containers;
+use super::map::{Action, StreamMap};
+use super::muxers::Source;
+use crate::cli::transcoder::{media, transform};
+use crate::cli::utils;
+use crate::container::interleave::Interleaver;
+use crate::core::Muxer;
+use crate::core::packet::Packet;
+use crate::core::time::Time;
+use crate::io::stdio::StdioSource;
+use crate::{error, message::Result};
+
+/// A stream of the input on its way to the output.
+struct Route {
+	/// Id of the stream in the input.
+	input_id: u32,
+	/// Id its packets take in the output.
+	output_id: u32,
+	/// Time base of the stream in the output.
+	time: Time,
+	/// `None` for a stream that is copied.
+	transcoder: Option<media::Transcoder>,
+}
+
+/// Runs `pipeline` on `source`, its probed input, for any pair of
+/// registered containers: the input is opened once, the stream map decides
+/// what each stream becomes, each transcoded stream gets its own
+/// transcoder, copied packets only move to the time base of the output,
+/// and the packets reach the muxer in decoding order across streams.
+pub fn run(pipeline: Pipeline, source: StdioSource) -> Result<()> {
+	let mut input = containers::find(&pipeline.input_container)?.open(source)?;
+	let container = containers::find(&utils::get_extension(&pipeline.output)?)?;
+	let factory = container.muxer()?;
+	let map = StreamMap::new(&pipeline, &input, &container)?;
+
+	let mut sources = Vec::new();
+	for mapping in map.outputs() {
+		let stream = mapping.stream.clone();
+		sources.push(match mapping.action {
+			Action::Transcode => Source::Decoded(stream.clone(), input.format(&stream)?),
+			_ => Source::Copied(stream),
+		});
+	}
+
+	let output = (factory.create)(&pipeline, &input, &sources)?;
+	let mut muxer = Interleaver::new(output.muxer);
+	let mut encoders = output.encoders.into_iter();
+	let mut routes = Vec::new();
+	for (output_id, source) in sources.iter().enumerate() {
+		let transcoder = match source {
+			Source::Decoded(stream, format) => {
+				let encoder = encoders.next().ok_or_else(|| error!("no encoder for {}", stream))?;
+				let decoder = common::create_audio_decoder(stream, format)?;
+				let chain = transform::build_chain(&pipeline.transform)?;
+				Some(media::Transcoder::new(decoder, encoder).with_transforms(chain))
+			}
+			Source::Copied(_) => None,
+		};
+		let input_id = source.stream().id;
+		let output_id = output_id as u32;
+		let stream = muxer.streams().get(output_id);
+		let time = stream.map_or(source.stream().time, |stream| stream.time);
+		routes.push(Route { input_id, output_id, time, transcoder });
+	}
+
+	while let Some(mut packet) = input.demuxer.read_packet()? {
+		let Some(route) = routes.iter_mut().find(|route| route.input_id == packet.stream_id) else {
+			continue;
+		};
+		let Some(transcoder) = &mut route.transcoder else {
+			packet.stream_id = route.output_id;
+			muxer.write(rescale(packet, route.time))?;
+			continue;
+		};
+		for mut output_packet in transcoder.transcode(packet)? {
+			output_packet.stream_id = route.output_id;
+			muxer.write(output_packet)?;
+		}
+	}
+
+	for route in &mut routes {
+		let Some(transcoder) = &mut route.transcoder else {
+			continue;
+		};
+		for mut packet in transcoder.flush()? {
+			packet.stream_id = route.output_id;
+			muxer.write(packet)?;
+		}
+		if let Some(codec_private) = transcoder.encoder.codec_private() {
+			muxer.update_codec_private(route.output_id, &codec_private)?;
+		}
+	}
+
+	// a piped input only has what follows its streams once they are read
+	if let Some(metadata) = input.demuxer.container_metadata() {
+		muxer.update_container_metadata(metadata)?;
+	}
+	muxer.finalize()
+}
+
+/// Moves the timestamps of a copied packet to the time base of its
+/// output stream.
+fn rescale(mut packet: Packet, time: Time) -> Packet {
+	if packet.time != time {
+		packet.pts = time.scale_pts(packet.pts, packet.time);
+		packet.dts = time.scale_pts(packet.dts, packet.time);
+		packet.duration = time.scale_pts(packet.duration, packet.time);
+		packet.time = time;
+	}
+	packet
+}
@@ -0,0 +1,140 @@
+use super::common::Pipeline;
+use super::containers::Container;
+use super::demuxers::Input;
+use crate::cli::config::Track;
+use crate::codecs;
+use crate::core::stream::Stream;
+use crate::{error, message::Result};
+
+/// What becomes of a stream of the input.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+	/// Packets go to the output as they are, as `codec=copy` asks.
+	Copy,
+	/// Packets are decoded and encoded again.
+	Transcode,
+	Drop,
+}
+
+/// Where a stream of the input goes.
+#[derive(Debug, Clone)]
+pub struct Mapping {
+	pub stream: Stream,
+	pub action: Action,
+	/// Id of the stream in the output, `None` when it is dropped.
+	pub output_id: Option<u32>,
+}
+
+/// Routes every stream of an input. Kept streams are numbered from 0 in
+/// the order of the input.
+#[derive(Debug, Clone)]
+pub struct StreamMap {
+	mappings: Vec<Mapping>,
+}
+
+impl StreamMap {
+	/// Audio is transcoded unless its codec is `copy`; video and
+	/// subtitles, which have no encoders, are copied when the output holds
+	/// their codec. Without a `track`, every stream of a kind is taken, or
+	/// the first audio one when the output holds a single stream.
+	pub fn new(pipeline: &Pipeline, input: &Input, container: &Container) -> Result<Self> {
+		let streams = input.demuxer.streams();
+		let mut actions = vec![Action::Drop; streams.all().len()];
+
+		let audio: Vec<&Stream> = streams.audio().collect();
+		let mut picked = pick(&audio, pipeline.audio.track, "audio")?;
+		if !container.muxer()?.multi_stream {
+			picked.truncate(1);
+		}
+		let copy = pipeline.audio.codec.as_deref() == Some(codecs::COPY);
+		if copy && !picked.is_empty() && !pipeline.transform.chain.is_empty() {
+			return Err(error!("copied audio cannot be transformed"));
+		}
+		for stream in picked {
+			if !copy {
+				actions[stream.index] = Action::Transcode;
+				continue;
+			}
+			container.assert_audio(&stream.codec)?;
+			actions[stream.index] = Action::Copy;
+		}
+
+		let video: Vec<&Stream> = streams.video().collect();
+		let codec = pipeline.video.codec.as_deref();
+		let explicit = pipeline.video.track.is_some() || codec.is_some();
+		for stream in pick(&video, pipeline.video.track, "video")? {
+			let supported = container.assert_video(&stream.codec);
+			match supported.and_then(|()| expect_copy(codec, stream)) {
+				Ok(()) => actions[stream.index] = Action::Copy,
+				Err(err) if explicit => return Err(err),
+				Err(_) => {}
+			}
+		}
+
+		// a language narrows the subtitles a track counts in
+		let language = pipeline.subtitle.language.as_deref();
+		let subtitles: Vec<&Stream> = streams
+			.subtitle()
+			.filter(|stream| language.is_none() || input.details(stream).language.as_deref() == language)
+			.collect();
+		if let (Some(language), true) = (language, subtitles.is_empty()) {
+			return Err(error!("input has no '{}' subtitle", language));
+		}
+		let codec = pipeline.subtitle.codec.as_deref();
+		let explicit = pipeline.subtitle.track.is_some() || language.is_some() || codec.is_some();
+		for stream in pick(&subtitles, pipeline.subtitle.track, "subtitle")? {
+			let supported = container.assert_subtitle(&stream.codec);
+			match supported.and_then(|()| expect_copy(codec, stream)) {
+				Ok(()) => actions[stream.index] = Action::Copy,
+				Err(err) if explicit => return Err(err),
+				Err(_) => {}
+			}
+		}
+
+		let mut mappings = Vec::new();
+		let mut next_id = 0;
+		for (stream, action) in streams.all().iter().zip(actions) {
+			let output_id = (action != Action::Drop).then(|| {
+				next_id += 1;
+				next_id - 1
+			});
+			mappings.push(Mapping { stream: stream.clone(), action, output_id });
+		}
+		if next_id == 0 {
+			return Err(error!("input has no stream '{}' can hold", container.name));
+		}
+		Ok(Self { mappings })
+	}
+
+	pub fn get(&self, input_id: u32) -> Option<&Mapping> {
+		self.mappings.iter().find(|mapping| mapping.stream.id == input_id)
+	}
+
+	/// The streams the output holds, in the order of their ids.
+	pub fn outputs(&self) -> impl Iterator<Item = &Mapping> {
+		self.mappings.iter().filter(|mapping| mapping.output_id.is_some())
+	}
+}
+
+/// The streams of one kind `track` picks, all of them without one.
+fn pick<'a>(streams: &[&'a Stream], track: Option<usize>, kind: &str) -> Result<Vec<&'a Stream>> {
+	let Some(track) = track else {
+		return Ok(streams.to_vec());
+	};
+	if streams.is_empty() {
+		return Err(error!("input has no {} track {}", kind, track));
+	}
+	let indexes = Track::One(track).resolve(streams.len())?;
+	Ok(indexes.into_iter().map(|index| streams[index]).collect())
+}
+
+/// Video and subtitles have no encoders: a `codec` other than `copy` is
+/// only met by streams already in it.
+fn expect_copy(codec: Option<&str>, stream: &Stream) -> Result<()> {
+	match codec {
+		Some(codec) if codec != codecs::COPY && codec != stream.codec => {
+			Err(error!("encoding '{}' is not supported", codec))
+		}
+		_ => Ok(()),
+	}
+}
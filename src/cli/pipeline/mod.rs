@@ -1,7 +1,7 @@
-pub mod aac;
 mod common;
-// pub mod mkv;
-pub mod raw;
-pub mod wav;
-pub mod webm;
+pub mod containers;
+pub mod demuxers;
+pub mod engine;
+pub mod map;
+pub mod muxers;
 pub use common::Pipeline;
@@ -0,0 +1,397 @@
+use super::common::{self, Pipeline};
+use super::demuxers::{Input, Metadata};
+use crate::codecs;
+use crate::codecs::audio::aac::AacEncoder;
+use crate::codecs::audio::aac::encoder::ENCODER_DELAY;
+use crate::codecs::audio::adpcm::AdpcmEncoder;
+use crate::codecs::audio::flac::encoder::DEFAULT_COMPRESSION_LEVEL;
+use crate::codecs::audio::flac::{FlacEncoder, StreamInfo};
+use crate::codecs::audio::g711::G711Encoder;
+use crate::container::{self, aac, aiff, au, flac, mkv, mp4, ogg, raw, voc, wav};
+use crate::core::frame::AudioFormat;
+use crate::core::stream::{Stream, StreamKind, Streams};
+use crate::core::time::Time;
+use crate::core::{Encoder, Muxer};
+use crate::io::{Error, File};
+use crate::{error, message::Result};
+
+/// The muxer of an output, with an encoder for each audio stream it
+/// decodes.
+pub struct Output {
+	pub muxer: Box<dyn Muxer>,
+	pub encoders: Vec<Box<dyn Encoder>>,
+}
+
+impl Output {
+	fn new(muxer: Box<dyn Muxer>, encoders: Vec<Box<dyn Encoder>>) -> Self {
+		Self { muxer, encoders }
+	}
+}
+
+/// A stream of the input the output is asked to hold.
+#[derive(Debug, Clone)]
+pub enum Source {
+	/// Audio decoded as this layout, for the output to encode.
+	Decoded(Stream, wav::WavFormat),
+	/// A stream written as it is, with the codec private data of the input.
+	Copied(Stream),
+}
+
+impl Source {
+	pub fn stream(&self) -> &Stream {
+		match self {
+			Source::Decoded(stream, _) | Source::Copied(stream) => stream,
+		}
+	}
+}
+
+/// Writes the outputs of a container.
+pub struct MuxerFactory {
+	/// Whether the container holds more than one stream.
+	pub multi_stream: bool,
+	/// Builds the output for `sources`, in the order of their stream ids.
+	pub create: fn(&Pipeline, &Input, &[Source]) -> Result<Output>,
+}
+
+pub fn create_wav(pipeline: &Pipeline, input: &Input, sources: &[Source]) -> Result<Output> {
+	let mut format = single(input, sources, container::WAV)?;
+	if let Some(codec) = output_codec(pipeline, sources)
+		&& codec != format.to_codec_string()
+	{
+		format.apply_codec(codec).map_err(Error::invalid_data)?;
+	}
+
+	let encoders = encoders(sources, || match (format.adpcm(), format.g711()) {
+		(Some(_), _) => Ok(Box::new(AdpcmEncoder::new_from_metadata(&format)?)),
+		(None, Some(law)) => Ok(Box::new(G711Encoder::new(law, format.sample_rate))),
+		(None, None) => Ok(common::create_pcm_encoder(format.sample_rate, format.audio_format())),
+	})?;
+
+	let mut muxer = wav::WavMuxer::new(File::create(&pipeline.output)?, format)?;
+	if let Metadata::Wav(metadata) = &input.metadata {
+		muxer.with_metadata(Some(metadata.as_ref().clone()));
+	}
+	Ok(Output::new(Box::new(muxer), encoders))
+}
+
+pub fn create_aiff(pipeline: &Pipeline, input: &Input, sources: &[Source]) -> Result<Output> {
+	let layout = single(input, sources, container::AIFF)?;
+	let (mut format, metadata) = match &input.metadata {
+		Metadata::Aiff(format, metadata) => (*format, Some(metadata.clone())),
+		_ => {
+			let format = aiff::AiffFormat::from_audio_format(
+				layout.audio_format(),
+				layout.channels,
+				layout.sample_rate,
+			);
+			(format, None)
+		}
+	};
+	if let Some(codec) = output_codec(pipeline, sources)
+		&& codec != format.to_codec_string()
+	{
+		format.apply_codec(codec).map_err(Error::invalid_data)?;
+	}
+
+	let encoders =
+		encoders(sources, || Ok(common::create_pcm_encoder(format.sample_rate, format.format)))?;
+	let mut muxer = aiff::AiffMuxer::new(File::create(&pipeline.output)?, format)?;
+	muxer.with_metadata(metadata);
+	Ok(Output::new(Box::new(muxer), encoders))
+}
+
+pub fn create_au(pipeline: &Pipeline, input: &Input, sources: &[Source]) -> Result<Output> {
+	let layout = single(input, sources, container::AU)?;
+	let (mut format, annotation) = match &input.metadata {
+		Metadata::Au(format, annotation) => (*format, annotation.as_str()),
+		_ => {
+			let format =
+				au::AuFormat::from_audio_format(layout.audio_format(), layout.channels, layout.sample_rate);
+			(format, "")
+		}
+	};
+	if let Some(codec) = output_codec(pipeline, sources)
+		&& codec != format.to_codec_string()
+	{
+		format.apply_codec(codec).map_err(Error::invalid_data)?;
+	}
+
+	let encoders =
+		encoders(sources, || Ok(common::create_pcm_encoder(format.sample_rate, format.format)))?;
+	let mut muxer = au::AuMuxer::new(File::create(&pipeline.output)?, format)?;
+	muxer.with_annotation(annotation);
+	Ok(Output::new(Box::new(muxer), encoders))
+}
+
+pub fn create_voc(pipeline: &Pipeline, input: &Input, sources: &[Source]) -> Result<Output> {
+	let layout = single(input, sources, container::VOC)?;
+	let mut format = match &input.metadata {
+		Metadata::Voc(format) => *format,
+		_ => {
+			voc::VocFormat::from_audio_format(layout.audio_format(), layout.channels, layout.sample_rate)
+		}
+	};
+	if let Some(codec) = output_codec(pipeline, sources)
+		&& codec != format.to_codec_string()
+	{
+		format.apply_codec(codec).map_err(Error::invalid_data)?;
+	}
+
+	let encoders =
+		encoders(sources, || Ok(common::create_pcm_encoder(format.sample_rate, format.format)))?;
+	let muxer = voc::VocMuxer::new(File::create(&pipeline.output)?, format)?;
+	Ok(Output::new(Box::new(muxer), encoders))
+}
+
+pub fn create_raw(pipeline: &Pipeline, input: &Input, sources: &[Source]) -> Result<Output> {
+	let mut format = single(input, sources, container::RAW)?.to_raw_format();
+	if let Some(codec) = output_codec(pipeline, sources)
+		&& codec != format.to_codec_string()
+	{
+		format.apply_codec(codec).map_err(Error::invalid_data)?;
+	}
+
+	let encoders = encoders(sources, || {
+		Ok(common::create_pcm_encoder(format.sample_rate, format.audio_format()))
+	})?;
+	let muxer = raw::RawPcmMuxer::new(File::create(&pipeline.output)?, format)?;
+	Ok(Output::new(Box::new(muxer), encoders))
+}
+
+pub fn create_flac(pipeline: &Pipeline, input: &Input, sources: &[Source]) -> Result<Output> {
+	let format = single(input, sources, container::FLAC)?;
+	let (info, encoders): (_, Vec<Box<dyn Encoder>>) = match sources {
+		// copied frames keep the STREAMINFO of the input
+		[Source::Copied(stream)] => (StreamInfo::parse(&stream.codec_private)?, Vec::new()),
+		_ => {
+			let encoder = flac_encoder(pipeline, &format)?;
+			(encoder.stream_info(), vec![Box::new(encoder)])
+		}
+	};
+
+	let mut muxer = flac::FlacMuxer::new(File::create(&pipeline.output)?, info)?;
+	if let Metadata::Flac(metadata) = &input.metadata {
+		muxer.with_metadata(Some(metadata.clone()));
+	}
+	Ok(Output::new(Box::new(muxer), encoders))
+}
+
+fn flac_encoder(pipeline: &Pipeline, format: &wav::WavFormat) -> Result<FlacEncoder> {
+	expect_codec(pipeline, codecs::audio::FLAC)?;
+
+	let level = match &pipeline.audio.compression_level {
+		Some(level) => {
+			level.parse::<u8>().map_err(|_| error!("invalid compression level: {}", level))?
+		}
+		None => DEFAULT_COMPRESSION_LEVEL,
+	};
+
+	// float and 32 bit input has no flac depth, keep 24 bits of it
+	let bits_per_sample = match format.audio_format() {
+		AudioFormat::PCM8 => 8,
+		AudioFormat::PCM16 => 16,
+		_ => 24,
+	};
+	FlacEncoder::new(format.sample_rate, format.channels, bits_per_sample)?
+		.with_compression_level(level)
+}
+
+pub fn create_adts(pipeline: &Pipeline, input: &Input, sources: &[Source]) -> Result<Output> {
+	single(input, sources, container::AAC)?;
+	let (streams, encoders) = aac_streams(pipeline, sources, true)?;
+	let muxer = aac::AdtsMuxer::new(File::create(&pipeline.output)?, streams)?;
+	Ok(Output::new(Box::new(muxer), encoders))
+}
+
+pub fn create_m4a(pipeline: &Pipeline, input: &Input, sources: &[Source]) -> Result<Output> {
+	let (streams, encoders) = aac_streams(pipeline, sources, false)?;
+	let mut muxer = mp4::Mp4Muxer::new_faststart(File::create(&pipeline.output)?, streams)?;
+	muxer.with_major_brand("M4A ")?;
+	describe_mp4_tracks(&mut muxer, input, sources);
+	Ok(Output::new(Box::new(muxer), encoders))
+}
+
+pub fn create_mp4(pipeline: &Pipeline, input: &Input, sources: &[Source]) -> Result<Output> {
+	let (streams, encoders) = aac_streams(pipeline, sources, false)?;
+	let mut muxer = mp4::Mp4Muxer::new_faststart(File::create(&pipeline.output)?, streams)?;
+	describe_mp4_tracks(&mut muxer, input, sources);
+	Ok(Output::new(Box::new(muxer), encoders))
+}
+
+pub fn create_mkv(pipeline: &Pipeline, input: &Input, sources: &[Source]) -> Result<Output> {
+	let (streams, encoders) = aac_streams(pipeline, sources, false)?;
+	let mut muxer = mkv::MkvMuxer::new(File::create(&pipeline.output)?, streams)?;
+	describe_mkv_tracks(&mut muxer, input, sources);
+	Ok(Output::new(Box::new(muxer), encoders))
+}
+
+pub fn create_webm(pipeline: &Pipeline, input: &Input, sources: &[Source]) -> Result<Output> {
+	let (streams, encoders) = aac_streams(pipeline, sources, false)?;
+	// refused codecs must not leave an empty output behind
+	mkv::muxer::check_doc_type("webm", &streams)?;
+	let mut muxer = mkv::MkvMuxer::new(File::create(&pipeline.output)?, streams)?;
+	muxer.with_doc_type("webm")?;
+	describe_mkv_tracks(&mut muxer, input, sources);
+	Ok(Output::new(Box::new(muxer), encoders))
+}
+
+// there is no encoder for the codecs ogg holds, its streams are copied
+pub fn create_ogg(pipeline: &Pipeline, _input: &Input, sources: &[Source]) -> Result<Output> {
+	let mut streams = Streams::new_empty();
+	for (id, source) in sources.iter().enumerate() {
+		let Source::Copied(stream) = source else {
+			return Err(error!("ogg output takes copied streams only, use codec=copy"));
+		};
+		streams.add(renumbered(stream, id as u32));
+	}
+	let muxer = ogg::OggMuxer::new(File::create(&pipeline.output)?, streams)?;
+	Ok(Output::new(Box::new(muxer), Vec::new()))
+}
+
+/// An aac encoder for each decoded source, and the streams of all
+/// `sources`.
+fn aac_streams(
+	pipeline: &Pipeline,
+	sources: &[Source],
+	adts: bool,
+) -> Result<(Streams, Vec<Box<dyn Encoder>>)> {
+	expect_codec(pipeline, codecs::audio::AAC)?;
+
+	let mut streams = Streams::new_empty();
+	let mut encoders: Vec<Box<dyn Encoder>> = Vec::new();
+	for (id, source) in sources.iter().enumerate() {
+		let id = id as u32;
+		let format = match source {
+			Source::Decoded(_, format) => format,
+			Source::Copied(stream) => {
+				streams.add(renumbered(stream, id));
+				continue;
+			}
+		};
+		let encoder = AacEncoder::new(format.sample_rate, format.channels)?.with_adts(adts);
+		let encoder = match (&pipeline.audio.bitrate, &pipeline.audio.quality) {
+			(Some(_), Some(_)) => return Err(error!("aac takes either a bitrate or a quality")),
+			(Some(bitrate), None) => encoder.with_bitrate(parse_bitrate(bitrate)?)?,
+			(None, Some(quality)) => {
+				let quality = quality.parse::<u8>().map_err(|_| error!("invalid quality: {}", quality))?;
+				encoder.with_quality(quality)?
+			}
+			(None, None) => encoder,
+		};
+
+		let time = Time::new(1, format.sample_rate);
+		let stream =
+			Stream::new(id, id as usize, StreamKind::Audio, codecs::audio::AAC.to_string(), time)
+				.with_codec_private(encoder.config().to_bytes());
+		streams.add(stream);
+		encoders.push(Box::new(encoder));
+	}
+	Ok((streams, encoders))
+}
+
+/// Fills in the track details mp4 streams leave out: the language of
+/// every stream, and the picture size of copied video.
+fn describe_mp4_tracks(muxer: &mut mp4::Mp4Muxer<File>, input: &Input, sources: &[Source]) {
+	for (index, source) in sources.iter().enumerate() {
+		let Some(track) = muxer.track_mut(index) else {
+			continue;
+		};
+		let details = input.details(source.stream());
+		track.language = details.language;
+		if let Source::Copied(_) = source {
+			(track.width, track.height) = (details.width as u16, details.height as u16);
+		}
+	}
+}
+
+/// Fills in the track details matroska streams leave out: the language of
+/// every stream, the layout of audio, whose time base may not be its
+/// sample rate, the picture size of video and the delay the decoder
+/// drops, from the aac encoder or the input.
+fn describe_mkv_tracks(muxer: &mut mkv::MkvMuxer<File>, input: &Input, sources: &[Source]) {
+	for (index, source) in sources.iter().enumerate() {
+		let Some(track) = muxer.track_mut(index) else {
+			continue;
+		};
+		let stream = source.stream();
+		let details = input.details(stream);
+		track.language = details.language;
+		if let Ok(format) = input.format(stream) {
+			track.sample_rate = format.sample_rate as f64;
+			track.channels = format.channels.count();
+		}
+		if stream.video_kind() {
+			(track.width, track.height) = (details.width, details.height);
+		}
+		let nanoseconds = Time::new(1, 1_000_000_000);
+		let delay = match source {
+			Source::Decoded(_, format) => {
+				nanoseconds.round_pts(ENCODER_DELAY as i64, Time::new(1, format.sample_rate))
+			}
+			Source::Copied(stream) => nanoseconds.round_pts(details.delay, stream.time),
+		};
+		track.codec_delay = delay.max(0) as u64;
+	}
+}
+
+/// A copy of `stream` that takes `id` in the output.
+fn renumbered(stream: &Stream, id: u32) -> Stream {
+	Stream { id, index: id as usize, ..stream.clone() }
+}
+
+/// Parses a bitrate in bits per second, with an optional `k` suffix for
+/// kilobits.
+fn parse_bitrate(value: &str) -> Result<u32> {
+	let (digits, scale) = match value.strip_suffix(['k', 'K']) {
+		Some(digits) => (digits, 1000),
+		None => (value, 1),
+	};
+	let bitrate = digits.parse::<u32>().map_err(|_| error!("invalid bitrate: {}", value))?;
+	bitrate.checked_mul(scale).ok_or_else(|| error!("invalid bitrate: {}", value))
+}
+
+/// Containers with a single codec only take that one as `--audio codec`,
+/// besides `copy`.
+fn expect_codec(pipeline: &Pipeline, codec: &str) -> Result<()> {
+	match pipeline.audio.codec.as_deref() {
+		Some(other) if other != codec && other != codecs::COPY => {
+			Err(error!("encoding '{}' is not supported", other))
+		}
+		_ => Ok(()),
+	}
+}
+
+/// The codec the single stream of `sources` is written with: its own when
+/// it is copied, the one asked for otherwise.
+fn output_codec<'a>(pipeline: &'a Pipeline, sources: &'a [Source]) -> Option<&'a str> {
+	match sources {
+		[Source::Copied(stream)] => Some(&stream.codec),
+		_ => pipeline.audio.codec.as_deref(),
+	}
+}
+
+/// The encoder `create` builds for the single stream of `sources`, none
+/// when it is copied.
+fn encoders(
+	sources: &[Source],
+	create: impl FnOnce() -> Result<Box<dyn Encoder>>,
+) -> Result<Vec<Box<dyn Encoder>>> {
+	match sources {
+		[Source::Copied(_)] => Ok(Vec::new()),
+		_ => Ok(vec![create()?]),
+	}
+}
+
+/// The audio stream of containers that hold a single one, and the layout
+/// it is decoded with or copied in.
+fn single(input: &Input, sources: &[Source], container_name: &str) -> Result<wav::WavFormat> {
+	match sources {
+		[Source::Decoded(_, format)] => Ok(*format),
+		[Source::Copied(stream)] if stream.audio_kind() => input.format(stream),
+		[Source::Copied(stream)] => {
+			Err(error!("'{}' cannot hold a copy of '{}'", container_name, stream.codec))
+		}
+		[] => Err(error!("input has no audio stream")),
+		_ => Err(error!("'{}' holds a single audio stream", container_name)),
+	}
+}
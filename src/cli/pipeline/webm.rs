@@ -1,6 +0,0 @@
-use super::common::Pipeline;
-use crate::{error, message::Result};
-
-pub fn run(_pipeline: Pipeline) -> Result<()> {
-	Err(error!("webm pipeline not implemented"))
-}
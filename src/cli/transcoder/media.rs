@@ -1,22 +1,30 @@
+use crate::core::frame::Frame;
 use crate::core::packet::Packet;
 use crate::core::{Decoder, Encoder};
 use crate::message::Result;
+use crate::transform::TransformChain;
 
 pub struct Transcoder {
 	pub decoder: Box<dyn Decoder>,
 	pub encoder: Box<dyn Encoder>,
+	pub transforms: TransformChain,
 }
 
 impl Transcoder {
 	pub fn new(decoder: Box<dyn Decoder>, encoder: Box<dyn Encoder>) -> Self {
-		Self { decoder, encoder }
+		Self { decoder, encoder, transforms: TransformChain::new() }
+	}
+
+	pub fn with_transforms(mut self, transforms: TransformChain) -> Self {
+		self.transforms = transforms;
+		self
 	}
 
 	pub fn transcode(&mut self, packet: Packet) -> Result<Vec<Packet>> {
 		let mut packets = Vec::new();
 		if let Some(frame) = self.decoder.decode(packet)? {
-			if let Some(encoded_packet) = self.encoder.encode(frame)? {
-				packets.push(encoded_packet);
+			for frame in self.transforms.process(frame)? {
+				self.encode(frame, &mut packets)?;
 			}
 		}
 		Ok(packets)
@@ -26,15 +34,29 @@ impl Transcoder {
 		let mut packets = Vec::new();
 
 		while let Some(frame) = self.decoder.flush()? {
-			if let Some(encoded_packet) = self.encoder.encode(frame)? {
-				packets.push(encoded_packet);
+			for frame in self.transforms.process(frame)? {
+				self.encode(frame, &mut packets)?;
 			}
 		}
 
+		for frame in self.transforms.flush()? {
+			self.encode(frame, &mut packets)?;
+		}
+
 		while let Some(packet) = self.encoder.flush()? {
 			packets.push(packet);
 		}
 
 		Ok(packets)
 	}
+
+	fn encode(&mut self, frame: Frame, packets: &mut Vec<Packet>) -> Result<()> {
+		if let Some(encoded_packet) = self.encoder.encode(frame)? {
+			packets.push(encoded_packet);
+		}
+		while let Some(encoded_packet) = self.encoder.receive()? {
+			packets.push(encoded_packet);
+		}
+		Ok(())
+	}
 }
@@ -0,0 +1,70 @@
+use crate::cli::config::TransformConfig;
+use crate::cli::config::transform::TransformSpec;
+use crate::message::Result;
+use crate::transform::{Normalize, NormalizeMode, TransformChain, Volume};
+use crate::{core::Transform, error};
+
+pub fn build_chain(config: &TransformConfig) -> Result<TransformChain> {
+	let mut chain = TransformChain::new();
+	for spec in &config.chain {
+		chain.push(build_transform(spec)?);
+	}
+	Ok(chain)
+}
+
+fn build_transform(spec: &TransformSpec) -> Result<Box<dyn Transform>> {
+	match spec.name.as_str() {
+		"gain" | "volume" => Ok(Box::new(Volume::new(parse_gain(&spec.value)?))),
+		"normalize" => build_normalize(&spec.value),
+		"trim" | "fade" | "reverse" | "speed" | "rotate" => {
+			Err(error!("transform '{}' is not implemented yet", spec.name))
+		}
+		name => Err(error!("unknown transform '{}'", name)),
+	}
+}
+
+// accepts a linear factor (2.0) or decibels (-6db)
+fn parse_gain(value: &str) -> Result<f32> {
+	let lower = value.trim().to_lowercase();
+	if let Some(db) = lower.strip_suffix("db") {
+		let db = db.trim().parse::<f32>().map_err(|_| error!("invalid gain: {}", value))?;
+		return Ok(10f32.powf(db / 20.0));
+	}
+
+	let factor = lower.parse::<f32>().map_err(|_| error!("invalid gain: {}", value))?;
+	if !factor.is_finite() || factor < 0.0 {
+		return Err(error!("gain must be a non-negative number, found {}", value));
+	}
+	Ok(factor)
+}
+
+// normalize, normalize=0.9, normalize=rms, normalize=rms:0.2, normalize=peak:0.95
+fn build_normalize(value: &str) -> Result<Box<dyn Transform>> {
+	let (mode, target) = match value.split_once(':') {
+		Some((mode, target)) => (mode, Some(target)),
+		None => (value, None),
+	};
+
+	let (mode, target) = match mode {
+		"true" | "peak" => (NormalizeMode::Peak, target),
+		"rms" => (NormalizeMode::Rms, target),
+		level if target.is_none() => (NormalizeMode::Peak, Some(level)),
+		other => return Err(error!("invalid normalize mode '{}' (expected peak or rms)", other)),
+	};
+
+	let target = match target {
+		Some(level) => parse_level(level)?,
+		None if mode == NormalizeMode::Rms => Normalize::DEFAULT_RMS,
+		None => Normalize::DEFAULT_PEAK,
+	};
+
+	Ok(Box::new(Normalize::with_mode(mode, target)))
+}
+
+fn parse_level(value: &str) -> Result<f32> {
+	let level = value.parse::<f32>().map_err(|_| error!("invalid normalize level: {}", value))?;
+	if !(level > 0.0 && level <= 1.0) {
+		return Err(error!("normalize level must be in (0, 1], found {}", value));
+	}
+	Ok(level)
+}
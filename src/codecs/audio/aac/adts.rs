@@ -0,0 +1,122 @@
+use super::config::{AudioSpecificConfig, SAMPLE_RATES};
+use crate::io::{BitReader, BitWriter};
+use crate::{error, message::Result};
+
+pub const HEADER_SIZE: usize = 7;
+// the header grows by a CRC when protection is on
+const CRC_SIZE: usize = 2;
+// buffer fullness of variable bitrate streams
+pub const VARIABLE_FULLNESS: u32 = 0x7FF;
+// the frame length field has 13 bits
+const MAX_FRAME_SIZE: usize = (1 << 13) - 1;
+
+/// Header of an ADTS frame, the self-contained framing of raw AAC streams.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AdtsHeader {
+	pub object_type: u8,
+	pub sample_rate_index: u8,
+	pub sample_rate: u32,
+	pub channel_config: u8,
+	/// Size of the whole frame, header included.
+	pub frame_size: usize,
+	pub header_size: usize,
+	pub raw_blocks: usize,
+}
+
+impl AdtsHeader {
+	pub fn parse(data: &[u8]) -> Result<Self> {
+		if data.len() < HEADER_SIZE {
+			return Err(error!("adts header is truncated"));
+		}
+		let mut reader = BitReader::new(data);
+		if reader.read(12)? != 0xFFF {
+			return Err(error!("adts frame has no sync word"));
+		}
+		reader.read(1)?;
+		if reader.read(2)? != 0 {
+			return Err(error!("adts layer is not zero"));
+		}
+		let protection_absent = reader.read_bit()?;
+		let object_type = reader.read(2)? as u8 + 1;
+		let sample_rate_index = reader.read(4)? as u8;
+		let sample_rate = *SAMPLE_RATES
+			.get(sample_rate_index as usize)
+			.ok_or_else(|| error!("adts sample rate index {} is invalid", sample_rate_index))?;
+		reader.read(1)?;
+		let channel_config = reader.read(3)? as u8;
+		reader.read(4)?;
+		let frame_size = reader.read(13)? as usize;
+		reader.read(11)?;
+		let raw_blocks = reader.read(2)? as usize + 1;
+
+		let header_size = if protection_absent { HEADER_SIZE } else { HEADER_SIZE + CRC_SIZE };
+		if frame_size < header_size {
+			return Err(error!("adts frame is smaller than its header"));
+		}
+		Ok(Self {
+			object_type,
+			sample_rate_index,
+			sample_rate,
+			channel_config,
+			frame_size,
+			header_size,
+			raw_blocks,
+		})
+	}
+
+	/// The header of a frame of `config` carrying one raw data block of
+	/// `payload` bytes, without CRC.
+	pub fn new(config: &AudioSpecificConfig, payload: usize) -> Result<Self> {
+		if !(1..=4).contains(&config.object_type) {
+			return Err(error!("aac object type {} cannot be framed as adts", config.object_type));
+		}
+		let frame_size = payload + HEADER_SIZE;
+		if frame_size > MAX_FRAME_SIZE {
+			return Err(error!("adts frame of {} bytes is too large", frame_size));
+		}
+		Ok(Self {
+			object_type: config.object_type,
+			sample_rate_index: config.sample_rate_index(),
+			sample_rate: config.sample_rate,
+			channel_config: config.channel_config,
+			frame_size,
+			header_size: HEADER_SIZE,
+			raw_blocks: 1,
+		})
+	}
+
+	/// The header as written; `fullness` is the state of the bit reservoir
+	/// in 32 bit words per channel, or [`VARIABLE_FULLNESS`].
+	pub fn to_bytes(&self, fullness: u32) -> Vec<u8> {
+		let mut writer = BitWriter::with_capacity(HEADER_SIZE);
+		writer.write(0xFFF, 12);
+		// MPEG-4, layer 0, no CRC
+		writer.write(0b0001, 4);
+		writer.write(self.object_type as u32 - 1, 2);
+		writer.write(self.sample_rate_index as u32, 4);
+		writer.write(0, 1);
+		writer.write(self.channel_config as u32, 3);
+		writer.write(0, 4);
+		writer.write(self.frame_size as u32, 13);
+		writer.write(fullness.min(VARIABLE_FULLNESS), 11);
+		writer.write(self.raw_blocks as u32 - 1, 2);
+		writer.finish()
+	}
+
+	/// Whether `data` is exactly one ADTS frame, header included.
+	pub fn is_frame(data: &[u8]) -> bool {
+		Self::parse(data).is_ok_and(|header| header.frame_size == data.len())
+	}
+
+	/// The decoder setup the header stands for.
+	pub fn config(&self) -> AudioSpecificConfig {
+		AudioSpecificConfig::new(self.object_type, self.sample_rate, self.channel_config)
+	}
+
+	/// Whether another frame belongs to the same stream.
+	pub fn is_compatible(&self, other: &Self) -> bool {
+		self.object_type == other.object_type
+			&& self.sample_rate_index == other.sample_rate_index
+			&& self.channel_config == other.channel_config
+	}
+}
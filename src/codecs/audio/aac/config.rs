@@ -0,0 +1,114 @@
+use crate::io::{BitReader, BitWriter};
+use crate::{error, message::Result};
+
+pub const SAMPLE_RATES: [u32; 13] =
+	[96000, 88200, 64000, 48000, 44100, 32000, 24000, 22050, 16000, 12000, 11025, 8000, 7350];
+
+// audio object types
+pub const OBJECT_TYPE_MAIN: u8 = 1;
+pub const OBJECT_TYPE_LC: u8 = 2;
+pub const OBJECT_TYPE_SBR: u8 = 5;
+pub const OBJECT_TYPE_PS: u8 = 29;
+
+/// The index of a sample rate in [`SAMPLE_RATES`], if it has one.
+pub fn sample_rate_index(sample_rate: u32) -> Option<u8> {
+	SAMPLE_RATES.iter().position(|&rate| rate == sample_rate).map(|index| index as u8)
+}
+
+/// AudioSpecificConfig, the decoder setup MP4 and Matroska carry as codec
+/// private data.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AudioSpecificConfig {
+	/// Object type of the core coder, behind any SBR or PS signalling.
+	pub object_type: u8,
+	/// Sample rate of the core coder.
+	pub sample_rate: u32,
+	/// Channel configuration; 0 leaves the layout to a program config
+	/// element.
+	pub channel_config: u8,
+	/// Samples per channel in a frame, 1024 or 960.
+	pub frame_length: usize,
+}
+
+impl AudioSpecificConfig {
+	pub fn new(object_type: u8, sample_rate: u32, channel_config: u8) -> Self {
+		Self { object_type, sample_rate, channel_config, frame_length: 1024 }
+	}
+
+	pub fn parse(data: &[u8]) -> Result<Self> {
+		let mut reader = BitReader::new(data);
+		let mut object_type = read_object_type(&mut reader)?;
+		let sample_rate = read_sample_rate(&mut reader)?;
+		let channel_config = reader.read(4)? as u8;
+		if object_type == OBJECT_TYPE_SBR || object_type == OBJECT_TYPE_PS {
+			// the extension rate is that of the SBR output, the core runs at half
+			read_sample_rate(&mut reader)?;
+			object_type = read_object_type(&mut reader)?;
+		}
+
+		let mut frame_length = 1024;
+		if matches!(object_type, 1..=4 | 6 | 7 | 17 | 19..=23) {
+			if reader.read_bit()? {
+				frame_length = 960;
+			}
+			if reader.read_bit()? {
+				reader.read(14)?;
+			}
+		}
+		if sample_rate == 0 {
+			return Err(error!("aac sample rate is invalid"));
+		}
+		Ok(Self { object_type, sample_rate, channel_config, frame_length })
+	}
+
+	/// Output channels of the channel configuration.
+	pub fn channels(&self) -> u8 {
+		match self.channel_config {
+			7 => 8,
+			config => config,
+		}
+	}
+
+	/// Index of the sample rate, or of the nearest one for rates without
+	/// their own.
+	pub fn sample_rate_index(&self) -> u8 {
+		let nearest =
+			SAMPLE_RATES.iter().enumerate().min_by_key(|(_, rate)| rate.abs_diff(self.sample_rate));
+		nearest.map_or(0, |(index, _)| index as u8)
+	}
+
+	/// The two byte form (five with an explicit sample rate), without
+	/// extensions.
+	pub fn to_bytes(&self) -> Vec<u8> {
+		let mut writer = BitWriter::new();
+		writer.write(self.object_type as u32, 5);
+		match sample_rate_index(self.sample_rate) {
+			Some(index) => writer.write(index as u32, 4),
+			None => {
+				writer.write(15, 4);
+				writer.write(self.sample_rate, 24);
+			}
+		}
+		writer.write(self.channel_config as u32, 4);
+		writer.write((self.frame_length == 960) as u32, 1);
+		writer.write(0, 2);
+		writer.finish()
+	}
+}
+
+fn read_object_type(reader: &mut BitReader) -> Result<u8> {
+	match reader.read(5)? {
+		31 => Ok(32 + reader.read(6)? as u8),
+		object_type => Ok(object_type as u8),
+	}
+}
+
+fn read_sample_rate(reader: &mut BitReader) -> Result<u32> {
+	match reader.read(4)? {
+		15 => reader.read(24),
+		index => SAMPLE_RATES
+			.get(index as usize)
+			.copied()
+			.ok_or_else(|| error!("aac sample rate index {} is invalid", index)),
+	}
+}
@@ -0,0 +1,333 @@
+use super::adts::AdtsHeader;
+use super::config::{AudioSpecificConfig, OBJECT_TYPE_LC};
+use super::filterbank::{FRAME_LENGTH, Filterbank};
+use super::huffman::{Codebooks, INTENSITY_HCB, INTENSITY_HCB2, NOISE_HCB};
+use super::ics::{Ics, IcsInfo, MAX_BANDS};
+use super::tables::Bands;
+use crate::core::frame::{AudioFormat, Channels, Frame, FrameAudio};
+use crate::core::packet::Packet;
+use crate::core::time::Time;
+use crate::core::traits::Decoder;
+use crate::io::BitReader;
+use crate::{error, message::Result};
+
+// syntactic elements of a raw data block
+const ID_SCE: u32 = 0;
+const ID_CPE: u32 = 1;
+const ID_CCE: u32 = 2;
+const ID_LFE: u32 = 3;
+const ID_DSE: u32 = 4;
+const ID_PCE: u32 = 5;
+const ID_FIL: u32 = 6;
+const ID_END: u32 = 7;
+
+// bitstream channel order to WAV order, by channel configuration
+pub const CHANNEL_ORDERS: [&[usize]; 8] = [
+	&[],
+	&[0],
+	&[0, 1],
+	&[1, 2, 0],
+	&[1, 2, 0, 3],
+	&[1, 2, 0, 3, 4],
+	&[1, 2, 0, 5, 3, 4],
+	&[1, 2, 0, 7, 5, 6, 3, 4],
+];
+
+struct ChannelState {
+	ics: Ics,
+	overlap: Vec<f32>,
+	previous_shape: usize,
+	output: Vec<f32>,
+}
+
+impl ChannelState {
+	fn new() -> Self {
+		Self {
+			ics: Ics::new(),
+			overlap: vec![0.0; FRAME_LENGTH],
+			previous_shape: 0,
+			output: vec![0.0; FRAME_LENGTH],
+		}
+	}
+}
+
+/// AAC LC decoder producing PCM16 in WAV channel order. The configuration
+/// comes from an AudioSpecificConfig, or from the ADTS header of every
+/// packet when packets carry one. Samples timed before zero and past the
+/// duration of a packet are left out. Coupling channels, the Main and SSR
+/// profiles and 960 sample frames are not supported.
+pub struct AacDecoder {
+	config: Option<AudioSpecificConfig>,
+	bands: Bands,
+	codebooks: Codebooks,
+	filterbank: Filterbank,
+	channels: Vec<ChannelState>,
+	random: u32,
+}
+
+impl AacDecoder {
+	/// Creates a decoder for ADTS packets, which configure it themselves.
+	pub fn new() -> Self {
+		Self {
+			config: None,
+			bands: Bands::new(0),
+			codebooks: Codebooks::new(),
+			filterbank: Filterbank::new(),
+			channels: Vec::new(),
+			random: 1,
+		}
+	}
+
+	/// Creates a decoder from the AudioSpecificConfig of an MP4 or Matroska
+	/// stream.
+	pub fn new_from_codec_private(codec_private: &[u8]) -> Result<Self> {
+		let mut decoder = Self::new();
+		decoder.configure(AudioSpecificConfig::parse(codec_private)?)?;
+		Ok(decoder)
+	}
+
+	fn configure(&mut self, config: AudioSpecificConfig) -> Result<()> {
+		if config.object_type != OBJECT_TYPE_LC {
+			return Err(error!("aac object type {} is not supported", config.object_type));
+		}
+		if config.frame_length != FRAME_LENGTH {
+			return Err(error!("aac frames of {} samples are not supported", config.frame_length));
+		}
+		self.bands = Bands::new(config.sample_rate_index());
+		self.channels.clear();
+		self.config = Some(config);
+		Ok(())
+	}
+
+	/// Decodes one raw data block into the output buffers of its channels
+	/// and returns how many channels it held.
+	fn decode_block(&mut self, data: &[u8]) -> Result<usize> {
+		let mut reader = BitReader::new(data);
+		let mut count = 0;
+		loop {
+			match reader.read(3)? {
+				ID_SCE | ID_LFE => {
+					reader.read(4)?;
+					self.ensure_channels(count + 1);
+					let channel = &mut self.channels[count];
+					channel.ics.parse(&mut reader, &self.codebooks, &self.bands, None)?;
+					self.finish_channel(count);
+					count += 1;
+				}
+				ID_CPE => {
+					reader.read(4)?;
+					self.ensure_channels(count + 2);
+					self.decode_pair(&mut reader, count)?;
+					self.finish_channel(count);
+					self.finish_channel(count + 1);
+					count += 2;
+				}
+				ID_CCE => return Err(error!("aac coupling channels are not supported")),
+				ID_DSE => {
+					reader.read(4)?;
+					let align = reader.read_bit()?;
+					let mut size = reader.read(8)? as usize;
+					if size == 255 {
+						size += reader.read(8)? as usize;
+					}
+					if align {
+						reader.align();
+					}
+					reader.skip(size * 8)?;
+				}
+				ID_PCE => skip_program_config(&mut reader)?,
+				ID_FIL => {
+					let mut size = reader.read(4)? as usize;
+					if size == 15 {
+						size += reader.read(8)? as usize - 1;
+					}
+					reader.skip(size * 8)?;
+				}
+				ID_END => return Ok(count),
+				_ => unreachable!(),
+			}
+		}
+	}
+
+	fn ensure_channels(&mut self, count: usize) {
+		while self.channels.len() < count {
+			self.channels.push(ChannelState::new());
+		}
+	}
+
+	fn decode_pair(&mut self, reader: &mut BitReader, index: usize) -> Result<()> {
+		let mut common = None;
+		let mut ms_used = [[false; MAX_BANDS]; 8];
+		let mut ms_mask = 0;
+		if reader.read_bit()? {
+			let info = IcsInfo::parse(reader, &self.bands)?;
+			ms_mask = reader.read(2)?;
+			for group in ms_used[..info.groups].iter_mut() {
+				for used in group[..info.max_sfb].iter_mut() {
+					*used = match ms_mask {
+						1 => reader.read_bit()?,
+						_ => ms_mask == 2,
+					};
+				}
+			}
+			common = Some(info);
+		}
+
+		let [left, right] = &mut self.channels[index..index + 2] else { unreachable!() };
+		left.ics.parse(reader, &self.codebooks, &self.bands, common)?;
+		right.ics.parse(reader, &self.codebooks, &self.bands, common)?;
+		if common.is_none() {
+			return Ok(());
+		}
+
+		let (left, right) = (&mut left.ics, &mut right.ics);
+		for (group, band, range) in left.info.coded_bands(&self.bands) {
+			let (left_type, right_type) = (left.band_types[group][band], right.band_types[group][band]);
+			let used = ms_used[group][band];
+			if right_type == INTENSITY_HCB || right_type == INTENSITY_HCB2 {
+				let mut scale = 0.5f32.powf(0.25 * right.scalefactors[group][band] as f32);
+				if right_type == INTENSITY_HCB2 {
+					scale = -scale;
+				}
+				if ms_mask == 1 && used {
+					scale = -scale;
+				}
+				for (r, l) in right.spectrum[range.clone()].iter_mut().zip(&left.spectrum[range]) {
+					*r = l * scale;
+				}
+			} else if used && left_type != NOISE_HCB && right_type != NOISE_HCB {
+				for (l, r) in left.spectrum[range.clone()].iter_mut().zip(right.spectrum[range].iter_mut())
+				{
+					(*l, *r) = (*l + *r, *l - *r);
+				}
+			}
+		}
+		Ok(())
+	}
+
+	/// Fills noise bands, runs TNS and the filterbank of a channel.
+	fn finish_channel(&mut self, index: usize) {
+		let channel = &mut self.channels[index];
+		let ics = &mut channel.ics;
+		for (group, band, range) in ics.info.coded_bands(&self.bands) {
+			if ics.band_types[group][band] != NOISE_HCB {
+				continue;
+			}
+			let noise = &mut ics.spectrum[range];
+			let mut energy = 0.0;
+			for value in noise.iter_mut() {
+				self.random = self.random.wrapping_mul(1664525).wrapping_add(1013904223);
+				*value = self.random as i32 as f32;
+				energy += *value * *value;
+			}
+			let scale = 2f32.powf(0.25 * ics.scalefactors[group][band] as f32) / energy.sqrt();
+			noise.iter_mut().for_each(|value| *value *= scale);
+		}
+		if let Some(tns) = &ics.tns {
+			tns.apply(&ics.info, &self.bands, &mut ics.spectrum);
+		}
+		let info = ics.info;
+		let sequence = info.window_sequence;
+		let (overlap, output) = (&mut channel.overlap, &mut channel.output);
+		self.filterbank.synthesize(
+			sequence,
+			info.window_shape,
+			channel.previous_shape,
+			&ics.spectrum,
+			overlap,
+			output,
+		);
+		channel.previous_shape = info.window_shape;
+	}
+}
+
+impl Default for AacDecoder {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl Decoder for AacDecoder {
+	fn decode(&mut self, packet: Packet) -> Result<Option<Frame>> {
+		if packet.is_empty() {
+			return Ok(None);
+		}
+
+		let mut data = &packet.data[..];
+		if AdtsHeader::is_frame(data) {
+			let header = AdtsHeader::parse(data)?;
+			if header.raw_blocks != 1 {
+				return Err(error!(
+					"adts frames of {} raw data blocks are not supported",
+					header.raw_blocks
+				));
+			}
+			if self.config.is_none_or(|config| config != header.config()) {
+				self.configure(header.config())?;
+			}
+			data = &data[header.header_size..];
+		}
+		let Some(config) = self.config else {
+			return Err(error!("aac decoder has no configuration"));
+		};
+
+		let count = self.decode_block(data)?;
+		let order = CHANNEL_ORDERS.get(config.channel_config as usize).copied().unwrap_or_default();
+		if !order.is_empty() && order.len() != count {
+			return Err(error!("aac frame holds {} channels, its configuration {}", count, order.len()));
+		}
+
+		// samples before zero are encoder delay, those past the duration
+		// padding of the last frame
+		let time = Time::new(1, config.sample_rate);
+		let frame = FRAME_LENGTH as i64;
+		let start = (-time.scale_pts(packet.pts, packet.time)).clamp(0, frame) as usize;
+		let end = match packet.duration {
+			0 => FRAME_LENGTH,
+			duration => time.scale_pts(duration, packet.time).clamp(0, frame) as usize,
+		};
+		if start >= end {
+			return Ok(None);
+		}
+
+		let mut samples = Vec::with_capacity((end - start) * count * 2);
+		for n in start..end {
+			for channel in 0..count {
+				let source = order.get(channel).copied().unwrap_or(channel);
+				let sample = self.channels[source].output[n].round().clamp(-32768.0, 32767.0) as i16;
+				samples.extend_from_slice(&sample.to_le_bytes());
+			}
+		}
+
+		let channels = Channels::from_count(count as u8);
+		let audio = FrameAudio::new(samples, config.sample_rate, channels, AudioFormat::PCM16);
+		let pts = packet.pts + packet.time.scale_pts(start as i64, time);
+		Ok(Some(Frame::new_audio(audio, packet.stream_id).with_pts(pts)))
+	}
+
+	fn flush(&mut self) -> Result<Option<Frame>> {
+		Ok(None)
+	}
+}
+
+/// Reads past a program config element; the channel layout it describes
+/// is taken in bitstream order.
+fn skip_program_config(reader: &mut BitReader) -> Result<()> {
+	reader.read(10)?;
+	let front = reader.read(4)? as usize;
+	let side = reader.read(4)? as usize;
+	let back = reader.read(4)? as usize;
+	let lfe = reader.read(2)? as usize;
+	let data = reader.read(3)? as usize;
+	let coupling = reader.read(4)? as usize;
+	// mono, stereo and matrix mixdown
+	for bits in [4, 4, 3] {
+		if reader.read_bit()? {
+			reader.read(bits)?;
+		}
+	}
+	reader.skip((front + side + back) * 5 + (lfe + data) * 4 + coupling * 5)?;
+	reader.align();
+	let comment = reader.read(8)? as usize;
+	reader.skip(comment * 8)
+}
@@ -0,0 +1,622 @@
+use super::adts::{AdtsHeader, HEADER_SIZE, VARIABLE_FULLNESS};
+use super::config::{self, AudioSpecificConfig, OBJECT_TYPE_LC};
+use super::decoder::CHANNEL_ORDERS;
+use super::filterbank::{FRAME_LENGTH, Filterbank, SHORT_LENGTH};
+use super::ics::{IcsInfo, MAX_BANDS, MAX_WINDOWS, WindowSequence};
+use super::psy::{self, Masking, PsyModel};
+use super::quantizer::{self, Quantized, Quantizer};
+use super::tables::Bands;
+use crate::container::wav::converter;
+use crate::core::Encoder;
+use crate::core::frame::{Channels, Frame};
+use crate::core::packet::Packet;
+use crate::core::time::Time;
+use crate::io::BitWriter;
+use crate::{error, message::Result};
+use std::collections::VecDeque;
+
+// default bitrate, 128 kb/s for stereo
+const BITRATE_PER_CHANNEL: u32 = 64000;
+const MIN_BITRATE_PER_CHANNEL: u32 = 8000;
+pub const DEFAULT_QUALITY: u8 = 3;
+/// Samples the first packet holds ahead of the input.
+pub const ENCODER_DELAY: usize = FRAME_LENGTH;
+const MAX_QUALITY: u8 = 5;
+// a decoder buffers this many bits per channel
+const BUFFER_BITS: usize = 6144;
+// the decoder output is scaled to 16 bit sample values
+const SAMPLE_SCALE: f32 = 32768.0;
+
+// syntactic elements the encoder writes
+const ID_SCE: u32 = 0;
+const ID_CPE: u32 = 1;
+const ID_LFE: u32 = 3;
+const ID_FIL: u32 = 6;
+const ID_END: u32 = 7;
+// payload bytes one fill element holds
+const MAX_FILL_BYTES: usize = 14 + 255;
+const FILL_BYTE: u32 = 0xA5;
+
+// the rate loop searches threshold scales between these powers of two
+const MIN_SCALE_LOG: f32 = -12.0;
+const MAX_SCALE_LOG: f32 = 30.0;
+const RATE_STEPS: usize = 12;
+// threshold scale, as a power of two, at the default quality and for each
+// quality step above it
+const QUALITY_SCALE_LOG: f32 = -2.6;
+const QUALITY_STEP_LOG: f32 = -1.5;
+// a frame takes between these shares of the average bits by its demand
+const MIN_DEMAND: f32 = 0.7;
+const MAX_DEMAND: f32 = 2.0;
+// the LFE channel carries nothing above this
+const LFE_BANDWIDTH: u32 = 240;
+// short windows cover an attack in the first five eighths of a frame, a
+// later one falls to the frame after
+const LAST_SHORT_BLOCK: usize = 4;
+
+/// How the encoder spends bits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RateControl {
+	/// A constant bitrate in bits per second, evened out over frames by a
+	/// bit reservoir.
+	Constant(u32),
+	/// A variable bitrate at a quality from 1 to 5.
+	Quality(u8),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Element {
+	Single,
+	Pair,
+	Lfe,
+}
+
+// channel elements of every channel configuration, in bitstream order
+const ELEMENTS: [&[Element]; 8] = [
+	&[],
+	&[Element::Single],
+	&[Element::Pair],
+	&[Element::Single, Element::Pair],
+	&[Element::Single, Element::Pair, Element::Single],
+	&[Element::Single, Element::Pair, Element::Pair],
+	&[Element::Single, Element::Pair, Element::Pair, Element::Lfe],
+	&[Element::Single, Element::Pair, Element::Pair, Element::Pair, Element::Lfe],
+];
+
+/// Window switching of one channel element.
+#[derive(Debug, Clone, Copy, Default)]
+struct WindowState {
+	previous: WindowSequence,
+	/// Attacks ahead call for short windows in the current and next frame.
+	current_short: bool,
+	next_short: bool,
+}
+
+impl WindowState {
+	fn advance(&mut self, attack: Option<usize>) -> WindowSequence {
+		let mut after_short = false;
+		match attack {
+			Some(block) if block <= LAST_SHORT_BLOCK => self.next_short = true,
+			Some(_) => after_short = true,
+			None => {}
+		}
+		let previous_short = self.previous == WindowSequence::EightShort;
+		let sequence = match (self.current_short, self.next_short, previous_short) {
+			(true, _, _) | (_, true, true) => WindowSequence::EightShort,
+			(_, true, _) => WindowSequence::LongStart,
+			(_, _, true) => WindowSequence::LongStop,
+			_ => WindowSequence::OnlyLong,
+		};
+		self.previous = sequence;
+		self.current_short = self.next_short;
+		self.next_short = after_short;
+		sequence
+	}
+}
+
+struct ChannelState {
+	/// The previous, the current and the next frame of input.
+	samples: Vec<f32>,
+	info: IcsInfo,
+	spectrum: Vec<f32>,
+	masking: Masking,
+	quantized: Quantized,
+}
+
+impl ChannelState {
+	fn new() -> Self {
+		Self {
+			samples: vec![0.0; FRAME_LENGTH],
+			info: IcsInfo::default(),
+			spectrum: vec![0.0; FRAME_LENGTH],
+			masking: Masking::new(),
+			quantized: Quantized::new(),
+		}
+	}
+}
+
+/// AAC LC encoder taking PCM in WAV channel order. Window switching and
+/// the noise every band may carry come from a psychoacoustic model; a
+/// rate loop scales that noise to a constant bitrate or a quality. Packets
+/// are raw access units for a container, described by `codec_private`,
+/// or ADTS frames. They start one frame early: the first frame is the
+/// encoder delay, which containers trim with an edit list, and the last
+/// lasts only as long as the input it holds.
+pub struct AacEncoder {
+	config: AudioSpecificConfig,
+	rate: RateControl,
+	adts: bool,
+	bands: Bands,
+	/// Bands coded in long and in short windows.
+	max_bands: [usize; 2],
+	filterbank: Filterbank,
+	psy: PsyModel,
+	quantizer: Quantizer,
+	elements: &'static [Element],
+	windows: Vec<WindowState>,
+	/// Channels in bitstream order.
+	channels: Vec<ChannelState>,
+	/// Mid/side coding of every band, per channel pair.
+	ms_used: Vec<[[bool; MAX_BANDS]; MAX_WINDOWS]>,
+	/// Bits written so far, to hold a constant bitrate with.
+	bits_written: u64,
+	average_entropy: f32,
+	input_samples: u64,
+	frames: u64,
+	queue: VecDeque<Packet>,
+	stream_id: u32,
+	flushed: bool,
+}
+
+impl AacEncoder {
+	pub fn new(sample_rate: u32, channels: Channels) -> Result<Self> {
+		let count = channels.count();
+		let channel_config = match count {
+			1..=6 => count,
+			8 => 7,
+			_ => return Err(error!("aac supports 1 to 6 or 8 channels, found {}", count)),
+		};
+		if config::sample_rate_index(sample_rate).is_none() {
+			return Err(error!("aac does not support a sample rate of {} Hz", sample_rate));
+		}
+
+		let config = AudioSpecificConfig::new(OBJECT_TYPE_LC, sample_rate, channel_config);
+		let bands = Bands::new(config.sample_rate_index());
+		let elements = ELEMENTS[channel_config as usize];
+		let pairs = elements.iter().filter(|element| **element == Element::Pair).count();
+		let bitrate = (BITRATE_PER_CHANNEL * count as u32).min(max_bitrate(sample_rate, count as u32));
+		let mut encoder = Self {
+			config,
+			rate: RateControl::Constant(bitrate),
+			adts: false,
+			bands,
+			max_bands: [0; 2],
+			filterbank: Filterbank::new(),
+			psy: PsyModel::new(sample_rate, bands, count as usize),
+			quantizer: Quantizer::new(bands),
+			elements,
+			windows: vec![WindowState::default(); elements.len()],
+			channels: (0..count).map(|_| ChannelState::new()).collect(),
+			ms_used: vec![[[false; MAX_BANDS]; MAX_WINDOWS]; pairs],
+			bits_written: 0,
+			average_entropy: 0.0,
+			input_samples: 0,
+			frames: 0,
+			queue: VecDeque::new(),
+			stream_id: 0,
+			flushed: false,
+		};
+		encoder.set_bandwidth();
+		Ok(encoder)
+	}
+
+	/// Encodes at a constant bitrate, in bits per second.
+	pub fn with_bitrate(mut self, bitrate: u32) -> Result<Self> {
+		let channels = self.channels.len() as u32;
+		let min = MIN_BITRATE_PER_CHANNEL * channels;
+		let max = max_bitrate(self.config.sample_rate, channels);
+		if !(min..=max).contains(&bitrate) {
+			return Err(error!("aac bitrate must be {} to {} b/s, found {}", min, max, bitrate));
+		}
+		self.rate = RateControl::Constant(bitrate);
+		self.set_bandwidth();
+		Ok(self)
+	}
+
+	/// Encodes at a variable bitrate for a quality from 1 to 5.
+	pub fn with_quality(mut self, quality: u8) -> Result<Self> {
+		if !(1..=MAX_QUALITY).contains(&quality) {
+			return Err(error!("aac quality must be 1 to {}, found {}", MAX_QUALITY, quality));
+		}
+		self.rate = RateControl::Quality(quality);
+		self.set_bandwidth();
+		Ok(self)
+	}
+
+	/// Frames packets as ADTS instead of raw access units.
+	pub fn with_adts(mut self, adts: bool) -> Self {
+		self.adts = adts;
+		self
+	}
+
+	pub fn config(&self) -> AudioSpecificConfig {
+		self.config
+	}
+
+	fn set_bandwidth(&mut self) {
+		let sample_rate = self.config.sample_rate;
+		let per_channel = match self.rate {
+			RateControl::Constant(bitrate) => bitrate / self.channels.len() as u32,
+			RateControl::Quality(quality) => 16000 * (quality as u32 + 1),
+		};
+		let bandwidth = match per_channel {
+			0..16000 => 5500,
+			16000..24000 => 8000,
+			24000..32000 => 11000,
+			32000..48000 => 14000,
+			48000..64000 => 16000,
+			64000..80000 => 18000,
+			_ => 20000,
+		};
+		self.max_bands =
+			[false, true].map(|short| coded_bands(&self.bands, short, bandwidth, sample_rate));
+	}
+
+	fn push_samples(&mut self, frame: &Frame) -> Result<()> {
+		let Some(audio) = frame.audio() else {
+			return Ok(());
+		};
+		let count = self.channels.len();
+		if audio.channels.count() as usize != count {
+			return Err(error!(
+				"aac encoder expects {} channels, found {}",
+				count,
+				audio.channels.count()
+			));
+		}
+		let order = CHANNEL_ORDERS[self.config.channel_config as usize];
+		let samples = converter::audio_to_f32(audio)?;
+		for frame in samples.chunks_exact(count) {
+			for (channel, sample) in frame.iter().enumerate() {
+				self.channels[order[channel]].samples.push(sample * SAMPLE_SCALE);
+			}
+		}
+		self.input_samples += (samples.len() / count) as u64;
+		self.stream_id = frame.stream_id;
+		Ok(())
+	}
+
+	/// Encodes every frame that has its next frame buffered, which the
+	/// window decision looks ahead into.
+	fn encode_pending(&mut self) -> Result<()> {
+		while self.channels[0].samples.len() >= 3 * FRAME_LENGTH {
+			let packet = self.encode_frame()?;
+			self.queue.push_back(packet);
+			for channel in &mut self.channels {
+				channel.samples.drain(..FRAME_LENGTH);
+			}
+		}
+		Ok(())
+	}
+
+	fn encode_frame(&mut self) -> Result<Packet> {
+		let mut index = 0;
+		let mut pair = 0;
+		for (element, windows) in self.elements.iter().zip(&mut self.windows) {
+			let count = if *element == Element::Pair { 2 } else { 1 };
+			let range = index..index + count;
+			index += count;
+
+			let mut attack: Option<usize> = None;
+			for channel in range.clone() {
+				let next = &self.channels[channel].samples[2 * FRAME_LENGTH..3 * FRAME_LENGTH];
+				if let Some(block) = self.psy.detect_attack(channel, next) {
+					attack = Some(attack.map_or(block, |first| first.min(block)));
+				}
+			}
+			if *element == Element::Lfe {
+				attack = None;
+			}
+			let sequence = windows.advance(attack);
+			let short = sequence == WindowSequence::EightShort;
+			let mut info = IcsInfo { window_sequence: sequence, groups: 1, ..Default::default() };
+			info.group_lengths[0] = if short { MAX_WINDOWS } else { 1 };
+			info.max_sfb = match element {
+				Element::Lfe => coded_bands(&self.bands, false, LFE_BANDWIDTH, self.config.sample_rate),
+				_ => self.max_bands[short as usize],
+			};
+
+			for channel in &mut self.channels[range.clone()] {
+				let samples = &channel.samples[..2 * FRAME_LENGTH];
+				self.filterbank.analyze(sequence, 0, 0, samples, &mut channel.spectrum);
+			}
+			if short {
+				let spectra: Vec<&[f32]> =
+					self.channels[range.clone()].iter().map(|channel| &channel.spectrum[..]).collect();
+				psy::group_windows(&mut info, &spectra);
+			}
+			for channel in range.clone() {
+				let state = &mut self.channels[channel];
+				state.info = info;
+				self.psy.analyze(channel, &info, &state.spectrum, &mut state.masking);
+			}
+			if *element == Element::Pair {
+				let [left, right] = &mut self.channels[range] else { unreachable!() };
+				mid_side(&self.bands, &info, left, right, &mut self.ms_used[pair]);
+				pair += 1;
+			}
+		}
+
+		let data = self.rate_loop()?;
+		let pts = (self.frames * FRAME_LENGTH as u64) as i64 - ENCODER_DELAY as i64;
+		self.frames += 1;
+		// the last frame is padded past the end of the input
+		let duration = match self.flushed {
+			true => (self.input_samples as i64 - pts).clamp(0, FRAME_LENGTH as i64),
+			false => FRAME_LENGTH as i64,
+		};
+		let time = Time::new(1, self.config.sample_rate);
+		let packet = Packet::new(data, self.stream_id, time).with_pts(pts).with_dts(pts);
+		Ok(packet.with_duration(duration).with_keyframe(true))
+	}
+
+	/// Finds the smallest threshold scale whose frame fits the bits this
+	/// frame may take, and writes that frame.
+	fn rate_loop(&mut self) -> Result<Vec<u8>> {
+		let channels = self.channels.len();
+		let header = if self.adts { HEADER_SIZE * 8 } else { 0 };
+		let max_bits = BUFFER_BITS * channels;
+		let entropy: f32 = self.channels.iter().map(|channel| channel.masking.entropy).sum();
+		self.average_entropy = match self.frames {
+			0 => entropy,
+			_ => 0.9 * self.average_entropy + 0.1 * entropy,
+		};
+
+		let (mut low, budget) = match self.rate {
+			RateControl::Constant(bitrate) => {
+				let (mean, reservoir) = self.reservoir(bitrate);
+				let demand = match self.average_entropy > 0.0 {
+					true => (entropy / self.average_entropy).clamp(MIN_DEMAND, MAX_DEMAND),
+					false => 1.0,
+				};
+				let budget = ((mean as f32 * demand) as usize).min(mean + reservoir);
+				(MIN_SCALE_LOG, budget.min(max_bits))
+			}
+			RateControl::Quality(quality) => {
+				let steps = quality as f32 - DEFAULT_QUALITY as f32;
+				(QUALITY_SCALE_LOG + steps * QUALITY_STEP_LOG, max_bits)
+			}
+		};
+		// the block still rounds up to whole bytes
+		let budget = budget.saturating_sub(header + 7);
+
+		if self.frame_bits(2f32.powf(low)) > budget {
+			let mut high = MAX_SCALE_LOG;
+			for _ in 0..RATE_STEPS {
+				let middle = (low + high) / 2.0;
+				if self.frame_bits(2f32.powf(middle)) > budget {
+					low = middle;
+				} else {
+					high = middle;
+				}
+			}
+			self.frame_bits(2f32.powf(high));
+		}
+
+		let mut writer = self.write_block();
+		let mut fullness = VARIABLE_FULLNESS;
+		if let RateControl::Constant(bitrate) = self.rate {
+			let (mean, reservoir) = self.reservoir(bitrate);
+			let used = (writer.bit_len() + 3).div_ceil(8) * 8 + header;
+			let left = (mean + reservoir).saturating_sub(used);
+			let max_reservoir = max_bits.saturating_sub(mean);
+			if left > max_reservoir {
+				write_fill(&mut writer, (left - max_reservoir) / 8);
+			}
+			let used = (writer.bit_len() + 3).div_ceil(8) * 8 + header;
+			fullness = ((mean + reservoir).saturating_sub(used) / (32 * channels)) as u32;
+		}
+		writer.write(ID_END, 3);
+		let data = writer.finish();
+		self.bits_written += (data.len() * 8 + header) as u64;
+		if !self.adts {
+			return Ok(data);
+		}
+		let mut frame = AdtsHeader::new(&self.config, data.len())?.to_bytes(fullness);
+		frame.extend_from_slice(&data);
+		Ok(frame)
+	}
+
+	/// Mean bits of a frame at `bitrate`, and those saved up by earlier
+	/// frames.
+	fn reservoir(&self, bitrate: u32) -> (usize, usize) {
+		let due =
+			|frames: u64| bitrate as u64 * FRAME_LENGTH as u64 * frames / self.config.sample_rate as u64;
+		let mean = (due(self.frames + 1) - due(self.frames)) as usize;
+		(mean, due(self.frames).saturating_sub(self.bits_written) as usize)
+	}
+
+	/// Quantizes every channel at threshold scale `scale` and returns the
+	/// bits of the raw data block.
+	fn frame_bits(&mut self, scale: f32) -> usize {
+		for channel in &mut self.channels {
+			self.quantizer.prepare(&channel.spectrum);
+			let (info, spectrum) = (&channel.info, &channel.spectrum);
+			self.quantizer.quantize(
+				info,
+				spectrum,
+				&channel.masking.threshold,
+				scale,
+				&mut channel.quantized,
+			);
+		}
+		self.write_block().bit_len() + 3
+	}
+
+	/// Writes the channel elements of a raw data block, without its end.
+	fn write_block(&self) -> BitWriter {
+		let mut writer = BitWriter::with_capacity(BUFFER_BITS / 8 * self.channels.len());
+		let (mut index, mut pair) = (0, 0);
+		let mut tags = [0u32; 4];
+		for element in self.elements {
+			let id = match element {
+				Element::Single => ID_SCE,
+				Element::Pair => ID_CPE,
+				Element::Lfe => ID_LFE,
+			};
+			writer.write(id, 3);
+			writer.write(tags[id as usize], 4);
+			tags[id as usize] += 1;
+
+			let channel = &self.channels[index];
+			if *element != Element::Pair {
+				channel.quantized.write(&channel.info, &self.bands, false, &mut writer);
+				index += 1;
+				continue;
+			}
+			let info = &channel.info;
+			writer.write_bit(true);
+			quantizer::write_info(info, &mut writer);
+			let ms_used = &self.ms_used[pair];
+			let used = ms_used[..info.groups].iter().flat_map(|group| &group[..info.max_sfb]);
+			let (all, any) = (used.clone().all(|used| *used), used.clone().any(|used| *used));
+			match (all, any) {
+				(true, _) => writer.write(2, 2),
+				(_, false) => writer.write(0, 2),
+				_ => {
+					writer.write(1, 2);
+					used.for_each(|used| writer.write_bit(*used));
+				}
+			}
+			for channel in &self.channels[index..index + 2] {
+				channel.quantized.write(&channel.info, &self.bands, true, &mut writer);
+			}
+			index += 2;
+			pair += 1;
+		}
+		writer
+	}
+}
+
+impl Encoder for AacEncoder {
+	fn encode(&mut self, frame: Frame) -> Result<Option<Packet>> {
+		self.push_samples(&frame)?;
+		self.encode_pending()?;
+		Ok(self.queue.pop_front())
+	}
+
+	fn receive(&mut self) -> Result<Option<Packet>> {
+		Ok(self.queue.pop_front())
+	}
+
+	fn flush(&mut self) -> Result<Option<Packet>> {
+		if !self.flushed {
+			self.flushed = true;
+			// the frame of encoder delay comes on top of the input
+			let frames = match self.input_samples {
+				0 => 0,
+				samples => samples.div_ceil(FRAME_LENGTH as u64) + 1,
+			};
+			while self.frames < frames {
+				for channel in &mut self.channels {
+					channel.samples.resize(3 * FRAME_LENGTH, 0.0);
+				}
+				self.encode_pending()?;
+			}
+		}
+		Ok(self.queue.pop_front())
+	}
+
+	fn codec_private(&self) -> Option<Vec<u8>> {
+		Some(self.config.to_bytes())
+	}
+}
+
+/// Codes the bands of a channel pair as mid and side where that takes
+/// fewer bits, estimated from their perceptual entropy.
+fn mid_side(
+	bands: &Bands,
+	info: &IcsInfo,
+	left: &mut ChannelState,
+	right: &mut ChannelState,
+	ms_used: &mut [[bool; MAX_BANDS]; MAX_WINDOWS],
+) {
+	let entropy = |energy: f32, threshold: f32| (energy / threshold).max(1.0).log2();
+	let mut mid = [[0.0; MAX_BANDS]; MAX_WINDOWS];
+	let mut side = [[0.0; MAX_BANDS]; MAX_WINDOWS];
+	for (group, band, range) in info.coded_bands(bands) {
+		for (l, r) in left.spectrum[range.clone()].iter().zip(&right.spectrum[range]) {
+			mid[group][band] += 0.25 * (l + r) * (l + r);
+			side[group][band] += 0.25 * (l - r) * (l - r);
+		}
+	}
+
+	let (left_masking, right_masking) = (&mut left.masking, &mut right.masking);
+	for group in 0..info.groups {
+		ms_used[group].fill(false);
+		for band in 0..info.max_sfb {
+			let left_threshold = left_masking.threshold[group][band];
+			let right_threshold = right_masking.threshold[group][band];
+			// mid and side both have to stay under the lower threshold
+			let threshold = left_threshold.min(right_threshold);
+			let separate = entropy(left_masking.energy[group][band], left_threshold)
+				+ entropy(right_masking.energy[group][band], right_threshold);
+			let joint = entropy(mid[group][band], threshold) + entropy(side[group][band], threshold);
+			if joint < separate {
+				ms_used[group][band] = true;
+				left_masking.threshold[group][band] = threshold;
+				right_masking.threshold[group][band] = threshold;
+			}
+		}
+	}
+
+	for (group, band, range) in info.coded_bands(bands) {
+		if !ms_used[group][band] {
+			continue;
+		}
+		for (l, r) in left.spectrum[range.clone()].iter_mut().zip(&mut right.spectrum[range]) {
+			(*l, *r) = (0.5 * (*l + *r), 0.5 * (*l - *r));
+		}
+	}
+}
+
+/// Writes fill elements of `bytes` bytes in all, to pad a frame of a
+/// constant bitrate stream.
+fn write_fill(writer: &mut BitWriter, mut bytes: usize) {
+	while bytes > 0 {
+		// the element header takes one or two bytes of the padding
+		let header = if bytes > 15 { 2 } else { 1 };
+		let count = (bytes - header).min(MAX_FILL_BYTES);
+		writer.write(ID_FIL, 3);
+		if count >= 15 {
+			writer.write(15, 4);
+			writer.write((count - 14) as u32, 8);
+		} else {
+			writer.write(count as u32, 4);
+		}
+		// a fill extension: its type and a nibble, then the fill pattern
+		for byte in 0..count {
+			writer.write(if byte == 0 { 0 } else { FILL_BYTE }, 8);
+		}
+		bytes = bytes.saturating_sub(count + header);
+		if count == 0 {
+			break;
+		}
+	}
+}
+
+/// The bitrate that fills the decoder buffer with every frame.
+fn max_bitrate(sample_rate: u32, channels: u32) -> u32 {
+	(BUFFER_BITS as u64 * channels as u64 * sample_rate as u64 / FRAME_LENGTH as u64) as u32
+}
+
+/// Bands that start below `bandwidth`.
+fn coded_bands(bands: &Bands, short: bool, bandwidth: u32, sample_rate: u32) -> usize {
+	let length = if short { SHORT_LENGTH } else { FRAME_LENGTH };
+	let offsets = bands.offsets(short);
+	let bandwidth = bandwidth.min(sample_rate / 2) as usize;
+	offsets[..offsets.len() - 1]
+		.iter()
+		.filter(|&&offset| offset * sample_rate as usize / (2 * length) < bandwidth)
+		.count()
+}
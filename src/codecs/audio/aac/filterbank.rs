@@ -0,0 +1,292 @@
+use super::ics::WindowSequence;
+use std::f64::consts::PI;
+
+pub const FRAME_LENGTH: usize = 1024;
+pub const SHORT_LENGTH: usize = 128;
+// where the eight short windows start and end within a long block
+const SHORT_START: usize = (FRAME_LENGTH - SHORT_LENGTH) / 2;
+const SHORT_END: usize = SHORT_START + FRAME_LENGTH + SHORT_LENGTH;
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Complex {
+	re: f32,
+	im: f32,
+}
+
+impl Complex {
+	fn new(re: f32, im: f32) -> Self {
+		Self { re, im }
+	}
+
+	fn mul(self, other: Self) -> Self {
+		Self::new(self.re * other.re - self.im * other.im, self.re * other.im + self.im * other.re)
+	}
+
+	fn polar(phase: f64) -> Self {
+		Self::new(phase.cos() as f32, phase.sin() as f32)
+	}
+}
+
+/// DCT-IV of `size` values through a complex FFT of half that many.
+struct Dct4 {
+	size: usize,
+	pre: Vec<Complex>,
+	post: Vec<Complex>,
+	twiddles: Vec<Complex>,
+	bitrev: Vec<usize>,
+	buffer: Vec<Complex>,
+}
+
+impl Dct4 {
+	fn new(size: usize) -> Self {
+		let half = size / 2;
+		let scale = PI / size as f64;
+		let pre = (0..half).map(|k| Complex::polar(-scale * (k as f64 + 0.25))).collect();
+		let post = (0..half).map(|n| Complex::polar(-scale * n as f64)).collect();
+		let twiddles =
+			(0..half / 2).map(|k| Complex::polar(-2.0 * PI * k as f64 / half as f64)).collect();
+		let bits = half.trailing_zeros();
+		let bitrev = (0..half).map(|i| i.reverse_bits() >> (usize::BITS - bits)).collect();
+		Self { size, pre, post, twiddles, bitrev, buffer: vec![Complex::default(); half] }
+	}
+
+	fn transform(&mut self, input: &[f32], out: &mut [f32]) {
+		let size = self.size;
+		let half = size / 2;
+		for k in 0..half {
+			let value = Complex::new(input[2 * k], input[size - 1 - 2 * k]);
+			self.buffer[self.bitrev[k]] = value.mul(self.pre[k]);
+		}
+
+		let mut length = 2;
+		while length <= half {
+			let stride = half / length;
+			for start in (0..half).step_by(length) {
+				for j in 0..length / 2 {
+					let twiddle = self.twiddles[j * stride];
+					let a = self.buffer[start + j];
+					let b = self.buffer[start + j + length / 2].mul(twiddle);
+					self.buffer[start + j] = Complex::new(a.re + b.re, a.im + b.im);
+					self.buffer[start + j + length / 2] = Complex::new(a.re - b.re, a.im - b.im);
+				}
+			}
+			length *= 2;
+		}
+
+		for n in 0..half {
+			let value = self.buffer[n].mul(self.post[n]);
+			out[2 * n] = value.re;
+			out[size - 1 - 2 * n] = -value.im;
+		}
+	}
+}
+
+/// Rising half of a sine window.
+fn sine_window(length: usize) -> Vec<f32> {
+	(0..length).map(|n| ((n as f64 + 0.5) * PI / (2 * length) as f64).sin() as f32).collect()
+}
+
+/// Rising half of a Kaiser-Bessel derived window.
+fn kbd_window(length: usize, alpha: f64) -> Vec<f32> {
+	let bessel = |x: f64| {
+		// zeroth order modified Bessel function of the first kind
+		let (mut sum, mut term) = (1.0, 1.0);
+		for k in 1..50 {
+			term *= (x / (2.0 * k as f64)).powi(2);
+			sum += term;
+		}
+		sum
+	};
+	let kaiser: Vec<f64> = (0..=length)
+		.map(|n| {
+			let ratio = (n as f64 - length as f64 / 2.0) / (length as f64 / 2.0);
+			bessel(PI * alpha * (1.0 - ratio * ratio).sqrt())
+		})
+		.collect();
+	let total: f64 = kaiser.iter().sum();
+	let mut sum = 0.0;
+	kaiser[..length]
+		.iter()
+		.map(|value| {
+			sum += value;
+			(sum / total).sqrt() as f32
+		})
+		.collect()
+}
+
+/// MDCT of `2 * dct.size` windowed samples, folded into `values` and
+/// then transformed into `out`. Scaled by 2 as the standard defines it, so
+/// `imdct` undoes it.
+fn mdct(dct: &mut Dct4, input: &[f32], values: &mut [f32], out: &mut [f32]) {
+	let size = dct.size;
+	let half = size / 2;
+	for n in 0..half {
+		values[n] = -2.0 * (input[3 * half - 1 - n] + input[3 * half + n]);
+		values[half + n] = 2.0 * (input[n] - input[size - 1 - n]);
+	}
+	dct.transform(&values[..size], out);
+}
+
+/// Inverse MDCT of `dct.size` coefficients into twice as many samples,
+/// scaled by 2 / N as the standard defines it.
+fn imdct(dct: &mut Dct4, input: &[f32], values: &mut [f32], block: &mut [f32]) {
+	let size = dct.size;
+	let (half, scale) = (size / 2, 1.0 / size as f32);
+	dct.transform(input, values);
+	for (n, sample) in block[..2 * size].iter_mut().enumerate() {
+		let value = match n {
+			_ if n < half => values[n + half],
+			_ if n < 3 * half => -values[3 * half - 1 - n],
+			_ => -values[n - 3 * half],
+		};
+		*sample = value * scale;
+	}
+}
+
+/// Inverse MDCT with windowing and overlap-add, for every window sequence.
+pub struct Filterbank {
+	long: Dct4,
+	short: Dct4,
+	/// Rising window halves by shape, sine then KBD.
+	long_windows: [Vec<f32>; 2],
+	short_windows: [Vec<f32>; 2],
+	values: Vec<f32>,
+	block: Vec<f32>,
+	buffer: Vec<f32>,
+}
+
+impl Filterbank {
+	pub fn new() -> Self {
+		Self {
+			long: Dct4::new(FRAME_LENGTH),
+			short: Dct4::new(SHORT_LENGTH),
+			long_windows: [sine_window(FRAME_LENGTH), kbd_window(FRAME_LENGTH, 4.0)],
+			short_windows: [sine_window(SHORT_LENGTH), kbd_window(SHORT_LENGTH, 6.0)],
+			values: vec![0.0; FRAME_LENGTH],
+			block: vec![0.0; 2 * FRAME_LENGTH],
+			buffer: vec![0.0; 2 * FRAME_LENGTH],
+		}
+	}
+
+	/// Turns one block of samples, the previous frame followed by the
+	/// current one, into the spectrum of the current frame.
+	pub fn analyze(
+		&mut self,
+		sequence: WindowSequence,
+		shape: usize,
+		previous_shape: usize,
+		samples: &[f32],
+		spectrum: &mut [f32],
+	) {
+		let windows = Windows::new(&self.long_windows, &self.short_windows, shape, previous_shape);
+		let (block, values) = (&mut self.block, &mut self.values);
+		if sequence == WindowSequence::EightShort {
+			for window in 0..8 {
+				let start = SHORT_START + window * SHORT_LENGTH;
+				let rising = if window == 0 { windows.short_rising } else { windows.short_falling };
+				for n in 0..SHORT_LENGTH {
+					block[n] = samples[start + n] * rising[n];
+					block[SHORT_LENGTH + n] =
+						samples[start + SHORT_LENGTH + n] * windows.short_falling[SHORT_LENGTH - 1 - n];
+				}
+				let coefficients = &mut spectrum[window * SHORT_LENGTH..(window + 1) * SHORT_LENGTH];
+				mdct(&mut self.short, block, values, coefficients);
+			}
+		} else {
+			for (n, value) in block.iter_mut().enumerate() {
+				*value = samples[n] * windows.long(sequence, n);
+			}
+			mdct(&mut self.long, block, values, spectrum);
+		}
+	}
+
+	/// Turns the spectrum of one frame into `out`, one frame of samples.
+	/// `overlap` carries the second half of the previous block over.
+	pub fn synthesize(
+		&mut self,
+		sequence: WindowSequence,
+		shape: usize,
+		previous_shape: usize,
+		spectrum: &[f32],
+		overlap: &mut [f32],
+		out: &mut [f32],
+	) {
+		let windows = Windows::new(&self.long_windows, &self.short_windows, shape, previous_shape);
+		let (buffer, block, values) = (&mut self.buffer, &mut self.block, &mut self.values);
+
+		if sequence == WindowSequence::EightShort {
+			buffer.fill(0.0);
+			for window in 0..8 {
+				let coefficients = &spectrum[window * SHORT_LENGTH..(window + 1) * SHORT_LENGTH];
+				imdct(&mut self.short, coefficients, values, block);
+				let rising = if window == 0 { windows.short_rising } else { windows.short_falling };
+				let start = SHORT_START + window * SHORT_LENGTH;
+				for n in 0..SHORT_LENGTH {
+					buffer[start + n] += block[n] * rising[n];
+					buffer[start + SHORT_LENGTH + n] +=
+						block[SHORT_LENGTH + n] * windows.short_falling[SHORT_LENGTH - 1 - n];
+				}
+			}
+		} else {
+			imdct(&mut self.long, spectrum, values, block);
+			for (n, value) in buffer.iter_mut().enumerate() {
+				*value = block[n] * windows.long(sequence, n);
+			}
+		}
+
+		for n in 0..FRAME_LENGTH {
+			out[n] = overlap[n] + buffer[n];
+		}
+		overlap.copy_from_slice(&buffer[FRAME_LENGTH..]);
+	}
+}
+
+/// Window halves of one block: the rising ones have the shape of the
+/// previous block, the falling ones that of the current block.
+struct Windows<'a> {
+	long_rising: &'a [f32],
+	long_falling: &'a [f32],
+	short_rising: &'a [f32],
+	short_falling: &'a [f32],
+}
+
+impl<'a> Windows<'a> {
+	fn new(
+		long: &'a [Vec<f32>; 2],
+		short: &'a [Vec<f32>; 2],
+		shape: usize,
+		previous_shape: usize,
+	) -> Self {
+		Self {
+			long_rising: &long[previous_shape],
+			long_falling: &long[shape],
+			short_rising: &short[previous_shape],
+			short_falling: &short[shape],
+		}
+	}
+
+	/// Gain at sample `n` of a long block of `sequence`; start and stop
+	/// blocks fit the short windows next to them.
+	fn long(&self, sequence: WindowSequence, n: usize) -> f32 {
+		match sequence {
+			WindowSequence::LongStop if n < SHORT_START => 0.0,
+			WindowSequence::LongStop if n < SHORT_START + SHORT_LENGTH => {
+				self.short_rising[n - SHORT_START]
+			}
+			WindowSequence::LongStop if n < FRAME_LENGTH => 1.0,
+			WindowSequence::LongStart if (FRAME_LENGTH..SHORT_END - SHORT_LENGTH).contains(&n) => 1.0,
+			WindowSequence::LongStart if (FRAME_LENGTH..SHORT_END).contains(&n) => {
+				self.short_falling[SHORT_END - 1 - n]
+			}
+			WindowSequence::LongStart if n >= SHORT_END => 0.0,
+			_ if n < FRAME_LENGTH => self.long_rising[n],
+			_ => self.long_falling[2 * FRAME_LENGTH - 1 - n],
+		}
+	}
+}
+
+impl Default for Filterbank {
+	fn default() -> Self {
+		Self::new()
+	}
+}
@@ -0,0 +1,306 @@
+use super::filterbank::{FRAME_LENGTH, SHORT_LENGTH};
+use super::huffman::{Codebooks, ESC_HCB, INTENSITY_HCB, INTENSITY_HCB2, NOISE_HCB, ZERO_HCB};
+use super::tables::Bands;
+use super::tns::Tns;
+use crate::io::BitReader;
+use crate::{error, message::Result};
+use std::ops::Range;
+
+// enough for the 51 long bands at 32 kHz
+pub const MAX_BANDS: usize = 64;
+pub const MAX_WINDOWS: usize = 8;
+// codebook 12 is reserved
+const RESERVED_HCB: u8 = 12;
+// scalefactor at which a quantized value keeps its magnitude
+pub const SCALEFACTOR_BIAS: i32 = 100;
+// noise energies are sent relative to the global gain minus this
+const NOISE_OFFSET: i32 = 90;
+// the first noise energy is sent as a plain 9 bit value with this bias
+const NOISE_START_BIAS: i32 = 256;
+const MAX_PULSES: usize = 4;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum WindowSequence {
+	#[default]
+	OnlyLong,
+	LongStart,
+	EightShort,
+	LongStop,
+}
+
+/// Window setup of a channel, shared by both channels of a common window
+/// pair.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct IcsInfo {
+	pub window_sequence: WindowSequence,
+	/// 0 for sine windows, 1 for Kaiser-Bessel derived ones.
+	pub window_shape: usize,
+	pub max_sfb: usize,
+	pub groups: usize,
+	/// Windows in each group, in order.
+	pub group_lengths: [usize; MAX_WINDOWS],
+}
+
+impl IcsInfo {
+	pub fn parse(reader: &mut BitReader, bands: &Bands) -> Result<Self> {
+		reader.read(1)?;
+		let window_sequence = match reader.read(2)? {
+			0 => WindowSequence::OnlyLong,
+			1 => WindowSequence::LongStart,
+			2 => WindowSequence::EightShort,
+			_ => WindowSequence::LongStop,
+		};
+		let window_shape = reader.read(1)? as usize;
+		let mut info = Self { window_sequence, window_shape, groups: 1, ..Default::default() };
+		info.group_lengths[0] = 1;
+		if info.is_short() {
+			info.max_sfb = reader.read(4)? as usize;
+			let grouping = reader.read(7)?;
+			for bit in (0..7).rev() {
+				if grouping & (1 << bit) == 0 {
+					info.groups += 1;
+				}
+				info.group_lengths[info.groups - 1] += 1;
+			}
+		} else {
+			info.max_sfb = reader.read(6)? as usize;
+			if reader.read_bit()? {
+				return Err(error!("aac prediction is not supported"));
+			}
+		}
+		if info.max_sfb > bands.count(info.is_short()) {
+			return Err(error!("aac max_sfb {} exceeds the band count", info.max_sfb));
+		}
+		Ok(info)
+	}
+
+	pub fn is_short(&self) -> bool {
+		self.window_sequence == WindowSequence::EightShort
+	}
+
+	pub fn windows(&self) -> usize {
+		if self.is_short() { MAX_WINDOWS } else { 1 }
+	}
+
+	/// Coefficients in one window.
+	pub fn window_length(&self) -> usize {
+		if self.is_short() { SHORT_LENGTH } else { FRAME_LENGTH }
+	}
+
+	/// Every band below `max_sfb` of every window, as its group, its band
+	/// and its coefficients within the frame.
+	pub fn coded_bands(&self, bands: &Bands) -> impl Iterator<Item = (usize, usize, Range<usize>)> {
+		let offsets = bands.offsets(self.is_short());
+		let (length, max_sfb) = (self.window_length(), self.max_sfb);
+		let windows =
+			(0..self.groups).flat_map(|group| std::iter::repeat_n(group, self.group_lengths[group]));
+		windows.enumerate().flat_map(move |(window, group)| {
+			let base = window * length;
+			(0..max_sfb).map(move |band| (group, band, base + offsets[band]..base + offsets[band + 1]))
+		})
+	}
+}
+
+/// One individual channel stream: the coded spectrum of a channel.
+#[derive(Debug, Clone)]
+pub struct Ics {
+	pub info: IcsInfo,
+	/// Codebook of every band, by group.
+	pub band_types: [[u8; MAX_BANDS]; MAX_WINDOWS],
+	/// Scalefactor, intensity position or noise energy of every band, by
+	/// group.
+	pub scalefactors: [[i32; MAX_BANDS]; MAX_WINDOWS],
+	pub tns: Option<Tns>,
+	/// Dequantized coefficients, window after window.
+	pub spectrum: Vec<f32>,
+	quantized: Vec<i32>,
+}
+
+impl Ics {
+	pub fn new() -> Self {
+		Self {
+			info: IcsInfo::default(),
+			band_types: [[ZERO_HCB; MAX_BANDS]; MAX_WINDOWS],
+			scalefactors: [[0; MAX_BANDS]; MAX_WINDOWS],
+			tns: None,
+			spectrum: vec![0.0; FRAME_LENGTH],
+			quantized: vec![0; FRAME_LENGTH],
+		}
+	}
+
+	/// Reads the channel stream and dequantizes its spectrum. `common` is
+	/// the window setup a channel pair shares, if it does.
+	pub fn parse(
+		&mut self,
+		reader: &mut BitReader,
+		codebooks: &Codebooks,
+		bands: &Bands,
+		common: Option<IcsInfo>,
+	) -> Result<()> {
+		let global_gain = reader.read(8)? as i32;
+		self.info = match common {
+			Some(info) => info,
+			None => IcsInfo::parse(reader, bands)?,
+		};
+		self.read_sections(reader)?;
+		self.read_scalefactors(reader, codebooks, global_gain)?;
+
+		let mut pulses = [(0usize, 0i32); MAX_PULSES];
+		let mut pulse_count = 0;
+		if reader.read_bit()? {
+			if self.info.is_short() {
+				return Err(error!("aac pulse data in a short window"));
+			}
+			pulse_count = reader.read(2)? as usize + 1;
+			let start_band = reader.read(6)? as usize;
+			let mut offset =
+				*bands.long.get(start_band).ok_or_else(|| error!("aac pulse band is invalid"))?;
+			for pulse in pulses[..pulse_count].iter_mut() {
+				offset += reader.read(5)? as usize;
+				*pulse = (offset, reader.read(4)? as i32);
+			}
+		}
+		self.tns = match reader.read_bit()? {
+			true => {
+				let mut tns = self.tns.take().unwrap_or_default();
+				tns.parse(reader, &self.info)?;
+				Some(tns)
+			}
+			false => None,
+		};
+		if reader.read_bit()? {
+			return Err(error!("aac gain control is not supported"));
+		}
+
+		self.read_spectrum(reader, codebooks, bands)?;
+		for &(offset, amplitude) in &pulses[..pulse_count] {
+			let value =
+				self.quantized.get_mut(offset).ok_or_else(|| error!("aac pulse offset is invalid"))?;
+			*value += if *value > 0 { amplitude } else { -amplitude };
+		}
+		self.dequantize(bands);
+		Ok(())
+	}
+
+	fn read_sections(&mut self, reader: &mut BitReader) -> Result<()> {
+		let bits = if self.info.is_short() { 3 } else { 5 };
+		let escape = (1 << bits) - 1;
+		for group in 0..self.info.groups {
+			let mut band = 0;
+			while band < self.info.max_sfb {
+				let codebook = reader.read(4)? as u8;
+				if codebook == RESERVED_HCB {
+					return Err(error!("aac section uses the reserved codebook"));
+				}
+				let mut length = 0;
+				loop {
+					let increment = reader.read(bits)?;
+					length += increment as usize;
+					if increment != escape {
+						break;
+					}
+				}
+				if band + length > self.info.max_sfb {
+					return Err(error!("aac section runs past max_sfb"));
+				}
+				self.band_types[group][band..band + length].fill(codebook);
+				band += length;
+			}
+			self.band_types[group][self.info.max_sfb..].fill(ZERO_HCB);
+		}
+		Ok(())
+	}
+
+	fn read_scalefactors(
+		&mut self,
+		reader: &mut BitReader,
+		codebooks: &Codebooks,
+		global_gain: i32,
+	) -> Result<()> {
+		let mut scalefactor = global_gain;
+		let mut position = 0;
+		let mut noise = global_gain - NOISE_OFFSET;
+		let mut first_noise = true;
+		for group in 0..self.info.groups {
+			for band in 0..self.info.max_sfb {
+				self.scalefactors[group][band] = match self.band_types[group][band] {
+					ZERO_HCB => 0,
+					INTENSITY_HCB | INTENSITY_HCB2 => {
+						position += codebooks.decode_scalefactor(reader)?;
+						position
+					}
+					NOISE_HCB => {
+						noise += match first_noise {
+							true => reader.read(9)? as i32 - NOISE_START_BIAS,
+							false => codebooks.decode_scalefactor(reader)?,
+						};
+						first_noise = false;
+						noise
+					}
+					_ => {
+						scalefactor += codebooks.decode_scalefactor(reader)?;
+						if !(0..=255).contains(&scalefactor) {
+							return Err(error!("aac scalefactor {} is out of range", scalefactor));
+						}
+						scalefactor
+					}
+				};
+			}
+		}
+		Ok(())
+	}
+
+	fn read_spectrum(
+		&mut self,
+		reader: &mut BitReader,
+		codebooks: &Codebooks,
+		bands: &Bands,
+	) -> Result<()> {
+		self.quantized.fill(0);
+		let offsets = bands.offsets(self.info.is_short());
+		let window_length = self.info.window_length();
+		let mut window = 0;
+		for group in 0..self.info.groups {
+			let windows = window..window + self.info.group_lengths[group];
+			window = windows.end;
+			for band in 0..self.info.max_sfb {
+				let codebook = self.band_types[group][band];
+				if codebook == ZERO_HCB || codebook > ESC_HCB {
+					continue;
+				}
+				for window in windows.clone() {
+					let base = window * window_length;
+					let mut offset = base + offsets[band];
+					let end = base + offsets[band + 1];
+					while offset < end {
+						offset +=
+							codebooks.decode_spectrum(codebook, reader, &mut self.quantized[offset..end])?;
+					}
+				}
+			}
+		}
+		Ok(())
+	}
+
+	fn dequantize(&mut self, bands: &Bands) {
+		self.spectrum.fill(0.0);
+		for (group, band, range) in self.info.coded_bands(bands) {
+			let codebook = self.band_types[group][band];
+			if codebook == ZERO_HCB || codebook > ESC_HCB {
+				continue;
+			}
+			let gain = 2f32.powf(0.25 * (self.scalefactors[group][band] - SCALEFACTOR_BIAS) as f32);
+			for (value, &quantized) in self.spectrum[range.clone()].iter_mut().zip(&self.quantized[range])
+			{
+				let magnitude = (quantized.unsigned_abs() as f32).powf(4.0 / 3.0);
+				*value = magnitude.copysign(quantized as f32) * gain;
+			}
+		}
+	}
+}
+
+impl Default for Ics {
+	fn default() -> Self {
+		Self::new()
+	}
+}
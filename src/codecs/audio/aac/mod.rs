@@ -0,0 +1,16 @@
+pub mod adts;
+pub mod config;
+pub mod decoder;
+pub mod encoder;
+pub mod filterbank;
+pub mod huffman;
+pub mod ics;
+pub mod psy;
+pub mod quantizer;
+pub mod tables;
+pub mod tns;
+
+pub use adts::AdtsHeader;
+pub use config::AudioSpecificConfig;
+pub use decoder::AacDecoder;
+pub use encoder::{AacEncoder, RateControl};
@@ -0,0 +1,292 @@
+use super::filterbank::{FRAME_LENGTH, SHORT_LENGTH};
+use super::ics::{IcsInfo, MAX_BANDS, MAX_WINDOWS};
+use super::tables::Bands;
+
+// an attack is a sub-block this much louder than the recent average
+const ATTACK_RATIO: f32 = 10.0;
+// sub-blocks below this energy never hold an attack, about -60 dBFS
+const ATTACK_FLOOR: f32 = 1.0e5;
+// short windows stay in one group while within this energy ratio
+const GROUP_RATIO: f32 = 4.0;
+// energy of the strongest line of a full scale sine in a long window
+const FULL_SCALE_LINE: f32 = 1.8e15;
+// the threshold in quiet puts a full scale sine at this level
+const FULL_SCALE_SPL: f32 = 96.0;
+// the threshold in quiet stops rising here towards high frequencies
+const MAX_QUIET_SPL: f32 = 70.0;
+// masking spreads over this many decibels per bark, up and down
+const SPREAD_UPWARD: f32 = 15.0;
+const SPREAD_DOWNWARD: f32 = 27.0;
+// signal to mask ratio of noise-like and of tonal bands, in decibels
+const NOISE_SMR: f32 = 6.0;
+const TONE_SMR: f32 = 18.0;
+// spectral flatness of noise and of a pure tone, in decibels
+const NOISE_FLATNESS: f32 = -6.0;
+const TONE_FLATNESS: f32 = -20.0;
+// the threshold of a long window may not rise faster than this
+const PRE_ECHO_RATIO: f32 = 2.0;
+
+/// Energy and masking threshold of every band of one channel, by group.
+#[derive(Debug, Clone)]
+pub struct Masking {
+	pub energy: [[f32; MAX_BANDS]; MAX_WINDOWS],
+	pub threshold: [[f32; MAX_BANDS]; MAX_WINDOWS],
+	/// Perceptual entropy, the bits the frame needs to stay transparent.
+	pub entropy: f32,
+}
+
+impl Masking {
+	pub fn new() -> Self {
+		Self {
+			energy: [[0.0; MAX_BANDS]; MAX_WINDOWS],
+			threshold: [[0.0; MAX_BANDS]; MAX_WINDOWS],
+			entropy: 0.0,
+		}
+	}
+}
+
+impl Default for Masking {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+#[derive(Debug, Clone)]
+struct ChannelState {
+	/// Last sample of the previous frame, for the high pass filter.
+	last_sample: f32,
+	/// High passed energy of the last eight sub-blocks.
+	block_energies: [f32; MAX_WINDOWS],
+	/// Thresholds of the previous frame, when it was a long one.
+	previous: Option<[f32; MAX_BANDS]>,
+}
+
+/// Psychoacoustic model: finds attacks that call for short windows and
+/// the noise every band can hide.
+pub struct PsyModel {
+	bands: Bands,
+	/// Bark position of every band centre, long windows then short ones.
+	barks: [Vec<f32>; 2],
+	/// Threshold in quiet of every band, long windows then short ones.
+	quiet: [Vec<f32>; 2],
+	channels: Vec<ChannelState>,
+}
+
+impl PsyModel {
+	pub fn new(sample_rate: u32, bands: Bands, channels: usize) -> Self {
+		let state =
+			ChannelState { last_sample: 0.0, block_energies: [0.0; MAX_WINDOWS], previous: None };
+		Self {
+			bands,
+			barks: [false, true].map(|short| band_barks(&bands, short, sample_rate)),
+			quiet: [false, true].map(|short| band_quiet(&bands, short, sample_rate)),
+			channels: vec![state; channels],
+		}
+	}
+
+	/// Looks for an attack in the next frame of `channel`, and returns the
+	/// short window it starts in.
+	pub fn detect_attack(&mut self, channel: usize, samples: &[f32]) -> Option<usize> {
+		let state = &mut self.channels[channel];
+		let mut attack = None;
+		for (block, samples) in samples.chunks_exact(SHORT_LENGTH).enumerate() {
+			let mut energy = 0.0;
+			for &sample in samples {
+				let value = sample - state.last_sample;
+				state.last_sample = sample;
+				energy += value * value;
+			}
+			let average = state.block_energies.iter().sum::<f32>() / MAX_WINDOWS as f32;
+			if attack.is_none() && energy > ATTACK_FLOOR && energy > ATTACK_RATIO * average {
+				attack = Some(block);
+			}
+			state.block_energies.rotate_left(1);
+			state.block_energies[MAX_WINDOWS - 1] = energy;
+		}
+		attack
+	}
+
+	/// Fills `masking` with the band energies and thresholds of one channel,
+	/// whose spectrum was taken with the windows of `info`.
+	pub fn analyze(
+		&mut self,
+		channel: usize,
+		info: &IcsInfo,
+		spectrum: &[f32],
+		masking: &mut Masking,
+	) {
+		let short = info.is_short();
+		let count = self.bands.count(short);
+		let length = info.window_length();
+		let mut energy = [0.0; MAX_BANDS];
+		let mut threshold = [0.0; MAX_BANDS];
+		masking.entropy = 0.0;
+
+		if !short {
+			self.thresholds(short, spectrum, &mut energy, &mut threshold);
+			let state = &mut self.channels[channel];
+			if let Some(previous) = &state.previous {
+				for band in 0..count {
+					let limited = threshold[band].min(PRE_ECHO_RATIO * previous[band]);
+					threshold[band] = limited.max(self.quiet[0][band]);
+				}
+			}
+			state.previous = Some(threshold);
+			masking.energy[0] = energy;
+			masking.threshold[0] = threshold;
+			masking.entropy = entropy(&self.bands, short, &energy, &threshold);
+			return;
+		}
+
+		self.channels[channel].previous = None;
+		let mut window = 0;
+		for group in 0..info.groups {
+			masking.energy[group] = [0.0; MAX_BANDS];
+			masking.threshold[group] = [f32::MAX; MAX_BANDS];
+			for _ in 0..info.group_lengths[group] {
+				let coefficients = &spectrum[window * length..(window + 1) * length];
+				self.thresholds(short, coefficients, &mut energy, &mut threshold);
+				masking.entropy += entropy(&self.bands, short, &energy, &threshold);
+				for band in 0..count {
+					masking.energy[group][band] += energy[band];
+					masking.threshold[group][band] = masking.threshold[group][band].min(threshold[band]);
+				}
+				window += 1;
+			}
+			// the noise of a group spreads evenly over its windows
+			for value in masking.threshold[group][..count].iter_mut() {
+				*value *= info.group_lengths[group] as f32;
+			}
+		}
+	}
+
+	/// Band energies and masking thresholds of one window.
+	fn thresholds(
+		&self,
+		short: bool,
+		coefficients: &[f32],
+		energy: &mut [f32],
+		threshold: &mut [f32],
+	) {
+		let offsets = self.bands.offsets(short);
+		let count = self.bands.count(short);
+		let (barks, quiet) = (&self.barks[short as usize], &self.quiet[short as usize]);
+
+		let mut density = [0.0; MAX_BANDS];
+		let mut smr = [0.0; MAX_BANDS];
+		for band in 0..count {
+			let lines = &coefficients[offsets[band]..offsets[band + 1]];
+			let width = lines.len() as f32;
+			let mut log_sum = 0.0;
+			energy[band] = 0.0;
+			for value in lines {
+				let power = value * value;
+				energy[band] += power;
+				log_sum += (power + 1.0).ln();
+			}
+			density[band] = energy[band] / width;
+			// spectral flatness tells tones, which mask less, from noise
+			let flatness = 10.0 * ((log_sum / width).exp() / (density[band] + 1.0)).log10();
+			let tonality =
+				((flatness - NOISE_FLATNESS) / (TONE_FLATNESS - NOISE_FLATNESS)).clamp(0.0, 1.0);
+			smr[band] = NOISE_SMR + tonality * (TONE_SMR - NOISE_SMR);
+		}
+
+		for band in 0..count {
+			let mut spread = 0.0f32;
+			for masker in 0..count {
+				let distance = barks[band] - barks[masker];
+				let attenuation = match distance >= 0.0 {
+					true => distance * SPREAD_UPWARD,
+					false => -distance * SPREAD_DOWNWARD,
+				};
+				spread = spread.max(density[masker] * 10f32.powf(-attenuation / 10.0));
+			}
+			let width = (offsets[band + 1] - offsets[band]) as f32;
+			let masked = spread * width * 10f32.powf(-smr[band] / 10.0);
+			threshold[band] = masked.max(quiet[band]);
+		}
+	}
+}
+
+/// Splits the eight short windows of a frame into groups of windows with
+/// similar energy, which then share their scalefactors. A channel pair
+/// groups by the energy of both of its channels.
+pub fn group_windows(info: &mut IcsInfo, spectra: &[&[f32]]) {
+	info.groups = 0;
+	let mut reference = 0.0;
+	for window in 0..MAX_WINDOWS {
+		let range = window * SHORT_LENGTH..(window + 1) * SHORT_LENGTH;
+		let energy = spectra
+			.iter()
+			.flat_map(|spectrum| &spectrum[range.clone()])
+			.map(|value| value * value)
+			.sum::<f32>()
+			.max(1.0);
+		let ratio = energy / reference;
+		if window == 0 || !(1.0 / GROUP_RATIO..=GROUP_RATIO).contains(&ratio) {
+			info.group_lengths[info.groups] = 0;
+			info.groups += 1;
+			reference = energy;
+		}
+		info.group_lengths[info.groups - 1] += 1;
+	}
+}
+
+/// Perceptual entropy of one window: the bits it takes to code every band
+/// above its threshold.
+fn entropy(bands: &Bands, short: bool, energy: &[f32], threshold: &[f32]) -> f32 {
+	let offsets = bands.offsets(short);
+	let mut entropy = 0.0;
+	for band in 0..bands.count(short) {
+		if energy[band] > threshold[band] {
+			let width = (offsets[band + 1] - offsets[band]) as f32;
+			entropy += 0.5 * width * (energy[band] / threshold[band]).log2();
+		}
+	}
+	entropy
+}
+
+/// Frequency of line `line` of a window of `length` lines.
+fn line_frequency(line: f32, length: usize, sample_rate: u32) -> f32 {
+	line * sample_rate as f32 / (2 * length) as f32
+}
+
+fn band_barks(bands: &Bands, short: bool, sample_rate: u32) -> Vec<f32> {
+	let offsets = bands.offsets(short);
+	let length = if short { SHORT_LENGTH } else { FRAME_LENGTH };
+	offsets
+		.windows(2)
+		.map(|band| {
+			let centre = (band[0] + band[1]) as f32 / 2.0;
+			let frequency = line_frequency(centre, length, sample_rate);
+			13.0 * (0.00076 * frequency).atan() + 3.5 * (frequency / 7500.0).powi(2).atan()
+		})
+		.collect()
+}
+
+/// The threshold in quiet of every band as an energy: that of its most
+/// sensitive line over all of its lines.
+fn band_quiet(bands: &Bands, short: bool, sample_rate: u32) -> Vec<f32> {
+	let offsets = bands.offsets(short);
+	let length = if short { SHORT_LENGTH } else { FRAME_LENGTH };
+	// short windows gather an eighth of the amplitude per line
+	let full_scale =
+		FULL_SCALE_LINE * (length * length) as f32 / (FRAME_LENGTH * FRAME_LENGTH) as f32;
+	offsets
+		.windows(2)
+		.map(|band| {
+			let lowest = (band[0]..band[1])
+				.map(|line| quiet_level(line_frequency(line as f32 + 0.5, length, sample_rate)))
+				.fold(f32::MAX, f32::min);
+			full_scale * 10f32.powf((lowest - FULL_SCALE_SPL) / 10.0) * (band[1] - band[0]) as f32
+		})
+		.collect()
+}
+
+/// Absolute threshold of hearing in dB SPL, after Terhardt.
+fn quiet_level(frequency: f32) -> f32 {
+	let khz = (frequency / 1000.0).max(0.02);
+	let level = 3.64 * khz.powf(-0.8) - 6.5 * (-0.6 * (khz - 3.3).powi(2)).exp() + 1e-3 * khz.powi(4);
+	level.min(MAX_QUIET_SPL)
+}
@@ -0,0 +1,394 @@
+use super::filterbank::FRAME_LENGTH;
+use super::huffman::{self, ESC_HCB, ZERO_HCB};
+use super::ics::{IcsInfo, MAX_BANDS, MAX_WINDOWS, SCALEFACTOR_BIAS, WindowSequence};
+use super::tables::Bands;
+use crate::io::BitWriter;
+
+// magnitudes round up from this fraction, which minimizes the error
+// after the 4/3 power law of the decoder
+const ROUNDING: f32 = 0.4054;
+const MAX_QUANTIZED: i32 = 8191;
+const MAX_SCALEFACTOR: i32 = 255;
+// scalefactors differ by at most this much from one band to the next
+const MAX_DELTA: i32 = 60;
+const CODEBOOKS: usize = ESC_HCB as usize + 1;
+const NO_BITS: u32 = u32::MAX;
+
+/// A run of bands sharing one codebook, with what it costs under every
+/// codebook.
+#[derive(Debug, Clone, Copy)]
+struct Section {
+	start: usize,
+	end: usize,
+	costs: [u32; CODEBOOKS],
+}
+
+impl Section {
+	fn best(&self, short: bool) -> (u8, u32) {
+		let (codebook, cost) = (0..CODEBOOKS)
+			.map(|codebook| (codebook as u8, self.costs[codebook]))
+			.min_by_key(|(_, cost)| *cost)
+			.unwrap_or((ZERO_HCB, 0));
+		(codebook, cost.saturating_add(section_header_bits(self.end - self.start, short)))
+	}
+}
+
+/// Quantized spectrum of one channel and the side information that codes
+/// it.
+#[derive(Debug, Clone)]
+pub struct Quantized {
+	pub global_gain: i32,
+	pub band_types: [[u8; MAX_BANDS]; MAX_WINDOWS],
+	pub scalefactors: [[i32; MAX_BANDS]; MAX_WINDOWS],
+	pub values: Vec<i32>,
+	/// Bits of the channel stream, ics_info aside.
+	pub bits: usize,
+}
+
+impl Quantized {
+	pub fn new() -> Self {
+		Self {
+			global_gain: SCALEFACTOR_BIAS,
+			band_types: [[ZERO_HCB; MAX_BANDS]; MAX_WINDOWS],
+			scalefactors: [[0; MAX_BANDS]; MAX_WINDOWS],
+			values: vec![0; FRAME_LENGTH],
+			bits: 0,
+		}
+	}
+
+	/// Writes the channel stream. `info` goes in unless a channel pair
+	/// shares it.
+	pub fn write(&self, info: &IcsInfo, bands: &Bands, common: bool, writer: &mut BitWriter) {
+		writer.write(self.global_gain as u32, 8);
+		if !common {
+			write_info(info, writer);
+		}
+
+		let short = info.is_short();
+		let (bits, escape) = section_length_bits(short);
+		for group in 0..info.groups {
+			let types = &self.band_types[group];
+			let mut band = 0;
+			while band < info.max_sfb {
+				let end =
+					(band..info.max_sfb).find(|&end| types[end] != types[band]).unwrap_or(info.max_sfb);
+				writer.write(types[band] as u32, 4);
+				let mut length = end - band;
+				while length >= escape {
+					writer.write(escape as u32, bits);
+					length -= escape;
+				}
+				writer.write(length as u32, bits);
+				band = end;
+			}
+		}
+
+		let mut previous = self.global_gain;
+		for group in 0..info.groups {
+			for band in 0..info.max_sfb {
+				if self.band_types[group][band] != ZERO_HCB {
+					let scalefactor = self.scalefactors[group][band];
+					huffman::write_scalefactor(scalefactor - previous, writer);
+					previous = scalefactor;
+				}
+			}
+		}
+
+		// no pulses, TNS or gain control
+		writer.write(0, 3);
+
+		let offsets = bands.offsets(short);
+		let length = info.window_length();
+		let mut window = 0;
+		for group in 0..info.groups {
+			let windows = window..window + info.group_lengths[group];
+			window = windows.end;
+			for band in 0..info.max_sfb {
+				let codebook = self.band_types[group][band];
+				if codebook == ZERO_HCB {
+					continue;
+				}
+				let dimension = huffman::dimension(codebook);
+				for window in windows.clone() {
+					let base = window * length;
+					let values = &self.values[base + offsets[band]..base + offsets[band + 1]];
+					for codeword in values.chunks_exact(dimension) {
+						huffman::write_spectrum(codebook, codeword, writer);
+					}
+				}
+			}
+		}
+	}
+}
+
+impl Default for Quantized {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+/// Writes the window setup of a channel or a channel pair.
+pub fn write_info(info: &IcsInfo, writer: &mut BitWriter) {
+	writer.write_bit(false);
+	let sequence = match info.window_sequence {
+		WindowSequence::OnlyLong => 0,
+		WindowSequence::LongStart => 1,
+		WindowSequence::EightShort => 2,
+		WindowSequence::LongStop => 3,
+	};
+	writer.write(sequence, 2);
+	writer.write(info.window_shape as u32, 1);
+	if info.is_short() {
+		writer.write(info.max_sfb as u32, 4);
+		// a set bit joins a window to the group of the one before it, the
+		// first window has none
+		let mut grouping = 0;
+		for &length in &info.group_lengths[..info.groups] {
+			grouping <<= 1;
+			for _ in 1..length {
+				grouping = (grouping << 1) | 1;
+			}
+		}
+		writer.write(grouping & 0x7F, 7);
+	} else {
+		writer.write(info.max_sfb as u32, 6);
+		// no prediction
+		writer.write_bit(false);
+	}
+}
+
+/// Chooses scalefactors that keep the quantization noise of every band
+/// under what it is allowed, then codebooks and sections for the result.
+pub struct Quantizer {
+	bands: Bands,
+	/// `n^(4/3)` for every quantized magnitude.
+	powers: Vec<f32>,
+	/// Magnitudes of the spectrum raised to 3/4.
+	scaled: Vec<f32>,
+}
+
+impl Quantizer {
+	pub fn new(bands: Bands) -> Self {
+		let powers = (0..=MAX_QUANTIZED).map(|value| (value as f32).powf(4.0 / 3.0)).collect();
+		Self { bands, powers, scaled: vec![0.0; FRAME_LENGTH] }
+	}
+
+	/// Takes the spectrum a channel is about to have quantized, repeatedly
+	/// with different allowed noise.
+	pub fn prepare(&mut self, spectrum: &[f32]) {
+		for (scaled, value) in self.scaled.iter_mut().zip(spectrum) {
+			*scaled = value.abs().powf(0.75);
+		}
+	}
+
+	/// Quantizes the prepared spectrum, letting every band carry the noise
+	/// of `allowed` scaled by `scale`.
+	pub fn quantize(
+		&self,
+		info: &IcsInfo,
+		spectrum: &[f32],
+		allowed: &[[f32; MAX_BANDS]; MAX_WINDOWS],
+		scale: f32,
+		out: &mut Quantized,
+	) {
+		let short = info.is_short();
+		let offsets = self.bands.offsets(short);
+		let length = info.window_length();
+		let mut window = 0;
+		let mut coded = Vec::with_capacity(MAX_WINDOWS * MAX_BANDS);
+		for group in 0..info.groups {
+			let windows = window..window + info.group_lengths[group];
+			window = windows.end;
+			for band in 0..info.max_sfb {
+				let ranges = windows
+					.clone()
+					.map(|window| window * length + offsets[band]..window * length + offsets[band + 1]);
+				let ranges: Vec<_> = ranges.collect();
+				let energy: f32 =
+					ranges.iter().flat_map(|range| &spectrum[range.clone()]).map(|x| x * x).sum();
+				let allowed = allowed[group][band] * scale;
+				if energy <= allowed || energy == 0.0 {
+					out.band_types[group][band] = ZERO_HCB;
+					continue;
+				}
+				out.band_types[group][band] = 1;
+				out.scalefactors[group][band] = self.search(spectrum, &ranges, allowed);
+				coded.push((group, band));
+			}
+			out.band_types[group][info.max_sfb..].fill(ZERO_HCB);
+		}
+
+		// neighbours may differ by 60 at most, coarser steps keep the values
+		// in range
+		for pair in 1..coded.len() {
+			let ((g0, b0), (g1, b1)) = (coded[pair - 1], coded[pair]);
+			let floor = out.scalefactors[g0][b0] - MAX_DELTA;
+			out.scalefactors[g1][b1] = out.scalefactors[g1][b1].max(floor);
+		}
+		for pair in (1..coded.len()).rev() {
+			let ((g0, b0), (g1, b1)) = (coded[pair - 1], coded[pair]);
+			let floor = out.scalefactors[g1][b1] - MAX_DELTA;
+			out.scalefactors[g0][b0] = out.scalefactors[g0][b0].max(floor);
+		}
+		out.global_gain = coded.first().map_or(SCALEFACTOR_BIAS, |&(g, b)| out.scalefactors[g][b]);
+
+		out.values.fill(0);
+		let mut costs = [[[NO_BITS; CODEBOOKS]; MAX_BANDS]; MAX_WINDOWS];
+		let mut window = 0;
+		for group in 0..info.groups {
+			let windows = window..window + info.group_lengths[group];
+			window = windows.end;
+			for band in 0..info.max_sfb {
+				let costs = &mut costs[group][band];
+				if out.band_types[group][band] == ZERO_HCB {
+					costs[ZERO_HCB as usize] = 0;
+					continue;
+				}
+				let scalefactor = out.scalefactors[group][band];
+				let mut largest = 0;
+				for window in windows.clone() {
+					let range = window * length + offsets[band]..window * length + offsets[band + 1];
+					largest = largest.max(self.quantize_range(spectrum, range, scalefactor, &mut out.values));
+				}
+				// the smallest pair of codebooks that fits, the next pair and the
+				// escape codebook, which the others merge into
+				let first = (1..ESC_HCB).find(|&codebook| huffman::max_value(codebook) >= largest);
+				let first = first.unwrap_or(ESC_HCB);
+				for codebook in (first..(first + 4).min(ESC_HCB)).chain([ESC_HCB]) {
+					let dimension = huffman::dimension(codebook);
+					let mut bits = 0;
+					for window in windows.clone() {
+						let base = window * length;
+						let values = &out.values[base + offsets[band]..base + offsets[band + 1]];
+						for codeword in values.chunks_exact(dimension) {
+							bits += huffman::spectrum_bits(codebook, codeword);
+						}
+					}
+					costs[codebook as usize] = bits;
+				}
+			}
+		}
+
+		let mut bits = 8 + 3;
+		for (group, costs) in costs.iter().enumerate().take(info.groups) {
+			for section in sections(&costs[..info.max_sfb], short) {
+				let (codebook, cost) = section.best(short);
+				out.band_types[group][section.start..section.end].fill(codebook);
+				bits += cost as usize;
+			}
+		}
+		let mut previous = out.global_gain;
+		for &(group, band) in &coded {
+			let scalefactor = out.scalefactors[group][band];
+			bits += huffman::scalefactor_bits(scalefactor - previous) as usize;
+			previous = scalefactor;
+		}
+		out.bits = bits;
+	}
+
+	/// The coarsest scalefactor that keeps the noise of a band within
+	/// `allowed`, or the finest one that keeps its values in range.
+	fn search(&self, spectrum: &[f32], ranges: &[std::ops::Range<usize>], allowed: f32) -> i32 {
+		let largest =
+			ranges.iter().flat_map(|range| &self.scaled[range.clone()]).fold(0.0f32, |a, &b| a.max(b));
+		// q = x^(3/4) * 2^(-3/16 (sf - 100)) + rounding stays within range
+		let finest =
+			SCALEFACTOR_BIAS as f32 - 16.0 / 3.0 * ((MAX_QUANTIZED as f32 - ROUNDING) / largest).log2();
+		let (mut low, mut high) = ((finest.ceil() as i32).clamp(0, MAX_SCALEFACTOR), MAX_SCALEFACTOR);
+		let noise = |scalefactor| -> f32 {
+			ranges.iter().map(|range| self.noise(spectrum, range.clone(), scalefactor)).sum()
+		};
+		if noise(low) > allowed {
+			return low;
+		}
+		while low < high {
+			let middle = (low + high + 1) / 2;
+			if noise(middle) <= allowed {
+				low = middle;
+			} else {
+				high = middle - 1;
+			}
+		}
+		low
+	}
+
+	/// Squared error of quantizing `range` with `scalefactor`.
+	fn noise(&self, spectrum: &[f32], range: std::ops::Range<usize>, scalefactor: i32) -> f32 {
+		let (step, gain) = steps(scalefactor);
+		let mut noise = 0.0;
+		for (value, scaled) in spectrum[range.clone()].iter().zip(&self.scaled[range]) {
+			let quantized = ((scaled * step + ROUNDING) as i32).min(MAX_QUANTIZED);
+			let error = value.abs() - self.powers[quantized as usize] * gain;
+			noise += error * error;
+		}
+		noise
+	}
+
+	/// Quantizes `range` into `values` and returns the largest magnitude.
+	fn quantize_range(
+		&self,
+		spectrum: &[f32],
+		range: std::ops::Range<usize>,
+		scalefactor: i32,
+		values: &mut [i32],
+	) -> i32 {
+		let (step, _) = steps(scalefactor);
+		let mut largest = 0;
+		for index in range {
+			let magnitude = ((self.scaled[index] * step + ROUNDING) as i32).min(MAX_QUANTIZED);
+			values[index] = if spectrum[index] < 0.0 { -magnitude } else { magnitude };
+			largest = largest.max(magnitude);
+		}
+		largest
+	}
+}
+
+/// The quantizer step applied to `x^(3/4)`, and the gain the decoder
+/// applies after its 4/3 power.
+fn steps(scalefactor: i32) -> (f32, f32) {
+	let exponent = (scalefactor - SCALEFACTOR_BIAS) as f32;
+	(2f32.powf(-0.1875 * exponent), 2f32.powf(0.25 * exponent))
+}
+
+/// Bits of a section length, and the value that escapes to another one.
+fn section_length_bits(short: bool) -> (u32, usize) {
+	if short { (3, 7) } else { (5, 31) }
+}
+
+fn section_header_bits(length: usize, short: bool) -> u32 {
+	let (bits, escape) = section_length_bits(short);
+	4 + bits * (length / escape + 1) as u32
+}
+
+/// Merges neighbouring bands into sections as long as that saves bits.
+fn sections(costs: &[[u32; CODEBOOKS]], short: bool) -> Vec<Section> {
+	let mut sections: Vec<Section> = costs
+		.iter()
+		.enumerate()
+		.map(|(band, costs)| Section { start: band, end: band + 1, costs: *costs })
+		.collect();
+	loop {
+		let mut best = None;
+		for index in 1..sections.len() {
+			let (left, right) = (sections[index - 1], sections[index]);
+			let merged = merge(&left, &right);
+			let separate = left.best(short).1.saturating_add(right.best(short).1);
+			let saving = separate as i64 - merged.best(short).1 as i64;
+			if saving > 0 && best.is_none_or(|(_, most)| saving > most) {
+				best = Some((index, saving));
+			}
+		}
+		let Some((index, _)) = best else { break };
+		sections[index - 1] = merge(&sections[index - 1], &sections[index]);
+		sections.remove(index);
+	}
+	sections
+}
+
+fn merge(left: &Section, right: &Section) -> Section {
+	let mut costs = [NO_BITS; CODEBOOKS];
+	for (cost, (a, b)) in costs.iter_mut().zip(left.costs.iter().zip(&right.costs)) {
+		*cost = a.saturating_add(*b);
+	}
+	Section { start: left.start, end: right.end, costs }
+}
@@ -0,0 +1,109 @@
+// Scalefactor band offsets, ISO/IEC 14496-3 4.5.4, for 1024 sample frames.
+
+const SWB_OFFSETS_LONG_96: [usize; 42] = [
+	0, 4, 8, 12, 16, 20, 24, 28, 32, 36, 40, 44, 48, 52, 56, 64, 72, 80, 88, 96, 108, 120, 132, 144,
+	156, 172, 188, 212, 240, 276, 320, 384, 448, 512, 576, 640, 704, 768, 832, 896, 960, 1024,
+];
+const SWB_OFFSETS_LONG_64: [usize; 48] = [
+	0, 4, 8, 12, 16, 20, 24, 28, 32, 36, 40, 44, 48, 52, 56, 64, 72, 80, 88, 100, 112, 124, 140, 156,
+	172, 192, 216, 240, 268, 304, 344, 384, 424, 464, 504, 544, 584, 624, 664, 704, 744, 784, 824,
+	864, 904, 944, 984, 1024,
+];
+const SWB_OFFSETS_LONG_48: [usize; 50] = [
+	0, 4, 8, 12, 16, 20, 24, 28, 32, 36, 40, 48, 56, 64, 72, 80, 88, 96, 108, 120, 132, 144, 160,
+	176, 196, 216, 240, 264, 292, 320, 352, 384, 416, 448, 480, 512, 544, 576, 608, 640, 672, 704,
+	736, 768, 800, 832, 864, 896, 928, 1024,
+];
+const SWB_OFFSETS_LONG_32: [usize; 52] = [
+	0, 4, 8, 12, 16, 20, 24, 28, 32, 36, 40, 48, 56, 64, 72, 80, 88, 96, 108, 120, 132, 144, 160,
+	176, 196, 216, 240, 264, 292, 320, 352, 384, 416, 448, 480, 512, 544, 576, 608, 640, 672, 704,
+	736, 768, 800, 832, 864, 896, 928, 960, 992, 1024,
+];
+const SWB_OFFSETS_LONG_24: [usize; 48] = [
+	0, 4, 8, 12, 16, 20, 24, 28, 32, 36, 40, 44, 52, 60, 68, 76, 84, 92, 100, 108, 116, 124, 136,
+	148, 160, 172, 188, 204, 220, 240, 260, 284, 308, 336, 364, 396, 432, 468, 508, 552, 600, 652,
+	704, 768, 832, 896, 960, 1024,
+];
+const SWB_OFFSETS_LONG_16: [usize; 44] = [
+	0, 8, 16, 24, 32, 40, 48, 56, 64, 72, 80, 88, 100, 112, 124, 136, 148, 160, 172, 184, 196, 212,
+	228, 244, 260, 280, 300, 320, 344, 368, 396, 424, 456, 492, 532, 572, 616, 664, 716, 772, 832,
+	896, 960, 1024,
+];
+const SWB_OFFSETS_LONG_8: [usize; 41] = [
+	0, 12, 24, 36, 48, 60, 72, 84, 96, 108, 120, 132, 144, 156, 172, 188, 204, 220, 236, 252, 268,
+	288, 308, 328, 348, 372, 396, 420, 448, 476, 508, 544, 580, 620, 664, 712, 764, 820, 880, 944,
+	1024,
+];
+
+const SWB_OFFSETS_SHORT_96: [usize; 13] = [0, 4, 8, 12, 16, 20, 24, 32, 40, 48, 64, 92, 128];
+const SWB_OFFSETS_SHORT_48: [usize; 15] =
+	[0, 4, 8, 12, 16, 20, 28, 36, 44, 56, 68, 80, 96, 112, 128];
+const SWB_OFFSETS_SHORT_24: [usize; 16] =
+	[0, 4, 8, 12, 16, 20, 24, 28, 36, 44, 52, 64, 76, 92, 108, 128];
+const SWB_OFFSETS_SHORT_16: [usize; 16] =
+	[0, 4, 8, 12, 16, 20, 24, 28, 32, 40, 48, 60, 72, 88, 108, 128];
+const SWB_OFFSETS_SHORT_8: [usize; 16] =
+	[0, 4, 8, 12, 16, 20, 24, 28, 36, 44, 52, 60, 72, 88, 108, 128];
+
+/// Long and short window band offsets by sample rate index.
+const SWB_OFFSETS: [(&[usize], &[usize]); 13] = [
+	(&SWB_OFFSETS_LONG_96, &SWB_OFFSETS_SHORT_96),
+	(&SWB_OFFSETS_LONG_96, &SWB_OFFSETS_SHORT_96),
+	(&SWB_OFFSETS_LONG_64, &SWB_OFFSETS_SHORT_96),
+	(&SWB_OFFSETS_LONG_48, &SWB_OFFSETS_SHORT_48),
+	(&SWB_OFFSETS_LONG_48, &SWB_OFFSETS_SHORT_48),
+	(&SWB_OFFSETS_LONG_32, &SWB_OFFSETS_SHORT_48),
+	(&SWB_OFFSETS_LONG_24, &SWB_OFFSETS_SHORT_24),
+	(&SWB_OFFSETS_LONG_24, &SWB_OFFSETS_SHORT_24),
+	(&SWB_OFFSETS_LONG_16, &SWB_OFFSETS_SHORT_16),
+	(&SWB_OFFSETS_LONG_16, &SWB_OFFSETS_SHORT_16),
+	(&SWB_OFFSETS_LONG_16, &SWB_OFFSETS_SHORT_16),
+	(&SWB_OFFSETS_LONG_8, &SWB_OFFSETS_SHORT_8),
+	(&SWB_OFFSETS_LONG_8, &SWB_OFFSETS_SHORT_8),
+];
+
+/// Highest band TNS filters reach, for long and short windows, by sample
+/// rate index (AAC LC).
+const TNS_MAX_BANDS: [(usize, usize); 13] = [
+	(31, 9),
+	(31, 9),
+	(34, 10),
+	(40, 14),
+	(42, 14),
+	(51, 14),
+	(46, 14),
+	(46, 14),
+	(42, 14),
+	(42, 14),
+	(42, 14),
+	(39, 14),
+	(39, 14),
+];
+
+/// Band layout for one sample rate.
+#[derive(Debug, Clone, Copy)]
+pub struct Bands {
+	pub long: &'static [usize],
+	pub short: &'static [usize],
+	pub tns_max_long: usize,
+	pub tns_max_short: usize,
+}
+
+impl Bands {
+	pub fn new(sample_rate_index: u8) -> Self {
+		let index = (sample_rate_index as usize).min(SWB_OFFSETS.len() - 1);
+		let (long, short) = SWB_OFFSETS[index];
+		let (tns_max_long, tns_max_short) = TNS_MAX_BANDS[index];
+		Self { long, short, tns_max_long, tns_max_short }
+	}
+
+	/// Band offsets of one window.
+	pub fn offsets(&self, short: bool) -> &'static [usize] {
+		if short { self.short } else { self.long }
+	}
+
+	/// Number of bands of one window.
+	pub fn count(&self, short: bool) -> usize {
+		self.offsets(short).len() - 1
+	}
+}
@@ -0,0 +1,122 @@
+use super::filterbank::SHORT_LENGTH;
+use super::ics::IcsInfo;
+use super::tables::Bands;
+use crate::io::BitReader;
+use crate::message::Result;
+use std::f32::consts::FRAC_PI_2;
+
+// filter orders AAC LC decoders apply, for long and short windows
+const MAX_ORDER_LONG: usize = 12;
+const MAX_ORDER_SHORT: usize = 7;
+// a filter may carry up to 31 coefficients, the order field allows no more
+const MAX_CODED_ORDER: usize = 31;
+const MAX_FILTERS: usize = 3;
+
+#[derive(Debug, Clone, Copy)]
+struct Filter {
+	/// Bands covered, counted down from the top of the previous filter.
+	length: usize,
+	order: usize,
+	/// Filters run from high to low frequencies when set.
+	downward: bool,
+	/// Prediction coefficients, the first one being 1.
+	lpc: [f32; MAX_ORDER_LONG + 1],
+}
+
+impl Default for Filter {
+	fn default() -> Self {
+		Self { length: 0, order: 0, downward: false, lpc: [0.0; MAX_ORDER_LONG + 1] }
+	}
+}
+
+/// Temporal noise shaping filters of one channel.
+#[derive(Debug, Clone, Default)]
+pub struct Tns {
+	filters: [[Filter; MAX_FILTERS]; 8],
+	counts: [usize; 8],
+}
+
+impl Tns {
+	pub fn parse(&mut self, reader: &mut BitReader, info: &IcsInfo) -> Result<()> {
+		let short = info.is_short();
+		let (count_bits, length_bits, order_bits) = if short { (1, 4, 3) } else { (2, 6, 5) };
+		let max_order = if short { MAX_ORDER_SHORT } else { MAX_ORDER_LONG };
+		for window in 0..info.windows() {
+			let count = reader.read(count_bits)? as usize;
+			self.counts[window] = count;
+			if count == 0 {
+				continue;
+			}
+			let resolution = reader.read(1)? + 3;
+			for filter in self.filters[window][..count].iter_mut() {
+				filter.length = reader.read(length_bits)? as usize;
+				let order = reader.read(order_bits)? as usize;
+				filter.order = order.min(max_order);
+				if order == 0 {
+					continue;
+				}
+				filter.downward = reader.read_bit()?;
+				let bits = resolution - reader.read(1)?;
+				let mut coefficients = [0i32; MAX_CODED_ORDER];
+				for coefficient in coefficients[..order].iter_mut() {
+					let value = reader.read(bits)? as i32;
+					// sign extension of a `bits` wide field
+					*coefficient = (value << (32 - bits)) >> (32 - bits);
+				}
+				filter.lpc = lpc(&coefficients[..filter.order], resolution);
+			}
+		}
+		Ok(())
+	}
+
+	/// Runs the all-pole filters over the spectrum of every window.
+	pub fn apply(&self, info: &IcsInfo, bands: &Bands, spectrum: &mut [f32]) {
+		let short = info.is_short();
+		let offsets = bands.offsets(short);
+		let max_band = if short { bands.tns_max_short } else { bands.tns_max_long }.min(info.max_sfb);
+		let window_length = if short { SHORT_LENGTH } else { spectrum.len() };
+		for window in 0..info.windows() {
+			let spectrum = &mut spectrum[window * window_length..(window + 1) * window_length];
+			let mut bottom = bands.count(short);
+			for filter in &self.filters[window][..self.counts[window]] {
+				let top = bottom;
+				bottom = top.saturating_sub(filter.length);
+				let start = offsets[bottom.min(max_band)];
+				let end = offsets[top.min(max_band)];
+				if filter.order == 0 || end <= start {
+					continue;
+				}
+				let lpc = &filter.lpc[1..=filter.order];
+				let size = end - start;
+				for m in 0..size {
+					let position = |step: usize| if filter.downward { end - 1 - step } else { start + step };
+					let mut value = spectrum[position(m)];
+					for (i, coefficient) in lpc.iter().enumerate().take(m) {
+						value -= coefficient * spectrum[position(m - i - 1)];
+					}
+					spectrum[position(m)] = value;
+				}
+			}
+		}
+	}
+}
+
+/// Turns quantized reflection coefficients into direct form prediction
+/// coefficients.
+fn lpc(coefficients: &[i32], resolution: u32) -> [f32; MAX_ORDER_LONG + 1] {
+	let steps = (1 << (resolution - 1)) as f32;
+	let positive = (steps - 0.5) / FRAC_PI_2;
+	let negative = (steps + 0.5) / FRAC_PI_2;
+	let mut lpc = [0f32; MAX_ORDER_LONG + 1];
+	lpc[0] = 1.0;
+	for (m, &coefficient) in coefficients.iter().enumerate() {
+		let scale = if coefficient >= 0 { positive } else { negative };
+		let reflection = (coefficient as f32 / scale).sin();
+		let previous = lpc;
+		for i in 1..=m {
+			lpc[i] = previous[i] + reflection * previous[m + 1 - i];
+		}
+		lpc[m + 1] = reflection;
+	}
+	lpc
+}
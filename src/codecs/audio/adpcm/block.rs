@@ -0,0 +1,101 @@
+use crate::codecs;
+
+// wav format codes
+pub const FORMAT_MS: u16 = 0x0002;
+pub const FORMAT_IMA: u16 = 0x0011;
+// size of the blocks we write, before trimming to whole samples
+const DEFAULT_BLOCK_SIZE: usize = 1024;
+
+/// The ADPCM flavours found in WAV files. Both code 4 bit samples in blocks
+/// of `block_align` bytes, each starting with a header per channel.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AdpcmKind {
+	Ima,
+	Microsoft,
+}
+
+impl AdpcmKind {
+	pub fn from_format_code(code: u16) -> Option<Self> {
+		match code {
+			FORMAT_IMA => Some(Self::Ima),
+			FORMAT_MS => Some(Self::Microsoft),
+			_ => None,
+		}
+	}
+
+	pub fn from_codec(codec: &str) -> Option<Self> {
+		match codec {
+			codecs::audio::ADPCM_IMA_WAV => Some(Self::Ima),
+			codecs::audio::ADPCM_MS => Some(Self::Microsoft),
+			_ => None,
+		}
+	}
+
+	pub fn format_code(self) -> u16 {
+		match self {
+			Self::Ima => FORMAT_IMA,
+			Self::Microsoft => FORMAT_MS,
+		}
+	}
+
+	pub fn codec(self) -> &'static str {
+		match self {
+			Self::Ima => codecs::audio::ADPCM_IMA_WAV,
+			Self::Microsoft => codecs::audio::ADPCM_MS,
+		}
+	}
+
+	/// Bytes of the block header of one channel.
+	pub fn header_size(self) -> usize {
+		match self {
+			Self::Ima => 4,
+			Self::Microsoft => 7,
+		}
+	}
+
+	/// Samples per channel in a block of `size` bytes, also for the shorter
+	/// last block of a stream.
+	pub fn samples_per_block(self, channels: usize, size: usize) -> usize {
+		let header = self.header_size() * channels;
+		if size < header {
+			return 0;
+		}
+		match self {
+			// the header holds one sample, then 8 samples per 4 bytes of a channel
+			Self::Ima => (size - header) / (4 * channels) * 8 + 1,
+			// the header holds two samples, then one per nibble
+			Self::Microsoft => (size - header) * 2 / channels + 2,
+		}
+	}
+
+	/// Bytes of a block holding `samples` per channel.
+	pub fn block_size(self, channels: usize, samples: usize) -> usize {
+		let header = self.header_size() * channels;
+		match self {
+			Self::Ima => header + samples.saturating_sub(1).div_ceil(8) * 4 * channels,
+			Self::Microsoft => header + (samples.saturating_sub(2) * channels).div_ceil(2),
+		}
+	}
+
+	/// Samples per channel of the blocks we write: as many as fit in 1024
+	/// bytes.
+	pub fn default_samples_per_block(self, channels: usize) -> usize {
+		let samples = self.samples_per_block(channels, DEFAULT_BLOCK_SIZE);
+		match self {
+			Self::Ima => samples,
+			// an odd channel count needs an even sample count to fill whole bytes
+			Self::Microsoft if channels % 2 == 1 => samples - samples % 2,
+			Self::Microsoft => samples,
+		}
+	}
+
+	/// The fewest samples per channel, from `samples` up, that fill whole
+	/// groups of a block.
+	pub fn pad_samples(self, channels: usize, samples: usize) -> usize {
+		match self {
+			Self::Ima => samples.saturating_sub(1).div_ceil(8) * 8 + 1,
+			Self::Microsoft if channels % 2 == 1 => samples.max(2).next_multiple_of(2),
+			Self::Microsoft => samples.max(2),
+		}
+	}
+}
@@ -0,0 +1,71 @@
+use super::block::AdpcmKind;
+use super::{ima, ms};
+use crate::container::wav::WavFormat;
+use crate::core::frame::{AudioFormat, Channels, Frame, FrameAudio};
+use crate::core::packet::Packet;
+use crate::core::traits::Decoder;
+use crate::{error, message::Result};
+
+/// Decodes IMA or Microsoft ADPCM to 16 bit PCM. Packets hold whole blocks,
+/// except that the last one of a stream may be cut short, and a packet
+/// duration trims the padding of the last block.
+pub struct AdpcmDecoder {
+	kind: AdpcmKind,
+	sample_rate: u32,
+	channels: Channels,
+	block_align: usize,
+}
+
+impl AdpcmDecoder {
+	pub fn new(
+		kind: AdpcmKind,
+		sample_rate: u32,
+		channels: Channels,
+		block_align: usize,
+	) -> Result<Self> {
+		let count = channels.count() as usize;
+		if count == 0 || block_align < kind.header_size() * count {
+			return Err(error!("adpcm block align {} is too small for {} channels", block_align, count));
+		}
+		Ok(Self { kind, sample_rate, channels, block_align })
+	}
+
+	pub fn new_from_metadata(metadata: &WavFormat) -> Result<Self> {
+		let kind = AdpcmKind::from_format_code(metadata.format_code)
+			.ok_or_else(|| error!("wav format code {} is not adpcm", metadata.format_code))?;
+		Self::new(kind, metadata.sample_rate, metadata.channels, metadata.block_align() as usize)
+	}
+}
+
+impl Decoder for AdpcmDecoder {
+	fn decode(&mut self, packet: Packet) -> Result<Option<Frame>> {
+		if packet.is_empty() {
+			return Ok(None);
+		}
+
+		let channels = self.channels.count() as usize;
+		let mut samples = Vec::new();
+		for block in packet.data.chunks(self.block_align) {
+			// a trailing scrap without a full header holds no samples
+			if block.len() < self.kind.header_size() * channels {
+				break;
+			}
+			match self.kind {
+				AdpcmKind::Ima => ima::decode_block(block, channels, &mut samples)?,
+				AdpcmKind::Microsoft => ms::decode_block(block, channels, &mut samples)?,
+			}
+		}
+
+		if packet.duration > 0 {
+			samples.truncate(packet.duration as usize * channels);
+		}
+
+		let data = samples.iter().flat_map(|sample| sample.to_le_bytes()).collect();
+		let audio = FrameAudio::new(data, self.sample_rate, self.channels, AudioFormat::PCM16);
+		Ok(Some(Frame::new_audio(audio, packet.stream_id).with_pts(packet.pts)))
+	}
+
+	fn flush(&mut self) -> Result<Option<Frame>> {
+		Ok(None)
+	}
+}
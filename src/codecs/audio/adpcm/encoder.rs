@@ -0,0 +1,121 @@
+use super::block::AdpcmKind;
+use super::ima::{self, ImaState};
+use super::ms::{self, MsState};
+use crate::container::wav::{WavFormat, converter, utils};
+use crate::core::Encoder;
+use crate::core::frame::{Channels, Frame};
+use crate::core::packet::Packet;
+use crate::core::time::Time;
+use crate::{error, message::Result};
+
+enum States {
+	Ima(Vec<ImaState>),
+	Microsoft(Vec<MsState>),
+}
+
+/// Codes PCM as IMA or Microsoft ADPCM. Every packet holds whole blocks;
+/// flushing pads what is left to a last full block, and the packet
+/// duration tells how many of its samples are real.
+pub struct AdpcmEncoder {
+	sample_rate: u32,
+	channels: usize,
+	samples_per_block: usize,
+	states: States,
+	/// Interleaved samples waiting for a full block.
+	pending: Vec<i16>,
+	/// Samples per channel coded so far.
+	position: u64,
+	stream_id: u32,
+}
+
+impl AdpcmEncoder {
+	pub fn new(
+		kind: AdpcmKind,
+		sample_rate: u32,
+		channels: Channels,
+		samples_per_block: usize,
+	) -> Result<Self> {
+		let count = channels.count() as usize;
+		if count == 0 {
+			return Err(error!("adpcm needs at least one channel"));
+		}
+		if kind.pad_samples(count, samples_per_block) != samples_per_block {
+			return Err(error!("{} samples do not fill whole adpcm blocks", samples_per_block));
+		}
+		let states = match kind {
+			AdpcmKind::Ima => States::Ima(vec![ImaState::default(); count]),
+			AdpcmKind::Microsoft => States::Microsoft(vec![MsState::default(); count]),
+		};
+		Ok(Self {
+			sample_rate,
+			channels: count,
+			samples_per_block,
+			states,
+			pending: Vec::new(),
+			position: 0,
+			stream_id: 0,
+		})
+	}
+
+	pub fn new_from_metadata(metadata: &WavFormat) -> Result<Self> {
+		let kind = AdpcmKind::from_format_code(metadata.format_code)
+			.ok_or_else(|| error!("wav format code {} is not adpcm", metadata.format_code))?;
+		let samples_per_block = metadata.samples_per_block as usize;
+		Self::new(kind, metadata.sample_rate, metadata.channels, samples_per_block)
+	}
+
+	fn encode_block(&mut self, samples: &[i16], out: &mut Vec<u8>) {
+		match &mut self.states {
+			States::Ima(states) => ima::encode_block(samples, self.channels, states, out),
+			States::Microsoft(states) => ms::encode_block(samples, self.channels, states, out),
+		}
+	}
+
+	fn packet(&mut self, data: Vec<u8>, samples: usize) -> Packet {
+		let time = Time::new(1, self.sample_rate);
+		let pts = self.position as i64;
+		self.position += samples as u64;
+		let packet = Packet::new(data, self.stream_id, time).with_pts(pts).with_dts(pts);
+		packet.with_duration(samples as i64).with_keyframe(true)
+	}
+}
+
+impl Encoder for AdpcmEncoder {
+	fn encode(&mut self, frame: Frame) -> Result<Option<Packet>> {
+		let audio = match frame.audio() {
+			Some(audio) => audio,
+			None => return Ok(None),
+		};
+		if audio.channels.count() as usize != self.channels {
+			return Err(error!("adpcm encoder expects {} channels", self.channels));
+		}
+		self.stream_id = frame.stream_id;
+		let samples = converter::audio_to_f32(audio)?;
+		self.pending.extend(samples.into_iter().map(utils::denormalize_pcm16));
+
+		let block = self.samples_per_block * self.channels;
+		let blocks = self.pending.len() / block;
+		if blocks == 0 {
+			return Ok(None);
+		}
+		let pending = std::mem::take(&mut self.pending);
+		let mut data = Vec::new();
+		for samples in pending.chunks_exact(block).take(blocks) {
+			self.encode_block(samples, &mut data);
+		}
+		self.pending = pending[blocks * block..].to_vec();
+		Ok(Some(self.packet(data, blocks * self.samples_per_block)))
+	}
+
+	fn flush(&mut self) -> Result<Option<Packet>> {
+		if self.pending.is_empty() {
+			return Ok(None);
+		}
+		let mut samples = std::mem::take(&mut self.pending);
+		let count = samples.len() / self.channels;
+		samples.resize(self.samples_per_block * self.channels, 0);
+		let mut data = Vec::new();
+		self.encode_block(&samples, &mut data);
+		Ok(Some(self.packet(data, count)))
+	}
+}
@@ -0,0 +1,108 @@
+use crate::{error, message::Result};
+
+const STEPS: [i32; 89] = [
+	7, 8, 9, 10, 11, 12, 13, 14, 16, 17, 19, 21, 23, 25, 28, 31, 34, 37, 41, 45, 50, 55, 60, 66, 73,
+	80, 88, 97, 107, 118, 130, 143, 157, 173, 190, 209, 230, 253, 279, 307, 337, 371, 408, 449, 494,
+	544, 598, 658, 724, 796, 876, 963, 1060, 1166, 1282, 1411, 1552, 1707, 1878, 2066, 2272, 2499,
+	2749, 3024, 3327, 3660, 4026, 4428, 4871, 5358, 5894, 6484, 7132, 7845, 8630, 9493, 10442, 11487,
+	12635, 13899, 15289, 16818, 18500, 20350, 22385, 24623, 27086, 29794, 32767,
+];
+
+const INDEX_ADJUST: [i32; 16] = [-1, -1, -1, -1, 2, 4, 6, 8, -1, -1, -1, -1, 2, 4, 6, 8];
+
+const MAX_INDEX: i32 = STEPS.len() as i32 - 1;
+
+/// Predictor of one channel.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ImaState {
+	pub predictor: i32,
+	pub index: i32,
+}
+
+impl ImaState {
+	/// Decodes one nibble and moves the predictor to the sample.
+	pub fn expand(&mut self, nibble: u8) -> i16 {
+		let step = STEPS[self.index as usize];
+		let mut difference = step >> 3;
+		if nibble & 1 != 0 {
+			difference += step >> 2;
+		}
+		if nibble & 2 != 0 {
+			difference += step >> 1;
+		}
+		if nibble & 4 != 0 {
+			difference += step;
+		}
+		if nibble & 8 != 0 {
+			difference = -difference;
+		}
+		self.predictor = (self.predictor + difference).clamp(i16::MIN as i32, i16::MAX as i32);
+		self.index = (self.index + INDEX_ADJUST[nibble as usize]).clamp(0, MAX_INDEX);
+		self.predictor as i16
+	}
+
+	/// The nibble closest to `sample`, applied as the decoder will.
+	pub fn compress(&mut self, sample: i16) -> u8 {
+		let difference = sample as i32 - self.predictor;
+		let step = STEPS[self.index as usize];
+		let magnitude = (difference.abs() * 4 / step).min(7) as u8;
+		let nibble = if difference < 0 { magnitude | 8 } else { magnitude };
+		self.expand(nibble);
+		nibble
+	}
+}
+
+/// Decodes one block, appending `channels` interleaved channels to `out`.
+pub fn decode_block(block: &[u8], channels: usize, out: &mut Vec<i16>) -> Result<()> {
+	if block.len() < 4 * channels {
+		return Err(error!("ima adpcm block of {} bytes is too short", block.len()));
+	}
+	let (header, body) = block.split_at(4 * channels);
+	// a truncated block ends in a partial group of channel words
+	let body = &body[..body.len() - body.len() % (4 * channels)];
+	let samples = 1 + body.len() / (4 * channels) * 8;
+	let start = out.len();
+	out.resize(start + samples * channels, 0);
+
+	let mut states = Vec::with_capacity(channels);
+	for (channel, header) in header.chunks_exact(4).enumerate() {
+		let predictor = i16::from_le_bytes([header[0], header[1]]);
+		if header[2] as i32 > MAX_INDEX {
+			return Err(error!("ima adpcm step index {} is out of range", header[2]));
+		}
+		states.push(ImaState { predictor: predictor as i32, index: header[2] as i32 });
+		out[start + channel] = predictor;
+	}
+
+	// each channel in turn codes 8 samples in 4 bytes, low nibbles first
+	for (word, bytes) in body.chunks_exact(4).enumerate() {
+		let channel = word % channels;
+		let first = 1 + word / channels * 8;
+		for (position, nibble) in bytes.iter().flat_map(|byte| [byte & 0x0F, byte >> 4]).enumerate() {
+			out[start + (first + position) * channels + channel] = states[channel].expand(nibble);
+		}
+	}
+	Ok(())
+}
+
+/// Codes `channels` interleaved channels as one block; the sample count per
+/// channel must be one more than a multiple of 8.
+pub fn encode_block(samples: &[i16], channels: usize, states: &mut [ImaState], out: &mut Vec<u8>) {
+	for (channel, state) in states.iter_mut().enumerate() {
+		state.predictor = samples[channel] as i32;
+		out.extend_from_slice(&samples[channel].to_le_bytes());
+		out.push(state.index as u8);
+		out.push(0);
+	}
+
+	let count = samples.len() / channels;
+	for first in (1..count).step_by(8) {
+		for (channel, state) in states.iter_mut().enumerate() {
+			for pair in 0..4 {
+				let low = state.compress(samples[(first + pair * 2) * channels + channel]);
+				let high = state.compress(samples[(first + pair * 2 + 1) * channels + channel]);
+				out.push(low | (high << 4));
+			}
+		}
+	}
+}
@@ -0,0 +1,9 @@
+pub mod block;
+pub mod decoder;
+pub mod encoder;
+pub mod ima;
+pub mod ms;
+
+pub use block::AdpcmKind;
+pub use decoder::AdpcmDecoder;
+pub use encoder::AdpcmEncoder;
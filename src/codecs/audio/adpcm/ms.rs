@@ -0,0 +1,129 @@
+use crate::{error, message::Result};
+
+const ADAPTATION: [i32; 16] =
+	[230, 230, 230, 230, 307, 409, 512, 614, 768, 614, 512, 409, 307, 230, 230, 230];
+
+// the seven standard predictors, as 8.8 fixed point coefficients
+pub const COEFFICIENTS: [(i32, i32); 7] =
+	[(256, 0), (512, -256), (0, 0), (192, 64), (240, 0), (460, -208), (392, -232)];
+
+const MIN_DELTA: i32 = 16;
+
+/// Predictor of one channel.
+#[derive(Debug, Clone, Copy)]
+pub struct MsState {
+	pub predictor: usize,
+	pub delta: i32,
+	pub sample1: i32,
+	pub sample2: i32,
+}
+
+impl Default for MsState {
+	fn default() -> Self {
+		Self { predictor: 0, delta: MIN_DELTA, sample1: 0, sample2: 0 }
+	}
+}
+
+impl MsState {
+	fn predict(&self) -> i32 {
+		let (coefficient1, coefficient2) = COEFFICIENTS[self.predictor];
+		(self.sample1 * coefficient1 + self.sample2 * coefficient2) / 256
+	}
+
+	/// Decodes one nibble and moves the history to the sample.
+	pub fn expand(&mut self, nibble: u8) -> i16 {
+		let signed = ((nibble << 4) as i8 >> 4) as i32;
+		let sample = (self.predict() + signed * self.delta).clamp(i16::MIN as i32, i16::MAX as i32);
+		self.sample2 = self.sample1;
+		self.sample1 = sample;
+		self.delta = ((ADAPTATION[nibble as usize] * self.delta) >> 8).max(MIN_DELTA);
+		sample as i16
+	}
+
+	/// The nibble closest to `sample`, applied as the decoder will.
+	pub fn compress(&mut self, sample: i16) -> u8 {
+		let error = sample as i32 - self.predict();
+		let rounded = (error + self.delta / 2 * error.signum()) / self.delta;
+		let nibble = rounded.clamp(-8, 7) as u8 & 0x0F;
+		self.expand(nibble);
+		nibble
+	}
+}
+
+/// Decodes one block, appending `channels` interleaved channels to `out`.
+pub fn decode_block(block: &[u8], channels: usize, out: &mut Vec<i16>) -> Result<()> {
+	if block.len() < 7 * channels {
+		return Err(error!("ms adpcm block of {} bytes is too short", block.len()));
+	}
+	let word = |index: usize| i16::from_le_bytes([block[index], block[index + 1]]) as i32;
+	let mut states = Vec::with_capacity(channels);
+	for (channel, &predictor) in block[..channels].iter().enumerate() {
+		let predictor = predictor as usize;
+		if predictor >= COEFFICIENTS.len() {
+			return Err(error!("ms adpcm predictor {} is out of range", predictor));
+		}
+		states.push(MsState {
+			predictor,
+			delta: word(channels + channel * 2),
+			sample1: word(channels * 3 + channel * 2),
+			sample2: word(channels * 5 + channel * 2),
+		});
+	}
+
+	// the header holds the two oldest samples, the older one last
+	out.extend(states.iter().map(|state| state.sample2 as i16));
+	out.extend(states.iter().map(|state| state.sample1 as i16));
+	// nibbles go through the channels in turn, high nibbles first
+	let nibbles = block[7 * channels..].iter().flat_map(|byte| [byte >> 4, byte & 0x0F]);
+	for (position, nibble) in nibbles.enumerate() {
+		out.push(states[position % channels].expand(nibble));
+	}
+	Ok(())
+}
+
+/// Codes `channels` interleaved channels as one block of at least two
+/// samples per channel. Every channel takes the predictor that fits it best,
+/// and keeps its step size from the block before.
+pub fn encode_block(samples: &[i16], channels: usize, states: &mut [MsState], out: &mut Vec<u8>) {
+	let count = samples.len() / channels;
+	for (channel, state) in states.iter_mut().enumerate() {
+		let start = |predictor| MsState {
+			predictor,
+			delta: state.delta.min(i16::MAX as i32),
+			sample1: samples[channels + channel] as i32,
+			sample2: samples[channel] as i32,
+		};
+		let error = |predictor| {
+			let mut trial = start(predictor);
+			(2..count)
+				.map(|position| {
+					let sample = samples[position * channels + channel];
+					trial.compress(sample);
+					(sample as i64 - trial.sample1 as i64).pow(2)
+				})
+				.sum::<i64>()
+		};
+		let predictor = (0..COEFFICIENTS.len()).min_by_key(|&predictor| error(predictor)).unwrap_or(0);
+		*state = start(predictor);
+	}
+
+	out.extend(states.iter().map(|state| state.predictor as u8));
+	for state in states.iter() {
+		out.extend_from_slice(&(state.delta as i16).to_le_bytes());
+	}
+	// the newer of the two first samples goes first
+	for sample in samples[channels..channels * 2].iter().chain(&samples[..channels]) {
+		out.extend_from_slice(&sample.to_le_bytes());
+	}
+
+	let mut nibbles = Vec::with_capacity((count - 2) * channels);
+	for position in 2..count {
+		for (channel, state) in states.iter_mut().enumerate() {
+			nibbles.push(state.compress(samples[position * channels + channel]));
+		}
+	}
+	for pair in nibbles.chunks(2) {
+		let low = pair.get(1).copied().unwrap_or(0);
+		out.push((pair[0] << 4) | low);
+	}
+}
@@ -19,12 +19,24 @@ pub const TTA: &str = "tta";
 pub const APE: &str = "ape";
 
 // pcm / uncompressed
-// pub const PCM_U8: &str = "pcm_u8";
+pub const PCM_U8: &str = "pcm_u8";
+pub const PCM_S8: &str = "pcm_s8";
 pub const PCM_S16LE: &str = "pcm_s16le";
 pub const PCM_S24LE: &str = "pcm_s24le";
 pub const PCM_F32LE: &str = "pcm_f32le";
-// pub const PCM_S32LE: &str = "pcm_s32le";
-// pub const PCM_F64LE: &str = "pcm_f64le";
+pub const PCM_S32LE: &str = "pcm_s32le";
+pub const PCM_F64LE: &str = "pcm_f64le";
+pub const PCM_S16BE: &str = "pcm_s16be";
+pub const PCM_S24BE: &str = "pcm_s24be";
+pub const PCM_F32BE: &str = "pcm_f32be";
+pub const PCM_S32BE: &str = "pcm_s32be";
+pub const PCM_F64BE: &str = "pcm_f64be";
+pub const PCM_MULAW: &str = "pcm_mulaw";
+pub const PCM_ALAW: &str = "pcm_alaw";
+
+// adpcm
+pub const ADPCM_IMA_WAV: &str = "adpcm_ima_wav";
+pub const ADPCM_MS: &str = "adpcm_ms";
 
 // misc / special
 pub const DSD_LSBF: &str = "dsd_lsbf";
@@ -0,0 +1,44 @@
+const CRC8_TABLE: [u8; 256] = crc8_table();
+const CRC16_TABLE: [u16; 256] = crc16_table();
+
+const fn crc8_table() -> [u8; 256] {
+	let mut table = [0u8; 256];
+	let mut i = 0;
+	while i < 256 {
+		let mut crc = i as u8;
+		let mut bit = 0;
+		while bit < 8 {
+			crc = if crc & 0x80 != 0 { (crc << 1) ^ 0x07 } else { crc << 1 };
+			bit += 1;
+		}
+		table[i] = crc;
+		i += 1;
+	}
+	table
+}
+
+const fn crc16_table() -> [u16; 256] {
+	let mut table = [0u16; 256];
+	let mut i = 0;
+	while i < 256 {
+		let mut crc = (i as u16) << 8;
+		let mut bit = 0;
+		while bit < 8 {
+			crc = if crc & 0x8000 != 0 { (crc << 1) ^ 0x8005 } else { crc << 1 };
+			bit += 1;
+		}
+		table[i] = crc;
+		i += 1;
+	}
+	table
+}
+
+/// CRC-8 (poly 0x07) protecting the frame header.
+pub fn crc8(data: &[u8]) -> u8 {
+	data.iter().fold(0u8, |crc, &byte| CRC8_TABLE[(crc ^ byte) as usize])
+}
+
+/// CRC-16 (poly 0x8005) protecting the whole frame.
+pub fn crc16(data: &[u8]) -> u16 {
+	data.iter().fold(0u16, |crc, &byte| (crc << 8) ^ CRC16_TABLE[((crc >> 8) as u8 ^ byte) as usize])
+}
@@ -0,0 +1,352 @@
+use super::crc::crc16;
+use super::header::{ChannelAssignment, FrameHeader};
+use super::md5::Md5;
+use super::streaminfo::{StreamInfo, output_format};
+use crate::core::frame::{AudioFormat, Channels, Frame, FrameAudio};
+use crate::core::packet::Packet;
+use crate::core::traits::Decoder;
+use crate::io::BitReader;
+use crate::{error, message::Result};
+
+pub struct FlacDecoder {
+	info: StreamInfo,
+	md5: Option<Md5>,
+	decoded_samples: u64,
+	channels: Vec<Vec<i32>>,
+}
+
+impl FlacDecoder {
+	pub fn new(info: StreamInfo) -> Self {
+		let md5 = info.has_md5().then(Md5::new);
+		Self { info, md5, decoded_samples: 0, channels: Vec::new() }
+	}
+
+	pub fn new_from_codec_private(codec_private: &[u8]) -> Result<Self> {
+		Ok(Self::new(StreamInfo::parse(codec_private)?))
+	}
+
+	pub fn with_md5_check(mut self, enabled: bool) -> Self {
+		self.md5 = (enabled && self.info.has_md5()).then(Md5::new);
+		self
+	}
+
+	pub fn stream_info(&self) -> &StreamInfo {
+		&self.info
+	}
+
+	pub fn output_format(&self) -> AudioFormat {
+		self.info.audio_format()
+	}
+
+	/// Decodes every frame in `data`, appending interleaved samples to `out`.
+	fn decode_frames(&mut self, data: &[u8], out: &mut Vec<u8>) -> Result<usize> {
+		let mut offset = 0;
+		let mut nb_samples = 0;
+		while offset < data.len() {
+			let (size, block_size) = self.decode_frame(&data[offset..])?;
+			self.write_interleaved(block_size, out);
+			offset += size;
+			nb_samples += block_size;
+		}
+		Ok(nb_samples)
+	}
+
+	fn decode_frame(&mut self, data: &[u8]) -> Result<(usize, usize)> {
+		let header = FrameHeader::parse(data)?;
+		let bits_per_sample = match header.bits_per_sample {
+			0 => self.info.bits_per_sample as u32,
+			bits => bits,
+		};
+		if bits_per_sample != self.info.bits_per_sample as u32 {
+			return Err(error!("flac frame changes bits per sample mid-stream"));
+		}
+		if header.channels.count() != self.info.channels {
+			return Err(error!("flac frame changes channel count mid-stream"));
+		}
+
+		let block_size = header.block_size as usize;
+		let channel_count = header.channels.count() as usize;
+		self.channels.resize_with(channel_count, Vec::new);
+
+		let mut reader = BitReader::new(data);
+		reader.set_position(header.size * 8);
+
+		for (index, samples) in self.channels.iter_mut().enumerate() {
+			let bits = bits_per_sample + header.channels.extra_bits(index);
+			if bits > 32 {
+				return Err(error!("flac 33-bit side channels are not supported"));
+			}
+			samples.clear();
+			samples.resize(block_size, 0);
+			decode_subframe(&mut reader, bits, samples)?;
+		}
+
+		reader.align();
+		let end = reader.byte_position();
+		let expected = reader.read(16)? as u16;
+		if crc16(&data[..end]) != expected {
+			return Err(error!("flac frame crc mismatch"));
+		}
+
+		decorrelate(header.channels, &mut self.channels);
+		self.update_md5(block_size);
+		self.decoded_samples += block_size as u64;
+
+		Ok((end + 2, block_size))
+	}
+
+	fn update_md5(&mut self, block_size: usize) {
+		let Some(md5) = self.md5.as_mut() else {
+			return;
+		};
+		let width = (self.info.bits_per_sample as usize).div_ceil(8);
+		let mut bytes = Vec::with_capacity(block_size * self.channels.len() * width);
+		for i in 0..block_size {
+			for channel in &self.channels {
+				bytes.extend_from_slice(&channel[i].to_le_bytes()[..width]);
+			}
+		}
+		md5.update(&bytes);
+	}
+
+	fn write_interleaved(&self, block_size: usize, out: &mut Vec<u8>) {
+		let bits = self.info.bits_per_sample as u32;
+		match output_format(bits) {
+			AudioFormat::PCM16 => {
+				let shift = 16 - bits;
+				for i in 0..block_size {
+					for channel in &self.channels {
+						out.extend_from_slice(&((channel[i] << shift) as i16).to_le_bytes());
+					}
+				}
+			}
+			AudioFormat::PCM24 => {
+				let shift = 24 - bits;
+				for i in 0..block_size {
+					for channel in &self.channels {
+						out.extend_from_slice(&(channel[i] << shift).to_le_bytes()[..3]);
+					}
+				}
+			}
+			_ => {
+				let scale = 1.0 / (1u64 << (bits - 1)) as f32;
+				for i in 0..block_size {
+					for channel in &self.channels {
+						out.extend_from_slice(&(channel[i] as f32 * scale).to_le_bytes());
+					}
+				}
+			}
+		}
+	}
+
+	fn verify_md5(&mut self) -> Result<()> {
+		let Some(md5) = self.md5.take() else {
+			return Ok(());
+		};
+		// a partial decode (seek, truncated input) can't be checked
+		if self.decoded_samples != self.info.total_samples {
+			return Ok(());
+		}
+		if md5.finalize() != self.info.md5 {
+			return Err(error!("flac md5 signature mismatch, decoded audio is corrupt"));
+		}
+		Ok(())
+	}
+}
+
+impl Decoder for FlacDecoder {
+	fn decode(&mut self, packet: Packet) -> Result<Option<Frame>> {
+		if packet.is_empty() {
+			return Ok(None);
+		}
+
+		let mut data = Vec::new();
+		let nb_samples = self.decode_frames(&packet.data, &mut data)?;
+
+		let channels = Channels::from_count(self.info.channels);
+		let audio = FrameAudio::new(data, self.info.sample_rate, channels, self.output_format());
+		let audio = audio.with_nb_samples(nb_samples);
+
+		Ok(Some(Frame::new_audio(audio, packet.stream_id).with_pts(packet.pts)))
+	}
+
+	fn flush(&mut self) -> Result<Option<Frame>> {
+		self.verify_md5()?;
+		Ok(None)
+	}
+}
+
+fn decode_subframe(reader: &mut BitReader, bits: u32, samples: &mut [i32]) -> Result<()> {
+	if reader.read_bit()? {
+		return Err(error!("flac subframe padding bit set"));
+	}
+	let kind = reader.read(6)?;
+
+	let mut wasted = 0;
+	if reader.read_bit()? {
+		wasted = reader.read_unary()? + 1;
+		if wasted >= bits {
+			return Err(error!("flac wasted bits exceed sample size"));
+		}
+	}
+	let bits = bits - wasted;
+
+	match kind {
+		0 => {
+			let value = reader.read_signed(bits)?;
+			samples.fill(value);
+		}
+		1 => {
+			for sample in samples.iter_mut() {
+				*sample = reader.read_signed(bits)?;
+			}
+		}
+		8..=12 => {
+			let order = (kind - 8) as usize;
+			decode_fixed(reader, bits, order, samples)?;
+		}
+		32..=63 => {
+			let order = (kind - 31) as usize;
+			decode_lpc(reader, bits, order, samples)?;
+		}
+		_ => return Err(error!("reserved flac subframe type {}", kind)),
+	}
+
+	if wasted > 0 {
+		for sample in samples.iter_mut() {
+			*sample <<= wasted;
+		}
+	}
+	Ok(())
+}
+
+fn decode_fixed(
+	reader: &mut BitReader,
+	bits: u32,
+	order: usize,
+	samples: &mut [i32],
+) -> Result<()> {
+	if order > samples.len() {
+		return Err(error!("flac predictor order exceeds block size"));
+	}
+	for sample in samples.iter_mut().take(order) {
+		*sample = reader.read_signed(bits)?;
+	}
+	decode_residual(reader, order, samples)?;
+
+	for i in order..samples.len() {
+		let s = |k: usize| samples[i - k] as i64;
+		let prediction = match order {
+			0 => 0,
+			1 => s(1),
+			2 => 2 * s(1) - s(2),
+			3 => 3 * s(1) - 3 * s(2) + s(3),
+			_ => 4 * s(1) - 6 * s(2) + 4 * s(3) - s(4),
+		};
+		samples[i] = (samples[i] as i64 + prediction) as i32;
+	}
+	Ok(())
+}
+
+fn decode_lpc(reader: &mut BitReader, bits: u32, order: usize, samples: &mut [i32]) -> Result<()> {
+	if order > samples.len() {
+		return Err(error!("flac predictor order exceeds block size"));
+	}
+	for sample in samples.iter_mut().take(order) {
+		*sample = reader.read_signed(bits)?;
+	}
+
+	let precision = reader.read(4)? + 1;
+	if precision == 16 {
+		return Err(error!("invalid flac lpc coefficient precision"));
+	}
+	let shift = reader.read_signed(5)?;
+	if shift < 0 {
+		return Err(error!("negative flac lpc shift"));
+	}
+
+	let mut coefs = [0i64; 32];
+	for coef in coefs.iter_mut().take(order) {
+		*coef = reader.read_signed(precision)? as i64;
+	}
+
+	decode_residual(reader, order, samples)?;
+
+	for i in order..samples.len() {
+		let mut prediction = 0i64;
+		for (j, coef) in coefs[..order].iter().enumerate() {
+			prediction += coef * samples[i - 1 - j] as i64;
+		}
+		samples[i] = (samples[i] as i64 + (prediction >> shift)) as i32;
+	}
+	Ok(())
+}
+
+// Rice coded residual, written in place after the warm-up samples.
+fn decode_residual(reader: &mut BitReader, order: usize, samples: &mut [i32]) -> Result<()> {
+	let (param_bits, escape) = match reader.read(2)? {
+		0 => (4, 15),
+		1 => (5, 31),
+		method => return Err(error!("reserved flac residual coding method {}", method)),
+	};
+
+	let partition_order = reader.read(4)?;
+	let partitions = 1usize << partition_order;
+	let block_size = samples.len();
+	if !block_size.is_multiple_of(partitions) || (block_size >> partition_order) < order {
+		return Err(error!("invalid flac rice partition order {}", partition_order));
+	}
+
+	let mut index = order;
+	for partition in 0..partitions {
+		let mut count = block_size >> partition_order;
+		if partition == 0 {
+			count -= order;
+		}
+
+		let param = reader.read(param_bits)?;
+		if param == escape {
+			let raw_bits = reader.read(5)?;
+			for sample in &mut samples[index..index + count] {
+				*sample = reader.read_signed(raw_bits)?;
+			}
+		} else {
+			for sample in &mut samples[index..index + count] {
+				let quotient = reader.read_unary()?;
+				let value = ((quotient as u64) << param) | reader.read(param)? as u64;
+				*sample = ((value >> 1) as i64 ^ -((value & 1) as i64)) as i32;
+			}
+		}
+		index += count;
+	}
+	Ok(())
+}
+
+fn decorrelate(assignment: ChannelAssignment, channels: &mut [Vec<i32>]) {
+	let (first, second) = match channels {
+		[first, second] => (first, second),
+		_ => return,
+	};
+
+	match assignment {
+		ChannelAssignment::Independent(_) => {}
+		ChannelAssignment::LeftSide => {
+			for (left, side) in first.iter().zip(second.iter_mut()) {
+				*side = left.wrapping_sub(*side);
+			}
+		}
+		ChannelAssignment::SideRight => {
+			for (side, right) in first.iter_mut().zip(second.iter()) {
+				*side = side.wrapping_add(*right);
+			}
+		}
+		ChannelAssignment::MidSide => {
+			for (mid, side) in first.iter_mut().zip(second.iter_mut()) {
+				let m = ((*mid as i64) << 1) | (*side as i64 & 1);
+				let s = *side as i64;
+				*mid = ((m + s) >> 1) as i32;
+				*side = ((m - s) >> 1) as i32;
+			}
+		}
+	}
+}
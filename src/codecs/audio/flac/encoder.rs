@@ -0,0 +1,591 @@
+use std::collections::VecDeque;
+
+use super::crc::crc16;
+use super::header::{ChannelAssignment, FrameHeader};
+use super::lpc;
+use super::md5::Md5;
+use super::streaminfo::StreamInfo;
+use crate::core::Encoder;
+use crate::core::frame::{AudioFormat, Channels, Frame};
+use crate::core::packet::Packet;
+use crate::core::time::Time;
+use crate::io::BitWriter;
+use crate::{error, message::Result};
+
+pub const DEFAULT_COMPRESSION_LEVEL: u8 = 5;
+
+#[derive(Debug, Clone, Copy)]
+pub struct CompressionLevel {
+	pub block_size: u16,
+	pub stereo_decorrelation: bool,
+	pub max_lpc_order: usize,
+	pub max_partition_order: u32,
+	pub exhaustive_order_search: bool,
+}
+
+impl CompressionLevel {
+	// mirrors the presets of the reference encoder (flac -0 .. -8)
+	const PRESETS: [CompressionLevel; 9] = [
+		Self::preset(1152, false, 0, 3, false),
+		Self::preset(1152, true, 0, 3, false),
+		Self::preset(1152, true, 0, 3, false),
+		Self::preset(4096, false, 6, 4, false),
+		Self::preset(4096, true, 8, 4, false),
+		Self::preset(4096, true, 8, 5, false),
+		Self::preset(4096, true, 8, 6, false),
+		Self::preset(4096, true, 12, 6, false),
+		Self::preset(4096, true, 12, 6, true),
+	];
+
+	const fn preset(
+		block_size: u16,
+		stereo_decorrelation: bool,
+		max_lpc_order: usize,
+		max_partition_order: u32,
+		exhaustive_order_search: bool,
+	) -> Self {
+		Self {
+			block_size,
+			stereo_decorrelation,
+			max_lpc_order,
+			max_partition_order,
+			exhaustive_order_search,
+		}
+	}
+
+	pub fn from_level(level: u8) -> Result<Self> {
+		match Self::PRESETS.get(level as usize) {
+			Some(preset) => Ok(*preset),
+			None => Err(error!("flac compression level must be 0..8, found {}", level)),
+		}
+	}
+}
+
+pub struct FlacEncoder {
+	info: StreamInfo,
+	level: CompressionLevel,
+	pending: Vec<Vec<i32>>,
+	queue: VecDeque<Packet>,
+	frame_number: u64,
+	md5: Option<Md5>,
+	window: Vec<f64>,
+	stream_id: u32,
+}
+
+impl FlacEncoder {
+	pub fn new(sample_rate: u32, channels: Channels, bits_per_sample: u8) -> Result<Self> {
+		if !(1..=8).contains(&channels.count()) {
+			return Err(error!("flac supports 1 to 8 channels, found {}", channels.count()));
+		}
+		if !(4..=32).contains(&bits_per_sample) {
+			return Err(error!("flac supports 4 to 32 bits per sample, found {}", bits_per_sample));
+		}
+		if sample_rate == 0 || sample_rate >= 1 << 20 {
+			return Err(error!("invalid flac sample rate {}", sample_rate));
+		}
+
+		let level = CompressionLevel::from_level(DEFAULT_COMPRESSION_LEVEL)?;
+		let info = StreamInfo::new(sample_rate, channels.count(), bits_per_sample, level.block_size);
+		let pending = vec![Vec::new(); channels.count() as usize];
+
+		Ok(Self {
+			info,
+			level,
+			pending,
+			queue: VecDeque::new(),
+			frame_number: 0,
+			md5: Some(Md5::new()),
+			window: Vec::new(),
+			stream_id: 0,
+		})
+	}
+
+	pub fn with_compression_level(mut self, level: u8) -> Result<Self> {
+		self.level = CompressionLevel::from_level(level)?;
+		self.info.min_block_size = self.level.block_size;
+		self.info.max_block_size = self.level.block_size;
+		Ok(self)
+	}
+
+	pub fn with_block_size(mut self, block_size: u16) -> Result<Self> {
+		if block_size < 16 {
+			return Err(error!("flac block size must be at least 16, found {}", block_size));
+		}
+		self.level.block_size = block_size;
+		self.info.min_block_size = block_size;
+		self.info.max_block_size = block_size;
+		Ok(self)
+	}
+
+	/// STREAMINFO for the encoded stream; complete once `flush` has run.
+	pub fn stream_info(&self) -> StreamInfo {
+		self.info
+	}
+
+	fn push_samples(&mut self, data: &[u8], format: AudioFormat) -> Result<()> {
+		let bits = self.info.bits_per_sample as u32;
+		let channels = self.pending.len();
+		let width = match format {
+			AudioFormat::PCM8 => 1,
+			AudioFormat::PCM16 => 2,
+			AudioFormat::PCM24 => 3,
+			AudioFormat::PCM32 | AudioFormat::PCM32I => 4,
+			AudioFormat::PCM64 => 8,
+			other => return Err(error!("flac encoder expects pcm input, found {:?}", other)),
+		};
+
+		let frames = data.len() / (width * channels);
+		let mut md5_bytes = Vec::with_capacity(frames * channels * (bits as usize).div_ceil(8));
+
+		for (index, chunk) in data.chunks_exact(width).take(frames * channels).enumerate() {
+			let sample = match format {
+				AudioFormat::PCM8 => rescale(chunk[0] as i32 - 128, 8, bits),
+				AudioFormat::PCM16 => rescale(i16::from_le_bytes([chunk[0], chunk[1]]) as i32, 16, bits),
+				AudioFormat::PCM24 => {
+					let value = i32::from_le_bytes([0, chunk[0], chunk[1], chunk[2]]) >> 8;
+					rescale(value, 24, bits)
+				}
+				AudioFormat::PCM32I => {
+					rescale(i32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]), 32, bits)
+				}
+				_ => {
+					let value = match chunk.try_into() {
+						Ok(bytes) => f64::from_le_bytes(bytes),
+						Err(_) => f32::from_le_bytes([chunk[0], chunk[1], chunk[2], chunk[3]]) as f64,
+					};
+					let scale = (1u64 << (bits - 1)) as f64;
+					(value * scale).round().clamp(-scale, scale - 1.0) as i32
+				}
+			};
+			md5_bytes.extend_from_slice(&sample.to_le_bytes()[..(bits as usize).div_ceil(8)]);
+			self.pending[index % channels].push(sample);
+		}
+
+		if let Some(md5) = self.md5.as_mut() {
+			md5.update(&md5_bytes);
+		}
+		Ok(())
+	}
+
+	fn encode_pending(&mut self, force: bool) {
+		let block_size = self.level.block_size as usize;
+		loop {
+			let available = self.pending[0].len();
+			if available == 0 || (available < block_size && !force) {
+				return;
+			}
+
+			let count = available.min(block_size);
+			let block: Vec<Vec<i32>> =
+				self.pending.iter_mut().map(|c| c.drain(..count).collect()).collect();
+			let packet = self.encode_block(&block);
+			self.queue.push_back(packet);
+		}
+	}
+
+	fn encode_block(&mut self, block: &[Vec<i32>]) -> Packet {
+		let block_size = block[0].len();
+		let bits = self.info.bits_per_sample as u32;
+
+		if self.window.len() != block_size {
+			self.window = lpc::tukey_window(block_size);
+		}
+
+		let (assignment, subframes) =
+			if block.len() == 2 && self.level.stereo_decorrelation && bits < 32 {
+				self.encode_stereo(&block[0], &block[1], bits)
+			} else {
+				let subframes = block.iter().map(|samples| self.encode_subframe(samples, bits)).collect();
+				(ChannelAssignment::Independent(block.len() as u8), subframes)
+			};
+
+		let header = FrameHeader {
+			variable_block_size: false,
+			block_size: block_size as u32,
+			sample_rate: self.info.sample_rate,
+			channels: assignment,
+			bits_per_sample: bits,
+			number: self.frame_number,
+			size: 0,
+		};
+
+		let mut writer = BitWriter::with_capacity(block_size * block.len() * 2);
+		header.write(&mut writer);
+		for subframe in &subframes {
+			subframe.write(&mut writer);
+		}
+		writer.align();
+		let crc = crc16(writer.bytes());
+		writer.write(crc as u32, 16);
+		let data = writer.finish();
+
+		let size = data.len() as u32;
+		if self.info.min_frame_size == 0 || size < self.info.min_frame_size {
+			self.info.min_frame_size = size;
+		}
+		self.info.max_frame_size = self.info.max_frame_size.max(size);
+
+		let pts = self.info.total_samples as i64;
+		self.info.total_samples += block_size as u64;
+		self.frame_number += 1;
+
+		let time = Time::new(1, self.info.sample_rate);
+		Packet::new(data, self.stream_id, time).with_pts(pts).with_dts(pts).with_keyframe(true)
+	}
+
+	fn encode_stereo(
+		&self,
+		left: &[i32],
+		right: &[i32],
+		bits: u32,
+	) -> (ChannelAssignment, Vec<Subframe>) {
+		let mid: Vec<i32> = left.iter().zip(right).map(|(&l, &r)| (l + r) >> 1).collect();
+		let side: Vec<i32> = left.iter().zip(right).map(|(&l, &r)| l - r).collect();
+
+		let left = self.encode_subframe(left, bits);
+		let right = self.encode_subframe(right, bits);
+		let mid = self.encode_subframe(&mid, bits);
+		let side = self.encode_subframe(&side, bits + 1);
+
+		let candidates = [
+			(ChannelAssignment::Independent(2), left.size + right.size),
+			(ChannelAssignment::LeftSide, left.size + side.size),
+			(ChannelAssignment::SideRight, side.size + right.size),
+			(ChannelAssignment::MidSide, mid.size + side.size),
+		];
+		let (assignment, _) =
+			candidates.into_iter().min_by_key(|(_, size)| *size).unwrap_or(candidates[0]);
+
+		let subframes = match assignment {
+			ChannelAssignment::LeftSide => vec![left, side],
+			ChannelAssignment::SideRight => vec![side, right],
+			ChannelAssignment::MidSide => vec![mid, side],
+			ChannelAssignment::Independent(_) => vec![left, right],
+		};
+		(assignment, subframes)
+	}
+
+	fn encode_subframe(&self, samples: &[i32], bits: u32) -> Subframe {
+		let first = samples[0];
+		if samples.iter().all(|&s| s == first) {
+			return Subframe::constant(first, bits);
+		}
+
+		let wasted = samples.iter().fold(0i32, |acc, &s| acc | s).trailing_zeros().min(bits - 1);
+		let shifted: Vec<i32> = samples.iter().map(|&s| s >> wasted).collect();
+		let bits = bits - wasted;
+
+		let mut best = Subframe::verbatim(&shifted, bits, wasted);
+		let mut residual = Vec::with_capacity(shifted.len());
+
+		for order in 0..=lpc::MAX_FIXED_ORDER.min(shifted.len() - 1) {
+			if !lpc::fixed_residual(&shifted, order, &mut residual) {
+				continue;
+			}
+			let rice = RicePlan::new(&residual, shifted.len(), order, self.level.max_partition_order);
+			let candidate = Subframe::predicted(
+				SubframeKind::Fixed(order),
+				&shifted[..order],
+				&residual,
+				rice,
+				bits,
+				wasted,
+			);
+			if candidate.size < best.size {
+				best = candidate;
+			}
+		}
+
+		let max_order = self.level.max_lpc_order.min(shifted.len().saturating_sub(1));
+		if max_order == 0 {
+			return best;
+		}
+
+		let autoc = lpc::autocorrelation(&shifted, &self.window, max_order);
+		let (coefs, errors) = lpc::levinson(&autoc, max_order);
+		if coefs.is_empty() {
+			return best;
+		}
+
+		let precision = qlp_precision(shifted.len());
+		let orders: Vec<usize> = if self.level.exhaustive_order_search {
+			(1..=coefs.len()).collect()
+		} else {
+			let overhead = (bits + precision) as usize;
+			vec![lpc::estimate_best_order(&errors, shifted.len(), overhead)]
+		};
+
+		for order in orders {
+			let Some((quantized, shift)) = lpc::quantize(&coefs[order - 1], precision) else {
+				continue;
+			};
+			if !lpc::lpc_residual(&shifted, &quantized, shift, &mut residual) {
+				continue;
+			}
+			let rice = RicePlan::new(&residual, shifted.len(), order, self.level.max_partition_order);
+			let kind = SubframeKind::Lpc { coefs: quantized, precision, shift };
+			let candidate = Subframe::predicted(kind, &shifted[..order], &residual, rice, bits, wasted);
+			if candidate.size < best.size {
+				best = candidate;
+			}
+		}
+
+		best
+	}
+}
+
+impl Encoder for FlacEncoder {
+	fn encode(&mut self, frame: Frame) -> Result<Option<Packet>> {
+		let audio = match frame.audio() {
+			Some(audio) => audio,
+			None => return Ok(None),
+		};
+
+		if audio.channels.count() != self.info.channels {
+			return Err(error!(
+				"flac encoder expects {} channels, found {}",
+				self.info.channels,
+				audio.channels.count()
+			));
+		}
+
+		self.stream_id = frame.stream_id;
+		self.push_samples(&audio.data, audio.format)?;
+		self.encode_pending(false);
+		Ok(self.queue.pop_front())
+	}
+
+	fn receive(&mut self) -> Result<Option<Packet>> {
+		Ok(self.queue.pop_front())
+	}
+
+	fn flush(&mut self) -> Result<Option<Packet>> {
+		if let Some(md5) = self.md5.take() {
+			self.encode_pending(true);
+			self.info.md5 = md5.finalize();
+		}
+		Ok(self.queue.pop_front())
+	}
+
+	fn codec_private(&self) -> Option<Vec<u8>> {
+		Some(self.info.to_bytes())
+	}
+}
+
+fn rescale(value: i32, from: u32, to: u32) -> i32 {
+	if to >= from { value << (to - from) } else { value >> (from - to) }
+}
+
+// quantized coefficient precision used by the reference encoder
+fn qlp_precision(block_size: usize) -> u32 {
+	match block_size {
+		0..=192 => 7,
+		193..=384 => 8,
+		385..=576 => 9,
+		577..=1152 => 10,
+		1153..=2304 => 11,
+		2305..=4608 => 12,
+		_ => 13,
+	}
+}
+
+enum SubframeKind {
+	Constant(i32),
+	Verbatim(Vec<i32>),
+	Fixed(usize),
+	Lpc { coefs: Vec<i32>, precision: u32, shift: i32 },
+}
+
+struct Subframe {
+	kind: SubframeKind,
+	bits: u32,
+	wasted: u32,
+	warmup: Vec<i32>,
+	residual: Vec<i32>,
+	rice: Option<RicePlan>,
+	size: usize,
+}
+
+impl Subframe {
+	fn header_size(wasted: u32) -> usize {
+		8 + wasted as usize
+	}
+
+	fn constant(value: i32, bits: u32) -> Self {
+		Self {
+			kind: SubframeKind::Constant(value),
+			bits,
+			wasted: 0,
+			warmup: Vec::new(),
+			residual: Vec::new(),
+			rice: None,
+			size: Self::header_size(0) + bits as usize,
+		}
+	}
+
+	fn verbatim(samples: &[i32], bits: u32, wasted: u32) -> Self {
+		Self {
+			kind: SubframeKind::Verbatim(samples.to_vec()),
+			bits,
+			wasted,
+			warmup: Vec::new(),
+			residual: Vec::new(),
+			rice: None,
+			size: Self::header_size(wasted) + samples.len() * bits as usize,
+		}
+	}
+
+	fn predicted(
+		kind: SubframeKind,
+		warmup: &[i32],
+		residual: &[i32],
+		rice: RicePlan,
+		bits: u32,
+		wasted: u32,
+	) -> Self {
+		let coef_size = match &kind {
+			SubframeKind::Lpc { coefs, precision, .. } => 9 + coefs.len() * *precision as usize,
+			_ => 0,
+		};
+		let size = Self::header_size(wasted) + warmup.len() * bits as usize + coef_size + rice.size;
+		Self {
+			kind,
+			bits,
+			wasted,
+			warmup: warmup.to_vec(),
+			residual: residual.to_vec(),
+			rice: Some(rice),
+			size,
+		}
+	}
+
+	fn write(&self, writer: &mut BitWriter) {
+		let kind = match &self.kind {
+			SubframeKind::Constant(_) => 0,
+			SubframeKind::Verbatim(_) => 1,
+			SubframeKind::Fixed(order) => 8 + *order as u32,
+			SubframeKind::Lpc { coefs, .. } => 31 + coefs.len() as u32,
+		};
+		writer.write(kind, 7);
+
+		if self.wasted > 0 {
+			writer.write_bit(true);
+			writer.write_unary(self.wasted - 1);
+		} else {
+			writer.write_bit(false);
+		}
+
+		match &self.kind {
+			SubframeKind::Constant(value) => writer.write_signed(*value, self.bits),
+			SubframeKind::Verbatim(samples) => {
+				for &sample in samples {
+					writer.write_signed(sample, self.bits);
+				}
+			}
+			SubframeKind::Fixed(_) | SubframeKind::Lpc { .. } => {
+				for &sample in &self.warmup {
+					writer.write_signed(sample, self.bits);
+				}
+				if let SubframeKind::Lpc { coefs, precision, shift } = &self.kind {
+					writer.write(precision - 1, 4);
+					writer.write_signed(*shift, 5);
+					for &coef in coefs {
+						writer.write_signed(coef, *precision);
+					}
+				}
+				if let Some(rice) = &self.rice {
+					rice.write(writer, &self.residual, self.warmup.len());
+				}
+			}
+		}
+	}
+}
+
+struct RicePlan {
+	order: u32,
+	params: Vec<u32>,
+	param_bits: u32,
+	size: usize,
+}
+
+impl RicePlan {
+	const MAX_PARAM: u32 = 30;
+
+	fn new(residual: &[i32], block_size: usize, predictor_order: usize, max_order: u32) -> Self {
+		let mut max_order = max_order;
+		while max_order > 0
+			&& (!block_size.is_multiple_of(1 << max_order)
+				|| (block_size >> max_order) <= predictor_order)
+		{
+			max_order -= 1;
+		}
+
+		// per-partition sums of the zigzag values at the finest partitioning
+		let partitions = 1usize << max_order;
+		let partition_size = block_size >> max_order;
+		let mut sums = vec![0u64; partitions];
+		let mut counts = vec![0usize; partitions];
+		for (index, &value) in residual.iter().enumerate() {
+			let partition = (index + predictor_order) / partition_size;
+			sums[partition] += zigzag(value) as u64;
+			counts[partition] += 1;
+		}
+
+		let mut best: Option<RicePlan> = None;
+		let mut order = max_order;
+		loop {
+			let plan = Self::plan(order, &sums, &counts);
+			if best.as_ref().map(|b| plan.size < b.size).unwrap_or(true) {
+				best = Some(plan);
+			}
+			if order == 0 {
+				break;
+			}
+			sums = sums.chunks(2).map(|pair| pair.iter().sum()).collect();
+			counts = counts.chunks(2).map(|pair| pair.iter().sum()).collect();
+			order -= 1;
+		}
+
+		best.unwrap_or(Self { order: 0, params: vec![0], param_bits: 4, size: 6 + 4 })
+	}
+
+	fn plan(order: u32, sums: &[u64], counts: &[usize]) -> Self {
+		let mut params = Vec::with_capacity(sums.len());
+		let mut size = 0usize;
+		for (&sum, &count) in sums.iter().zip(counts) {
+			let (param, cost) = (0..=Self::MAX_PARAM)
+				.map(|k| (k, count as u64 * (k as u64 + 1) + (sum >> k)))
+				.min_by_key(|(_, cost)| *cost)
+				.unwrap_or((0, 0));
+			params.push(param);
+			size += cost as usize;
+		}
+		let param_bits = if params.iter().all(|&p| p < 15) { 4 } else { 5 };
+		size += 6 + params.len() * param_bits as usize;
+		Self { order, params, param_bits, size }
+	}
+
+	fn write(&self, writer: &mut BitWriter, residual: &[i32], predictor_order: usize) {
+		writer.write(if self.param_bits == 4 { 0 } else { 1 }, 2);
+		writer.write(self.order, 4);
+
+		let block_size = residual.len() + predictor_order;
+		let partition_size = block_size >> self.order;
+		let mut start = 0;
+		for (partition, &param) in self.params.iter().enumerate() {
+			let count = if partition == 0 { partition_size - predictor_order } else { partition_size };
+			writer.write(param, self.param_bits);
+			for &value in &residual[start..start + count] {
+				let value = zigzag(value);
+				writer.write_unary(value >> param);
+				writer.write(value, param);
+			}
+			start += count;
+		}
+	}
+}
+
+#[inline]
+fn zigzag(value: i32) -> u32 {
+	((value << 1) ^ (value >> 31)) as u32
+}
@@ -0,0 +1,258 @@
+use super::crc::crc8;
+use crate::io::{BitReader, BitWriter};
+use crate::{error, message::Result};
+
+pub const SYNC_CODE: u32 = 0x3FFE;
+pub const MAX_HEADER_SIZE: usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChannelAssignment {
+	Independent(u8),
+	LeftSide,
+	SideRight,
+	MidSide,
+}
+
+impl ChannelAssignment {
+	pub fn count(&self) -> u8 {
+		match self {
+			ChannelAssignment::Independent(count) => *count,
+			_ => 2,
+		}
+	}
+
+	/// Side channels carry one extra bit of precision.
+	pub fn extra_bits(&self, channel: usize) -> u32 {
+		match (self, channel) {
+			(ChannelAssignment::LeftSide, 1) => 1,
+			(ChannelAssignment::SideRight, 0) => 1,
+			(ChannelAssignment::MidSide, 1) => 1,
+			_ => 0,
+		}
+	}
+
+	fn code(&self) -> u32 {
+		match self {
+			ChannelAssignment::Independent(count) => *count as u32 - 1,
+			ChannelAssignment::LeftSide => 8,
+			ChannelAssignment::SideRight => 9,
+			ChannelAssignment::MidSide => 10,
+		}
+	}
+
+	fn from_code(code: u32) -> Result<Self> {
+		match code {
+			0..=7 => Ok(ChannelAssignment::Independent(code as u8 + 1)),
+			8 => Ok(ChannelAssignment::LeftSide),
+			9 => Ok(ChannelAssignment::SideRight),
+			10 => Ok(ChannelAssignment::MidSide),
+			_ => Err(error!("reserved flac channel assignment {}", code)),
+		}
+	}
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameHeader {
+	pub variable_block_size: bool,
+	pub block_size: u32,
+	// zero means "take it from STREAMINFO"
+	pub sample_rate: u32,
+	pub channels: ChannelAssignment,
+	// zero means "take it from STREAMINFO"
+	pub bits_per_sample: u32,
+	// frame number for fixed block size streams, sample number otherwise
+	pub number: u64,
+	pub size: usize,
+}
+
+impl FrameHeader {
+	/// Parses a frame header at the start of `data`, checking its CRC-8.
+	pub fn parse(data: &[u8]) -> Result<Self> {
+		let mut reader = BitReader::new(data);
+		if reader.read(14)? != SYNC_CODE {
+			return Err(error!("flac frame sync code not found"));
+		}
+		if reader.read_bit()? {
+			return Err(error!("flac frame reserved bit set"));
+		}
+		let variable_block_size = reader.read_bit()?;
+		let block_size_code = reader.read(4)?;
+		let sample_rate_code = reader.read(4)?;
+		let channels = ChannelAssignment::from_code(reader.read(4)?)?;
+		let bits_per_sample = match reader.read(3)? {
+			0 => 0,
+			1 => 8,
+			2 => 12,
+			4 => 16,
+			5 => 20,
+			6 => 24,
+			7 => 32,
+			code => return Err(error!("reserved flac sample size code {}", code)),
+		};
+		if reader.read_bit()? {
+			return Err(error!("flac frame reserved bit set"));
+		}
+
+		let number = read_utf8_number(&mut reader)?;
+		if !variable_block_size && number >= 1 << 31 {
+			return Err(error!("flac frame number out of range"));
+		}
+
+		let block_size = match block_size_code {
+			0 => return Err(error!("reserved flac block size code")),
+			1 => 192,
+			2..=5 => 576 << (block_size_code - 2),
+			6 => reader.read(8)? + 1,
+			7 => reader.read(16)? + 1,
+			_ => 256 << (block_size_code - 8),
+		};
+
+		let sample_rate = match sample_rate_code {
+			0 => 0,
+			1 => 88200,
+			2 => 176400,
+			3 => 192000,
+			4 => 8000,
+			5 => 16000,
+			6 => 22050,
+			7 => 24000,
+			8 => 32000,
+			9 => 44100,
+			10 => 48000,
+			11 => 96000,
+			12 => reader.read(8)? * 1000,
+			13 => reader.read(16)?,
+			14 => reader.read(16)? * 10,
+			_ => return Err(error!("invalid flac sample rate code")),
+		};
+
+		let size = reader.byte_position();
+		let crc = reader.read(8)? as u8;
+		if crc8(&data[..size]) != crc {
+			return Err(error!("flac frame header crc mismatch"));
+		}
+
+		Ok(Self {
+			variable_block_size,
+			block_size,
+			sample_rate,
+			channels,
+			bits_per_sample,
+			number,
+			size: size + 1,
+		})
+	}
+
+	/// First sample of the frame. Fixed block size streams count frames, so
+	/// the stream's nominal block size is needed to turn that into samples.
+	pub fn first_sample(&self, fixed_block_size: u32) -> u64 {
+		if self.variable_block_size { self.number } else { self.number * fixed_block_size as u64 }
+	}
+
+	pub fn write(&self, writer: &mut BitWriter) {
+		let start = writer.bytes().len();
+		debug_assert!(writer.is_aligned());
+
+		let (block_size_code, block_size_extra) = match self.block_size {
+			192 => (1, None),
+			576 | 1152 | 2304 | 4608 => (2 + (self.block_size / 576).trailing_zeros(), None),
+			256 | 512 | 1024 | 2048 | 4096 | 8192 | 16384 | 32768 => {
+				(8 + (self.block_size / 256).trailing_zeros(), None)
+			}
+			size if size <= 256 => (6, Some((size - 1, 8))),
+			size => (7, Some((size - 1, 16))),
+		};
+
+		let (sample_rate_code, sample_rate_extra) = match self.sample_rate {
+			0 => (0, None),
+			88200 => (1, None),
+			176400 => (2, None),
+			192000 => (3, None),
+			8000 => (4, None),
+			16000 => (5, None),
+			22050 => (6, None),
+			24000 => (7, None),
+			32000 => (8, None),
+			44100 => (9, None),
+			48000 => (10, None),
+			96000 => (11, None),
+			rate if rate % 1000 == 0 && rate / 1000 <= 255 => (12, Some((rate / 1000, 8))),
+			rate if rate <= 0xFFFF => (13, Some((rate, 16))),
+			rate if rate % 10 == 0 && rate / 10 <= 0xFFFF => (14, Some((rate / 10, 16))),
+			_ => (0, None),
+		};
+
+		let sample_size_code = match self.bits_per_sample {
+			8 => 1,
+			12 => 2,
+			16 => 4,
+			20 => 5,
+			24 => 6,
+			32 => 7,
+			_ => 0,
+		};
+
+		writer.write(SYNC_CODE, 14);
+		writer.write(0, 1);
+		writer.write_bit(self.variable_block_size);
+		writer.write(block_size_code, 4);
+		writer.write(sample_rate_code, 4);
+		writer.write(self.channels.code(), 4);
+		writer.write(sample_size_code, 3);
+		writer.write(0, 1);
+		write_utf8_number(writer, self.number);
+
+		if let Some((value, bits)) = block_size_extra {
+			writer.write(value, bits);
+		}
+		if let Some((value, bits)) = sample_rate_extra {
+			writer.write(value, bits);
+		}
+
+		let crc = crc8(&writer.bytes()[start..]);
+		writer.write(crc as u32, 8);
+	}
+}
+
+// "UTF-8" style variable length coding of up to 36 bits.
+fn read_utf8_number(reader: &mut BitReader) -> Result<u64> {
+	let first = reader.read(8)?;
+	let extra = match (first as u8).leading_ones() {
+		0 => return Ok(first as u64),
+		len @ 2..=7 => len - 1,
+		_ => return Err(error!("invalid flac coded number")),
+	};
+
+	let mut value = (first & (0x7F >> (extra + 1))) as u64;
+	for _ in 0..extra {
+		let byte = reader.read(8)?;
+		if byte & 0xC0 != 0x80 {
+			return Err(error!("invalid flac coded number"));
+		}
+		value = (value << 6) | (byte & 0x3F) as u64;
+	}
+	Ok(value)
+}
+
+fn write_utf8_number(writer: &mut BitWriter, value: u64) {
+	if value < 0x80 {
+		writer.write(value as u32, 8);
+		return;
+	}
+
+	let extra = match value {
+		0..=0x7FF => 1,
+		0x800..=0xFFFF => 2,
+		0x10000..=0x1F_FFFF => 3,
+		0x20_0000..=0x3FF_FFFF => 4,
+		0x400_0000..=0x7FFF_FFFF => 5,
+		_ => 6,
+	};
+
+	let prefix = (0xFF00u32 >> (extra + 1)) as u8;
+	let first = prefix as u32 | (value >> (6 * extra)) as u32;
+	writer.write(first & 0xFF, 8);
+	for i in (0..extra).rev() {
+		writer.write(0x80 | ((value >> (6 * i)) & 0x3F) as u32, 8);
+	}
+}
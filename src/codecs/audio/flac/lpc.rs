@@ -0,0 +1,150 @@
+pub const MAX_LPC_ORDER: usize = 32;
+pub const MAX_FIXED_ORDER: usize = 4;
+
+/// Tukey(0.5) window, the default apodization of the reference encoder.
+pub fn tukey_window(len: usize) -> Vec<f64> {
+	let mut window = vec![1.0; len];
+	if len < 3 {
+		return window;
+	}
+	let taper = ((0.5 * len as f64) / 2.0) as usize;
+	let n = (len - 1) as f64;
+	for i in 0..taper {
+		let value = 0.5 * (1.0 - (2.0 * std::f64::consts::PI * i as f64 / (n * 0.5)).cos());
+		window[i] = value;
+		window[len - 1 - i] = value;
+	}
+	window
+}
+
+pub fn autocorrelation(samples: &[i32], window: &[f64], max_lag: usize) -> Vec<f64> {
+	let windowed: Vec<f64> = samples.iter().zip(window).map(|(&s, &w)| s as f64 * w).collect();
+	(0..=max_lag)
+		.map(|lag| {
+			if lag >= windowed.len() {
+				return 0.0;
+			}
+			windowed[lag..].iter().zip(&windowed).map(|(a, b)| a * b).sum()
+		})
+		.collect()
+}
+
+/// Levinson-Durbin recursion. Returns the predictor coefficients for every
+/// order up to `max_order` together with the prediction error of each.
+pub fn levinson(autoc: &[f64], max_order: usize) -> (Vec<Vec<f64>>, Vec<f64>) {
+	let mut coefs = Vec::with_capacity(max_order);
+	let mut errors = Vec::with_capacity(max_order);
+	let mut lpc = vec![0.0; max_order];
+	let mut error = autoc[0];
+
+	for i in 0..max_order {
+		if error <= 0.0 {
+			break;
+		}
+		let mut r = -autoc[i + 1];
+		for j in 0..i {
+			r -= lpc[j] * autoc[i - j];
+		}
+		r /= error;
+
+		lpc[i] = r;
+		for j in 0..i / 2 {
+			let tmp = lpc[j];
+			lpc[j] += r * lpc[i - 1 - j];
+			lpc[i - 1 - j] += r * tmp;
+		}
+		if i % 2 == 1 {
+			lpc[i / 2] += lpc[i / 2] * r;
+		}
+
+		error *= 1.0 - r * r;
+		coefs.push(lpc[..=i].iter().map(|c| -c).collect());
+		errors.push(error);
+	}
+
+	(coefs, errors)
+}
+
+/// Picks the order with the lowest estimated frame size.
+pub fn estimate_best_order(errors: &[f64], block_size: usize, overhead_per_order: usize) -> usize {
+	let scale = 0.5 / block_size as f64;
+	let mut best = 0;
+	let mut best_bits = f64::MAX;
+	for (i, &error) in errors.iter().enumerate() {
+		let order = i + 1;
+		let bits_per_sample = if error > 0.0 { (0.5 * (error * scale).log2()).max(0.0) } else { 0.0 };
+		let bits = bits_per_sample * (block_size - order) as f64 + (order * overhead_per_order) as f64;
+		if bits < best_bits {
+			best_bits = bits;
+			best = order;
+		}
+	}
+	best
+}
+
+/// Quantizes coefficients to `precision` bits. Returns the integer
+/// coefficients and the right shift the decoder applies.
+pub fn quantize(coefs: &[f64], precision: u32) -> Option<(Vec<i32>, i32)> {
+	let cmax = coefs.iter().fold(0.0f64, |acc, c| acc.max(c.abs()));
+	if cmax <= 0.0 || !cmax.is_finite() {
+		return None;
+	}
+
+	let precision = precision - 1;
+	let qmax = (1i32 << precision) - 1;
+	let qmin = -(1i32 << precision);
+
+	let log2cmax = cmax.log2().floor() as i32 + 1;
+	let shift = (precision as i32 - log2cmax).min(15);
+	if shift < 0 {
+		return None;
+	}
+
+	let mut error = 0.0;
+	let mut quantized = Vec::with_capacity(coefs.len());
+	for &coef in coefs {
+		error += coef * (1i64 << shift) as f64;
+		let q = (error.round() as i64).clamp(qmin as i64, qmax as i64) as i32;
+		error -= q as f64;
+		quantized.push(q);
+	}
+	Some((quantized, shift))
+}
+
+/// Residual of the quantized predictor, or `None` when it does not fit 32 bits.
+pub fn lpc_residual(samples: &[i32], coefs: &[i32], shift: i32, residual: &mut Vec<i32>) -> bool {
+	let order = coefs.len();
+	residual.clear();
+	for i in order..samples.len() {
+		let mut prediction = 0i64;
+		for (j, &coef) in coefs.iter().enumerate() {
+			prediction += coef as i64 * samples[i - 1 - j] as i64;
+		}
+		let value = samples[i] as i64 - (prediction >> shift);
+		if value < i32::MIN as i64 || value > i32::MAX as i64 {
+			return false;
+		}
+		residual.push(value as i32);
+	}
+	true
+}
+
+pub fn fixed_residual(samples: &[i32], order: usize, residual: &mut Vec<i32>) -> bool {
+	residual.clear();
+	for i in order..samples.len() {
+		let s = |k: usize| samples[i - k] as i64;
+		let prediction = match order {
+			0 => 0,
+			1 => s(1),
+			2 => 2 * s(1) - s(2),
+			3 => 3 * s(1) - 3 * s(2) + s(3),
+			_ => 4 * s(1) - 6 * s(2) + 4 * s(3) - s(4),
+		};
+		let value = samples[i] as i64 - prediction;
+		if value < i32::MIN as i64 || value > i32::MAX as i64 {
+			return false;
+		}
+		residual.push(value as i32);
+	}
+	true
+}
@@ -0,0 +1,12 @@
+pub mod crc;
+pub mod decoder;
+pub mod encoder;
+pub mod header;
+pub mod lpc;
+pub mod md5;
+pub mod streaminfo;
+
+pub use decoder::FlacDecoder;
+pub use encoder::{CompressionLevel, FlacEncoder};
+pub use header::{ChannelAssignment, FrameHeader};
+pub use streaminfo::StreamInfo;
@@ -0,0 +1,124 @@
+use crate::core::frame::{AudioFormat, Channels};
+use crate::io::{BitReader, BitWriter};
+use crate::{error, message::Result};
+
+pub const STREAMINFO_SIZE: usize = 34;
+
+/// The mandatory STREAMINFO metadata block. Its 34-byte body is also what
+/// FLAC streams carry as `Stream.codec_private`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StreamInfo {
+	pub min_block_size: u16,
+	pub max_block_size: u16,
+	pub min_frame_size: u32,
+	pub max_frame_size: u32,
+	pub sample_rate: u32,
+	pub channels: u8,
+	pub bits_per_sample: u8,
+	pub total_samples: u64,
+	pub md5: [u8; 16],
+}
+
+impl StreamInfo {
+	pub fn new(sample_rate: u32, channels: u8, bits_per_sample: u8, block_size: u16) -> Self {
+		Self {
+			min_block_size: block_size,
+			max_block_size: block_size,
+			min_frame_size: 0,
+			max_frame_size: 0,
+			sample_rate,
+			channels,
+			bits_per_sample,
+			total_samples: 0,
+			md5: [0; 16],
+		}
+	}
+
+	pub fn parse(data: &[u8]) -> Result<Self> {
+		if data.len() < STREAMINFO_SIZE {
+			return Err(error!("flac streaminfo too small ({} bytes)", data.len()));
+		}
+
+		let mut reader = BitReader::new(&data[..STREAMINFO_SIZE]);
+		let min_block_size = reader.read(16)? as u16;
+		let max_block_size = reader.read(16)? as u16;
+		let min_frame_size = reader.read(24)?;
+		let max_frame_size = reader.read(24)?;
+		let sample_rate = reader.read(20)?;
+		let channels = reader.read(3)? as u8 + 1;
+		let bits_per_sample = reader.read(5)? as u8 + 1;
+		let total_samples = reader.read_u64(36)?;
+
+		let mut md5 = [0u8; 16];
+		md5.copy_from_slice(&data[18..STREAMINFO_SIZE]);
+
+		let info = Self {
+			min_block_size,
+			max_block_size,
+			min_frame_size,
+			max_frame_size,
+			sample_rate,
+			channels,
+			bits_per_sample,
+			total_samples,
+			md5,
+		};
+		info.validate()?;
+		Ok(info)
+	}
+
+	pub fn validate(&self) -> Result<()> {
+		if self.sample_rate == 0 {
+			return Err(error!("flac sample rate must be non-zero"));
+		}
+		if self.min_block_size < 16 || self.max_block_size < self.min_block_size {
+			return Err(error!(
+				"invalid flac block sizes {}..{}",
+				self.min_block_size, self.max_block_size
+			));
+		}
+		if self.bits_per_sample < 4 {
+			return Err(error!("flac bits per sample must be at least 4"));
+		}
+		Ok(())
+	}
+
+	pub fn to_bytes(&self) -> Vec<u8> {
+		let mut writer = BitWriter::with_capacity(STREAMINFO_SIZE);
+		writer.write(self.min_block_size as u32, 16);
+		writer.write(self.max_block_size as u32, 16);
+		writer.write(self.min_frame_size, 24);
+		writer.write(self.max_frame_size, 24);
+		writer.write(self.sample_rate, 20);
+		writer.write(self.channels as u32 - 1, 3);
+		writer.write(self.bits_per_sample as u32 - 1, 5);
+		writer.write_u64(self.total_samples, 36);
+		writer.write_bytes(&self.md5);
+		writer.finish()
+	}
+
+	pub fn channel_layout(&self) -> Channels {
+		Channels::from_count(self.channels)
+	}
+
+	pub fn has_md5(&self) -> bool {
+		self.md5.iter().any(|&b| b != 0)
+	}
+
+	pub fn is_fixed_block_size(&self) -> bool {
+		self.min_block_size == self.max_block_size
+	}
+
+	/// PCM format the decoder produces for this stream.
+	pub fn audio_format(&self) -> AudioFormat {
+		output_format(self.bits_per_sample as u32)
+	}
+}
+
+pub fn output_format(bits_per_sample: u32) -> AudioFormat {
+	match bits_per_sample {
+		0..=16 => AudioFormat::PCM16,
+		17..=24 => AudioFormat::PCM24,
+		_ => AudioFormat::PCM32,
+	}
+}
@@ -0,0 +1,43 @@
+use super::law::G711Law;
+use crate::container::wav::WavFormat;
+use crate::core::frame::{AudioFormat, Channels, Frame, FrameAudio};
+use crate::core::packet::Packet;
+use crate::core::traits::Decoder;
+use crate::{error, message::Result};
+
+/// Expands µ-law or A-law bytes to 16 bit PCM.
+pub struct G711Decoder {
+	sample_rate: u32,
+	channels: Channels,
+	table: [i16; 256],
+}
+
+impl G711Decoder {
+	pub fn new(law: G711Law, sample_rate: u32, channels: Channels) -> Self {
+		let table = std::array::from_fn(|value| law.expand(value as u8));
+		Self { sample_rate, channels, table }
+	}
+
+	pub fn new_from_metadata(metadata: &WavFormat) -> Result<Self> {
+		let law = G711Law::from_format_code(metadata.format_code)
+			.ok_or_else(|| error!("wav format code {} is not g711", metadata.format_code))?;
+		Ok(Self::new(law, metadata.sample_rate, metadata.channels))
+	}
+}
+
+impl Decoder for G711Decoder {
+	fn decode(&mut self, packet: Packet) -> Result<Option<Frame>> {
+		if packet.is_empty() {
+			return Ok(None);
+		}
+
+		let data =
+			packet.data.iter().flat_map(|&value| self.table[value as usize].to_le_bytes()).collect();
+		let audio = FrameAudio::new(data, self.sample_rate, self.channels, AudioFormat::PCM16);
+		Ok(Some(Frame::new_audio(audio, packet.stream_id).with_pts(packet.pts)))
+	}
+
+	fn flush(&mut self) -> Result<Option<Frame>> {
+		Ok(None)
+	}
+}
@@ -0,0 +1,41 @@
+use super::law::G711Law;
+use crate::container::wav::{converter, utils};
+use crate::core::Encoder;
+use crate::core::frame::Frame;
+use crate::core::packet::Packet;
+use crate::core::time::Time;
+use crate::message::Result;
+
+/// Compresses PCM of any depth to µ-law or A-law bytes.
+pub struct G711Encoder {
+	law: G711Law,
+	sample_rate: u32,
+}
+
+impl G711Encoder {
+	pub fn new(law: G711Law, sample_rate: u32) -> Self {
+		Self { law, sample_rate }
+	}
+}
+
+impl Encoder for G711Encoder {
+	fn encode(&mut self, frame: Frame) -> Result<Option<Packet>> {
+		let audio = match frame.audio() {
+			Some(audio) => audio,
+			None => return Ok(None),
+		};
+
+		let samples = converter::audio_to_f32(audio)?;
+		let data = samples
+			.into_iter()
+			.map(|sample| self.law.compress(utils::denormalize_pcm16(sample)))
+			.collect();
+		let time = Time::new(1, self.sample_rate);
+		let packet = Packet::new(data, frame.stream_id, time);
+		Ok(Some(packet.with_pts(frame.pts).with_dts(frame.pts)))
+	}
+
+	fn flush(&mut self) -> Result<Option<Packet>> {
+		Ok(None)
+	}
+}
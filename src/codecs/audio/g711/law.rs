@@ -0,0 +1,129 @@
+use crate::codecs;
+use crate::core::frame::AudioFormat;
+
+// wav format codes
+pub const FORMAT_ALAW: u16 = 0x0006;
+pub const FORMAT_MULAW: u16 = 0x0007;
+
+// µ-law works on 14 bit magnitudes, offset by this bias
+const MULAW_BIAS: i32 = 0x84;
+const MULAW_CLIP: i32 = 8159;
+// largest magnitude of each of the eight segments
+const MULAW_SEGMENTS: [i32; 8] = [0x3F, 0x7F, 0xFF, 0x1FF, 0x3FF, 0x7FF, 0xFFF, 0x1FFF];
+const ALAW_SEGMENTS: [i32; 8] = [0x1F, 0x3F, 0x7F, 0xFF, 0x1FF, 0x3FF, 0x7FF, 0xFFF];
+
+/// The two companding laws of G.711, each coding a sample in 8 bits.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum G711Law {
+	MuLaw,
+	ALaw,
+}
+
+impl G711Law {
+	pub fn from_format_code(code: u16) -> Option<Self> {
+		match code {
+			FORMAT_MULAW => Some(Self::MuLaw),
+			FORMAT_ALAW => Some(Self::ALaw),
+			_ => None,
+		}
+	}
+
+	pub fn from_codec(codec: &str) -> Option<Self> {
+		match codec {
+			codecs::audio::PCM_MULAW => Some(Self::MuLaw),
+			codecs::audio::PCM_ALAW => Some(Self::ALaw),
+			_ => None,
+		}
+	}
+
+	pub fn format_code(self) -> u16 {
+		match self {
+			Self::MuLaw => FORMAT_MULAW,
+			Self::ALaw => FORMAT_ALAW,
+		}
+	}
+
+	pub fn codec(self) -> &'static str {
+		match self {
+			Self::MuLaw => codecs::audio::PCM_MULAW,
+			Self::ALaw => codecs::audio::PCM_ALAW,
+		}
+	}
+
+	pub fn audio_format(self) -> AudioFormat {
+		match self {
+			Self::MuLaw => AudioFormat::MULAW,
+			Self::ALaw => AudioFormat::ALAW,
+		}
+	}
+
+	pub fn expand(self, value: u8) -> i16 {
+		match self {
+			Self::MuLaw => mulaw_expand(value),
+			Self::ALaw => alaw_expand(value),
+		}
+	}
+
+	pub fn compress(self, sample: i16) -> u8 {
+		match self {
+			Self::MuLaw => mulaw_compress(sample),
+			Self::ALaw => alaw_compress(sample),
+		}
+	}
+}
+
+fn segment(value: i32, segments: &[i32; 8]) -> usize {
+	segments.iter().position(|&end| value <= end).unwrap_or(segments.len())
+}
+
+fn mulaw_compress(sample: i16) -> u8 {
+	let mut value = sample as i32 >> 2;
+	let mask = if value < 0 {
+		value = -value;
+		0x7F
+	} else {
+		0xFF
+	};
+	value = value.min(MULAW_CLIP) + (MULAW_BIAS >> 2);
+	let segment = segment(value, &MULAW_SEGMENTS);
+	if segment >= MULAW_SEGMENTS.len() {
+		return (0x7F ^ mask) as u8;
+	}
+	let code = ((segment as i32) << 4) | ((value >> (segment + 1)) & 0x0F);
+	(code ^ mask) as u8
+}
+
+fn mulaw_expand(value: u8) -> i16 {
+	let value = !value as i32;
+	let magnitude = ((((value & 0x0F) << 3) + MULAW_BIAS) << ((value & 0x70) >> 4)) - MULAW_BIAS;
+	if value & 0x80 != 0 { -magnitude as i16 } else { magnitude as i16 }
+}
+
+fn alaw_compress(sample: i16) -> u8 {
+	let mut value = sample as i32 >> 3;
+	let mask = if value >= 0 {
+		0xD5
+	} else {
+		value = -value - 1;
+		0x55
+	};
+	let segment = segment(value, &ALAW_SEGMENTS);
+	if segment >= ALAW_SEGMENTS.len() {
+		return (0x7F ^ mask) as u8;
+	}
+	let shift = if segment < 2 { 1 } else { segment };
+	let code = ((segment as i32) << 4) | ((value >> shift) & 0x0F);
+	(code ^ mask) as u8
+}
+
+fn alaw_expand(value: u8) -> i16 {
+	let value = (value ^ 0x55) as i32;
+	let mut magnitude = (value & 0x0F) << 4;
+	let segment = (value & 0x70) >> 4;
+	magnitude = match segment {
+		0 => magnitude + 8,
+		1 => magnitude + 0x108,
+		_ => (magnitude + 0x108) << (segment - 1),
+	};
+	if value & 0x80 != 0 { magnitude as i16 } else { -magnitude as i16 }
+}
@@ -0,0 +1,7 @@
+pub mod decoder;
+pub mod encoder;
+pub mod law;
+
+pub use decoder::G711Decoder;
+pub use encoder::G711Encoder;
+pub use law::G711Law;
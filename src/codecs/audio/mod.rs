@@ -1,3 +1,9 @@
+pub mod aac;
+pub mod adpcm;
 mod constants;
+pub mod flac;
+pub mod g711;
+pub mod mp3;
+pub mod opus;
 pub mod pcm;
 pub use constants::*;
@@ -0,0 +1,190 @@
+use super::header::FrameHeader;
+use super::huffman::Codebooks;
+use super::hybrid::HybridFilter;
+use super::layer3::{self, Bands, ChannelData, GRANULE_SAMPLES, SideInfo};
+use super::synthesis::{SynthesisFilter, SynthesisState};
+use crate::core::frame::{AudioFormat, Channels, Frame, FrameAudio};
+use crate::core::packet::Packet;
+use crate::core::traits::Decoder;
+use crate::io::BitReader;
+use crate::{error, message::Result};
+
+// largest value a big_values pair can hold: 15 plus 13 linbits
+const MAX_QUANTIZED: usize = 15 + (1 << 13);
+// main_data_begin is at most 511 bytes back
+const MAX_RESERVOIR: usize = 511;
+
+struct ChannelState {
+	data: ChannelData,
+	overlap: [[f32; 18]; 32],
+	synthesis: SynthesisState,
+}
+
+impl ChannelState {
+	fn new() -> Self {
+		Self {
+			data: ChannelData::default(),
+			overlap: [[0.0; 18]; 32],
+			synthesis: SynthesisState::new(),
+		}
+	}
+}
+
+pub struct Mp3Decoder {
+	codebooks: Codebooks,
+	hybrid: HybridFilter,
+	synthesis: SynthesisFilter,
+	pow43: Vec<f32>,
+	reservoir: Vec<u8>,
+	channels: [ChannelState; 2],
+}
+
+impl Mp3Decoder {
+	pub fn new() -> Self {
+		let pow43 = (0..=MAX_QUANTIZED).map(|i| (i as f32).powf(4.0 / 3.0)).collect();
+		Self {
+			codebooks: Codebooks::new(),
+			hybrid: HybridFilter::new(),
+			synthesis: SynthesisFilter::new(),
+			pow43,
+			reservoir: Vec::with_capacity(MAX_RESERVOIR * 2),
+			channels: [ChannelState::new(), ChannelState::new()],
+		}
+	}
+
+	/// Decodes one frame at the start of `data`, appending interleaved samples
+	/// to `out`. Returns the frame header.
+	fn decode_frame(&mut self, data: &[u8], out: &mut Vec<i16>) -> Result<FrameHeader> {
+		let header = FrameHeader::parse(data)?;
+		if header.layer != 3 {
+			return Err(error!("mpeg audio layer {} is not supported", header.layer));
+		}
+		if data.len() < header.frame_size {
+			return Err(error!("mp3 frame is truncated"));
+		}
+
+		let side_start = header.side_info_offset();
+		let side_end = side_start + header.side_info_size();
+		if header.frame_size < side_end {
+			return Err(error!("mp3 frame is smaller than its side information"));
+		}
+		let main_data = &data[side_end..header.frame_size];
+
+		let start = out.len();
+		out.resize(start + header.samples_per_frame() * header.channels() as usize, 0);
+
+		// frames pointing into a reservoir we never saw, as after a cut, and
+		// damaged frames decode to silence rather than ending the stream
+		if let Ok(info) = SideInfo::parse(&header, &data[side_start..side_end])
+			&& info.main_data_begin <= self.reservoir.len()
+		{
+			let mut buffer = self.reservoir[self.reservoir.len() - info.main_data_begin..].to_vec();
+			buffer.extend_from_slice(main_data);
+			if self.decode_granules(&header, &info, &buffer, &mut out[start..]).is_err() {
+				out[start..].fill(0);
+			}
+		}
+
+		self.reservoir.extend_from_slice(main_data);
+		if self.reservoir.len() > MAX_RESERVOIR {
+			self.reservoir.drain(..self.reservoir.len() - MAX_RESERVOIR);
+		}
+		Ok(header)
+	}
+
+	fn decode_granules(
+		&mut self,
+		header: &FrameHeader,
+		info: &SideInfo,
+		main_data: &[u8],
+		out: &mut [i16],
+	) -> Result<()> {
+		let channels = header.channels() as usize;
+		let mut reader = BitReader::new(main_data);
+
+		for granule in 0..header.granules() {
+			for channel in 0..channels {
+				let mut gc = info.granules[granule][channel];
+				if !header.is_mpeg1() && header.is_intensity_stereo() && channel == 1 {
+					gc.preflag = false;
+				}
+				let bands = Bands::new(header, &gc);
+				let end = reader.position() + gc.part2_3_length;
+				if end > main_data.len() * 8 {
+					return Err(error!("mp3 granule overruns the main data"));
+				}
+
+				let data = &mut self.channels[channel].data;
+				layer3::read_scalefactors(header, info, granule, channel, &mut reader, data)?;
+				layer3::read_samples(&gc, &bands, &self.codebooks, &self.pow43, &mut reader, end, data)?;
+			}
+
+			if channels == 2 {
+				let gc = &info.granules[granule][0];
+				let bands = Bands::new(header, gc);
+				let [left, right] = &mut self.channels;
+				let right_gc = &info.granules[granule][1];
+				layer3::process_stereo(header, right_gc, &bands, &mut left.data, &mut right.data);
+			}
+
+			for channel in 0..channels {
+				let gc = &info.granules[granule][channel];
+				let bands = Bands::new(header, gc);
+				let state = &mut self.channels[channel];
+				layer3::reorder(header, &bands, &mut state.data.samples);
+				self.hybrid.antialias(gc, &bands, &mut state.data.samples);
+				self.hybrid.synthesize(gc, &bands, &mut state.data.samples, &mut state.overlap);
+
+				let offset = granule * GRANULE_SAMPLES;
+				let mut subbands = [0f32; 32];
+				let mut pcm = [0f32; 32];
+				for slot in 0..18 {
+					for (subband, value) in subbands.iter_mut().enumerate() {
+						*value = state.data.samples[subband * 18 + slot];
+					}
+					self.synthesis.synthesize(&mut state.synthesis, &subbands, &mut pcm);
+					for (i, &sample) in pcm.iter().enumerate() {
+						let index = (offset + slot * 32 + i) * channels + channel;
+						out[index] = (sample * 32768.0).round().clamp(-32768.0, 32767.0) as i16;
+					}
+				}
+			}
+		}
+		Ok(())
+	}
+}
+
+impl Default for Mp3Decoder {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl Decoder for Mp3Decoder {
+	fn decode(&mut self, packet: Packet) -> Result<Option<Frame>> {
+		if packet.is_empty() {
+			return Ok(None);
+		}
+
+		let mut samples = Vec::new();
+		let mut offset = 0;
+		let mut header = None;
+		while offset < packet.data.len() {
+			let frame = self.decode_frame(&packet.data[offset..], &mut samples)?;
+			offset += frame.frame_size;
+			header = Some(frame);
+		}
+		let Some(header) = header else {
+			return Ok(None);
+		};
+
+		let data = samples.iter().flat_map(|s| s.to_le_bytes()).collect();
+		let channels = Channels::from_count(header.channels());
+		let audio = FrameAudio::new(data, header.sample_rate, channels, AudioFormat::PCM16);
+		Ok(Some(Frame::new_audio(audio, packet.stream_id).with_pts(packet.pts)))
+	}
+
+	fn flush(&mut self) -> Result<Option<Frame>> {
+		Ok(None)
+	}
+}
@@ -0,0 +1,190 @@
+use crate::{error, message::Result};
+
+pub const HEADER_SIZE: usize = 4;
+
+const BITRATES_V1: [[u32; 15]; 3] = [
+	[0, 32, 64, 96, 128, 160, 192, 224, 256, 288, 320, 352, 384, 416, 448],
+	[0, 32, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320, 384],
+	[0, 32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320],
+];
+
+const BITRATES_V2: [[u32; 15]; 3] = [
+	[0, 32, 48, 56, 64, 80, 96, 112, 128, 144, 160, 176, 192, 224, 256],
+	[0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160],
+	[0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160],
+];
+
+const SAMPLE_RATES: [u32; 3] = [44100, 48000, 32000];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MpegVersion {
+	Mpeg1,
+	Mpeg2,
+	Mpeg25,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChannelMode {
+	Stereo,
+	JointStereo,
+	DualChannel,
+	Mono,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FrameHeader {
+	pub version: MpegVersion,
+	pub layer: u8,
+	pub has_crc: bool,
+	pub bitrate: u32,
+	pub sample_rate: u32,
+	pub padding: bool,
+	pub mode: ChannelMode,
+	pub mode_extension: u8,
+	pub frame_size: usize,
+}
+
+impl FrameHeader {
+	pub fn parse(data: &[u8]) -> Result<Self> {
+		if data.len() < HEADER_SIZE {
+			return Err(error!("mp3 frame header is truncated"));
+		}
+		if data[0] != 0xFF || data[1] & 0xE0 != 0xE0 {
+			return Err(error!("mp3 frame sync not found"));
+		}
+
+		let version = match (data[1] >> 3) & 0x03 {
+			0 => MpegVersion::Mpeg25,
+			2 => MpegVersion::Mpeg2,
+			3 => MpegVersion::Mpeg1,
+			_ => return Err(error!("reserved mpeg audio version")),
+		};
+		let layer = match (data[1] >> 1) & 0x03 {
+			0 => return Err(error!("reserved mpeg audio layer")),
+			bits => 4 - bits,
+		};
+		let has_crc = data[1] & 0x01 == 0;
+
+		let bitrate_index = (data[2] >> 4) as usize;
+		if bitrate_index == 0 {
+			return Err(error!("free-format mp3 streams are not supported"));
+		}
+		if bitrate_index == 15 {
+			return Err(error!("invalid mp3 bitrate index"));
+		}
+		let bitrates = match version {
+			MpegVersion::Mpeg1 => &BITRATES_V1,
+			_ => &BITRATES_V2,
+		};
+		let bitrate = bitrates[layer as usize - 1][bitrate_index] * 1000;
+
+		let rate_index = ((data[2] >> 2) & 0x03) as usize;
+		if rate_index == 3 {
+			return Err(error!("invalid mp3 sample rate index"));
+		}
+		let sample_rate = match version {
+			MpegVersion::Mpeg1 => SAMPLE_RATES[rate_index],
+			MpegVersion::Mpeg2 => SAMPLE_RATES[rate_index] / 2,
+			MpegVersion::Mpeg25 => SAMPLE_RATES[rate_index] / 4,
+		};
+
+		let padding = data[2] & 0x02 != 0;
+		let mode = match data[3] >> 6 {
+			0 => ChannelMode::Stereo,
+			1 => ChannelMode::JointStereo,
+			2 => ChannelMode::DualChannel,
+			_ => ChannelMode::Mono,
+		};
+		let mode_extension = (data[3] >> 4) & 0x03;
+
+		let mut header = Self {
+			version,
+			layer,
+			has_crc,
+			bitrate,
+			sample_rate,
+			padding,
+			mode,
+			mode_extension,
+			frame_size: 0,
+		};
+		header.frame_size = header.compute_frame_size();
+		Ok(header)
+	}
+
+	fn compute_frame_size(&self) -> usize {
+		let padding = self.padding as usize;
+		match self.layer {
+			1 => (12 * self.bitrate as usize / self.sample_rate as usize + padding) * 4,
+			3 if self.version != MpegVersion::Mpeg1 => {
+				72 * self.bitrate as usize / self.sample_rate as usize + padding
+			}
+			_ => 144 * self.bitrate as usize / self.sample_rate as usize + padding,
+		}
+	}
+
+	pub fn is_mpeg1(&self) -> bool {
+		self.version == MpegVersion::Mpeg1
+	}
+
+	pub fn channels(&self) -> u8 {
+		if self.mode == ChannelMode::Mono { 1 } else { 2 }
+	}
+
+	pub fn samples_per_frame(&self) -> usize {
+		match self.layer {
+			1 => 384,
+			3 if !self.is_mpeg1() => 576,
+			_ => 1152,
+		}
+	}
+
+	pub fn granules(&self) -> usize {
+		if self.is_mpeg1() { 2 } else { 1 }
+	}
+
+	pub fn side_info_size(&self) -> usize {
+		match (self.is_mpeg1(), self.channels()) {
+			(true, 1) => 17,
+			(true, _) => 32,
+			(false, 1) => 9,
+			(false, _) => 17,
+		}
+	}
+
+	/// Offset of the side information, past the header and optional CRC.
+	pub fn side_info_offset(&self) -> usize {
+		HEADER_SIZE + if self.has_crc { 2 } else { 0 }
+	}
+
+	pub fn is_ms_stereo(&self) -> bool {
+		self.mode == ChannelMode::JointStereo && self.mode_extension & 0x02 != 0
+	}
+
+	pub fn is_intensity_stereo(&self) -> bool {
+		self.mode == ChannelMode::JointStereo && self.mode_extension & 0x01 != 0
+	}
+
+	/// Index into the scale factor band tables, 44.1 kHz first.
+	pub fn sample_rate_index(&self) -> usize {
+		let base = match self.version {
+			MpegVersion::Mpeg1 => 0,
+			MpegVersion::Mpeg2 => 3,
+			MpegVersion::Mpeg25 => 6,
+		};
+		let rate = match self.version {
+			MpegVersion::Mpeg1 => self.sample_rate,
+			MpegVersion::Mpeg2 => self.sample_rate * 2,
+			MpegVersion::Mpeg25 => self.sample_rate * 4,
+		};
+		base + SAMPLE_RATES.iter().position(|&r| r == rate).unwrap_or(0)
+	}
+
+	/// Whether `other` can belong to the same stream as this header.
+	pub fn is_compatible(&self, other: &FrameHeader) -> bool {
+		self.version == other.version
+			&& self.layer == other.layer
+			&& self.sample_rate == other.sample_rate
+			&& self.channels() == other.channels()
+	}
+}
@@ -0,0 +1,375 @@
+use crate::io::BitReader;
+use crate::{error, message::Result};
+
+const LEAF: u32 = 0x8000_0000;
+
+/// Binary decoding tree built from a code table.
+pub struct HuffmanTree {
+	nodes: Vec<[u32; 2]>,
+}
+
+impl HuffmanTree {
+	pub fn new(codes: &[u32], lengths: &[u8]) -> Self {
+		let mut nodes = vec![[0u32; 2]];
+		for (value, (&code, &length)) in codes.iter().zip(lengths).enumerate() {
+			let mut node = 0;
+			for bit in (0..length).rev() {
+				let branch = ((code >> bit) & 1) as usize;
+				if bit == 0 {
+					nodes[node][branch] = LEAF | value as u32;
+					break;
+				}
+				if nodes[node][branch] == 0 {
+					nodes.push([0; 2]);
+					nodes[node][branch] = (nodes.len() - 1) as u32;
+				}
+				node = nodes[node][branch] as usize;
+			}
+		}
+		Self { nodes }
+	}
+
+	pub fn decode(&self, reader: &mut BitReader) -> Result<u32> {
+		let mut node = 0;
+		loop {
+			let next = self.nodes[node][reader.read_bit()? as usize];
+			if next & LEAF != 0 {
+				return Ok(next & !LEAF);
+			}
+			if next == 0 {
+				return Err(error!("invalid huffman code"));
+			}
+			node = next as usize;
+		}
+	}
+}
+
+struct PairTable {
+	tree: Option<usize>,
+	size: u32,
+	linbits: u32,
+}
+
+const fn pair(tree: usize, size: u32, linbits: u32) -> PairTable {
+	PairTable { tree: Some(tree), size, linbits }
+}
+
+const NO_TABLE: PairTable = PairTable { tree: None, size: 0, linbits: 0 };
+
+/// Layer III big_values tables by table_select; 4 and 14 are unused.
+const PAIR_TABLES: [PairTable; 32] = [
+	NO_TABLE,
+	pair(0, 2, 0),
+	pair(1, 3, 0),
+	pair(2, 3, 0),
+	NO_TABLE,
+	pair(3, 4, 0),
+	pair(4, 4, 0),
+	pair(5, 6, 0),
+	pair(6, 6, 0),
+	pair(7, 6, 0),
+	pair(8, 8, 0),
+	pair(9, 8, 0),
+	pair(10, 8, 0),
+	pair(11, 16, 0),
+	NO_TABLE,
+	pair(12, 16, 0),
+	pair(13, 16, 1),
+	pair(13, 16, 2),
+	pair(13, 16, 3),
+	pair(13, 16, 4),
+	pair(13, 16, 6),
+	pair(13, 16, 8),
+	pair(13, 16, 10),
+	pair(13, 16, 13),
+	pair(14, 16, 4),
+	pair(14, 16, 5),
+	pair(14, 16, 6),
+	pair(14, 16, 7),
+	pair(14, 16, 8),
+	pair(14, 16, 9),
+	pair(14, 16, 11),
+	pair(14, 16, 13),
+];
+
+pub struct Codebooks {
+	pairs: Vec<HuffmanTree>,
+	quad_a: HuffmanTree,
+}
+
+impl Codebooks {
+	pub fn new() -> Self {
+		let pairs = vec![
+			HuffmanTree::new(&CODES_1, &LENGTHS_1),
+			HuffmanTree::new(&CODES_2, &LENGTHS_2),
+			HuffmanTree::new(&CODES_3, &LENGTHS_3),
+			HuffmanTree::new(&CODES_5, &LENGTHS_5),
+			HuffmanTree::new(&CODES_6, &LENGTHS_6),
+			HuffmanTree::new(&CODES_7, &LENGTHS_7),
+			HuffmanTree::new(&CODES_8, &LENGTHS_8),
+			HuffmanTree::new(&CODES_9, &LENGTHS_9),
+			HuffmanTree::new(&CODES_10, &LENGTHS_10),
+			HuffmanTree::new(&CODES_11, &LENGTHS_11),
+			HuffmanTree::new(&CODES_12, &LENGTHS_12),
+			HuffmanTree::new(&CODES_13, &LENGTHS_13),
+			HuffmanTree::new(&CODES_15, &LENGTHS_15),
+			HuffmanTree::new(&CODES_16, &LENGTHS_16),
+			HuffmanTree::new(&CODES_24, &LENGTHS_24),
+		];
+		Self { pairs, quad_a: HuffmanTree::new(&QUAD_CODES_A, &QUAD_LENGTHS_A) }
+	}
+
+	/// Decodes one signed big_values pair with `table_select`.
+	pub fn decode_pair(&self, table_select: usize, reader: &mut BitReader) -> Result<(i32, i32)> {
+		let table = &PAIR_TABLES[table_select];
+		let Some(tree) = table.tree else {
+			return Ok((0, 0));
+		};
+		let value = self.pairs[tree].decode(reader)?;
+		let x = read_value(reader, value / table.size, table.linbits)?;
+		let y = read_value(reader, value % table.size, table.linbits)?;
+		Ok((x, y))
+	}
+
+	/// Decodes one signed count1 quadruple; table B is a plain inverted nibble.
+	pub fn decode_quad(&self, table_b: bool, reader: &mut BitReader) -> Result<[i32; 4]> {
+		let value = match table_b {
+			true => 15 - reader.read(4)?,
+			false => self.quad_a.decode(reader)?,
+		};
+		let mut quad = [0i32; 4];
+		for (index, sample) in quad.iter_mut().enumerate() {
+			if value & (8 >> index) != 0 {
+				*sample = if reader.read_bit()? { -1 } else { 1 };
+			}
+		}
+		Ok(quad)
+	}
+}
+
+impl Default for Codebooks {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+fn read_value(reader: &mut BitReader, value: u32, linbits: u32) -> Result<i32> {
+	let mut value = value as i32;
+	if linbits > 0 && value == 15 {
+		value += reader.read(linbits)? as i32;
+	}
+	if value != 0 && reader.read_bit()? {
+		value = -value;
+	}
+	Ok(value)
+}
+
+// Layer III Huffman code tables, ISO/IEC 11172-3 Annex B.7.
+
+const CODES_1: [u32; 4] = [1, 1, 1, 0];
+const LENGTHS_1: [u8; 4] = [1, 3, 2, 3];
+
+const CODES_2: [u32; 9] = [1, 2, 1, 3, 1, 1, 3, 2, 0];
+const LENGTHS_2: [u8; 9] = [1, 3, 6, 3, 3, 5, 5, 5, 6];
+
+const CODES_3: [u32; 9] = [3, 2, 1, 1, 1, 1, 3, 2, 0];
+const LENGTHS_3: [u8; 9] = [2, 2, 6, 3, 2, 5, 5, 5, 6];
+
+const CODES_5: [u32; 16] = [1, 2, 6, 5, 3, 1, 4, 4, 7, 5, 7, 1, 6, 1, 1, 0];
+const LENGTHS_5: [u8; 16] = [1, 3, 6, 7, 3, 3, 6, 7, 6, 6, 7, 8, 7, 6, 7, 8];
+
+const CODES_6: [u32; 16] = [7, 3, 5, 1, 6, 2, 3, 2, 5, 4, 4, 1, 3, 3, 2, 0];
+const LENGTHS_6: [u8; 16] = [3, 3, 5, 7, 3, 2, 4, 5, 4, 4, 5, 6, 6, 5, 6, 7];
+
+const CODES_7: [u32; 36] = [
+	1, 2, 0xa, 0x13, 0x10, 0xa, 3, 3, 7, 0xa, 5, 3, 0xb, 4, 0xd, 0x11, 8, 4, 0xc, 0xb, 0x12, 0xf,
+	0xb, 2, 7, 6, 9, 0xe, 3, 1, 6, 4, 5, 3, 2, 0,
+];
+const LENGTHS_7: [u8; 36] = [
+	1, 3, 6, 8, 8, 9, 3, 4, 6, 7, 7, 8, 6, 5, 7, 8, 8, 9, 7, 7, 8, 9, 9, 9, 7, 7, 8, 9, 9, 10, 8, 8,
+	9, 10, 10, 10,
+];
+
+const CODES_8: [u32; 36] = [
+	3, 4, 6, 0x12, 0xc, 5, 5, 1, 2, 0x10, 9, 3, 7, 3, 5, 0xe, 7, 3, 0x13, 0x11, 0xf, 0xd, 0xa, 4,
+	0xd, 5, 8, 0xb, 5, 1, 0xc, 4, 4, 1, 1, 0,
+];
+const LENGTHS_8: [u8; 36] = [
+	2, 3, 6, 8, 8, 9, 3, 2, 4, 8, 8, 8, 6, 4, 6, 8, 8, 9, 8, 8, 8, 9, 9, 10, 8, 7, 8, 9, 10, 10, 9,
+	8, 9, 9, 11, 11,
+];
+
+const CODES_9: [u32; 36] = [
+	7, 5, 9, 0xe, 0xf, 7, 6, 4, 5, 5, 6, 7, 7, 6, 8, 8, 8, 5, 0xf, 6, 9, 0xa, 5, 1, 0xb, 7, 9, 6, 4,
+	1, 0xe, 4, 6, 2, 6, 0,
+];
+const LENGTHS_9: [u8; 36] = [
+	3, 3, 5, 6, 8, 9, 3, 3, 4, 5, 6, 8, 4, 4, 5, 6, 7, 8, 6, 5, 6, 7, 7, 8, 7, 6, 7, 7, 8, 9, 8, 7,
+	8, 8, 9, 9,
+];
+
+const CODES_10: [u32; 64] = [
+	1, 2, 0xa, 0x17, 0x23, 0x1e, 0xc, 0x11, 3, 3, 8, 0xc, 0x12, 0x15, 0xc, 7, 0xb, 9, 0xf, 0x15,
+	0x20, 0x28, 0x13, 6, 0xe, 0xd, 0x16, 0x22, 0x2e, 0x17, 0x12, 7, 0x14, 0x13, 0x21, 0x2f, 0x1b,
+	0x16, 9, 3, 0x1f, 0x16, 0x29, 0x1a, 0x15, 0x14, 5, 3, 0xe, 0xd, 0xa, 0xb, 0x10, 6, 5, 1, 9, 8, 7,
+	8, 4, 4, 2, 0,
+];
+const LENGTHS_10: [u8; 64] = [
+	1, 3, 6, 8, 9, 9, 9, 10, 3, 4, 6, 7, 8, 9, 8, 8, 6, 6, 7, 8, 9, 10, 9, 9, 7, 7, 8, 9, 10, 10, 9,
+	10, 8, 8, 9, 10, 10, 10, 10, 10, 9, 9, 10, 10, 11, 11, 10, 11, 8, 8, 9, 10, 10, 10, 11, 11, 9, 8,
+	9, 10, 10, 11, 11, 11,
+];
+
+const CODES_11: [u32; 64] = [
+	3, 4, 0xa, 0x18, 0x22, 0x21, 0x15, 0xf, 5, 3, 4, 0xa, 0x20, 0x11, 0xb, 0xa, 0xb, 7, 0xd, 0x12,
+	0x1e, 0x1f, 0x14, 5, 0x19, 0xb, 0x13, 0x3b, 0x1b, 0x12, 0xc, 5, 0x23, 0x21, 0x1f, 0x3a, 0x1e,
+	0x10, 7, 5, 0x1c, 0x1a, 0x20, 0x13, 0x11, 0xf, 8, 0xe, 0xe, 0xc, 9, 0xd, 0xe, 9, 4, 1, 0xb, 4, 6,
+	6, 6, 3, 2, 0,
+];
+const LENGTHS_11: [u8; 64] = [
+	2, 3, 5, 7, 8, 9, 8, 9, 3, 3, 4, 6, 8, 8, 7, 8, 5, 5, 6, 7, 8, 9, 8, 8, 7, 6, 7, 9, 8, 10, 8, 9,
+	8, 8, 8, 9, 9, 10, 9, 10, 8, 8, 9, 10, 10, 11, 10, 11, 8, 7, 7, 8, 9, 10, 10, 10, 8, 7, 8, 9, 10,
+	10, 10, 10,
+];
+
+const CODES_12: [u32; 64] = [
+	9, 6, 0x10, 0x21, 0x29, 0x27, 0x26, 0x1a, 7, 5, 6, 9, 0x17, 0x10, 0x1a, 0xb, 0x11, 7, 0xb, 0xe,
+	0x15, 0x1e, 0xa, 7, 0x11, 0xa, 0xf, 0xc, 0x12, 0x1c, 0xe, 5, 0x20, 0xd, 0x16, 0x13, 0x12, 0x10,
+	9, 5, 0x28, 0x11, 0x1f, 0x1d, 0x11, 0xd, 4, 2, 0x1b, 0xc, 0xb, 0xf, 0xa, 7, 4, 1, 0x1b, 0xc, 8,
+	0xc, 6, 3, 1, 0,
+];
+const LENGTHS_12: [u8; 64] = [
+	4, 3, 5, 7, 8, 9, 9, 9, 3, 3, 4, 5, 7, 7, 8, 8, 5, 4, 5, 6, 7, 8, 7, 8, 6, 5, 6, 6, 7, 8, 8, 8,
+	7, 6, 7, 7, 8, 8, 8, 9, 8, 7, 8, 8, 8, 9, 8, 9, 8, 7, 7, 8, 8, 9, 9, 10, 9, 8, 8, 9, 9, 9, 9, 10,
+];
+
+const CODES_13: [u32; 256] = [
+	1, 5, 0xe, 0x15, 0x22, 0x33, 0x2e, 0x47, 0x2a, 0x34, 0x44, 0x34, 0x43, 0x2c, 0x2b, 0x13, 3, 4,
+	0xc, 0x13, 0x1f, 0x1a, 0x2c, 0x21, 0x1f, 0x18, 0x20, 0x18, 0x1f, 0x23, 0x16, 0xe, 0xf, 0xd, 0x17,
+	0x24, 0x3b, 0x31, 0x4d, 0x41, 0x1d, 0x28, 0x1e, 0x28, 0x1b, 0x21, 0x2a, 0x10, 0x16, 0x14, 0x25,
+	0x3d, 0x38, 0x4f, 0x49, 0x40, 0x2b, 0x4c, 0x38, 0x25, 0x1a, 0x1f, 0x19, 0xe, 0x23, 0x10, 0x3c,
+	0x39, 0x61, 0x4b, 0x72, 0x5b, 0x36, 0x49, 0x37, 0x29, 0x30, 0x35, 0x17, 0x18, 0x3a, 0x1b, 0x32,
+	0x60, 0x4c, 0x46, 0x5d, 0x54, 0x4d, 0x3a, 0x4f, 0x1d, 0x4a, 0x31, 0x29, 0x11, 0x2f, 0x2d, 0x4e,
+	0x4a, 0x73, 0x5e, 0x5a, 0x4f, 0x45, 0x53, 0x47, 0x32, 0x3b, 0x26, 0x24, 0xf, 0x48, 0x22, 0x38,
+	0x5f, 0x5c, 0x55, 0x5b, 0x5a, 0x56, 0x49, 0x4d, 0x41, 0x33, 0x2c, 0x2b, 0x2a, 0x2b, 0x14, 0x1e,
+	0x2c, 0x37, 0x4e, 0x48, 0x57, 0x4e, 0x3d, 0x2e, 0x36, 0x25, 0x1e, 0x14, 0x10, 0x35, 0x19, 0x29,
+	0x25, 0x2c, 0x3b, 0x36, 0x51, 0x42, 0x4c, 0x39, 0x36, 0x25, 0x12, 0x27, 0xb, 0x23, 0x21, 0x1f,
+	0x39, 0x2a, 0x52, 0x48, 0x50, 0x2f, 0x3a, 0x37, 0x15, 0x16, 0x1a, 0x26, 0x16, 0x35, 0x19, 0x17,
+	0x26, 0x46, 0x3c, 0x33, 0x24, 0x37, 0x1a, 0x22, 0x17, 0x1b, 0xe, 9, 7, 0x22, 0x20, 0x1c, 0x27,
+	0x31, 0x4b, 0x1e, 0x34, 0x30, 0x28, 0x34, 0x1c, 0x12, 0x11, 9, 5, 0x2d, 0x15, 0x22, 0x40, 0x38,
+	0x32, 0x31, 0x2d, 0x1f, 0x13, 0xc, 0xf, 0xa, 7, 6, 3, 0x30, 0x17, 0x14, 0x27, 0x24, 0x23, 0x35,
+	0x15, 0x10, 0x17, 0xd, 0xa, 6, 1, 4, 2, 0x10, 0xf, 0x11, 0x1b, 0x19, 0x14, 0x1d, 0xb, 0x11, 0xc,
+	0x10, 8, 1, 1, 0, 1,
+];
+const LENGTHS_13: [u8; 256] = [
+	1, 4, 6, 7, 8, 9, 9, 10, 9, 10, 11, 11, 12, 12, 13, 13, 3, 4, 6, 7, 8, 8, 9, 9, 9, 9, 10, 10, 11,
+	12, 12, 12, 6, 6, 7, 8, 9, 9, 10, 10, 9, 10, 10, 11, 11, 12, 13, 13, 7, 7, 8, 9, 9, 10, 10, 10,
+	10, 11, 11, 11, 11, 12, 13, 13, 8, 7, 9, 9, 10, 10, 11, 11, 10, 11, 11, 12, 12, 13, 13, 14, 9, 8,
+	9, 10, 10, 10, 11, 11, 11, 11, 12, 11, 13, 13, 14, 14, 9, 9, 10, 10, 11, 11, 11, 11, 11, 12, 12,
+	12, 13, 13, 14, 14, 10, 9, 10, 11, 11, 11, 12, 12, 12, 12, 13, 13, 13, 14, 16, 16, 9, 8, 9, 10,
+	10, 11, 11, 12, 12, 12, 12, 13, 13, 14, 15, 15, 10, 9, 10, 10, 11, 11, 11, 13, 12, 13, 13, 14,
+	14, 14, 16, 15, 10, 10, 10, 11, 11, 12, 12, 13, 12, 13, 14, 13, 14, 15, 16, 17, 11, 10, 10, 11,
+	12, 12, 12, 12, 13, 13, 13, 14, 15, 15, 15, 16, 11, 11, 11, 12, 12, 13, 12, 13, 14, 14, 15, 15,
+	15, 16, 16, 16, 12, 11, 12, 13, 13, 13, 14, 14, 14, 14, 14, 15, 16, 15, 16, 16, 13, 12, 12, 13,
+	13, 13, 15, 14, 14, 17, 15, 15, 15, 17, 16, 16, 12, 12, 13, 14, 14, 14, 15, 14, 15, 15, 16, 16,
+	19, 18, 19, 16,
+];
+
+const CODES_15: [u32; 256] = [
+	7, 0xc, 0x12, 0x35, 0x2f, 0x4c, 0x7c, 0x6c, 0x59, 0x7b, 0x6c, 0x77, 0x6b, 0x51, 0x7a, 0x3f, 0xd,
+	5, 0x10, 0x1b, 0x2e, 0x24, 0x3d, 0x33, 0x2a, 0x46, 0x34, 0x53, 0x41, 0x29, 0x3b, 0x24, 0x13,
+	0x11, 0xf, 0x18, 0x29, 0x22, 0x3b, 0x30, 0x28, 0x40, 0x32, 0x4e, 0x3e, 0x50, 0x38, 0x21, 0x1d,
+	0x1c, 0x19, 0x2b, 0x27, 0x3f, 0x37, 0x5d, 0x4c, 0x3b, 0x5d, 0x48, 0x36, 0x4b, 0x32, 0x1d, 0x34,
+	0x16, 0x2a, 0x28, 0x43, 0x39, 0x5f, 0x4f, 0x48, 0x39, 0x59, 0x45, 0x31, 0x42, 0x2e, 0x1b, 0x4d,
+	0x25, 0x23, 0x42, 0x3a, 0x34, 0x5b, 0x4a, 0x3e, 0x30, 0x4f, 0x3f, 0x5a, 0x3e, 0x28, 0x26, 0x7d,
+	0x20, 0x3c, 0x38, 0x32, 0x5c, 0x4e, 0x41, 0x37, 0x57, 0x47, 0x33, 0x49, 0x33, 0x46, 0x1e, 0x6d,
+	0x35, 0x31, 0x5e, 0x58, 0x4b, 0x42, 0x7a, 0x5b, 0x49, 0x38, 0x2a, 0x40, 0x2c, 0x15, 0x19, 0x5a,
+	0x2b, 0x29, 0x4d, 0x49, 0x3f, 0x38, 0x5c, 0x4d, 0x42, 0x2f, 0x43, 0x30, 0x35, 0x24, 0x14, 0x47,
+	0x22, 0x43, 0x3c, 0x3a, 0x31, 0x58, 0x4c, 0x43, 0x6a, 0x47, 0x36, 0x26, 0x27, 0x17, 0xf, 0x6d,
+	0x35, 0x33, 0x2f, 0x5a, 0x52, 0x3a, 0x39, 0x30, 0x48, 0x39, 0x29, 0x17, 0x1b, 0x3e, 9, 0x56,
+	0x2a, 0x28, 0x25, 0x46, 0x40, 0x34, 0x2b, 0x46, 0x37, 0x2a, 0x19, 0x1d, 0x12, 0xb, 0xb, 0x76,
+	0x44, 0x1e, 0x37, 0x32, 0x2e, 0x4a, 0x41, 0x31, 0x27, 0x18, 0x10, 0x16, 0xd, 0xe, 7, 0x5b, 0x2c,
+	0x27, 0x26, 0x22, 0x3f, 0x34, 0x2d, 0x1f, 0x34, 0x1c, 0x13, 0xe, 8, 9, 3, 0x7b, 0x3c, 0x3a, 0x35,
+	0x2f, 0x2b, 0x20, 0x16, 0x25, 0x18, 0x11, 0xc, 0xf, 0xa, 2, 1, 0x47, 0x25, 0x22, 0x1e, 0x1c,
+	0x14, 0x11, 0x1a, 0x15, 0x10, 0xa, 6, 8, 6, 2, 0,
+];
+const LENGTHS_15: [u8; 256] = [
+	3, 4, 5, 7, 7, 8, 9, 9, 9, 10, 10, 11, 11, 11, 12, 13, 4, 3, 5, 6, 7, 7, 8, 8, 8, 9, 9, 10, 10,
+	10, 11, 11, 5, 5, 5, 6, 7, 7, 8, 8, 8, 9, 9, 10, 10, 11, 11, 11, 6, 6, 6, 7, 7, 8, 8, 9, 9, 9,
+	10, 10, 10, 11, 11, 11, 7, 6, 7, 7, 8, 8, 9, 9, 9, 9, 10, 10, 10, 11, 11, 11, 8, 7, 7, 8, 8, 8,
+	9, 9, 9, 9, 10, 10, 11, 11, 11, 12, 9, 7, 8, 8, 8, 9, 9, 9, 9, 10, 10, 10, 11, 11, 12, 12, 9, 8,
+	8, 9, 9, 9, 9, 10, 10, 10, 10, 10, 11, 11, 11, 12, 9, 8, 8, 9, 9, 9, 9, 10, 10, 10, 10, 11, 11,
+	12, 12, 12, 9, 8, 9, 9, 9, 9, 10, 10, 10, 11, 11, 11, 11, 12, 12, 12, 10, 9, 9, 9, 10, 10, 10,
+	10, 10, 11, 11, 11, 11, 12, 13, 12, 10, 9, 9, 9, 10, 10, 10, 10, 11, 11, 11, 11, 12, 12, 12, 13,
+	11, 10, 9, 10, 10, 10, 11, 11, 11, 11, 11, 11, 12, 12, 13, 13, 11, 10, 10, 10, 10, 11, 11, 11,
+	11, 12, 12, 12, 12, 12, 13, 13, 12, 11, 11, 11, 11, 11, 11, 11, 12, 12, 12, 12, 13, 13, 12, 13,
+	12, 11, 11, 11, 11, 11, 11, 12, 12, 12, 12, 12, 13, 13, 13, 13,
+];
+
+const CODES_16: [u32; 256] = [
+	1, 5, 0xe, 0x2c, 0x4a, 0x3f, 0x6e, 0x5d, 0xac, 0x95, 0x8a, 0xf2, 0xe1, 0xc3, 0x178, 0x11, 3, 4,
+	0xc, 0x14, 0x23, 0x3e, 0x35, 0x2f, 0x53, 0x4b, 0x44, 0x77, 0xc9, 0x6b, 0xcf, 9, 0xf, 0xd, 0x17,
+	0x26, 0x43, 0x3a, 0x67, 0x5a, 0xa1, 0x48, 0x7f, 0x75, 0x6e, 0xd1, 0xce, 0x10, 0x2d, 0x15, 0x27,
+	0x45, 0x40, 0x72, 0x63, 0x57, 0x9e, 0x8c, 0xfc, 0xd4, 0xc7, 0x183, 0x16d, 0x1a, 0x4b, 0x24, 0x44,
+	0x41, 0x73, 0x65, 0xb3, 0xa4, 0x9b, 0x108, 0xf6, 0xe2, 0x18b, 0x17e, 0x16a, 9, 0x42, 0x1e, 0x3b,
+	0x38, 0x66, 0xb9, 0xad, 0x109, 0x8e, 0xfd, 0xe8, 0x190, 0x184, 0x17a, 0x1bd, 0x10, 0x6f, 0x36,
+	0x34, 0x64, 0xb8, 0xb2, 0xa0, 0x85, 0x101, 0xf4, 0xe4, 0xd9, 0x181, 0x16e, 0x2cb, 0xa, 0x62,
+	0x30, 0x5b, 0x58, 0xa5, 0x9d, 0x94, 0x105, 0xf8, 0x197, 0x18d, 0x174, 0x17c, 0x379, 0x374, 8,
+	0x55, 0x54, 0x51, 0x9f, 0x9c, 0x8f, 0x104, 0xf9, 0x1ab, 0x191, 0x188, 0x17f, 0x2d7, 0x2c9, 0x2c4,
+	7, 0x9a, 0x4c, 0x49, 0x8d, 0x83, 0x100, 0xf5, 0x1aa, 0x196, 0x18a, 0x180, 0x2df, 0x167, 0x2c6,
+	0x160, 0xb, 0x8b, 0x81, 0x43, 0x7d, 0xf7, 0xe9, 0xe5, 0xdb, 0x189, 0x2e7, 0x2e1, 0x2d0, 0x375,
+	0x372, 0x1b7, 4, 0xf3, 0x78, 0x76, 0x73, 0xe3, 0xdf, 0x18c, 0x2ea, 0x2e6, 0x2e0, 0x2d1, 0x2c8,
+	0x2c2, 0xdf, 0x1b4, 6, 0xca, 0xe0, 0xde, 0xda, 0xd8, 0x185, 0x182, 0x17d, 0x16c, 0x378, 0x1bb,
+	0x2c3, 0x1b8, 0x1b5, 0x6c0, 4, 0x2eb, 0xd3, 0xd2, 0xd0, 0x172, 0x17b, 0x2de, 0x2d3, 0x2ca, 0x6c7,
+	0x373, 0x36d, 0x36c, 0xd83, 0x361, 2, 0x179, 0x171, 0x66, 0xbb, 0x2d6, 0x2d2, 0x166, 0x2c7,
+	0x2c5, 0x362, 0x6c6, 0x367, 0xd82, 0x366, 0x1b2, 0, 0xc, 0xa, 7, 0xb, 0xa, 0x11, 0xb, 9, 0xd,
+	0xc, 0xa, 7, 5, 3, 1, 3,
+];
+const LENGTHS_16: [u8; 256] = [
+	1, 4, 6, 8, 9, 9, 10, 10, 11, 11, 11, 12, 12, 12, 13, 9, 3, 4, 6, 7, 8, 9, 9, 9, 10, 10, 10, 11,
+	12, 11, 12, 8, 6, 6, 7, 8, 9, 9, 10, 10, 11, 10, 11, 11, 11, 12, 12, 9, 8, 7, 8, 9, 9, 10, 10,
+	10, 11, 11, 12, 12, 12, 13, 13, 10, 9, 8, 9, 9, 10, 10, 11, 11, 11, 12, 12, 12, 13, 13, 13, 9, 9,
+	8, 9, 9, 10, 11, 11, 12, 11, 12, 12, 13, 13, 13, 14, 10, 10, 9, 9, 10, 11, 11, 11, 11, 12, 12,
+	12, 12, 13, 13, 14, 10, 10, 9, 10, 10, 11, 11, 11, 12, 12, 13, 13, 13, 13, 15, 15, 10, 10, 10,
+	10, 11, 11, 11, 12, 12, 13, 13, 13, 13, 14, 14, 14, 10, 11, 10, 10, 11, 11, 12, 12, 13, 13, 13,
+	13, 14, 13, 14, 13, 11, 11, 11, 10, 11, 12, 12, 12, 12, 13, 14, 14, 14, 15, 15, 14, 10, 12, 11,
+	11, 11, 12, 12, 13, 14, 14, 14, 14, 14, 14, 13, 14, 11, 12, 12, 12, 12, 12, 13, 13, 13, 13, 15,
+	14, 14, 14, 14, 16, 11, 14, 12, 12, 12, 13, 13, 14, 14, 14, 16, 15, 15, 15, 17, 15, 11, 13, 13,
+	11, 12, 14, 14, 13, 14, 14, 15, 16, 15, 17, 15, 14, 11, 9, 8, 8, 9, 9, 10, 10, 10, 11, 11, 11,
+	11, 11, 11, 11, 8,
+];
+
+const CODES_24: [u32; 256] = [
+	0xf, 0xd, 0x2e, 0x50, 0x92, 0x106, 0xf8, 0x1b2, 0x1aa, 0x29d, 0x28d, 0x289, 0x26d, 0x205, 0x408,
+	0x58, 0xe, 0xc, 0x15, 0x26, 0x47, 0x82, 0x7a, 0xd8, 0xd1, 0xc6, 0x147, 0x159, 0x13f, 0x129,
+	0x117, 0x2a, 0x2f, 0x16, 0x29, 0x4a, 0x44, 0x80, 0x78, 0xdd, 0xcf, 0xc2, 0xb6, 0x154, 0x13b,
+	0x127, 0x21d, 0x12, 0x51, 0x27, 0x4b, 0x46, 0x86, 0x7d, 0x74, 0xdc, 0xcc, 0xbe, 0xb2, 0x145,
+	0x137, 0x125, 0x10f, 0x10, 0x93, 0x48, 0x45, 0x87, 0x7f, 0x76, 0x70, 0xd2, 0xc8, 0xbc, 0x160,
+	0x143, 0x132, 0x11d, 0x21c, 0xe, 0x107, 0x42, 0x81, 0x7e, 0x77, 0x72, 0xd6, 0xca, 0xc0, 0xb4,
+	0x155, 0x13d, 0x12d, 0x119, 0x106, 0xc, 0xf9, 0x7b, 0x79, 0x75, 0x71, 0xd7, 0xce, 0xc3, 0xb9,
+	0x15b, 0x14a, 0x134, 0x123, 0x110, 0x208, 0xa, 0x1b3, 0x73, 0x6f, 0x6d, 0xd3, 0xcb, 0xc4, 0xbb,
+	0x161, 0x14c, 0x139, 0x12a, 0x11b, 0x213, 0x17d, 0x11, 0x1ab, 0xd4, 0xd0, 0xcd, 0xc9, 0xc1, 0xba,
+	0xb1, 0xa9, 0x140, 0x12f, 0x11e, 0x10c, 0x202, 0x179, 0x10, 0x14f, 0xc7, 0xc5, 0xbf, 0xbd, 0xb5,
+	0xae, 0x14d, 0x141, 0x131, 0x121, 0x113, 0x209, 0x17b, 0x173, 0xb, 0x29c, 0xb8, 0xb7, 0xb3, 0xaf,
+	0x158, 0x14b, 0x13a, 0x130, 0x122, 0x115, 0x212, 0x17f, 0x175, 0x16e, 0xa, 0x28c, 0x15a, 0xab,
+	0xa8, 0xa4, 0x13e, 0x135, 0x12b, 0x11f, 0x114, 0x107, 0x201, 0x177, 0x170, 0x16a, 6, 0x288,
+	0x142, 0x13c, 0x138, 0x133, 0x12e, 0x124, 0x11c, 0x10d, 0x105, 0x200, 0x178, 0x172, 0x16c, 0x167,
+	4, 0x26c, 0x12c, 0x128, 0x126, 0x120, 0x11a, 0x111, 0x10a, 0x203, 0x17c, 0x176, 0x171, 0x16d,
+	0x169, 0x165, 2, 0x409, 0x118, 0x116, 0x112, 0x10b, 0x108, 0x103, 0x17e, 0x17a, 0x174, 0x16f,
+	0x16b, 0x168, 0x166, 0x164, 0, 0x2b, 0x14, 0x13, 0x11, 0xf, 0xd, 0xb, 9, 7, 6, 4, 7, 5, 3, 1, 3,
+];
+const LENGTHS_24: [u8; 256] = [
+	4, 4, 6, 7, 8, 9, 9, 10, 10, 11, 11, 11, 11, 11, 12, 9, 4, 4, 5, 6, 7, 8, 8, 9, 9, 9, 10, 10, 10,
+	10, 10, 8, 6, 5, 6, 7, 7, 8, 8, 9, 9, 9, 9, 10, 10, 10, 11, 7, 7, 6, 7, 7, 8, 8, 8, 9, 9, 9, 9,
+	10, 10, 10, 10, 7, 8, 7, 7, 8, 8, 8, 8, 9, 9, 9, 10, 10, 10, 10, 11, 7, 9, 7, 8, 8, 8, 8, 9, 9,
+	9, 9, 10, 10, 10, 10, 10, 7, 9, 8, 8, 8, 8, 9, 9, 9, 9, 10, 10, 10, 10, 10, 11, 7, 10, 8, 8, 8,
+	9, 9, 9, 9, 10, 10, 10, 10, 10, 11, 11, 8, 10, 9, 9, 9, 9, 9, 9, 9, 9, 10, 10, 10, 10, 11, 11, 8,
+	10, 9, 9, 9, 9, 9, 9, 10, 10, 10, 10, 10, 11, 11, 11, 8, 11, 9, 9, 9, 9, 10, 10, 10, 10, 10, 10,
+	11, 11, 11, 11, 8, 11, 10, 9, 9, 9, 10, 10, 10, 10, 10, 10, 11, 11, 11, 11, 8, 11, 10, 10, 10,
+	10, 10, 10, 10, 10, 10, 11, 11, 11, 11, 11, 8, 11, 10, 10, 10, 10, 10, 10, 10, 11, 11, 11, 11,
+	11, 11, 11, 8, 12, 10, 10, 10, 10, 10, 10, 11, 11, 11, 11, 11, 11, 11, 11, 8, 8, 7, 7, 7, 7, 7,
+	7, 7, 7, 7, 7, 8, 8, 8, 8, 4,
+];
+
+const QUAD_CODES_A: [u32; 16] = [1, 5, 4, 5, 6, 5, 4, 4, 7, 3, 6, 0, 7, 2, 3, 1];
+const QUAD_LENGTHS_A: [u8; 16] = [1, 4, 4, 5, 4, 6, 5, 6, 4, 5, 5, 6, 5, 6, 6, 6];
@@ -0,0 +1,146 @@
+use super::layer3::{Bands, GRANULE_SAMPLES, GranuleChannel};
+use super::tables::ALIAS_COEFFICIENTS;
+use std::f32::consts::PI;
+
+const SUBBANDS: usize = 32;
+const LINES: usize = 18;
+
+/// Alias reduction, IMDCT and overlap-add turning 576 frequency lines into
+/// 18 time samples for each of the 32 subbands.
+pub struct HybridFilter {
+	alias_cs: [f32; 8],
+	alias_ca: [f32; 8],
+	long_cos: Vec<f32>,
+	short_cos: Vec<f32>,
+	windows: [[f32; 36]; 4],
+	short_window: [f32; 12],
+}
+
+impl HybridFilter {
+	pub fn new() -> Self {
+		let mut alias_cs = [0f32; 8];
+		let mut alias_ca = [0f32; 8];
+		for (i, &c) in ALIAS_COEFFICIENTS.iter().enumerate() {
+			let norm = (1.0 + c * c).sqrt();
+			alias_cs[i] = 1.0 / norm;
+			alias_ca[i] = c / norm;
+		}
+
+		let mut long_cos = vec![0f32; 36 * LINES];
+		for i in 0..36 {
+			for k in 0..LINES {
+				let angle = PI / 72.0 * (2 * i + 19) as f32 * (2 * k + 1) as f32;
+				long_cos[i * LINES + k] = angle.cos();
+			}
+		}
+		let mut short_cos = vec![0f32; 12 * 6];
+		for i in 0..12 {
+			for k in 0..6 {
+				let angle = PI / 24.0 * (2 * i + 7) as f32 * (2 * k + 1) as f32;
+				short_cos[i * 6 + k] = angle.cos();
+			}
+		}
+
+		let long = |i: usize| (PI / 36.0 * (i as f32 + 0.5)).sin();
+		let short = |i: usize| (PI / 12.0 * (i as f32 + 0.5)).sin();
+		let windows = [
+			std::array::from_fn(long),
+			std::array::from_fn(|i| match i {
+				0..18 => long(i),
+				18..24 => 1.0,
+				24..30 => short(i - 18),
+				_ => 0.0,
+			}),
+			[0.0; 36],
+			std::array::from_fn(|i| match i {
+				0..6 => 0.0,
+				6..12 => short(i - 6),
+				12..18 => 1.0,
+				_ => long(i),
+			}),
+		];
+		let short_window = std::array::from_fn(short);
+
+		Self { alias_cs, alias_ca, long_cos, short_cos, windows, short_window }
+	}
+
+	pub fn antialias(&self, gc: &GranuleChannel, bands: &Bands, samples: &mut [f32]) {
+		let subbands = match (gc.is_short(), gc.mixed_block) {
+			(true, false) => return,
+			(true, true) => bands.widths[..bands.long].iter().sum::<usize>() / LINES,
+			(false, _) => SUBBANDS,
+		};
+		for subband in 1..subbands {
+			for i in 0..8 {
+				let low = subband * LINES - 1 - i;
+				let high = subband * LINES + i;
+				let (a, b) = (samples[low], samples[high]);
+				samples[low] = a * self.alias_cs[i] - b * self.alias_ca[i];
+				samples[high] = b * self.alias_cs[i] + a * self.alias_ca[i];
+			}
+		}
+	}
+
+	/// Runs the IMDCT of every subband in place, overlapping with `overlap`
+	/// from the previous granule.
+	pub fn synthesize(
+		&self,
+		gc: &GranuleChannel,
+		bands: &Bands,
+		samples: &mut [f32; GRANULE_SAMPLES],
+		overlap: &mut [[f32; LINES]; SUBBANDS],
+	) {
+		let long_subbands = match (gc.is_short(), gc.mixed_block) {
+			(true, false) => 0,
+			(true, true) => bands.widths[..bands.long].iter().sum::<usize>() / LINES,
+			(false, _) => SUBBANDS,
+		};
+
+		let mut output = [0f32; 36];
+		for (subband, previous) in overlap.iter_mut().enumerate() {
+			let lines = &mut samples[subband * LINES..(subband + 1) * LINES];
+			if subband < long_subbands {
+				let block_type = if gc.is_short() { 0 } else { gc.block_type as usize };
+				self.imdct_long(lines, &self.windows[block_type], &mut output);
+			} else {
+				self.imdct_short(lines, &mut output);
+			}
+
+			for i in 0..LINES {
+				lines[i] = output[i] + previous[i];
+				previous[i] = output[i + LINES];
+			}
+			if subband % 2 == 1 {
+				for sample in lines.iter_mut().skip(1).step_by(2) {
+					*sample = -*sample;
+				}
+			}
+		}
+	}
+
+	fn imdct_long(&self, input: &[f32], window: &[f32; 36], output: &mut [f32; 36]) {
+		for (i, out) in output.iter_mut().enumerate() {
+			let row = &self.long_cos[i * LINES..(i + 1) * LINES];
+			let sum: f32 = input.iter().zip(row).map(|(x, c)| x * c).sum();
+			*out = sum * window[i];
+		}
+	}
+
+	fn imdct_short(&self, input: &[f32], output: &mut [f32; 36]) {
+		output.fill(0.0);
+		for window in 0..3 {
+			let lines = &input[window * 6..(window + 1) * 6];
+			for i in 0..12 {
+				let row = &self.short_cos[i * 6..(i + 1) * 6];
+				let sum: f32 = lines.iter().zip(row).map(|(x, c)| x * c).sum();
+				output[6 + window * 6 + i] += sum * self.short_window[i];
+			}
+		}
+	}
+}
+
+impl Default for HybridFilter {
+	fn default() -> Self {
+		Self::new()
+	}
+}
@@ -0,0 +1,454 @@
+use super::header::FrameHeader;
+use super::huffman::Codebooks;
+use super::tables::{MPEG1_PARTITIONS, MPEG2_PARTITIONS, PRETAB, SFB_LONG, SFB_SHORT, SLEN};
+use crate::io::BitReader;
+use crate::{error, message::Result};
+
+pub const GRANULE_SAMPLES: usize = 576;
+pub const MAX_BANDS: usize = 39;
+
+const SHORT_BLOCK: u8 = 2;
+const MPEG1_ILLEGAL_POSITION: u8 = 7;
+const MPEG2_ILLEGAL_POSITION: u8 = 255;
+
+/// Intensity stereo left/right gains for MPEG-1 positions 0..6.
+const INTENSITY_PAN: [(f32, f32); 7] = [
+	(0.0, 1.0),
+	(0.211_324_9, 0.788_675_1),
+	(0.366_025_4, 0.633_974_6),
+	(0.5, 0.5),
+	(0.633_974_6, 0.366_025_4),
+	(0.788_675_1, 0.211_324_9),
+	(1.0, 0.0),
+];
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct GranuleChannel {
+	pub part2_3_length: usize,
+	pub big_values: usize,
+	pub global_gain: i32,
+	pub scalefac_compress: u32,
+	pub block_type: u8,
+	pub mixed_block: bool,
+	pub table_select: [usize; 3],
+	pub subblock_gain: [i32; 3],
+	pub region0_count: usize,
+	pub region1_count: usize,
+	pub preflag: bool,
+	pub scalefac_scale: bool,
+	pub count1_table_b: bool,
+}
+
+impl GranuleChannel {
+	pub fn is_short(&self) -> bool {
+		self.block_type == SHORT_BLOCK
+	}
+}
+
+#[derive(Debug, Default)]
+pub struct SideInfo {
+	pub main_data_begin: usize,
+	pub scfsi: [[bool; 4]; 2],
+	pub granules: [[GranuleChannel; 2]; 2],
+}
+
+impl SideInfo {
+	pub fn parse(header: &FrameHeader, data: &[u8]) -> Result<Self> {
+		let mut reader = BitReader::new(data);
+		let channels = header.channels() as usize;
+		let mut info = SideInfo::default();
+
+		if header.is_mpeg1() {
+			info.main_data_begin = reader.read(9)? as usize;
+			reader.skip(if channels == 1 { 5 } else { 3 })?;
+			for scfsi in info.scfsi.iter_mut().take(channels) {
+				for band in scfsi.iter_mut() {
+					*band = reader.read_bit()?;
+				}
+			}
+		} else {
+			info.main_data_begin = reader.read(8)? as usize;
+			reader.skip(channels)?;
+		}
+
+		for granule in info.granules.iter_mut().take(header.granules()) {
+			for channel in granule.iter_mut().take(channels) {
+				*channel = read_granule_channel(header, &mut reader)?;
+			}
+		}
+		Ok(info)
+	}
+}
+
+fn read_granule_channel(header: &FrameHeader, reader: &mut BitReader) -> Result<GranuleChannel> {
+	let mut gc = GranuleChannel {
+		part2_3_length: reader.read(12)? as usize,
+		big_values: reader.read(9)? as usize,
+		global_gain: reader.read(8)? as i32,
+		scalefac_compress: reader.read(if header.is_mpeg1() { 4 } else { 9 })?,
+		..Default::default()
+	};
+	if gc.big_values > GRANULE_SAMPLES / 2 {
+		return Err(error!("mp3 big_values exceeds the granule size"));
+	}
+
+	if reader.read_bit()? {
+		gc.block_type = reader.read(2)? as u8;
+		if gc.block_type == 0 {
+			return Err(error!("mp3 window switching with a normal block type"));
+		}
+		gc.mixed_block = reader.read_bit()?;
+		for table in gc.table_select.iter_mut().take(2) {
+			*table = reader.read(5)? as usize;
+		}
+		for gain in gc.subblock_gain.iter_mut() {
+			*gain = reader.read(3)? as i32;
+		}
+		gc.region0_count = if gc.is_short() && !gc.mixed_block { 8 } else { 7 };
+		gc.region1_count = MAX_BANDS;
+	} else {
+		for table in gc.table_select.iter_mut() {
+			*table = reader.read(5)? as usize;
+		}
+		gc.region0_count = reader.read(4)? as usize;
+		gc.region1_count = reader.read(3)? as usize;
+	}
+
+	gc.preflag = if header.is_mpeg1() { reader.read_bit()? } else { gc.scalefac_compress >= 500 };
+	gc.scalefac_scale = reader.read_bit()?;
+	gc.count1_table_b = reader.read_bit()?;
+	Ok(gc)
+}
+
+/// Scale factor bands of a granule in bitstream order; short bands appear
+/// once per window.
+pub struct Bands {
+	pub widths: [usize; MAX_BANDS],
+	pub len: usize,
+	pub long: usize,
+}
+
+impl Bands {
+	pub fn new(header: &FrameHeader, gc: &GranuleChannel) -> Self {
+		let rate = header.sample_rate_index();
+		let long = &SFB_LONG[rate];
+		let short = &SFB_SHORT[rate];
+		let mut bands = Self { widths: [0; MAX_BANDS], len: 0, long: 0 };
+
+		let (long_count, first_short) = match (gc.is_short(), gc.mixed_block) {
+			(false, _) => (22, 13),
+			(true, false) => (0, 0),
+			(true, true) => (if header.is_mpeg1() { 8 } else { 6 }, 3),
+		};
+		for band in 0..long_count {
+			bands.push(long[band + 1] - long[band]);
+		}
+		bands.long = bands.len;
+		for band in first_short..13 {
+			for _ in 0..3 {
+				bands.push(short[band + 1] - short[band]);
+			}
+		}
+		bands
+	}
+
+	fn push(&mut self, width: usize) {
+		self.widths[self.len] = width;
+		self.len += 1;
+	}
+
+	fn is_short(&self, band: usize) -> bool {
+		band >= self.long
+	}
+
+	fn window(&self, band: usize) -> usize {
+		(band - self.long) % 3
+	}
+
+	/// First sample after the first `count` bands.
+	fn end_of(&self, count: usize) -> usize {
+		self.widths[..count.min(self.len)].iter().sum()
+	}
+}
+
+/// Decoded state of one channel in a granule.
+pub struct ChannelData {
+	pub samples: [f32; GRANULE_SAMPLES],
+	pub scalefac: [u8; MAX_BANDS],
+	/// Intensity stereo positions, only meaningful for the right channel.
+	pub positions: [u8; MAX_BANDS],
+	pub nonzero: usize,
+}
+
+impl Default for ChannelData {
+	fn default() -> Self {
+		Self {
+			samples: [0.0; GRANULE_SAMPLES],
+			scalefac: [0; MAX_BANDS],
+			positions: [0; MAX_BANDS],
+			nonzero: 0,
+		}
+	}
+}
+
+pub fn read_scalefactors(
+	header: &FrameHeader,
+	info: &SideInfo,
+	granule: usize,
+	channel: usize,
+	reader: &mut BitReader,
+	data: &mut ChannelData,
+) -> Result<()> {
+	let gc = &info.granules[granule][channel];
+	let kind = match (gc.is_short(), gc.mixed_block) {
+		(false, _) => 0,
+		(true, true) => 1,
+		(true, false) => 2,
+	};
+
+	let mut band = 0;
+	if header.is_mpeg1() {
+		let (slen1, slen2) = SLEN[gc.scalefac_compress as usize];
+		let slen = [slen1, slen1, slen2, slen2];
+		for (group, &count) in MPEG1_PARTITIONS[kind].iter().enumerate() {
+			let reuse = kind == 0 && granule == 1 && info.scfsi[channel][group];
+			for _ in 0..count {
+				if !reuse {
+					data.scalefac[band] = reader.read(slen[group] as u32)? as u8;
+				}
+				data.positions[band] = data.scalefac[band];
+				band += 1;
+			}
+		}
+	} else {
+		let intensity = header.is_intensity_stereo() && channel == 1;
+		let (row, slen) = mpeg2_slen(gc.scalefac_compress, intensity);
+		for (group, &count) in MPEG2_PARTITIONS[row][kind].iter().enumerate() {
+			let bits = slen[group];
+			for _ in 0..count {
+				let value = reader.read(bits)? as u8;
+				data.scalefac[band] = value;
+				let illegal = bits > 0 && value as u32 == (1 << bits) - 1;
+				data.positions[band] = if illegal { MPEG2_ILLEGAL_POSITION } else { value };
+				band += 1;
+			}
+		}
+	}
+
+	for index in band..MAX_BANDS {
+		data.scalefac[index] = 0;
+		data.positions[index] = 0;
+	}
+	Ok(())
+}
+
+/// slen widths and partition row for an MPEG-2 scalefac_compress value.
+fn mpeg2_slen(compress: u32, intensity: bool) -> (usize, [u32; 4]) {
+	if intensity {
+		let sfc = compress >> 1;
+		return match sfc {
+			0..180 => (3, [sfc / 36, (sfc % 36) / 6, sfc % 6, 0]),
+			180..244 => {
+				let sfc = sfc - 180;
+				(4, [(sfc % 64) >> 4, (sfc % 16) >> 2, sfc % 4, 0])
+			}
+			_ => {
+				let sfc = sfc - 244;
+				(5, [sfc / 3, sfc % 3, 0, 0])
+			}
+		};
+	}
+	match compress {
+		0..400 => (0, [(compress >> 4) / 5, (compress >> 4) % 5, (compress & 15) >> 2, compress & 3]),
+		400..500 => {
+			let sfc = compress - 400;
+			(1, [(sfc >> 2) / 5, (sfc >> 2) % 5, sfc & 3, 0])
+		}
+		_ => {
+			let sfc = compress - 500;
+			(2, [sfc / 3, sfc % 3, 0, 0])
+		}
+	}
+}
+
+/// Decodes the Huffman coded samples up to bit `end` and requantizes them.
+pub fn read_samples(
+	gc: &GranuleChannel,
+	bands: &Bands,
+	codebooks: &Codebooks,
+	pow43: &[f32],
+	reader: &mut BitReader,
+	end: usize,
+	data: &mut ChannelData,
+) -> Result<()> {
+	let mut values = [0i32; GRANULE_SAMPLES];
+	let big_end = gc.big_values * 2;
+	let region1 = bands.end_of(gc.region0_count + 1);
+	let region2 = bands.end_of(gc.region0_count + gc.region1_count + 2);
+
+	let mut index = 0;
+	while index < big_end {
+		let table = match index {
+			i if i < region1 => gc.table_select[0],
+			i if i < region2 => gc.table_select[1],
+			_ => gc.table_select[2],
+		};
+		let (x, y) = codebooks.decode_pair(table, reader)?;
+		values[index] = x;
+		values[index + 1] = y;
+		index += 2;
+	}
+
+	while index + 4 <= GRANULE_SAMPLES && reader.position() < end {
+		let Ok(quad) = codebooks.decode_quad(gc.count1_table_b, reader) else {
+			break;
+		};
+		if reader.position() > end {
+			break;
+		}
+		values[index..index + 4].copy_from_slice(&quad);
+		index += 4;
+	}
+	data.nonzero = index;
+	reader.set_position(end);
+
+	requantize(gc, bands, pow43, &values[..index], data);
+	Ok(())
+}
+
+fn requantize(
+	gc: &GranuleChannel,
+	bands: &Bands,
+	pow43: &[f32],
+	values: &[i32],
+	data: &mut ChannelData,
+) {
+	let multiplier = if gc.scalefac_scale { 1.0 } else { 0.5 };
+	let mut start = 0;
+	data.samples.fill(0.0);
+
+	for (band, &width) in bands.widths[..bands.len].iter().enumerate() {
+		if start >= values.len() {
+			break;
+		}
+		let end = (start + width).min(values.len());
+		let scalefac = data.scalefac[band] as f32;
+		let exponent = if bands.is_short(band) {
+			let gain = gc.global_gain - 210 - 8 * gc.subblock_gain[bands.window(band)];
+			0.25 * gain as f32 - multiplier * scalefac
+		} else {
+			let pretab = if gc.preflag { PRETAB[band] as f32 } else { 0.0 };
+			0.25 * (gc.global_gain - 210) as f32 - multiplier * (scalefac + pretab)
+		};
+		let gain = exponent.exp2();
+
+		for (sample, &value) in data.samples[start..end].iter_mut().zip(&values[start..end]) {
+			let magnitude = pow43[value.unsigned_abs() as usize] * gain;
+			*sample = if value < 0 { -magnitude } else { magnitude };
+		}
+		start = end;
+	}
+}
+
+/// Mid/side and intensity stereo reconstruction of a granule.
+pub fn process_stereo(
+	header: &FrameHeader,
+	gc: &GranuleChannel,
+	bands: &Bands,
+	left: &mut ChannelData,
+	right: &mut ChannelData,
+) {
+	let ms = header.is_ms_stereo();
+	if !header.is_intensity_stereo() {
+		if ms {
+			let end = left.nonzero.max(right.nonzero);
+			mid_side(&mut left.samples[..end], &mut right.samples[..end]);
+		}
+		return;
+	}
+
+	// highest band per window in which the right channel still carries data
+	let mut max_band = [-1i32; 3];
+	let mut start = 0;
+	for band in 0..bands.len {
+		let end = start + bands.widths[band];
+		if right.samples[start..end].iter().any(|&s| s != 0.0) {
+			max_band[band % 3] = band as i32;
+		}
+		start = end;
+	}
+	if bands.long > 0 {
+		let max = max_band.iter().copied().max().unwrap_or(-1);
+		max_band = [max; 3];
+	}
+
+	// the last band carries no position, it inherits the one before it
+	let windows = if bands.long < bands.len { 3 } else { 1 };
+	let default = if header.is_mpeg1() { 3 } else { 0 };
+	for (window, &max) in max_band.iter().enumerate().take(windows) {
+		let top = bands.len - windows + window;
+		let previous = top - windows;
+		right.positions[top] = match max >= previous as i32 {
+			true => default,
+			false => right.positions[previous],
+		};
+	}
+
+	let illegal = if header.is_mpeg1() { MPEG1_ILLEGAL_POSITION } else { MPEG2_ILLEGAL_POSITION };
+	let mut start = 0;
+	for band in 0..bands.len {
+		let end = start + bands.widths[band];
+		let position = right.positions[band];
+		if band as i32 > max_band[band % 3] && position < illegal {
+			let (kl, kr) = intensity_gains(header, gc, position);
+			for index in start..end {
+				let sample = left.samples[index];
+				left.samples[index] = sample * kl;
+				right.samples[index] = sample * kr;
+			}
+		} else if ms {
+			mid_side(&mut left.samples[start..end], &mut right.samples[start..end]);
+		}
+		start = end;
+	}
+}
+
+fn intensity_gains(header: &FrameHeader, gc: &GranuleChannel, position: u8) -> (f32, f32) {
+	if header.is_mpeg1() {
+		return INTENSITY_PAN[position as usize];
+	}
+	let step = if gc.scalefac_compress & 1 != 0 { 0.5 } else { 0.25 };
+	let gain = (-step * ((position as u32 + 1) >> 1) as f32).exp2();
+	if position & 1 != 0 { (gain, 1.0) } else { (1.0, gain) }
+}
+
+fn mid_side(mid: &mut [f32], side: &mut [f32]) {
+	for (m, s) in mid.iter_mut().zip(side.iter_mut()) {
+		let (a, b) = (*m, *s);
+		*m = (a + b) * std::f32::consts::FRAC_1_SQRT_2;
+		*s = (a - b) * std::f32::consts::FRAC_1_SQRT_2;
+	}
+}
+
+/// Moves short block samples from band order into per-subband windows of six
+/// lines each, the layout the short IMDCT expects.
+pub fn reorder(header: &FrameHeader, bands: &Bands, samples: &mut [f32; GRANULE_SAMPLES]) {
+	if bands.long == bands.len {
+		return;
+	}
+	let short = &SFB_SHORT[header.sample_rate_index()];
+	let first_short = if bands.long == 0 { 0 } else { 3 };
+	let offset = bands.end_of(bands.long);
+
+	let mut source = samples[offset..].iter().copied();
+	let mut reordered = [0f32; GRANULE_SAMPLES];
+	for band in first_short..13 {
+		for window in 0..3 {
+			for line in short[band]..short[band + 1] {
+				let sample = source.next().unwrap_or(0.0);
+				reordered[(line / 6) * 18 + window * 6 + line % 6] = sample;
+			}
+		}
+	}
+	samples[offset..].copy_from_slice(&reordered[offset..]);
+}
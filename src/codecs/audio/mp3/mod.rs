@@ -0,0 +1,10 @@
+pub mod decoder;
+pub mod header;
+pub mod huffman;
+pub mod hybrid;
+pub mod layer3;
+pub mod synthesis;
+pub mod tables;
+
+pub use decoder::Mp3Decoder;
+pub use header::{ChannelMode, FrameHeader, MpegVersion};
@@ -0,0 +1,65 @@
+use super::tables::SYNTHESIS_WINDOW;
+use std::f32::consts::PI;
+
+/// Polyphase synthesis filterbank, ISO/IEC 11172-3 Figure A.2.
+pub struct SynthesisFilter {
+	matrix: Vec<f32>,
+	window: Vec<f32>,
+}
+
+/// Per-channel history of the synthesis filterbank.
+pub struct SynthesisState {
+	v: [f32; 1024],
+	offset: usize,
+}
+
+impl SynthesisFilter {
+	pub fn new() -> Self {
+		let mut matrix = vec![0f32; 64 * 32];
+		for i in 0..64 {
+			for k in 0..32 {
+				matrix[i * 32 + k] = ((16 + i) as f32 * (2 * k + 1) as f32 * PI / 64.0).cos();
+			}
+		}
+		let window = SYNTHESIS_WINDOW.iter().map(|&d| d as f32 / 65536.0).collect();
+		Self { matrix, window }
+	}
+
+	/// Turns one sample of each of the 32 subbands into 32 PCM samples.
+	pub fn synthesize(&self, state: &mut SynthesisState, subbands: &[f32; 32], out: &mut [f32; 32]) {
+		state.offset = (state.offset + 1024 - 64) & 1023;
+		for i in 0..64 {
+			let row = &self.matrix[i * 32..(i + 1) * 32];
+			state.v[state.offset + i] = subbands.iter().zip(row).map(|(s, n)| s * n).sum();
+		}
+
+		for (j, sample) in out.iter_mut().enumerate() {
+			let mut sum = 0.0;
+			for i in 0..8 {
+				let low = (state.offset + i * 128 + j) & 1023;
+				let high = (state.offset + i * 128 + 96 + j) & 1023;
+				sum += state.v[low] * self.window[i * 64 + j];
+				sum += state.v[high] * self.window[i * 64 + 32 + j];
+			}
+			*sample = sum;
+		}
+	}
+}
+
+impl Default for SynthesisFilter {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl SynthesisState {
+	pub fn new() -> Self {
+		Self { v: [0.0; 1024], offset: 0 }
+	}
+}
+
+impl Default for SynthesisState {
+	fn default() -> Self {
+		Self::new()
+	}
+}
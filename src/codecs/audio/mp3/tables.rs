@@ -0,0 +1,131 @@
+// Scale factor band boundaries, ISO/IEC 11172-3 Table B.8 and ISO/IEC 13818-3
+// Table B.2, ordered 44.1, 48, 32, 22.05, 24, 16, 11.025, 12 and 8 kHz.
+
+pub const SFB_LONG: [[usize; 23]; 9] = [
+	[
+		0, 4, 8, 12, 16, 20, 24, 30, 36, 44, 52, 62, 74, 90, 110, 134, 162, 196, 238, 288, 342, 418,
+		576,
+	],
+	[
+		0, 4, 8, 12, 16, 20, 24, 30, 36, 42, 50, 60, 72, 88, 106, 128, 156, 190, 230, 276, 330, 384,
+		576,
+	],
+	[
+		0, 4, 8, 12, 16, 20, 24, 30, 36, 44, 54, 66, 82, 102, 126, 156, 194, 240, 296, 364, 448, 550,
+		576,
+	],
+	[
+		0, 6, 12, 18, 24, 30, 36, 44, 54, 66, 80, 96, 116, 140, 168, 200, 238, 284, 336, 396, 464, 522,
+		576,
+	],
+	[
+		0, 6, 12, 18, 24, 30, 36, 44, 54, 66, 80, 96, 114, 136, 162, 194, 232, 278, 332, 394, 464, 540,
+		576,
+	],
+	[
+		0, 6, 12, 18, 24, 30, 36, 44, 54, 66, 80, 96, 116, 140, 168, 200, 238, 284, 336, 396, 464, 522,
+		576,
+	],
+	[
+		0, 6, 12, 18, 24, 30, 36, 44, 54, 66, 80, 96, 116, 140, 168, 200, 238, 284, 336, 396, 464, 522,
+		576,
+	],
+	[
+		0, 6, 12, 18, 24, 30, 36, 44, 54, 66, 80, 96, 116, 140, 168, 200, 238, 284, 336, 396, 464, 522,
+		576,
+	],
+	[
+		0, 12, 24, 36, 48, 60, 72, 88, 108, 132, 160, 192, 232, 280, 336, 400, 476, 566, 568, 570, 572,
+		574, 576,
+	],
+];
+
+/// Boundaries of one short window; each band repeats for the three windows.
+pub const SFB_SHORT: [[usize; 14]; 9] = [
+	[0, 4, 8, 12, 16, 22, 30, 40, 52, 66, 84, 106, 136, 192],
+	[0, 4, 8, 12, 16, 22, 28, 38, 50, 64, 80, 100, 126, 192],
+	[0, 4, 8, 12, 16, 22, 30, 42, 58, 78, 104, 138, 180, 192],
+	[0, 4, 8, 12, 18, 24, 32, 42, 56, 74, 100, 132, 174, 192],
+	[0, 4, 8, 12, 18, 26, 36, 48, 62, 80, 104, 136, 180, 192],
+	[0, 4, 8, 12, 18, 26, 36, 48, 62, 80, 104, 134, 174, 192],
+	[0, 4, 8, 12, 18, 26, 36, 48, 62, 80, 104, 134, 174, 192],
+	[0, 4, 8, 12, 18, 26, 36, 48, 62, 80, 104, 134, 174, 192],
+	[0, 8, 16, 24, 36, 52, 72, 96, 124, 160, 162, 164, 166, 192],
+];
+
+pub const PRETAB: [u8; 22] = [0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 3, 3, 3, 2, 0];
+
+/// slen1 and slen2 for each MPEG-1 scalefac_compress value.
+pub const SLEN: [(u8, u8); 16] = [
+	(0, 0),
+	(0, 1),
+	(0, 2),
+	(0, 3),
+	(3, 0),
+	(1, 1),
+	(1, 2),
+	(1, 3),
+	(2, 1),
+	(2, 2),
+	(2, 3),
+	(3, 1),
+	(3, 2),
+	(3, 3),
+	(4, 2),
+	(4, 3),
+];
+
+/// Scale factors read per slen group in MPEG-1, for long, mixed and short
+/// blocks. The first two groups use slen1, the last two slen2.
+pub const MPEG1_PARTITIONS: [[usize; 4]; 3] = [[6, 5, 5, 5], [8, 9, 6, 12], [9, 9, 6, 12]];
+
+/// MPEG-2 scale factor partitions, ISO/IEC 13818-3 Table B.1, indexed by the
+/// scalefac_compress range and then by long, mixed and short blocks.
+pub const MPEG2_PARTITIONS: [[[usize; 4]; 3]; 6] = [
+	[[6, 5, 5, 5], [6, 9, 9, 9], [9, 9, 9, 9]],
+	[[6, 5, 7, 3], [6, 9, 12, 6], [9, 9, 12, 6]],
+	[[11, 10, 0, 0], [15, 18, 0, 0], [18, 18, 0, 0]],
+	[[7, 7, 7, 0], [6, 15, 12, 0], [12, 12, 12, 0]],
+	[[6, 6, 6, 3], [6, 12, 9, 6], [12, 9, 9, 6]],
+	[[8, 8, 5, 0], [6, 18, 9, 0], [15, 12, 9, 0]],
+];
+
+/// Alias reduction coefficients c[i], ISO/IEC 11172-3 Table B.9.
+pub const ALIAS_COEFFICIENTS: [f32; 8] =
+	[-0.6, -0.535, -0.33, -0.185, -0.095, -0.041, -0.0142, -0.0037];
+
+/// Synthesis window D[i] in units of 1/65536, ISO/IEC 11172-3 Table B.3.
+pub const SYNTHESIS_WINDOW: [i32; 512] = [
+	0, -1, -1, -1, -1, -1, -1, -2, -2, -2, -2, -3, -3, -4, -4, -5, -5, -6, -7, -7, -8, -9, -10, -11,
+	-13, -14, -16, -17, -19, -21, -24, -26, -29, -31, -35, -38, -41, -45, -49, -53, -58, -63, -68,
+	-73, -79, -85, -91, -97, -104, -111, -117, -125, -132, -139, -147, -154, -161, -169, -176, -183,
+	-190, -196, -202, -208, 213, 218, 222, 225, 227, 228, 228, 227, 224, 221, 215, 208, 200, 189,
+	177, 163, 146, 127, 106, 83, 57, 29, -2, -36, -72, -111, -153, -197, -244, -294, -347, -401,
+	-459, -519, -581, -645, -711, -779, -848, -919, -991, -1064, -1137, -1210, -1283, -1356, -1428,
+	-1498, -1567, -1634, -1698, -1759, -1817, -1870, -1919, -1962, -2001, -2032, -2057, -2075, -2085,
+	-2087, -2080, -2063, 2037, 2000, 1952, 1893, 1822, 1739, 1644, 1535, 1414, 1280, 1131, 970, 794,
+	605, 402, 185, -45, -288, -545, -814, -1095, -1388, -1692, -2006, -2330, -2663, -3004, -3351,
+	-3705, -4063, -4425, -4788, -5153, -5517, -5879, -6237, -6589, -6935, -7271, -7597, -7910, -8209,
+	-8491, -8755, -8998, -9219, -9416, -9585, -9727, -9838, -9916, -9959, -9966, -9935, -9863, -9750,
+	-9592, -9389, -9139, -8840, -8492, -8092, -7640, -7134, 6574, 5959, 5288, 4561, 3776, 2935, 2037,
+	1082, 70, -998, -2122, -3300, -4533, -5818, -7154, -8540, -9975, -11455, -12980, -14548, -16155,
+	-17799, -19478, -21189, -22929, -24694, -26482, -28289, -30112, -31947, -33791, -35640, -37489,
+	-39336, -41176, -43006, -44821, -46617, -48390, -50137, -51853, -53534, -55178, -56778, -58333,
+	-59838, -61289, -62684, -64019, -65290, -66494, -67629, -68692, -69679, -70590, -71420, -72169,
+	-72835, -73415, -73908, -74313, -74630, -74856, -74992, 75038, 74992, 74856, 74630, 74313, 73908,
+	73415, 72835, 72169, 71420, 70590, 69679, 68692, 67629, 66494, 65290, 64019, 62684, 61289, 59838,
+	58333, 56778, 55178, 53534, 51853, 50137, 48390, 46617, 44821, 43006, 41176, 39336, 37489, 35640,
+	33791, 31947, 30112, 28289, 26482, 24694, 22929, 21189, 19478, 17799, 16155, 14548, 12980, 11455,
+	9975, 8540, 7154, 5818, 4533, 3300, 2122, 998, -70, -1082, -2037, -2935, -3776, -4561, -5288,
+	-5959, 6574, 7134, 7640, 8092, 8492, 8840, 9139, 9389, 9592, 9750, 9863, 9935, 9966, 9959, 9916,
+	9838, 9727, 9585, 9416, 9219, 8998, 8755, 8491, 8209, 7910, 7597, 7271, 6935, 6589, 6237, 5879,
+	5517, 5153, 4788, 4425, 4063, 3705, 3351, 3004, 2663, 2330, 2006, 1692, 1388, 1095, 814, 545,
+	288, 45, -185, -402, -605, -794, -970, -1131, -1280, -1414, -1535, -1644, -1739, -1822, -1893,
+	-1952, -2000, 2037, 2063, 2080, 2087, 2085, 2075, 2057, 2032, 2001, 1962, 1919, 1870, 1817, 1759,
+	1698, 1634, 1567, 1498, 1428, 1356, 1283, 1210, 1137, 1064, 991, 919, 848, 779, 711, 645, 581,
+	519, 459, 401, 347, 294, 244, 197, 153, 111, 72, 36, 2, -29, -57, -83, -106, -127, -146, -163,
+	-177, -189, -200, -208, -215, -221, -224, -227, -228, -228, -227, -225, -222, -218, 213, 208,
+	202, 196, 190, 183, 176, 169, 161, 154, 147, 139, 132, 125, 117, 111, 104, 97, 91, 85, 79, 73,
+	68, 63, 58, 53, 49, 45, 41, 38, 35, 31, 29, 26, 24, 21, 19, 17, 16, 14, 13, 11, 10, 9, 8, 7, 7,
+	6, 5, 5, 4, 4, 3, 3, 2, 2, 2, 2, 1, 1, 1, 1, 1, 1,
+];
@@ -0,0 +1,884 @@
+use super::rate::{bits_to_pulses, max_pulse_bits, pulses, pulses_to_bits};
+use super::tables::{EBANDS, LOG_N, NB_BANDS};
+use super::{Allocation, Layout};
+use crate::codecs::audio::opus::range::{BITRES, RangeDecoder, ilog};
+
+pub const SPREAD_NONE: usize = 0;
+pub const SPREAD_NORMAL: usize = 2;
+pub const SPREAD_AGGRESSIVE: usize = 3;
+
+/// Widest band, band 20 of a 20 ms frame.
+const MAX_BAND_SIZE: usize = 176;
+const SPREAD_FACTOR: [usize; 3] = [15, 10, 5];
+const EXP2_TABLE8: [i32; 8] = [16384, 17866, 19483, 21247, 23170, 25267, 27554, 30048];
+const ORDERY: [usize; 30] =
+	[1, 0, 3, 0, 2, 1, 7, 0, 4, 3, 6, 1, 5, 2, 15, 0, 8, 7, 12, 3, 11, 4, 14, 1, 9, 6, 13, 2, 10, 5];
+const BIT_INTERLEAVE: [u32; 16] = [0, 1, 1, 1, 2, 3, 3, 3, 2, 3, 3, 3, 2, 3, 3, 3];
+const BIT_DEINTERLEAVE: [u32; 16] =
+	[0x00, 0x03, 0x0C, 0x0F, 0x30, 0x33, 0x3C, 0x3F, 0xC0, 0xC3, 0xCC, 0xCF, 0xF0, 0xF3, 0xFC, 0xFF];
+
+const PVQ_ROWS: usize = 15;
+const PVQ_COLUMNS: usize = MAX_BAND_SIZE + 1;
+
+/// U(n, k) of the pulse vector codebook, indexed by `[min(n, k)][max(n, k)]`.
+/// Entries too large for 32 bits wrap around; no valid codebook reaches them.
+static PVQ_U: [[u32; PVQ_COLUMNS]; PVQ_ROWS] = pvq_table();
+
+const fn pvq_table() -> [[u32; PVQ_COLUMNS]; PVQ_ROWS] {
+	let mut table = [[0u32; PVQ_COLUMNS]; PVQ_ROWS];
+	table[0][0] = 1;
+	let mut n = 1;
+	while n < PVQ_ROWS {
+		let mut k = 1;
+		while k < PVQ_COLUMNS {
+			// U(n, k) = U(n - 1, k) + U(n, k - 1) + U(n - 1, k - 1), mirrored below
+			// the diagonal
+			let up = if n - 1 <= k { table[n - 1][k] } else { table[k][n - 1] };
+			let left = if n < k { table[n][k - 1] } else { table[k - 1][n] };
+			let diagonal = if n - 1 < k { table[n - 1][k - 1] } else { table[k - 1][n - 1] };
+			table[n][k] = up.wrapping_add(left).wrapping_add(diagonal);
+			k += 1;
+		}
+		n += 1;
+	}
+	table
+}
+
+fn pvq_u(n: usize, k: usize) -> u32 {
+	PVQ_U[n.min(k)][n.max(k)]
+}
+
+/// Decodes the index of a pulse vector with `k` pulses over `y.len()`
+/// dimensions, returning its squared norm.
+fn decode_pulses(rc: &mut RangeDecoder, y: &mut [i32], k: usize) -> f32 {
+	let mut n = y.len();
+	let mut k = k;
+	let mut index = rc.decode_uint(pvq_u(n, k).wrapping_add(pvq_u(n, k + 1)));
+	let mut yy = 0f32;
+	let mut pos = 0;
+	let mut push = |value: i32, pos: &mut usize| {
+		y[*pos] = value;
+		*pos += 1;
+		yy += (value * value) as f32;
+	};
+
+	while n > 2 {
+		let value;
+		if k >= n {
+			// more pulses than dimensions
+			let row = &PVQ_U[n];
+			let mut p = row[k + 1];
+			let sign = -((index >= p) as i32);
+			index -= p & sign as u32;
+			let k0 = k;
+			if row[n] > index {
+				k = n;
+				loop {
+					k -= 1;
+					p = PVQ_U[k][n];
+					if p <= index {
+						break;
+					}
+				}
+			} else {
+				p = row[k];
+				while p > index {
+					k -= 1;
+					p = row[k];
+				}
+			}
+			index -= p;
+			value = (k0 as i32 - k as i32 + sign) ^ sign;
+		} else {
+			let p = PVQ_U[k][n];
+			let q = PVQ_U[k + 1][n];
+			if p <= index && index < q {
+				index -= p;
+				value = 0;
+			} else {
+				let sign = -((index >= q) as i32);
+				index -= q & sign as u32;
+				let k0 = k;
+				let mut p;
+				loop {
+					k -= 1;
+					p = PVQ_U[k][n];
+					if p <= index {
+						break;
+					}
+				}
+				index -= p;
+				value = (k0 as i32 - k as i32 + sign) ^ sign;
+			}
+		}
+		push(value, &mut pos);
+		n -= 1;
+	}
+
+	let p = 2 * k as u32 + 1;
+	let sign = -((index >= p) as i32);
+	index -= p & sign as u32;
+	let k0 = k;
+	k = ((index + 1) >> 1) as usize;
+	if k != 0 {
+		index -= 2 * k as u32 - 1;
+	}
+	push((k0 as i32 - k as i32 + sign) ^ sign, &mut pos);
+
+	let sign = -(index as i32);
+	push((k as i32 + sign) ^ sign, &mut pos);
+	yy
+}
+
+fn isqrt(mut value: u32) -> u32 {
+	let mut root = 0;
+	let mut shift = (ilog(value) - 1) >> 1;
+	let mut bit = 1 << shift;
+	while shift >= 0 {
+		let t = ((root << 1) + bit) << shift;
+		if t <= value {
+			root += bit;
+			value -= t;
+		}
+		bit >>= 1;
+		shift -= 1;
+	}
+	root
+}
+
+fn frac_mul16(a: i32, b: i32) -> i32 {
+	(16384 + (a as i16 as i32) * (b as i16 as i32)) >> 15
+}
+
+fn bitexact_cos(x: i32) -> i32 {
+	let x2 = (4096 + x * x) >> 13;
+	let x2 = (32767 - x2) + frac_mul16(x2, -7651 + frac_mul16(x2, 8277 + frac_mul16(-626, x2)));
+	1 + x2
+}
+
+fn bitexact_log2tan(sin: i32, cos: i32) -> i32 {
+	let lc = ilog(cos as u32);
+	let ls = ilog(sin as u32);
+	let cos = cos << (15 - lc);
+	let sin = sin << (15 - ls);
+	(ls - lc) * (1 << 11) + frac_mul16(sin, frac_mul16(sin, -2597) + 7932)
+		- frac_mul16(cos, frac_mul16(cos, -2597) + 7932)
+}
+
+pub fn lcg_rand(seed: u32) -> u32 {
+	seed.wrapping_mul(1664525).wrapping_add(1013904223)
+}
+
+/// Scales `x` to a norm of `gain`.
+pub fn renormalise(x: &mut [f32], gain: f32) {
+	let energy = 1e-15 + x.iter().fold(0.0, |sum, &v| sum + v * v);
+	let g = 1.0 / energy.sqrt() * gain;
+	x.iter_mut().for_each(|v| *v *= g);
+}
+
+fn cos_norm(x: f32) -> f32 {
+	((0.5 * std::f32::consts::PI * x) as f64).cos() as f32
+}
+
+fn rotate(x: &mut [f32], stride: usize, c: f32, s: f32) {
+	let len = x.len();
+	for i in 0..len.saturating_sub(stride) {
+		let (x1, x2) = (x[i], x[i + stride]);
+		x[i + stride] = c * x2 + s * x1;
+		x[i] = c * x1 - s * x2;
+	}
+	for i in (0..len.saturating_sub(2 * stride)).rev() {
+		let (x1, x2) = (x[i], x[i + stride]);
+		x[i + stride] = c * x2 + s * x1;
+		x[i] = c * x1 - s * x2;
+	}
+}
+
+/// Undoes the spreading rotation applied by the encoder.
+fn exp_rotation(x: &mut [f32], blocks: usize, k: usize, spread: usize) {
+	let len = x.len();
+	if 2 * k >= len || spread == SPREAD_NONE {
+		return;
+	}
+	let factor = SPREAD_FACTOR[spread - 1];
+	let gain = len as f32 / (len + factor * k) as f32;
+	let theta = 0.5 * (gain * gain);
+	let c = cos_norm(theta);
+	let s = cos_norm(1.0 - theta);
+
+	let mut stride2 = 0;
+	if len >= 8 * blocks {
+		stride2 = 1;
+		while (stride2 * stride2 + stride2) * blocks + (blocks >> 2) < len {
+			stride2 += 1;
+		}
+	}
+	for block in x.chunks_exact_mut(len / blocks) {
+		if stride2 != 0 {
+			rotate(block, stride2, s, c);
+		}
+		rotate(block, 1, c, s);
+	}
+}
+
+fn alg_unquant(
+	rc: &mut RangeDecoder,
+	x: &mut [f32],
+	k: usize,
+	spread: usize,
+	blocks: usize,
+	gain: f32,
+) -> u32 {
+	let n = x.len();
+	let mut iy = [0i32; MAX_BAND_SIZE];
+	let iy = &mut iy[..n];
+	let ryy = decode_pulses(rc, iy, k);
+	let g = 1.0 / ryy.sqrt() * gain;
+	for (value, &pulse) in x.iter_mut().zip(iy.iter()) {
+		*value = g * pulse as f32;
+	}
+	exp_rotation(x, blocks, k, spread);
+
+	if blocks <= 1 {
+		return 1;
+	}
+	iy.chunks_exact(n / blocks)
+		.enumerate()
+		.fold(0, |mask, (i, block)| mask | ((block.iter().any(|&v| v != 0) as u32) << i))
+}
+
+fn haar1(x: &mut [f32], n0: usize, stride: usize) {
+	for i in 0..stride {
+		for j in 0..n0 >> 1 {
+			let tmp1 = std::f32::consts::FRAC_1_SQRT_2 * x[stride * 2 * j + i];
+			let tmp2 = std::f32::consts::FRAC_1_SQRT_2 * x[stride * (2 * j + 1) + i];
+			x[stride * 2 * j + i] = tmp1 + tmp2;
+			x[stride * (2 * j + 1) + i] = tmp1 - tmp2;
+		}
+	}
+}
+
+fn deinterleave_hadamard(x: &mut [f32], n0: usize, stride: usize, hadamard: bool) {
+	let mut tmp = [0f32; MAX_BAND_SIZE];
+	for i in 0..stride {
+		let row = if hadamard { ORDERY[stride - 2 + i] } else { i };
+		for j in 0..n0 {
+			tmp[row * n0 + j] = x[j * stride + i];
+		}
+	}
+	x[..n0 * stride].copy_from_slice(&tmp[..n0 * stride]);
+}
+
+fn interleave_hadamard(x: &mut [f32], n0: usize, stride: usize, hadamard: bool) {
+	let mut tmp = [0f32; MAX_BAND_SIZE];
+	for i in 0..stride {
+		let row = if hadamard { ORDERY[stride - 2 + i] } else { i };
+		for j in 0..n0 {
+			tmp[j * stride + i] = x[row * n0 + j];
+		}
+	}
+	x[..n0 * stride].copy_from_slice(&tmp[..n0 * stride]);
+}
+
+fn stereo_merge(x: &mut [f32], y: &mut [f32], mid: f32) {
+	let (mut xp, mut side) = (0f32, 0f32);
+	for (&l, &r) in x.iter().zip(y.iter()) {
+		xp += r * l;
+		side += r * r;
+	}
+	let xp = mid * xp;
+	let el = mid * mid + side - 2.0 * xp;
+	let er = mid * mid + side + 2.0 * xp;
+	if er < 6e-4 || el < 6e-4 {
+		y.copy_from_slice(x);
+		return;
+	}
+	let lgain = 1.0 / el.sqrt();
+	let rgain = 1.0 / er.sqrt();
+	for (l, r) in x.iter_mut().zip(y.iter_mut()) {
+		let left = mid * *l;
+		let right = *r;
+		*l = lgain * (left - right);
+		*r = rgain * (left + right);
+	}
+}
+
+fn compute_qn(n: i32, b: i32, offset: i32, pulse_cap: i32, stereo: bool) -> i32 {
+	let mut n2 = 2 * n - 1;
+	if stereo && n == 2 {
+		n2 -= 1;
+	}
+	let qb = ((b + n2 * offset) / n2).min(b - pulse_cap - (4 << BITRES)).min(8 << BITRES);
+	if qb < (1 << BITRES >> 1) {
+		1
+	} else {
+		let qn = EXP2_TABLE8[(qb & 7) as usize] >> (14 - (qb >> BITRES));
+		(qn + 1) >> 1 << 1
+	}
+}
+
+/// Bit budget and time-frequency shape of a band, or of part of one.
+#[derive(Debug, Clone, Copy)]
+struct Band {
+	bits: i32,
+	blocks: usize,
+	lm: i32,
+	fill: u32,
+}
+
+struct Split {
+	inv: bool,
+	imid: i32,
+	iside: i32,
+	delta: i32,
+	itheta: i32,
+	qalloc: i32,
+}
+
+struct BandContext<'a, 'b> {
+	rc: &'a mut RangeDecoder<'b>,
+	band: usize,
+	intensity: usize,
+	spread: usize,
+	tf_change: i32,
+	remaining_bits: i32,
+	seed: u32,
+	disable_inv: bool,
+}
+
+impl BandContext<'_, '_> {
+	/// Decodes the angle splitting a band between two halves, or between mid
+	/// and side, and charges its cost to `band`.
+	fn compute_theta(&mut self, n: usize, band: &mut Band, b0: usize, stereo: bool) -> Split {
+		let n = n as i32;
+		let pulse_cap = LOG_N[self.band] + band.lm * (1 << BITRES);
+		let offset = (pulse_cap >> 1) - if stereo && n == 2 { 16 } else { 4 };
+		let mut qn = compute_qn(n, band.bits, offset, pulse_cap, stereo);
+		if stereo && self.band >= self.intensity {
+			qn = 1;
+		}
+
+		let tell = self.rc.tell_frac() as i32;
+		let mut itheta = 0;
+		let mut inv = false;
+		if qn != 1 {
+			if stereo && n > 2 {
+				// a step distribution, three times likelier up to itheta = 8192
+				let p0 = 3;
+				let x0 = qn / 2;
+				let ft = p0 * (x0 + 1) + x0;
+				let fs = self.rc.decode(ft as u32) as i32;
+				let x = if fs < (x0 + 1) * p0 { fs / p0 } else { x0 + 1 + (fs - (x0 + 1) * p0) };
+				let (fl, fh) = if x <= x0 {
+					(p0 * x, p0 * (x + 1))
+				} else {
+					((x - 1 - x0) + (x0 + 1) * p0, (x - x0) + (x0 + 1) * p0)
+				};
+				self.rc.update(fl as u32, fh as u32, ft as u32);
+				itheta = x;
+			} else if b0 > 1 || stereo {
+				itheta = self.rc.decode_uint(qn as u32 + 1) as i32;
+			} else {
+				// triangular distribution
+				let half = qn >> 1;
+				let ft = (half + 1) * (half + 1);
+				let fm = self.rc.decode(ft as u32) as i32;
+				let (fl, fs);
+				if fm < ((half * (half + 1)) >> 1) {
+					itheta = (isqrt(8 * fm as u32 + 1) as i32 - 1) >> 1;
+					fs = itheta + 1;
+					fl = (itheta * (itheta + 1)) >> 1;
+				} else {
+					itheta = (2 * (qn + 1) - isqrt(8 * (ft - fm - 1) as u32 + 1) as i32) >> 1;
+					fs = qn + 1 - itheta;
+					fl = ft - (((qn + 1 - itheta) * (qn + 2 - itheta)) >> 1);
+				}
+				self.rc.update(fl as u32, (fl + fs) as u32, ft as u32);
+			}
+			itheta = itheta * 16384 / qn;
+		} else if stereo {
+			if band.bits > 2 << BITRES && self.remaining_bits > 2 << BITRES {
+				inv = self.rc.decode_bit_logp(2);
+			}
+			inv &= !self.disable_inv;
+		}
+		let qalloc = self.rc.tell_frac() as i32 - tell;
+		band.bits -= qalloc;
+
+		let mask = (1u32 << band.blocks) - 1;
+		let (imid, iside, delta) = match itheta {
+			0 => {
+				band.fill &= mask;
+				(32767, 0, -16384)
+			}
+			16384 => {
+				band.fill &= mask << band.blocks;
+				(0, 32767, 16384)
+			}
+			_ => {
+				let imid = bitexact_cos(itheta);
+				let iside = bitexact_cos(16384 - itheta);
+				(imid, iside, frac_mul16((n - 1) << 7, bitexact_log2tan(iside, imid)))
+			}
+		};
+		Split { inv, imid, iside, delta, itheta, qalloc }
+	}
+
+	fn quant_band_n1(
+		&mut self,
+		x: &mut [f32],
+		y: Option<&mut [f32]>,
+		lowband_out: Option<&mut [f32]>,
+	) -> u32 {
+		let mut decode_sign = |value: &mut f32| {
+			let mut sign = false;
+			if self.remaining_bits >= 1 << BITRES {
+				sign = self.rc.decode_bits(1) != 0;
+				self.remaining_bits -= 1 << BITRES;
+			}
+			*value = if sign { -1.0 } else { 1.0 };
+		};
+		decode_sign(&mut x[0]);
+		if let Some(y) = y {
+			decode_sign(&mut y[0]);
+		}
+		if let Some(out) = lowband_out {
+			out[0] = x[0];
+		}
+		1
+	}
+
+	/// Decodes a mono partition, recursively splitting it in halves while it
+	/// has more bits than a single codebook can use.
+	fn quant_partition(
+		&mut self,
+		x: &mut [f32],
+		lowband: Option<&[f32]>,
+		band: Band,
+		gain: f32,
+	) -> u32 {
+		let mut band = band;
+		let n = x.len();
+		let b0 = band.blocks;
+
+		if band.lm != -1 && band.bits > max_pulse_bits(self.band, band.lm) + 12 && n > 2 {
+			let n = n >> 1;
+			let (x, y) = x.split_at_mut(n);
+			band.lm -= 1;
+			if band.blocks == 1 {
+				band.fill = (band.fill & 1) | (band.fill << 1);
+			}
+			band.blocks = (band.blocks + 1) >> 1;
+
+			let split = self.compute_theta(n, &mut band, b0, false);
+			let mid = (1.0 / 32768.0) * split.imid as f32;
+			let side = (1.0 / 32768.0) * split.iside as f32;
+			let itheta = split.itheta;
+			let mut delta = split.delta;
+
+			// give more bits to low-energy MDCTs than they would otherwise deserve
+			if b0 > 1 && itheta & 0x3fff != 0 {
+				if itheta > 8192 {
+					delta -= delta >> (4 - band.lm);
+				} else {
+					delta = (delta + ((n as i32) << BITRES >> (5 - band.lm))).min(0);
+				}
+			}
+			let b = band.bits;
+			let mut mbits = b.min((b - delta) / 2).max(0);
+			let mut sbits = b - mbits;
+			self.remaining_bits -= split.qalloc;
+
+			let next_lowband = lowband.map(|lowband| &lowband[n..]);
+			let mid_band = |bits| Band { bits, ..band };
+			let side_band = |bits| Band { bits, fill: band.fill >> band.blocks, ..band };
+			let rebalance = self.remaining_bits;
+			if mbits >= sbits {
+				let mut cm = self.quant_partition(x, lowband, mid_band(mbits), gain * mid);
+				let rebalance = mbits - (rebalance - self.remaining_bits);
+				if rebalance > 3 << BITRES && itheta != 0 {
+					sbits += rebalance - (3 << BITRES);
+				}
+				cm |= self.quant_partition(y, next_lowband, side_band(sbits), gain * side) << (b0 >> 1);
+				cm
+			} else {
+				let mut cm =
+					self.quant_partition(y, next_lowband, side_band(sbits), gain * side) << (b0 >> 1);
+				let rebalance = sbits - (rebalance - self.remaining_bits);
+				if rebalance > 3 << BITRES && itheta != 16384 {
+					mbits += rebalance - (3 << BITRES);
+				}
+				cm |= self.quant_partition(x, lowband, mid_band(mbits), gain * mid);
+				cm
+			}
+		} else {
+			let (i, lm) = (self.band, band.lm);
+			let mut q = bits_to_pulses(i, lm, band.bits);
+			let mut curr_bits = pulses_to_bits(i, lm, q);
+			self.remaining_bits -= curr_bits;
+			// never bust the budget
+			while self.remaining_bits < 0 && q > 0 {
+				self.remaining_bits += curr_bits;
+				q -= 1;
+				curr_bits = pulses_to_bits(i, lm, q);
+				self.remaining_bits -= curr_bits;
+			}
+
+			if q != 0 {
+				return alg_unquant(self.rc, x, pulses(q) as usize, self.spread, band.blocks, gain);
+			}
+
+			// no pulses, fill the band anyway
+			let mask = ((1u64 << band.blocks) - 1) as u32;
+			let fill = band.fill & mask;
+			if fill == 0 {
+				x.fill(0.0);
+				return 0;
+			}
+			let cm = match lowband {
+				None => {
+					for value in x.iter_mut() {
+						self.seed = lcg_rand(self.seed);
+						*value = (self.seed as i32 >> 20) as f32;
+					}
+					mask
+				}
+				Some(lowband) => {
+					// about 48 dB below the normal folding level
+					for (value, &folded) in x.iter_mut().zip(lowband) {
+						self.seed = lcg_rand(self.seed);
+						let noise = if self.seed & 0x8000 != 0 { 1.0 / 256.0 } else { -1.0 / 256.0 };
+						*value = folded + noise;
+					}
+					fill
+				}
+			};
+			renormalise(x, gain);
+			cm
+		}
+	}
+
+	/// Decodes a mono band. `lowband` is a private copy of the folding source,
+	/// free to be reordered.
+	fn quant_band(
+		&mut self,
+		x: &mut [f32],
+		mut lowband: Option<&mut [f32]>,
+		lowband_out: Option<&mut [f32]>,
+		gain: f32,
+		band: Band,
+	) -> u32 {
+		let n0 = x.len();
+		if n0 == 1 {
+			return self.quant_band_n1(x, None, lowband_out);
+		}
+		let long_blocks = band.blocks == 1;
+		let mut blocks = band.blocks;
+		let mut fill = band.fill;
+		let mut n_b = n0 / blocks;
+		let mut tf_change = self.tf_change;
+
+		// recombine bands to increase the frequency resolution
+		let recombine = tf_change.max(0) as usize;
+		for k in 0..recombine {
+			if let Some(lowband) = lowband.as_deref_mut() {
+				haar1(lowband, n0 >> k, 1 << k);
+			}
+			fill = BIT_INTERLEAVE[(fill & 0xF) as usize] | BIT_INTERLEAVE[(fill >> 4) as usize] << 2;
+		}
+		blocks >>= recombine;
+		n_b <<= recombine;
+
+		// or split them to increase the time resolution
+		let mut time_divide = 0;
+		while n_b & 1 == 0 && tf_change < 0 {
+			if let Some(lowband) = lowband.as_deref_mut() {
+				haar1(lowband, n_b, blocks);
+			}
+			fill |= fill << blocks;
+			blocks <<= 1;
+			n_b >>= 1;
+			time_divide += 1;
+			tf_change += 1;
+		}
+		let (b0, n_b0) = (blocks, n_b);
+
+		// samples go in time order rather than frequency order
+		if b0 > 1
+			&& let Some(lowband) = lowband.as_deref_mut()
+		{
+			deinterleave_hadamard(lowband, n_b >> recombine, b0 << recombine, long_blocks);
+		}
+
+		let partition = Band { blocks, fill, ..band };
+		let mut cm = self.quant_partition(x, lowband.as_deref(), partition, gain);
+
+		if b0 > 1 {
+			interleave_hadamard(x, n_b >> recombine, b0 << recombine, long_blocks);
+		}
+		n_b = n_b0;
+		blocks = b0;
+		for _ in 0..time_divide {
+			blocks >>= 1;
+			n_b <<= 1;
+			cm |= cm >> blocks;
+			haar1(x, n_b, blocks);
+		}
+		for k in 0..recombine {
+			cm = BIT_DEINTERLEAVE[cm as usize];
+			haar1(x, n0 >> k, 1 << k);
+		}
+		blocks <<= recombine;
+
+		// scaled for folding into later bands
+		if let Some(out) = lowband_out {
+			let scale = (n0 as f32).sqrt();
+			for (out, &value) in out.iter_mut().zip(x.iter()) {
+				*out = scale * value;
+			}
+		}
+		cm & ((1 << blocks) - 1)
+	}
+
+	fn quant_band_stereo(
+		&mut self,
+		x: &mut [f32],
+		y: &mut [f32],
+		lowband: Option<&mut [f32]>,
+		lowband_out: Option<&mut [f32]>,
+		band: Band,
+	) -> u32 {
+		let n = x.len();
+		if n == 1 {
+			return self.quant_band_n1(x, Some(y), lowband_out);
+		}
+		let orig_fill = band.fill;
+		let mut band = band;
+		let blocks = band.blocks;
+		let split = self.compute_theta(n, &mut band, blocks, true);
+		let mid = (1.0 / 32768.0) * split.imid as f32;
+		let side = (1.0 / 32768.0) * split.iside as f32;
+		let itheta = split.itheta;
+		let b = band.bits;
+
+		let cm;
+		if n == 2 {
+			// mid and side are orthogonal, so the side only needs a sign
+			let sbits = if itheta != 0 && itheta != 16384 { 1 << BITRES } else { 0 };
+			let mbits = b - sbits;
+			self.remaining_bits -= split.qalloc + sbits;
+			let sign = if sbits != 0 { self.rc.decode_bits(1) as i32 } else { 0 };
+			let sign = (1 - 2 * sign) as f32;
+
+			let (x2, y2) = if itheta > 8192 { (&mut *y, &mut *x) } else { (&mut *x, &mut *y) };
+			let mid_band = Band { bits: mbits, fill: orig_fill, ..band };
+			cm = self.quant_band(x2, lowband, lowband_out, 1.0, mid_band);
+			y2[0] = -sign * x2[1];
+			y2[1] = sign * x2[0];
+
+			for j in 0..2 {
+				let (l, r) = (mid * x[j], side * y[j]);
+				x[j] = l - r;
+				y[j] = l + r;
+			}
+		} else {
+			let mut mbits = b.min((b - split.delta) / 2).max(0);
+			let mut sbits = b - mbits;
+			self.remaining_bits -= split.qalloc;
+
+			// the mid stays normalised for folding; the side never folds
+			let side_band = |bits| Band { bits, fill: band.fill >> band.blocks, ..band };
+			let rebalance = self.remaining_bits;
+			if mbits >= sbits {
+				let mut mask = self.quant_band(x, lowband, lowband_out, 1.0, Band { bits: mbits, ..band });
+				let rebalance = mbits - (rebalance - self.remaining_bits);
+				if rebalance > 3 << BITRES && itheta != 0 {
+					sbits += rebalance - (3 << BITRES);
+				}
+				mask |= self.quant_band(y, None, None, side, side_band(sbits));
+				cm = mask;
+			} else {
+				let mut mask = self.quant_band(y, None, None, side, side_band(sbits));
+				let rebalance = sbits - (rebalance - self.remaining_bits);
+				if rebalance > 3 << BITRES && itheta != 16384 {
+					mbits += rebalance - (3 << BITRES);
+				}
+				mask |= self.quant_band(x, lowband, lowband_out, 1.0, Band { bits: mbits, ..band });
+				cm = mask;
+			}
+			stereo_merge(x, y, mid);
+		}
+
+		if split.inv {
+			y.iter_mut().for_each(|v| *v = -*v);
+		}
+		cm
+	}
+}
+
+/// Frame wide parameters of the band shape decoding.
+pub struct BandConfig<'a> {
+	pub layout: Layout,
+	pub allocation: &'a Allocation,
+	pub tf_res: &'a [i32; NB_BANDS],
+	pub short_blocks: bool,
+	pub spread: usize,
+	pub total_bits: i32,
+	pub disable_inv: bool,
+}
+
+/// Copies the folding source of a band out of the normalised history.
+fn fold_source<'a>(
+	buffer: &'a mut [f32; MAX_BAND_SIZE],
+	norm: &[f32],
+	lowband: Option<usize>,
+	n: usize,
+) -> Option<&'a mut [f32]> {
+	let start = lowband?;
+	buffer[..n].copy_from_slice(&norm[start..start + n]);
+	Some(&mut buffer[..n])
+}
+
+/// Decodes the normalised shape of every coded band into `x` and, for
+/// stereo frames, `y`, recording which blocks got any energy.
+pub fn quant_all_bands(
+	rc: &mut RangeDecoder,
+	config: &BandConfig,
+	x: &mut [f32],
+	mut y: Option<&mut [f32]>,
+	collapse_masks: &mut [u8],
+	seed: &mut u32,
+) {
+	let Layout { start, end, channels, lm } = config.layout;
+	let allocation = config.allocation;
+	let m = 1 << lm;
+	let blocks = if config.short_blocks { m } else { 1 };
+	let norm_offset = m * EBANDS[start];
+	let norm_len = m * EBANDS[NB_BANDS - 1] - norm_offset;
+	let mut norm = vec![0f32; norm_len];
+	let mut norm2 = vec![0f32; if channels == 2 { norm_len } else { 0 }];
+
+	let mut ctx = BandContext {
+		rc,
+		band: start,
+		intensity: allocation.intensity,
+		spread: config.spread,
+		tf_change: 0,
+		remaining_bits: 0,
+		seed: *seed,
+		disable_inv: config.disable_inv,
+	};
+	let coded_bands = allocation.coded_bands;
+	let mut dual_stereo = allocation.dual_stereo;
+	let mut balance = allocation.balance;
+	let mut lowband_offset = 0;
+	let mut update_lowband = true;
+
+	for i in start..end {
+		ctx.band = i;
+		let last = i == end - 1;
+		let band_start = m * EBANDS[i];
+		let n = m * EBANDS[i + 1] - band_start;
+
+		let tell = ctx.rc.tell_frac() as i32;
+		if i != start {
+			balance -= tell;
+		}
+		let remaining_bits = config.total_bits - tell - 1;
+		ctx.remaining_bits = remaining_bits;
+		let b = if i < coded_bands {
+			let curr_balance = balance / (coded_bands - i).min(3) as i32;
+			(remaining_bits + 1).min(allocation.pulses[i] + curr_balance).clamp(0, 16383)
+		} else {
+			0
+		};
+
+		if (band_start as i32 - n as i32 >= norm_offset as i32 || i == start + 1)
+			&& (update_lowband || lowband_offset == 0)
+		{
+			lowband_offset = i;
+		}
+		if i == start + 1 {
+			// duplicate enough of the first band to fold the second, which only
+			// copies anything in hybrid frames
+			let n1 = m * (EBANDS[start + 1] - EBANDS[start]);
+			let n2 = m * (EBANDS[start + 2] - EBANDS[start + 1]);
+			if n2 > n1 {
+				norm.copy_within(2 * n1 - n2..n1, n1);
+				if dual_stereo {
+					norm2.copy_within(2 * n1 - n2..n1, n1);
+				}
+			}
+		}
+
+		ctx.tf_change = config.tf_res[i];
+		let mut effective_lowband = None;
+		let (mut x_cm, mut y_cm);
+		if lowband_offset != 0
+			&& (config.spread != SPREAD_AGGRESSIVE || blocks > 1 || ctx.tf_change < 0)
+		{
+			// the collapse masks of the bands folded from, conservatively
+			let effective = (m * EBANDS[lowband_offset]).saturating_sub(norm_offset + n);
+			let mut fold_start = lowband_offset - 1;
+			while m * EBANDS[fold_start] > effective + norm_offset {
+				fold_start -= 1;
+			}
+			let mut fold_end = lowband_offset;
+			while fold_end < i && m * EBANDS[fold_end] < effective + norm_offset + n {
+				fold_end += 1;
+			}
+			x_cm = 0;
+			y_cm = 0;
+			for fold in fold_start..fold_end.max(fold_start + 1) {
+				x_cm |= collapse_masks[fold * channels] as u32;
+				y_cm |= collapse_masks[fold * channels + channels - 1] as u32;
+			}
+			effective_lowband = Some(effective);
+		} else {
+			x_cm = (1 << blocks) - 1;
+			y_cm = x_cm;
+		}
+
+		if dual_stereo && i == allocation.intensity {
+			// dual stereo switches off for the intensity coded bands
+			dual_stereo = false;
+			for (l, r) in norm[..band_start - norm_offset].iter_mut().zip(&norm2) {
+				*l = 0.5 * (*l + r);
+			}
+		}
+
+		let mut fold_buffer = [0f32; MAX_BAND_SIZE];
+		let out_range = band_start - norm_offset..band_start - norm_offset + n;
+		let xb = &mut x[band_start..band_start + n];
+		let band = Band { bits: b, blocks, lm: lm as i32, fill: 0 };
+		if dual_stereo {
+			let yb =
+				&mut y.as_deref_mut().expect("dual stereo needs two channels")[band_start..band_start + n];
+			let lowband = fold_source(&mut fold_buffer, &norm, effective_lowband, n);
+			let out = if last { None } else { Some(&mut norm[out_range.clone()]) };
+			x_cm = ctx.quant_band(xb, lowband, out, 1.0, Band { bits: b / 2, fill: x_cm, ..band });
+
+			let lowband = fold_source(&mut fold_buffer, &norm2, effective_lowband, n);
+			let out = if last { None } else { Some(&mut norm2[out_range]) };
+			y_cm = ctx.quant_band(yb, lowband, out, 1.0, Band { bits: b / 2, fill: y_cm, ..band });
+		} else {
+			let lowband = fold_source(&mut fold_buffer, &norm, effective_lowband, n);
+			let out = if last { None } else { Some(&mut norm[out_range]) };
+			let band = Band { fill: x_cm | y_cm, ..band };
+			x_cm = match y.as_deref_mut() {
+				Some(y) => {
+					ctx.quant_band_stereo(xb, &mut y[band_start..band_start + n], lowband, out, band)
+				}
+				None => ctx.quant_band(xb, lowband, out, 1.0, band),
+			};
+			y_cm = x_cm;
+		}
+		collapse_masks[i * channels] = x_cm as u8;
+		collapse_masks[i * channels + channels - 1] = y_cm as u8;
+		balance += allocation.pulses[i] + tell;
+
+		// the folding position only moves while there is 1 bit per sample
+		update_lowband = b > (n << BITRES) as i32;
+	}
+	*seed = ctx.seed;
+}
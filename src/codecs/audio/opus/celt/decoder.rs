@@ -0,0 +1,494 @@
+use super::Layout;
+use super::bands::{BandConfig, SPREAD_NORMAL, lcg_rand, quant_all_bands, renormalise};
+use super::energy::{decode_coarse, decode_finalise, decode_fine};
+use super::mdct::Mdct;
+use super::rate::{compute_allocation, init_caps};
+use super::tables::{
+	COMB_GAINS, E_MEANS, EBANDS, NB_BANDS, SPREAD_ICDF, TAPSET_ICDF, TF_SELECT, TRIM_ICDF,
+};
+use crate::codecs::audio::opus::range::{BITRES, RangeDecoder};
+
+pub const SHORT_BLOCK_SIZE: usize = 120;
+pub const OVERLAP: usize = 120;
+const MAX_LM: usize = 3;
+const DECODE_BUFFER_SIZE: usize = 2048;
+const COMBFILTER_MIN_PERIOD: usize = 15;
+const PREEMPHASIS: f32 = 0.850_006_1;
+
+/// Postfilter parameters, a comb filter on the pitch period.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct Postfilter {
+	period: usize,
+	gain: f32,
+	tapset: usize,
+}
+
+/// The CELT layer of an Opus decoder (RFC 6716 section 4.3), always running
+/// at 48 kHz.
+pub struct CeltDecoder {
+	channels: usize,
+	stream_channels: usize,
+	start: usize,
+	end: usize,
+	disable_inv: bool,
+	mdct: Mdct,
+	window: Vec<f32>,
+	rng: u32,
+	postfilter: Postfilter,
+	postfilter_old: Postfilter,
+	preemphasis_mem: [f32; 2],
+	decode_mem: Vec<Vec<f32>>,
+	old_band_e: [f32; 2 * NB_BANDS],
+	old_log_e: [f32; 2 * NB_BANDS],
+	old_log_e2: [f32; 2 * NB_BANDS],
+	background_log_e: [f32; 2 * NB_BANDS],
+}
+
+impl CeltDecoder {
+	pub fn new(channels: usize) -> Self {
+		let window = (0..OVERLAP)
+			.map(|i| {
+				let x = (0.5 * std::f64::consts::PI * (i as f64 + 0.5) / OVERLAP as f64).sin();
+				(0.5 * std::f64::consts::PI * x * x).sin() as f32
+			})
+			.collect();
+		let mut decoder = Self {
+			channels,
+			stream_channels: channels,
+			start: 0,
+			end: NB_BANDS,
+			disable_inv: channels == 1,
+			mdct: Mdct::new((2 * SHORT_BLOCK_SIZE) << MAX_LM),
+			window,
+			rng: 0,
+			postfilter: Postfilter::default(),
+			postfilter_old: Postfilter::default(),
+			preemphasis_mem: [0.0; 2],
+			decode_mem: vec![vec![0.0; DECODE_BUFFER_SIZE + OVERLAP]; channels],
+			old_band_e: [0.0; 2 * NB_BANDS],
+			old_log_e: [0.0; 2 * NB_BANDS],
+			old_log_e2: [0.0; 2 * NB_BANDS],
+			background_log_e: [0.0; 2 * NB_BANDS],
+		};
+		decoder.reset();
+		decoder
+	}
+
+	pub fn reset(&mut self) {
+		self.rng = 0;
+		self.postfilter = Postfilter::default();
+		self.postfilter_old = Postfilter::default();
+		self.preemphasis_mem = [0.0; 2];
+		self.decode_mem.iter_mut().for_each(|mem| mem.fill(0.0));
+		self.old_band_e = [0.0; 2 * NB_BANDS];
+		self.old_log_e = [-28.0; 2 * NB_BANDS];
+		self.old_log_e2 = [-28.0; 2 * NB_BANDS];
+		self.background_log_e = [0.0; 2 * NB_BANDS];
+	}
+
+	/// Restricts decoding to bands `start..end`; hybrid frames start at band 17.
+	pub fn set_bands(&mut self, start: usize, end: usize) {
+		self.start = start;
+		self.end = end;
+	}
+
+	pub fn set_stream_channels(&mut self, channels: usize) {
+		self.stream_channels = channels;
+	}
+
+	pub fn final_range(&self) -> u32 {
+		self.rng
+	}
+
+	/// The MDCT overlap window, which Opus reuses for cross-fades.
+	pub fn window(&self) -> &[f32] {
+		&self.window
+	}
+
+	/// Decodes one frame of `frame_size` samples per channel into `out`,
+	/// interleaved and scaled to ±1. Frames of at most one byte are taken as
+	/// lost and decode to silence.
+	pub fn decode(&mut self, rc: &mut RangeDecoder, out: &mut [f32], frame_size: usize) {
+		if rc.storage() <= 1 {
+			self.decode_frame(&mut RangeDecoder::new(&[]), out, frame_size);
+		} else {
+			self.decode_frame(rc, out, frame_size);
+		}
+	}
+
+	fn decode_frame(&mut self, rc: &mut RangeDecoder, out: &mut [f32], frame_size: usize) {
+		let lm = (frame_size / SHORT_BLOCK_SIZE).trailing_zeros() as usize;
+		let m = 1 << lm;
+		let n = m * SHORT_BLOCK_SIZE;
+		let c = self.stream_channels;
+		let layout = Layout { start: self.start, end: self.end, channels: c, lm };
+		let len = rc.storage() as i32;
+
+		if c == 1 {
+			for i in 0..NB_BANDS {
+				self.old_band_e[i] = self.old_band_e[i].max(self.old_band_e[NB_BANDS + i]);
+			}
+		}
+
+		let mut total_bits = len * 8;
+		let mut tell = rc.tell();
+		let silence = if tell >= total_bits {
+			true
+		} else if tell == 1 {
+			rc.decode_bit_logp(15)
+		} else {
+			false
+		};
+		if silence {
+			// pretend all the remaining bits were read
+			rc.skip_to_end();
+			tell = len * 8;
+		}
+
+		let mut postfilter = Postfilter::default();
+		if layout.start == 0 && tell + 16 <= total_bits {
+			if rc.decode_bit_logp(1) {
+				let octave = rc.decode_uint(6);
+				postfilter.period = ((16 << octave) + rc.decode_bits(4 + octave) - 1) as usize;
+				let qg = rc.decode_bits(3);
+				if rc.tell() + 2 <= total_bits {
+					postfilter.tapset = rc.decode_icdf(&TAPSET_ICDF, 2);
+				}
+				postfilter.gain = 0.09375 * (qg + 1) as f32;
+			}
+			tell = rc.tell();
+		}
+
+		let transient = if lm > 0 && tell + 3 <= total_bits {
+			let transient = rc.decode_bit_logp(3);
+			tell = rc.tell();
+			transient
+		} else {
+			false
+		};
+		let intra = tell + 3 <= total_bits && rc.decode_bit_logp(3);
+		decode_coarse(rc, &mut self.old_band_e, &layout, intra);
+
+		let tf_res = decode_tf(rc, &layout, transient);
+		let spread =
+			if rc.tell() + 4 <= total_bits { rc.decode_icdf(&SPREAD_ICDF, 5) } else { SPREAD_NORMAL };
+
+		// dynamic allocation boosts
+		let caps = init_caps(&layout);
+		let mut offsets = [0; NB_BANDS];
+		let mut dynalloc_logp = 6;
+		total_bits <<= BITRES;
+		let mut tell = rc.tell_frac() as i32;
+		for i in layout.start..layout.end {
+			let width = ((c * (EBANDS[i + 1] - EBANDS[i])) << lm) as i32;
+			// six bits, but no more than one bit per sample and no less than 1/8
+			let quanta = (width << BITRES).min(width.max(6 << BITRES));
+			let mut loop_logp = dynalloc_logp;
+			let mut boost = 0;
+			while tell + (loop_logp << BITRES) < total_bits && boost < caps[i] {
+				let flag = rc.decode_bit_logp(loop_logp as u32);
+				tell = rc.tell_frac() as i32;
+				if !flag {
+					break;
+				}
+				boost += quanta;
+				total_bits -= quanta;
+				loop_logp = 1;
+			}
+			offsets[i] = boost;
+			if boost > 0 {
+				dynalloc_logp = (dynalloc_logp - 1).max(2);
+			}
+		}
+
+		let trim =
+			if tell + (6 << BITRES) <= total_bits { rc.decode_icdf(&TRIM_ICDF, 7) as i32 } else { 5 };
+		let mut bits = ((len * 8) << BITRES) - rc.tell_frac() as i32 - 1;
+		let anti_collapse_rsv =
+			if transient && lm >= 2 && bits >= (lm as i32 + 2) << BITRES { 1 << BITRES } else { 0 };
+		bits -= anti_collapse_rsv;
+		let allocation = compute_allocation(rc, &layout, &offsets, &caps, trim, bits);
+		decode_fine(rc, &mut self.old_band_e, &layout, &allocation);
+
+		for mem in &mut self.decode_mem {
+			mem.copy_within(n..DECODE_BUFFER_SIZE + OVERLAP / 2, 0);
+		}
+
+		let mut spectrum = vec![0f32; c * n];
+		let mut collapse_masks = [0u8; 2 * NB_BANDS];
+		let config = BandConfig {
+			layout,
+			allocation: &allocation,
+			tf_res: &tf_res,
+			short_blocks: transient,
+			spread,
+			total_bits: ((len * 8) << BITRES) - anti_collapse_rsv,
+			disable_inv: self.disable_inv,
+		};
+		let (x, y) = spectrum.split_at_mut(n);
+		let y = if c == 2 { Some(y) } else { None };
+		quant_all_bands(rc, &config, x, y, &mut collapse_masks, &mut self.rng);
+
+		let anti_collapse = anti_collapse_rsv > 0 && rc.decode_bits(1) != 0;
+		let bits_left = len * 8 - rc.tell();
+		decode_finalise(rc, &mut self.old_band_e, &layout, &allocation, bits_left);
+		if anti_collapse {
+			self.anti_collapse(&mut spectrum, &collapse_masks, &layout, &allocation.pulses);
+		}
+		if silence {
+			self.old_band_e = [-28.0; 2 * NB_BANDS];
+		}
+
+		self.synthesis(&spectrum, &layout, transient, silence);
+
+		let short = SHORT_BLOCK_SIZE;
+		let old = self.postfilter_old;
+		let current = self.postfilter;
+		for mem in &mut self.decode_mem {
+			let out_start = DECODE_BUFFER_SIZE - n;
+			comb_filter(mem, out_start, short, old, current, &self.window);
+			if lm != 0 {
+				comb_filter(mem, out_start + short, n - short, current, postfilter, &self.window);
+			}
+		}
+		self.postfilter_old = if lm != 0 { postfilter } else { current };
+		self.postfilter = postfilter;
+
+		if c == 1 {
+			self.old_band_e.copy_within(..NB_BANDS, NB_BANDS);
+		}
+		if !transient {
+			self.old_log_e2 = self.old_log_e;
+			self.old_log_e = self.old_band_e;
+			let increase = m as f32 * 0.001;
+			for (background, &energy) in self.background_log_e.iter_mut().zip(&self.old_band_e) {
+				*background = (*background + increase).min(energy);
+			}
+		} else {
+			for (log_e, &energy) in self.old_log_e.iter_mut().zip(&self.old_band_e) {
+				*log_e = log_e.min(energy);
+			}
+		}
+		for channel in 0..2 {
+			let offset = channel * NB_BANDS;
+			for i in (0..layout.start).chain(layout.end..NB_BANDS) {
+				self.old_band_e[offset + i] = 0.0;
+				self.old_log_e[offset + i] = -28.0;
+				self.old_log_e2[offset + i] = -28.0;
+			}
+		}
+		self.rng = rc.final_range();
+
+		self.deemphasis(out, n);
+	}
+
+	/// Fills the blocks of transient bands that got no pulses with noise, so
+	/// that they do not collapse to silence.
+	fn anti_collapse(
+		&self,
+		spectrum: &mut [f32],
+		masks: &[u8],
+		layout: &Layout,
+		pulses: &[i32; NB_BANDS],
+	) {
+		let Layout { start, end, channels, lm } = *layout;
+		let size = spectrum.len() / channels;
+		let mut seed = self.rng;
+		for i in start..end {
+			let n0 = EBANDS[i + 1] - EBANDS[i];
+			// depth in 1/8 bits
+			let depth = ((1 + pulses[i]) / n0 as i32) >> lm;
+			let thresh = 0.5 * exp2(-0.125 * depth as f32);
+			let sqrt_1 = 1.0 / ((n0 << lm) as f32).sqrt();
+
+			for c in 0..channels {
+				let mut prev1 = self.old_log_e[c * NB_BANDS + i];
+				let mut prev2 = self.old_log_e2[c * NB_BANDS + i];
+				if channels == 1 {
+					prev1 = prev1.max(self.old_log_e[NB_BANDS + i]);
+					prev2 = prev2.max(self.old_log_e2[NB_BANDS + i]);
+				}
+				let ediff = (self.old_band_e[c * NB_BANDS + i] - prev1.min(prev2)).max(0.0);
+				// short blocks don't have the same energy as long ones
+				let mut r = 2.0 * exp2(-ediff);
+				if lm == 3 {
+					r *= std::f32::consts::SQRT_2;
+				}
+				let r = r.min(thresh) * sqrt_1;
+
+				let x = &mut spectrum[c * size + (EBANDS[i] << lm)..][..n0 << lm];
+				let mut renormalize = false;
+				for k in 0..1 << lm {
+					if masks[i * channels + c] & (1 << k) == 0 {
+						for j in 0..n0 {
+							seed = lcg_rand(seed);
+							x[(j << lm) + k] = if seed & 0x8000 != 0 { r } else { -r };
+						}
+						renormalize = true;
+					}
+				}
+				if renormalize {
+					renormalise(x, 1.0);
+				}
+			}
+		}
+	}
+
+	fn synthesis(&mut self, spectrum: &[f32], layout: &Layout, transient: bool, silence: bool) {
+		let lm = layout.lm;
+		let m = 1 << lm;
+		let n = SHORT_BLOCK_SIZE << lm;
+		let (blocks, block_size, shift) =
+			if transient { (m, SHORT_BLOCK_SIZE, MAX_LM) } else { (1, n, MAX_LM - lm) };
+		let out_start = DECODE_BUFFER_SIZE - n;
+		let mut freq = vec![0f32; n];
+
+		let (cc, c) = (self.channels, layout.channels);
+		if cc == 2 && c == 1 {
+			denormalise(&spectrum[..n], &mut freq, &self.old_band_e[..NB_BANDS], layout, silence);
+		} else if cc == 1 && c == 2 {
+			let mut freq2 = vec![0f32; n];
+			denormalise(&spectrum[..n], &mut freq, &self.old_band_e[..NB_BANDS], layout, silence);
+			denormalise(&spectrum[n..], &mut freq2, &self.old_band_e[NB_BANDS..], layout, silence);
+			for (a, b) in freq.iter_mut().zip(&freq2) {
+				*a = 0.5 * *a + 0.5 * b;
+			}
+		}
+
+		for channel in 0..cc {
+			if cc == c {
+				let offset = channel * NB_BANDS;
+				let energy = &self.old_band_e[offset..offset + NB_BANDS];
+				denormalise(&spectrum[channel * n..(channel + 1) * n], &mut freq, energy, layout, silence);
+			}
+			let out = &mut self.decode_mem[channel][out_start..];
+			for b in 0..blocks {
+				self.mdct.backward(&freq[b..], &mut out[block_size * b..], &self.window, shift, blocks);
+			}
+		}
+	}
+
+	fn deemphasis(&mut self, out: &mut [f32], n: usize) {
+		let cc = self.channels;
+		for (channel, mem) in self.decode_mem.iter().enumerate() {
+			let mut m = self.preemphasis_mem[channel];
+			for (j, &x) in mem[DECODE_BUFFER_SIZE - n..DECODE_BUFFER_SIZE].iter().enumerate() {
+				let tmp = x + 1e-30 + m;
+				m = PREEMPHASIS * tmp;
+				out[j * cc + channel] = tmp * (1.0 / 32768.0);
+			}
+			self.preemphasis_mem[channel] = m;
+		}
+	}
+}
+
+fn exp2(x: f32) -> f32 {
+	(std::f64::consts::LN_2 * x as f64).exp() as f32
+}
+
+/// Decodes the per-band time-frequency resolution changes.
+fn decode_tf(rc: &mut RangeDecoder, layout: &Layout, transient: bool) -> [i32; NB_BANDS] {
+	let Layout { start, end, lm, .. } = *layout;
+	let mut tf_res = [0; NB_BANDS];
+	let mut budget = rc.storage() as i32 * 8;
+	let mut tell = rc.tell();
+	let mut logp = if transient { 2 } else { 4 };
+	let select_rsv = lm > 0 && tell + logp < budget;
+	budget -= select_rsv as i32;
+
+	let mut changed = 0;
+	let mut curr = 0;
+	for res in &mut tf_res[start..end] {
+		if tell + logp <= budget {
+			curr ^= rc.decode_bit_logp(logp as u32) as i32;
+			tell = rc.tell();
+			changed |= curr;
+		}
+		*res = curr;
+		logp = if transient { 4 } else { 5 };
+	}
+
+	let table = &TF_SELECT[lm];
+	let base = 4 * transient as usize;
+	let mut select = 0;
+	if select_rsv && table[base + changed as usize] != table[base + 2 + changed as usize] {
+		select = rc.decode_bit_logp(1) as usize;
+	}
+	for res in &mut tf_res[start..end] {
+		*res = table[base + 2 * select + *res as usize];
+	}
+	tf_res
+}
+
+/// Scales the normalised bands of one channel by their energies.
+fn denormalise(x: &[f32], freq: &mut [f32], energy: &[f32], layout: &Layout, silence: bool) {
+	let m = 1 << layout.lm;
+	let (start, end) = if silence { (0, 0) } else { (layout.start, layout.end) };
+	let bound = m * EBANDS[end];
+	freq[..m * EBANDS[start]].fill(0.0);
+	for i in start..end {
+		let gain = exp2((energy[i] + E_MEANS[i]).min(32.0));
+		let band = m * EBANDS[i]..m * EBANDS[i + 1];
+		for (f, &v) in freq[band.clone()].iter_mut().zip(&x[band]) {
+			*f = v * gain;
+		}
+	}
+	freq[bound..].fill(0.0);
+}
+
+/// Runs the pitch postfilter in place over `mem[offset..offset + n]`,
+/// crossfading from `old` to `new` over the window.
+fn comb_filter(
+	mem: &mut [f32],
+	offset: usize,
+	n: usize,
+	old: Postfilter,
+	new: Postfilter,
+	window: &[f32],
+) {
+	if old.gain == 0.0 && new.gain == 0.0 {
+		return;
+	}
+	let t0 = old.period.max(COMBFILTER_MIN_PERIOD);
+	let t1 = new.period.max(COMBFILTER_MIN_PERIOD);
+	let [g00, g01, g02] = COMB_GAINS[old.tapset].map(|g| old.gain * g);
+	let [g10, g11, g12] = COMB_GAINS[new.tapset].map(|g| new.gain * g);
+
+	let mut x1 = mem[offset - t1 + 1];
+	let mut x2 = mem[offset - t1];
+	let mut x3 = mem[offset - t1 - 1];
+	let mut x4 = mem[offset - t1 - 2];
+	// the filter did not change, so no crossfade is needed
+	let overlap =
+		if old.gain == new.gain && t0 == t1 && old.tapset == new.tapset { 0 } else { window.len() };
+	for (i, &w) in window[..overlap].iter().enumerate() {
+		let p = offset + i;
+		let x0 = mem[p - t1 + 2];
+		let f = w * w;
+		let fade = 1.0 - f;
+		mem[p] = mem[p]
+			+ fade * g00 * mem[p - t0]
+			+ fade * g01 * (mem[p - t0 + 1] + mem[p - t0 - 1])
+			+ fade * g02 * (mem[p - t0 + 2] + mem[p - t0 - 2])
+			+ f * g10 * x2
+			+ f * g11 * (x1 + x3)
+			+ f * g12 * (x0 + x4);
+		x4 = x3;
+		x3 = x2;
+		x2 = x1;
+		x1 = x0;
+	}
+	if new.gain == 0.0 {
+		return;
+	}
+
+	for i in overlap..n {
+		let p = offset + i;
+		let x0 = mem[p - t1 + 2];
+		mem[p] = mem[p] + g10 * x2 + g11 * (x1 + x3) + g12 * (x0 + x4);
+		x4 = x3;
+		x3 = x2;
+		x2 = x1;
+		x1 = x0;
+	}
+}
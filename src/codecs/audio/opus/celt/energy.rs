@@ -0,0 +1,126 @@
+use super::tables::{BETA_COEF, BETA_INTRA, E_PROB_MODEL, NB_BANDS, PRED_COEF, SMALL_ENERGY_ICDF};
+use super::{Allocation, Layout};
+use crate::codecs::audio::opus::range::RangeDecoder;
+
+pub const MAX_FINE_BITS: i32 = 8;
+
+/// Decodes the coarse band energies, predicted from the previous frame and
+/// from lower bands, into `energy` in the log2 domain.
+pub fn decode_coarse(
+	rc: &mut RangeDecoder,
+	energy: &mut [f32; 2 * NB_BANDS],
+	layout: &Layout,
+	intra: bool,
+) {
+	let Layout { start, end, channels, lm } = *layout;
+	let model = &E_PROB_MODEL[lm][intra as usize];
+	let (coef, beta) = if intra { (0.0, BETA_INTRA) } else { (PRED_COEF[lm], BETA_COEF[lm]) };
+	let budget = rc.storage() as i32 * 8;
+	let mut prev = [0f32; 2];
+
+	for i in start..end {
+		for c in 0..channels {
+			let tell = rc.tell();
+			let qi = if budget - tell >= 15 {
+				let pi = 2 * i.min(20);
+				decode_laplace(rc, (model[pi] as u32) << 7, (model[pi + 1] as u32) << 6)
+			} else if budget - tell >= 2 {
+				let qi = rc.decode_icdf(&SMALL_ENERGY_ICDF, 2) as i32;
+				(qi >> 1) ^ -(qi & 1)
+			} else if budget - tell >= 1 {
+				-(rc.decode_bit_logp(1) as i32)
+			} else {
+				-1
+			};
+			let q = qi as f32;
+
+			let band = &mut energy[i + c * NB_BANDS];
+			*band = band.max(-9.0);
+			*band = coef * *band + prev[c] + q;
+			prev[c] = prev[c] + q - beta * q;
+		}
+	}
+}
+
+fn decode_laplace(rc: &mut RangeDecoder, mut fs: u32, decay: u32) -> i32 {
+	const MIN_PROBABILITY: u32 = 1;
+	const MIN_DELTAS: u32 = 16;
+
+	let fm = rc.decode_bin(15);
+	let mut value = 0;
+	let mut fl = 0;
+	if fm >= fs {
+		value += 1;
+		fl = fs;
+		let ft = 32768 - MIN_PROBABILITY * (2 * MIN_DELTAS) - fs;
+		fs = ((ft * (16384 - decay)) >> 15) + MIN_PROBABILITY;
+		// the decaying part of the distribution
+		while fs > MIN_PROBABILITY && fm >= fl + 2 * fs {
+			fs *= 2;
+			fl += fs;
+			fs = (((fs - 2 * MIN_PROBABILITY) * decay) >> 15) + MIN_PROBABILITY;
+			value += 1;
+		}
+		// everything beyond has the minimum probability
+		if fs <= MIN_PROBABILITY {
+			let di = (fm - fl) >> 1;
+			value += di as i32;
+			fl += 2 * di * MIN_PROBABILITY;
+		}
+		if fm < fl + fs {
+			value = -value;
+		} else {
+			fl += fs;
+		}
+	}
+	rc.update(fl, (fl + fs).min(32768), 32768);
+	value
+}
+
+pub fn decode_fine(
+	rc: &mut RangeDecoder,
+	energy: &mut [f32; 2 * NB_BANDS],
+	layout: &Layout,
+	allocation: &Allocation,
+) {
+	for i in layout.start..layout.end {
+		let bits = allocation.fine_quant[i];
+		if bits <= 0 {
+			continue;
+		}
+		for c in 0..layout.channels {
+			let q = rc.decode_bits(bits as u32);
+			let offset = (q as f32 + 0.5) * (1 << (14 - bits)) as f32 * (1.0 / 16384.0) - 0.5;
+			energy[i + c * NB_BANDS] += offset;
+		}
+	}
+}
+
+/// Spends the bits left at the end of the frame on one more bit of energy
+/// resolution, in priority order.
+pub fn decode_finalise(
+	rc: &mut RangeDecoder,
+	energy: &mut [f32; 2 * NB_BANDS],
+	layout: &Layout,
+	allocation: &Allocation,
+	mut bits_left: i32,
+) {
+	let Layout { start, end, channels, .. } = *layout;
+	let Allocation { fine_quant, fine_priority, .. } = allocation;
+	for priority in 0..2 {
+		for i in start..end {
+			if bits_left < channels as i32 {
+				break;
+			}
+			if fine_quant[i] >= MAX_FINE_BITS || fine_priority[i] != priority {
+				continue;
+			}
+			for c in 0..channels {
+				let q = rc.decode_bits(1);
+				let offset = (q as f32 - 0.5) * (1 << (14 - fine_quant[i] - 1)) as f32 * (1.0 / 16384.0);
+				energy[i + c * NB_BANDS] += offset;
+				bits_left -= 1;
+			}
+		}
+	}
+}
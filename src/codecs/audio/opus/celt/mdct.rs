@@ -0,0 +1,363 @@
+use std::f64::consts::PI;
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Complex {
+	re: f32,
+	im: f32,
+}
+
+impl Complex {
+	fn new(re: f32, im: f32) -> Self {
+		Self { re, im }
+	}
+
+	fn add(self, other: Self) -> Self {
+		Self::new(self.re + other.re, self.im + other.im)
+	}
+
+	fn sub(self, other: Self) -> Self {
+		Self::new(self.re - other.re, self.im - other.im)
+	}
+
+	fn mul(self, other: Self) -> Self {
+		Self::new(self.re * other.re - self.im * other.im, self.re * other.im + self.im * other.re)
+	}
+
+	fn scale(self, factor: f32) -> Self {
+		Self::new(self.re * factor, self.im * factor)
+	}
+}
+
+/// Mixed radix FFT of one of the sizes sharing the 480 point twiddles.
+struct Fft {
+	size: usize,
+	shift: usize,
+	factors: Vec<(usize, usize)>,
+	bitrev: Vec<usize>,
+}
+
+impl Fft {
+	fn new(size: usize, shift: usize) -> Self {
+		let factors = factor(size);
+		let mut bitrev = vec![0; size];
+		fill_bitrev(0, &mut bitrev, 0, 1, &factors);
+		Self { size, shift, factors, bitrev }
+	}
+}
+
+/// Powers of four first, then two, three and five, in reverse order.
+fn factor(mut size: usize) -> Vec<(usize, usize)> {
+	let mut radices = Vec::new();
+	let mut p = 4;
+	while size > 1 {
+		while !size.is_multiple_of(p) {
+			p = match p {
+				4 => 2,
+				2 => 3,
+				_ => p + 2,
+			};
+			if p * p > size {
+				p = size;
+			}
+		}
+		size /= p;
+		radices.push(p);
+		if p == 2 && radices.len() > 2 {
+			let last = radices.len() - 1;
+			radices[last] = 4;
+			radices[1] = 2;
+		}
+	}
+	radices.reverse();
+
+	let mut remaining: usize = radices.iter().product();
+	radices
+		.into_iter()
+		.map(|p| {
+			remaining /= p;
+			(p, remaining)
+		})
+		.collect()
+}
+
+fn fill_bitrev(
+	out: usize,
+	table: &mut [usize],
+	index: usize,
+	stride: usize,
+	factors: &[(usize, usize)],
+) {
+	let (p, m) = factors[0];
+	let mut index = index;
+	let mut out = out;
+	for j in 0..p {
+		if m == 1 {
+			table[index] = out + j;
+		} else {
+			fill_bitrev(out, table, index, stride * p, &factors[1..]);
+			out += m;
+		}
+		index += stride;
+	}
+}
+
+/// Inverse MDCT of the CELT frame sizes, through an FFT of a quarter the
+/// length.
+pub struct Mdct {
+	size: usize,
+	twiddles: Vec<Complex>,
+	trig: Vec<f32>,
+	ffts: Vec<Fft>,
+	buffer: Vec<Complex>,
+}
+
+impl Mdct {
+	pub const MAX_SHIFT: usize = 3;
+
+	pub fn new(size: usize) -> Self {
+		let base = size / 4;
+		let twiddles = (0..base)
+			.map(|i| {
+				let phase = -2.0 * PI / base as f64 * i as f64;
+				Complex::new(phase.cos() as f32, phase.sin() as f32)
+			})
+			.collect();
+		let ffts = (0..=Self::MAX_SHIFT).map(|shift| Fft::new(base >> shift, shift)).collect();
+
+		let mut trig = Vec::new();
+		for shift in 0..=Self::MAX_SHIFT {
+			let n = size >> shift;
+			trig.extend((0..n / 2).map(|i| (2.0 * PI * (i as f64 + 0.125) / n as f64).cos() as f32));
+		}
+		Self { size, twiddles, trig, ffts, buffer: vec![Complex::default(); base] }
+	}
+
+	/// Runs the inverse transform of `input`, read with `stride`, and folds
+	/// the result into `out`, whose first `window.len()` samples hold the
+	/// overlap of the previous block.
+	pub fn backward(
+		&mut self,
+		input: &[f32],
+		out: &mut [f32],
+		window: &[f32],
+		shift: usize,
+		stride: usize,
+	) {
+		let overlap = window.len();
+		let n = self.size >> shift;
+		let n2 = n / 2;
+		let n4 = n / 4;
+		let trig_start: usize = (0..shift).map(|s| (self.size >> s) / 2).sum();
+		let trig = &self.trig[trig_start..trig_start + n2];
+		let fft = &self.ffts[shift];
+
+		// pre-rotation, stored straight in bit-reversed order; real and
+		// imaginary parts swap because this runs a forward FFT
+		let buffer = &mut self.buffer[..n4];
+		for i in 0..n4 {
+			let x1 = input[2 * i * stride];
+			let x2 = input[stride * (n2 - 1 - 2 * i)];
+			let yr = x2 * trig[i] + x1 * trig[n4 + i];
+			let yi = x1 * trig[i] - x2 * trig[n4 + i];
+			buffer[fft.bitrev[i]] = Complex::new(yi, yr);
+		}
+
+		fft_impl(fft, &self.twiddles, buffer);
+
+		// post-rotation from both ends at once
+		let yp = &mut out[overlap / 2..overlap / 2 + n2];
+		for (i, value) in buffer.iter().enumerate() {
+			yp[2 * i] = value.re;
+			yp[2 * i + 1] = value.im;
+		}
+		let mut low = 0;
+		let mut high = n2 - 2;
+		for i in 0..n4.div_ceil(2) {
+			let (re, im) = (yp[low + 1], yp[low]);
+			let (t0, t1) = (trig[i], trig[n4 + i]);
+			let yr = re * t0 + im * t1;
+			let yi = re * t1 - im * t0;
+			let (re, im) = (yp[high + 1], yp[high]);
+			yp[low] = yr;
+			yp[high + 1] = yi;
+
+			let (t0, t1) = (trig[n4 - i - 1], trig[n2 - i - 1]);
+			let yr = re * t0 + im * t1;
+			let yi = re * t1 - im * t0;
+			yp[high] = yr;
+			yp[low + 1] = yi;
+			low += 2;
+			high = high.wrapping_sub(2);
+		}
+
+		// mirror on both sides for TDAC
+		for i in 0..overlap / 2 {
+			let x1 = out[overlap - 1 - i];
+			let x2 = out[i];
+			let (w1, w2) = (window[i], window[overlap - 1 - i]);
+			out[i] = w2 * x2 - w1 * x1;
+			out[overlap - 1 - i] = w1 * x2 + w2 * x1;
+		}
+	}
+}
+
+fn fft_impl(fft: &Fft, twiddles: &[Complex], data: &mut [Complex]) {
+	let stages = fft.factors.len();
+	let mut strides = vec![1; stages];
+	for i in 1..stages {
+		strides[i] = strides[i - 1] * fft.factors[i - 1].0;
+	}
+
+	for i in (0..stages).rev() {
+		let (p, m) = fft.factors[i];
+		let mm = if i == 0 { 1 } else { fft.factors[i - 1].1 };
+		let count = strides[i];
+		let stride = strides[i] << fft.shift;
+		match p {
+			2 => butterfly2(data, count),
+			3 => butterfly3(data, twiddles, stride, m, count, mm),
+			4 => butterfly4(data, twiddles, stride, m, count, mm),
+			5 => butterfly5(data, twiddles, stride, m, count, mm),
+			_ => unreachable!(),
+		}
+	}
+	debug_assert_eq!(data.len(), fft.size);
+}
+
+// the radix two stage only ever runs with m == 4
+fn butterfly2(data: &mut [Complex], count: usize) {
+	let tw = std::f32::consts::FRAC_1_SQRT_2;
+	for i in 0..count {
+		let f = &mut data[i * 8..i * 8 + 8];
+		let t = f[4];
+		f[4] = f[0].sub(t);
+		f[0] = f[0].add(t);
+
+		let t = Complex::new((f[5].re + f[5].im) * tw, (f[5].im - f[5].re) * tw);
+		f[5] = f[1].sub(t);
+		f[1] = f[1].add(t);
+
+		let t = Complex::new(f[6].im, -f[6].re);
+		f[6] = f[2].sub(t);
+		f[2] = f[2].add(t);
+
+		let t = Complex::new((f[7].im - f[7].re) * tw, -(f[7].im + f[7].re) * tw);
+		f[7] = f[3].sub(t);
+		f[3] = f[3].add(t);
+	}
+}
+
+fn butterfly3(
+	data: &mut [Complex],
+	twiddles: &[Complex],
+	stride: usize,
+	m: usize,
+	count: usize,
+	mm: usize,
+) {
+	let epi3 = twiddles[stride * m];
+	for i in 0..count {
+		let base = i * mm;
+		for k in 0..m {
+			let f = base + k;
+			let s1 = data[f + m].mul(twiddles[k * stride]);
+			let s2 = data[f + 2 * m].mul(twiddles[2 * k * stride]);
+			let s3 = s1.add(s2);
+			let s0 = s1.sub(s2).scale(epi3.im);
+
+			let half = Complex::new(data[f].re - s3.re * 0.5, data[f].im - s3.im * 0.5);
+			data[f] = data[f].add(s3);
+			data[f + 2 * m] = Complex::new(half.re + s0.im, half.im - s0.re);
+			data[f + m] = Complex::new(half.re - s0.im, half.im + s0.re);
+		}
+	}
+}
+
+fn butterfly4(
+	data: &mut [Complex],
+	twiddles: &[Complex],
+	stride: usize,
+	m: usize,
+	count: usize,
+	mm: usize,
+) {
+	if m == 1 {
+		for i in 0..count {
+			let f = &mut data[i * 4..i * 4 + 4];
+			let s0 = f[0].sub(f[2]);
+			f[0] = f[0].add(f[2]);
+			let s1 = f[1].add(f[3]);
+			f[2] = f[0].sub(s1);
+			f[0] = f[0].add(s1);
+			let s1 = f[1].sub(f[3]);
+			f[1] = Complex::new(s0.re + s1.im, s0.im - s1.re);
+			f[3] = Complex::new(s0.re - s1.im, s0.im + s1.re);
+		}
+		return;
+	}
+
+	for i in 0..count {
+		let base = i * mm;
+		for j in 0..m {
+			let f = base + j;
+			let s0 = data[f + m].mul(twiddles[j * stride]);
+			let s1 = data[f + 2 * m].mul(twiddles[2 * j * stride]);
+			let s2 = data[f + 3 * m].mul(twiddles[3 * j * stride]);
+			let s5 = data[f].sub(s1);
+			data[f] = data[f].add(s1);
+			let s3 = s0.add(s2);
+			let s4 = s0.sub(s2);
+			data[f + 2 * m] = data[f].sub(s3);
+			data[f] = data[f].add(s3);
+			data[f + m] = Complex::new(s5.re + s4.im, s5.im - s4.re);
+			data[f + 3 * m] = Complex::new(s5.re - s4.im, s5.im + s4.re);
+		}
+	}
+}
+
+fn butterfly5(
+	data: &mut [Complex],
+	twiddles: &[Complex],
+	stride: usize,
+	m: usize,
+	count: usize,
+	mm: usize,
+) {
+	let ya = twiddles[stride * m];
+	let yb = twiddles[stride * 2 * m];
+	for i in 0..count {
+		let base = i * mm;
+		for u in 0..m {
+			let f0 = base + u;
+			let (f1, f2, f3, f4) = (f0 + m, f0 + 2 * m, f0 + 3 * m, f0 + 4 * m);
+			let s0 = data[f0];
+			let s1 = data[f1].mul(twiddles[u * stride]);
+			let s2 = data[f2].mul(twiddles[2 * u * stride]);
+			let s3 = data[f3].mul(twiddles[3 * u * stride]);
+			let s4 = data[f4].mul(twiddles[4 * u * stride]);
+
+			let s7 = s1.add(s4);
+			let s10 = s1.sub(s4);
+			let s8 = s2.add(s3);
+			let s9 = s2.sub(s3);
+
+			data[f0] = Complex::new(s0.re + (s7.re + s8.re), s0.im + (s7.im + s8.im));
+
+			let s5 = Complex::new(
+				s0.re + (s7.re * ya.re + s8.re * yb.re),
+				s0.im + (s7.im * ya.re + s8.im * yb.re),
+			);
+			let s6 = Complex::new(s10.im * ya.im + s9.im * yb.im, -(s10.re * ya.im + s9.re * yb.im));
+			data[f1] = s5.sub(s6);
+			data[f4] = s5.add(s6);
+
+			let s11 = Complex::new(
+				s0.re + (s7.re * yb.re + s8.re * ya.re),
+				s0.im + (s7.im * yb.re + s8.im * ya.re),
+			);
+			let s12 = Complex::new(s9.im * ya.im - s10.im * yb.im, s10.re * yb.im - s9.re * ya.im);
+			data[f2] = s11.add(s12);
+			data[f3] = s11.sub(s12);
+		}
+	}
+}
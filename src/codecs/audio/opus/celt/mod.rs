@@ -0,0 +1,33 @@
+pub mod bands;
+pub mod decoder;
+pub mod energy;
+pub mod mdct;
+pub mod rate;
+pub mod tables;
+
+pub use decoder::CeltDecoder;
+
+use tables::NB_BANDS;
+
+/// The coded band range and shape of one CELT frame.
+#[derive(Debug, Clone, Copy)]
+pub struct Layout {
+	pub start: usize,
+	pub end: usize,
+	pub channels: usize,
+	/// Log2 of the number of 2.5 ms blocks in the frame.
+	pub lm: usize,
+}
+
+/// Bit allocation of one frame, as decided by `rate::compute_allocation`.
+#[derive(Debug, Clone, Default)]
+pub struct Allocation {
+	pub coded_bands: usize,
+	pub intensity: usize,
+	pub dual_stereo: bool,
+	pub balance: i32,
+	/// Shape bits per band, in 1/8 bits.
+	pub pulses: [i32; NB_BANDS],
+	pub fine_quant: [i32; NB_BANDS],
+	pub fine_priority: [i32; NB_BANDS],
+}
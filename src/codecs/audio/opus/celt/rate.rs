@@ -0,0 +1,348 @@
+use super::energy::MAX_FINE_BITS;
+use super::tables::{
+	BAND_ALLOCATION, CACHE_BITS, CACHE_CAPS, CACHE_INDEX, EBANDS, LOG_N, LOG2_FRAC, NB_BANDS,
+};
+use super::{Allocation, Layout};
+use crate::codecs::audio::opus::range::{BITRES, RangeDecoder};
+
+const ALLOC_STEPS: i32 = 6;
+const FINE_OFFSET: i32 = 21;
+
+fn pulse_cache(band: usize, lm: i32) -> &'static [u8] {
+	let index = CACHE_INDEX[(lm + 1) as usize * NB_BANDS + band];
+	&CACHE_BITS[index as usize..]
+}
+
+/// Largest split-free band size, in 1/8 bits, for `band` at block size `lm`;
+/// `lm` is -1 for the halves of a split 2.5 ms band.
+pub fn max_pulse_bits(band: usize, lm: i32) -> i32 {
+	let cache = pulse_cache(band, lm);
+	cache[cache[0] as usize] as i32
+}
+
+pub fn bits_to_pulses(band: usize, lm: i32, bits: i32) -> i32 {
+	let cache = pulse_cache(band, lm);
+	let bits = bits - 1;
+	let (mut low, mut high) = (0, cache[0] as usize);
+	for _ in 0..6 {
+		let mid = (low + high + 1) >> 1;
+		if cache[mid] as i32 >= bits {
+			high = mid;
+		} else {
+			low = mid;
+		}
+	}
+	let below = if low == 0 { -1 } else { cache[low] as i32 };
+	if bits - below <= cache[high] as i32 - bits { low as i32 } else { high as i32 }
+}
+
+pub fn pulses_to_bits(band: usize, lm: i32, pulses: i32) -> i32 {
+	if pulses == 0 { 0 } else { pulse_cache(band, lm)[pulses as usize] as i32 + 1 }
+}
+
+/// Pulse count of a pseudo-pulse index.
+pub fn pulses(index: i32) -> i32 {
+	if index < 8 { index } else { (8 + (index & 7)) << ((index >> 3) - 1) }
+}
+
+pub fn init_caps(layout: &Layout) -> [i32; NB_BANDS] {
+	let (lm, channels) = (layout.lm, layout.channels);
+	std::array::from_fn(|i| {
+		let n = ((EBANDS[i + 1] - EBANDS[i]) << lm) as i32;
+		let cap = CACHE_CAPS[NB_BANDS * (2 * lm + channels - 1) + i] as i32;
+		((cap + 64) * channels as i32 * n) >> 2
+	})
+}
+
+fn band_width(start: usize, end: usize) -> i32 {
+	(EBANDS[end] - EBANDS[start]) as i32
+}
+
+/// Splits the frame's bit budget between bands (RFC 6716 section 4.3.3),
+/// decoding the band skipping, intensity and dual stereo parameters.
+pub fn compute_allocation(
+	rc: &mut RangeDecoder,
+	layout: &Layout,
+	offsets: &[i32; NB_BANDS],
+	caps: &[i32; NB_BANDS],
+	trim: i32,
+	total: i32,
+) -> Allocation {
+	let Layout { start, end, channels, lm } = *layout;
+	let c = channels as i32;
+	let lm = lm as i32;
+	let mut total = total.max(0);
+
+	let skip_rsv = if total >= 1 << BITRES { 1 << BITRES } else { 0 };
+	total -= skip_rsv;
+	let mut intensity_rsv = 0;
+	let mut dual_stereo_rsv = 0;
+	if channels == 2 {
+		intensity_rsv = LOG2_FRAC[end - start];
+		if intensity_rsv > total {
+			intensity_rsv = 0;
+		} else {
+			total -= intensity_rsv;
+			dual_stereo_rsv = if total >= 1 << BITRES { 1 << BITRES } else { 0 };
+			total -= dual_stereo_rsv;
+		}
+	}
+
+	let mut thresh = [0; NB_BANDS];
+	let mut trim_offset = [0; NB_BANDS];
+	for j in start..end {
+		let width = (EBANDS[j + 1] - EBANDS[j]) as i32;
+		thresh[j] = (c << BITRES).max(((3 * width) << lm << BITRES) >> 4);
+		trim_offset[j] =
+			(c * width * (trim - 5 - lm) * (end - j - 1) as i32 * (1 << (lm + BITRES as i32))) >> 6;
+		if width << lm == 1 {
+			trim_offset[j] -= c << BITRES;
+		}
+	}
+
+	let vector_bits = |vector: usize, j: usize| {
+		let width = (EBANDS[j + 1] - EBANDS[j]) as i32;
+		(c * width * BAND_ALLOCATION[vector][j] as i32 * (1 << lm)) >> 2
+	};
+
+	let (mut low, mut high) = (1, BAND_ALLOCATION.len() as i32 - 1);
+	while low <= high {
+		let mid = (low + high) >> 1;
+		let mut done = false;
+		let mut psum = 0;
+		for j in (start..end).rev() {
+			let mut bits = vector_bits(mid as usize, j);
+			if bits > 0 {
+				bits = (bits + trim_offset[j]).max(0);
+			}
+			bits += offsets[j];
+			if bits >= thresh[j] || done {
+				done = true;
+				psum += bits.min(caps[j]);
+			} else if bits >= c << BITRES {
+				psum += c << BITRES;
+			}
+		}
+		if psum > total {
+			high = mid - 1;
+		} else {
+			low = mid + 1;
+		}
+	}
+	let high = low as usize;
+	let low = high - 1;
+
+	let mut skip_start = start;
+	let mut bits1 = [0; NB_BANDS];
+	let mut bits2 = [0; NB_BANDS];
+	for j in start..end {
+		let mut bits1j = vector_bits(low, j);
+		let mut bits2j = if high >= BAND_ALLOCATION.len() { caps[j] } else { vector_bits(high, j) };
+		if bits1j > 0 {
+			bits1j = (bits1j + trim_offset[j]).max(0);
+		}
+		if bits2j > 0 {
+			bits2j = (bits2j + trim_offset[j]).max(0);
+		}
+		if low > 0 {
+			bits1j += offsets[j];
+		}
+		bits2j += offsets[j];
+		if offsets[j] > 0 {
+			skip_start = j;
+		}
+		bits1[j] = bits1j;
+		bits2[j] = (bits2j - bits1j).max(0);
+	}
+
+	let bounds =
+		Bounds { bits1, bits2, thresh, skip_start, skip_rsv, intensity_rsv, dual_stereo_rsv, total };
+	interpolate(rc, layout, caps, &bounds)
+}
+
+/// The two allocation vectors bracketing the budget, and the reservations
+/// taken out of it.
+struct Bounds {
+	bits1: [i32; NB_BANDS],
+	bits2: [i32; NB_BANDS],
+	thresh: [i32; NB_BANDS],
+	skip_start: usize,
+	skip_rsv: i32,
+	intensity_rsv: i32,
+	dual_stereo_rsv: i32,
+	total: i32,
+}
+
+fn interpolate(
+	rc: &mut RangeDecoder,
+	layout: &Layout,
+	caps: &[i32; NB_BANDS],
+	bounds: &Bounds,
+) -> Allocation {
+	let Layout { start, end, channels, lm } = *layout;
+	let Bounds { bits1, bits2, thresh, mut total, .. } = *bounds;
+	let c = channels as i32;
+	let stereo = (channels > 1) as i32;
+	let alloc_floor = c << BITRES;
+	let log_m = (lm as i32) << BITRES;
+	let mut intensity_rsv = bounds.intensity_rsv;
+	let mut dual_stereo_rsv = bounds.dual_stereo_rsv;
+
+	let (mut low, mut high) = (0, 1 << ALLOC_STEPS);
+	for _ in 0..ALLOC_STEPS {
+		let mid = (low + high) >> 1;
+		let mut psum = 0;
+		let mut done = false;
+		for j in (start..end).rev() {
+			let bits = bits1[j] + ((mid * bits2[j]) >> ALLOC_STEPS);
+			if bits >= thresh[j] || done {
+				done = true;
+				psum += bits.min(caps[j]);
+			} else if bits >= alloc_floor {
+				psum += alloc_floor;
+			}
+		}
+		if psum > total {
+			high = mid;
+		} else {
+			low = mid;
+		}
+	}
+
+	let mut allocation = Allocation::default();
+	let bits = &mut allocation.pulses;
+	let mut psum = 0;
+	let mut done = false;
+	for j in (start..end).rev() {
+		let mut value = bits1[j] + ((low * bits2[j]) >> ALLOC_STEPS);
+		if value < thresh[j] && !done {
+			value = if value >= alloc_floor { alloc_floor } else { 0 };
+		} else {
+			done = true;
+		}
+		value = value.min(caps[j]);
+		bits[j] = value;
+		psum += value;
+	}
+
+	// skip bands from the top down while that frees bits for the others
+	let mut coded_bands = end;
+	loop {
+		let j = coded_bands - 1;
+		if j <= bounds.skip_start {
+			total += bounds.skip_rsv;
+			break;
+		}
+		let mut left = total - psum;
+		let percoeff = left / band_width(start, coded_bands);
+		left -= band_width(start, coded_bands) * percoeff;
+		let rem = (left - band_width(start, j)).max(0);
+		let width = band_width(j, coded_bands);
+		let mut band_bits = bits[j] + percoeff * width + rem;
+		if band_bits >= thresh[j].max(alloc_floor + (1 << BITRES)) {
+			if rc.decode_bit_logp(1) {
+				break;
+			}
+			psum += 1 << BITRES;
+			band_bits -= 1 << BITRES;
+		}
+		psum -= bits[j] + intensity_rsv;
+		if intensity_rsv > 0 {
+			intensity_rsv = LOG2_FRAC[j - start];
+		}
+		psum += intensity_rsv;
+		if band_bits >= alloc_floor {
+			psum += alloc_floor;
+			bits[j] = alloc_floor;
+		} else {
+			bits[j] = 0;
+		}
+		coded_bands -= 1;
+	}
+
+	allocation.intensity = 0;
+	if intensity_rsv > 0 {
+		allocation.intensity = start + rc.decode_uint((coded_bands + 1 - start) as u32) as usize;
+	}
+	if allocation.intensity <= start {
+		total += dual_stereo_rsv;
+		dual_stereo_rsv = 0;
+	}
+	allocation.dual_stereo = dual_stereo_rsv > 0 && rc.decode_bit_logp(1);
+
+	let mut left = total - psum;
+	let percoeff = left / band_width(start, coded_bands);
+	left -= band_width(start, coded_bands) * percoeff;
+	for (j, band_bits) in bits.iter_mut().enumerate().take(coded_bands).skip(start) {
+		*band_bits += percoeff * band_width(j, j + 1);
+	}
+	for (j, band_bits) in bits.iter_mut().enumerate().take(coded_bands).skip(start) {
+		let extra = left.min(band_width(j, j + 1));
+		*band_bits += extra;
+		left -= extra;
+	}
+
+	let ebits = &mut allocation.fine_quant;
+	let priority = &mut allocation.fine_priority;
+	let mut balance = 0;
+	for j in start..coded_bands {
+		let n0 = band_width(j, j + 1);
+		let n = n0 << lm;
+		let bit = bits[j] + balance;
+		let mut excess;
+
+		if n > 1 {
+			excess = (bit - caps[j]).max(0);
+			bits[j] = bit - excess;
+
+			// one more degree of freedom for intensity coded stereo bands
+			let extra_dof = channels == 2 && n > 2 && !allocation.dual_stereo && j < allocation.intensity;
+			let den = c * n + extra_dof as i32;
+			let nc_log_n = den * (LOG_N[j] + log_m);
+			let mut offset = (nc_log_n >> 1) - den * FINE_OFFSET;
+			if n == 2 {
+				offset += den << BITRES >> 2;
+			}
+			if bits[j] + offset < (den * 2) << BITRES {
+				offset += nc_log_n >> 2;
+			} else if bits[j] + offset < (den * 3) << BITRES {
+				offset += nc_log_n >> 3;
+			}
+
+			ebits[j] = (bits[j] + offset + (den << (BITRES - 1))).max(0);
+			ebits[j] = (ebits[j] / den) >> BITRES;
+			if c * ebits[j] > bits[j] >> BITRES {
+				ebits[j] = bits[j] >> stereo >> BITRES;
+			}
+			ebits[j] = ebits[j].min(MAX_FINE_BITS);
+			priority[j] = (ebits[j] * (den << BITRES) >= bits[j] + offset) as i32;
+			bits[j] -= (c * ebits[j]) << BITRES;
+		} else {
+			// a single coefficient only needs its sign
+			excess = (bit - (c << BITRES)).max(0);
+			bits[j] = bit - excess;
+			ebits[j] = 0;
+			priority[j] = 1;
+		}
+
+		// fine energy cannot use the rebalancing in the band decoding
+		if excess > 0 {
+			let extra_fine = (excess >> (stereo + BITRES as i32)).min(MAX_FINE_BITS - ebits[j]);
+			ebits[j] += extra_fine;
+			let extra_bits = (extra_fine * c) << BITRES;
+			priority[j] = (extra_bits >= excess - balance) as i32;
+			excess -= extra_bits;
+		}
+		balance = excess;
+	}
+	allocation.balance = balance;
+
+	// skipped bands spend everything on fine energy
+	for j in coded_bands..end {
+		ebits[j] = bits[j] >> stereo >> BITRES;
+		bits[j] = 0;
+		priority[j] = (ebits[j] < 1) as i32;
+	}
+	allocation.coded_bands = coded_bands;
+	allocation
+}
@@ -0,0 +1,399 @@
+use super::celt::CeltDecoder;
+use super::packet::{Bandwidth, Mode, OpusHead, Toc, parse_packet};
+use super::range::RangeDecoder;
+use super::silk::{self, SilkDecoder};
+use crate::core::frame::{AudioFormat, Channels, Frame, FrameAudio};
+use crate::core::packet::Packet;
+use crate::core::time::Time;
+use crate::core::traits::Decoder;
+use crate::{error, message::Result};
+
+const SAMPLE_RATE: u32 = 48000;
+const F20: usize = 960;
+const F10: usize = 480;
+const F5: usize = 240;
+const F2_5: usize = 120;
+
+/// Opus decoder (RFC 6716) producing 48 kHz PCM16. SILK, CELT and hybrid
+/// frames are decoded, including the redundant CELT frames smoothing mode
+/// switches. Packet loss concealment is not implemented: lost and DTX
+/// frames decode to silence.
+pub struct OpusDecoder {
+	channels: usize,
+	pre_skip: usize,
+	gain: f32,
+	silk: SilkDecoder,
+	celt: CeltDecoder,
+	toc: Option<Toc>,
+	end_band: usize,
+	prev_mode: Option<Mode>,
+	prev_redundancy: bool,
+	softclip_mem: [f32; 2],
+	final_range: u32,
+}
+
+impl OpusDecoder {
+	pub fn new(channels: usize) -> Self {
+		Self {
+			channels,
+			pre_skip: 0,
+			gain: 1.0,
+			silk: SilkDecoder::new(),
+			celt: CeltDecoder::new(channels),
+			toc: None,
+			end_band: Bandwidth::Full.end_band(),
+			prev_mode: None,
+			prev_redundancy: false,
+			softclip_mem: [0.0; 2],
+			final_range: 0,
+		}
+	}
+
+	/// Creates a decoder from the OpusHead of an Ogg or Matroska stream,
+	/// dropping its pre-skip and applying its output gain.
+	pub fn new_from_codec_private(codec_private: &[u8]) -> Result<Self> {
+		let head = OpusHead::parse(codec_private)?;
+		if head.channels > 2 {
+			return Err(error!("opus with {} channels is not supported", head.channels));
+		}
+		let mut decoder = Self::new(head.channels as usize);
+		decoder.pre_skip = head.pre_skip as usize;
+		decoder.gain = 10f32.powf(head.output_gain as f32 / (20.0 * 256.0));
+		Ok(decoder)
+	}
+
+	/// Range coder state after the last frame, for checking conformance
+	/// against the encoder.
+	pub fn final_range(&self) -> u32 {
+		self.final_range
+	}
+
+	/// Decodes a packet, appending interleaved samples in ±1 to `pcm`, and
+	/// returns the number of samples per channel.
+	pub fn decode_packet(&mut self, packet: &[u8], pcm: &mut Vec<f32>) -> Result<usize> {
+		let (toc, frames) = parse_packet(packet)?;
+		self.toc = Some(toc);
+		let channels = self.channels;
+		let start = pcm.len();
+		pcm.resize(start + frames.len() * toc.frame_size * channels, 0.0);
+
+		let mut samples = 0;
+		for frame in frames {
+			samples += self.decode_frame(frame, &mut pcm[start + samples * channels..], toc.frame_size);
+		}
+		soft_clip(&mut pcm[start..], channels, &mut self.softclip_mem);
+		Ok(samples)
+	}
+
+	/// Decodes one frame of the current packet, following `opus_decode_frame`
+	/// of the reference decoder. Frames of at most one byte are lost.
+	fn decode_frame(&mut self, data: &[u8], pcm: &mut [f32], frame_size: usize) -> usize {
+		let channels = self.channels;
+		let Some(toc) = self.toc else {
+			return 0;
+		};
+		let lost = data.len() <= 1;
+		let (mode, bandwidth, audiosize) = if lost {
+			let frame_size = frame_size.min(toc.frame_size);
+			let Some(mode) = self.prev_mode else {
+				pcm[..frame_size * channels].fill(0.0);
+				return frame_size;
+			};
+			// conceal in pieces of at most 20 ms
+			if frame_size > F20 {
+				let mut done = 0;
+				while done < frame_size {
+					let size = (frame_size - done).min(F20);
+					done += self.decode_frame(&[], &mut pcm[done * channels..], size);
+				}
+				return frame_size;
+			}
+			let audiosize = if frame_size < F20 && frame_size > F10 {
+				F10
+			} else if frame_size < F10 && frame_size > F5 && mode != Mode::Silk {
+				F5
+			} else {
+				frame_size
+			};
+			(mode, None, audiosize)
+		} else {
+			(toc.mode, Some(toc.bandwidth), toc.frame_size)
+		};
+		let frame_size = audiosize;
+		let pcm = &mut pcm[..frame_size * channels];
+
+		let mut transition = !lost
+			&& self.prev_mode.is_some_and(|prev| {
+				(mode == Mode::Celt && prev != Mode::Celt && !self.prev_redundancy)
+					|| (mode != Mode::Celt && prev == Mode::Celt)
+			});
+		let mut pcm_transition = Vec::new();
+		if transition && mode == Mode::Celt {
+			pcm_transition = vec![0.0; F5 * channels];
+			self.decode_frame(&[], &mut pcm_transition, F5.min(audiosize));
+		}
+
+		let mut rc = RangeDecoder::new(data);
+		let mut pcm_silk = vec![0i16; F10.max(frame_size) * channels];
+		if mode != Mode::Celt {
+			if self.prev_mode == Some(Mode::Celt) {
+				self.silk.reset();
+			}
+			// lost SILK frames are left silent
+			if !lost {
+				let config = silk::Config {
+					channels: if toc.stereo { 2 } else { 1 },
+					output_channels: channels,
+					fs_khz: match (mode, toc.bandwidth) {
+						(Mode::Silk, Bandwidth::Narrow) => 8,
+						(Mode::Silk, Bandwidth::Medium) => 12,
+						_ => 16,
+					},
+					payload_ms: (audiosize * 1000 / SAMPLE_RATE as usize).max(10),
+				};
+				let mut decoded = 0;
+				while decoded < frame_size {
+					let out = &mut pcm_silk[decoded * channels..];
+					decoded += self.silk.decode(&mut rc, &config, decoded == 0, out);
+				}
+			}
+		}
+
+		let mut len = data.len();
+		let mut redundancy = false;
+		let mut celt_to_silk = false;
+		let mut redundancy_bytes = 0;
+		let hybrid_bits = if mode == Mode::Hybrid { 20 } else { 0 };
+		if !lost && mode != Mode::Celt && rc.tell() + 17 + hybrid_bits <= 8 * len as i32 {
+			// check for a redundant CELT frame smoothing a mode switch
+			redundancy = mode != Mode::Hybrid || rc.decode_bit_logp(12);
+			if redundancy {
+				celt_to_silk = rc.decode_bit_logp(1);
+				redundancy_bytes = match mode {
+					Mode::Hybrid => rc.decode_uint(256) as usize + 2,
+					_ => len - ((rc.tell() + 7) >> 3) as usize,
+				};
+				match len.checked_sub(redundancy_bytes) {
+					Some(rest) if rest as i32 * 8 >= rc.tell() => {
+						len = rest;
+						rc.shrink(len);
+					}
+					_ => {
+						len = 0;
+						redundancy_bytes = 0;
+						redundancy = false;
+					}
+				}
+			}
+		}
+		if redundancy {
+			transition = false;
+		}
+		if transition && mode != Mode::Celt {
+			pcm_transition = vec![0.0; F5 * channels];
+			self.decode_frame(&[], &mut pcm_transition, F5.min(audiosize));
+		}
+
+		if let Some(bandwidth) = bandwidth {
+			self.end_band = bandwidth.end_band();
+		}
+		self.celt.set_stream_channels(if toc.stereo { 2 } else { 1 });
+		let redundant_data = &data[len..len + redundancy_bytes];
+		let mut redundant_audio = vec![0.0; F5 * channels];
+		let mut redundant_rng = 0;
+		if redundancy && celt_to_silk {
+			self.celt.set_bands(0, self.end_band);
+			self.celt.decode(&mut RangeDecoder::new(redundant_data), &mut redundant_audio, F5);
+			redundant_rng = self.celt.final_range();
+		}
+
+		let start_band = if mode == Mode::Celt { 0 } else { 17 };
+		self.celt.set_bands(start_band, self.end_band);
+		if mode != Mode::Silk {
+			if self.prev_mode.is_some_and(|prev| prev != mode) && !self.prev_redundancy {
+				self.celt.reset();
+			}
+			let celt_frame_size = frame_size.min(F20);
+			let out = &mut pcm[..celt_frame_size * channels];
+			match len <= 1 {
+				true => self.celt.decode(&mut RangeDecoder::new(&[]), out, celt_frame_size),
+				false => self.celt.decode(&mut rc, out, celt_frame_size),
+			}
+		} else {
+			pcm.fill(0.0);
+			// a silence frame lets the CELT overlap of a hybrid frame fade out
+			if self.prev_mode == Some(Mode::Hybrid)
+				&& !(redundancy && celt_to_silk && self.prev_redundancy)
+			{
+				self.celt.set_bands(0, self.end_band);
+				let out = &mut pcm[..F2_5 * channels];
+				self.celt.decode(&mut RangeDecoder::new(&[0xFF, 0xFF]), out, F2_5);
+			}
+		}
+
+		if mode != Mode::Celt {
+			for (sample, &silk) in pcm.iter_mut().zip(&pcm_silk) {
+				*sample += (1.0 / 32768.0) * silk as f32;
+			}
+		}
+
+		let window = self.celt.window().to_vec();
+		if redundancy && !celt_to_silk {
+			self.celt.reset();
+			self.celt.set_bands(0, self.end_band);
+			self.celt.decode(&mut RangeDecoder::new(redundant_data), &mut redundant_audio, F5);
+			redundant_rng = self.celt.final_range();
+			let tail = &mut pcm[channels * (frame_size - F2_5)..];
+			let fade_from = tail.to_vec();
+			smooth_fade(&fade_from, &redundant_audio[channels * F2_5..], tail, channels, &window);
+		}
+		if redundancy && celt_to_silk {
+			let head = F2_5 * channels;
+			pcm[..head].copy_from_slice(&redundant_audio[..head]);
+			let fade_to = pcm[head..2 * head].to_vec();
+			smooth_fade(&redundant_audio[head..], &fade_to, &mut pcm[head..], channels, &window);
+		}
+		if transition {
+			let head = F2_5 * channels;
+			if audiosize >= F5 {
+				pcm[..head].copy_from_slice(&pcm_transition[..head]);
+				let fade_to = pcm[head..2 * head].to_vec();
+				smooth_fade(&pcm_transition[head..], &fade_to, &mut pcm[head..], channels, &window);
+			} else {
+				let fade_to = pcm[..head].to_vec();
+				smooth_fade(&pcm_transition, &fade_to, pcm, channels, &window);
+			}
+		}
+
+		if self.gain != 1.0 {
+			pcm.iter_mut().for_each(|sample| *sample *= self.gain);
+		}
+
+		self.final_range = if len <= 1 { 0 } else { rc.final_range() ^ redundant_rng };
+		self.prev_mode = Some(mode);
+		self.prev_redundancy = redundancy && !celt_to_silk;
+		audiosize
+	}
+}
+
+impl Default for OpusDecoder {
+	fn default() -> Self {
+		Self::new(2)
+	}
+}
+
+impl Decoder for OpusDecoder {
+	fn decode(&mut self, packet: Packet) -> Result<Option<Frame>> {
+		if packet.is_empty() {
+			return Ok(None);
+		}
+
+		let mut pcm = Vec::new();
+		let samples = self.decode_packet(&packet.data, &mut pcm)?;
+		let skip = self.pre_skip.min(samples);
+		self.pre_skip -= skip;
+		if skip == samples {
+			return Ok(None);
+		}
+
+		let data =
+			pcm[skip * self.channels..].iter().flat_map(|&x| to_pcm16(x).to_le_bytes()).collect();
+		let pts = packet.pts + packet.time.scale_pts(skip as i64, Time::new(1, SAMPLE_RATE));
+		let channels = Channels::from_count(self.channels as u8);
+		let audio = FrameAudio::new(data, SAMPLE_RATE, channels, AudioFormat::PCM16);
+		Ok(Some(Frame::new_audio(audio, packet.stream_id).with_pts(pts)))
+	}
+
+	fn flush(&mut self) -> Result<Option<Frame>> {
+		Ok(None)
+	}
+}
+
+fn to_pcm16(sample: f32) -> i16 {
+	(sample * 32768.0).clamp(-32768.0, 32767.0).round_ties_even() as i16
+}
+
+/// Cross-fades from `from` to `to` over the first 2.5 ms with the squared
+/// CELT window.
+fn smooth_fade(from: &[f32], to: &[f32], out: &mut [f32], channels: usize, window: &[f32]) {
+	for (i, &w) in window[..F2_5].iter().enumerate() {
+		let w = w * w;
+		for c in 0..channels {
+			let index = i * channels + c;
+			out[index] = w * to[index] + (1.0 - w) * from[index];
+		}
+	}
+}
+
+/// Keeps the output within ±1 by applying a smooth non-linearity to each
+/// excursion between zero crossings (`opus_pcm_soft_clip`). `memory` carries
+/// the curve of an excursion running into the next packet.
+fn soft_clip(pcm: &mut [f32], channels: usize, memory: &mut [f32; 2]) {
+	let n = pcm.len() / channels;
+	if n == 0 {
+		return;
+	}
+	pcm.iter_mut().for_each(|x| *x = x.clamp(-2.0, 2.0));
+	for (c, mem) in memory.iter_mut().enumerate().take(channels) {
+		let x = |i: usize| i * channels + c;
+		let mut a = *mem;
+		for i in 0..n {
+			let v = pcm[x(i)];
+			if v * a >= 0.0 {
+				break;
+			}
+			pcm[x(i)] = v + a * v * v;
+		}
+
+		let mut curr = 0;
+		let x0 = pcm[x(0)];
+		loop {
+			let Some(peak) = (curr..n).find(|&i| pcm[x(i)] > 1.0 || pcm[x(i)] < -1.0) else {
+				a = 0.0;
+				break;
+			};
+			let value = pcm[x(peak)];
+			let mut peak_pos = peak;
+			let mut start = peak;
+			let mut end = peak;
+			let mut max = value.abs();
+			while start > 0 && value * pcm[x(start - 1)] >= 0.0 {
+				start -= 1;
+			}
+			while end < n && value * pcm[x(end)] >= 0.0 {
+				if pcm[x(end)].abs() > max {
+					max = pcm[x(end)].abs();
+					peak_pos = end;
+				}
+				end += 1;
+			}
+			// the excursion began in an earlier packet
+			let special = start == 0 && value * pcm[x(0)] >= 0.0;
+
+			a = (max - 1.0) / (max * max);
+			a += a * 2.4e-7;
+			if value > 0.0 {
+				a = -a;
+			}
+			for i in start..end {
+				let v = pcm[x(i)];
+				pcm[x(i)] = v + a * v * v;
+			}
+
+			if special && peak_pos >= 2 {
+				// ramp from the first sample to the peak to avoid a discontinuity
+				let mut offset = x0 - pcm[x(0)];
+				let delta = offset / peak_pos as f32;
+				for i in curr..peak_pos {
+					offset -= delta;
+					pcm[x(i)] = (pcm[x(i)] + offset).clamp(-1.0, 1.0);
+				}
+			}
+			curr = end;
+			if curr == n {
+				break;
+			}
+		}
+		*mem = a;
+	}
+}
@@ -0,0 +1,7 @@
+pub mod celt;
+pub mod decoder;
+pub mod packet;
+pub mod range;
+pub mod silk;
+
+pub use decoder::OpusDecoder;
@@ -0,0 +1,208 @@
+use crate::{error, message::Result};
+
+/// Most samples per channel a packet may hold, 120 ms at 48 kHz.
+pub const MAX_PACKET_SAMPLES: usize = 5760;
+const MAX_FRAME_BYTES: usize = 1275;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+	Silk,
+	Hybrid,
+	Celt,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Bandwidth {
+	Narrow,
+	Medium,
+	Wide,
+	SuperWide,
+	Full,
+}
+
+impl Bandwidth {
+	/// Last CELT band coded at this bandwidth, plus one.
+	pub fn end_band(self) -> usize {
+		match self {
+			Bandwidth::Narrow => 13,
+			Bandwidth::Medium | Bandwidth::Wide => 17,
+			Bandwidth::SuperWide => 19,
+			Bandwidth::Full => 21,
+		}
+	}
+}
+
+/// The table of contents byte opening every packet (RFC 6716 section 3.1).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Toc {
+	pub mode: Mode,
+	pub bandwidth: Bandwidth,
+	/// Samples per channel of each frame, at 48 kHz.
+	pub frame_size: usize,
+	pub stereo: bool,
+}
+
+impl Toc {
+	pub fn parse(byte: u8) -> Self {
+		let config = (byte >> 3) as usize;
+		let stereo = byte & 0x04 != 0;
+		let (mode, bandwidth, frame_size) = match config {
+			0..=11 => {
+				let bandwidth = [Bandwidth::Narrow, Bandwidth::Medium, Bandwidth::Wide][config / 4];
+				(Mode::Silk, bandwidth, [480, 960, 1920, 2880][config % 4])
+			}
+			12..=15 => {
+				let bandwidth = if config < 14 { Bandwidth::SuperWide } else { Bandwidth::Full };
+				(Mode::Hybrid, bandwidth, [480, 960][config % 2])
+			}
+			_ => {
+				let bandwidth = match (config - 16) / 4 {
+					0 => Bandwidth::Narrow,
+					1 => Bandwidth::Wide,
+					2 => Bandwidth::SuperWide,
+					_ => Bandwidth::Full,
+				};
+				(Mode::Celt, bandwidth, 120 << (config % 4))
+			}
+		};
+		Self { mode, bandwidth, frame_size, stereo }
+	}
+}
+
+fn parse_size(data: &[u8]) -> Option<(usize, usize)> {
+	match *data {
+		[first, ..] if first < 252 => Some((first as usize, 1)),
+		[first, second, ..] => Some((4 * second as usize + first as usize, 2)),
+		_ => None,
+	}
+}
+
+/// Splits a packet into its TOC and compressed frames (RFC 6716 section 3.2).
+pub fn parse_packet(packet: &[u8]) -> Result<(Toc, Vec<&[u8]>)> {
+	let invalid = || error!("opus packet is malformed");
+	let (&toc_byte, mut data) = packet.split_first().ok_or_else(invalid)?;
+	let toc = Toc::parse(toc_byte);
+
+	let mut sizes = Vec::new();
+	let last_size;
+	match toc_byte & 0x03 {
+		0 => last_size = data.len(),
+		1 => {
+			if data.len() % 2 != 0 {
+				return Err(invalid());
+			}
+			sizes.push(data.len() / 2);
+			last_size = data.len() / 2;
+		}
+		2 => {
+			let (size, bytes) = parse_size(data).ok_or_else(invalid)?;
+			data = &data[bytes..];
+			if size > data.len() {
+				return Err(invalid());
+			}
+			sizes.push(size);
+			last_size = data.len() - size;
+		}
+		_ => {
+			let (&header, rest) = data.split_first().ok_or_else(invalid)?;
+			data = rest;
+			let count = (header & 0x3F) as usize;
+			if count == 0 || count * toc.frame_size > MAX_PACKET_SAMPLES {
+				return Err(invalid());
+			}
+
+			if header & 0x40 != 0 {
+				let mut padding = 0;
+				loop {
+					let (&byte, rest) = data.split_first().ok_or_else(invalid)?;
+					data = rest;
+					padding += if byte == 255 { 254 } else { byte as usize };
+					if byte != 255 {
+						break;
+					}
+				}
+				if padding > data.len() {
+					return Err(invalid());
+				}
+				data = &data[..data.len() - padding];
+			}
+
+			if header & 0x80 != 0 {
+				// variable bitrate, all but the last size coded
+				let mut remaining = data.len();
+				for _ in 0..count - 1 {
+					let (size, bytes) = parse_size(data).ok_or_else(invalid)?;
+					data = &data[bytes..];
+					if size > data.len() {
+						return Err(invalid());
+					}
+					sizes.push(size);
+					remaining = remaining.checked_sub(bytes + size).ok_or_else(invalid)?;
+				}
+				last_size = remaining;
+			} else {
+				if data.len() % count != 0 {
+					return Err(invalid());
+				}
+				last_size = data.len() / count;
+				sizes.resize(count - 1, last_size);
+			}
+		}
+	}
+	if last_size > MAX_FRAME_BYTES {
+		return Err(invalid());
+	}
+	sizes.push(last_size);
+
+	let mut frames = Vec::with_capacity(sizes.len());
+	for size in sizes {
+		let (frame, rest) = data.split_at(size);
+		frames.push(frame);
+		data = rest;
+	}
+	Ok((toc, frames))
+}
+
+/// The identification header of an Ogg or Matroska Opus stream (RFC 7845
+/// section 5.1).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OpusHead {
+	pub channels: u8,
+	pub pre_skip: u16,
+	pub input_sample_rate: u32,
+	/// Output gain in Q7.8 dB.
+	pub output_gain: i16,
+	pub mapping_family: u8,
+}
+
+impl OpusHead {
+	pub const MAGIC: &'static [u8; 8] = b"OpusHead";
+
+	pub fn parse(data: &[u8]) -> Result<Self> {
+		if data.len() < 19 || &data[..8] != Self::MAGIC {
+			return Err(error!("opus stream has no OpusHead header"));
+		}
+		if data[8] >> 4 != 0 {
+			return Err(error!("OpusHead version {} is not supported", data[8]));
+		}
+		let head = Self {
+			channels: data[9],
+			pre_skip: u16::from_le_bytes([data[10], data[11]]),
+			input_sample_rate: u32::from_le_bytes([data[12], data[13], data[14], data[15]]),
+			output_gain: i16::from_le_bytes([data[16], data[17]]),
+			mapping_family: data[18],
+		};
+		if head.channels == 0 {
+			return Err(error!("OpusHead has no channels"));
+		}
+		// other families carry several streams, which are not supported
+		let single_stream = match head.mapping_family {
+			0 => head.channels <= 2,
+			_ => data.len() >= 21 + head.channels as usize && data[19] == 1,
+		};
+		if !single_stream {
+			return Err(error!("multistream opus with {} channels is not supported", head.channels));
+		}
+		Ok(head)
+	}
+}
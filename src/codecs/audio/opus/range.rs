@@ -0,0 +1,206 @@
+// RFC 6716 section 4.1: the range decoder shared by SILK and CELT, with raw
+// bits read backwards from the end of the frame.
+
+const SYM_BITS: u32 = 8;
+const CODE_BITS: u32 = 32;
+const SYM_MAX: u32 = (1 << SYM_BITS) - 1;
+const CODE_TOP: u32 = 1 << (CODE_BITS - 1);
+const CODE_BOT: u32 = CODE_TOP >> SYM_BITS;
+const CODE_EXTRA: u32 = (CODE_BITS - 2) % SYM_BITS + 1;
+const UINT_BITS: u32 = 8;
+pub const BITRES: u32 = 3;
+
+pub fn ilog(value: u32) -> i32 {
+	(u32::BITS - value.leading_zeros()) as i32
+}
+
+pub struct RangeDecoder<'a> {
+	data: &'a [u8],
+	offset: usize,
+	end_offset: usize,
+	end_window: u32,
+	end_bits: u32,
+	total_bits: i32,
+	range: u32,
+	value: u32,
+	ext: u32,
+	rem: u32,
+}
+
+impl<'a> RangeDecoder<'a> {
+	pub fn new(data: &'a [u8]) -> Self {
+		let mut decoder = Self {
+			data,
+			offset: 0,
+			end_offset: 0,
+			end_window: 0,
+			end_bits: 0,
+			total_bits: (CODE_BITS + 1 - ((CODE_BITS - CODE_EXTRA) / SYM_BITS) * SYM_BITS) as i32,
+			range: 1 << CODE_EXTRA,
+			value: 0,
+			ext: 0,
+			rem: 0,
+		};
+		decoder.rem = decoder.read_byte();
+		decoder.value = decoder.range - 1 - (decoder.rem >> (SYM_BITS - CODE_EXTRA));
+		decoder.normalize();
+		decoder
+	}
+
+	pub fn storage(&self) -> usize {
+		self.data.len()
+	}
+
+	/// Shrinks the buffer, as hybrid frames do once SILK is done, so that raw
+	/// bits are read from the new end.
+	pub fn shrink(&mut self, storage: usize) {
+		self.data = &self.data[..storage];
+	}
+
+	fn read_byte(&mut self) -> u32 {
+		match self.data.get(self.offset) {
+			Some(&byte) => {
+				self.offset += 1;
+				byte as u32
+			}
+			None => 0,
+		}
+	}
+
+	fn read_byte_from_end(&mut self) -> u32 {
+		if self.end_offset < self.data.len() {
+			self.end_offset += 1;
+			self.data[self.data.len() - self.end_offset] as u32
+		} else {
+			0
+		}
+	}
+
+	fn normalize(&mut self) {
+		while self.range <= CODE_BOT {
+			self.total_bits += SYM_BITS as i32;
+			self.range <<= SYM_BITS;
+			let mut symbol = self.rem;
+			self.rem = self.read_byte();
+			symbol = ((symbol << SYM_BITS) | self.rem) >> (SYM_BITS - CODE_EXTRA);
+			self.value = ((self.value << SYM_BITS) + (SYM_MAX & !symbol)) & (CODE_TOP - 1);
+		}
+	}
+
+	pub fn decode(&mut self, total: u32) -> u32 {
+		self.ext = self.range / total;
+		let symbol = self.value / self.ext;
+		total - (symbol + 1).min(total)
+	}
+
+	pub fn decode_bin(&mut self, bits: u32) -> u32 {
+		self.ext = self.range >> bits;
+		let symbol = self.value / self.ext;
+		(1 << bits) - (symbol + 1).min(1 << bits)
+	}
+
+	pub fn update(&mut self, low: u32, high: u32, total: u32) {
+		let scaled = self.ext.wrapping_mul(total - high);
+		self.value = self.value.wrapping_sub(scaled);
+		self.range =
+			if low > 0 { self.ext.wrapping_mul(high - low) } else { self.range.wrapping_sub(scaled) };
+		self.normalize();
+	}
+
+	/// Decodes a bit whose probability of being one is `1 / (1 << logp)`.
+	pub fn decode_bit_logp(&mut self, logp: u32) -> bool {
+		let scaled = self.range >> logp;
+		let bit = self.value < scaled;
+		if !bit {
+			self.value -= scaled;
+		}
+		self.range = if bit { scaled } else { self.range - scaled };
+		self.normalize();
+		bit
+	}
+
+	/// Decodes a symbol from an inverse cumulative distribution scaled to
+	/// `1 << ftb`.
+	pub fn decode_icdf(&mut self, icdf: &[u8], ftb: u32) -> usize {
+		let mut scaled = self.range;
+		let step = scaled >> ftb;
+		let mut symbol = 0;
+		let mut previous;
+		loop {
+			previous = scaled;
+			scaled = step.wrapping_mul(icdf[symbol] as u32);
+			if self.value >= scaled {
+				break;
+			}
+			symbol += 1;
+		}
+		self.value -= scaled;
+		self.range = previous - scaled;
+		self.normalize();
+		symbol
+	}
+
+	pub fn decode_uint(&mut self, total: u32) -> u32 {
+		let max = total - 1;
+		let bits = ilog(max) as u32;
+		if bits > UINT_BITS {
+			let shift = bits - UINT_BITS;
+			let high = (max >> shift) + 1;
+			let symbol = self.decode(high);
+			self.update(symbol, symbol + 1, high);
+			let value = (symbol << shift) | self.decode_bits(shift);
+			return value.min(max);
+		}
+		let symbol = self.decode(total);
+		self.update(symbol, symbol + 1, total);
+		symbol
+	}
+
+	/// Reads raw bits from the end of the frame.
+	pub fn decode_bits(&mut self, bits: u32) -> u32 {
+		let mut window = self.end_window;
+		let mut available = self.end_bits;
+		if available < bits {
+			loop {
+				window |= self.read_byte_from_end() << available;
+				available += SYM_BITS;
+				if available > u32::BITS - SYM_BITS {
+					break;
+				}
+			}
+		}
+		let value = if bits == 32 { window } else { window & ((1 << bits) - 1) };
+		self.end_window = if bits == 32 { 0 } else { window >> bits };
+		self.end_bits = available - bits;
+		self.total_bits += bits as i32;
+		value
+	}
+
+	/// Accounts for every remaining bit of the frame as read, as CELT does
+	/// for silence frames.
+	pub fn skip_to_end(&mut self) {
+		self.total_bits += self.data.len() as i32 * 8 - self.tell();
+	}
+
+	/// Bits consumed so far, rounded up.
+	pub fn tell(&self) -> i32 {
+		self.total_bits - ilog(self.range)
+	}
+
+	/// Bits consumed so far in 1/8 bit units.
+	pub fn tell_frac(&self) -> u32 {
+		const CORRECTION: [u32; 8] = [35733, 38967, 42495, 46340, 50535, 55109, 60097, 65535];
+		let bits = (self.total_bits as u32) << BITRES;
+		let log = ilog(self.range);
+		let range = self.range >> (log - 16);
+		let mut b = (range >> 12) - 8;
+		b += (range > CORRECTION[b as usize]) as u32;
+		bits - ((log as u32) << 3) - b
+	}
+
+	/// Final range state, which matches the encoder's for a correctly
+	/// decoded frame.
+	pub fn final_range(&self) -> u32 {
+		self.range
+	}
+}
@@ -0,0 +1,282 @@
+use super::MAX_FRAME_LENGTH;
+use super::fixed::{rshift_round, sat16, smlawb, smulbb, smulwb};
+use super::frame::{ChannelDecoder, Coding};
+use super::tables::{
+	LBRR_FLAGS_2_ICDF, LBRR_FLAGS_3_ICDF, STEREO_ONLY_CODE_MID_ICDF, STEREO_PRED_JOINT_ICDF,
+	STEREO_PRED_QUANT_Q13, UNIFORM3_ICDF, UNIFORM5_ICDF,
+};
+use crate::codecs::audio::opus::range::RangeDecoder;
+
+const STEREO_INTERP_LEN_MS: usize = 8;
+
+/// Layout of the SILK layer of the packet being decoded.
+#[derive(Debug, Clone, Copy)]
+pub struct Config {
+	/// Channels coded in the stream.
+	pub channels: usize,
+	/// Channels of the decoder output.
+	pub output_channels: usize,
+	/// Internal sample rate in kHz: 8, 12 or 16.
+	pub fs_khz: usize,
+	/// Duration of the SILK payload: 10, 20, 40 or 60 ms.
+	pub payload_ms: usize,
+}
+
+#[derive(Debug, Clone, Default)]
+struct StereoState {
+	pred_prev_q13: [i32; 2],
+	mid: [i16; 2],
+	side: [i16; 2],
+}
+
+/// SILK decoder producing 48 kHz output (RFC 6716 section 4.2).
+#[derive(Debug, Clone)]
+pub struct SilkDecoder {
+	channels: [ChannelDecoder; 2],
+	stereo: StereoState,
+	output_channels: usize,
+	internal_channels: usize,
+	prev_decode_only_middle: bool,
+}
+
+impl SilkDecoder {
+	pub fn new() -> Self {
+		Self {
+			channels: [ChannelDecoder::new(), ChannelDecoder::new()],
+			stereo: StereoState::default(),
+			output_channels: 0,
+			internal_channels: 0,
+			prev_decode_only_middle: false,
+		}
+	}
+
+	/// Resets the prediction state, keeping the channel layout.
+	pub fn reset(&mut self) {
+		self.channels = [ChannelDecoder::new(), ChannelDecoder::new()];
+		self.stereo = StereoState::default();
+		self.prev_decode_only_middle = false;
+	}
+
+	/// Decodes one SILK frame into `out` as interleaved 48 kHz samples and
+	/// returns the number of samples per channel. `new_packet` marks the
+	/// first frame of a packet, which carries the voice activity and LBRR
+	/// flags of all of them.
+	pub fn decode(
+		&mut self,
+		rc: &mut RangeDecoder,
+		config: &Config,
+		new_packet: bool,
+		out: &mut [i16],
+	) -> usize {
+		let internal = config.channels;
+		let output = config.output_channels;
+		if new_packet {
+			for channel in &mut self.channels[..internal] {
+				channel.frames_decoded = 0;
+			}
+		}
+		if internal > self.internal_channels {
+			self.channels[1] = ChannelDecoder::new();
+		}
+		let stereo_to_mono =
+			internal == 1 && self.internal_channels == 2 && config.fs_khz == self.channels[0].fs_khz;
+
+		let first_frame = self.channels[0].frames_decoded == 0;
+		if first_frame {
+			for channel in &mut self.channels[..internal] {
+				channel.configure(config.fs_khz, config.payload_ms);
+			}
+		}
+		if output == 2 && internal == 2 && (self.output_channels == 1 || self.internal_channels == 1) {
+			self.stereo.pred_prev_q13 = [0; 2];
+			self.stereo.side = [0; 2];
+			self.channels[1].resampler = self.channels[0].resampler.clone();
+		}
+		self.output_channels = output;
+		self.internal_channels = internal;
+
+		let mut decode_only_middle = false;
+		if first_frame {
+			self.decode_flags(rc, internal);
+			decode_only_middle = self.skip_lbrr(rc, internal);
+		}
+
+		let mut pred_q13 = [0; 2];
+		if internal == 2 {
+			pred_q13 = decode_pred(rc);
+			let index = self.channels[0].frames_decoded;
+			decode_only_middle = !self.channels[1].vad_flags[index] && decode_mid_only(rc);
+		}
+		if internal == 2 && !decode_only_middle && self.prev_decode_only_middle {
+			self.channels[1].reset_prediction();
+		}
+
+		let length = self.channels[0].frame_length;
+		let mut buf = [[0i16; MAX_FRAME_LENGTH + 2]; 2];
+		for (n, samples) in buf[..internal].iter_mut().enumerate() {
+			if n == 0 || !decode_only_middle {
+				let index = self.channels[0].frames_decoded - n;
+				let coding = if index == 0 {
+					Coding::Independent
+				} else if n > 0 && self.prev_decode_only_middle {
+					Coding::NoLtpScaling
+				} else {
+					Coding::Conditional
+				};
+				self.channels[n].decode_frame(rc, &mut samples[2..], coding);
+			}
+			self.channels[n].frames_decoded += 1;
+		}
+
+		let [mid, side] = &mut buf;
+		if output == 2 && internal == 2 {
+			self.stereo.ms_to_lr(mid, side, pred_q13, config.fs_khz, length);
+		} else {
+			mid[..2].copy_from_slice(&self.stereo.mid);
+			self.stereo.mid.copy_from_slice(&mid[length..length + 2]);
+		}
+
+		let out_length = length * 48 / config.fs_khz;
+		let mut resampled = [0i16; MAX_FRAME_LENGTH * 6];
+		let resampled = &mut resampled[..out_length];
+		for n in 0..output.min(internal) {
+			self.channels[n].resampler.process(resampled, &buf[n][1..1 + length]);
+			for (frame, &sample) in out.chunks_exact_mut(output).zip(resampled.iter()) {
+				frame[n] = sample;
+			}
+		}
+		if output == 2 && internal == 1 {
+			if stereo_to_mono {
+				// the right channel resampler was running until this frame
+				self.channels[1].resampler.process(resampled, &buf[0][1..1 + length]);
+				for (frame, &sample) in out.chunks_exact_mut(2).zip(resampled.iter()) {
+					frame[1] = sample;
+				}
+			} else {
+				for frame in out[..2 * out_length].chunks_exact_mut(2) {
+					frame[1] = frame[0];
+				}
+			}
+		}
+
+		self.prev_decode_only_middle = decode_only_middle;
+		out_length
+	}
+
+	fn decode_flags(&mut self, rc: &mut RangeDecoder, internal: usize) {
+		for channel in &mut self.channels[..internal] {
+			for flag in &mut channel.vad_flags[..channel.frames_per_packet] {
+				*flag = rc.decode_bit_logp(1);
+			}
+			channel.lbrr_flag = rc.decode_bit_logp(1);
+		}
+		for channel in &mut self.channels[..internal] {
+			channel.lbrr_flags = [false; 3];
+			if !channel.lbrr_flag {
+				continue;
+			}
+			let symbol = match channel.frames_per_packet {
+				1 => 1,
+				2 => rc.decode_icdf(&LBRR_FLAGS_2_ICDF, 8) + 1,
+				_ => rc.decode_icdf(&LBRR_FLAGS_3_ICDF, 8) + 1,
+			};
+			for (i, flag) in channel.lbrr_flags[..channel.frames_per_packet].iter_mut().enumerate() {
+				*flag = (symbol >> i) & 1 != 0;
+			}
+		}
+	}
+
+	/// Reads past the low bitrate redundancy, which only matters for
+	/// concealing lost packets.
+	fn skip_lbrr(&mut self, rc: &mut RangeDecoder, internal: usize) -> bool {
+		let mut decode_only_middle = false;
+		for i in 0..self.channels[0].frames_per_packet {
+			for n in 0..internal {
+				if !self.channels[n].lbrr_flags[i] {
+					continue;
+				}
+				if internal == 2 && n == 0 {
+					decode_pred(rc);
+					if !self.channels[1].lbrr_flags[i] {
+						decode_only_middle = decode_mid_only(rc);
+					}
+				}
+				self.channels[n].skip_lbrr(rc, i);
+			}
+		}
+		decode_only_middle
+	}
+}
+
+impl Default for SilkDecoder {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl StereoState {
+	/// Converts mid/side to left/right in place. Both buffers hold two
+	/// samples of history followed by `length` new ones.
+	fn ms_to_lr(
+		&mut self,
+		mid: &mut [i16],
+		side: &mut [i16],
+		pred_q13: [i32; 2],
+		fs_khz: usize,
+		length: usize,
+	) {
+		mid[..2].copy_from_slice(&self.mid);
+		side[..2].copy_from_slice(&self.side);
+		self.mid.copy_from_slice(&mid[length..length + 2]);
+		self.side.copy_from_slice(&side[length..length + 2]);
+
+		let interp_length = STEREO_INTERP_LEN_MS * fs_khz;
+		let denom_q16 = (1 << 16) / interp_length as i32;
+		let delta0_q13 = rshift_round(smulbb(pred_q13[0] - self.pred_prev_q13[0], denom_q16), 16);
+		let delta1_q13 = rshift_round(smulbb(pred_q13[1] - self.pred_prev_q13[1], denom_q16), 16);
+		let [mut pred0_q13, mut pred1_q13] = self.pred_prev_q13;
+		for n in 0..length {
+			if n < interp_length {
+				pred0_q13 += delta0_q13;
+				pred1_q13 += delta1_q13;
+			} else {
+				[pred0_q13, pred1_q13] = pred_q13;
+			}
+			let sum = (mid[n] as i32 + mid[n + 2] as i32 + ((mid[n + 1] as i32) << 1)) << 9;
+			let sum = smlawb((side[n + 1] as i32) << 8, sum, pred0_q13);
+			let sum = smlawb(sum, (mid[n + 1] as i32) << 11, pred1_q13);
+			side[n + 1] = sat16(rshift_round(sum, 8));
+		}
+		self.pred_prev_q13 = pred_q13;
+
+		for (m, s) in mid[1..=length].iter_mut().zip(&mut side[1..=length]) {
+			let (left, right) = (*m as i32 + *s as i32, *m as i32 - *s as i32);
+			*m = sat16(left);
+			*s = sat16(right);
+		}
+	}
+}
+
+fn decode_pred(rc: &mut RangeDecoder) -> [i32; 2] {
+	let joint = rc.decode_icdf(&STEREO_PRED_JOINT_ICDF, 8);
+	let coarse = [joint / 5, joint % 5];
+	let mut fine = [[0; 2]; 2];
+	for ix in &mut fine {
+		ix[0] = rc.decode_icdf(&UNIFORM3_ICDF, 8);
+		ix[1] = rc.decode_icdf(&UNIFORM5_ICDF, 8);
+	}
+
+	let mut pred_q13 = [0; 2];
+	for n in 0..2 {
+		let index = fine[n][0] + 3 * coarse[n];
+		let low_q13 = STEREO_PRED_QUANT_Q13[index];
+		let step_q13 = smulwb(STEREO_PRED_QUANT_Q13[index + 1] - low_q13, 6554);
+		pred_q13[n] = low_q13 + step_q13 * (2 * fine[n][1] as i32 + 1);
+	}
+	pred_q13[0] -= pred_q13[1];
+	pred_q13
+}
+
+fn decode_mid_only(rc: &mut RangeDecoder) -> bool {
+	rc.decode_icdf(&STEREO_ONLY_CODE_MID_ICDF, 8) == 1
+}
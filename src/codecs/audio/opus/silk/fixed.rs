@@ -0,0 +1,108 @@
+// Fixed-point primitives of the SILK reference implementation. SILK is
+// specified bit-exactly, so these mirror the C macros including their
+// rounding and wrapping behaviour.
+
+pub fn smulbb(a: i32, b: i32) -> i32 {
+	(a as i16 as i32) * (b as i16 as i32)
+}
+
+pub fn smulwb(a: i32, b: i32) -> i32 {
+	((a as i64 * (b as i16) as i64) >> 16) as i32
+}
+
+pub fn smlawb(a: i32, b: i32, c: i32) -> i32 {
+	a.wrapping_add(smulwb(b, c))
+}
+
+pub fn smulww(a: i32, b: i32) -> i32 {
+	((a as i64 * b as i64) >> 16) as i32
+}
+
+pub fn smlaww(a: i32, b: i32, c: i32) -> i32 {
+	a.wrapping_add(smulww(b, c))
+}
+
+pub fn smmul(a: i32, b: i32) -> i32 {
+	((a as i64 * b as i64) >> 32) as i32
+}
+
+pub fn rshift_round(a: i32, shift: u32) -> i32 {
+	if shift == 1 { (a >> 1) + (a & 1) } else { ((a >> (shift - 1)) + 1) >> 1 }
+}
+
+pub fn rshift_round64(a: i64, shift: u32) -> i64 {
+	if shift == 1 { (a >> 1) + (a & 1) } else { ((a >> (shift - 1)) + 1) >> 1 }
+}
+
+pub fn lshift_sat32(a: i32, shift: u32) -> i32 {
+	a.clamp(i32::MIN >> shift, i32::MAX >> shift) << shift
+}
+
+pub fn sat16(a: i32) -> i16 {
+	a.clamp(i16::MIN as i32, i16::MAX as i32) as i16
+}
+
+/// Linear congruential generator driving the excitation signs.
+pub fn rand(seed: i32) -> i32 {
+	907_633_515i32.wrapping_add(seed.wrapping_mul(196_314_165))
+}
+
+fn headroom(a: i32) -> i32 {
+	a.wrapping_abs().leading_zeros() as i32 - 1
+}
+
+/// Approximates `(1 << qres) / b`.
+pub fn inverse32_varq(b: i32, qres: i32) -> i32 {
+	let b_headroom = headroom(b);
+	let b_norm = b << b_headroom;
+	let b_inv = (i32::MAX >> 2) / (b_norm >> 16);
+
+	let result = b_inv << 16;
+	let err_q32 = ((1 << 29) - smulwb(b_norm, b_inv)) << 3;
+	let result = smlaww(result, err_q32, b_inv);
+
+	let shift = 61 - b_headroom - qres;
+	if shift <= 0 {
+		lshift_sat32(result, (-shift) as u32)
+	} else if shift < 32 {
+		result >> shift
+	} else {
+		0
+	}
+}
+
+/// Approximates `(a << qres) / b`.
+pub fn div32_varq(a: i32, b: i32, qres: i32) -> i32 {
+	let a_headroom = headroom(a);
+	let mut a_norm = a << a_headroom;
+	let b_headroom = headroom(b);
+	let b_norm = b << b_headroom;
+	let b_inv = (i32::MAX >> 2) / (b_norm >> 16);
+
+	let result = smulwb(a_norm, b_inv);
+	a_norm = a_norm.wrapping_sub(smmul(b_norm, result).wrapping_shl(3));
+	let result = smlawb(result, a_norm, b_inv);
+
+	let shift = 29 + a_headroom - b_headroom - qres;
+	if shift < 0 {
+		lshift_sat32(result, (-shift) as u32)
+	} else if shift < 32 {
+		result >> shift
+	} else {
+		0
+	}
+}
+
+/// Approximates `2^(x / 128)`.
+pub fn log2lin(log_q7: i32) -> i32 {
+	if log_q7 < 0 {
+		return 0;
+	}
+	if log_q7 >= 3967 {
+		return i32::MAX;
+	}
+	let out = 1 << (log_q7 >> 7);
+	let frac = log_q7 & 0x7F;
+	let curve = smlawb(frac, smulbb(frac, 128 - frac), -174);
+	if log_q7 < 2048 { out + ((out * curve) >> 7) } else { out + (out >> 7) * curve }
+}
@@ -0,0 +1,494 @@
+use super::fixed::{
+	div32_varq, inverse32_varq, log2lin, lshift_sat32, rand, rshift_round, sat16, smlawb, smulwb,
+	smulww,
+};
+use super::lpc::{analysis_filter, nlsf_decode, nlsf_to_lpc, nlsf_unpack};
+use super::resampler::Resampler;
+use super::tables::*;
+use super::{MAX_FRAME_LENGTH, MAX_LPC_ORDER};
+use crate::codecs::audio::opus::range::RangeDecoder;
+
+const MAX_NB_SUBFR: usize = 4;
+const MAX_FRAMES_PER_PACKET: usize = 3;
+const LTP_ORDER: usize = 5;
+const SHELL_BLOCK: usize = 16;
+const MAX_SHELL_BLOCKS: usize = MAX_FRAME_LENGTH / SHELL_BLOCK;
+const MAX_SUBFR_LENGTH: usize = 80;
+const OUT_BUF_LENGTH: usize = MAX_FRAME_LENGTH + 2 * MAX_SUBFR_LENGTH;
+const QUANT_LEVEL_ADJUST_Q10: i32 = 80;
+const NLSF_QUANT_MAX_AMPLITUDE: i32 = 4;
+
+const TYPE_VOICED: usize = 2;
+
+/// How a frame's parameters depend on the previous frame.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Coding {
+	Independent,
+	/// Independent, but without scaling down the long-term prediction state.
+	NoLtpScaling,
+	Conditional,
+}
+
+/// Quantisation indices of one frame, as read from the bitstream.
+#[derive(Debug, Default)]
+struct Indices {
+	signal_type: usize,
+	quant_offset: usize,
+	gains: [i32; MAX_NB_SUBFR],
+	nlsf: [i32; MAX_LPC_ORDER + 1],
+	nlsf_interp: i32,
+	lag_index: i32,
+	contour: usize,
+	per: usize,
+	ltp: [usize; MAX_NB_SUBFR],
+	ltp_scale: usize,
+	seed: i32,
+}
+
+/// Dequantised parameters of one frame.
+#[derive(Debug, Default)]
+struct Control {
+	gains_q16: [i32; MAX_NB_SUBFR],
+	pred_q12: [[i16; MAX_LPC_ORDER]; 2],
+	pitch_lags: [i32; MAX_NB_SUBFR],
+	ltp_coef_q14: [i32; MAX_NB_SUBFR * LTP_ORDER],
+	ltp_scale_q14: i32,
+}
+
+/// Decoder state of one SILK channel, mid or side for stereo streams.
+#[derive(Debug, Clone)]
+pub struct ChannelDecoder {
+	pub fs_khz: usize,
+	pub frames_per_packet: usize,
+	pub frame_length: usize,
+	pub frames_decoded: usize,
+	pub vad_flags: [bool; MAX_FRAMES_PER_PACKET],
+	pub lbrr_flag: bool,
+	pub lbrr_flags: [bool; MAX_FRAMES_PER_PACKET],
+	pub resampler: Resampler,
+	nb_subfr: usize,
+	subfr_length: usize,
+	ltp_mem_length: usize,
+	lpc_order: usize,
+	prev_nlsf_q15: [i32; MAX_LPC_ORDER],
+	s_lpc_q14: [i32; MAX_LPC_ORDER],
+	out_buf: [i16; OUT_BUF_LENGTH],
+	prev_gain_q16: i32,
+	last_gain_index: i32,
+	first_frame_after_reset: bool,
+	ec_prev_signal_type: usize,
+	ec_prev_lag_index: i32,
+}
+
+impl ChannelDecoder {
+	pub fn new() -> Self {
+		Self {
+			fs_khz: 0,
+			frames_per_packet: 0,
+			frame_length: 0,
+			frames_decoded: 0,
+			vad_flags: [false; MAX_FRAMES_PER_PACKET],
+			lbrr_flag: false,
+			lbrr_flags: [false; MAX_FRAMES_PER_PACKET],
+			// replaced as soon as the internal rate is known
+			resampler: Resampler::new(8),
+			nb_subfr: 0,
+			subfr_length: 0,
+			ltp_mem_length: 0,
+			lpc_order: 0,
+			prev_nlsf_q15: [0; MAX_LPC_ORDER],
+			s_lpc_q14: [0; MAX_LPC_ORDER],
+			out_buf: [0; OUT_BUF_LENGTH],
+			prev_gain_q16: 65536,
+			last_gain_index: 0,
+			first_frame_after_reset: true,
+			ec_prev_signal_type: 0,
+			ec_prev_lag_index: 0,
+		}
+	}
+
+	/// Sets the frame layout for a packet of `payload_ms` at `fs_khz`,
+	/// resetting the prediction state when the internal rate changes.
+	pub fn configure(&mut self, fs_khz: usize, payload_ms: usize) {
+		let (frames, nb_subfr) = match payload_ms {
+			20 => (1, 4),
+			40 => (2, 4),
+			60 => (3, 4),
+			_ => (1, 2),
+		};
+		self.frames_per_packet = frames;
+		self.nb_subfr = nb_subfr;
+		self.subfr_length = 5 * fs_khz;
+		self.frame_length = nb_subfr * self.subfr_length;
+		if self.fs_khz == fs_khz {
+			return;
+		}
+		self.resampler = Resampler::new(fs_khz);
+		self.ltp_mem_length = 20 * fs_khz;
+		self.lpc_order = if fs_khz == 16 { 16 } else { 10 };
+		self.fs_khz = fs_khz;
+		self.reset_prediction();
+	}
+
+	/// Clears the prediction memory, as done for the side channel when it
+	/// resumes after mid-only frames.
+	pub fn reset_prediction(&mut self) {
+		self.first_frame_after_reset = true;
+		self.last_gain_index = 10;
+		self.out_buf = [0; OUT_BUF_LENGTH];
+		self.s_lpc_q14 = [0; MAX_LPC_ORDER];
+	}
+
+	fn codebook(&self) -> &'static NlsfCodebook {
+		if self.fs_khz == 16 { &NLSF_CB_WB } else { &NLSF_CB_NB_MB }
+	}
+
+	/// Reads the redundant low bitrate copy of frame `index` without decoding it.
+	pub fn skip_lbrr(&mut self, rc: &mut RangeDecoder, index: usize) {
+		let coding = match index > 0 && self.lbrr_flags[index - 1] {
+			true => Coding::Conditional,
+			false => Coding::Independent,
+		};
+		let indices = self.decode_indices(rc, index, true, coding);
+		let mut pulses = [0i32; MAX_FRAME_LENGTH];
+		decode_pulses(rc, &mut pulses, &indices, self.frame_length);
+	}
+
+	/// Decodes the next frame of the packet into `out`.
+	pub fn decode_frame(&mut self, rc: &mut RangeDecoder, out: &mut [i16], coding: Coding) {
+		let mut indices = self.decode_indices(rc, self.frames_decoded, false, coding);
+		let mut pulses = [0i32; MAX_FRAME_LENGTH];
+		decode_pulses(rc, &mut pulses, &indices, self.frame_length);
+		let ctrl = self.decode_parameters(&mut indices, coding);
+		self.decode_core(&indices, &ctrl, &pulses, &mut out[..self.frame_length]);
+		self.first_frame_after_reset = false;
+
+		let length = self.frame_length;
+		let keep = self.ltp_mem_length - length;
+		self.out_buf.copy_within(length..length + keep, 0);
+		self.out_buf[keep..keep + length].copy_from_slice(&out[..length]);
+	}
+
+	fn decode_indices(
+		&mut self,
+		rc: &mut RangeDecoder,
+		index: usize,
+		lbrr: bool,
+		coding: Coding,
+	) -> Indices {
+		let mut indices = Indices::default();
+		let kind = match lbrr || self.vad_flags[index] {
+			true => rc.decode_icdf(&TYPE_OFFSET_VAD_ICDF, 8) + 2,
+			false => rc.decode_icdf(&TYPE_OFFSET_NO_VAD_ICDF, 8),
+		};
+		indices.signal_type = kind >> 1;
+		indices.quant_offset = kind & 1;
+
+		if coding == Coding::Conditional {
+			indices.gains[0] = rc.decode_icdf(&DELTA_GAIN_ICDF, 8) as i32;
+		} else {
+			let msb = rc.decode_icdf(&GAIN_ICDF[indices.signal_type], 8) as i32;
+			indices.gains[0] = (msb << 3) + rc.decode_icdf(&UNIFORM8_ICDF, 8) as i32;
+		}
+		for gain in &mut indices.gains[1..self.nb_subfr] {
+			*gain = rc.decode_icdf(&DELTA_GAIN_ICDF, 8) as i32;
+		}
+
+		let codebook = self.codebook();
+		let offset = (indices.signal_type >> 1) * codebook.vectors;
+		let vector = rc.decode_icdf(&codebook.cb1_icdf[offset..], 8);
+		indices.nlsf[0] = vector as i32;
+		let (ec_ix, _) = nlsf_unpack(codebook, vector);
+		for (i, &ix) in ec_ix[..codebook.order].iter().enumerate() {
+			let mut value = rc.decode_icdf(&codebook.ec_icdf[ix..], 8) as i32;
+			if value == 0 {
+				value -= rc.decode_icdf(&NLSF_EXT_ICDF, 8) as i32;
+			} else if value == 2 * NLSF_QUANT_MAX_AMPLITUDE {
+				value += rc.decode_icdf(&NLSF_EXT_ICDF, 8) as i32;
+			}
+			indices.nlsf[i + 1] = value - NLSF_QUANT_MAX_AMPLITUDE;
+		}
+		indices.nlsf_interp = match self.nb_subfr {
+			MAX_NB_SUBFR => rc.decode_icdf(&NLSF_INTERPOLATION_FACTOR_ICDF, 8) as i32,
+			_ => 4,
+		};
+
+		if indices.signal_type == TYPE_VOICED {
+			let mut absolute = true;
+			if coding == Coding::Conditional && self.ec_prev_signal_type == TYPE_VOICED {
+				let delta = rc.decode_icdf(&PITCH_DELTA_ICDF, 8) as i32;
+				if delta > 0 {
+					indices.lag_index = self.ec_prev_lag_index + delta - 9;
+					absolute = false;
+				}
+			}
+			if absolute {
+				let low_bits: &[u8] = match self.fs_khz {
+					16 => &UNIFORM8_ICDF,
+					12 => &UNIFORM6_ICDF,
+					_ => &UNIFORM4_ICDF,
+				};
+				let high = rc.decode_icdf(&PITCH_LAG_ICDF, 8) * (self.fs_khz >> 1);
+				indices.lag_index = (high + rc.decode_icdf(low_bits, 8)) as i32;
+			}
+			self.ec_prev_lag_index = indices.lag_index;
+
+			let contour: &[u8] = match (self.fs_khz, self.nb_subfr) {
+				(8, MAX_NB_SUBFR) => &PITCH_CONTOUR_NB_ICDF,
+				(8, _) => &PITCH_CONTOUR_10MS_NB_ICDF,
+				(_, MAX_NB_SUBFR) => &PITCH_CONTOUR_ICDF,
+				_ => &PITCH_CONTOUR_10MS_ICDF,
+			};
+			indices.contour = rc.decode_icdf(contour, 8);
+			indices.per = rc.decode_icdf(&LTP_PER_INDEX_ICDF, 8);
+			for ltp in &mut indices.ltp[..self.nb_subfr] {
+				*ltp = rc.decode_icdf(LTP_GAIN_ICDF[indices.per], 8);
+			}
+			if coding == Coding::Independent {
+				indices.ltp_scale = rc.decode_icdf(&LTP_SCALE_ICDF, 8);
+			}
+		}
+		self.ec_prev_signal_type = indices.signal_type;
+		indices.seed = rc.decode_icdf(&UNIFORM4_ICDF, 8) as i32;
+		indices
+	}
+
+	fn decode_parameters(&mut self, indices: &mut Indices, coding: Coding) -> Control {
+		let mut ctrl = Control::default();
+		let conditional = coding == Coding::Conditional;
+		for k in 0..self.nb_subfr {
+			let prev = &mut self.last_gain_index;
+			if k == 0 && !conditional {
+				*prev = indices.gains[k].max(*prev - 16);
+			} else {
+				let delta = indices.gains[k] - 4;
+				let threshold = 8 + *prev;
+				*prev += if delta > threshold { 2 * delta - threshold } else { delta };
+			}
+			*prev = (*prev).clamp(0, 63);
+			ctrl.gains_q16[k] = log2lin((smulwb(1_907_825, *prev) + 2090).min(3967));
+		}
+
+		let order = self.lpc_order;
+		let nlsf_q15 = nlsf_decode(self.codebook(), &indices.nlsf);
+		ctrl.pred_q12[1] = nlsf_to_lpc(&nlsf_q15[..order]);
+		// no interpolation right after a reset, which also skips re-whitening
+		if self.first_frame_after_reset {
+			indices.nlsf_interp = 4;
+		}
+		let interp = indices.nlsf_interp;
+		if interp < 4 {
+			let mut nlsf0_q15 = [0i32; MAX_LPC_ORDER];
+			for i in 0..order {
+				let prev = self.prev_nlsf_q15[i];
+				nlsf0_q15[i] = prev + ((interp * (nlsf_q15[i] - prev)) >> 2);
+			}
+			ctrl.pred_q12[0] = nlsf_to_lpc(&nlsf0_q15[..order]);
+		} else {
+			ctrl.pred_q12[0] = ctrl.pred_q12[1];
+		}
+		self.prev_nlsf_q15 = nlsf_q15;
+
+		if indices.signal_type == TYPE_VOICED {
+			self.decode_pitch(indices, &mut ctrl.pitch_lags);
+			let codebook = LTP_VQ_Q7[indices.per];
+			for k in 0..self.nb_subfr {
+				for (i, &tap) in codebook[indices.ltp[k]].iter().enumerate() {
+					ctrl.ltp_coef_q14[k * LTP_ORDER + i] = (tap as i32) << 7;
+				}
+			}
+			ctrl.ltp_scale_q14 = LTP_SCALES_Q14[indices.ltp_scale];
+		}
+		ctrl
+	}
+
+	fn decode_pitch(&self, indices: &Indices, lags: &mut [i32; MAX_NB_SUBFR]) {
+		let min_lag = 2 * self.fs_khz as i32;
+		let max_lag = 18 * self.fs_khz as i32;
+		let lag = min_lag + indices.lag_index;
+		let contour = indices.contour;
+		for (k, out) in lags[..self.nb_subfr].iter_mut().enumerate() {
+			let offset = match (self.fs_khz, self.nb_subfr) {
+				(8, MAX_NB_SUBFR) => CB_LAGS_STAGE2[k][contour],
+				(8, _) => CB_LAGS_STAGE2_10MS[k][contour],
+				(_, MAX_NB_SUBFR) => CB_LAGS_STAGE3[k][contour],
+				_ => CB_LAGS_STAGE3_10MS[k][contour],
+			};
+			*out = (lag + offset as i32).clamp(min_lag, max_lag);
+		}
+	}
+
+	/// Runs the excitation through the long-term and short-term synthesis
+	/// filters.
+	fn decode_core(&mut self, indices: &Indices, ctrl: &Control, pulses: &[i32], xq: &mut [i16]) {
+		let length = self.frame_length;
+		let subfr = self.subfr_length;
+		let order = self.lpc_order;
+		let ltp_mem = self.ltp_mem_length;
+		let voiced = indices.signal_type == TYPE_VOICED;
+		let interpolated = indices.nlsf_interp < 4;
+
+		let offset_q10 = QUANTIZATION_OFFSETS_Q10[indices.signal_type >> 1][indices.quant_offset];
+		let mut exc_q14 = [0i32; MAX_FRAME_LENGTH];
+		let mut seed = indices.seed;
+		for (exc, &pulse) in exc_q14[..length].iter_mut().zip(pulses) {
+			seed = rand(seed);
+			let mut value = pulse << 14;
+			if value > 0 {
+				value -= QUANT_LEVEL_ADJUST_Q10 << 4;
+			} else if value < 0 {
+				value += QUANT_LEVEL_ADJUST_Q10 << 4;
+			}
+			value += offset_q10 << 4;
+			*exc = if seed < 0 { -value } else { value };
+			seed = seed.wrapping_add(pulse);
+		}
+
+		let mut s_lpc = [0i32; MAX_SUBFR_LENGTH + MAX_LPC_ORDER];
+		s_lpc[..MAX_LPC_ORDER].copy_from_slice(&self.s_lpc_q14);
+		let mut s_ltp = vec![0i16; ltp_mem];
+		let mut s_ltp_q15 = vec![0i32; ltp_mem + length];
+		let mut ltp_index = ltp_mem;
+		let mut res_q14 = [0i32; MAX_SUBFR_LENGTH];
+
+		for k in 0..self.nb_subfr {
+			let a_q12 = &ctrl.pred_q12[k >> 1][..order];
+			let b_q14 = &ctrl.ltp_coef_q14[k * LTP_ORDER..(k + 1) * LTP_ORDER];
+			let gain_q16 = ctrl.gains_q16[k];
+			let gain_q10 = gain_q16 >> 6;
+			let mut inv_gain_q31 = inverse32_varq(gain_q16, 47);
+
+			let mut gain_adj_q16 = 1 << 16;
+			if gain_q16 != self.prev_gain_q16 {
+				gain_adj_q16 = div32_varq(self.prev_gain_q16, gain_q16, 16);
+				for state in &mut s_lpc[..MAX_LPC_ORDER] {
+					*state = smulww(gain_adj_q16, *state);
+				}
+			}
+			self.prev_gain_q16 = gain_q16;
+
+			let exc = &exc_q14[k * subfr..(k + 1) * subfr];
+			let res = if voiced {
+				let lag = ctrl.pitch_lags[k] as usize;
+				if k == 0 || (k == 2 && interpolated) {
+					// re-whiten the past output with the current predictor
+					let start = ltp_mem - lag - order - LTP_ORDER / 2;
+					if k == 2 {
+						self.out_buf[ltp_mem..ltp_mem + 2 * subfr].copy_from_slice(&xq[..2 * subfr]);
+					}
+					let input = &self.out_buf[start + k * subfr..k * subfr + ltp_mem];
+					analysis_filter(&mut s_ltp[start..], input, a_q12);
+					if k == 0 {
+						inv_gain_q31 = smulwb(inv_gain_q31, ctrl.ltp_scale_q14) << 2;
+					}
+					for i in 0..lag + LTP_ORDER / 2 {
+						s_ltp_q15[ltp_index - i - 1] = smulwb(inv_gain_q31, s_ltp[ltp_mem - i - 1] as i32);
+					}
+				} else if gain_adj_q16 != 1 << 16 {
+					for state in &mut s_ltp_q15[ltp_index - lag - LTP_ORDER / 2..ltp_index] {
+						*state = smulww(gain_adj_q16, *state);
+					}
+				}
+
+				for (i, res) in res_q14[..subfr].iter_mut().enumerate() {
+					let base = ltp_index - lag + LTP_ORDER / 2;
+					let mut pred_q13 = 2;
+					for (j, &tap) in b_q14.iter().enumerate() {
+						pred_q13 = smlawb(pred_q13, s_ltp_q15[base - j], tap);
+					}
+					*res = exc[i].wrapping_add(pred_q13 << 1);
+					s_ltp_q15[ltp_index] = *res << 1;
+					ltp_index += 1;
+				}
+				&res_q14[..subfr]
+			} else {
+				exc
+			};
+
+			for (i, &res) in res.iter().enumerate() {
+				let mut pred_q10 = (order >> 1) as i32;
+				for (j, &coef) in a_q12.iter().enumerate() {
+					pred_q10 = smlawb(pred_q10, s_lpc[MAX_LPC_ORDER + i - 1 - j], coef as i32);
+				}
+				let value = res.saturating_add(lshift_sat32(pred_q10, 4));
+				s_lpc[MAX_LPC_ORDER + i] = value;
+				xq[k * subfr + i] = sat16(rshift_round(smulww(value, gain_q10), 8));
+			}
+			s_lpc.copy_within(subfr..subfr + MAX_LPC_ORDER, 0);
+		}
+		self.s_lpc_q14.copy_from_slice(&s_lpc[..MAX_LPC_ORDER]);
+	}
+}
+
+impl Default for ChannelDecoder {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+/// Decodes the excitation pulses of a frame, `pulses` being at least
+/// `length` rounded up to whole shell blocks.
+fn decode_pulses(rc: &mut RangeDecoder, pulses: &mut [i32], indices: &Indices, length: usize) {
+	let rate_level = rc.decode_icdf(&RATE_LEVELS_ICDF[indices.signal_type >> 1], 8);
+	let blocks = length.div_ceil(SHELL_BLOCK);
+
+	let mut sums = [0usize; MAX_SHELL_BLOCKS];
+	let mut lshifts = [0usize; MAX_SHELL_BLOCKS];
+	for (sum, lshift) in sums[..blocks].iter_mut().zip(&mut lshifts) {
+		*sum = rc.decode_icdf(&PULSES_PER_BLOCK_ICDF[rate_level], 8);
+		while *sum == SHELL_BLOCK + 1 {
+			*lshift += 1;
+			let skip = (*lshift == 10) as usize;
+			*sum = rc.decode_icdf(&PULSES_PER_BLOCK_ICDF[9][skip..], 8);
+		}
+	}
+
+	for (block, &sum) in pulses.chunks_exact_mut(SHELL_BLOCK).zip(&sums[..blocks]) {
+		if sum > 0 {
+			shell_decode(rc, block, sum);
+		} else {
+			block.fill(0);
+		}
+	}
+
+	for (i, block) in pulses.chunks_exact_mut(SHELL_BLOCK).take(blocks).enumerate() {
+		let shift = lshifts[i];
+		if shift == 0 {
+			continue;
+		}
+		for pulse in block.iter_mut() {
+			for _ in 0..shift {
+				*pulse = (*pulse << 1) + rc.decode_icdf(&LSB_ICDF, 8) as i32;
+			}
+		}
+		sums[i] |= shift << 5;
+	}
+
+	let sign_icdf = &SIGN_ICDF[7 * (indices.quant_offset + (indices.signal_type << 1))..];
+	let sign_blocks = (length + SHELL_BLOCK / 2) / SHELL_BLOCK;
+	for (block, &sum) in pulses.chunks_exact_mut(SHELL_BLOCK).zip(&sums[..sign_blocks]) {
+		if sum == 0 {
+			continue;
+		}
+		let icdf = [sign_icdf[(sum & 0x1F).min(6)], 0];
+		for pulse in block.iter_mut().filter(|pulse| **pulse > 0) {
+			*pulse *= 2 * rc.decode_icdf(&icdf, 8) as i32 - 1;
+		}
+	}
+}
+
+/// Splits `total` pulses over `out` by recursive binary partitioning.
+fn shell_decode(rc: &mut RangeDecoder, out: &mut [i32], total: usize) {
+	if out.len() == 1 {
+		out[0] = total as i32;
+		return;
+	}
+	let level = out.len().trailing_zeros() as usize - 1;
+	let left = match total {
+		0 => 0,
+		_ => rc.decode_icdf(&SHELL_CODE_TABLES[level][SHELL_CODE_TABLE_OFFSETS[total]..], 8),
+	};
+	let (first, second) = out.split_at_mut(out.len() / 2);
+	shell_decode(rc, first, left);
+	shell_decode(rc, second, total - left);
+}
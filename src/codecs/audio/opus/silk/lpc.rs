@@ -0,0 +1,279 @@
+use super::MAX_LPC_ORDER;
+use super::fixed::{
+	inverse32_varq, rshift_round, rshift_round64, sat16, smlawb, smmul, smulbb, smulww,
+};
+use super::tables::{LSF_COS_Q12, NlsfCodebook};
+
+const NLSF_QUANT_MAX_AMPLITUDE: i32 = 4;
+const NLSF_QUANT_LEVEL_ADJ_Q10: i32 = 102;
+const MAX_LPC_STABILIZE_ITERATIONS: u32 = 16;
+const MAX_STABILIZE_LOOPS: usize = 20;
+
+/// Entropy table offsets and prediction weights of the second stage for
+/// first stage vector `index`.
+pub fn nlsf_unpack(
+	codebook: &NlsfCodebook,
+	index: usize,
+) -> ([usize; MAX_LPC_ORDER], [i32; MAX_LPC_ORDER]) {
+	let order = codebook.order;
+	let mut ec_ix = [0; MAX_LPC_ORDER];
+	let mut pred_q8 = [0; MAX_LPC_ORDER];
+	let selectors = &codebook.ec_sel[index * order / 2..];
+	for i in (0..order).step_by(2) {
+		let entry = selectors[i / 2] as usize;
+		let stride = (2 * NLSF_QUANT_MAX_AMPLITUDE + 1) as usize;
+		ec_ix[i] = ((entry >> 1) & 7) * stride;
+		pred_q8[i] = codebook.pred_q8[i + (entry & 1) * (order - 1)] as i32;
+		ec_ix[i + 1] = ((entry >> 5) & 7) * stride;
+		pred_q8[i + 1] = codebook.pred_q8[i + ((entry >> 4) & 1) * (order - 1) + 1] as i32;
+	}
+	(ec_ix, pred_q8)
+}
+
+/// Reconstructs the normalised line spectral frequencies in Q15 from the
+/// codebook path, `indices[0]` being the first stage vector.
+pub fn nlsf_decode(codebook: &NlsfCodebook, indices: &[i32]) -> [i32; MAX_LPC_ORDER] {
+	let order = codebook.order;
+	let vector = indices[0] as usize;
+	let (_, pred_q8) = nlsf_unpack(codebook, vector);
+
+	let mut residual_q10 = [0i32; MAX_LPC_ORDER];
+	let mut out_q10 = 0;
+	for i in (0..order).rev() {
+		let pred_q10 = smulbb(out_q10, pred_q8[i]) >> 8;
+		out_q10 = indices[i + 1] << 10;
+		if out_q10 > 0 {
+			out_q10 -= NLSF_QUANT_LEVEL_ADJ_Q10;
+		} else if out_q10 < 0 {
+			out_q10 += NLSF_QUANT_LEVEL_ADJ_Q10;
+		}
+		out_q10 = smlawb(pred_q10, out_q10, codebook.step_q16);
+		residual_q10[i] = out_q10 as i16 as i32;
+	}
+
+	let mut nlsf_q15 = [0i32; MAX_LPC_ORDER];
+	for i in 0..order {
+		let weight = codebook.cb1_weights_q9[vector * order + i] as i32;
+		let base = (codebook.cb1_q8[vector * order + i] as i32) << 7;
+		nlsf_q15[i] = ((residual_q10[i] << 14) / weight + base).clamp(0, 32767);
+	}
+	stabilize(&mut nlsf_q15[..order], codebook.delta_min_q15);
+	nlsf_q15
+}
+
+/// Enforces the minimum spacing `delta_min_q15` between the frequencies.
+fn stabilize(nlsf: &mut [i32], delta_min: &[i32]) {
+	let order = nlsf.len();
+	for _ in 0..MAX_STABILIZE_LOOPS {
+		let mut min_diff = nlsf[0] - delta_min[0];
+		let mut index = 0;
+		for i in 1..order {
+			let diff = nlsf[i] - (nlsf[i - 1] + delta_min[i]);
+			if diff < min_diff {
+				min_diff = diff;
+				index = i;
+			}
+		}
+		let diff = (1 << 15) - (nlsf[order - 1] + delta_min[order]);
+		if diff < min_diff {
+			min_diff = diff;
+			index = order;
+		}
+		if min_diff >= 0 {
+			return;
+		}
+
+		if index == 0 {
+			nlsf[0] = delta_min[0];
+		} else if index == order {
+			nlsf[order - 1] = (1 << 15) - delta_min[order];
+		} else {
+			let min_center = delta_min[..index].iter().sum::<i32>() + (delta_min[index] >> 1);
+			let max_center =
+				(1 << 15) - delta_min[index + 1..].iter().sum::<i32>() - (delta_min[index] >> 1);
+			let center = rshift_round(nlsf[index - 1] + nlsf[index], 1).clamp(min_center, max_center);
+			nlsf[index - 1] = center - (delta_min[index] >> 1);
+			nlsf[index] = nlsf[index - 1] + delta_min[index];
+		}
+	}
+
+	// fall back to sorting and clamping
+	nlsf.sort_unstable();
+	nlsf[0] = nlsf[0].max(delta_min[0]);
+	for i in 1..order {
+		nlsf[i] = nlsf[i].max((nlsf[i - 1] + delta_min[i]).min(i16::MAX as i32));
+	}
+	nlsf[order - 1] = nlsf[order - 1].min((1 << 15) - delta_min[order]);
+	for i in (0..order - 1).rev() {
+		nlsf[i] = nlsf[i].min(nlsf[i + 1] - delta_min[i + 1]);
+	}
+}
+
+const QA: u32 = 16;
+
+fn find_poly(cos_lsf: &[i32], half_order: usize) -> [i32; MAX_LPC_ORDER / 2 + 1] {
+	let mut out = [0i32; MAX_LPC_ORDER / 2 + 1];
+	out[0] = 1 << QA;
+	out[1] = -cos_lsf[0];
+	for k in 1..half_order {
+		let ftmp = cos_lsf[2 * k];
+		out[k + 1] = (out[k - 1] << 1) - rshift_round64(ftmp as i64 * out[k] as i64, QA) as i32;
+		for n in (2..=k).rev() {
+			out[n] += out[n - 2] - rshift_round64(ftmp as i64 * out[n - 1] as i64, QA) as i32;
+		}
+		out[1] -= ftmp;
+	}
+	out
+}
+
+/// Converts line spectral frequencies to Q12 prediction coefficients,
+/// bandwidth expanding until the filter is stable.
+pub fn nlsf_to_lpc(nlsf: &[i32]) -> [i16; MAX_LPC_ORDER] {
+	const ORDERING_16: [usize; 16] = [0, 15, 8, 7, 4, 11, 12, 3, 2, 13, 10, 5, 6, 9, 14, 1];
+	const ORDERING_10: [usize; 10] = [0, 9, 6, 3, 4, 5, 8, 1, 2, 7];
+	let order = nlsf.len();
+	let ordering: &[usize] = if order == 16 { &ORDERING_16 } else { &ORDERING_10 };
+
+	let mut cos_lsf = [0i32; MAX_LPC_ORDER];
+	for (k, &freq) in nlsf.iter().enumerate() {
+		let f_int = (freq >> 8) as usize;
+		let f_frac = freq - ((f_int as i32) << 8);
+		let cos = LSF_COS_Q12[f_int];
+		let delta = LSF_COS_Q12[f_int + 1] - cos;
+		cos_lsf[ordering[k]] = rshift_round((cos << 8) + delta * f_frac, 20 - QA);
+	}
+
+	let half = order / 2;
+	let p = find_poly(&cos_lsf, half);
+	let q = find_poly(&cos_lsf[1..], half);
+	let mut a32_q17 = [0i32; MAX_LPC_ORDER];
+	for k in 0..half {
+		let p_tmp = p[k + 1] + p[k];
+		let q_tmp = q[k + 1] - q[k];
+		a32_q17[k] = -q_tmp - p_tmp;
+		a32_q17[order - k - 1] = q_tmp - p_tmp;
+	}
+
+	let a32_q17 = &mut a32_q17[..order];
+	let mut a_q12 = fit(a32_q17, 12, QA + 1);
+	let mut i = 0;
+	while inverse_pred_gain(&a_q12[..order]) == 0 && i < MAX_LPC_STABILIZE_ITERATIONS {
+		bwexpander_32(a32_q17, 65536 - (2 << i));
+		for (out, &a) in a_q12.iter_mut().zip(a32_q17.iter()) {
+			*out = rshift_round(a, QA + 1 - 12) as i16;
+		}
+		i += 1;
+	}
+	a_q12
+}
+
+/// Limits the coefficients so that they fit 16 bits in Q`q_out`.
+fn fit(a: &mut [i32], q_out: u32, q_in: u32) -> [i16; MAX_LPC_ORDER] {
+	let shift = q_in - q_out;
+	let mut out = [0i16; MAX_LPC_ORDER];
+	let mut iteration = 0;
+	while iteration < 10 {
+		let (index, max_abs) = a
+			.iter()
+			.enumerate()
+			.fold((0, 0), |best, (k, &v)| if v.abs() > best.1 { (k, v.abs()) } else { best });
+		let max_abs = rshift_round(max_abs, shift);
+		if max_abs <= i16::MAX as i32 {
+			break;
+		}
+		let max_abs = max_abs.min(163838);
+		let chirp_q16 =
+			65470 - ((max_abs - i16::MAX as i32) << 14) / ((max_abs * (index as i32 + 1)) >> 2);
+		bwexpander_32(a, chirp_q16);
+		iteration += 1;
+	}
+
+	if iteration == 10 {
+		for (out, a) in out.iter_mut().zip(a.iter_mut()) {
+			*out = sat16(rshift_round(*a, shift));
+			*a = (*out as i32) << shift;
+		}
+	} else {
+		for (out, &a) in out.iter_mut().zip(a.iter()) {
+			*out = rshift_round(a, shift) as i16;
+		}
+	}
+	out
+}
+
+fn bwexpander_32(a: &mut [i32], mut chirp_q16: i32) {
+	let chirp_minus_one_q16 = chirp_q16 - 65536;
+	let last = a.len() - 1;
+	for value in &mut a[..last] {
+		*value = smulww(chirp_q16, *value);
+		chirp_q16 += rshift_round(chirp_q16.wrapping_mul(chirp_minus_one_q16), 16);
+	}
+	a[last] = smulww(chirp_q16, a[last]);
+}
+
+/// Inverse prediction gain in Q30, or zero if the filter is unstable.
+fn inverse_pred_gain(a_q12: &[i16]) -> i32 {
+	const QA: u32 = 24;
+	const A_LIMIT: i32 = 16_773_022;
+	const MIN_INV_GAIN_Q30: i32 = 107_374;
+
+	let order = a_q12.len();
+	let mut a = [0i32; MAX_LPC_ORDER];
+	let mut dc_response = 0;
+	for (a, &coef) in a.iter_mut().zip(a_q12) {
+		dc_response += coef as i32;
+		*a = (coef as i32) << (QA - 12);
+	}
+	if dc_response >= 4096 {
+		return 0;
+	}
+
+	let frac = |a: i32, b: i32| rshift_round64(a as i64 * b as i64, 31) as i32;
+	let mut inv_gain_q30 = 1 << 30;
+	for k in (0..order).rev() {
+		if a[k] > A_LIMIT || a[k] < -A_LIMIT {
+			return 0;
+		}
+		let rc_q31 = -(a[k] << (31 - QA));
+		let rc_mult1_q30 = (1 << 30) - smmul(rc_q31, rc_q31);
+		inv_gain_q30 = smmul(inv_gain_q30, rc_mult1_q30) << 2;
+		if inv_gain_q30 < MIN_INV_GAIN_Q30 {
+			return 0;
+		}
+		if k == 0 {
+			break;
+		}
+
+		let mult2q = 32 - rc_mult1_q30.abs().leading_zeros();
+		let rc_mult2 = inverse32_varq(rc_mult1_q30, mult2q as i32 + 30);
+		for n in 0..(k + 1) >> 1 {
+			let tmp1 = a[n];
+			let tmp2 = a[k - n - 1];
+			let first =
+				rshift_round64(tmp1.saturating_sub(frac(tmp2, rc_q31)) as i64 * rc_mult2 as i64, mult2q);
+			let second =
+				rshift_round64(tmp2.saturating_sub(frac(tmp1, rc_q31)) as i64 * rc_mult2 as i64, mult2q);
+			let (Ok(first), Ok(second)) = (i32::try_from(first), i32::try_from(second)) else {
+				return 0;
+			};
+			a[n] = first;
+			a[k - n - 1] = second;
+		}
+	}
+	inv_gain_q30
+}
+
+/// Whitens `input` with the Q12 predictor `b`; the first `b.len()` outputs
+/// are zero.
+pub fn analysis_filter(out: &mut [i16], input: &[i16], b: &[i16]) {
+	let order = b.len();
+	for ix in order..out.len() {
+		let mut prediction = 0i32;
+		for (j, &coef) in b.iter().enumerate() {
+			prediction = prediction.wrapping_add(smulbb(input[ix - 1 - j] as i32, coef as i32));
+		}
+		let residual = ((input[ix] as i32) << 12).wrapping_sub(prediction);
+		out[ix] = sat16(rshift_round(residual, 12));
+	}
+	out[..order].fill(0);
+}
@@ -0,0 +1,11 @@
+pub mod decoder;
+pub mod fixed;
+pub mod frame;
+pub mod lpc;
+pub mod resampler;
+pub mod tables;
+
+pub use decoder::{Config, SilkDecoder};
+
+const MAX_LPC_ORDER: usize = 16;
+const MAX_FRAME_LENGTH: usize = 320;
@@ -0,0 +1,117 @@
+use super::fixed::{rshift_round, sat16, smlawb, smulbb, smulwb, smulww};
+use super::tables::{RESAMPLER_FRAC_FIR_12, RESAMPLER_UP2_HQ_0, RESAMPLER_UP2_HQ_1};
+
+const ORDER_FIR: usize = 8;
+const MAX_BATCH_MS: usize = 10;
+const OUTPUT_RATE: i32 = 48000;
+
+/// Upsamples the internal 8, 12 or 16 kHz signal to 48 kHz with a 2x
+/// all-pass interpolator followed by a fractional FIR.
+#[derive(Debug, Clone)]
+pub struct Resampler {
+	iir: [i32; 6],
+	fir: [i16; ORDER_FIR],
+	delay: [i16; 16],
+	input_delay: usize,
+	rate_khz: usize,
+	batch: usize,
+	inv_ratio_q16: i32,
+}
+
+impl Resampler {
+	pub fn new(rate_khz: usize) -> Self {
+		let input_delay = match rate_khz {
+			12 => 4,
+			16 => 7,
+			_ => 0,
+		};
+		let rate = rate_khz as i32 * 1000;
+		let mut inv_ratio_q16 = ((rate << 15) / OUTPUT_RATE) << 2;
+		while smulww(inv_ratio_q16, OUTPUT_RATE) < rate << 1 {
+			inv_ratio_q16 += 1;
+		}
+		Self {
+			iir: [0; 6],
+			fir: [0; ORDER_FIR],
+			delay: [0; 16],
+			input_delay,
+			rate_khz,
+			batch: rate_khz * MAX_BATCH_MS,
+			inv_ratio_q16,
+		}
+	}
+
+	/// Resamples one frame, writing `input.len() * 48 / rate_khz` samples.
+	pub fn process(&mut self, out: &mut [i16], input: &[i16]) {
+		let khz = self.rate_khz;
+		let fresh = khz - self.input_delay;
+		self.delay[self.input_delay..khz].copy_from_slice(&input[..fresh]);
+
+		let delay = self.delay;
+		self.interpolate(&mut out[..48], &delay[..khz]);
+		self.interpolate(&mut out[48..], &input[fresh..input.len() - self.input_delay]);
+
+		let tail = input.len() - self.input_delay;
+		self.delay[..self.input_delay].copy_from_slice(&input[tail..]);
+	}
+
+	fn interpolate(&mut self, out: &mut [i16], mut input: &[i16]) {
+		let mut buf = vec![0i16; 2 * self.batch + ORDER_FIR];
+		buf[..ORDER_FIR].copy_from_slice(&self.fir);
+		let mut written = 0;
+		let mut count;
+		loop {
+			count = input.len().min(self.batch);
+			self.up2(&mut buf[ORDER_FIR..ORDER_FIR + 2 * count], &input[..count]);
+
+			let max_index_q16 = (count as i32) << 17;
+			let mut index_q16 = 0;
+			while index_q16 < max_index_q16 {
+				let phase = smulwb(index_q16 & 0xFFFF, 12) as usize;
+				let taps = &buf[(index_q16 >> 16) as usize..];
+				let (head, tail) = (RESAMPLER_FRAC_FIR_12[phase], RESAMPLER_FRAC_FIR_12[11 - phase]);
+				let mut res_q15 = 0;
+				for i in 0..4 {
+					res_q15 += smulbb(taps[i] as i32, head[i]);
+					res_q15 += smulbb(taps[7 - i] as i32, tail[i]);
+				}
+				out[written] = sat16(rshift_round(res_q15, 15));
+				written += 1;
+				index_q16 += self.inv_ratio_q16;
+			}
+
+			input = &input[count..];
+			if input.is_empty() {
+				break;
+			}
+			buf.copy_within(2 * count..2 * count + ORDER_FIR, 0);
+		}
+		self.fir.copy_from_slice(&buf[2 * count..2 * count + ORDER_FIR]);
+	}
+
+	fn up2(&mut self, out: &mut [i16], input: &[i16]) {
+		let state = &mut self.iir;
+		for (k, &sample) in input.iter().enumerate() {
+			let in32 = (sample as i32) << 10;
+			for (phase, coefs) in [RESAMPLER_UP2_HQ_0, RESAMPLER_UP2_HQ_1].iter().enumerate() {
+				let s = &mut state[3 * phase..3 * phase + 3];
+				let y = in32 - s[0];
+				let x = smulwb(y, coefs[0]);
+				let out1 = s[0] + x;
+				s[0] = in32 + x;
+
+				let y = out1 - s[1];
+				let x = smulwb(y, coefs[1]);
+				let out2 = s[1] + x;
+				s[1] = out1 + x;
+
+				let y = out2 - s[2];
+				let x = smlawb(y, y, coefs[2]);
+				let out1 = s[2] + x;
+				s[2] = out2 + x;
+
+				out[2 * k + phase] = sat16(rshift_round(out1, 10));
+			}
+		}
+	}
+}
@@ -1,3 +1,4 @@
+use super::layout;
 use crate::container::wav::WavFormat;
 use crate::core::frame::{AudioFormat, Channels, Frame, FrameAudio};
 use crate::core::packet::Packet;
@@ -7,16 +8,16 @@ use crate::message::Result;
 pub struct PcmDecoder {
 	sample_rate: u32,
 	channels: Channels,
-	bytes_per_sample: usize,
+	format: AudioFormat,
 }
 
 impl PcmDecoder {
-	pub fn new(sample_rate: u32, channels: Channels, bytes_per_sample: usize) -> Self {
-		Self { sample_rate, channels, bytes_per_sample }
+	pub fn new(sample_rate: u32, channels: Channels, format: AudioFormat) -> Self {
+		Self { sample_rate, channels, format }
 	}
 
 	pub fn new_from_metadata(metadata: &WavFormat) -> Self {
-		Self::new(metadata.sample_rate, metadata.channels, metadata.bytes_per_sample())
+		Self::new(metadata.sample_rate, metadata.channels, metadata.audio_format())
 	}
 }
 
@@ -26,16 +27,13 @@ impl Decoder for PcmDecoder {
 			return Ok(None);
 		}
 
-		let nb_samples = packet.data.len() / (self.channels.count() as usize * self.bytes_per_sample);
+		let bytes_per_sample = self.format.bytes_per_sample().unwrap_or(2);
+		let nb_samples = packet.data.len() / (self.channels.count() as usize * bytes_per_sample);
 
-		let audio_format = match self.bytes_per_sample {
-			2 => AudioFormat::PCM16,
-			3 => AudioFormat::PCM24,
-			4 => AudioFormat::PCM32,
-			_ => AudioFormat::PCM16,
-		};
-
-		let audio = FrameAudio::new(packet.data, self.sample_rate, self.channels, audio_format);
+		let mut data = packet.data;
+		layout::swap_layout(&mut data, self.format);
+		let format = self.format.native();
+		let audio = FrameAudio::new(data, self.sample_rate, self.channels, format);
 		let audio = audio.with_nb_samples(nb_samples);
 
 		// let time = Time::new(1, self.sample_rate);
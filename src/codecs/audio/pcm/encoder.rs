@@ -1,6 +1,7 @@
+use super::layout;
 use crate::container::wav::{WavFormat, converter};
 use crate::core::Encoder;
-use crate::core::frame::{AudioFormat, Channels, Frame};
+use crate::core::frame::{AudioFormat, Frame};
 use crate::core::packet::Packet;
 use crate::core::time::Time;
 use crate::message::Result;
@@ -19,17 +20,6 @@ impl PcmEncoder {
 		self.target_format = Some(format);
 		self
 	}
-
-	fn wav_format(format: AudioFormat, channels: Channels, sample_rate: u32) -> WavFormat {
-		let bit_depth = match format {
-			AudioFormat::PCM16 => 16,
-			AudioFormat::PCM24 => 24,
-			AudioFormat::PCM32 => 32,
-			_ => 16,
-		};
-		let format_code = if bit_depth == 32 { 3 } else { 1 };
-		WavFormat { channels, sample_rate, bit_depth, format_code }
-	}
 }
 
 impl Encoder for PcmEncoder {
@@ -42,18 +32,24 @@ impl Encoder for PcmEncoder {
 		let time = Time::new(1, self.sample_rate);
 
 		if let Some(target) = self.target_format {
-			let format = Self::wav_format(audio.format, audio.channels, self.sample_rate);
-			let target_format = Self::wav_format(target, audio.channels, self.sample_rate);
-
-			let samples = converter::to_f32(&audio.data, &format)?;
-
-			let data = converter::from_f32(&samples, &target_format)?;
+			let native = target.native();
+			let mut data = match audio.format == native {
+				true => audio.data.clone(),
+				false => {
+					let format = WavFormat::from_audio_format(audio.format, audio.channels, self.sample_rate);
+					let target_format =
+						WavFormat::from_audio_format(native, audio.channels, self.sample_rate);
+					let samples = converter::to_f32(&audio.data, &format)?;
+					converter::from_f32(&samples, &target_format)?
+				}
+			};
+			layout::swap_layout(&mut data, target);
 			let packet = Packet::new(data, frame.stream_id, time);
-			return Ok(Some(packet.with_pts(frame.pts)));
+			return Ok(Some(packet.with_pts(frame.pts).with_dts(frame.pts)));
 		}
 
 		let packet = Packet::new(audio.data.clone(), frame.stream_id, time);
-		Ok(Some(packet.with_pts(frame.pts)))
+		Ok(Some(packet.with_pts(frame.pts).with_dts(frame.pts)))
 	}
 
 	fn flush(&mut self) -> Result<Option<Packet>> {
@@ -0,0 +1,44 @@
+use crate::codecs;
+use crate::core::frame::AudioFormat;
+
+const CODECS: [(&str, AudioFormat); 14] = [
+	(codecs::audio::PCM_U8, AudioFormat::PCM8),
+	(codecs::audio::PCM_S8, AudioFormat::PCM8S),
+	(codecs::audio::PCM_S16LE, AudioFormat::PCM16),
+	(codecs::audio::PCM_S24LE, AudioFormat::PCM24),
+	(codecs::audio::PCM_S32LE, AudioFormat::PCM32I),
+	(codecs::audio::PCM_F32LE, AudioFormat::PCM32),
+	(codecs::audio::PCM_F64LE, AudioFormat::PCM64),
+	(codecs::audio::PCM_S16BE, AudioFormat::PCM16BE),
+	(codecs::audio::PCM_S24BE, AudioFormat::PCM24BE),
+	(codecs::audio::PCM_S32BE, AudioFormat::PCM32IBE),
+	(codecs::audio::PCM_F32BE, AudioFormat::PCM32BE),
+	(codecs::audio::PCM_F64BE, AudioFormat::PCM64BE),
+	(codecs::audio::PCM_MULAW, AudioFormat::MULAW),
+	(codecs::audio::PCM_ALAW, AudioFormat::ALAW),
+];
+
+/// Sample layout of an uncompressed codec.
+pub fn format_for_codec(codec: &str) -> Option<AudioFormat> {
+	CODECS.iter().find(|(name, _)| *name == codec).map(|(_, format)| *format)
+}
+
+pub fn codec_for_format(format: AudioFormat) -> Option<&'static str> {
+	CODECS.iter().find(|(_, layout)| *layout == format).map(|(name, _)| *name)
+}
+
+/// Converts samples between `format` and its native layout, both ways.
+pub fn swap_layout(data: &mut [u8], format: AudioFormat) {
+	match format {
+		AudioFormat::PCM8S => data.iter_mut().for_each(|byte| *byte ^= 0x80),
+		AudioFormat::PCM16BE
+		| AudioFormat::PCM24BE
+		| AudioFormat::PCM32BE
+		| AudioFormat::PCM32IBE
+		| AudioFormat::PCM64BE => {
+			let width = format.bytes_per_sample().unwrap_or(1);
+			data.chunks_exact_mut(width).for_each(|sample| sample.reverse());
+		}
+		_ => {}
+	}
+}
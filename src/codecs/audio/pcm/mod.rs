@@ -1,5 +1,6 @@
 pub mod decoder;
 pub mod encoder;
+pub mod layout;
 
 pub use decoder::PcmDecoder;
 pub use encoder::PcmEncoder;
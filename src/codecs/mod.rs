@@ -4,3 +4,5 @@ pub mod video;
 
 pub const UNKNOWN: &str = "unknown";
 pub const RAW: &str = "raw";
+/// Codec asking for a stream to be copied as it is.
+pub const COPY: &str = "copy";
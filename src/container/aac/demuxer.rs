@@ -0,0 +1,88 @@
+use crate::codecs;
+use crate::codecs::audio::aac::AdtsHeader;
+use crate::codecs::audio::aac::adts::HEADER_SIZE;
+use crate::codecs::audio::aac::filterbank::FRAME_LENGTH;
+use crate::container::frames::{FrameReader, SyncHeader};
+use crate::container::probe;
+use crate::core::packet::Packet;
+use crate::core::{Demuxer, stream, time};
+use crate::io::MediaRead;
+use crate::{error, message::Result};
+
+impl SyncHeader for AdtsHeader {
+	const SIZE: usize = HEADER_SIZE;
+
+	fn parse(data: &[u8]) -> Option<Self> {
+		AdtsHeader::parse(data).ok()
+	}
+
+	fn frame_size(&self) -> usize {
+		self.frame_size
+	}
+
+	fn is_compatible(&self, other: &Self) -> bool {
+		AdtsHeader::is_compatible(self, other)
+	}
+}
+
+/// Demuxer for raw AAC in ADTS framing. Packets are the raw access units,
+/// with the ADTS header stripped; the stream carries the matching
+/// AudioSpecificConfig as codec private data.
+pub struct AdtsDemuxer<R: MediaRead> {
+	frames: FrameReader<R, AdtsHeader>,
+	streams: stream::Streams,
+	first: AdtsHeader,
+	samples: u64,
+}
+
+impl<R: MediaRead> AdtsDemuxer<R> {
+	pub fn new(reader: R) -> Result<Self> {
+		let mut frames = FrameReader::<R, AdtsHeader>::new(reader);
+		frames.skip_id3v2()?;
+		let first = match frames.resync(None)? {
+			Some(header) => header,
+			None => return Err(error!("no adts frame found in input")),
+		};
+
+		let codec_name = codecs::audio::AAC.to_string();
+		let time = time::Time::new(1, first.sample_rate);
+		let stream = stream::Stream::new(0, 0, stream::StreamKind::Audio, codec_name, time)
+			.with_codec_private(first.config().to_bytes());
+		let streams = stream::Streams::new(vec![stream]);
+
+		Ok(Self { frames, streams, first, samples: 0 })
+	}
+
+	/// Header of the first frame.
+	pub fn first_header(&self) -> &AdtsHeader {
+		&self.first
+	}
+}
+
+impl<R: MediaRead> Demuxer for AdtsDemuxer<R> {
+	fn streams(&self) -> &stream::Streams {
+		&self.streams
+	}
+
+	fn read_packet(&mut self) -> Result<Option<Packet>> {
+		let Some((header, data)) = self.frames.next_frame(&self.first)? else {
+			return Ok(None);
+		};
+		if header.raw_blocks != 1 {
+			return Err(error!("adts frames of {} raw data blocks are not supported", header.raw_blocks));
+		}
+
+		let time = time::Time::new(1, header.sample_rate);
+		let pts = self.samples as i64;
+		self.samples += FRAME_LENGTH as u64;
+		let data = data[header.header_size..].to_vec();
+		let packet = Packet::new(data, 0, time).with_pts(pts).with_dts(pts).with_keyframe(true);
+		Ok(Some(packet))
+	}
+}
+
+/// Scores how likely `data` is the start of an ADTS stream: ID3v2 tags,
+/// then two frames in a row.
+pub fn probe(data: &[u8]) -> u8 {
+	probe::score_frames(data, |frame| AdtsHeader::parse(frame).ok().map(|header| header.frame_size))
+}
@@ -0,0 +1,5 @@
+pub mod demuxer;
+pub mod muxer;
+
+pub use demuxer::AdtsDemuxer;
+pub use muxer::AdtsMuxer;
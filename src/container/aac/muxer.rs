@@ -0,0 +1,53 @@
+use crate::codecs::audio::aac::adts::VARIABLE_FULLNESS;
+use crate::codecs::audio::aac::{AdtsHeader, AudioSpecificConfig};
+use crate::core::Muxer;
+use crate::core::packet::Packet;
+use crate::core::stream::{self, Stream};
+use crate::io::{MediaWrite, WritePrimitives};
+use crate::{error, message::Result};
+
+/// Muxer for raw AAC in ADTS framing. Packets that already are ADTS frames
+/// are written as they are; raw access units get a header built from the
+/// AudioSpecificConfig of the stream.
+pub struct AdtsMuxer<W: MediaWrite> {
+	writer: W,
+	streams: stream::Streams,
+	config: AudioSpecificConfig,
+}
+
+impl<W: MediaWrite> AdtsMuxer<W> {
+	pub fn new(writer: W, streams: stream::Streams) -> Result<Self> {
+		let stream =
+			streams.audio().next().cloned().ok_or_else(|| error!("adts output needs an audio stream"))?;
+		let config = AudioSpecificConfig::parse(&stream.codec_private)?;
+		let output = Stream::new(0, 0, stream.kind, stream.codec.clone(), stream.time)
+			.with_codec_private(stream.codec_private);
+		Ok(Self { writer, streams: stream::Streams::new(vec![output]), config })
+	}
+
+	pub fn write_packet(&mut self, packet: Packet) -> Result<()> {
+		if !AdtsHeader::is_frame(&packet.data) {
+			let header = AdtsHeader::new(&self.config, packet.data.len())?;
+			self.writer.write_all(&header.to_bytes(VARIABLE_FULLNESS))?;
+		}
+		self.writer.write_all(&packet.data)
+	}
+
+	pub fn finalize(&mut self) -> Result<()> {
+		self.writer.flush()
+	}
+}
+
+impl<W: MediaWrite> Muxer for AdtsMuxer<W> {
+	fn streams(&self) -> &stream::Streams {
+		&self.streams
+	}
+
+	fn write(&mut self, packet: Packet) -> Result<()> {
+		self.write_packet(packet)
+	}
+
+	fn finalize(&mut self) -> Result<()> {
+		self.finalize()
+	}
+}
@@ -0,0 +1,267 @@
+use super::extended::from_extended;
+use super::metadata::{self, Instrument};
+use super::{AiffFormat, AiffMetadata};
+use crate::container::probe::SCORE_MAX;
+use crate::core::frame::Channels;
+use crate::core::packet::Packet;
+use crate::core::{Demuxer, stream, time};
+use crate::io::{MediaRead, MediaSeek, ReadPrimitives, SeekFrom};
+use crate::{error, message::Result};
+
+type SeekFn<R> = fn(&mut R, SeekFrom) -> Result<u64>;
+
+/// Reads AIFF and AIFF-C. The chunks may come in any order, so the whole
+/// file is walked before the audio is read when the input can seek; when
+/// it cannot, the audio is read where SSND comes and COMM must precede it.
+pub struct AiffDemuxer<R: MediaRead> {
+	reader: R,
+	format: AiffFormat,
+	streams: stream::Streams,
+	metadata: AiffMetadata,
+	data_remaining: u64,
+	sample_position: u64,
+}
+
+impl<R: MediaRead + MediaSeek> AiffDemuxer<R> {
+	pub fn new(reader: R) -> Result<Self> {
+		Self::build(reader, Some(<R as MediaSeek>::seek))
+	}
+}
+
+impl<R: MediaRead> AiffDemuxer<R> {
+	const CHUNK_SIZE_LIMIT: usize = 65536;
+
+	/// A demuxer for input that cannot seek, such as a pipe. Chunks after
+	/// SSND are left unread.
+	pub fn new_stream(reader: R) -> Result<Self> {
+		Self::build(reader, None)
+	}
+
+	fn build(mut reader: R, seek: Option<SeekFn<R>>) -> Result<Self> {
+		Self::check_fourcc(&mut reader, b"FORM")?;
+		let _form_size = reader.read_u32_be()?;
+		let mut form_type = [0u8; 4];
+		reader.read_exact(&mut form_type)?;
+		let aifc = match &form_type {
+			b"AIFF" => false,
+			b"AIFC" => true,
+			other => return Err(error!("expected AIFF, found {}", String::from_utf8_lossy(other))),
+		};
+
+		let mut format = None;
+		let mut frames = 0u64;
+		let mut sound = None;
+		let mut metadata = AiffMetadata::new();
+
+		while let Some((chunk_id, chunk_size)) = Self::read_chunk_header(&mut reader)? {
+			match &chunk_id {
+				b"COMM" => {
+					let (comm, count) = Self::read_comm_chunk(&mut reader, chunk_size, aifc)?;
+					(format, frames) = (Some(comm), count);
+				}
+				b"SSND" => {
+					if chunk_size < 8 {
+						return Err(error!("SSND chunk too small"));
+					}
+					let offset = reader.read_u32_be()? as u64;
+					let _block_size = reader.read_u32_be()?;
+					let size = chunk_size.saturating_sub(8 + offset);
+					let Some(seek) = seek else {
+						if format.is_none() {
+							return Err(error!("aiff COMM chunk after SSND needs an input that can seek"));
+						}
+						Self::skip_bytes(&mut reader, offset)?;
+						sound = Some((None, size));
+						break;
+					};
+					let start = seek(&mut reader, SeekFrom::Current(0))? + offset;
+					sound = Some((Some(start), size));
+					// the audio is read once the chunks after it are
+					let next = start - offset + chunk_size - 8 + chunk_size % 2;
+					seek(&mut reader, SeekFrom::Start(next))?;
+					continue;
+				}
+				b"MARK" => {
+					metadata.markers = metadata::parse_markers(&Self::read_bytes(&mut reader, chunk_size)?)?
+				}
+				b"INST" => {
+					metadata.instrument =
+						Some(Instrument::parse(&Self::read_bytes(&mut reader, chunk_size)?)?)
+				}
+				_ => Self::skip_bytes(&mut reader, chunk_size)?,
+			}
+			Self::skip_padding(&mut reader, chunk_size)?;
+		}
+
+		let format = format.ok_or_else(|| error!("aiff file has no COMM chunk"))?;
+		let (data_start, mut data_size) = sound.ok_or_else(|| error!("aiff file has no SSND chunk"))?;
+		if let (Some(seek), Some(data_start)) = (seek, data_start) {
+			let end = seek(&mut reader, SeekFrom::End(0))?;
+			data_size = data_size.min(end.saturating_sub(data_start));
+			seek(&mut reader, SeekFrom::Start(data_start))?;
+		}
+
+		let codec_name = format.to_codec_string().to_string();
+		let time = time::Time::new(1, format.sample_rate);
+		let stream = stream::Stream::new(0, 0, stream::StreamKind::Audio, codec_name, time);
+		let streams = stream::Streams::new(vec![stream]);
+
+		Ok(Self {
+			reader,
+			format,
+			streams,
+			metadata,
+			data_remaining: data_size.min(frames * format.bytes_per_frame() as u64),
+			sample_position: 0,
+		})
+	}
+
+	/// The id and size of the next chunk, `None` at the end of the input.
+	fn read_chunk_header(reader: &mut R) -> Result<Option<([u8; 4], u64)>> {
+		let mut buf = [0u8; 8];
+		let mut filled = 0;
+		while filled < buf.len() {
+			let read = reader.read(&mut buf[filled..])?;
+			if read == 0 {
+				// a few stray bytes at the end are not a chunk
+				return Ok(None);
+			}
+			filled += read;
+		}
+		let chunk_id = [buf[0], buf[1], buf[2], buf[3]];
+		Ok(Some((chunk_id, u32::from_be_bytes([buf[4], buf[5], buf[6], buf[7]]) as u64)))
+	}
+
+	/// Reads the format and the number of sample frames.
+	fn read_comm_chunk(reader: &mut R, chunk_size: u64, aifc: bool) -> Result<(AiffFormat, u64)> {
+		if chunk_size < 18 || (aifc && chunk_size < 22) {
+			return Err(error!("COMM chunk too small"));
+		}
+		let channels = reader.read_u16_be()?;
+		let frames = reader.read_u32_be()? as u64;
+		let bits = reader.read_u16_be()?;
+		let mut rate = [0u8; 10];
+		reader.read_exact(&mut rate)?;
+		let sample_rate = from_extended(rate).round();
+		if channels == 0 || channels > u8::MAX as u16 {
+			return Err(error!("aiff files with {} channels are not supported", channels));
+		}
+		if !(1.0..=u32::MAX as f64).contains(&sample_rate) {
+			return Err(error!("invalid aiff sample rate {}", sample_rate));
+		}
+
+		let mut compression = None;
+		if aifc {
+			let mut kind = [0u8; 4];
+			reader.read_exact(&mut kind)?;
+			compression = Some(kind);
+		}
+		// the name of the compression type
+		Self::skip_bytes(reader, chunk_size - if aifc { 22 } else { 18 })?;
+		let format = AiffFormat::layout(bits, compression.as_ref()).map_err(|e| error!("{}", e))?;
+		let channels = Channels::from_count(channels as u8);
+		Ok((AiffFormat { channels, sample_rate: sample_rate as u32, format }, frames))
+	}
+
+	fn check_fourcc(reader: &mut R, expected: &[u8; 4]) -> Result<()> {
+		let mut actual = [0u8; 4];
+		reader.read_exact(&mut actual)?;
+		if &actual != expected {
+			return Err(error!(
+				"expected {}, found {}",
+				String::from_utf8_lossy(expected),
+				String::from_utf8_lossy(&actual)
+			));
+		}
+		Ok(())
+	}
+
+	/// Reads `size` bytes as they come, so a damaged size cannot allocate
+	/// more than the input holds.
+	fn read_bytes(reader: &mut R, size: u64) -> Result<Vec<u8>> {
+		let mut data = Vec::new();
+		let mut chunk = [0u8; 8192];
+		while (data.len() as u64) < size {
+			let count = (size - data.len() as u64).min(chunk.len() as u64) as usize;
+			reader.read_exact(&mut chunk[..count])?;
+			data.extend_from_slice(&chunk[..count]);
+		}
+		Ok(data)
+	}
+
+	fn skip_bytes(reader: &mut R, size: u64) -> Result<()> {
+		let mut chunk = [0u8; 8192];
+		let mut left = size;
+		while left > 0 {
+			let count = left.min(chunk.len() as u64) as usize;
+			reader.read_exact(&mut chunk[..count])?;
+			left -= count as u64;
+		}
+		Ok(())
+	}
+
+	/// Chunks start on even offsets; the pad byte may be missing at the end.
+	fn skip_padding(reader: &mut R, chunk_size: u64) -> Result<()> {
+		if chunk_size % 2 == 1 {
+			reader.read(&mut [0u8; 1])?;
+		}
+		Ok(())
+	}
+
+	pub fn read_packet(&mut self) -> Result<Option<Packet>> {
+		if self.data_remaining == 0 {
+			return Ok(None);
+		}
+
+		let frame_size = self.format.bytes_per_frame() as u64;
+		let max_chunk = (Self::CHUNK_SIZE_LIMIT as u64 / frame_size) * frame_size;
+		let chunk_size = std::cmp::min(self.data_remaining, max_chunk) as usize;
+		let mut data = vec![0u8; chunk_size];
+		let mut bytes_read = 0;
+		while bytes_read < chunk_size {
+			let read = self.reader.read(&mut data[bytes_read..])?;
+			if read == 0 {
+				break;
+			}
+			bytes_read += read;
+		}
+
+		if bytes_read == 0 {
+			self.data_remaining = 0;
+			return Ok(None);
+		}
+
+		data.truncate(bytes_read);
+		self.data_remaining -= bytes_read as u64;
+
+		let time = time::Time::new(1, self.format.sample_rate);
+		let position = self.sample_position as i64;
+		let packet = Packet::new(data, 0, time).with_pts(position).with_dts(position);
+		self.sample_position += bytes_read as u64 / frame_size;
+
+		Ok(Some(packet))
+	}
+
+	pub fn format(&self) -> AiffFormat {
+		self.format
+	}
+
+	pub fn metadata(&self) -> &AiffMetadata {
+		&self.metadata
+	}
+}
+
+impl<R: MediaRead> Demuxer for AiffDemuxer<R> {
+	fn streams(&self) -> &stream::Streams {
+		&self.streams
+	}
+	fn read_packet(&mut self) -> Result<Option<Packet>> {
+		self.read_packet()
+	}
+}
+
+/// Scores how likely `data` is the start of an AIFF or AIFF-C file.
+pub fn probe(data: &[u8]) -> u8 {
+	let form = data.len() >= 12 && &data[..4] == b"FORM";
+	if form && matches!(&data[8..12], b"AIFF" | b"AIFC") { SCORE_MAX } else { 0 }
+}
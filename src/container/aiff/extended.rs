@@ -0,0 +1,29 @@
+// exponent bias of the 80 bit format, plus the 63 fraction bits of its
+// explicit integer mantissa
+const BIAS: i32 = 16383 + 63;
+
+/// Reads an 80 bit IEEE 754 extended float, which AIFF stores its sample
+/// rate in.
+pub fn from_extended(bytes: [u8; 10]) -> f64 {
+	let exponent = u16::from_be_bytes([bytes[0], bytes[1]]);
+	let mut mantissa = [0u8; 8];
+	mantissa.copy_from_slice(&bytes[2..]);
+	let mantissa = u64::from_be_bytes(mantissa);
+	if mantissa == 0 {
+		return 0.0;
+	}
+	let value = mantissa as f64 * 2f64.powi((exponent & 0x7FFF) as i32 - BIAS);
+	if exponent & 0x8000 != 0 { -value } else { value }
+}
+
+pub fn to_extended(value: u32) -> [u8; 10] {
+	let mut bytes = [0u8; 10];
+	if value == 0 {
+		return bytes;
+	}
+	let shift = (value as u64).leading_zeros();
+	let exponent = (BIAS - shift as i32) as u16;
+	bytes[..2].copy_from_slice(&exponent.to_be_bytes());
+	bytes[2..].copy_from_slice(&((value as u64) << shift).to_be_bytes());
+	bytes
+}
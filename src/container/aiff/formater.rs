@@ -0,0 +1,118 @@
+use crate::codecs;
+use crate::codecs::audio::g711::G711Law;
+use crate::codecs::audio::pcm::layout;
+use crate::container::wav::WavFormat;
+use crate::core::frame::{AudioFormat, Channels};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AiffFormat {
+	pub channels: Channels,
+	pub sample_rate: u32,
+	/// Layout of the stored samples. Anything but signed big-endian
+	/// integers needs AIFF-C.
+	pub format: AudioFormat,
+}
+
+impl Default for AiffFormat {
+	fn default() -> Self {
+		Self { channels: Channels::Stereo, sample_rate: 44100, format: AudioFormat::PCM16BE }
+	}
+}
+
+impl AiffFormat {
+	/// The layout of a COMM chunk, from its sample size and the
+	/// compression type of AIFF-C.
+	pub fn layout(bits: u16, compression: Option<&[u8; 4]>) -> Result<AudioFormat, String> {
+		let bytes = bits.div_ceil(8);
+		let format = match (compression.unwrap_or(b"NONE"), bytes) {
+			(b"NONE" | b"twos" | b"sowt", 1) => AudioFormat::PCM8S,
+			(b"NONE" | b"twos", 2) => AudioFormat::PCM16BE,
+			(b"NONE" | b"twos", 3) => AudioFormat::PCM24BE,
+			(b"NONE" | b"twos", 4) => AudioFormat::PCM32IBE,
+			(b"sowt", 2) => AudioFormat::PCM16,
+			(b"sowt", 3) => AudioFormat::PCM24,
+			(b"sowt", 4) => AudioFormat::PCM32I,
+			(b"fl32" | b"FL32", _) => AudioFormat::PCM32BE,
+			(b"fl64" | b"FL64", _) => AudioFormat::PCM64BE,
+			(b"ulaw" | b"ULAW", _) => AudioFormat::MULAW,
+			(b"alaw" | b"ALAW", _) => AudioFormat::ALAW,
+			(b"raw ", _) => AudioFormat::PCM8,
+			(b"NONE" | b"twos" | b"sowt", _) => {
+				return Err(format!("{} bit aiff samples are not supported", bits));
+			}
+			(other, _) => {
+				let name = String::from_utf8_lossy(other);
+				return Err(format!("aiff-c compression '{}' is not supported", name));
+			}
+		};
+		Ok(format)
+	}
+
+	/// The AIFF layout closest to the decoded `format`.
+	pub fn from_audio_format(format: AudioFormat, channels: Channels, sample_rate: u32) -> Self {
+		let format = match format {
+			AudioFormat::PCM8 => AudioFormat::PCM8S,
+			AudioFormat::PCM24 => AudioFormat::PCM24BE,
+			AudioFormat::PCM32 => AudioFormat::PCM32BE,
+			AudioFormat::PCM32I => AudioFormat::PCM32IBE,
+			AudioFormat::PCM64 => AudioFormat::PCM64BE,
+			AudioFormat::MULAW | AudioFormat::ALAW => format,
+			_ => AudioFormat::PCM16BE,
+		};
+		Self { channels, sample_rate, format }
+	}
+
+	/// Sample size of the COMM chunk; G.711 reports its decoded size.
+	pub fn bit_depth(&self) -> u16 {
+		match self.format.bytes_per_sample() {
+			Some(bytes) => bytes as u16 * 8,
+			None => 16,
+		}
+	}
+
+	pub fn bytes_per_frame(&self) -> usize {
+		self.format.bytes_per_sample().unwrap_or(1) * self.channels.count() as usize
+	}
+
+	/// The AIFF-C compression type and name, `None` for plain AIFF.
+	pub fn compression(&self) -> Option<(&'static [u8; 4], &'static str)> {
+		match self.format {
+			AudioFormat::PCM8S | AudioFormat::PCM16BE | AudioFormat::PCM24BE | AudioFormat::PCM32IBE => {
+				None
+			}
+			AudioFormat::PCM8 => Some((b"raw ", "")),
+			AudioFormat::PCM32BE => Some((b"fl32", "32-bit floating point")),
+			AudioFormat::PCM64BE => Some((b"fl64", "64-bit floating point")),
+			AudioFormat::MULAW => Some((b"ulaw", "uLaw 2:1")),
+			AudioFormat::ALAW => Some((b"alaw", "ALaw 2:1")),
+			_ => Some((b"sowt", "")),
+		}
+	}
+
+	pub fn to_codec_string(&self) -> &'static str {
+		layout::codec_for_format(self.format).unwrap_or(codecs::audio::PCM_S16BE)
+	}
+
+	/// The decoded PCM layout, as the decoders take it.
+	pub fn to_wav_format(&self) -> WavFormat {
+		let mut format =
+			WavFormat::from_audio_format(self.format.native(), self.channels, self.sample_rate);
+		if let Some(law) = G711Law::from_codec(self.to_codec_string()) {
+			(format.bit_depth, format.format_code) = (8, law.format_code());
+		}
+		format
+	}
+
+	pub fn apply_codec(&mut self, codec: &str) -> Result<(), String> {
+		match layout::format_for_codec(codec) {
+			// aiff-c has no little-endian float
+			Some(AudioFormat::PCM32 | AudioFormat::PCM64) | None => {
+				Err(format!("aiff codec '{}' is not supported", codec))
+			}
+			Some(format) => {
+				self.format = format;
+				Ok(())
+			}
+		}
+	}
+}
@@ -0,0 +1,138 @@
+use crate::{error, message::Result};
+
+/// A position in the audio from the MARK chunk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Marker {
+	pub id: u16,
+	/// Position in sample frames from the start of the audio.
+	pub position: u32,
+	pub name: String,
+}
+
+/// A loop of the INST chunk, between two markers.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Loop {
+	/// 0 no looping, 1 forward, 2 forward and backward.
+	pub play_mode: u16,
+	pub begin_marker: u16,
+	pub end_marker: u16,
+}
+
+/// How a sampler plays the sound, from the INST chunk.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Instrument {
+	pub base_note: u8,
+	/// Cents, from -50 to 50.
+	pub detune: i8,
+	pub low_note: u8,
+	pub high_note: u8,
+	pub low_velocity: u8,
+	pub high_velocity: u8,
+	/// Decibels.
+	pub gain: i16,
+	pub sustain_loop: Loop,
+	pub release_loop: Loop,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct AiffMetadata {
+	pub markers: Vec<Marker>,
+	pub instrument: Option<Instrument>,
+}
+
+impl AiffMetadata {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.markers.is_empty() && self.instrument.is_none()
+	}
+}
+
+pub fn parse_markers(data: &[u8]) -> Result<Vec<Marker>> {
+	let count = field(data, 0, 2)?;
+	let count = u16::from_be_bytes([count[0], count[1]]);
+	let mut markers = Vec::with_capacity(count as usize);
+	let mut offset = 2;
+	for _ in 0..count {
+		let marker = field(data, offset, 7)?;
+		let length = marker[6] as usize;
+		let name = field(data, offset + 7, length)?;
+		markers.push(Marker {
+			id: u16::from_be_bytes([marker[0], marker[1]]),
+			position: u32::from_be_bytes([marker[2], marker[3], marker[4], marker[5]]),
+			name: String::from_utf8_lossy(name).to_string(),
+		});
+		offset += 6 + pstring_size(length);
+	}
+	Ok(markers)
+}
+
+pub fn markers_to_bytes(markers: &[Marker]) -> Vec<u8> {
+	let mut out = (markers.len() as u16).to_be_bytes().to_vec();
+	for marker in markers {
+		out.extend_from_slice(&marker.id.to_be_bytes());
+		out.extend_from_slice(&marker.position.to_be_bytes());
+		put_pstring(&mut out, &marker.name);
+	}
+	out
+}
+
+impl Instrument {
+	pub fn parse(data: &[u8]) -> Result<Self> {
+		let data = field(data, 0, 20)?;
+		let read_loop = |offset: usize| Loop {
+			play_mode: u16::from_be_bytes([data[offset], data[offset + 1]]),
+			begin_marker: u16::from_be_bytes([data[offset + 2], data[offset + 3]]),
+			end_marker: u16::from_be_bytes([data[offset + 4], data[offset + 5]]),
+		};
+		Ok(Self {
+			base_note: data[0],
+			detune: data[1] as i8,
+			low_note: data[2],
+			high_note: data[3],
+			low_velocity: data[4],
+			high_velocity: data[5],
+			gain: i16::from_be_bytes([data[6], data[7]]),
+			sustain_loop: read_loop(8),
+			release_loop: read_loop(14),
+		})
+	}
+
+	pub fn to_bytes(&self) -> Vec<u8> {
+		let mut out = vec![
+			self.base_note,
+			self.detune as u8,
+			self.low_note,
+			self.high_note,
+			self.low_velocity,
+			self.high_velocity,
+		];
+		out.extend_from_slice(&self.gain.to_be_bytes());
+		for sample_loop in [self.sustain_loop, self.release_loop] {
+			out.extend_from_slice(&sample_loop.play_mode.to_be_bytes());
+			out.extend_from_slice(&sample_loop.begin_marker.to_be_bytes());
+			out.extend_from_slice(&sample_loop.end_marker.to_be_bytes());
+		}
+		out
+	}
+}
+
+/// Bytes a pascal string of `length` characters takes, padded to even.
+pub fn pstring_size(length: usize) -> usize {
+	(length + 1).next_multiple_of(2)
+}
+
+pub fn put_pstring(out: &mut Vec<u8>, text: &str) {
+	let bytes = &text.as_bytes()[..text.len().min(255)];
+	out.push(bytes.len() as u8);
+	out.extend_from_slice(bytes);
+	if bytes.len().is_multiple_of(2) {
+		out.push(0);
+	}
+}
+
+fn field(data: &[u8], offset: usize, size: usize) -> Result<&[u8]> {
+	data.get(offset..offset + size).ok_or_else(|| error!("truncated aiff chunk"))
+}
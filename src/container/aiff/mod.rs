@@ -0,0 +1,9 @@
+pub mod demuxer;
+pub mod extended;
+pub mod formater;
+pub mod metadata;
+pub mod muxer;
+pub use demuxer::AiffDemuxer;
+pub use formater::AiffFormat;
+pub use metadata::AiffMetadata;
+pub use muxer::AiffMuxer;
@@ -0,0 +1,152 @@
+use super::extended::to_extended;
+use super::metadata::{self, put_pstring};
+use super::{AiffFormat, AiffMetadata};
+use crate::core::Muxer;
+use crate::core::packet::Packet;
+use crate::core::stream::{self, Stream, StreamKind};
+use crate::core::time::Time;
+use crate::io::{MediaSeek, MediaWrite, SeekFrom, WritePrimitives};
+use crate::{error, message::Result};
+
+// the AIFF-C version the FVER chunk must carry
+const AIFC_VERSION: u32 = 0xA2805140;
+
+pub struct AiffMuxer<W: MediaWrite + MediaSeek> {
+	writer: W,
+	format: AiffFormat,
+	streams: stream::Streams,
+	metadata: Option<AiffMetadata>,
+	data_size: u64,
+	form_size_pos: u64,
+	frames_pos: u64,
+	sound_size_pos: u64,
+}
+
+impl<W: MediaWrite + MediaSeek> AiffMuxer<W> {
+	pub fn new(mut writer: W, format: AiffFormat) -> Result<Self> {
+		let codec_name = format.to_codec_string().to_string();
+		let time = Time::new(1, format.sample_rate);
+		let mut streams = stream::Streams::new_empty();
+		let stream = Stream::new(0, 0, StreamKind::Audio, codec_name, time);
+
+		streams.add(stream);
+
+		let (form_size_pos, frames_pos, sound_size_pos) = Self::write_header(&mut writer, &format)?;
+
+		Ok(Self {
+			writer,
+			format,
+			streams,
+			metadata: None,
+			data_size: 0,
+			form_size_pos,
+			frames_pos,
+			sound_size_pos,
+		})
+	}
+
+	pub fn with_metadata(&mut self, metadata: Option<AiffMetadata>) {
+		self.metadata = metadata;
+	}
+
+	fn write_header(writer: &mut W, format: &AiffFormat) -> Result<(u64, u64, u64)> {
+		let compression = format.compression();
+		writer.write_all(b"FORM")?;
+		let form_size_pos = writer.stream_position()?;
+		writer.write_u32_be(0)?;
+		writer.write_all(if compression.is_some() { b"AIFC" } else { b"AIFF" })?;
+
+		if compression.is_some() {
+			writer.write_all(b"FVER")?;
+			writer.write_u32_be(4)?;
+			writer.write_u32_be(AIFC_VERSION)?;
+		}
+
+		let mut comm = Vec::new();
+		comm.extend_from_slice(&(format.channels.count() as u16).to_be_bytes());
+		comm.extend_from_slice(&0u32.to_be_bytes());
+		comm.extend_from_slice(&format.bit_depth().to_be_bytes());
+		comm.extend_from_slice(&to_extended(format.sample_rate));
+		if let Some((kind, name)) = compression {
+			comm.extend_from_slice(kind);
+			put_pstring(&mut comm, name);
+		}
+		writer.write_all(b"COMM")?;
+		writer.write_u32_be(comm.len() as u32)?;
+		let frames_pos = writer.stream_position()? + 2;
+		writer.write_all(&comm)?;
+
+		// no offset and no block alignment
+		writer.write_all(b"SSND")?;
+		let sound_size_pos = writer.stream_position()?;
+		writer.write_u32_be(8)?;
+		writer.write_u32_be(0)?;
+		writer.write_u32_be(0)?;
+		Ok((form_size_pos, frames_pos, sound_size_pos))
+	}
+
+	pub fn write_packet(&mut self, packet: Packet) -> Result<()> {
+		self.writer.write_all(&packet.data)?;
+		self.data_size += packet.data.len() as u64;
+		Ok(())
+	}
+
+	pub fn finalize(&mut self) -> Result<()> {
+		// chunks start on even offsets
+		if self.data_size % 2 == 1 {
+			self.writer.write_u8(0)?;
+		}
+
+		if let Some(metadata) = &self.metadata {
+			if !metadata.markers.is_empty() {
+				Self::write_chunk(
+					&mut self.writer,
+					b"MARK",
+					&metadata::markers_to_bytes(&metadata.markers),
+				)?;
+			}
+			if let Some(instrument) = &metadata.instrument {
+				Self::write_chunk(&mut self.writer, b"INST", &instrument.to_bytes())?;
+			}
+		}
+
+		let form_size = self.writer.stream_position()? - self.form_size_pos - 4;
+		if form_size > u32::MAX as u64 {
+			return Err(error!("aiff files cannot hold more than 4 GiB"));
+		}
+		let frames = self.data_size / self.format.bytes_per_frame() as u64;
+
+		self.writer.seek(SeekFrom::Start(self.frames_pos))?;
+		self.writer.write_u32_be(frames as u32)?;
+
+		self.writer.seek(SeekFrom::Start(self.sound_size_pos))?;
+		self.writer.write_u32_be(self.data_size as u32 + 8)?;
+
+		self.writer.seek(SeekFrom::Start(self.form_size_pos))?;
+		self.writer.write_u32_be(form_size as u32)?;
+		self.writer.flush()?;
+		Ok(())
+	}
+
+	fn write_chunk(writer: &mut W, id: &[u8; 4], data: &[u8]) -> Result<()> {
+		writer.write_all(id)?;
+		writer.write_u32_be(data.len() as u32)?;
+		writer.write_all(data)?;
+		if data.len() % 2 == 1 {
+			writer.write_u8(0)?;
+		}
+		Ok(())
+	}
+}
+
+impl<W: MediaWrite + MediaSeek> Muxer for AiffMuxer<W> {
+	fn streams(&self) -> &stream::Streams {
+		&self.streams
+	}
+	fn write(&mut self, packet: Packet) -> Result<()> {
+		self.write_packet(packet)
+	}
+	fn finalize(&mut self) -> Result<()> {
+		self.finalize()
+	}
+}
@@ -0,0 +1,129 @@
+use super::AuFormat;
+use crate::container::probe::SCORE_MAX;
+use crate::core::frame::Channels;
+use crate::core::packet::Packet;
+use crate::core::{Demuxer, stream, time};
+use crate::io::{MediaRead, ReadPrimitives};
+use crate::{error, message::Result};
+
+// magic, data offset, data size, encoding, sample rate and channels
+const HEADER_SIZE: u32 = 24;
+
+pub struct AuDemuxer<R: MediaRead> {
+	reader: R,
+	format: AuFormat,
+	streams: stream::Streams,
+	annotation: String,
+	/// Audio bytes left, `None` when the size is unknown and the audio runs
+	/// to the end of the input.
+	data_remaining: Option<u64>,
+	sample_position: u64,
+}
+
+impl<R: MediaRead> AuDemuxer<R> {
+	const CHUNK_SIZE_LIMIT: usize = 65536;
+
+	pub fn new(mut reader: R) -> Result<Self> {
+		let mut magic = [0u8; 4];
+		reader.read_exact(&mut magic)?;
+		if &magic != b".snd" {
+			return Err(error!("expected .snd, found {}", String::from_utf8_lossy(&magic)));
+		}
+		let data_offset = reader.read_u32_be()?;
+		let data_size = reader.read_u32_be()?;
+		let encoding = reader.read_u32_be()?;
+		let sample_rate = reader.read_u32_be()?;
+		let channels = reader.read_u32_be()?;
+
+		let format = AuFormat::from_encoding(encoding)
+			.ok_or_else(|| error!("au encoding {} is not supported", encoding))?;
+		if channels == 0 || channels > u8::MAX as u32 {
+			return Err(error!("au files with {} channels are not supported", channels));
+		}
+		if sample_rate == 0 {
+			return Err(error!("invalid au sample rate 0"));
+		}
+		if data_offset < HEADER_SIZE {
+			return Err(error!("au data offset {} overlaps the header", data_offset));
+		}
+
+		let mut annotation = vec![0u8; (data_offset - HEADER_SIZE) as usize];
+		reader.read_exact(&mut annotation)?;
+		let end = annotation.iter().position(|&byte| byte == 0).unwrap_or(annotation.len());
+		let annotation = String::from_utf8_lossy(&annotation[..end]).to_string();
+
+		let format = AuFormat { channels: Channels::from_count(channels as u8), sample_rate, format };
+		let codec_name = format.to_codec_string().to_string();
+		let time = time::Time::new(1, sample_rate);
+		let stream = stream::Stream::new(0, 0, stream::StreamKind::Audio, codec_name, time);
+		let streams = stream::Streams::new(vec![stream]);
+
+		Ok(Self {
+			reader,
+			format,
+			streams,
+			annotation,
+			data_remaining: (data_size != u32::MAX).then_some(data_size as u64),
+			sample_position: 0,
+		})
+	}
+
+	pub fn read_packet(&mut self) -> Result<Option<Packet>> {
+		let frame_size = self.format.bytes_per_frame() as u64;
+		let max_chunk = (Self::CHUNK_SIZE_LIMIT as u64 / frame_size) * frame_size;
+		let chunk_size = self.data_remaining.map_or(max_chunk, |left| left.min(max_chunk)) as usize;
+		if chunk_size == 0 {
+			return Ok(None);
+		}
+
+		let mut data = vec![0u8; chunk_size];
+		let mut bytes_read = 0;
+		while bytes_read < chunk_size {
+			let read = self.reader.read(&mut data[bytes_read..])?;
+			if read == 0 {
+				break;
+			}
+			bytes_read += read;
+		}
+
+		if bytes_read == 0 {
+			self.data_remaining = Some(0);
+			return Ok(None);
+		}
+
+		data.truncate(bytes_read);
+		if let Some(remaining) = &mut self.data_remaining {
+			*remaining -= bytes_read as u64;
+		}
+
+		let time = time::Time::new(1, self.format.sample_rate);
+		let position = self.sample_position as i64;
+		let packet = Packet::new(data, 0, time).with_pts(position).with_dts(position);
+		self.sample_position += bytes_read as u64 / frame_size;
+
+		Ok(Some(packet))
+	}
+
+	pub fn format(&self) -> AuFormat {
+		self.format
+	}
+
+	/// The text between the header and the audio.
+	pub fn annotation(&self) -> &str {
+		&self.annotation
+	}
+}
+
+impl<R: MediaRead> Demuxer for AuDemuxer<R> {
+	fn streams(&self) -> &stream::Streams {
+		&self.streams
+	}
+	fn read_packet(&mut self) -> Result<Option<Packet>> {
+		self.read_packet()
+	}
+}
+
+/// Scores how likely `data` is the start of an .au file.
+pub fn probe(data: &[u8]) -> u8 {
+	if data.starts_with(b".snd") { SCORE_MAX } else { 0 }
+}
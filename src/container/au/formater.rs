@@ -0,0 +1,83 @@
+use crate::codecs;
+use crate::codecs::audio::g711::G711Law;
+use crate::codecs::audio::pcm::layout;
+use crate::container::wav::WavFormat;
+use crate::core::frame::{AudioFormat, Channels};
+
+// encodings of the .au header
+const ENCODINGS: [(u32, AudioFormat); 8] = [
+	(1, AudioFormat::MULAW),
+	(2, AudioFormat::PCM8S),
+	(3, AudioFormat::PCM16BE),
+	(4, AudioFormat::PCM24BE),
+	(5, AudioFormat::PCM32IBE),
+	(6, AudioFormat::PCM32BE),
+	(7, AudioFormat::PCM64BE),
+	(27, AudioFormat::ALAW),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AuFormat {
+	pub channels: Channels,
+	pub sample_rate: u32,
+	/// Layout of the stored samples, always big-endian.
+	pub format: AudioFormat,
+}
+
+impl Default for AuFormat {
+	fn default() -> Self {
+		Self { channels: Channels::Mono, sample_rate: 8000, format: AudioFormat::MULAW }
+	}
+}
+
+impl AuFormat {
+	pub fn from_encoding(encoding: u32) -> Option<AudioFormat> {
+		ENCODINGS.iter().find(|(code, _)| *code == encoding).map(|(_, format)| *format)
+	}
+
+	pub fn encoding(&self) -> u32 {
+		ENCODINGS.iter().find(|(_, format)| *format == self.format).map_or(3, |(code, _)| *code)
+	}
+
+	/// The .au layout closest to the decoded `format`.
+	pub fn from_audio_format(format: AudioFormat, channels: Channels, sample_rate: u32) -> Self {
+		let format = match format {
+			AudioFormat::PCM8 => AudioFormat::PCM8S,
+			AudioFormat::PCM24 => AudioFormat::PCM24BE,
+			AudioFormat::PCM32 => AudioFormat::PCM32BE,
+			AudioFormat::PCM32I => AudioFormat::PCM32IBE,
+			AudioFormat::PCM64 => AudioFormat::PCM64BE,
+			AudioFormat::MULAW | AudioFormat::ALAW => format,
+			_ => AudioFormat::PCM16BE,
+		};
+		Self { channels, sample_rate, format }
+	}
+
+	pub fn bytes_per_frame(&self) -> usize {
+		self.format.bytes_per_sample().unwrap_or(1) * self.channels.count() as usize
+	}
+
+	pub fn to_codec_string(&self) -> &'static str {
+		layout::codec_for_format(self.format).unwrap_or(codecs::audio::PCM_S16BE)
+	}
+
+	/// The decoded PCM layout, as the decoders take it.
+	pub fn to_wav_format(&self) -> WavFormat {
+		let mut format =
+			WavFormat::from_audio_format(self.format.native(), self.channels, self.sample_rate);
+		if let Some(law) = G711Law::from_codec(self.to_codec_string()) {
+			(format.bit_depth, format.format_code) = (8, law.format_code());
+		}
+		format
+	}
+
+	pub fn apply_codec(&mut self, codec: &str) -> Result<(), String> {
+		match layout::format_for_codec(codec) {
+			Some(format) if ENCODINGS.iter().any(|(_, supported)| *supported == format) => {
+				self.format = format;
+				Ok(())
+			}
+			_ => Err(format!("au codec '{}' is not supported", codec)),
+		}
+	}
+}
@@ -0,0 +1,7 @@
+pub mod demuxer;
+pub mod formater;
+pub mod muxer;
+
+pub use demuxer::AuDemuxer;
+pub use formater::AuFormat;
+pub use muxer::AuMuxer;
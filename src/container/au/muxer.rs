@@ -0,0 +1,97 @@
+use super::AuFormat;
+use crate::core::Muxer;
+use crate::core::packet::Packet;
+use crate::core::stream::{self, Stream, StreamKind};
+use crate::core::time::Time;
+use crate::io::{MediaSeek, MediaWrite, SeekFrom, WritePrimitives};
+use crate::message::Result;
+
+// the data size of a file whose length is not known
+const UNKNOWN_SIZE: u32 = u32::MAX;
+
+pub struct AuMuxer<W: MediaWrite + MediaSeek> {
+	writer: W,
+	format: AuFormat,
+	streams: stream::Streams,
+	annotation: String,
+	data_size: u64,
+	data_size_pos: u64,
+	header_written: bool,
+}
+
+impl<W: MediaWrite + MediaSeek> AuMuxer<W> {
+	pub fn new(writer: W, format: AuFormat) -> Result<Self> {
+		let codec_name = format.to_codec_string().to_string();
+		let time = Time::new(1, format.sample_rate);
+		let mut streams = stream::Streams::new_empty();
+		let stream = Stream::new(0, 0, StreamKind::Audio, codec_name, time);
+
+		streams.add(stream);
+
+		Ok(Self {
+			writer,
+			format,
+			streams,
+			annotation: String::new(),
+			data_size: 0,
+			data_size_pos: 0,
+			header_written: false,
+		})
+	}
+
+	pub fn with_annotation(&mut self, annotation: &str) {
+		self.annotation = annotation.to_string();
+	}
+
+	fn ensure_header(&mut self) -> Result<()> {
+		if self.header_written {
+			return Ok(());
+		}
+		// the annotation is nul terminated and padded to four bytes
+		let mut annotation = self.annotation.as_bytes().to_vec();
+		annotation.resize((annotation.len() + 1).next_multiple_of(4), 0);
+
+		self.writer.write_all(b".snd")?;
+		self.writer.write_u32_be(24 + annotation.len() as u32)?;
+		self.data_size_pos = self.writer.stream_position()?;
+		self.writer.write_u32_be(UNKNOWN_SIZE)?;
+		self.writer.write_u32_be(self.format.encoding())?;
+		self.writer.write_u32_be(self.format.sample_rate)?;
+		self.writer.write_u32_be(self.format.channels.count() as u32)?;
+		self.writer.write_all(&annotation)?;
+		self.header_written = true;
+		Ok(())
+	}
+
+	pub fn write_packet(&mut self, packet: Packet) -> Result<()> {
+		self.ensure_header()?;
+		self.writer.write_all(&packet.data)?;
+		self.data_size += packet.data.len() as u64;
+		Ok(())
+	}
+
+	pub fn finalize(&mut self) -> Result<()> {
+		self.ensure_header()?;
+		// larger files keep the unknown size and are read to the end
+		if self.data_size < UNKNOWN_SIZE as u64 {
+			let end = self.writer.stream_position()?;
+			self.writer.seek(SeekFrom::Start(self.data_size_pos))?;
+			self.writer.write_u32_be(self.data_size as u32)?;
+			self.writer.seek(SeekFrom::Start(end))?;
+		}
+		self.writer.flush()?;
+		Ok(())
+	}
+}
+
+impl<W: MediaWrite + MediaSeek> Muxer for AuMuxer<W> {
+	fn streams(&self) -> &stream::Streams {
+		&self.streams
+	}
+	fn write(&mut self, packet: Packet) -> Result<()> {
+		self.write_packet(packet)
+	}
+	fn finalize(&mut self) -> Result<()> {
+		self.finalize()
+	}
+}
@@ -14,6 +14,12 @@ pub const AAC: &str = "aac";
 pub const OPUS: &str = "opus";
 pub const FLAC: &str = "flac";
 pub const WAV: &str = "wav";
+pub const AIFF: &str = "aiff";
+pub const AIF: &str = "aif";
+pub const AIFC: &str = "aifc";
+pub const AU: &str = "au";
+pub const SND: &str = "snd";
+pub const VOC: &str = "voc";
 pub const RAW: &str = "raw";
 pub const PCM: &str = "pcm";
 pub const M4A: &str = "m4a";
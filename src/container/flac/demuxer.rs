@@ -0,0 +1,236 @@
+use super::metadata::{BLOCK_STREAMINFO, FlacMetadata};
+use crate::codecs;
+use crate::codecs::audio::flac::header::{FrameHeader, MAX_HEADER_SIZE};
+use crate::codecs::audio::flac::{StreamInfo, crc};
+use crate::container::probe::{self, SCORE_MAX};
+use crate::core::packet::Packet;
+use crate::core::{Demuxer, stream, time};
+use crate::io::{MediaRead, ReadPrimitives};
+use crate::{error, message::Result};
+
+pub struct FlacDemuxer<R: MediaRead> {
+	reader: R,
+	info: StreamInfo,
+	metadata: FlacMetadata,
+	streams: stream::Streams,
+	buffer: Vec<u8>,
+	eof: bool,
+	packet_count: u64,
+}
+
+impl<R: MediaRead> FlacDemuxer<R> {
+	const READ_SIZE: usize = 65536;
+
+	pub fn new(mut reader: R) -> Result<Self> {
+		Self::skip_id3v2(&mut reader)?;
+		let (info, metadata) = Self::read_metadata(&mut reader)?;
+
+		let codec_name = codecs::audio::FLAC.to_string();
+		let time = time::Time::new(1, info.sample_rate);
+		let stream = stream::Stream::new(0, 0, stream::StreamKind::Audio, codec_name, time)
+			.with_codec_private(info.to_bytes());
+		let streams = stream::Streams::new(vec![stream]);
+
+		Ok(Self { reader, info, metadata, streams, buffer: Vec::new(), eof: false, packet_count: 0 })
+	}
+
+	fn skip_id3v2(reader: &mut R) -> Result<()> {
+		let mut magic = [0u8; 4];
+		reader.read_exact(&mut magic)?;
+		if &magic == b"fLaC" {
+			return Ok(());
+		}
+		if &magic[..3] != b"ID3" {
+			return Err(error!("expected fLaC, found {}", String::from_utf8_lossy(&magic)));
+		}
+
+		let mut header = [0u8; probe::ID3V2_HEADER_SIZE];
+		header[..4].copy_from_slice(&magic);
+		reader.read_exact(&mut header[4..])?;
+		let size = probe::id3v2_size(&header).ok_or_else(|| error!("invalid ID3v2 tag header"))?;
+		let mut skip = vec![0u8; size - probe::ID3V2_HEADER_SIZE];
+		reader.read_exact(&mut skip)?;
+
+		reader.read_exact(&mut magic)?;
+		if &magic != b"fLaC" {
+			return Err(error!("expected fLaC, found {}", String::from_utf8_lossy(&magic)));
+		}
+		Ok(())
+	}
+
+	fn read_metadata(reader: &mut R) -> Result<(StreamInfo, FlacMetadata)> {
+		let mut info = None;
+		let mut metadata = FlacMetadata::new();
+
+		loop {
+			let header = reader.read_u32_be()?;
+			let last = header & 0x8000_0000 != 0;
+			let kind = ((header >> 24) & 0x7F) as u8;
+			let size = (header & 0x00FF_FFFF) as usize;
+
+			let mut data = vec![0u8; size];
+			reader.read_exact(&mut data)?;
+
+			match kind {
+				BLOCK_STREAMINFO => info = Some(StreamInfo::parse(&data)?),
+				127 => return Err(error!("invalid flac metadata block type")),
+				_ => metadata.add_block(kind, data)?,
+			}
+
+			if last {
+				break;
+			}
+		}
+
+		match info {
+			Some(info) => Ok((info, metadata)),
+			None => Err(error!("flac stream has no STREAMINFO block")),
+		}
+	}
+
+	fn fill(&mut self) -> Result<bool> {
+		if self.eof {
+			return Ok(false);
+		}
+		let start = self.buffer.len();
+		self.buffer.resize(start + Self::READ_SIZE, 0);
+		let read = self.reader.read(&mut self.buffer[start..])?;
+		self.buffer.truncate(start + read);
+		if read == 0 {
+			self.eof = true;
+		}
+		Ok(read > 0)
+	}
+
+	fn is_frame_start(&self, offset: usize, first: &FrameHeader) -> bool {
+		let data = &self.buffer[offset..];
+		if data.len() < 2 || data[0] != 0xFF || data[1] & 0xFE != 0xF8 {
+			return false;
+		}
+		match FrameHeader::parse(data) {
+			Ok(header) => {
+				header.variable_block_size == first.variable_block_size
+					&& header.channels.count() == first.channels.count()
+			}
+			Err(_) => false,
+		}
+	}
+
+	// A frame ends where the next valid header starts and the bytes before
+	// it pass the frame CRC-16.
+	fn find_frame_end(&mut self, header: &FrameHeader) -> Result<Option<usize>> {
+		let min_size = (self.info.min_frame_size as usize).max(header.size + 2);
+		// a corrupt frame never matches its CRC; give up on it past this point
+		// and cut at the first header seen so the decoder can report it
+		let max_size = match self.info.max_frame_size as usize {
+			0 => usize::MAX,
+			size => size * 2,
+		};
+		let mut offset = min_size;
+		let mut first_header = None;
+
+		loop {
+			while offset + MAX_HEADER_SIZE <= self.buffer.len()
+				|| (self.eof && offset + 2 <= self.buffer.len())
+			{
+				if self.is_frame_start(offset, header) {
+					if Self::crc_matches(&self.buffer[..offset]) {
+						return Ok(Some(offset));
+					}
+					first_header.get_or_insert(offset);
+				}
+				if offset > max_size && first_header.is_some() {
+					return Ok(first_header);
+				}
+				offset += 1;
+			}
+
+			if !self.fill()? {
+				if Self::crc_matches(&self.buffer) {
+					return Ok(Some(self.buffer.len()));
+				}
+				return Ok(first_header);
+			}
+		}
+	}
+
+	fn crc_matches(frame: &[u8]) -> bool {
+		if frame.len() < 2 {
+			return false;
+		}
+		let (body, crc) = frame.split_at(frame.len() - 2);
+		crc::crc16(body) == u16::from_be_bytes([crc[0], crc[1]])
+	}
+
+	// drops bytes up to the next plausible frame header
+	fn resync(&mut self) -> Result<Option<FrameHeader>> {
+		loop {
+			while self.buffer.len() < MAX_HEADER_SIZE && self.fill()? {}
+			if self.buffer.len() < 2 {
+				return Ok(None);
+			}
+
+			match FrameHeader::parse(&self.buffer) {
+				Ok(header) => return Ok(Some(header)),
+				Err(_) if self.buffer.len() < MAX_HEADER_SIZE => return Ok(None),
+				Err(_) => {
+					let skip = self.buffer[1..].iter().position(|&b| b == 0xFF).map(|p| p + 1);
+					let skip = skip.unwrap_or(self.buffer.len());
+					self.buffer.drain(..skip);
+				}
+			}
+		}
+	}
+
+	pub fn read_packet(&mut self) -> Result<Option<Packet>> {
+		loop {
+			let Some(header) = self.resync()? else {
+				return Ok(None);
+			};
+
+			let Some(end) = self.find_frame_end(&header)? else {
+				// trailing garbage or a truncated last frame
+				if self.eof {
+					self.buffer.clear();
+					return Ok(None);
+				}
+				continue;
+			};
+
+			let data: Vec<u8> = self.buffer.drain(..end).collect();
+			let pts = header.first_sample(self.info.min_block_size as u32) as i64;
+			let time = time::Time::new(1, self.info.sample_rate);
+			let packet = Packet::new(data, 0, time).with_pts(pts).with_dts(pts).with_keyframe(true);
+
+			self.packet_count += 1;
+			return Ok(Some(packet));
+		}
+	}
+
+	pub fn stream_info(&self) -> &StreamInfo {
+		&self.info
+	}
+
+	pub fn metadata(&self) -> &FlacMetadata {
+		&self.metadata
+	}
+}
+
+impl<R: MediaRead> Demuxer for FlacDemuxer<R> {
+	fn streams(&self) -> &stream::Streams {
+		&self.streams
+	}
+
+	fn read_packet(&mut self) -> Result<Option<Packet>> {
+		self.read_packet()
+	}
+}
+
+/// Scores how likely `data` is the start of a flac file, after any ID3v2
+/// tags.
+pub fn probe(data: &[u8]) -> u8 {
+	match probe::skip_id3v2(data) {
+		Some(data) if data.starts_with(b"fLaC") => SCORE_MAX,
+		_ => 0,
+	}
+}
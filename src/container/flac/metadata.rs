@@ -0,0 +1,79 @@
+use crate::{error, message::Result};
+
+pub const BLOCK_STREAMINFO: u8 = 0;
+pub const BLOCK_PADDING: u8 = 1;
+pub const BLOCK_APPLICATION: u8 = 2;
+pub const BLOCK_SEEKTABLE: u8 = 3;
+pub const BLOCK_VORBIS_COMMENT: u8 = 4;
+pub const BLOCK_CUESHEET: u8 = 5;
+pub const BLOCK_PICTURE: u8 = 6;
+
+#[derive(Debug, Clone)]
+pub struct MetadataBlock {
+	pub kind: u8,
+	pub data: Vec<u8>,
+}
+
+/// Metadata blocks other than STREAMINFO, kept so a flac -> flac transcode can
+/// write them back. Vorbis comments are also exposed as key/value pairs.
+#[derive(Debug, Clone, Default)]
+pub struct FlacMetadata {
+	pub blocks: Vec<MetadataBlock>,
+	pub vendor: Option<String>,
+	pub comments: Vec<(String, String)>,
+}
+
+impl FlacMetadata {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn add_block(&mut self, kind: u8, data: Vec<u8>) -> Result<()> {
+		if kind == BLOCK_VORBIS_COMMENT {
+			self.parse_vorbis_comment(&data)?;
+		}
+		// padding and seek points describe the old file layout
+		if kind != BLOCK_PADDING && kind != BLOCK_SEEKTABLE {
+			self.blocks.push(MetadataBlock { kind, data });
+		}
+		Ok(())
+	}
+
+	pub fn get(&self, key: &str) -> Option<&str> {
+		let key = key.to_uppercase();
+		self.comments.iter().find(|(k, _)| k.to_uppercase() == key).map(|(_, v)| v.as_str())
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.blocks.is_empty()
+	}
+
+	fn parse_vorbis_comment(&mut self, data: &[u8]) -> Result<()> {
+		let mut offset = 0;
+		let vendor = read_string(data, &mut offset)?;
+		let count = read_u32(data, &mut offset)?;
+
+		for _ in 0..count {
+			let comment = read_string(data, &mut offset)?;
+			if let Some((key, value)) = comment.split_once('=') {
+				self.comments.push((key.to_string(), value.to_string()));
+			}
+		}
+		self.vendor = Some(vendor);
+		Ok(())
+	}
+}
+
+// vorbis comments use little-endian lengths, unlike the rest of flac
+fn read_u32(data: &[u8], offset: &mut usize) -> Result<u32> {
+	let bytes = data.get(*offset..*offset + 4).ok_or_else(|| error!("truncated vorbis comment"))?;
+	*offset += 4;
+	Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn read_string(data: &[u8], offset: &mut usize) -> Result<String> {
+	let len = read_u32(data, offset)? as usize;
+	let bytes = data.get(*offset..*offset + len).ok_or_else(|| error!("truncated vorbis comment"))?;
+	*offset += len;
+	Ok(String::from_utf8_lossy(bytes).to_string())
+}
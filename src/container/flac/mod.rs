@@ -0,0 +1,7 @@
+pub mod demuxer;
+pub mod metadata;
+pub mod muxer;
+
+pub use demuxer::FlacDemuxer;
+pub use metadata::FlacMetadata;
+pub use muxer::FlacMuxer;
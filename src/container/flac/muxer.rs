@@ -0,0 +1,133 @@
+use super::metadata::{BLOCK_PADDING, BLOCK_STREAMINFO, FlacMetadata};
+use crate::codecs;
+use crate::codecs::audio::flac::{FrameHeader, StreamInfo};
+use crate::core::Muxer;
+use crate::core::packet::Packet;
+use crate::core::stream::{self, Stream, StreamKind};
+use crate::core::time::Time;
+use crate::io::{MediaSeek, MediaWrite, SeekFrom, WritePrimitives};
+use crate::message::Result;
+
+pub struct FlacMuxer<W: MediaWrite + MediaSeek> {
+	writer: W,
+	info: StreamInfo,
+	streams: stream::Streams,
+	metadata: Option<FlacMetadata>,
+	header_written: bool,
+	streaminfo_pos: u64,
+	total_samples: u64,
+	min_frame_size: u32,
+	max_frame_size: u32,
+}
+
+impl<W: MediaWrite + MediaSeek> FlacMuxer<W> {
+	// room for tags to grow without rewriting the whole file
+	const PADDING_SIZE: u32 = 8192;
+
+	pub fn new(writer: W, info: StreamInfo) -> Result<Self> {
+		let codec_name = codecs::audio::FLAC.to_string();
+		let time = Time::new(1, info.sample_rate);
+		let mut streams = stream::Streams::new_empty();
+		let stream = Stream::new(0, 0, StreamKind::Audio, codec_name, time);
+		streams.add(stream.with_codec_private(info.to_bytes()));
+
+		Ok(Self {
+			writer,
+			info,
+			streams,
+			metadata: None,
+			header_written: false,
+			streaminfo_pos: 0,
+			total_samples: 0,
+			min_frame_size: 0,
+			max_frame_size: 0,
+		})
+	}
+
+	pub fn with_metadata(&mut self, metadata: Option<FlacMetadata>) {
+		self.metadata = metadata;
+	}
+
+	/// Replaces STREAMINFO, typically with the one an encoder completed on
+	/// flush so the MD5 signature ends up in the file.
+	pub fn set_stream_info(&mut self, info: StreamInfo) {
+		self.info = info;
+	}
+
+	fn write_header(&mut self) -> Result<()> {
+		self.writer.write_all(b"fLaC")?;
+		Self::write_block_header(&mut self.writer, BLOCK_STREAMINFO, false, 34)?;
+		self.streaminfo_pos = self.writer.stream_position()?;
+		self.writer.write_all(&self.info.to_bytes())?;
+
+		if let Some(metadata) = &self.metadata {
+			for block in &metadata.blocks {
+				Self::write_block_header(&mut self.writer, block.kind, false, block.data.len() as u32)?;
+				self.writer.write_all(&block.data)?;
+			}
+		}
+
+		Self::write_block_header(&mut self.writer, BLOCK_PADDING, true, Self::PADDING_SIZE)?;
+		self.writer.write_all(&vec![0u8; Self::PADDING_SIZE as usize])?;
+		self.header_written = true;
+		Ok(())
+	}
+
+	fn write_block_header(writer: &mut W, kind: u8, last: bool, size: u32) -> Result<()> {
+		let flag = if last { 0x80 } else { 0 };
+		writer.write_u32_be(((flag | kind as u32) << 24) | (size & 0x00FF_FFFF))
+	}
+
+	pub fn write_packet(&mut self, packet: Packet) -> Result<()> {
+		if !self.header_written {
+			self.write_header()?;
+		}
+
+		let header = FrameHeader::parse(&packet.data)?;
+		self.total_samples += header.block_size as u64;
+
+		let size = packet.data.len() as u32;
+		if self.min_frame_size == 0 || size < self.min_frame_size {
+			self.min_frame_size = size;
+		}
+		self.max_frame_size = self.max_frame_size.max(size);
+
+		self.writer.write_all(&packet.data)
+	}
+
+	pub fn finalize(&mut self) -> Result<()> {
+		if !self.header_written {
+			self.write_header()?;
+		}
+
+		let mut info = self.info;
+		info.total_samples = self.total_samples;
+		info.min_frame_size = self.min_frame_size;
+		info.max_frame_size = self.max_frame_size;
+
+		let end = self.writer.stream_position()?;
+		self.writer.seek(SeekFrom::Start(self.streaminfo_pos))?;
+		self.writer.write_all(&info.to_bytes())?;
+		self.writer.seek(SeekFrom::Start(end))?;
+		self.writer.flush()
+	}
+}
+
+impl<W: MediaWrite + MediaSeek> Muxer for FlacMuxer<W> {
+	fn streams(&self) -> &stream::Streams {
+		&self.streams
+	}
+
+	fn write(&mut self, packet: Packet) -> Result<()> {
+		self.write_packet(packet)
+	}
+
+	fn finalize(&mut self) -> Result<()> {
+		self.finalize()
+	}
+
+	fn update_codec_private(&mut self, _stream_id: u32, codec_private: &[u8]) -> Result<()> {
+		self.set_stream_info(StreamInfo::parse(codec_private)?);
+		Ok(())
+	}
+}
@@ -0,0 +1,163 @@
+use std::marker::PhantomData;
+
+use crate::container::probe::{self, ID3V2_HEADER_SIZE};
+use crate::io::MediaRead;
+use crate::message::Result;
+
+const ID3V1_SIZE: usize = 128;
+
+/// Header of a self-delimiting frame, found by scanning for its sync word.
+pub trait SyncHeader: Sized {
+	/// Bytes needed to parse a header.
+	const SIZE: usize;
+
+	fn parse(data: &[u8]) -> Option<Self>;
+
+	/// Size of the whole frame, header included.
+	fn frame_size(&self) -> usize;
+
+	/// Whether `other` belongs to the same stream as this header.
+	fn is_compatible(&self, other: &Self) -> bool;
+}
+
+/// Buffered byte source that finds frames and steps over tags.
+pub struct FrameReader<R: MediaRead, H: SyncHeader> {
+	reader: R,
+	buffer: Vec<u8>,
+	position: usize,
+	eof: bool,
+	id3v1: bool,
+	header: PhantomData<H>,
+}
+
+impl<R: MediaRead, H: SyncHeader> FrameReader<R, H> {
+	const READ_SIZE: usize = 65536;
+
+	pub fn new(reader: R) -> Self {
+		Self { reader, buffer: Vec::new(), position: 0, eof: false, id3v1: false, header: PhantomData }
+	}
+
+	/// Leaves out an ID3v1 tag at the end of the input.
+	pub fn with_id3v1(mut self) -> Self {
+		self.id3v1 = true;
+		self
+	}
+
+	/// The buffered bytes from the current position on.
+	pub fn data(&self) -> &[u8] {
+		&self.buffer[self.position..]
+	}
+
+	pub fn advance(&mut self, size: usize) {
+		self.position = (self.position + size).min(self.buffer.len());
+	}
+
+	fn fill(&mut self) -> Result<bool> {
+		if self.eof {
+			return Ok(false);
+		}
+		if self.position > Self::READ_SIZE {
+			self.buffer.drain(..self.position);
+			self.position = 0;
+		}
+		let start = self.buffer.len();
+		self.buffer.resize(start + Self::READ_SIZE, 0);
+		let read = self.reader.read(&mut self.buffer[start..])?;
+		self.buffer.truncate(start + read);
+		if read == 0 {
+			self.eof = true;
+		}
+		Ok(read > 0)
+	}
+
+	/// Makes `size` bytes available past the current position, unless the
+	/// input ends first.
+	fn ensure(&mut self, size: usize) -> Result<bool> {
+		while self.buffer.len() - self.position < size {
+			if !self.fill()? {
+				return Ok(false);
+			}
+		}
+		Ok(true)
+	}
+
+	/// End of the frame data in the buffer, before a trailing ID3v1 tag.
+	fn data_end(&self) -> usize {
+		let len = self.buffer.len();
+		if self.id3v1 && self.eof && len >= self.position + ID3V1_SIZE {
+			let tag = len - ID3V1_SIZE;
+			if &self.buffer[tag..tag + 3] == b"TAG" {
+				return tag;
+			}
+		}
+		len
+	}
+
+	pub fn skip_id3v2(&mut self) -> Result<()> {
+		while self.ensure(ID3V2_HEADER_SIZE)? {
+			let Some(total) = probe::id3v2_size(self.data()) else {
+				break;
+			};
+			self.ensure(total)?;
+			self.advance(total);
+		}
+		Ok(())
+	}
+
+	fn header_at(&self, offset: usize, reference: Option<&H>) -> Option<H> {
+		let data = self.buffer.get(offset..offset + H::SIZE)?;
+		let header = H::parse(data)?;
+		match reference {
+			Some(reference) => reference.is_compatible(&header).then_some(header),
+			None => Some(header),
+		}
+	}
+
+	/// Scans forward for a frame whose successor also starts with a matching
+	/// header, so stray sync patterns inside frame data are not taken.
+	pub fn resync(&mut self, reference: Option<&H>) -> Result<Option<H>> {
+		loop {
+			if !self.ensure(H::SIZE)? {
+				return Ok(None);
+			}
+			if let Some(header) = self.header_at(self.position, reference) {
+				self.ensure(header.frame_size() + H::SIZE)?;
+				let next = self.position + header.frame_size();
+				let end = self.data_end();
+				let confirmed = match self.header_at(next, Some(&header)) {
+					Some(_) => true,
+					None => next >= end,
+				};
+				if confirmed && next <= end {
+					return Ok(Some(header));
+				}
+			}
+			self.position += 1;
+		}
+	}
+
+	/// Returns the frame at the current position and moves past it.
+	pub fn next_frame(&mut self, reference: &H) -> Result<Option<(H, Vec<u8>)>> {
+		self.skip_id3v2()?;
+		if !self.ensure(H::SIZE)? || self.position >= self.data_end() {
+			return Ok(None);
+		}
+
+		let header = match self.header_at(self.position, Some(reference)) {
+			Some(header) => header,
+			None => match self.resync(Some(reference))? {
+				Some(header) => header,
+				None => return Ok(None),
+			},
+		};
+
+		// a frame cut short by the end of the input is dropped
+		let size = header.frame_size();
+		if !self.ensure(size)? || self.position + size > self.data_end() {
+			return Ok(None);
+		}
+		let data = self.buffer[self.position..self.position + size].to_vec();
+		self.position += size;
+		Ok(Some((header, data)))
+	}
+}
@@ -0,0 +1,119 @@
+use std::any::Any;
+use std::cmp::Ordering;
+use std::collections::VecDeque;
+
+use crate::core::Muxer;
+use crate::core::packet::Packet;
+use crate::core::stream::Streams;
+use crate::{error, message::Result};
+
+/// Seconds the queued packets may span before the earliest one is written
+/// without waiting for every stream.
+pub const DEFAULT_MAX_DELTA: f64 = 10.0;
+
+/// Puts the packets of several streams in decoding order in front of a
+/// muxer. Packets are queued per stream and the earliest one is written
+/// once every stream has one queued, so a stream that goes quiet, such as
+/// subtitles, holds the others back for at most the maximum delta.
+pub struct Interleaver<M: Muxer> {
+	muxer: M,
+	queues: Vec<VecDeque<Packet>>,
+	max_delta: f64,
+}
+
+impl<M: Muxer> Interleaver<M> {
+	pub fn new(muxer: M) -> Self {
+		let queues = vec![VecDeque::new(); muxer.streams().all().len()];
+		Self { muxer, queues, max_delta: DEFAULT_MAX_DELTA }
+	}
+
+	/// Sets how many seconds the queued packets may span.
+	pub fn with_max_delta(mut self, seconds: f64) -> Self {
+		self.max_delta = seconds;
+		self
+	}
+
+	pub fn muxer(&self) -> &M {
+		&self.muxer
+	}
+
+	pub fn into_inner(self) -> M {
+		self.muxer
+	}
+
+	pub fn write_packet(&mut self, packet: Packet) -> Result<()> {
+		let stream_id = packet.stream_id;
+		let queue = self.queues.get_mut(stream_id as usize);
+		queue.ok_or_else(|| error!("stream {} is not in the output", stream_id))?.push_back(packet);
+
+		while self.ready() {
+			self.write_earliest()?;
+		}
+		Ok(())
+	}
+
+	/// Writes what is still queued in order, then finalizes the muxer.
+	pub fn finalize(&mut self) -> Result<()> {
+		while self.write_earliest()? {}
+		self.muxer.finalize()
+	}
+
+	fn ready(&self) -> bool {
+		if self.queues.iter().all(|queue| !queue.is_empty()) {
+			return true;
+		}
+		let heads = self.queues.iter().filter_map(VecDeque::front);
+		let tails = self.queues.iter().filter_map(VecDeque::back);
+		match (heads.min_by(|a, b| order(a, b)), tails.max_by(|a, b| order(a, b))) {
+			(Some(first), Some(last)) => {
+				let span = last.time.to_seconds(last.dts) - first.time.to_seconds(first.dts);
+				span > self.max_delta
+			}
+			_ => false,
+		}
+	}
+
+	/// Writes the queued packet with the earliest decoding time, `false`
+	/// when none is left.
+	fn write_earliest(&mut self) -> Result<bool> {
+		let earliest = self
+			.queues
+			.iter()
+			.enumerate()
+			.filter_map(|(index, queue)| Some((index, queue.front()?)))
+			.min_by(|(_, a), (_, b)| order(a, b))
+			.map(|(index, _)| index);
+		let Some(packet) = earliest.and_then(|index| self.queues[index].pop_front()) else {
+			return Ok(false);
+		};
+		self.muxer.write(packet)?;
+		Ok(true)
+	}
+}
+
+// ties go to the lower stream id
+fn order(a: &Packet, b: &Packet) -> Ordering {
+	a.time.compare(a.dts, b.time, b.dts).then(a.stream_id.cmp(&b.stream_id))
+}
+
+impl<M: Muxer> Muxer for Interleaver<M> {
+	fn streams(&self) -> &Streams {
+		self.muxer.streams()
+	}
+
+	fn write(&mut self, packet: Packet) -> Result<()> {
+		self.write_packet(packet)
+	}
+
+	fn finalize(&mut self) -> Result<()> {
+		self.finalize()
+	}
+
+	fn update_codec_private(&mut self, stream_id: u32, codec_private: &[u8]) -> Result<()> {
+		self.muxer.update_codec_private(stream_id, codec_private)
+	}
+
+	fn update_container_metadata(&mut self, metadata: &dyn Any) -> Result<()> {
+		self.muxer.update_container_metadata(metadata)
+	}
+}
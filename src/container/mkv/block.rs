@@ -0,0 +1,142 @@
+use super::ebml::{self, put_element, put_id, put_int, put_size, put_uint, read_vint};
+use crate::{error, message::Result};
+
+const FLAG_KEYFRAME: u8 = 0x80;
+const FLAG_DISCARDABLE: u8 = 0x01;
+
+const LACING_XIPH: u8 = 1;
+const LACING_EBML: u8 = 3;
+
+/// A SimpleBlock or the Block of a BlockGroup, split into its laced frames.
+#[derive(Debug, Clone)]
+pub struct Block<'a> {
+	pub track: u64,
+	/// Timestamp relative to the cluster, in segment timestamp units.
+	pub timestamp: i16,
+	/// Only meaningful for SimpleBlocks; a BlockGroup is a keyframe when it
+	/// has no ReferenceBlock.
+	pub keyframe: bool,
+	pub discardable: bool,
+	pub frames: Vec<&'a [u8]>,
+}
+
+impl<'a> Block<'a> {
+	pub fn parse(data: &'a [u8]) -> Result<Self> {
+		let (track, length) = read_vint(data)?;
+		let header =
+			data.get(length..length + 3).ok_or_else(|| error!("matroska block is truncated"))?;
+		let timestamp = i16::from_be_bytes([header[0], header[1]]);
+		let flags = header[2];
+		let payload = &data[length + 3..];
+		let frames = match (flags >> 1) & 0x03 {
+			0 => vec![payload],
+			lacing => unlace(payload, lacing)?,
+		};
+		Ok(Self {
+			track,
+			timestamp,
+			keyframe: flags & FLAG_KEYFRAME != 0,
+			discardable: flags & FLAG_DISCARDABLE != 0,
+			frames,
+		})
+	}
+}
+
+/// Writes a SimpleBlock holding a single frame, without lacing.
+pub fn put_simple_block(
+	out: &mut Vec<u8>,
+	track: u64,
+	timestamp: i16,
+	keyframe: bool,
+	frame: &[u8],
+) {
+	let flags = if keyframe { FLAG_KEYFRAME } else { 0 };
+	put_block(out, ebml::SIMPLE_BLOCK, track, timestamp, flags, frame);
+}
+
+/// Writes a BlockGroup holding a single keyframe and how long it lasts, in
+/// segment timestamp units. A positive `discard_padding` tells, in
+/// nanoseconds, how much of the end of the decoded frame is padding.
+pub fn put_block_group(
+	out: &mut Vec<u8>,
+	track: u64,
+	timestamp: i16,
+	frame: &[u8],
+	duration: u64,
+	discard_padding: i64,
+) {
+	let mut group = Vec::new();
+	put_block(&mut group, ebml::BLOCK, track, timestamp, 0, frame);
+	put_uint(&mut group, ebml::BLOCK_DURATION, duration);
+	if discard_padding > 0 {
+		put_int(&mut group, ebml::DISCARD_PADDING, discard_padding);
+	}
+	put_element(out, ebml::BLOCK_GROUP, &group);
+}
+
+fn put_block(out: &mut Vec<u8>, id: u32, track: u64, timestamp: i16, flags: u8, frame: &[u8]) {
+	let mut header = Vec::with_capacity(4);
+	put_size(&mut header, track);
+	header.extend_from_slice(&timestamp.to_be_bytes());
+	header.push(flags);
+	put_id(out, id);
+	put_size(out, (header.len() + frame.len()) as u64);
+	out.extend_from_slice(&header);
+	out.extend_from_slice(frame);
+}
+
+fn unlace(data: &[u8], lacing: u8) -> Result<Vec<&[u8]>> {
+	let truncated = || error!("matroska block lacing is truncated");
+	let count = *data.first().ok_or_else(truncated)? as usize + 1;
+	let mut position = 1;
+	let mut sizes = Vec::with_capacity(count);
+	match lacing {
+		LACING_XIPH => {
+			for _ in 1..count {
+				let mut size = 0;
+				loop {
+					let byte = *data.get(position).ok_or_else(truncated)?;
+					position += 1;
+					size += byte as usize;
+					if byte != 255 {
+						break;
+					}
+				}
+				sizes.push(size);
+			}
+		}
+		LACING_EBML if count > 1 => {
+			let (first, length) = read_vint(&data[position..])?;
+			position += length;
+			let mut size = first as i64;
+			sizes.push(first as usize);
+			for _ in 2..count {
+				// later sizes are stored as signed differences to the previous one
+				let (raw, length) = read_vint(&data[position..])?;
+				position += length;
+				size += raw as i64 - ((1i64 << (7 * length - 1)) - 1);
+				if size < 0 {
+					return Err(error!("matroska block has a negative lace size"));
+				}
+				sizes.push(size as usize);
+			}
+		}
+		LACING_EBML => {}
+		_ => {
+			let rest = data.len() - position;
+			if !rest.is_multiple_of(count) {
+				return Err(error!("matroska block does not split into {} frames", count));
+			}
+			sizes.resize(count - 1, rest / count);
+		}
+	}
+
+	let mut frames = Vec::with_capacity(count);
+	for size in sizes {
+		let frame = data.get(position..position + size).ok_or_else(truncated)?;
+		frames.push(frame);
+		position += size;
+	}
+	frames.push(&data[position..]);
+	Ok(frames)
+}
@@ -0,0 +1,63 @@
+use super::ebml::{self, children, put_element, put_uint, read_uint};
+use crate::message::Result;
+
+/// One entry of the Cues index: where a track can start decoding at `time`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CuePoint {
+	/// Timestamp in segment timestamp units.
+	pub time: u64,
+	pub track: u64,
+	/// Offset of the cluster from the start of the segment payload.
+	pub cluster_position: u64,
+	/// Offset of the block from the start of the cluster payload.
+	pub relative_position: Option<u64>,
+}
+
+pub fn parse_cues(data: &[u8]) -> Result<Vec<CuePoint>> {
+	let mut cues = Vec::new();
+	for (id, point) in children(data)? {
+		if id != ebml::CUE_POINT {
+			continue;
+		}
+		let mut time = 0;
+		let mut positions = Vec::new();
+		for (id, value) in children(point)? {
+			match id {
+				ebml::CUE_TIME => time = read_uint(value),
+				ebml::CUE_TRACK_POSITIONS => positions.push(value),
+				_ => {}
+			}
+		}
+		for position in positions {
+			let mut cue = CuePoint { time, track: 0, cluster_position: 0, relative_position: None };
+			for (id, value) in children(position)? {
+				match id {
+					ebml::CUE_TRACK => cue.track = read_uint(value),
+					ebml::CUE_CLUSTER_POSITION => cue.cluster_position = read_uint(value),
+					ebml::CUE_RELATIVE_POSITION => cue.relative_position = Some(read_uint(value)),
+					_ => {}
+				}
+			}
+			cues.push(cue);
+		}
+	}
+	Ok(cues)
+}
+
+impl CuePoint {
+	/// The CuePoint element, with a single track position.
+	pub fn to_bytes(&self) -> Vec<u8> {
+		let mut position = Vec::new();
+		put_uint(&mut position, ebml::CUE_TRACK, self.track);
+		put_uint(&mut position, ebml::CUE_CLUSTER_POSITION, self.cluster_position);
+		if let Some(relative) = self.relative_position {
+			put_uint(&mut position, ebml::CUE_RELATIVE_POSITION, relative);
+		}
+		let mut point = Vec::new();
+		put_uint(&mut point, ebml::CUE_TIME, self.time);
+		put_element(&mut point, ebml::CUE_TRACK_POSITIONS, &position);
+		let mut out = Vec::new();
+		put_element(&mut out, ebml::CUE_POINT, &point);
+		out
+	}
+}
@@ -0,0 +1,308 @@
+use super::block::Block;
+use super::cues::{CuePoint, parse_cues};
+use super::ebml::read_uint;
+use super::ebml::{self, EbmlReader, Header, children, read_float, read_int, read_string};
+use super::track::Track;
+use crate::container::probe::SCORE_MAX;
+use crate::core::packet::Packet;
+use crate::core::time::Time;
+use crate::core::{Demuxer, stream};
+use crate::io::MediaRead;
+use crate::{error, message::Result};
+use std::collections::VecDeque;
+
+const NANOSECONDS: u64 = 1_000_000_000;
+const DEFAULT_TIMESTAMP_SCALE: u64 = 1_000_000;
+
+pub struct MkvDemuxer<R: MediaRead> {
+	reader: EbmlReader<R>,
+	doc_type: String,
+	timestamp_scale: u64,
+	duration: Option<f64>,
+	segment_offset: u64,
+	/// Tracks with a known codec, in stream order.
+	tracks: Vec<Track>,
+	streams: stream::Streams,
+	cues: Vec<CuePoint>,
+	cluster_timestamp: i64,
+	/// Where the next frame of each track starts, for tracks whose frames
+	/// are counted in samples.
+	next_pts: Vec<Option<i64>>,
+	pending: VecDeque<Packet>,
+}
+
+impl<R: MediaRead> MkvDemuxer<R> {
+	pub fn new(reader: R) -> Result<Self> {
+		let mut reader = EbmlReader::new(reader);
+		let header = reader.read_header()?.filter(|header| header.id == ebml::EBML_HEADER);
+		let header = header.ok_or_else(|| error!("not a matroska file: missing EBML header"))?;
+		let doc_type = children(&reader.read_data(&header)?)?
+			.into_iter()
+			.find(|(id, _)| *id == ebml::DOC_TYPE)
+			.map_or_else(|| "matroska".to_string(), |(_, value)| read_string(value));
+		if doc_type != "matroska" && doc_type != "webm" {
+			return Err(error!("unsupported ebml document type '{}'", doc_type));
+		}
+
+		loop {
+			let header = reader.read_header()?.ok_or_else(|| error!("matroska file has no segment"))?;
+			if header.id == ebml::SEGMENT {
+				break;
+			}
+			reader.skip(&header)?;
+		}
+
+		let mut demuxer = Self {
+			segment_offset: reader.position(),
+			reader,
+			doc_type,
+			timestamp_scale: DEFAULT_TIMESTAMP_SCALE,
+			duration: None,
+			tracks: Vec::new(),
+			streams: stream::Streams::new_empty(),
+			cues: Vec::new(),
+			cluster_timestamp: 0,
+			next_pts: Vec::new(),
+			pending: VecDeque::new(),
+		};
+
+		// everything up to the first cluster describes the segment
+		let mut has_tracks = false;
+		while let Some(header) = demuxer.reader.read_header()? {
+			match header.id {
+				ebml::CLUSTER => break,
+				ebml::INFO => demuxer.parse_info(&header)?,
+				ebml::TRACKS => {
+					demuxer.parse_tracks(&header)?;
+					has_tracks = true;
+				}
+				ebml::CUES => demuxer.cues = parse_cues(&demuxer.reader.read_data(&header)?)?,
+				_ => demuxer.reader.skip(&header)?,
+			}
+		}
+		if !has_tracks {
+			return Err(error!("matroska file has no tracks"));
+		}
+
+		for (index, track) in demuxer.tracks.iter().enumerate() {
+			let (Some(kind), Some(codec)) = (track.kind, track.codec()) else { continue };
+			let time = demuxer.track_time(track);
+			let stream = stream::Stream::new(index as u32, index, kind, codec.to_string(), time)
+				.with_codec_private(track.stream_codec_private()?);
+			demuxer.streams.add(stream);
+		}
+		demuxer.next_pts = vec![None; demuxer.tracks.len()];
+		Ok(demuxer)
+	}
+
+	/// "matroska" or "webm".
+	pub fn doc_type(&self) -> &str {
+		&self.doc_type
+	}
+
+	/// Segment duration in seconds, when the file states it.
+	pub fn duration(&self) -> Option<f64> {
+		self.duration.map(|duration| duration * self.timestamp_scale as f64 / NANOSECONDS as f64)
+	}
+
+	/// The tracks behind the streams, in the same order.
+	pub fn tracks(&self) -> &[Track] {
+		&self.tracks
+	}
+
+	/// Cue points read so far. Cues usually follow the clusters, so they
+	/// may only be complete once every packet has been read.
+	pub fn cues(&self) -> &[CuePoint] {
+		&self.cues
+	}
+
+	/// Offset of the segment payload, which cluster positions count from.
+	pub fn segment_offset(&self) -> u64 {
+		self.segment_offset
+	}
+
+	fn time(&self) -> Time {
+		Time::new(self.timestamp_scale as u32, NANOSECONDS as u32).simplify()
+	}
+
+	/// Audio is timed in samples, so the codec delay and padding, which are
+	/// given in nanoseconds, come out exact.
+	fn track_time(&self, track: &Track) -> Time {
+		track.sample_time().unwrap_or_else(|| self.time())
+	}
+
+	fn parse_info(&mut self, header: &Header) -> Result<()> {
+		for (id, value) in children(&self.reader.read_data(header)?)? {
+			match id {
+				ebml::TIMESTAMP_SCALE => self.timestamp_scale = read_uint(value),
+				ebml::DURATION => self.duration = Some(read_float(value)),
+				_ => {}
+			}
+		}
+		if self.timestamp_scale == 0 || self.timestamp_scale > u32::MAX as u64 {
+			return Err(error!("unsupported matroska timestamp scale {}", self.timestamp_scale));
+		}
+		Ok(())
+	}
+
+	fn parse_tracks(&mut self, header: &Header) -> Result<()> {
+		for (id, value) in children(&self.reader.read_data(header)?)? {
+			if id != ebml::TRACK_ENTRY {
+				continue;
+			}
+			// tracks of unknown codecs are dropped, so stream ids stay dense
+			let track = Track::parse(value)?;
+			if track.kind.is_some() && track.codec().is_some() {
+				self.tracks.push(track);
+			}
+		}
+		Ok(())
+	}
+
+	fn parse_block_group(&mut self, data: &[u8]) -> Result<()> {
+		let mut block = None;
+		let mut keyframe = true;
+		let mut duration = None;
+		let mut padding = 0;
+		for (id, value) in children(data)? {
+			match id {
+				ebml::BLOCK => block = Some(value),
+				ebml::REFERENCE_BLOCK => keyframe = false,
+				ebml::BLOCK_DURATION => duration = Some(read_uint(value) as i64),
+				ebml::DISCARD_PADDING => padding = read_int(value),
+				_ => {}
+			}
+		}
+		let block = block.ok_or_else(|| error!("matroska block group has no block"))?;
+		self.queue_block(Block::parse(block)?, keyframe, duration, padding);
+		Ok(())
+	}
+
+	/// Queues the frames of a block; `block_duration` is how long a block of
+	/// a single frame lasts, when its group states it, and `padding` how
+	/// much of its end is left out, in nanoseconds.
+	fn queue_block(
+		&mut self,
+		block: Block,
+		keyframe: bool,
+		block_duration: Option<i64>,
+		padding: i64,
+	) {
+		let Some(index) = self.tracks.iter().position(|track| track.number == block.track) else {
+			return;
+		};
+		let track = &self.tracks[index];
+		let segment = self.time();
+		let time = self.track_time(track);
+		let nanoseconds = Time::new(1, NANOSECONDS as u32);
+		let timestamp = self.cluster_timestamp + block.timestamp as i64;
+		// timestamps of Opus and similar codecs include the samples the
+		// decoder drops, which come before zero
+		let delay = time.round_pts(track.codec_delay as i64, nanoseconds);
+		let mut pts = time.round_pts(timestamp, segment) - delay;
+		let frame = track.default_duration.map(|duration| time.round_pts(duration as i64, nanoseconds));
+
+		// block timestamps are rounded to the segment scale; a frame that
+		// starts within that of where the last one ended starts there
+		let counted = track.sample_time().and(frame);
+		if counted.is_some()
+			&& let Some(next) = self.next_pts[index]
+			&& (pts - next).abs() <= time.round_pts(1, segment).max(1)
+		{
+			pts = next;
+		}
+
+		let single = block.frames.len() == 1;
+		let block_duration = match (block_duration.filter(|_| single), counted) {
+			(Some(_), Some(frame)) if padding > 0 => {
+				Some((frame - time.round_pts(padding, nanoseconds)).max(0))
+			}
+			(Some(duration), _) => Some(time.round_pts(duration, segment)),
+			(None, _) => None,
+		};
+		for frame_data in block.frames {
+			let packet = Packet::new(frame_data.to_vec(), index as u32, time)
+				.with_pts(pts)
+				.with_dts(pts)
+				.with_duration(block_duration.unwrap_or(0))
+				.with_keyframe(keyframe);
+			self.pending.push_back(packet);
+			pts += frame.unwrap_or(0);
+		}
+		if counted.is_some() {
+			self.next_pts[index] = Some(pts);
+		}
+	}
+}
+
+impl<R: MediaRead> Demuxer for MkvDemuxer<R> {
+	fn streams(&self) -> &stream::Streams {
+		&self.streams
+	}
+
+	fn read_packet(&mut self) -> Result<Option<Packet>> {
+		loop {
+			if let Some(packet) = self.pending.pop_front() {
+				return Ok(Some(packet));
+			}
+			let Some(header) = self.reader.read_header()? else {
+				return Ok(None);
+			};
+			// clusters are entered rather than read whole, which also copes
+			// with the unknown sizes of live streams
+			match header.id {
+				ebml::CLUSTER => {}
+				ebml::TIMESTAMP => {
+					self.cluster_timestamp = read_uint(&self.reader.read_data(&header)?) as i64;
+				}
+				ebml::SIMPLE_BLOCK => {
+					let data = self.reader.read_data(&header)?;
+					let block = Block::parse(&data)?;
+					let keyframe = block.keyframe;
+					self.queue_block(block, keyframe, None, 0);
+				}
+				ebml::BLOCK_GROUP => {
+					let data = self.reader.read_data(&header)?;
+					self.parse_block_group(&data)?;
+				}
+				ebml::CUES => {
+					let cues = parse_cues(&self.reader.read_data(&header)?)?;
+					self.cues.extend(cues);
+				}
+				// a chained segment would bring tracks of its own
+				ebml::EBML_HEADER | ebml::SEGMENT => return Ok(None),
+				_ => self.reader.skip(&header)?,
+			}
+		}
+	}
+}
+
+/// Scores how likely `data` is the start of a matroska file.
+pub fn probe(data: &[u8]) -> u8 {
+	match doc_type(data) {
+		Some(b"matroska") => SCORE_MAX,
+		// webm, or an EBML header too long to find the doc type in
+		Some(_) => SCORE_MAX / 2,
+		None if data.starts_with(&ebml::EBML_HEADER.to_be_bytes()) => SCORE_MAX / 2,
+		None => 0,
+	}
+}
+
+/// Scores how likely `data` is the start of a webm file.
+pub fn probe_webm(data: &[u8]) -> u8 {
+	if doc_type(data) == Some(b"webm") { SCORE_MAX } else { 0 }
+}
+
+/// The doc type of the EBML header at the start of `data`.
+fn doc_type(data: &[u8]) -> Option<&[u8]> {
+	let header = data.strip_prefix(&ebml::EBML_HEADER.to_be_bytes())?;
+	let header = &header[..header.len().min(64)];
+	let id = ebml::DOC_TYPE.to_be_bytes();
+	let start = header.windows(2).position(|window| window == &id[2..])? + 2;
+	// doc types are short, so their size is a one byte vint
+	let size = *header.get(start)?;
+	if size & 0x80 == 0 {
+		return None;
+	}
+	header.get(start + 1..start + 1 + (size & 0x7F) as usize)
+}
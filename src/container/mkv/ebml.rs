@@ -0,0 +1,273 @@
+use crate::io::{MediaRead, ReadPrimitives};
+use crate::{error, message::Result};
+
+// EBML header
+pub const EBML_HEADER: u32 = 0x1A45_DFA3;
+pub const EBML_VERSION: u32 = 0x4286;
+pub const EBML_READ_VERSION: u32 = 0x42F7;
+pub const EBML_MAX_ID_LENGTH: u32 = 0x42F2;
+pub const EBML_MAX_SIZE_LENGTH: u32 = 0x42F3;
+pub const DOC_TYPE: u32 = 0x4282;
+pub const DOC_TYPE_VERSION: u32 = 0x4287;
+pub const DOC_TYPE_READ_VERSION: u32 = 0x4285;
+pub const VOID: u32 = 0xEC;
+
+// top level
+pub const SEGMENT: u32 = 0x1853_8067;
+pub const SEEK_HEAD: u32 = 0x114D_9B74;
+pub const INFO: u32 = 0x1549_A966;
+pub const TRACKS: u32 = 0x1654_AE6B;
+pub const CLUSTER: u32 = 0x1F43_B675;
+pub const CUES: u32 = 0x1C53_BB6B;
+
+// seek head
+pub const SEEK: u32 = 0x4DBB;
+pub const SEEK_ID: u32 = 0x53AB;
+pub const SEEK_POSITION: u32 = 0x53AC;
+
+// info
+pub const TIMESTAMP_SCALE: u32 = 0x2A_D7B1;
+pub const DURATION: u32 = 0x4489;
+pub const MUXING_APP: u32 = 0x4D80;
+pub const WRITING_APP: u32 = 0x5741;
+
+// tracks
+pub const TRACK_ENTRY: u32 = 0xAE;
+pub const TRACK_NUMBER: u32 = 0xD7;
+pub const TRACK_UID: u32 = 0x73C5;
+pub const TRACK_TYPE: u32 = 0x83;
+pub const FLAG_LACING: u32 = 0x9C;
+pub const CODEC_ID: u32 = 0x86;
+pub const CODEC_PRIVATE: u32 = 0x63A2;
+pub const CODEC_DELAY: u32 = 0x56AA;
+pub const SEEK_PRE_ROLL: u32 = 0x56BB;
+pub const DEFAULT_DURATION: u32 = 0x23_E383;
+pub const LANGUAGE: u32 = 0x22_B59C;
+pub const VIDEO: u32 = 0xE0;
+pub const PIXEL_WIDTH: u32 = 0xB0;
+pub const PIXEL_HEIGHT: u32 = 0xBA;
+pub const AUDIO: u32 = 0xE1;
+pub const SAMPLING_FREQUENCY: u32 = 0xB5;
+pub const CHANNELS: u32 = 0x9F;
+pub const BIT_DEPTH: u32 = 0x6264;
+
+// clusters
+pub const TIMESTAMP: u32 = 0xE7;
+pub const SIMPLE_BLOCK: u32 = 0xA3;
+pub const BLOCK_GROUP: u32 = 0xA0;
+pub const BLOCK: u32 = 0xA1;
+pub const BLOCK_DURATION: u32 = 0x9B;
+pub const REFERENCE_BLOCK: u32 = 0xFB;
+pub const DISCARD_PADDING: u32 = 0x75A2;
+
+// cues
+pub const CUE_POINT: u32 = 0xBB;
+pub const CUE_TIME: u32 = 0xB3;
+pub const CUE_TRACK_POSITIONS: u32 = 0xB7;
+pub const CUE_TRACK: u32 = 0xF7;
+pub const CUE_CLUSTER_POSITION: u32 = 0xF1;
+pub const CUE_RELATIVE_POSITION: u32 = 0xF0;
+
+// payloads read into memory, anything bigger is taken as corrupt
+const MAX_ELEMENT_SIZE: u64 = 1 << 28;
+
+/// Element header: its id and the size of its payload, `None` when the
+/// size is unknown (live streams leave segments and clusters open).
+#[derive(Debug, Clone, Copy)]
+pub struct Header {
+	pub id: u32,
+	pub size: Option<u64>,
+}
+
+/// Reads element headers and payloads from a stream, keeping count of the
+/// bytes consumed so element offsets can be related to Cues positions.
+pub struct EbmlReader<R: MediaRead> {
+	reader: R,
+	position: u64,
+}
+
+impl<R: MediaRead> EbmlReader<R> {
+	pub fn new(reader: R) -> Self {
+		Self { reader, position: 0 }
+	}
+
+	/// Offset of the next byte from the start of the stream.
+	pub fn position(&self) -> u64 {
+		self.position
+	}
+
+	/// Reads the next element header, or `None` at the end of the stream.
+	pub fn read_header(&mut self) -> Result<Option<Header>> {
+		let mut first = [0u8; 1];
+		if self.reader.read(&mut first)? == 0 {
+			return Ok(None);
+		}
+		let (id, length) = self.read_vint(first[0])?;
+		if length > 4 {
+			return Err(error!("invalid ebml element id"));
+		}
+		let first = self.reader.read_u8()?;
+		let (size, length) = self.read_vint(first)?;
+		Ok(Some(Header { id: id as u32, size: size_value(size, length) }))
+	}
+
+	/// Reads the payload of an element with a known size.
+	pub fn read_data(&mut self, header: &Header) -> Result<Vec<u8>> {
+		let size = header.size.ok_or_else(|| error!("element {:#x} has an unknown size", header.id))?;
+		if size > MAX_ELEMENT_SIZE {
+			return Err(error!("element {:#x} is too large ({} bytes)", header.id, size));
+		}
+		let mut data = vec![0u8; size as usize];
+		self.reader.read_exact(&mut data)?;
+		self.position += size;
+		Ok(data)
+	}
+
+	/// Skips the payload of an element with a known size.
+	pub fn skip(&mut self, header: &Header) -> Result<()> {
+		let size = header.size.ok_or_else(|| error!("element {:#x} has an unknown size", header.id))?;
+		let mut chunk = [0u8; 8192];
+		let mut left = size;
+		while left > 0 {
+			let count = left.min(chunk.len() as u64) as usize;
+			self.reader.read_exact(&mut chunk[..count])?;
+			left -= count as u64;
+		}
+		self.position += size;
+		Ok(())
+	}
+
+	/// Reads the rest of a variable length integer whose first byte is
+	/// `first`, returning it with its length marker and its length.
+	fn read_vint(&mut self, first: u8) -> Result<(u64, usize)> {
+		let length = vint_length(first)?;
+		let mut value = first as u64;
+		for _ in 1..length {
+			value = (value << 8) | self.reader.read_u8()? as u64;
+		}
+		self.position += length as u64;
+		Ok((value, length))
+	}
+}
+
+fn vint_length(first: u8) -> Result<usize> {
+	match first.leading_zeros() {
+		8 => Err(error!("invalid ebml variable length integer")),
+		zeros => Ok(zeros as usize + 1),
+	}
+}
+
+/// Strips the length marker from a size, mapping the all ones value to an
+/// unknown size.
+fn size_value(raw: u64, length: usize) -> Option<u64> {
+	let mask = (1u64 << (7 * length)) - 1;
+	let value = raw & mask;
+	(value != mask).then_some(value)
+}
+
+/// Reads a variable length integer from the start of `data`, returning its
+/// value without the length marker and the bytes it took.
+pub fn read_vint(data: &[u8]) -> Result<(u64, usize)> {
+	let first = *data.first().ok_or_else(|| error!("truncated ebml variable length integer"))?;
+	let length = vint_length(first)?;
+	let bytes = data.get(..length).ok_or_else(|| error!("truncated ebml variable length integer"))?;
+	let raw = bytes.iter().fold(0u64, |acc, &byte| (acc << 8) | byte as u64);
+	Ok((raw & ((1u64 << (7 * length)) - 1), length))
+}
+
+/// Splits the payload of a master element into its children.
+pub fn children(mut data: &[u8]) -> Result<Vec<(u32, &[u8])>> {
+	let mut elements = Vec::new();
+	while !data.is_empty() {
+		let length = vint_length(data[0])?;
+		if length > 4 || data.len() < length {
+			return Err(error!("invalid ebml element id"));
+		}
+		let id = data[..length].iter().fold(0u32, |acc, &byte| (acc << 8) | byte as u32);
+		let (size, size_length) = read_vint(&data[length..])?;
+		let start = length + size_length;
+		let end = start.checked_add(size as usize).filter(|&end| end <= data.len());
+		let end = end.ok_or_else(|| error!("ebml element {:#x} overruns its parent", id))?;
+		elements.push((id, &data[start..end]));
+		data = &data[end..];
+	}
+	Ok(elements)
+}
+
+pub fn read_uint(data: &[u8]) -> u64 {
+	data.iter().fold(0u64, |acc, &byte| (acc << 8) | byte as u64)
+}
+
+pub fn read_int(data: &[u8]) -> i64 {
+	match data.len() {
+		0 => 0,
+		len => ((read_uint(data) << (64 - 8 * len.min(8))) as i64) >> (64 - 8 * len.min(8)),
+	}
+}
+
+pub fn read_float(data: &[u8]) -> f64 {
+	match data.len() {
+		4 => f32::from_be_bytes(data.try_into().unwrap()) as f64,
+		8 => f64::from_be_bytes(data.try_into().unwrap()),
+		_ => 0.0,
+	}
+}
+
+/// Reads a string element, which may be padded with zero bytes.
+pub fn read_string(data: &[u8]) -> String {
+	let end = data.iter().position(|&byte| byte == 0).unwrap_or(data.len());
+	String::from_utf8_lossy(&data[..end]).into_owned()
+}
+
+/// Size field of an element left open, for live output.
+pub const UNKNOWN_SIZE: [u8; 8] = [0x01, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF];
+
+pub fn put_id(out: &mut Vec<u8>, id: u32) {
+	let length = (4 - id.leading_zeros() as usize / 8).max(1);
+	out.extend_from_slice(&id.to_be_bytes()[4 - length..]);
+}
+
+/// Writes a size in the fewest bytes, avoiding the all ones unknown size.
+pub fn put_size(out: &mut Vec<u8>, size: u64) {
+	let length = (1..8).find(|&length| size < (1u64 << (7 * length)) - 1).unwrap_or(8);
+	put_size_fixed(out, size, length);
+}
+
+/// Writes a size in exactly `length` bytes, so it can be patched later.
+pub fn put_size_fixed(out: &mut Vec<u8>, size: u64, length: usize) {
+	let value = size | (1u64 << (7 * length));
+	out.extend_from_slice(&value.to_be_bytes()[8 - length..]);
+}
+
+pub fn put_element(out: &mut Vec<u8>, id: u32, data: &[u8]) {
+	put_id(out, id);
+	put_size(out, data.len() as u64);
+	out.extend_from_slice(data);
+}
+
+pub fn put_uint(out: &mut Vec<u8>, id: u32, value: u64) {
+	let length = (8 - value.leading_zeros() as usize / 8).max(1);
+	put_element(out, id, &value.to_be_bytes()[8 - length..]);
+}
+
+pub fn put_int(out: &mut Vec<u8>, id: u32, value: i64) {
+	// the fewest bytes whose sign bit still matches the value
+	let redundant = (value.leading_zeros().max(value.leading_ones()) as usize - 1) / 8;
+	put_element(out, id, &value.to_be_bytes()[redundant.min(7)..]);
+}
+
+pub fn put_float(out: &mut Vec<u8>, id: u32, value: f64) {
+	put_element(out, id, &value.to_be_bytes());
+}
+
+pub fn put_string(out: &mut Vec<u8>, id: u32, value: &str) {
+	put_element(out, id, value.as_bytes());
+}
+
+/// Fills exactly `total` bytes, at least two, with a Void element.
+pub fn put_void(out: &mut Vec<u8>, total: usize) {
+	put_id(out, VOID);
+	let length = if total - 2 < 127 { 1 } else { 8 };
+	put_size_fixed(out, (total - 1 - length) as u64, length);
+	out.resize(out.len() + total - 1 - length, 0);
+}
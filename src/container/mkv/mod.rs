@@ -1 +1,11 @@
+pub mod block;
+pub mod cues;
+pub mod demuxer;
+pub mod ebml;
+pub mod muxer;
+pub mod track;
 
+pub use cues::CuePoint;
+pub use demuxer::MkvDemuxer;
+pub use muxer::MkvMuxer;
+pub use track::Track;
@@ -0,0 +1,374 @@
+use super::block::{put_block_group, put_simple_block};
+use super::cues::CuePoint;
+use super::ebml::{self, UNKNOWN_SIZE, put_element, put_float, put_id, put_size_fixed};
+use super::ebml::{put_string, put_uint, put_void};
+use super::track::Track;
+use crate::codecs;
+use crate::core::Muxer;
+use crate::core::packet::Packet;
+use crate::core::stream::{self, Stream, StreamKind};
+use crate::core::time::Time;
+use crate::io::{MediaSeek, MediaWrite, SeekFrom, WritePrimitives};
+use crate::{error, message::Result};
+
+const TIMESTAMP_SCALE: u64 = 1_000_000;
+const SEGMENT_TIME: Time = Time { num: 1, den: 1000 };
+const NANOSECONDS: Time = Time { num: 1, den: 1_000_000_000 };
+// room kept after the segment header for the SeekHead, which only knows
+// where the Cues are once every cluster has been written
+const SEEK_HEAD_SIZE: usize = 96;
+// audio only files get a new cluster after this many milliseconds
+const MAX_CLUSTER_DURATION: i64 = 5000;
+
+const WEBM_CODECS: &[&str] = &[
+	codecs::video::VP8,
+	codecs::video::VP9,
+	codecs::video::AV1,
+	codecs::audio::VORBIS,
+	codecs::audio::OPUS,
+	codecs::subtitle::VTT,
+];
+
+type SeekFn<W> = fn(&mut W, SeekFrom) -> Result<u64>;
+
+/// Whether `streams` fit a file of `doc_type`, for checking before the
+/// file is created.
+pub fn check_doc_type(doc_type: &str, streams: &stream::Streams) -> Result<()> {
+	match doc_type {
+		"matroska" => Ok(()),
+		"webm" => {
+			let stream =
+				streams.all().iter().find(|stream| !WEBM_CODECS.contains(&stream.codec.as_str()));
+			match stream {
+				Some(stream) => Err(error!("codec '{}' cannot be stored in webm", stream.codec)),
+				None => Ok(()),
+			}
+		}
+		_ => Err(error!("unsupported ebml document type '{}'", doc_type)),
+	}
+}
+
+struct Cluster {
+	timestamp: i64,
+	/// Offset from the start of the segment payload.
+	position: u64,
+	/// Payload written so far; only buffered when the size can be patched in.
+	data: Vec<u8>,
+	size: u64,
+	cued: bool,
+}
+
+/// Writes Matroska or WebM. Seekable output gets a complete SeekHead, the
+/// segment duration and sizes for every element; live output leaves the
+/// segment and clusters with unknown sizes and is written as it goes.
+pub struct MkvMuxer<W: MediaWrite> {
+	writer: W,
+	seek: Option<SeekFn<W>>,
+	doc_type: String,
+	streams: stream::Streams,
+	tracks: Vec<Track>,
+	/// Last timestamp and how long it lasts, per track, for the duration;
+	/// the gap before it unless its block states a duration.
+	last: Vec<Option<(i64, i64)>>,
+	/// Presentation time and duration of the last packet per track, in its
+	/// own time units.
+	last_packet: Vec<Option<(i64, i64)>>,
+	cue_track: usize,
+	position: u64,
+	segment_offset: u64,
+	duration_offset: u64,
+	info_position: u64,
+	tracks_position: u64,
+	cluster: Option<Cluster>,
+	cues: Vec<CuePoint>,
+	header_written: bool,
+}
+
+impl<W: MediaWrite + MediaSeek> MkvMuxer<W> {
+	pub fn new(writer: W, streams: stream::Streams) -> Result<Self> {
+		Self::build(writer, streams, Some(<W as MediaSeek>::seek))
+	}
+}
+
+impl<W: MediaWrite> MkvMuxer<W> {
+	/// A muxer for output that cannot seek, such as a pipe.
+	pub fn new_live(writer: W, streams: stream::Streams) -> Result<Self> {
+		Self::build(writer, streams, None)
+	}
+
+	fn build(writer: W, streams: stream::Streams, seek: Option<SeekFn<W>>) -> Result<Self> {
+		let mut tracks = Vec::new();
+		let mut muxed = stream::Streams::new_empty();
+		for stream in streams.all() {
+			let id = tracks.len() as u32;
+			tracks.push(Track::from_stream(stream, id as u64 + 1)?);
+			// packets keep their own time until they are written, which
+			// rounds them once
+			let output = Stream::new(id, id as usize, stream.kind, stream.codec.clone(), stream.time)
+				.with_codec_private(stream.codec_private.clone());
+			muxed.add(output);
+		}
+		if tracks.is_empty() {
+			return Err(error!("matroska output needs at least one stream"));
+		}
+		// cues point at video keyframes when there is video
+		let cue_track = muxed.all().iter().position(Stream::video_kind).unwrap_or(0);
+
+		Ok(Self {
+			writer,
+			seek,
+			doc_type: "matroska".to_string(),
+			streams: muxed,
+			last: vec![None; tracks.len()],
+			last_packet: vec![None; tracks.len()],
+			tracks,
+			cue_track,
+			position: 0,
+			segment_offset: 0,
+			duration_offset: 0,
+			info_position: 0,
+			tracks_position: 0,
+			cluster: None,
+			cues: Vec::new(),
+			header_written: false,
+		})
+	}
+
+	/// Sets the document type, "matroska" or "webm". WebM only carries
+	/// VP8, VP9, AV1, Vorbis, Opus and WebVTT.
+	pub fn with_doc_type(&mut self, doc_type: &str) -> Result<()> {
+		check_doc_type(doc_type, &self.streams)?;
+		self.doc_type = doc_type.to_string();
+		Ok(())
+	}
+
+	/// The track written for stream `index`, to fill in what the stream does
+	/// not describe (picture size, frame duration, language) before the
+	/// first packet.
+	pub fn track_mut(&mut self, index: usize) -> Option<&mut Track> {
+		self.tracks.get_mut(index)
+	}
+
+	fn write_bytes(&mut self, data: &[u8]) -> Result<()> {
+		self.writer.write_all(data)?;
+		self.position += data.len() as u64;
+		Ok(())
+	}
+
+	fn write_header(&mut self) -> Result<()> {
+		let mut header = Vec::new();
+		put_uint(&mut header, ebml::EBML_VERSION, 1);
+		put_uint(&mut header, ebml::EBML_READ_VERSION, 1);
+		put_uint(&mut header, ebml::EBML_MAX_ID_LENGTH, 4);
+		put_uint(&mut header, ebml::EBML_MAX_SIZE_LENGTH, 8);
+		put_string(&mut header, ebml::DOC_TYPE, &self.doc_type);
+		put_uint(&mut header, ebml::DOC_TYPE_VERSION, 4);
+		put_uint(&mut header, ebml::DOC_TYPE_READ_VERSION, 2);
+		let mut out = Vec::new();
+		put_element(&mut out, ebml::EBML_HEADER, &header);
+
+		// the segment size is patched in by finalize when the output seeks
+		put_id(&mut out, ebml::SEGMENT);
+		out.extend_from_slice(&UNKNOWN_SIZE);
+		self.segment_offset = self.position + out.len() as u64;
+
+		let mut info = Vec::new();
+		put_uint(&mut info, ebml::TIMESTAMP_SCALE, TIMESTAMP_SCALE);
+		put_string(&mut info, ebml::MUXING_APP, "ffmpreg");
+		put_string(&mut info, ebml::WRITING_APP, "ffmpreg");
+		let mut duration_offset = None;
+		if self.seek.is_some() {
+			put_float(&mut info, ebml::DURATION, 0.0);
+			duration_offset = Some(info.len() - 8);
+		}
+		let mut segment = Vec::new();
+		put_id(&mut segment, ebml::INFO);
+		put_size_fixed(&mut segment, info.len() as u64, 2);
+		let info_start = segment.len();
+		segment.extend_from_slice(&info);
+
+		let tracks: Vec<u8> = self.tracks.iter().flat_map(Track::to_bytes).collect();
+		let tracks_position = segment.len();
+		put_element(&mut segment, ebml::TRACKS, &tracks);
+
+		self.info_position = SEEK_HEAD_SIZE as u64;
+		self.tracks_position = (SEEK_HEAD_SIZE + tracks_position) as u64;
+		if let Some(offset) = duration_offset {
+			self.duration_offset = self.segment_offset + (SEEK_HEAD_SIZE + info_start + offset) as u64;
+		}
+		out.extend_from_slice(&self.seek_head(None));
+		out.extend_from_slice(&segment);
+		self.write_bytes(&out)?;
+		self.header_written = true;
+		Ok(())
+	}
+
+	/// The SeekHead padded to its reserved size.
+	fn seek_head(&self, cues_position: Option<u64>) -> Vec<u8> {
+		let mut entries = vec![(ebml::INFO, self.info_position), (ebml::TRACKS, self.tracks_position)];
+		entries.extend(cues_position.map(|position| (ebml::CUES, position)));
+		let mut seeks = Vec::new();
+		for (id, position) in entries {
+			let mut seek = Vec::new();
+			put_element(&mut seek, ebml::SEEK_ID, &id.to_be_bytes());
+			put_uint(&mut seek, ebml::SEEK_POSITION, position);
+			put_element(&mut seeks, ebml::SEEK, &seek);
+		}
+		let mut out = Vec::new();
+		put_element(&mut out, ebml::SEEK_HEAD, &seeks);
+		let used = out.len();
+		put_void(&mut out, SEEK_HEAD_SIZE - used);
+		out
+	}
+
+	pub fn write_packet(&mut self, packet: Packet) -> Result<()> {
+		if !self.header_written {
+			self.write_header()?;
+		}
+
+		let index = packet.stream_id as usize;
+		let track = self.tracks.get(index).ok_or_else(|| error!("no matroska track {}", index))?;
+		let number = track.number;
+		let video = track.kind == Some(StreamKind::Video);
+		// rounded once, so the codec delay and the packet time read back to
+		// the same sample
+		let nanoseconds = NANOSECONDS.round_pts(packet.pts, packet.time) + track.codec_delay as i64;
+		let timestamp = SEGMENT_TIME.round_pts(nanoseconds, NANOSECONDS);
+		let keyframe = packet.keyframe || !video;
+
+		let cluster_start = self.cluster.as_ref().map(|cluster| cluster.timestamp);
+		let open_cluster = match cluster_start {
+			None => true,
+			Some(start) => {
+				let relative = timestamp - start;
+				relative < i16::MIN as i64
+					|| relative > i16::MAX as i64
+					|| (video && packet.keyframe)
+					|| (self.streams.video().next().is_none() && relative >= MAX_CLUSTER_DURATION)
+			}
+		};
+		if open_cluster {
+			self.close_cluster()?;
+			self.open_cluster(timestamp.max(0))?;
+		}
+
+		let cluster = self.cluster.as_mut().unwrap();
+		let relative = i16::try_from(timestamp - cluster.timestamp)
+			.map_err(|_| error!("timestamp {} ms cannot be stored in matroska", timestamp))?;
+		if index == self.cue_track && keyframe && !cluster.cued {
+			cluster.cued = true;
+			self.cues.push(CuePoint {
+				time: timestamp.max(0) as u64,
+				track: number,
+				cluster_position: cluster.position,
+				relative_position: Some(cluster.size),
+			});
+		}
+		// a frame cut shorter than the one before it, such as the padded
+		// last frame of audio, states how long it lasts
+		let previous = self.last_packet[index].map(|(pts, duration)| match duration {
+			0 => packet.pts - pts,
+			duration => duration,
+		});
+		let short = packet.duration > 0 && previous.is_some_and(|previous| packet.duration < previous);
+		self.last_packet[index] = Some((packet.pts, packet.duration));
+		let mut block = Vec::new();
+		let mut gap = self.last[index].map_or(0, |(last, _)| timestamp - last);
+		if let (true, true, Some(previous)) = (short, keyframe, previous) {
+			let duration = SEGMENT_TIME.round_pts(packet.duration, packet.time);
+			// the padding is exact where the block duration is not
+			let padding = NANOSECONDS.round_pts(previous - packet.duration, packet.time);
+			put_block_group(&mut block, number, relative, &packet.data, duration as u64, padding);
+			gap = duration;
+		} else {
+			put_simple_block(&mut block, number, relative, keyframe, &packet.data);
+		}
+		cluster.size += block.len() as u64;
+		if self.seek.is_some() {
+			cluster.data.extend_from_slice(&block);
+		} else {
+			self.write_bytes(&block)?;
+		}
+
+		self.last[index] = Some((timestamp, gap));
+		Ok(())
+	}
+
+	fn open_cluster(&mut self, timestamp: i64) -> Result<()> {
+		let mut data = Vec::new();
+		put_uint(&mut data, ebml::TIMESTAMP, timestamp as u64);
+		let mut cluster = Cluster {
+			timestamp,
+			position: self.position - self.segment_offset,
+			size: data.len() as u64,
+			data,
+			cued: false,
+		};
+		if self.seek.is_none() {
+			let mut header = Vec::new();
+			put_id(&mut header, ebml::CLUSTER);
+			header.extend_from_slice(&UNKNOWN_SIZE);
+			header.append(&mut cluster.data);
+			self.write_bytes(&header)?;
+		}
+		self.cluster = Some(cluster);
+		Ok(())
+	}
+
+	fn close_cluster(&mut self) -> Result<()> {
+		let Some(cluster) = self.cluster.take() else {
+			return Ok(());
+		};
+		if self.seek.is_some() {
+			let mut out = Vec::new();
+			put_element(&mut out, ebml::CLUSTER, &cluster.data);
+			self.write_bytes(&out)?;
+		}
+		Ok(())
+	}
+
+	pub fn finalize(&mut self) -> Result<()> {
+		if !self.header_written {
+			self.write_header()?;
+		}
+		self.close_cluster()?;
+
+		let cues_position = self.position - self.segment_offset;
+		if !self.cues.is_empty() {
+			let points: Vec<u8> = self.cues.iter().flat_map(CuePoint::to_bytes).collect();
+			let mut out = Vec::new();
+			put_element(&mut out, ebml::CUES, &points);
+			self.write_bytes(&out)?;
+		}
+
+		if let Some(seek) = self.seek {
+			let end = self.position;
+			let duration = self.last.iter().flatten().map(|(last, gap)| last + gap).max().unwrap_or(0);
+			let seek_head = self.seek_head((!self.cues.is_empty()).then_some(cues_position));
+			let mut size = Vec::new();
+			put_size_fixed(&mut size, end - self.segment_offset, 8);
+
+			seek(&mut self.writer, SeekFrom::Start(self.segment_offset - 8))?;
+			self.writer.write_all(&size)?;
+			self.writer.write_all(&seek_head)?;
+			seek(&mut self.writer, SeekFrom::Start(self.duration_offset))?;
+			self.writer.write_all(&(duration.max(0) as f64).to_be_bytes())?;
+			seek(&mut self.writer, SeekFrom::Start(end))?;
+		}
+		self.writer.flush()
+	}
+}
+
+impl<W: MediaWrite> Muxer for MkvMuxer<W> {
+	fn streams(&self) -> &stream::Streams {
+		&self.streams
+	}
+
+	fn write(&mut self, packet: Packet) -> Result<()> {
+		self.write_packet(packet)
+	}
+
+	fn finalize(&mut self) -> Result<()> {
+		self.finalize()
+	}
+}
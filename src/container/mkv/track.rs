@@ -0,0 +1,283 @@
+use super::ebml::{self, children, put_element, put_float, put_string, put_uint};
+use super::ebml::{read_float, read_string, read_uint};
+use crate::codecs;
+use crate::codecs::audio::aac::AudioSpecificConfig;
+use crate::codecs::audio::flac::StreamInfo;
+use crate::codecs::audio::opus::packet::OpusHead;
+use crate::container::ogg::mapping::xiph_unlace;
+use crate::core::stream::{Stream, StreamKind};
+use crate::core::time::Time;
+use crate::{error, message::Result};
+
+const TYPE_VIDEO: u64 = 1;
+const TYPE_AUDIO: u64 = 2;
+const TYPE_SUBTITLE: u64 = 0x11;
+
+const NANOSECONDS: u64 = 1_000_000_000;
+// what libopus recommends decoding ahead of a seek target
+const OPUS_SEEK_PRE_ROLL: u64 = 80_000_000;
+
+/// Matroska codec ids and the codecs they carry. PCM and AAC ids are
+/// matched separately, since the former depends on the bit depth and the
+/// latter has legacy variants.
+pub const CODEC_IDS: &[(&str, &str)] = &[
+	("A_OPUS", codecs::audio::OPUS),
+	("A_VORBIS", codecs::audio::VORBIS),
+	("A_FLAC", codecs::audio::FLAC),
+	("A_MPEG/L3", codecs::audio::MP3),
+	("A_MPEG/L2", codecs::audio::MP2),
+	("A_AC3", codecs::audio::AC3),
+	("A_EAC3", codecs::audio::EAC3),
+	("A_ALAC", codecs::audio::ALAC),
+	("A_WAVPACK4", codecs::audio::WAVPACK),
+	("A_TTA1", codecs::audio::TTA),
+	("V_MPEG4/ISO/AVC", codecs::video::H264),
+	("V_MPEGH/ISO/HEVC", codecs::video::H265),
+	("V_VP8", codecs::video::VP8),
+	("V_VP9", codecs::video::VP9),
+	("V_AV1", codecs::video::AV1),
+	("V_THEORA", codecs::video::THEORA),
+	("V_MPEG2", codecs::video::MPEG2),
+	("V_MPEG4/ISO/ASP", codecs::video::MPEG4),
+	("V_MJPEG", codecs::video::MJPEG),
+	("V_PRORES", codecs::video::PRORES),
+	("S_TEXT/UTF8", codecs::subtitle::SRT),
+	("S_TEXT/ASS", codecs::subtitle::ASS),
+	("S_TEXT/SSA", codecs::subtitle::ASS),
+	("S_TEXT/WEBVTT", codecs::subtitle::VTT),
+];
+
+/// A TrackEntry of the segment.
+#[derive(Debug, Clone, Default)]
+pub struct Track {
+	pub number: u64,
+	pub kind: Option<StreamKind>,
+	pub codec_id: String,
+	pub codec_private: Vec<u8>,
+	/// Duration of each frame in nanoseconds, when constant.
+	pub default_duration: Option<u64>,
+	/// Samples the decoder must drop, in nanoseconds (Opus pre-skip).
+	pub codec_delay: u64,
+	pub seek_pre_roll: u64,
+	pub language: Option<String>,
+	pub sample_rate: f64,
+	pub channels: u8,
+	pub bit_depth: u8,
+	pub width: u32,
+	pub height: u32,
+}
+
+impl Track {
+	pub fn parse(data: &[u8]) -> Result<Self> {
+		let mut track = Self { channels: 1, sample_rate: 8000.0, ..Self::default() };
+		for (id, value) in children(data)? {
+			match id {
+				ebml::TRACK_NUMBER => track.number = read_uint(value),
+				ebml::TRACK_TYPE => {
+					track.kind = match read_uint(value) {
+						TYPE_VIDEO => Some(StreamKind::Video),
+						TYPE_AUDIO => Some(StreamKind::Audio),
+						TYPE_SUBTITLE => Some(StreamKind::Subtitle),
+						_ => None,
+					}
+				}
+				ebml::CODEC_ID => track.codec_id = read_string(value),
+				ebml::CODEC_PRIVATE => track.codec_private = value.to_vec(),
+				ebml::DEFAULT_DURATION => track.default_duration = Some(read_uint(value)),
+				ebml::CODEC_DELAY => track.codec_delay = read_uint(value),
+				ebml::SEEK_PRE_ROLL => track.seek_pre_roll = read_uint(value),
+				ebml::LANGUAGE => track.language = Some(read_string(value)),
+				ebml::AUDIO => track.parse_audio(value)?,
+				ebml::VIDEO => track.parse_video(value)?,
+				_ => {}
+			}
+		}
+		if track.number == 0 {
+			return Err(error!("matroska track has no number"));
+		}
+		Ok(track)
+	}
+
+	fn parse_audio(&mut self, data: &[u8]) -> Result<()> {
+		for (id, value) in children(data)? {
+			match id {
+				ebml::SAMPLING_FREQUENCY => self.sample_rate = read_float(value),
+				ebml::CHANNELS => self.channels = read_uint(value) as u8,
+				ebml::BIT_DEPTH => self.bit_depth = read_uint(value) as u8,
+				_ => {}
+			}
+		}
+		Ok(())
+	}
+
+	fn parse_video(&mut self, data: &[u8]) -> Result<()> {
+		for (id, value) in children(data)? {
+			match id {
+				ebml::PIXEL_WIDTH => self.width = read_uint(value) as u32,
+				ebml::PIXEL_HEIGHT => self.height = read_uint(value) as u32,
+				_ => {}
+			}
+		}
+		Ok(())
+	}
+
+	/// The codec of the track, `None` for codec ids without a mapping.
+	pub fn codec(&self) -> Option<&'static str> {
+		let id = self.codec_id.as_str();
+		match id {
+			"A_PCM/INT/LIT" => match self.bit_depth {
+				16 => Some(codecs::audio::PCM_S16LE),
+				24 => Some(codecs::audio::PCM_S24LE),
+				_ => None,
+			},
+			"A_PCM/FLOAT/IEEE" if self.bit_depth == 32 => Some(codecs::audio::PCM_F32LE),
+			_ if id.starts_with("A_AAC") => Some(codecs::audio::AAC),
+			_ => CODEC_IDS.iter().find(|(codec_id, _)| *codec_id == id).map(|(_, codec)| *codec),
+		}
+	}
+
+	/// The sample rate as a time base, when it is a whole number of hertz.
+	pub fn sample_time(&self) -> Option<Time> {
+		let rate = self.sample_rate;
+		let whole = rate >= 1.0 && rate <= u32::MAX as f64 && rate.fract() == 0.0;
+		(self.kind == Some(StreamKind::Audio) && whole).then(|| Time::new(1, rate as u32))
+	}
+
+	/// The codec private data in the layout other containers use for the
+	/// same codec: FLAC keeps only its STREAMINFO block.
+	pub fn stream_codec_private(&self) -> Result<Vec<u8>> {
+		let private = &self.codec_private;
+		if self.codec() != Some(codecs::audio::FLAC) {
+			return Ok(private.clone());
+		}
+		if private.len() < 42 || &private[..4] != b"fLaC" || private[4] & 0x7F != 0 {
+			return Err(error!("matroska flac track {} has no STREAMINFO", self.number));
+		}
+		Ok(private[8..42].to_vec())
+	}
+
+	/// Describes a stream about to be muxed as track `number`. The audio
+	/// layout comes from the codec private data where the codec has one;
+	/// other tracks can be completed before the first packet is written.
+	pub fn from_stream(stream: &Stream, number: u64) -> Result<Self> {
+		let codec = stream.codec.as_str();
+		let mut track = Self {
+			number,
+			kind: Some(stream.kind),
+			codec_private: stream.codec_private.clone(),
+			channels: 1,
+			sample_rate: 8000.0,
+			..Self::default()
+		};
+		if stream.time.num == 1 && stream.kind == StreamKind::Audio {
+			track.sample_rate = stream.time.den as f64;
+		}
+
+		track.codec_id = match codec {
+			codecs::audio::AAC => "A_AAC".to_string(),
+			codecs::audio::PCM_S16LE | codecs::audio::PCM_S24LE => "A_PCM/INT/LIT".to_string(),
+			codecs::audio::PCM_F32LE => "A_PCM/FLOAT/IEEE".to_string(),
+			_ => CODEC_IDS
+				.iter()
+				.find(|(_, known)| *known == codec)
+				.map(|(codec_id, _)| codec_id.to_string())
+				.ok_or_else(|| error!("codec '{}' cannot be stored in matroska", codec))?,
+		};
+		track.bit_depth = match codec {
+			codecs::audio::PCM_S16LE => 16,
+			codecs::audio::PCM_S24LE => 24,
+			codecs::audio::PCM_F32LE => 32,
+			_ => 0,
+		};
+
+		let private = &stream.codec_private;
+		match codec {
+			codecs::audio::OPUS => {
+				let head = OpusHead::parse(private)?;
+				track.sample_rate = 48000.0;
+				track.channels = head.channels;
+				track.codec_delay = head.pre_skip as u64 * NANOSECONDS / 48000;
+				track.seek_pre_roll = OPUS_SEEK_PRE_ROLL;
+			}
+			codecs::audio::FLAC => {
+				let info = StreamInfo::parse(private)?;
+				track.sample_rate = info.sample_rate as f64;
+				track.channels = info.channel_layout().count();
+				track.codec_private = [b"fLaC".as_slice(), &[0x80, 0, 0, 34], private].concat();
+			}
+			// frames are the same length, which lets readers place each of
+			// them to the sample
+			codecs::audio::AAC => {
+				let config = AudioSpecificConfig::parse(private)?;
+				track.sample_rate = config.sample_rate as f64;
+				track.channels = config.channels();
+				let nanoseconds = Time::new(1, NANOSECONDS as u32);
+				let frame = config.frame_length as i64;
+				let duration = nanoseconds.round_pts(frame, Time::new(1, config.sample_rate));
+				track.default_duration = Some(duration as u64);
+			}
+			codecs::audio::VORBIS => {
+				let headers = xiph_unlace(private)?;
+				let id = headers.first().filter(|id| id.len() >= 16);
+				let id = id.ok_or_else(|| error!("vorbis codec private data is truncated"))?;
+				track.channels = id[11];
+				track.sample_rate = u32::from_le_bytes(id[12..16].try_into().unwrap()) as f64;
+			}
+			_ => {}
+		}
+		Ok(track)
+	}
+
+	/// The TrackEntry element.
+	pub fn to_bytes(&self) -> Vec<u8> {
+		let mut entry = Vec::new();
+		put_uint(&mut entry, ebml::TRACK_NUMBER, self.number);
+		put_uint(&mut entry, ebml::TRACK_UID, self.number);
+		let kind = match self.kind {
+			Some(StreamKind::Video) => TYPE_VIDEO,
+			Some(StreamKind::Audio) => TYPE_AUDIO,
+			_ => TYPE_SUBTITLE,
+		};
+		put_uint(&mut entry, ebml::TRACK_TYPE, kind);
+		put_uint(&mut entry, ebml::FLAG_LACING, 0);
+		put_string(&mut entry, ebml::CODEC_ID, &self.codec_id);
+		if !self.codec_private.is_empty() {
+			put_element(&mut entry, ebml::CODEC_PRIVATE, &self.codec_private);
+		}
+		if let Some(duration) = self.default_duration {
+			put_uint(&mut entry, ebml::DEFAULT_DURATION, duration);
+		}
+		if self.codec_delay > 0 {
+			put_uint(&mut entry, ebml::CODEC_DELAY, self.codec_delay);
+		}
+		if self.seek_pre_roll > 0 {
+			put_uint(&mut entry, ebml::SEEK_PRE_ROLL, self.seek_pre_roll);
+		}
+		if let Some(language) = &self.language {
+			put_string(&mut entry, ebml::LANGUAGE, language);
+		}
+
+		match self.kind {
+			Some(StreamKind::Audio) => {
+				let mut audio = Vec::new();
+				put_float(&mut audio, ebml::SAMPLING_FREQUENCY, self.sample_rate);
+				put_uint(&mut audio, ebml::CHANNELS, self.channels as u64);
+				if self.bit_depth > 0 {
+					put_uint(&mut audio, ebml::BIT_DEPTH, self.bit_depth as u64);
+				}
+				put_element(&mut entry, ebml::AUDIO, &audio);
+			}
+			Some(StreamKind::Video) if self.width > 0 && self.height > 0 => {
+				let mut video = Vec::new();
+				put_uint(&mut video, ebml::PIXEL_WIDTH, self.width as u64);
+				put_uint(&mut video, ebml::PIXEL_HEIGHT, self.height as u64);
+				put_element(&mut entry, ebml::VIDEO, &video);
+			}
+			_ => {}
+		}
+
+		let mut out = Vec::new();
+		put_element(&mut out, ebml::TRACK_ENTRY, &entry);
+		out
+	}
+}
@@ -1,6 +1,19 @@
+pub mod aac;
+pub mod aiff;
+pub mod au;
+pub mod flac;
+pub mod frames;
+pub mod interleave;
 pub mod mkv;
+pub mod mp3;
+pub mod mp4;
+pub mod ogg;
+pub mod probe;
 pub mod raw;
+pub mod registry;
+pub mod voc;
 pub mod wav;
+pub mod webm;
 
 mod constants;
 pub use constants::*;
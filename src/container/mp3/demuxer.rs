@@ -0,0 +1,107 @@
+use super::vbr::VbrHeader;
+use crate::codecs;
+use crate::codecs::audio::mp3::FrameHeader;
+use crate::codecs::audio::mp3::header::HEADER_SIZE;
+use crate::container::frames::{FrameReader, SyncHeader};
+use crate::container::probe::{self, SCORE_MAX};
+use crate::core::packet::Packet;
+use crate::core::{Demuxer, stream, time};
+use crate::io::MediaRead;
+use crate::{error, message::Result};
+
+impl SyncHeader for FrameHeader {
+	const SIZE: usize = HEADER_SIZE;
+
+	fn parse(data: &[u8]) -> Option<Self> {
+		FrameHeader::parse(data).ok()
+	}
+
+	fn frame_size(&self) -> usize {
+		self.frame_size
+	}
+
+	fn is_compatible(&self, other: &Self) -> bool {
+		FrameHeader::is_compatible(self, other)
+	}
+}
+
+pub struct Mp3Demuxer<R: MediaRead> {
+	frames: FrameReader<R, FrameHeader>,
+	streams: stream::Streams,
+	first: FrameHeader,
+	vbr: Option<VbrHeader>,
+	samples: u64,
+}
+
+impl<R: MediaRead> Mp3Demuxer<R> {
+	pub fn new(reader: R) -> Result<Self> {
+		let mut frames = FrameReader::new(reader).with_id3v1();
+		frames.skip_id3v2()?;
+		let first = match frames.resync(None)? {
+			Some(header) => header,
+			None => return Err(error!("no mp3 frame found in input")),
+		};
+
+		// the Xing/Info/VBRI frame holds no audio
+		let vbr = VbrHeader::parse(&first, &frames.data()[..first.frame_size]);
+		if vbr.is_some() {
+			frames.advance(first.frame_size);
+		}
+
+		let codec_name = codecs::audio::MP3.to_string();
+		let time = time::Time::new(1, first.sample_rate);
+		let stream = stream::Stream::new(0, 0, stream::StreamKind::Audio, codec_name, time);
+		let streams = stream::Streams::new(vec![stream]);
+
+		Ok(Self { frames, streams, first, vbr, samples: 0 })
+	}
+
+	/// Header of the first audio frame.
+	pub fn first_header(&self) -> &FrameHeader {
+		&self.first
+	}
+
+	pub fn vbr_header(&self) -> Option<&VbrHeader> {
+		self.vbr.as_ref()
+	}
+
+	/// Stream length in samples per channel, when a VBR header states it.
+	pub fn total_samples(&self) -> Option<u64> {
+		self.vbr.as_ref()?.total_samples(self.first.samples_per_frame())
+	}
+
+	pub fn duration(&self) -> Option<f64> {
+		Some(self.total_samples()? as f64 / self.first.sample_rate as f64)
+	}
+}
+
+impl<R: MediaRead> Demuxer for Mp3Demuxer<R> {
+	fn streams(&self) -> &stream::Streams {
+		&self.streams
+	}
+
+	fn read_packet(&mut self) -> Result<Option<Packet>> {
+		let Some((header, data)) = self.frames.next_frame(&self.first)? else {
+			return Ok(None);
+		};
+
+		let time = time::Time::new(1, header.sample_rate);
+		let pts = self.samples as i64;
+		self.samples += header.samples_per_frame() as u64;
+		let packet = Packet::new(data, 0, time).with_pts(pts).with_dts(pts).with_keyframe(true);
+		Ok(Some(packet))
+	}
+}
+
+/// Scores how likely `data` is the start of an mp3 file: ID3v2 tags, then
+/// two frames in a row.
+pub fn probe(data: &[u8]) -> u8 {
+	// a tag larger than the probe data is most likely mp3
+	if probe::skip_id3v2(data).is_none() {
+		return SCORE_MAX / 4;
+	}
+	probe::score_frames(data, |frame| {
+		let header = FrameHeader::parse(frame).ok().filter(|header| header.layer == 3)?;
+		Some(header.frame_size).filter(|&size| size > HEADER_SIZE)
+	})
+}
@@ -0,0 +1,5 @@
+pub mod demuxer;
+pub mod vbr;
+
+pub use demuxer::Mp3Demuxer;
+pub use vbr::{VbrHeader, VbrKind};
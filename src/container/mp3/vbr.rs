@@ -0,0 +1,107 @@
+use crate::codecs::audio::mp3::FrameHeader;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VbrKind {
+	/// Xing header written by VBR encoders.
+	Xing,
+	/// Same layout as Xing, written by LAME for CBR streams.
+	Info,
+	/// Fraunhofer VBRI header.
+	Vbri,
+}
+
+/// Stream summary carried in the first frame of many mp3 files.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VbrHeader {
+	pub kind: VbrKind,
+	pub frames: Option<u32>,
+	pub bytes: Option<u32>,
+	pub encoder: Option<String>,
+	pub encoder_delay: u32,
+	pub encoder_padding: u32,
+}
+
+impl VbrHeader {
+	/// Looks for a Xing, Info or VBRI header in `frame`.
+	pub fn parse(header: &FrameHeader, frame: &[u8]) -> Option<Self> {
+		let xing_offset = header.side_info_offset() + header.side_info_size();
+		if let Some(tag) = frame.get(xing_offset..xing_offset + 4) {
+			let kind = match tag {
+				b"Xing" => Some(VbrKind::Xing),
+				b"Info" => Some(VbrKind::Info),
+				_ => None,
+			};
+			if let Some(kind) = kind {
+				return Self::parse_xing(kind, &frame[xing_offset + 4..]);
+			}
+		}
+
+		// VBRI sits at a fixed offset past the 32 byte side information
+		let vbri_offset = header.side_info_offset() + 32;
+		if frame.get(vbri_offset..vbri_offset + 4) == Some(b"VBRI") {
+			return Self::parse_vbri(&frame[vbri_offset + 4..]);
+		}
+		None
+	}
+
+	fn parse_xing(kind: VbrKind, data: &[u8]) -> Option<Self> {
+		let flags = read_u32(data, 0)?;
+		let mut offset = 4;
+		let mut field = |flag: u32, size: usize| {
+			if flags & flag == 0 {
+				return None;
+			}
+			let value = read_u32(data, offset);
+			offset += size;
+			value
+		};
+
+		let frames = field(0x1, 4);
+		let bytes = field(0x2, 4);
+		field(0x4, 100);
+		field(0x8, 4);
+
+		let mut vbr = Self { kind, frames, bytes, encoder: None, encoder_delay: 0, encoder_padding: 0 };
+
+		// LAME extension: 9 byte version string, then delay and padding as
+		// two 12-bit values at byte 21
+		if let Some(lame) = data.get(offset..offset + 24) {
+			let version = &lame[..9];
+			if version.starts_with(b"LAME")
+				|| version.starts_with(b"Lavc")
+				|| version.starts_with(b"Lavf")
+			{
+				let name = String::from_utf8_lossy(version).trim_end_matches('\0').trim().to_string();
+				vbr.encoder = Some(name);
+				let packed = u32::from_be_bytes([0, lame[21], lame[22], lame[23]]);
+				vbr.encoder_delay = packed >> 12;
+				vbr.encoder_padding = packed & 0xFFF;
+			}
+		}
+		Some(vbr)
+	}
+
+	fn parse_vbri(data: &[u8]) -> Option<Self> {
+		let delay = u16::from_be_bytes([*data.get(2)?, *data.get(3)?]);
+		Some(Self {
+			kind: VbrKind::Vbri,
+			frames: read_u32(data, 10),
+			bytes: read_u32(data, 6),
+			encoder: None,
+			encoder_delay: delay as u32,
+			encoder_padding: 0,
+		})
+	}
+
+	/// Number of audio samples per channel, without encoder delay and padding.
+	pub fn total_samples(&self, samples_per_frame: usize) -> Option<u64> {
+		let samples = self.frames? as u64 * samples_per_frame as u64;
+		let trimmed = (self.encoder_delay + self.encoder_padding) as u64;
+		Some(samples.saturating_sub(trimmed))
+	}
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+	let bytes = data.get(offset..offset + 4)?;
+	Some(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
@@ -0,0 +1,191 @@
+use crate::io::{MediaRead, ReadPrimitives};
+use crate::{error, message::Result};
+
+pub type BoxType = [u8; 4];
+
+// top level
+pub const FTYP: BoxType = *b"ftyp";
+pub const MOOV: BoxType = *b"moov";
+pub const MDAT: BoxType = *b"mdat";
+pub const MOOF: BoxType = *b"moof";
+pub const FREE: BoxType = *b"free";
+
+// movie
+pub const MVHD: BoxType = *b"mvhd";
+pub const MVEX: BoxType = *b"mvex";
+pub const TREX: BoxType = *b"trex";
+pub const TRAK: BoxType = *b"trak";
+pub const TKHD: BoxType = *b"tkhd";
+pub const EDTS: BoxType = *b"edts";
+pub const ELST: BoxType = *b"elst";
+pub const MDIA: BoxType = *b"mdia";
+pub const MDHD: BoxType = *b"mdhd";
+pub const HDLR: BoxType = *b"hdlr";
+pub const MINF: BoxType = *b"minf";
+pub const STBL: BoxType = *b"stbl";
+
+// sample table
+pub const STSD: BoxType = *b"stsd";
+pub const STTS: BoxType = *b"stts";
+pub const CTTS: BoxType = *b"ctts";
+pub const STSS: BoxType = *b"stss";
+pub const STSC: BoxType = *b"stsc";
+pub const STSZ: BoxType = *b"stsz";
+pub const STZ2: BoxType = *b"stz2";
+pub const STCO: BoxType = *b"stco";
+pub const CO64: BoxType = *b"co64";
+
+// fragments
+pub const MFHD: BoxType = *b"mfhd";
+pub const TRAF: BoxType = *b"traf";
+pub const TFHD: BoxType = *b"tfhd";
+pub const TFDT: BoxType = *b"tfdt";
+pub const TRUN: BoxType = *b"trun";
+
+// brands
+pub const ISOM: BoxType = *b"isom";
+pub const ISO2: BoxType = *b"iso2";
+pub const ISO6: BoxType = *b"iso6";
+pub const MP41: BoxType = *b"mp41";
+pub const CMFC: BoxType = *b"cmfc";
+
+// sample entries and their configuration boxes
+pub const MP4A: BoxType = *b"mp4a";
+pub const ESDS: BoxType = *b"esds";
+pub const WAVE: BoxType = *b"wave";
+pub const ALAC: BoxType = *b"alac";
+pub const OPUS: BoxType = *b"Opus";
+pub const DOPS: BoxType = *b"dOps";
+pub const FLAC: BoxType = *b"fLaC";
+pub const DFLA: BoxType = *b"dfLa";
+pub const SOWT: BoxType = *b"sowt";
+pub const AVC1: BoxType = *b"avc1";
+pub const AVC3: BoxType = *b"avc3";
+pub const AVCC: BoxType = *b"avcC";
+pub const HVC1: BoxType = *b"hvc1";
+pub const HEV1: BoxType = *b"hev1";
+pub const HVCC: BoxType = *b"hvcC";
+pub const AV01: BoxType = *b"av01";
+pub const AV1C: BoxType = *b"av1C";
+pub const VP09: BoxType = *b"vp09";
+pub const VPCC: BoxType = *b"vpcC";
+pub const MP4V: BoxType = *b"mp4v";
+pub const TX3G: BoxType = *b"tx3g";
+
+// media headers
+pub const VMHD: BoxType = *b"vmhd";
+pub const SMHD: BoxType = *b"smhd";
+pub const NMHD: BoxType = *b"nmhd";
+pub const DINF: BoxType = *b"dinf";
+pub const DREF: BoxType = *b"dref";
+pub const URL: BoxType = *b"url ";
+
+// handler types
+pub const VIDE: BoxType = *b"vide";
+pub const SOUN: BoxType = *b"soun";
+pub const SBTL: BoxType = *b"sbtl";
+pub const TEXT: BoxType = *b"text";
+pub const SUBT: BoxType = *b"subt";
+
+// boxes read into memory, anything bigger is taken as corrupt
+const MAX_BOX_SIZE: u64 = 1 << 28;
+
+/// Box header: its type, the size of its payload (`None` when the box runs
+/// to the end of the file) and the size of the header itself.
+#[derive(Debug, Clone, Copy)]
+pub struct Header {
+	pub kind: BoxType,
+	pub size: Option<u64>,
+	pub header_size: u64,
+}
+
+impl Header {
+	/// Reads the next box header, or `None` at the end of the stream.
+	pub fn read<R: MediaRead>(reader: &mut R) -> Result<Option<Self>> {
+		let mut size = [0u8; 4];
+		let mut filled = 0;
+		while filled < size.len() {
+			match reader.read(&mut size[filled..])? {
+				0 if filled == 0 => return Ok(None),
+				0 => return Err(error!("mp4 box header is truncated")),
+				read => filled += read,
+			}
+		}
+		let mut kind = [0u8; 4];
+		reader.read_exact(&mut kind)?;
+		let (size, header_size) = match u32::from_be_bytes(size) {
+			0 => (None, 8),
+			1 => (Some(reader.read_u64_be()?), 16),
+			size => (Some(size as u64), 8),
+		};
+		let size = match size {
+			Some(size) if size < header_size => {
+				return Err(error!("mp4 box '{}' is smaller than its header", name(kind)));
+			}
+			size => size.map(|size| size - header_size),
+		};
+		Ok(Some(Self { kind, size, header_size }))
+	}
+
+	/// Reads the payload of a box with a known size.
+	pub fn read_data<R: MediaRead>(&self, reader: &mut R) -> Result<Vec<u8>> {
+		let size = self.size.ok_or_else(|| error!("mp4 box '{}' has no size", name(self.kind)))?;
+		if size > MAX_BOX_SIZE {
+			return Err(error!("mp4 box '{}' is too large ({} bytes)", name(self.kind), size));
+		}
+		let mut data = vec![0u8; size as usize];
+		reader.read_exact(&mut data)?;
+		Ok(data)
+	}
+}
+
+/// The box type as text, for messages.
+pub fn name(kind: BoxType) -> String {
+	String::from_utf8_lossy(&kind).into_owned()
+}
+
+/// Splits the payload of a container box into its children.
+pub fn children(mut data: &[u8]) -> Result<Vec<(BoxType, &[u8])>> {
+	let mut boxes = Vec::new();
+	// some writers pad containers with a zero terminator
+	while data.len() >= 8 {
+		let mut cursor = data;
+		let header = Header::read(&mut cursor)?.ok_or_else(|| error!("mp4 box header is truncated"))?;
+		let start = header.header_size as usize;
+		let end = match header.size {
+			Some(size) => start.checked_add(size as usize).filter(|&end| end <= data.len()),
+			None => Some(data.len()),
+		};
+		let end = end.ok_or_else(|| error!("mp4 box '{}' overruns its parent", name(header.kind)))?;
+		boxes.push((header.kind, &data[start..end]));
+		data = &data[end..];
+	}
+	Ok(boxes)
+}
+
+/// The first child of the given type.
+pub fn find(data: &[u8], kind: BoxType) -> Result<Option<&[u8]>> {
+	Ok(children(data)?.into_iter().find(|(child, _)| *child == kind).map(|(_, data)| data))
+}
+
+/// Splits a full box into its version, flags and payload.
+pub fn full_box(data: &[u8]) -> Result<(u8, u32, &[u8])> {
+	if data.len() < 4 {
+		return Err(error!("mp4 full box is truncated"));
+	}
+	let flags = u32::from_be_bytes([0, data[1], data[2], data[3]]);
+	Ok((data[0], flags, &data[4..]))
+}
+
+pub fn put_box(out: &mut Vec<u8>, kind: BoxType, data: &[u8]) {
+	out.extend_from_slice(&(8 + data.len() as u32).to_be_bytes());
+	out.extend_from_slice(&kind);
+	out.extend_from_slice(data);
+}
+
+pub fn put_full_box(out: &mut Vec<u8>, kind: BoxType, version: u8, flags: u32, data: &[u8]) {
+	out.extend_from_slice(&(12 + data.len() as u32).to_be_bytes());
+	out.extend_from_slice(&kind);
+	out.extend_from_slice(&((version as u32) << 24 | flags).to_be_bytes());
+	out.extend_from_slice(data);
+}
@@ -0,0 +1,267 @@
+use super::atom::{self, BoxType, Header, children, full_box, name};
+use super::fragment::parse_moof;
+use super::sample::Sample;
+use super::track::{Track, TrackDefaults};
+use crate::container::probe::SCORE_MAX;
+use crate::core::packet::Packet;
+use crate::core::time::Time;
+use crate::core::{Demuxer, stream};
+use crate::io::{MediaRead, MediaSeek, ReadPrimitives, SeekFrom};
+use crate::{error, message::Result};
+use std::collections::VecDeque;
+
+// samples read into memory, anything bigger is taken as corrupt
+const MAX_SAMPLE_SIZE: u32 = 1 << 28;
+
+/// Reads MP4, MOV and M4A. Samples of all tracks come out in file order,
+/// first those of the sample tables and then those of any movie fragments.
+pub struct Mp4Demuxer<R: MediaRead + MediaSeek> {
+	reader: R,
+	position: u64,
+	major_brand: Option<BoxType>,
+	timescale: u32,
+	duration: u64,
+	/// Tracks with a known codec, in stream order.
+	tracks: Vec<Track>,
+	streams: stream::Streams,
+	/// Decoding time following the last sample of each track, for
+	/// fragments that do not state theirs.
+	next_dts: Vec<i64>,
+	pending: VecDeque<(usize, Sample)>,
+	/// Offset of the next top level box to look at for fragments.
+	next_box: Option<u64>,
+}
+
+impl<R: MediaRead + MediaSeek> Mp4Demuxer<R> {
+	pub fn new(reader: R) -> Result<Self> {
+		let mut demuxer = Self {
+			reader,
+			position: 0,
+			major_brand: None,
+			timescale: 1,
+			duration: 0,
+			tracks: Vec::new(),
+			streams: stream::Streams::new_empty(),
+			next_dts: Vec::new(),
+			pending: VecDeque::new(),
+			next_box: None,
+		};
+
+		// the movie box may come before or after the media data
+		loop {
+			let start = demuxer.position;
+			let Some(header) = demuxer.read_header()? else {
+				return Err(error!("mp4 file has no moov box"));
+			};
+			match header.kind {
+				atom::FTYP => {
+					let data = demuxer.read_data(&header)?;
+					demuxer.major_brand = data.get(..4).map(|brand| [brand[0], brand[1], brand[2], brand[3]]);
+				}
+				atom::MOOV => {
+					let data = demuxer.read_data(&header)?;
+					demuxer.parse_movie(&data)?;
+					demuxer.next_box = Some(demuxer.position);
+					break;
+				}
+				_ => {
+					let size = header.size.ok_or_else(|| error!("mp4 file has no moov box"))?;
+					demuxer.seek(start + header.header_size + size)?;
+				}
+			}
+		}
+
+		let mut samples = Vec::new();
+		for (index, track) in demuxer.tracks.iter_mut().enumerate() {
+			let (Some(kind), Some(codec)) = (track.kind, track.codec) else { continue };
+			let time = Time::new(1, track.timescale);
+			let stream = stream::Stream::new(index as u32, index, kind, codec.to_string(), time)
+				.with_codec_private(track.codec_private.clone());
+			demuxer.streams.add(stream);
+			demuxer.next_dts.push(if track.samples.is_empty() { 0 } else { track.duration as i64 });
+			samples.extend(std::mem::take(&mut track.samples).into_iter().map(|sample| (index, sample)));
+		}
+		demuxer.queue(samples);
+		Ok(demuxer)
+	}
+
+	/// The major brand of the ftyp box, such as "isom", "M4A " or "qt  ".
+	pub fn major_brand(&self) -> Option<String> {
+		self.major_brand.map(name)
+	}
+
+	/// Movie duration in seconds, as the movie header states it.
+	pub fn duration(&self) -> f64 {
+		self.duration as f64 / self.timescale as f64
+	}
+
+	/// The tracks behind the streams, in the same order.
+	pub fn tracks(&self) -> &[Track] {
+		&self.tracks
+	}
+
+	fn read_header(&mut self) -> Result<Option<Header>> {
+		let header = Header::read(&mut self.reader)?;
+		if let Some(header) = &header {
+			self.position += header.header_size;
+		}
+		Ok(header)
+	}
+
+	fn read_data(&mut self, header: &Header) -> Result<Vec<u8>> {
+		let data = header.read_data(&mut self.reader)?;
+		self.position += data.len() as u64;
+		Ok(data)
+	}
+
+	fn seek(&mut self, position: u64) -> Result<()> {
+		if position != self.position {
+			self.position = self.reader.seek(SeekFrom::Start(position))?;
+		}
+		Ok(())
+	}
+
+	fn parse_movie(&mut self, moov: &[u8]) -> Result<()> {
+		let boxes = children(moov)?;
+		for &(kind, data) in &boxes {
+			if kind == atom::MVHD {
+				let (version, _, data) = full_box(data)?;
+				let fields = if version == 1 { data.get(16..28) } else { data.get(8..16) };
+				let mut fields = fields.ok_or_else(|| error!("mp4 mvhd box is truncated"))?;
+				self.timescale = fields.read_u32_be()?.max(1);
+				self.duration = match version {
+					1 => fields.read_u64_be()?,
+					_ => fields.read_u32_be()? as u64,
+				};
+			}
+		}
+
+		let mut defaults = Vec::new();
+		for &(kind, data) in &boxes {
+			match kind {
+				atom::TRAK => {
+					// tracks of unknown codecs are dropped, so stream ids stay dense
+					let track = Track::parse(data, self.timescale)?;
+					if track.kind.is_some() && track.codec.is_some() {
+						self.tracks.push(track);
+					}
+				}
+				atom::MVEX => {
+					for (kind, trex) in children(data)? {
+						if kind != atom::TREX {
+							continue;
+						}
+						let (_, _, mut trex) = full_box(trex)?;
+						let track_id = trex.read_u32_be()?;
+						trex.read_u32_be()?;
+						let duration = trex.read_u32_be()?;
+						let size = trex.read_u32_be()?;
+						let flags = trex.read_u32_be()?;
+						defaults.push((track_id, TrackDefaults { duration, size, flags }));
+					}
+				}
+				_ => {}
+			}
+		}
+		for (track_id, track_defaults) in defaults {
+			if let Some(track) = self.tracks.iter_mut().find(|track| track.id == track_id) {
+				track.defaults = track_defaults;
+			}
+		}
+		if self.tracks.is_empty() {
+			return Err(error!("mp4 file has no supported tracks"));
+		}
+		Ok(())
+	}
+
+	/// Adds samples to the queue in file order, which keeps reads forward.
+	fn queue(&mut self, mut samples: Vec<(usize, Sample)>) {
+		samples.sort_by_key(|(_, sample)| sample.offset);
+		self.pending.extend(samples);
+	}
+
+	fn parse_fragment(&mut self, moof: &[u8], offset: u64) -> Result<()> {
+		let tracks = &self.tracks;
+		let defaults = |track_id| {
+			let track = tracks.iter().find(|track| track.id == track_id);
+			track.map(|track| track.defaults).unwrap_or_default()
+		};
+		let mut samples = Vec::new();
+		for fragment in parse_moof(moof, offset, defaults)? {
+			let Some(index) = self.tracks.iter().position(|track| track.id == fragment.track_id) else {
+				continue;
+			};
+			let base = fragment.base_decode_time.map_or(self.next_dts[index], |time| time as i64);
+			self.next_dts[index] = base + fragment.duration;
+			samples.extend(fragment.samples.into_iter().map(|sample| {
+				let dts = base + sample.dts;
+				(index, Sample { dts, ..sample })
+			}));
+		}
+		self.queue(samples);
+		Ok(())
+	}
+
+	fn read_sample(&mut self, index: usize, sample: Sample) -> Result<Packet> {
+		if sample.size > MAX_SAMPLE_SIZE {
+			return Err(error!("mp4 sample is too large ({} bytes)", sample.size));
+		}
+		self.seek(sample.offset)?;
+		let mut data = vec![0u8; sample.size as usize];
+		self.reader.read_exact(&mut data)?;
+		self.position += data.len() as u64;
+
+		let track = &self.tracks[index];
+		let dts = sample.dts + track.edit_shift;
+		let pts = dts + sample.composition_offset as i64;
+		// the edit list may end presentation inside the sample
+		let mut duration = sample.duration as i64;
+		if let Some(end) = track.edit_end {
+			duration = duration.min(end - pts).max(0);
+		}
+		let packet = Packet::new(data, index as u32, Time::new(1, track.timescale))
+			.with_pts(pts)
+			.with_dts(dts)
+			.with_duration(duration)
+			.with_keyframe(sample.keyframe);
+		Ok(packet)
+	}
+}
+
+impl<R: MediaRead + MediaSeek> Demuxer for Mp4Demuxer<R> {
+	fn streams(&self) -> &stream::Streams {
+		&self.streams
+	}
+
+	fn read_packet(&mut self) -> Result<Option<Packet>> {
+		loop {
+			if let Some((index, sample)) = self.pending.pop_front() {
+				return self.read_sample(index, sample).map(Some);
+			}
+			let Some(start) = self.next_box else {
+				return Ok(None);
+			};
+			self.seek(start)?;
+			let Some(header) = self.read_header()? else {
+				self.next_box = None;
+				return Ok(None);
+			};
+			// the media data itself is read sample by sample
+			self.next_box = header.size.map(|size| start + header.header_size + size);
+			if header.kind == atom::MOOF {
+				let data = self.read_data(&header)?;
+				self.parse_fragment(&data, start)?;
+			}
+		}
+	}
+}
+
+/// Scores how likely `data` is the start of an mp4 or QuickTime file.
+pub fn probe(data: &[u8]) -> u8 {
+	match data.get(4..8) {
+		Some(b"ftyp") => SCORE_MAX,
+		// old QuickTime files start straight with their boxes
+		Some(b"moov" | b"mdat" | b"wide" | b"free" | b"skip") => SCORE_MAX / 2,
+		_ => 0,
+	}
+}
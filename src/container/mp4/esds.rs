@@ -0,0 +1,146 @@
+use super::atom::full_box;
+use crate::codecs;
+use crate::{error, message::Result};
+
+const ES_DESCRIPTOR: u8 = 0x03;
+const DECODER_CONFIG_DESCRIPTOR: u8 = 0x04;
+const DECODER_SPECIFIC_INFO: u8 = 0x05;
+const SL_CONFIG_DESCRIPTOR: u8 = 0x06;
+
+// streamType values
+const STREAM_TYPE_VISUAL: u8 = 0x04;
+const STREAM_TYPE_AUDIO: u8 = 0x05;
+
+// objectTypeIndication values
+pub const OBJECT_TYPE_AAC: u8 = 0x40;
+pub const OBJECT_TYPE_AAC_MAIN: u8 = 0x66;
+pub const OBJECT_TYPE_AAC_LC: u8 = 0x67;
+pub const OBJECT_TYPE_AAC_SSR: u8 = 0x68;
+pub const OBJECT_TYPE_MP3: u8 = 0x6B;
+pub const OBJECT_TYPE_MPEG2_AUDIO: u8 = 0x69;
+pub const OBJECT_TYPE_MPEG4_VIDEO: u8 = 0x20;
+
+/// The parts of an elementary stream descriptor a decoder needs.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Esds {
+	pub object_type: u8,
+	pub avg_bitrate: u32,
+	pub max_bitrate: u32,
+	/// DecoderSpecificInfo, the AudioSpecificConfig for AAC.
+	pub decoder_config: Vec<u8>,
+}
+
+impl Esds {
+	/// Parses the payload of an esds box.
+	pub fn parse(data: &[u8]) -> Result<Self> {
+		let (_, _, data) = full_box(data)?;
+		let (tag, mut es, _) = descriptor(data)?;
+		if tag != ES_DESCRIPTOR || es.len() < 3 {
+			return Err(error!("mp4 esds box has no ES descriptor"));
+		}
+		let flags = es[2];
+		es = &es[3..];
+		if flags & 0x80 != 0 {
+			es = es.get(2..).unwrap_or_default();
+		}
+		if flags & 0x40 != 0 {
+			let url_length = es.first().map_or(0, |&length| length as usize + 1);
+			es = es.get(url_length..).unwrap_or_default();
+		}
+		if flags & 0x20 != 0 {
+			es = es.get(2..).unwrap_or_default();
+		}
+
+		let mut esds = Self::default();
+		while !es.is_empty() {
+			let (tag, body, next) = descriptor(es)?;
+			es = next;
+			if tag != DECODER_CONFIG_DESCRIPTOR {
+				continue;
+			}
+			if body.len() < 13 {
+				return Err(error!("mp4 decoder config descriptor is truncated"));
+			}
+			esds.object_type = body[0];
+			esds.max_bitrate = u32::from_be_bytes([body[5], body[6], body[7], body[8]]);
+			esds.avg_bitrate = u32::from_be_bytes([body[9], body[10], body[11], body[12]]);
+			let mut rest = &body[13..];
+			while !rest.is_empty() {
+				let (tag, info, next) = descriptor(rest)?;
+				rest = next;
+				if tag == DECODER_SPECIFIC_INFO {
+					esds.decoder_config = info.to_vec();
+				}
+			}
+			return Ok(esds);
+		}
+		Err(error!("mp4 esds box has no decoder config"))
+	}
+
+	/// The payload of an esds box, after its version and flags.
+	pub fn to_bytes(&self, es_id: u16) -> Vec<u8> {
+		let stream_type = match self.object_type {
+			OBJECT_TYPE_MPEG4_VIDEO => STREAM_TYPE_VISUAL,
+			_ => STREAM_TYPE_AUDIO,
+		};
+		let mut config = vec![self.object_type, (stream_type << 2) | 1, 0, 0, 0];
+		config.extend_from_slice(&self.max_bitrate.to_be_bytes());
+		config.extend_from_slice(&self.avg_bitrate.to_be_bytes());
+		if !self.decoder_config.is_empty() {
+			put_descriptor(&mut config, DECODER_SPECIFIC_INFO, &self.decoder_config);
+		}
+
+		let mut es = es_id.to_be_bytes().to_vec();
+		es.push(0);
+		put_descriptor(&mut es, DECODER_CONFIG_DESCRIPTOR, &config);
+		// predefined SL config for MP4 files
+		put_descriptor(&mut es, SL_CONFIG_DESCRIPTOR, &[0x02]);
+		let mut out = Vec::new();
+		put_descriptor(&mut out, ES_DESCRIPTOR, &es);
+		out
+	}
+
+	/// The codec the object type stands for.
+	pub fn codec(&self) -> Option<&'static str> {
+		match self.object_type {
+			OBJECT_TYPE_AAC | OBJECT_TYPE_AAC_MAIN | OBJECT_TYPE_AAC_LC | OBJECT_TYPE_AAC_SSR => {
+				Some(codecs::audio::AAC)
+			}
+			OBJECT_TYPE_MP3 | OBJECT_TYPE_MPEG2_AUDIO => Some(codecs::audio::MP3),
+			OBJECT_TYPE_MPEG4_VIDEO => Some(codecs::video::MPEG4),
+			_ => None,
+		}
+	}
+}
+
+/// Splits a descriptor into its tag, its body and what follows it. Sizes
+/// take up to four bytes of seven bits each.
+fn descriptor(data: &[u8]) -> Result<(u8, &[u8], &[u8])> {
+	let truncated = || error!("mp4 descriptor is truncated");
+	let tag = *data.first().ok_or_else(truncated)?;
+	let mut size = 0usize;
+	let mut position = 1;
+	for _ in 0..4 {
+		let byte = *data.get(position).ok_or_else(truncated)?;
+		position += 1;
+		size = (size << 7) | (byte & 0x7F) as usize;
+		if byte & 0x80 == 0 {
+			break;
+		}
+	}
+	let body = data.get(position..position + size).ok_or_else(truncated)?;
+	Ok((tag, body, &data[position + size..]))
+}
+
+/// Writes a descriptor with its size in the four byte form.
+fn put_descriptor(out: &mut Vec<u8>, tag: u8, body: &[u8]) {
+	let size = body.len() as u32;
+	out.push(tag);
+	out.extend_from_slice(&[
+		0x80 | (size >> 21) as u8 & 0x7F,
+		0x80 | (size >> 14) as u8 & 0x7F,
+		0x80 | (size >> 7) as u8 & 0x7F,
+	]);
+	out.push(size as u8 & 0x7F);
+	out.extend_from_slice(body);
+}
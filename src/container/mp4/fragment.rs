@@ -0,0 +1,152 @@
+use super::atom::{self, children, full_box};
+use super::sample::Sample;
+use super::track::TrackDefaults;
+use crate::io::ReadPrimitives;
+use crate::{error, message::Result};
+
+// tfhd flags
+const BASE_DATA_OFFSET: u32 = 0x00_0001;
+const SAMPLE_DESCRIPTION_INDEX: u32 = 0x00_0002;
+const DEFAULT_DURATION: u32 = 0x00_0008;
+const DEFAULT_SIZE: u32 = 0x00_0010;
+const DEFAULT_FLAGS: u32 = 0x00_0020;
+const DEFAULT_BASE_IS_MOOF: u32 = 0x02_0000;
+
+// trun flags
+const DATA_OFFSET: u32 = 0x00_0001;
+const FIRST_SAMPLE_FLAGS: u32 = 0x00_0004;
+const SAMPLE_DURATION: u32 = 0x00_0100;
+const SAMPLE_SIZE: u32 = 0x00_0200;
+const SAMPLE_FLAGS: u32 = 0x00_0400;
+const SAMPLE_COMPOSITION_OFFSET: u32 = 0x00_0800;
+
+const SAMPLE_IS_NON_SYNC: u32 = 0x1_0000;
+
+/// A track fragment of a moof box, with its samples located in the file.
+#[derive(Debug, Clone)]
+pub struct TrackFragment {
+	pub track_id: u32,
+	/// Decoding time of the first sample, when the fragment states it.
+	pub base_decode_time: Option<u64>,
+	/// Samples with decoding times relative to the first one.
+	pub samples: Vec<Sample>,
+	/// Sum of the sample durations.
+	pub duration: i64,
+}
+
+/// Parses a moof box found at `moof_offset`. `defaults` gives the trex
+/// defaults of a track id.
+pub fn parse_moof(
+	moof: &[u8],
+	moof_offset: u64,
+	defaults: impl Fn(u32) -> TrackDefaults,
+) -> Result<Vec<TrackFragment>> {
+	let mut fragments = Vec::new();
+	// without an explicit base, each traf continues where the data of the
+	// previous one ended
+	let mut data_end = moof_offset;
+	for (kind, traf) in children(moof)? {
+		if kind != atom::TRAF {
+			continue;
+		}
+		let mut fragment =
+			TrackFragment { track_id: 0, base_decode_time: None, samples: Vec::new(), duration: 0 };
+		let mut track_defaults = TrackDefaults::default();
+		let mut base_offset = data_end;
+		// where the next run continues: data offset and decoding time
+		let mut next = None;
+		for (kind, data) in children(traf)? {
+			match kind {
+				atom::TFHD => {
+					let (_, flags, mut data) = full_box(data)?;
+					fragment.track_id = data.read_u32_be()?;
+					track_defaults = defaults(fragment.track_id);
+					base_offset = match flags {
+						_ if flags & BASE_DATA_OFFSET != 0 => data.read_u64_be()?,
+						_ if flags & DEFAULT_BASE_IS_MOOF != 0 => moof_offset,
+						_ => data_end,
+					};
+					if flags & SAMPLE_DESCRIPTION_INDEX != 0 {
+						data.read_u32_be()?;
+					}
+					if flags & DEFAULT_DURATION != 0 {
+						track_defaults.duration = data.read_u32_be()?;
+					}
+					if flags & DEFAULT_SIZE != 0 {
+						track_defaults.size = data.read_u32_be()?;
+					}
+					if flags & DEFAULT_FLAGS != 0 {
+						track_defaults.flags = data.read_u32_be()?;
+					}
+				}
+				atom::TFDT => {
+					let (version, _, mut data) = full_box(data)?;
+					fragment.base_decode_time = Some(match version {
+						1 => data.read_u64_be()?,
+						_ => data.read_u32_be()? as u64,
+					});
+				}
+				atom::TRUN => {
+					let start = next.unwrap_or((base_offset, 0));
+					let end = parse_trun(data, base_offset, start, &track_defaults, &mut fragment.samples)?;
+					data_end = end.0;
+					next = Some(end);
+				}
+				_ => {}
+			}
+		}
+		if fragment.track_id == 0 {
+			return Err(error!("mp4 track fragment has no tfhd box"));
+		}
+		fragment.duration = next.map_or(0, |(_, dts)| dts);
+		fragments.push(fragment);
+	}
+	Ok(fragments)
+}
+
+/// Appends the samples of a trun box. A run without a data offset starts
+/// at `next`, the data offset and decoding time the previous run ended
+/// with, which is also what this returns.
+fn parse_trun(
+	trun: &[u8],
+	base_offset: u64,
+	next: (u64, i64),
+	defaults: &TrackDefaults,
+	samples: &mut Vec<Sample>,
+) -> Result<(u64, i64)> {
+	let (_, flags, mut data) = full_box(trun)?;
+	let count = data.read_u32_be()?;
+	let (mut offset, mut dts) = next;
+	if flags & DATA_OFFSET != 0 {
+		offset = base_offset.wrapping_add_signed(data.read_i32_be()? as i64);
+	}
+	let first_flags = match flags & FIRST_SAMPLE_FLAGS {
+		0 => None,
+		_ => Some(data.read_u32_be()?),
+	};
+	for index in 0..count {
+		let duration =
+			if flags & SAMPLE_DURATION != 0 { data.read_u32_be()? } else { defaults.duration };
+		let size = if flags & SAMPLE_SIZE != 0 { data.read_u32_be()? } else { defaults.size };
+		let sample_flags = match (flags & SAMPLE_FLAGS != 0, first_flags) {
+			(true, _) => data.read_u32_be()?,
+			(false, Some(first)) if index == 0 => first,
+			_ => defaults.flags,
+		};
+		let composition_offset = match flags & SAMPLE_COMPOSITION_OFFSET {
+			0 => 0,
+			_ => data.read_i32_be()?,
+		};
+		samples.push(Sample {
+			offset,
+			size,
+			dts,
+			duration,
+			composition_offset,
+			keyframe: sample_flags & SAMPLE_IS_NON_SYNC == 0,
+		});
+		offset += size as u64;
+		dts += duration as i64;
+	}
+	Ok((offset, dts))
+}
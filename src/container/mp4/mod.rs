@@ -0,0 +1,13 @@
+pub mod atom;
+pub mod demuxer;
+pub mod esds;
+pub mod fragment;
+pub mod muxer;
+pub mod sample;
+pub mod track;
+
+pub use demuxer::Mp4Demuxer;
+pub use esds::Esds;
+pub use muxer::Mp4Muxer;
+pub use sample::Sample;
+pub use track::Track;
@@ -0,0 +1,475 @@
+use super::atom::{self, BoxType, put_box, put_full_box};
+use super::sample::{Sample, write_tables};
+use super::track::Track;
+use crate::core::Muxer;
+use crate::core::packet::Packet;
+use crate::core::stream::{self, Stream, StreamKind};
+use crate::core::time::Time;
+use crate::io::{MediaRead, MediaSeek, MediaWrite, ReadPrimitives, SeekFrom, WritePrimitives};
+use crate::{error, message::Result};
+
+const MOVIE_TIMESCALE: u32 = 1000;
+// fragments are cut at the first keyframe after this many milliseconds
+const FRAGMENT_DURATION: i64 = 1000;
+// media data moved in one go when faststart makes room for moov
+const COPY_BLOCK_SIZE: u64 = 1 << 20;
+
+// tfhd and trun flags
+const DEFAULT_BASE_IS_MOOF: u32 = 0x02_0000;
+const TRUN_FLAGS: u32 = 0x00_0F01;
+const SYNC_SAMPLE: u32 = 0x0200_0000;
+const NON_SYNC_SAMPLE: u32 = 0x0101_0000;
+
+type SeekFn<W> = fn(&mut W, SeekFrom) -> Result<u64>;
+type ReadFn<W> = fn(&mut W, &mut [u8]) -> Result<()>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Layout {
+	/// moov after mdat.
+	MoovAtEnd,
+	/// moov after mdat, then moved ahead of it by finalize.
+	Faststart,
+	/// moov without samples, then moof and mdat pairs.
+	Fragmented,
+}
+
+/// Writes MP4 and M4A. The sample tables are built from the packets and
+/// written at finalize, after the media data or, in faststart mode, ahead
+/// of it. Fragmented output (fMP4/CMAF) is written as it goes and needs
+/// no seeking.
+pub struct Mp4Muxer<W: MediaWrite> {
+	writer: W,
+	seek: Option<SeekFn<W>>,
+	read: Option<ReadFn<W>>,
+	layout: Layout,
+	major_brand: BoxType,
+	streams: stream::Streams,
+	tracks: Vec<Track>,
+	/// Samples with decoding times relative to the first one; only those of
+	/// the open fragment when fragmented.
+	samples: Vec<Vec<Sample>>,
+	/// Decoding time of the first and of the last sample, per track.
+	first_dts: Vec<Option<i64>>,
+	last_dts: Vec<Option<i64>>,
+	/// Duration of the last sample, taken from the gap before it.
+	last_duration: Vec<u32>,
+	/// Latest end of the packets that state their duration, per track.
+	presentation_end: Vec<Option<i64>>,
+	/// Media data of the open fragment, per track.
+	fragment_data: Vec<Vec<u8>>,
+	fragment_track: usize,
+	fragment_start: Option<i64>,
+	sequence: u32,
+	position: u64,
+	mdat_start: u64,
+	header_written: bool,
+}
+
+impl<W: MediaWrite + MediaSeek> Mp4Muxer<W> {
+	pub fn new(writer: W, streams: stream::Streams) -> Result<Self> {
+		Self::build(writer, streams, Layout::MoovAtEnd, Some(<W as MediaSeek>::seek), None)
+	}
+}
+
+impl<W: MediaRead + MediaWrite + MediaSeek> Mp4Muxer<W> {
+	/// A muxer that moves moov ahead of mdat on finalize, so players can
+	/// start before the whole file is there. The output is read back to
+	/// make room.
+	pub fn new_faststart(writer: W, streams: stream::Streams) -> Result<Self> {
+		let seek = Some(<W as MediaSeek>::seek as SeekFn<W>);
+		let read = Some(<W as ReadPrimitives>::read_exact as ReadFn<W>);
+		Self::build(writer, streams, Layout::Faststart, seek, read)
+	}
+}
+
+impl<W: MediaWrite> Mp4Muxer<W> {
+	/// A muxer writing movie fragments, for output that cannot seek.
+	pub fn new_fragmented(writer: W, streams: stream::Streams) -> Result<Self> {
+		Self::build(writer, streams, Layout::Fragmented, None, None)
+	}
+
+	fn build(
+		writer: W,
+		streams: stream::Streams,
+		layout: Layout,
+		seek: Option<SeekFn<W>>,
+		read: Option<ReadFn<W>>,
+	) -> Result<Self> {
+		let mut tracks = Vec::new();
+		let mut muxed = stream::Streams::new_empty();
+		for stream in streams.all() {
+			let id = tracks.len() as u32;
+			let track = Track::from_stream(stream, id + 1)?;
+			let output = Stream::new(
+				id,
+				id as usize,
+				stream.kind,
+				stream.codec.clone(),
+				Time::new(1, track.timescale),
+			)
+			.with_codec_private(stream.codec_private.clone());
+			muxed.add(output);
+			tracks.push(track);
+		}
+		if tracks.is_empty() {
+			return Err(error!("mp4 output needs at least one stream"));
+		}
+		// fragments follow the keyframes of the video when there is video
+		let fragment_track = muxed.all().iter().position(Stream::video_kind).unwrap_or(0);
+
+		let count = tracks.len();
+		Ok(Self {
+			writer,
+			seek,
+			read,
+			layout,
+			major_brand: atom::ISOM,
+			streams: muxed,
+			tracks,
+			samples: vec![Vec::new(); count],
+			first_dts: vec![None; count],
+			last_dts: vec![None; count],
+			last_duration: vec![0; count],
+			presentation_end: vec![None; count],
+			fragment_data: vec![Vec::new(); count],
+			fragment_track,
+			fragment_start: None,
+			sequence: 0,
+			position: 0,
+			mdat_start: 0,
+			header_written: false,
+		})
+	}
+
+	/// Sets the major brand of the ftyp box, such as "M4A " for audio.
+	pub fn with_major_brand(&mut self, brand: &str) -> Result<()> {
+		let brand: BoxType = brand
+			.as_bytes()
+			.try_into()
+			.ok()
+			.filter(|brand: &BoxType| brand.is_ascii())
+			.ok_or_else(|| error!("mp4 brand '{}' is not four characters", brand))?;
+		self.major_brand = brand;
+		Ok(())
+	}
+
+	/// The track written for stream `index`, to fill in what the stream does
+	/// not describe (picture size, channel count of PCM, language) before
+	/// the first packet.
+	pub fn track_mut(&mut self, index: usize) -> Option<&mut Track> {
+		self.tracks.get_mut(index)
+	}
+
+	fn write_bytes(&mut self, data: &[u8]) -> Result<()> {
+		self.writer.write_all(data)?;
+		self.position += data.len() as u64;
+		Ok(())
+	}
+
+	fn write_header(&mut self) -> Result<()> {
+		let mut brands = vec![self.major_brand, atom::ISOM, atom::ISO2, atom::MP41];
+		if self.layout == Layout::Fragmented {
+			brands.extend([atom::ISO6, atom::CMFC]);
+		}
+		let mut data = self.major_brand.to_vec();
+		data.extend_from_slice(&0x200u32.to_be_bytes());
+		for (index, brand) in brands.iter().enumerate() {
+			if !brands[..index].contains(brand) {
+				data.extend_from_slice(brand);
+			}
+		}
+		let mut out = Vec::new();
+		put_box(&mut out, atom::FTYP, &data);
+
+		if self.layout == Layout::Fragmented {
+			out.extend_from_slice(&self.movie(0)?);
+		} else {
+			// mdat gets a 64 bit size, patched in by finalize
+			self.mdat_start = out.len() as u64;
+			out.extend_from_slice(&1u32.to_be_bytes());
+			out.extend_from_slice(&atom::MDAT);
+			out.extend_from_slice(&0u64.to_be_bytes());
+		}
+		self.write_bytes(&out)?;
+		self.header_written = true;
+		Ok(())
+	}
+
+	/// The timescale every track shares, which keeps edit lists exact to the
+	/// sample, unless the movie would not fit 32 bits in it.
+	fn movie_timescale(&self) -> u32 {
+		let Some(first) = self.tracks.first() else {
+			return MOVIE_TIMESCALE;
+		};
+		let shared = self.tracks.iter().all(|track| track.timescale == first.timescale);
+		let fits = |track: &Track| track.duration + track.edit_shift.max(0) as u64 <= u32::MAX as u64;
+		if shared && first.timescale > 0 && self.tracks.iter().all(fits) {
+			return first.timescale;
+		}
+		MOVIE_TIMESCALE
+	}
+
+	/// The moov box. Chunk offsets move by `shift`; fragmented output gets
+	/// empty sample tables and the mvex box instead.
+	fn movie(&mut self, shift: u64) -> Result<Vec<u8>> {
+		let fragmented = self.layout == Layout::Fragmented;
+		for (index, track) in self.tracks.iter_mut().enumerate() {
+			track.edit_shift = self.first_dts[index].unwrap_or(0);
+			track.edit_end = if fragmented { None } else { self.presentation_end[index] };
+			track.duration = match (fragmented, self.samples[index].last()) {
+				(false, Some(last)) => (last.dts + self.last_duration[index] as i64).max(0) as u64,
+				_ => 0,
+			};
+		}
+
+		let timescale = self.movie_timescale();
+		let mut traks = Vec::new();
+		let mut duration = 0;
+		for (index, track) in self.tracks.iter().enumerate() {
+			let samples: &[Sample] = if fragmented { &[] } else { &self.samples[index] };
+			let tables = write_tables(samples, self.last_duration[index], shift);
+			traks.extend_from_slice(&track.write_trak(&tables, timescale)?);
+			let delay = track.edit_shift.max(0) as u64;
+			let end = (track.duration + delay) as u128 * timescale as u128 / track.timescale as u128;
+			duration = duration.max(end as u64);
+		}
+
+		let mut header = vec![0; 8];
+		header.extend_from_slice(&timescale.to_be_bytes());
+		header.extend_from_slice(&(duration.min(u32::MAX as u64) as u32).to_be_bytes());
+		// rate, volume and reserved
+		header.extend_from_slice(&0x1_0000u32.to_be_bytes());
+		header.extend_from_slice(&0x0100u16.to_be_bytes());
+		header.extend_from_slice(&[0; 10]);
+		for value in [0x1_0000u32, 0, 0, 0, 0x1_0000, 0, 0, 0, 0x4000_0000] {
+			header.extend_from_slice(&value.to_be_bytes());
+		}
+		header.extend_from_slice(&[0; 24]);
+		header.extend_from_slice(&(self.tracks.len() as u32 + 1).to_be_bytes());
+
+		let mut moov = Vec::new();
+		put_full_box(&mut moov, atom::MVHD, 0, 0, &header);
+		moov.extend_from_slice(&traks);
+		if fragmented {
+			let mut extends = Vec::new();
+			for track in &self.tracks {
+				// track id, sample description 1, no sample defaults
+				let mut defaults = track.id.to_be_bytes().to_vec();
+				defaults.extend_from_slice(&1u32.to_be_bytes());
+				defaults.extend_from_slice(&[0; 12]);
+				put_full_box(&mut extends, atom::TREX, 0, 0, &defaults);
+			}
+			put_box(&mut moov, atom::MVEX, &extends);
+		}
+		let mut out = Vec::new();
+		put_box(&mut out, atom::MOOV, &moov);
+		Ok(out)
+	}
+
+	pub fn write_packet(&mut self, packet: Packet) -> Result<()> {
+		let index = packet.stream_id as usize;
+		let track = self.tracks.get(index).ok_or_else(|| error!("no mp4 track {}", index))?;
+		let time = Time::new(1, track.timescale);
+		let pts = time.scale_pts(packet.pts, packet.time);
+		// only video reorders frames
+		let dts = match track.kind {
+			Some(StreamKind::Video) => time.scale_pts(packet.dts, packet.time),
+			_ => pts,
+		};
+		let keyframe = packet.keyframe || track.kind != Some(StreamKind::Video);
+		let composition_offset = i32::try_from(pts - dts)
+			.map_err(|_| error!("mp4 track {} has a composition offset out of range", index))?;
+
+		if let Some(last) = self.last_dts[index] {
+			if dts < last {
+				return Err(error!("mp4 track {} has decoding times out of order", index));
+			}
+			self.last_duration[index] = u32::try_from(dts - last)
+				.map_err(|_| error!("mp4 track {} has a sample too long", index))?;
+		}
+		self.last_dts[index] = Some(dts);
+		if packet.duration > 0 {
+			let end = pts + time.scale_pts(packet.duration, packet.time);
+			let presentation_end = &mut self.presentation_end[index];
+			*presentation_end = Some(presentation_end.map_or(end, |last| last.max(end)));
+		}
+		// a track starting after the fragmented header has no edit list, so
+		// its decoding times are kept as they are
+		let late = self.layout == Layout::Fragmented && self.header_written;
+		let first = *self.first_dts[index].get_or_insert(if late { 0 } else { dts });
+
+		if self.layout == Layout::Fragmented {
+			if index == self.fragment_track && keyframe {
+				let start = *self.fragment_start.get_or_insert(dts);
+				let elapsed = Time::new(1, 1000).scale_pts(dts - start, time);
+				if elapsed >= FRAGMENT_DURATION {
+					self.write_fragment()?;
+					self.fragment_start = Some(dts);
+				}
+			}
+			let data = &mut self.fragment_data[index];
+			let sample = Sample {
+				offset: data.len() as u64,
+				size: packet.size(),
+				dts: dts - first,
+				duration: 0,
+				composition_offset,
+				keyframe,
+			};
+			data.extend_from_slice(&packet.data);
+			self.samples[index].push(sample);
+			return Ok(());
+		}
+
+		if !self.header_written {
+			self.write_header()?;
+		}
+		let sample = Sample {
+			offset: self.position,
+			size: packet.size(),
+			dts: dts - first,
+			duration: 0,
+			composition_offset,
+			keyframe,
+		};
+		self.samples[index].push(sample);
+		self.write_bytes(&packet.data)
+	}
+
+	/// Writes the open fragment as a moof and mdat pair. The header goes out
+	/// with the first fragment, when the first decoding times are known.
+	fn write_fragment(&mut self) -> Result<()> {
+		if self.samples.iter().all(Vec::is_empty) {
+			return Ok(());
+		}
+		if !self.header_written {
+			self.write_header()?;
+		}
+		self.sequence += 1;
+
+		// the data offsets depend on the size of moof itself
+		let placeholder = self.movie_fragment(0);
+		let moof = self.movie_fragment(placeholder.len() as u64 + 8);
+		let data: Vec<u8> = self.fragment_data.iter_mut().flat_map(std::mem::take).collect();
+		let mut out = moof;
+		put_box(&mut out, atom::MDAT, &data);
+		self.samples.iter_mut().for_each(Vec::clear);
+		self.write_bytes(&out)
+	}
+
+	/// The moof box of the open fragment, whose media data starts
+	/// `data_offset` bytes after the start of moof.
+	fn movie_fragment(&self, mut data_offset: u64) -> Vec<u8> {
+		let mut moof = Vec::new();
+		put_full_box(&mut moof, atom::MFHD, 0, 0, &self.sequence.to_be_bytes());
+		for (index, samples) in self.samples.iter().enumerate() {
+			let Some(first) = samples.first() else { continue };
+			let mut traf = Vec::new();
+			put_full_box(
+				&mut traf,
+				atom::TFHD,
+				0,
+				DEFAULT_BASE_IS_MOOF,
+				&self.tracks[index].id.to_be_bytes(),
+			);
+			put_full_box(&mut traf, atom::TFDT, 1, 0, &(first.dts.max(0) as u64).to_be_bytes());
+
+			let mut run = (samples.len() as u32).to_be_bytes().to_vec();
+			run.extend_from_slice(&(data_offset as u32).to_be_bytes());
+			for (position, sample) in samples.iter().enumerate() {
+				let duration = match samples.get(position + 1) {
+					Some(next) => (next.dts - sample.dts) as u32,
+					None => self.last_duration[index],
+				};
+				let flags = if sample.keyframe { SYNC_SAMPLE } else { NON_SYNC_SAMPLE };
+				run.extend_from_slice(&duration.to_be_bytes());
+				run.extend_from_slice(&sample.size.to_be_bytes());
+				run.extend_from_slice(&flags.to_be_bytes());
+				run.extend_from_slice(&sample.composition_offset.to_be_bytes());
+			}
+			// version 1 takes the composition offsets as signed
+			put_full_box(&mut traf, atom::TRUN, 1, TRUN_FLAGS, &run);
+			put_box(&mut moof, atom::TRAF, &traf);
+			data_offset += self.fragment_data[index].len() as u64;
+		}
+		let mut out = Vec::new();
+		put_box(&mut out, atom::MOOF, &moof);
+		out
+	}
+
+	/// Moves everything from mdat on forward by `shift` bytes, last block
+	/// first, so the moved data never overwrites what is still to be read.
+	fn move_media_data(&mut self, seek: SeekFn<W>, read: ReadFn<W>, shift: u64) -> Result<()> {
+		let mut end = self.position;
+		let mut block = vec![0u8; COPY_BLOCK_SIZE as usize];
+		while end > self.mdat_start {
+			let start = end.saturating_sub(COPY_BLOCK_SIZE).max(self.mdat_start);
+			let block = &mut block[..(end - start) as usize];
+			seek(&mut self.writer, SeekFrom::Start(start))?;
+			read(&mut self.writer, block)?;
+			seek(&mut self.writer, SeekFrom::Start(start + shift))?;
+			self.writer.write_all(block)?;
+			end = start;
+		}
+		Ok(())
+	}
+
+	pub fn finalize(&mut self) -> Result<()> {
+		if self.layout == Layout::Fragmented {
+			self.write_fragment()?;
+			if !self.header_written {
+				self.write_header()?;
+			}
+			return self.writer.flush();
+		}
+		if !self.header_written {
+			self.write_header()?;
+		}
+
+		let end = self.position;
+		let seek = self.seek.ok_or_else(|| error!("mp4 output cannot seek"))?;
+		seek(&mut self.writer, SeekFrom::Start(self.mdat_start + 8))?;
+		self.writer.write_all(&(end - self.mdat_start).to_be_bytes())?;
+
+		match (self.layout, self.read) {
+			(Layout::Faststart, Some(read)) => {
+				// chunk offsets grow with moov, which may then need co64
+				let mut moov = self.movie(0)?;
+				loop {
+					let resized = self.movie(moov.len() as u64)?;
+					if resized.len() == moov.len() {
+						moov = resized;
+						break;
+					}
+					moov = resized;
+				}
+				let shift = moov.len() as u64;
+				self.move_media_data(seek, read, shift)?;
+				seek(&mut self.writer, SeekFrom::Start(self.mdat_start))?;
+				self.writer.write_all(&moov)?;
+				self.position = end + shift;
+				seek(&mut self.writer, SeekFrom::Start(self.position))?;
+			}
+			_ => {
+				seek(&mut self.writer, SeekFrom::Start(end))?;
+				let moov = self.movie(0)?;
+				self.write_bytes(&moov)?;
+			}
+		}
+		self.writer.flush()
+	}
+}
+
+impl<W: MediaWrite> Muxer for Mp4Muxer<W> {
+	fn streams(&self) -> &stream::Streams {
+		&self.streams
+	}
+
+	fn write(&mut self, packet: Packet) -> Result<()> {
+		self.write_packet(packet)
+	}
+
+	fn finalize(&mut self) -> Result<()> {
+		self.finalize()
+	}
+}
@@ -0,0 +1,276 @@
+use super::atom::{self, children, full_box, put_full_box};
+use crate::io::ReadPrimitives;
+use crate::{error, message::Result};
+
+// constant size tables are expanded, so their count is bounded
+const MAX_SAMPLES: u32 = 1 << 26;
+
+/// One access unit of a track, located in the file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sample {
+	pub offset: u64,
+	pub size: u32,
+	/// Decoding time in track timescale units.
+	pub dts: i64,
+	/// Time to the next sample, 0 for samples being muxed.
+	pub duration: u32,
+	/// Presentation time minus decoding time.
+	pub composition_offset: i32,
+	pub keyframe: bool,
+}
+
+/// The tables of an stbl box, kept as read until the samples are built.
+#[derive(Debug, Clone, Default)]
+pub struct SampleTable {
+	sizes: Vec<u32>,
+	chunk_offsets: Vec<u64>,
+	/// (first chunk, samples per chunk), chunks counted from one.
+	chunks: Vec<(u32, u32)>,
+	/// (sample count, delta)
+	time_to_sample: Vec<(u32, u32)>,
+	/// (sample count, offset)
+	composition_offsets: Vec<(u32, i32)>,
+	/// Sync samples counted from one, `None` when every sample is one.
+	sync_samples: Option<Vec<u32>>,
+}
+
+impl SampleTable {
+	pub fn parse(stbl: &[u8]) -> Result<Self> {
+		let mut table = Self::default();
+		for (kind, data) in children(stbl)? {
+			let (_, _, mut data) = full_box(data)?;
+			match kind {
+				atom::STSZ => {
+					let size = data.read_u32_be()?;
+					let count = data.read_u32_be()?;
+					table.sizes = match size {
+						0 => (0..count).map(|_| data.read_u32_be()).collect::<Result<_>>()?,
+						_ if count > MAX_SAMPLES => {
+							return Err(error!("mp4 track has too many samples ({})", count));
+						}
+						size => vec![size; count as usize],
+					};
+				}
+				atom::STZ2 => table.sizes = parse_compact_sizes(data)?,
+				atom::STCO => {
+					let count = data.read_u32_be()?;
+					let offsets = (0..count).map(|_| data.read_u32_be().map(u64::from));
+					table.chunk_offsets = offsets.collect::<Result<_>>()?;
+				}
+				atom::CO64 => {
+					let count = data.read_u32_be()?;
+					table.chunk_offsets = (0..count).map(|_| data.read_u64_be()).collect::<Result<_>>()?;
+				}
+				atom::STSC => {
+					let count = data.read_u32_be()?;
+					for _ in 0..count {
+						let first_chunk = data.read_u32_be()?;
+						let samples_per_chunk = data.read_u32_be()?;
+						data.read_u32_be()?;
+						table.chunks.push((first_chunk, samples_per_chunk));
+					}
+				}
+				atom::STTS => {
+					let count = data.read_u32_be()?;
+					for _ in 0..count {
+						table.time_to_sample.push((data.read_u32_be()?, data.read_u32_be()?));
+					}
+				}
+				atom::CTTS => {
+					let count = data.read_u32_be()?;
+					for _ in 0..count {
+						// version 0 offsets are unsigned, but negative ones
+						// written that way are common enough to read as signed
+						table.composition_offsets.push((data.read_u32_be()?, data.read_i32_be()?));
+					}
+				}
+				atom::STSS => {
+					let count = data.read_u32_be()?;
+					table.sync_samples = Some((0..count).map(|_| data.read_u32_be()).collect::<Result<_>>()?);
+				}
+				_ => {}
+			}
+		}
+		Ok(table)
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.sizes.is_empty()
+	}
+
+	/// Resolves the tables into samples, in decoding order.
+	pub fn samples(&self) -> Result<Vec<Sample>> {
+		let mut samples = Vec::with_capacity(self.sizes.len());
+		let mut sizes = self.sizes.iter();
+		let mut entry = 0;
+		'chunks: for (index, &chunk_offset) in self.chunk_offsets.iter().enumerate() {
+			let chunk = index as u32 + 1;
+			while self.chunks.get(entry + 1).is_some_and(|(first, _)| *first <= chunk) {
+				entry += 1;
+			}
+			let Some(&(_, per_chunk)) = self.chunks.get(entry).filter(|(first, _)| *first <= chunk)
+			else {
+				return Err(error!("mp4 chunk {} has no sample-to-chunk entry", chunk));
+			};
+			let mut offset = chunk_offset;
+			for _ in 0..per_chunk {
+				let Some(&size) = sizes.next() else { break 'chunks };
+				let sample =
+					Sample { offset, size, dts: 0, duration: 0, composition_offset: 0, keyframe: true };
+				samples.push(sample);
+				offset += size as u64;
+			}
+		}
+		if samples.len() < self.sizes.len() {
+			return Err(error!(
+				"mp4 sample table has {} samples but chunks for {}",
+				self.sizes.len(),
+				samples.len()
+			));
+		}
+
+		let mut deltas =
+			self.time_to_sample.iter().flat_map(|&(count, delta)| (0..count).map(move |_| delta));
+		let mut dts = 0i64;
+		for sample in samples.iter_mut() {
+			// a short table repeats its last delta
+			let delta = deltas.next().or(self.time_to_sample.last().map(|&(_, delta)| delta));
+			sample.dts = dts;
+			sample.duration = delta.unwrap_or(0);
+			dts += sample.duration as i64;
+		}
+		let offsets =
+			self.composition_offsets.iter().flat_map(|&(count, offset)| (0..count).map(move |_| offset));
+		for (sample, offset) in samples.iter_mut().zip(offsets) {
+			sample.composition_offset = offset;
+		}
+		if let Some(sync) = &self.sync_samples {
+			for sample in samples.iter_mut() {
+				sample.keyframe = false;
+			}
+			for &number in sync {
+				if let Some(sample) =
+					(number as usize).checked_sub(1).and_then(|index| samples.get_mut(index))
+				{
+					sample.keyframe = true;
+				}
+			}
+		}
+		Ok(samples)
+	}
+}
+
+fn parse_compact_sizes(mut data: &[u8]) -> Result<Vec<u32>> {
+	let field_size = data.read_u32_be()? & 0xFF;
+	let count = data.read_u32_be()? as usize;
+	let needed = (count * field_size as usize).div_ceil(8);
+	if data.len() < needed {
+		return Err(error!("mp4 stz2 box is truncated"));
+	}
+	let sizes = match field_size {
+		4 => {
+			(0..count).map(|index| ((data[index / 2] >> (4 - 4 * (index % 2))) & 0x0F) as u32).collect()
+		}
+		8 => data[..count].iter().map(|&size| size as u32).collect(),
+		16 => data[..count * 2]
+			.chunks_exact(2)
+			.map(|size| u16::from_be_bytes([size[0], size[1]]) as u32)
+			.collect(),
+		_ => return Err(error!("mp4 stz2 field size {} is invalid", field_size)),
+	};
+	Ok(sizes)
+}
+
+/// Writes the stts, ctts, stss, stsc, stsz and stco (or co64) boxes of the
+/// samples, in decoding order. A sample's duration is the gap to the next
+/// one; the last lasts `last_duration`. Chunk offsets move by `shift`.
+pub fn write_tables(samples: &[Sample], last_duration: u32, shift: u64) -> Vec<u8> {
+	let mut out = Vec::new();
+
+	let mut time_to_sample: Vec<(u32, u32)> = Vec::new();
+	for (index, sample) in samples.iter().enumerate() {
+		let duration = match samples.get(index + 1) {
+			Some(next) => (next.dts - sample.dts).max(0) as u32,
+			None => last_duration,
+		};
+		match time_to_sample.last_mut() {
+			Some((count, delta)) if *delta == duration => *count += 1,
+			_ => time_to_sample.push((1, duration)),
+		}
+	}
+	put_run_table(&mut out, atom::STTS, 0, &time_to_sample);
+
+	if samples.iter().any(|sample| sample.composition_offset != 0) {
+		let mut offsets: Vec<(u32, u32)> = Vec::new();
+		for sample in samples {
+			let offset = sample.composition_offset as u32;
+			match offsets.last_mut() {
+				Some((count, last)) if *last == offset => *count += 1,
+				_ => offsets.push((1, offset)),
+			}
+		}
+		// version 1 marks the offsets as signed
+		let version = samples.iter().any(|sample| sample.composition_offset < 0) as u8;
+		put_run_table(&mut out, atom::CTTS, version, &offsets);
+	}
+
+	if samples.iter().any(|sample| !sample.keyframe) {
+		let sync: Vec<u32> = samples
+			.iter()
+			.enumerate()
+			.filter(|(_, sample)| sample.keyframe)
+			.map(|(index, _)| index as u32 + 1)
+			.collect();
+		let mut data = (sync.len() as u32).to_be_bytes().to_vec();
+		sync.iter().for_each(|number| data.extend_from_slice(&number.to_be_bytes()));
+		put_full_box(&mut out, atom::STSS, 0, 0, &data);
+	}
+
+	// a chunk is a run of samples stored back to back
+	let mut chunks: Vec<(u64, u32)> = Vec::new();
+	let mut end = None;
+	for sample in samples {
+		match chunks.last_mut() {
+			Some((_, count)) if end == Some(sample.offset) => *count += 1,
+			_ => chunks.push((sample.offset + shift, 1)),
+		}
+		end = Some(sample.offset + sample.size as u64);
+	}
+	let mut sample_to_chunk: Vec<(u32, u32)> = Vec::new();
+	for (index, &(_, count)) in chunks.iter().enumerate() {
+		if sample_to_chunk.last().is_none_or(|&(_, last)| last != count) {
+			sample_to_chunk.push((index as u32 + 1, count));
+		}
+	}
+	let mut data = (sample_to_chunk.len() as u32).to_be_bytes().to_vec();
+	for (first_chunk, count) in sample_to_chunk {
+		data.extend_from_slice(&first_chunk.to_be_bytes());
+		data.extend_from_slice(&count.to_be_bytes());
+		data.extend_from_slice(&1u32.to_be_bytes());
+	}
+	put_full_box(&mut out, atom::STSC, 0, 0, &data);
+
+	let mut data = 0u32.to_be_bytes().to_vec();
+	data.extend_from_slice(&(samples.len() as u32).to_be_bytes());
+	samples.iter().for_each(|sample| data.extend_from_slice(&sample.size.to_be_bytes()));
+	put_full_box(&mut out, atom::STSZ, 0, 0, &data);
+
+	let mut data = (chunks.len() as u32).to_be_bytes().to_vec();
+	if chunks.iter().any(|&(offset, _)| offset > u32::MAX as u64) {
+		chunks.iter().for_each(|(offset, _)| data.extend_from_slice(&offset.to_be_bytes()));
+		put_full_box(&mut out, atom::CO64, 0, 0, &data);
+	} else {
+		chunks.iter().for_each(|&(offset, _)| data.extend_from_slice(&(offset as u32).to_be_bytes()));
+		put_full_box(&mut out, atom::STCO, 0, 0, &data);
+	}
+	out
+}
+
+fn put_run_table(out: &mut Vec<u8>, kind: atom::BoxType, version: u8, runs: &[(u32, u32)]) {
+	let mut data = (runs.len() as u32).to_be_bytes().to_vec();
+	for (count, value) in runs {
+		data.extend_from_slice(&count.to_be_bytes());
+		data.extend_from_slice(&value.to_be_bytes());
+	}
+	put_full_box(out, kind, version, 0, &data);
+}
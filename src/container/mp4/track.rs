@@ -0,0 +1,588 @@
+use super::atom::{self, BoxType, children, find, full_box, put_box, put_full_box};
+use super::esds::{self, Esds};
+use super::sample::{Sample, SampleTable};
+use crate::codecs;
+use crate::codecs::audio::aac::AudioSpecificConfig;
+use crate::codecs::audio::flac::StreamInfo;
+use crate::codecs::audio::opus::packet::OpusHead;
+use crate::core::stream::{Stream, StreamKind};
+use crate::io::ReadPrimitives;
+use crate::{error, message::Result};
+
+// fixed part of the sample entries, before their child boxes
+const AUDIO_ENTRY_SIZE: usize = 28;
+const VISUAL_ENTRY_SIZE: usize = 78;
+// extra fields of QuickTime sound description versions 1 and 2
+const SOUND_V1_SIZE: usize = 16;
+const SOUND_V2_SIZE: usize = 36;
+
+const VIDEO_TIMESCALE: u32 = 90000;
+const UNDETERMINED_LANGUAGE: u16 = 0x55C4;
+// the identity transform of track headers
+const MATRIX: [u32; 9] = [0x1_0000, 0, 0, 0, 0x1_0000, 0, 0, 0, 0x4000_0000];
+
+/// Sample defaults of a track in fragmented files, from mvex/trex.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TrackDefaults {
+	pub duration: u32,
+	pub size: u32,
+	pub flags: u32,
+}
+
+/// A trak box, with the sample tables resolved.
+#[derive(Debug, Clone, Default)]
+pub struct Track {
+	pub id: u32,
+	pub kind: Option<StreamKind>,
+	pub codec: Option<&'static str>,
+	pub codec_private: Vec<u8>,
+	pub timescale: u32,
+	/// Duration in timescale units, as the media header states it.
+	pub duration: u64,
+	pub language: Option<String>,
+	pub sample_rate: u32,
+	pub channels: u16,
+	pub bits_per_sample: u16,
+	pub width: u16,
+	pub height: u16,
+	/// Offset the edit list moves presentation by, in timescale units.
+	pub edit_shift: i64,
+	/// Presentation time the edit list ends the track at, in timescale
+	/// units, when it cuts the last samples short.
+	pub edit_end: Option<i64>,
+	/// Samples of the sample tables, which the demuxer takes over.
+	pub samples: Vec<Sample>,
+	pub defaults: TrackDefaults,
+}
+
+impl Track {
+	/// Parses a trak box; `movie_timescale` converts its edit list.
+	pub fn parse(trak: &[u8], movie_timescale: u32) -> Result<Self> {
+		let mut track = Self { timescale: 1, ..Self::default() };
+		let mut edits = None;
+		for (kind, data) in children(trak)? {
+			match kind {
+				atom::TKHD => track.parse_header(data)?,
+				atom::EDTS => edits = find(data, atom::ELST)?,
+				atom::MDIA => track.parse_media(data)?,
+				_ => {}
+			}
+		}
+		if track.id == 0 {
+			return Err(error!("mp4 track has no id"));
+		}
+		if let Some(edits) = edits {
+			track.parse_edits(edits, movie_timescale)?;
+		}
+		Ok(track)
+	}
+
+	fn parse_header(&mut self, data: &[u8]) -> Result<()> {
+		let (version, _, mut data) = full_box(data)?;
+		// creation and modification times come first
+		let skip = if version == 1 { 16 } else { 8 };
+		data = data.get(skip..).ok_or_else(|| error!("mp4 tkhd box is truncated"))?;
+		self.id = data.read_u32_be()?;
+		Ok(())
+	}
+
+	fn parse_media(&mut self, mdia: &[u8]) -> Result<()> {
+		for (kind, data) in children(mdia)? {
+			match kind {
+				atom::MDHD => {
+					let (version, _, mut data) = full_box(data)?;
+					let skip = if version == 1 { 16 } else { 8 };
+					data = data.get(skip..).ok_or_else(|| error!("mp4 mdhd box is truncated"))?;
+					self.timescale = data.read_u32_be()?;
+					self.duration = match version {
+						1 => data.read_u64_be()?,
+						_ => data.read_u32_be()? as u64,
+					};
+					self.language = parse_language(data.read_u16_be()?);
+				}
+				atom::HDLR => {
+					let (_, _, data) = full_box(data)?;
+					let handler = data.get(4..8).ok_or_else(|| error!("mp4 hdlr box is truncated"))?;
+					self.kind = match [handler[0], handler[1], handler[2], handler[3]] {
+						atom::VIDE => Some(StreamKind::Video),
+						atom::SOUN => Some(StreamKind::Audio),
+						atom::SBTL | atom::TEXT | atom::SUBT => Some(StreamKind::Subtitle),
+						_ => None,
+					};
+				}
+				atom::MINF => {
+					let Some(stbl) = find(data, atom::STBL)? else { continue };
+					if let Some(stsd) = find(stbl, atom::STSD)? {
+						self.parse_sample_description(stsd)?;
+					}
+					let table = SampleTable::parse(stbl)?;
+					if !table.is_empty() {
+						self.samples = table.samples()?;
+					}
+				}
+				_ => {}
+			}
+		}
+		if self.timescale == 0 {
+			return Err(error!("mp4 track {} has a zero timescale", self.id));
+		}
+		Ok(())
+	}
+
+	/// Reads the first sample entry, which names the codec.
+	fn parse_sample_description(&mut self, stsd: &[u8]) -> Result<()> {
+		let (_, _, data) = full_box(stsd)?;
+		let entries = children(data.get(4..).unwrap_or_default())?;
+		let Some(&(kind, entry)) = entries.first() else { return Ok(()) };
+		match self.kind {
+			Some(StreamKind::Audio) => self.parse_audio_entry(kind, entry),
+			Some(StreamKind::Video) => self.parse_visual_entry(kind, entry),
+			Some(StreamKind::Subtitle) => {
+				self.codec = (kind == atom::TX3G).then_some(codecs::subtitle::MOV_TEXT);
+				Ok(())
+			}
+			_ => Ok(()),
+		}
+	}
+
+	fn parse_audio_entry(&mut self, kind: BoxType, entry: &[u8]) -> Result<()> {
+		if entry.len() < AUDIO_ENTRY_SIZE {
+			return Err(error!("mp4 audio sample entry is truncated"));
+		}
+		let version = u16::from_be_bytes([entry[8], entry[9]]);
+		self.channels = u16::from_be_bytes([entry[16], entry[17]]);
+		self.bits_per_sample = u16::from_be_bytes([entry[18], entry[19]]);
+		self.sample_rate = u16::from_be_bytes([entry[24], entry[25]]) as u32;
+		let extra = match version {
+			1 => SOUND_V1_SIZE,
+			2 => SOUND_V2_SIZE,
+			_ => 0,
+		};
+		if version == 2 && entry.len() >= AUDIO_ENTRY_SIZE + SOUND_V2_SIZE {
+			let fields = &entry[AUDIO_ENTRY_SIZE..];
+			self.sample_rate = f64::from_be_bytes(fields[4..12].try_into().unwrap()) as u32;
+			self.channels = u32::from_be_bytes(fields[12..16].try_into().unwrap()) as u16;
+		}
+		let boxes = entry.get(AUDIO_ENTRY_SIZE + extra..).unwrap_or_default();
+		// QuickTime nests the configuration in a wave box
+		let boxes = match find(boxes, atom::WAVE)? {
+			Some(wave) if kind == atom::MP4A => wave,
+			_ => boxes,
+		};
+
+		match kind {
+			atom::MP4A => {
+				let esds =
+					find(boxes, atom::ESDS)?.ok_or_else(|| error!("mp4a sample entry has no esds box"))?;
+				let esds = Esds::parse(esds)?;
+				self.codec = esds.codec();
+				self.codec_private = esds.decoder_config;
+			}
+			atom::ALAC => {
+				if let Some(config) = find(boxes, atom::ALAC)? {
+					self.codec_private = full_box(config)?.2.to_vec();
+				}
+				self.codec = Some(codecs::audio::ALAC);
+			}
+			atom::OPUS => {
+				let config =
+					find(boxes, atom::DOPS)?.ok_or_else(|| error!("Opus sample entry has no dOps box"))?;
+				self.codec_private = opus_head(config)?;
+				self.sample_rate = 48000;
+				self.codec = Some(codecs::audio::OPUS);
+			}
+			atom::FLAC => {
+				let config =
+					find(boxes, atom::DFLA)?.ok_or_else(|| error!("fLaC sample entry has no dfLa box"))?;
+				// the first metadata block is the STREAMINFO
+				let (_, _, blocks) = full_box(config)?;
+				let info = blocks.get(4..38).filter(|_| blocks[0] & 0x7F == 0);
+				let info = info.ok_or_else(|| error!("mp4 flac track {} has no STREAMINFO", self.id))?;
+				self.codec_private = info.to_vec();
+				self.codec = Some(codecs::audio::FLAC);
+			}
+			atom::SOWT if self.bits_per_sample == 16 => self.codec = Some(codecs::audio::PCM_S16LE),
+			_ => {}
+		}
+		Ok(())
+	}
+
+	fn parse_visual_entry(&mut self, kind: BoxType, entry: &[u8]) -> Result<()> {
+		if entry.len() < VISUAL_ENTRY_SIZE {
+			return Err(error!("mp4 visual sample entry is truncated"));
+		}
+		self.width = u16::from_be_bytes([entry[24], entry[25]]);
+		self.height = u16::from_be_bytes([entry[26], entry[27]]);
+		let boxes = &entry[VISUAL_ENTRY_SIZE..];
+		let (codec, config) = match kind {
+			atom::AVC1 | atom::AVC3 => (codecs::video::H264, atom::AVCC),
+			atom::HVC1 | atom::HEV1 => (codecs::video::H265, atom::HVCC),
+			atom::AV01 => (codecs::video::AV1, atom::AV1C),
+			atom::VP09 => (codecs::video::VP9, atom::VPCC),
+			atom::MP4V => {
+				if let Some(esds) = find(boxes, atom::ESDS)? {
+					self.codec_private = Esds::parse(esds)?.decoder_config;
+				}
+				self.codec = Some(codecs::video::MPEG4);
+				return Ok(());
+			}
+			_ => return Ok(()),
+		};
+		self.codec = Some(codec);
+		self.codec_private = find(boxes, config)?.map(<[u8]>::to_vec).unwrap_or_default();
+		Ok(())
+	}
+
+	/// Turns the edit list into a single presentation offset: leading empty
+	/// edits delay the track, the first media edit skips into it and may
+	/// end it early.
+	fn parse_edits(&mut self, elst: &[u8], movie_timescale: u32) -> Result<()> {
+		let (version, _, mut data) = full_box(elst)?;
+		let count = data.read_u32_be()?;
+		let mut delay = 0u64;
+		for _ in 0..count {
+			let (duration, media_time) = match version {
+				1 => (data.read_u64_be()?, data.read_i64_be()?),
+				_ => (data.read_u32_be()? as u64, data.read_i32_be()? as i64),
+			};
+			data.read_u32_be()?;
+			if media_time == -1 {
+				delay += duration;
+				continue;
+			}
+			let scale =
+				|time: u64| (time as u128 * self.timescale as u128 / movie_timescale.max(1) as u128) as i64;
+			let delay = scale(delay);
+			self.edit_shift = delay - media_time;
+			// a media edit of no duration runs to the end of the media
+			self.edit_end = (duration > 0).then(|| delay + scale(duration));
+			break;
+		}
+		Ok(())
+	}
+}
+
+impl Track {
+	/// Describes a stream about to be muxed as track `id`. The audio layout
+	/// comes from the codec private data where the codec has one; picture
+	/// sizes can be filled in before the header is written.
+	pub fn from_stream(stream: &Stream, id: u32) -> Result<Self> {
+		let codec = CODECS
+			.iter()
+			.find(|known| **known == stream.codec)
+			.ok_or_else(|| error!("codec '{}' cannot be stored in mp4", stream.codec))?;
+		let mut track = Self {
+			id,
+			kind: Some(stream.kind),
+			codec: Some(codec),
+			codec_private: stream.codec_private.clone(),
+			timescale: VIDEO_TIMESCALE,
+			channels: 2,
+			bits_per_sample: 16,
+			..Self::default()
+		};
+		if stream.time.num == 1 {
+			track.timescale = stream.time.den;
+			track.sample_rate = stream.time.den;
+		}
+
+		let private = &stream.codec_private;
+		match *codec {
+			codecs::audio::AAC => {
+				let config = AudioSpecificConfig::parse(private)?;
+				track.sample_rate = config.sample_rate;
+				track.channels = config.channels() as u16;
+			}
+			codecs::audio::OPUS => {
+				track.channels = OpusHead::parse(private)?.channels as u16;
+				track.sample_rate = 48000;
+			}
+			codecs::audio::FLAC => {
+				let info = StreamInfo::parse(private)?;
+				track.sample_rate = info.sample_rate;
+				track.channels = info.channels as u16;
+				track.bits_per_sample = info.bits_per_sample as u16;
+			}
+			codecs::audio::ALAC if private.len() >= 24 => {
+				track.bits_per_sample = private[5] as u16;
+				track.channels = private[9] as u16;
+				track.sample_rate =
+					u32::from_be_bytes([private[20], private[21], private[22], private[23]]);
+			}
+			_ => {}
+		}
+		if stream.kind == StreamKind::Audio {
+			if track.sample_rate == 0 {
+				return Err(error!("mp4 track {} has no sample rate", id));
+			}
+			track.timescale = track.sample_rate;
+		}
+		Ok(track)
+	}
+
+	/// The sample description entry, which names the codec and carries its
+	/// configuration.
+	pub fn sample_entry(&self) -> Result<Vec<u8>> {
+		let private = &self.codec_private;
+		let mut config = Vec::new();
+		let kind = match self.codec.unwrap_or_default() {
+			codecs::audio::AAC => {
+				let esds = Esds {
+					object_type: esds::OBJECT_TYPE_AAC,
+					decoder_config: private.clone(),
+					..Esds::default()
+				};
+				put_full_box(&mut config, atom::ESDS, 0, 0, &esds.to_bytes(self.id as u16));
+				atom::MP4A
+			}
+			codecs::audio::MP3 => {
+				let esds = Esds { object_type: esds::OBJECT_TYPE_MP3, ..Esds::default() };
+				put_full_box(&mut config, atom::ESDS, 0, 0, &esds.to_bytes(self.id as u16));
+				atom::MP4A
+			}
+			codecs::audio::ALAC => {
+				put_full_box(&mut config, atom::ALAC, 0, 0, private);
+				atom::ALAC
+			}
+			codecs::audio::OPUS => {
+				put_box(&mut config, atom::DOPS, &dops(private)?);
+				atom::OPUS
+			}
+			codecs::audio::FLAC => {
+				// a single STREAMINFO block, flagged as the last one
+				let block = [&[0x80, 0, 0, 34], private.as_slice()].concat();
+				put_full_box(&mut config, atom::DFLA, 0, 0, &block);
+				atom::FLAC
+			}
+			codecs::audio::PCM_S16LE => atom::SOWT,
+			codecs::video::H264 => {
+				put_box(&mut config, atom::AVCC, private);
+				atom::AVC1
+			}
+			codecs::video::H265 => {
+				put_box(&mut config, atom::HVCC, private);
+				atom::HVC1
+			}
+			codecs::video::AV1 => {
+				put_box(&mut config, atom::AV1C, private);
+				atom::AV01
+			}
+			codecs::video::VP9 => {
+				put_full_box(&mut config, atom::VPCC, 1, 0, private.get(4..).unwrap_or_default());
+				atom::VP09
+			}
+			codecs::video::MPEG4 => {
+				let esds = Esds {
+					object_type: esds::OBJECT_TYPE_MPEG4_VIDEO,
+					decoder_config: private.clone(),
+					..Esds::default()
+				};
+				put_full_box(&mut config, atom::ESDS, 0, 0, &esds.to_bytes(self.id as u16));
+				atom::MP4V
+			}
+			codecs::subtitle::MOV_TEXT => atom::TX3G,
+			codec => return Err(error!("codec '{}' cannot be stored in mp4", codec)),
+		};
+
+		// reserved bytes and the data reference index
+		let mut entry = vec![0, 0, 0, 0, 0, 0, 0, 1];
+		match self.kind {
+			Some(StreamKind::Audio) => {
+				entry.extend_from_slice(&[0; 8]);
+				entry.extend_from_slice(&self.channels.to_be_bytes());
+				entry.extend_from_slice(&self.bits_per_sample.to_be_bytes());
+				entry.extend_from_slice(&[0; 4]);
+				let rate = if self.sample_rate > u16::MAX as u32 { 0 } else { self.sample_rate << 16 };
+				entry.extend_from_slice(&rate.to_be_bytes());
+			}
+			Some(StreamKind::Video) => {
+				entry.extend_from_slice(&[0; 16]);
+				entry.extend_from_slice(&self.width.to_be_bytes());
+				entry.extend_from_slice(&self.height.to_be_bytes());
+				// 72 dpi, one frame per sample, no compressor name, 24 bit
+				entry.extend_from_slice(&0x0048_0000u32.to_be_bytes());
+				entry.extend_from_slice(&0x0048_0000u32.to_be_bytes());
+				entry.extend_from_slice(&[0; 4]);
+				entry.extend_from_slice(&1u16.to_be_bytes());
+				entry.extend_from_slice(&[0; 32]);
+				entry.extend_from_slice(&0x0018u16.to_be_bytes());
+				entry.extend_from_slice(&(-1i16).to_be_bytes());
+			}
+			_ => {
+				// tx3g display flags, justification, colors, box and style
+				entry.extend_from_slice(&[0; 30]);
+				entry.extend_from_slice(&[
+					0, 0, 0, 0x12, b'f', b't', b'a', b'b', 0, 1, 0, 1, 0, 0, 0, 0, 0, 0,
+				]);
+			}
+		}
+		entry.extend_from_slice(&config);
+		let mut out = Vec::new();
+		put_box(&mut out, kind, &entry);
+		Ok(out)
+	}
+
+	/// The trak box, around the sample `tables` (see
+	/// [`write_tables`](super::sample::write_tables)).
+	pub fn write_trak(&self, tables: &[u8], movie_timescale: u32) -> Result<Vec<u8>> {
+		let duration = self.duration;
+		let movie_duration =
+			(duration as u128 * movie_timescale as u128 / self.timescale as u128) as u64;
+		let mut trak = Vec::new();
+
+		let mut header = Vec::new();
+		header.extend_from_slice(&[0; 8]);
+		header.extend_from_slice(&self.id.to_be_bytes());
+		header.extend_from_slice(&[0; 4]);
+		header.extend_from_slice(&(movie_duration as u32).to_be_bytes());
+		header.extend_from_slice(&[0; 12]);
+		let volume: u16 = if self.kind == Some(StreamKind::Audio) { 0x0100 } else { 0 };
+		header.extend_from_slice(&volume.to_be_bytes());
+		header.extend_from_slice(&[0; 2]);
+		MATRIX.iter().for_each(|value| header.extend_from_slice(&value.to_be_bytes()));
+		header.extend_from_slice(&((self.width as u32) << 16).to_be_bytes());
+		header.extend_from_slice(&((self.height as u32) << 16).to_be_bytes());
+		// enabled and in the presentation
+		put_full_box(&mut trak, atom::TKHD, 0, 3, &header);
+
+		let segment = match self.edit_end {
+			Some(end) => {
+				let presented = (end - self.edit_shift.max(0)).max(0) as u128;
+				(presented * movie_timescale as u128 / self.timescale as u128) as u64
+			}
+			None => movie_duration,
+		};
+		if self.edit_shift != 0 || segment != movie_duration {
+			let mut edits = Vec::new();
+			let mut entries = Vec::new();
+			if self.edit_shift > 0 {
+				let delay = self.edit_shift as u128 * movie_timescale as u128 / self.timescale as u128;
+				entries.push((delay as u32, -1));
+			}
+			entries.push((segment as u32, (-self.edit_shift).max(0) as i32));
+			let mut list = (entries.len() as u32).to_be_bytes().to_vec();
+			for (segment_duration, media_time) in entries {
+				list.extend_from_slice(&segment_duration.to_be_bytes());
+				list.extend_from_slice(&media_time.to_be_bytes());
+				list.extend_from_slice(&0x1_0000u32.to_be_bytes());
+			}
+			put_full_box(&mut edits, atom::ELST, 0, 0, &list);
+			put_box(&mut trak, atom::EDTS, &edits);
+		}
+
+		let mut media = Vec::new();
+		let mut media_header = vec![0; 8];
+		media_header.extend_from_slice(&self.timescale.to_be_bytes());
+		media_header.extend_from_slice(&(duration.min(u32::MAX as u64) as u32).to_be_bytes());
+		let language =
+			self.language.as_deref().and_then(pack_language).unwrap_or(UNDETERMINED_LANGUAGE);
+		media_header.extend_from_slice(&language.to_be_bytes());
+		media_header.extend_from_slice(&[0; 2]);
+		put_full_box(&mut media, atom::MDHD, 0, 0, &media_header);
+
+		let (handler, media_info): (BoxType, Vec<u8>) = match self.kind {
+			Some(StreamKind::Video) => {
+				let mut info = Vec::new();
+				put_full_box(&mut info, atom::VMHD, 0, 1, &[0; 8]);
+				(atom::VIDE, info)
+			}
+			Some(StreamKind::Audio) => {
+				let mut info = Vec::new();
+				put_full_box(&mut info, atom::SMHD, 0, 0, &[0; 4]);
+				(atom::SOUN, info)
+			}
+			_ => {
+				let mut info = Vec::new();
+				put_full_box(&mut info, atom::NMHD, 0, 0, &[]);
+				(atom::SBTL, info)
+			}
+		};
+		let mut handler_data = vec![0; 4];
+		handler_data.extend_from_slice(&handler);
+		handler_data.extend_from_slice(&[0; 12]);
+		handler_data.extend_from_slice(b"ffmpreg\0");
+		put_full_box(&mut media, atom::HDLR, 0, 0, &handler_data);
+
+		let mut info = media_info;
+		let mut references = 1u32.to_be_bytes().to_vec();
+		// the media is in this file
+		put_full_box(&mut references, atom::URL, 0, 1, &[]);
+		let mut data_info = Vec::new();
+		put_full_box(&mut data_info, atom::DREF, 0, 0, &references);
+		put_box(&mut info, atom::DINF, &data_info);
+
+		let mut descriptions = 1u32.to_be_bytes().to_vec();
+		descriptions.extend_from_slice(&self.sample_entry()?);
+		let mut stbl = Vec::new();
+		put_full_box(&mut stbl, atom::STSD, 0, 0, &descriptions);
+		stbl.extend_from_slice(tables);
+		put_box(&mut info, atom::STBL, &stbl);
+		put_box(&mut media, atom::MINF, &info);
+		put_box(&mut trak, atom::MDIA, &media);
+
+		let mut out = Vec::new();
+		put_box(&mut out, atom::TRAK, &trak);
+		Ok(out)
+	}
+}
+
+/// Codecs the muxer has a sample entry for.
+const CODECS: &[&str] = &[
+	codecs::audio::AAC,
+	codecs::audio::MP3,
+	codecs::audio::ALAC,
+	codecs::audio::OPUS,
+	codecs::audio::FLAC,
+	codecs::audio::PCM_S16LE,
+	codecs::video::H264,
+	codecs::video::H265,
+	codecs::video::AV1,
+	codecs::video::VP9,
+	codecs::video::MPEG4,
+	codecs::subtitle::MOV_TEXT,
+];
+
+/// Rewrites an OpusHead as the dOps box payload: big endian, no magic.
+fn dops(head: &[u8]) -> Result<Vec<u8>> {
+	OpusHead::parse(head)?;
+	let mut dops = vec![0, head[9]];
+	dops.extend_from_slice(&[head[11], head[10]]);
+	dops.extend_from_slice(&[head[15], head[14], head[13], head[12]]);
+	dops.extend_from_slice(&[head[17], head[16]]);
+	dops.push(head[18]);
+	dops.extend_from_slice(&head[19..]);
+	Ok(dops)
+}
+
+fn pack_language(language: &str) -> Option<u16> {
+	let bytes = language.as_bytes();
+	if bytes.len() != 3 || !bytes.iter().all(u8::is_ascii_lowercase) {
+		return None;
+	}
+	Some(bytes.iter().fold(0u16, |acc, &letter| (acc << 5) | (letter - 0x60) as u16))
+}
+
+/// ISO 639-2 code packed as three five bit letters.
+fn parse_language(code: u16) -> Option<String> {
+	let letters: String =
+		(0..3).rev().map(|index| (((code >> (5 * index)) & 0x1F) as u8 + 0x60) as char).collect();
+	(letters.chars().all(|letter| letter.is_ascii_lowercase()) && letters != "und").then_some(letters)
+}
+
+/// Rewrites a dOps box as the OpusHead the Ogg mapping and the decoder use:
+/// same fields, little endian and with a magic.
+fn opus_head(dops: &[u8]) -> Result<Vec<u8>> {
+	if dops.len() < 11 || dops[0] != 0 {
+		return Err(error!("mp4 dOps box is invalid"));
+	}
+	let mut head = OpusHead::MAGIC.to_vec();
+	head.push(1);
+	head.push(dops[1]);
+	head.extend_from_slice(&[dops[3], dops[2]]);
+	head.extend_from_slice(&[dops[7], dops[6], dops[5], dops[4]]);
+	head.extend_from_slice(&[dops[9], dops[8]]);
+	head.push(dops[10]);
+	head.extend_from_slice(&dops[11..]);
+	OpusHead::parse(&head)?;
+	Ok(head)
+}
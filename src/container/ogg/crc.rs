@@ -0,0 +1,23 @@
+const CRC32_TABLE: [u32; 256] = crc32_table();
+
+const fn crc32_table() -> [u32; 256] {
+	let mut table = [0u32; 256];
+	let mut i = 0;
+	while i < 256 {
+		let mut crc = (i as u32) << 24;
+		let mut bit = 0;
+		while bit < 8 {
+			crc = if crc & 0x8000_0000 != 0 { (crc << 1) ^ 0x04C1_1DB7 } else { crc << 1 };
+			bit += 1;
+		}
+		table[i] = crc;
+		i += 1;
+	}
+	table
+}
+
+/// CRC-32 (poly 0x04C11DB7, no reflection, zero initial value) over a whole
+/// page with its checksum field zeroed.
+pub fn crc32(data: &[u8]) -> u32 {
+	data.iter().fold(0u32, |crc, &byte| (crc << 8) ^ CRC32_TABLE[((crc >> 24) as u8 ^ byte) as usize])
+}
@@ -0,0 +1,257 @@
+use super::mapping::Mapping;
+use super::page::{CAPTURE_PATTERN, HEADER_SIZE, NO_GRANULE, Page};
+use crate::container::probe::SCORE_MAX;
+use crate::core::packet::Packet;
+use crate::core::{Demuxer, stream};
+use crate::io::MediaRead;
+use crate::{error, message::Result};
+use std::collections::VecDeque;
+
+const READ_SIZE: usize = 8192;
+
+struct LogicalStream {
+	serial: u32,
+	index: u32,
+	mapping: Mapping,
+	headers: Vec<Vec<u8>>,
+	partial: Vec<u8>,
+	sequence: Option<u32>,
+	// stream time right after the last data packet returned
+	position: Option<i64>,
+}
+
+impl LogicalStream {
+	fn has_headers(&self) -> bool {
+		self.headers.len() >= self.mapping.header_count
+	}
+}
+
+pub struct OggDemuxer<R: MediaRead> {
+	reader: R,
+	buffer: Vec<u8>,
+	eof: bool,
+	streams: stream::Streams,
+	logical: Vec<LogicalStream>,
+	pending: VecDeque<Packet>,
+}
+
+impl<R: MediaRead> OggDemuxer<R> {
+	pub fn new(reader: R) -> Result<Self> {
+		let mut demuxer = Self {
+			reader,
+			buffer: Vec::new(),
+			eof: false,
+			streams: stream::Streams::new_empty(),
+			logical: Vec::new(),
+			pending: VecDeque::new(),
+		};
+
+		// every logical stream opens with a page holding only its first header,
+		// and all of those pages come before any other
+		let mut page = demuxer.read_page()?;
+		while let Some(first) = page.as_ref().filter(|page| page.is_first()) {
+			demuxer.add_stream(first)?;
+			page = demuxer.read_page()?;
+		}
+		if demuxer.logical.is_empty() {
+			return Err(error!("ogg file has no supported streams"));
+		}
+
+		while let Some(next) = page {
+			demuxer.process_page(next)?;
+			if demuxer.logical.iter().all(LogicalStream::has_headers) {
+				break;
+			}
+			page = demuxer.read_page()?;
+		}
+		if !demuxer.logical.iter().all(LogicalStream::has_headers) {
+			return Err(error!("ogg file ended before its stream headers"));
+		}
+
+		for logical in &demuxer.logical {
+			let mapping = &logical.mapping;
+			let codec = mapping.codec.to_string();
+			let index = logical.index;
+			let stream = stream::Stream::new(index, index as usize, mapping.kind, codec, mapping.time)
+				.with_codec_private(mapping.codec_private(&logical.headers));
+			demuxer.streams.add(stream);
+		}
+		Ok(demuxer)
+	}
+
+	/// Raw header packets of a stream, such as the comment header.
+	pub fn headers(&self, stream_id: u32) -> Option<&[Vec<u8>]> {
+		self.logical.iter().find(|logical| logical.index == stream_id).map(|l| l.headers.as_slice())
+	}
+
+	fn add_stream(&mut self, page: &Page) -> Result<()> {
+		let Some((packet, true)) = page.packets().first().copied() else {
+			return Err(error!("ogg stream {:#x} does not open with a complete packet", page.serial));
+		};
+		// streams without a mapping, such as skeleton metadata, are ignored
+		let Some(mapping) = Mapping::detect(packet)? else {
+			return Ok(());
+		};
+		self.logical.push(LogicalStream {
+			serial: page.serial,
+			index: self.logical.len() as u32,
+			mapping,
+			headers: vec![packet.to_vec()],
+			partial: Vec::new(),
+			sequence: Some(page.sequence),
+			position: None,
+		});
+		Ok(())
+	}
+
+	fn process_page(&mut self, page: Page) -> Result<()> {
+		let Some(stream) = self.logical.iter_mut().find(|logical| logical.serial == page.serial) else {
+			return Ok(());
+		};
+
+		// a gap in the sequence loses whatever packet was being assembled
+		if stream.sequence.is_some_and(|sequence| sequence.wrapping_add(1) != page.sequence) {
+			stream.partial.clear();
+		}
+		stream.sequence = Some(page.sequence);
+
+		let mut packets = Vec::new();
+		for (i, (piece, complete)) in page.packets().into_iter().enumerate() {
+			let continued = i == 0 && page.is_continued();
+			if continued && stream.partial.is_empty() {
+				continue;
+			}
+			if !continued {
+				stream.partial.clear();
+			}
+			stream.partial.extend_from_slice(piece);
+			if complete {
+				packets.push(std::mem::take(&mut stream.partial));
+			}
+		}
+
+		let mut data = Vec::new();
+		for packet in packets {
+			if stream.has_headers() {
+				data.push(packet);
+			} else {
+				stream.mapping.add_header(stream.headers.len(), &packet)?;
+				stream.headers.push(packet);
+			}
+		}
+		if data.is_empty() {
+			return Ok(());
+		}
+
+		let durations: Vec<i64> =
+			data.iter().map(|packet| stream.mapping.packet_duration(packet).unwrap_or(0)).collect();
+		let end = (page.granule != NO_GRANULE).then(|| stream.mapping.granule_end(page.granule));
+		// the first page only tells where its last packet ends, so count back
+		let mut pts = match (stream.position, end) {
+			(Some(position), _) => position,
+			(None, Some(end)) => end - durations.iter().sum::<i64>(),
+			(None, None) => 0,
+		};
+
+		let time = stream.mapping.time;
+		for (packet, duration) in data.into_iter().zip(durations) {
+			if !packet.is_empty() {
+				let keyframe = stream.mapping.is_keyframe(&packet);
+				let packet = Packet::new(packet, stream.index, time)
+					.with_pts(pts)
+					.with_dts(pts)
+					.with_keyframe(keyframe);
+				self.pending.push_back(packet);
+			}
+			pts += duration;
+		}
+		// the granule position wins over summed durations, which is how the
+		// last page trims the stream
+		stream.position = Some(end.unwrap_or(pts));
+		Ok(())
+	}
+
+	fn read_page(&mut self) -> Result<Option<Page>> {
+		loop {
+			if !self.fill(HEADER_SIZE)? {
+				return Ok(None);
+			}
+			if &self.buffer[..4] != CAPTURE_PATTERN {
+				let next = self.buffer[1..].iter().position(|&byte| byte == b'O');
+				self.buffer.drain(..next.map_or(self.buffer.len(), |next| next + 1));
+				continue;
+			}
+
+			if !self.fill(HEADER_SIZE + self.buffer[HEADER_SIZE - 1] as usize)? {
+				return Ok(None);
+			}
+			let size = Page::size(&self.buffer).unwrap_or(HEADER_SIZE);
+			if !self.fill(size)? {
+				return Ok(None);
+			}
+
+			match Page::parse(&self.buffer[..size]) {
+				Ok(page) => {
+					self.buffer.drain(..size);
+					return Ok(Some(page));
+				}
+				// a damaged page or a capture pattern inside packet data
+				Err(_) => {
+					self.buffer.drain(..1);
+				}
+			}
+		}
+	}
+
+	fn fill(&mut self, size: usize) -> Result<bool> {
+		let mut chunk = [0u8; READ_SIZE];
+		while self.buffer.len() < size {
+			if self.eof {
+				return Ok(false);
+			}
+			let read = self.reader.read(&mut chunk)?;
+			if read == 0 {
+				self.eof = true;
+			}
+			self.buffer.extend_from_slice(&chunk[..read]);
+		}
+		Ok(true)
+	}
+}
+
+impl<R: MediaRead> Demuxer for OggDemuxer<R> {
+	fn streams(&self) -> &stream::Streams {
+		&self.streams
+	}
+
+	fn read_packet(&mut self) -> Result<Option<Packet>> {
+		loop {
+			if let Some(packet) = self.pending.pop_front() {
+				return Ok(Some(packet));
+			}
+			let Some(page) = self.read_page()? else {
+				return Ok(None);
+			};
+			self.process_page(page)?;
+		}
+	}
+}
+
+/// Scores how likely `data` is the start of an ogg file.
+pub fn probe(data: &[u8]) -> u8 {
+	if data.starts_with(CAPTURE_PATTERN) { SCORE_MAX } else { 0 }
+}
+
+/// Scores how likely `data` is the start of an ogg file whose first
+/// stream is opus.
+pub fn probe_opus(data: &[u8]) -> u8 {
+	// the first page carries only the identification header
+	let Some(&segments) = data.get(26).filter(|_| data.starts_with(CAPTURE_PATTERN)) else {
+		return 0;
+	};
+	let payload = 27 + segments as usize;
+	match data.get(payload..payload + 8) {
+		Some(b"OpusHead") => SCORE_MAX,
+		_ => 0,
+	}
+}
@@ -0,0 +1,363 @@
+use crate::codecs;
+use crate::codecs::audio::flac::{FrameHeader, StreamInfo};
+use crate::core::stream::{Stream, StreamKind};
+use crate::core::time::Time;
+use crate::io::BitReader;
+use crate::{error, message::Result};
+
+const VENDOR: &[u8] = b"ffmpreg";
+
+#[derive(Debug, Clone)]
+enum CodecState {
+	Vorbis { blocksizes: [i64; 2], modes: Vec<bool>, previous: Option<i64> },
+	Opus { pre_skip: i64 },
+	Flac,
+	Theora { shift: u32, offset: i64, keyframe: i64 },
+}
+
+/// How a codec is carried in Ogg: which packets are headers, what a granule
+/// position counts and how long each packet lasts.
+#[derive(Debug, Clone)]
+pub struct Mapping {
+	pub codec: &'static str,
+	pub kind: StreamKind,
+	pub time: Time,
+	/// Header packets at the start of the logical stream, the first included.
+	pub header_count: usize,
+	state: CodecState,
+}
+
+impl Mapping {
+	/// Identifies the codec from the first packet of a logical stream.
+	/// Returns `None` for codecs without a mapping.
+	pub fn detect(packet: &[u8]) -> Result<Option<Self>> {
+		if packet.starts_with(b"\x01vorbis") {
+			return Self::vorbis(packet).map(Some);
+		}
+		if packet.starts_with(b"OpusHead") {
+			return Self::opus(packet).map(Some);
+		}
+		if packet.starts_with(b"\x7FFLAC") {
+			return Self::flac(packet).map(Some);
+		}
+		if packet.starts_with(b"\x80theora") {
+			return Self::theora(packet).map(Some);
+		}
+		Ok(None)
+	}
+
+	fn vorbis(packet: &[u8]) -> Result<Self> {
+		if packet.len() < 30 {
+			return Err(error!("vorbis identification header is truncated"));
+		}
+		let sample_rate = u32::from_le_bytes(packet[12..16].try_into().unwrap());
+		if sample_rate == 0 {
+			return Err(error!("vorbis sample rate is zero"));
+		}
+		let blocksizes = [1i64 << (packet[28] & 0x0F), 1i64 << (packet[28] >> 4)];
+		Ok(Self {
+			codec: codecs::audio::VORBIS,
+			kind: StreamKind::Audio,
+			time: Time::new(1, sample_rate),
+			header_count: 3,
+			state: CodecState::Vorbis { blocksizes, modes: Vec::new(), previous: None },
+		})
+	}
+
+	fn opus(packet: &[u8]) -> Result<Self> {
+		if packet.len() < 19 {
+			return Err(error!("opus identification header is truncated"));
+		}
+		// granule positions always count 48 kHz samples, whatever the input rate
+		let pre_skip = u16::from_le_bytes([packet[10], packet[11]]) as i64;
+		Ok(Self {
+			codec: codecs::audio::OPUS,
+			kind: StreamKind::Audio,
+			time: Time::new(1, 48000),
+			header_count: 2,
+			state: CodecState::Opus { pre_skip },
+		})
+	}
+
+	fn flac(packet: &[u8]) -> Result<Self> {
+		if packet.len() < 51 || &packet[9..13] != b"fLaC" {
+			return Err(error!("flac mapping header is truncated"));
+		}
+		if packet[5] != 1 {
+			return Err(error!("unsupported flac ogg mapping version {}.{}", packet[5], packet[6]));
+		}
+		let info = StreamInfo::parse(&packet[17..])?;
+		// zero means the number of metadata packets is unknown; only the
+		// mandatory vorbis comment can be relied on
+		let headers = u16::from_be_bytes([packet[7], packet[8]]).max(1) as usize;
+		Ok(Self {
+			codec: codecs::audio::FLAC,
+			kind: StreamKind::Audio,
+			time: Time::new(1, info.sample_rate),
+			header_count: 1 + headers,
+			state: CodecState::Flac,
+		})
+	}
+
+	fn theora(packet: &[u8]) -> Result<Self> {
+		if packet.len() < 42 {
+			return Err(error!("theora identification header is truncated"));
+		}
+		let frame_rate = u32::from_be_bytes(packet[22..26].try_into().unwrap());
+		let frame_duration = u32::from_be_bytes(packet[26..30].try_into().unwrap());
+		if frame_rate == 0 || frame_duration == 0 {
+			return Err(error!("theora frame rate is zero"));
+		}
+		let shift = (((packet[40] & 0x03) << 3) | (packet[41] >> 5)) as u32;
+		// since 3.2.1 granule positions count frames from one
+		let version = u32::from_be_bytes([0, packet[7], packet[8], packet[9]]);
+		let offset = if version >= 0x030201 { 1 } else { 0 };
+		Ok(Self {
+			codec: codecs::video::THEORA,
+			kind: StreamKind::Video,
+			time: Time::new(frame_duration, frame_rate),
+			header_count: 3,
+			state: CodecState::Theora { shift, offset, keyframe: 0 },
+		})
+	}
+
+	/// Builds the mapping and header packets for a stream about to be muxed,
+	/// from the codec private data demuxers attach to it.
+	pub fn from_stream(stream: &Stream) -> Result<(Self, Vec<Vec<u8>>)> {
+		let private = &stream.codec_private;
+		let headers = match stream.codec.as_str() {
+			codecs::audio::OPUS => vec![private.clone(), opus_tags()],
+			codecs::audio::VORBIS | codecs::video::THEORA => xiph_unlace(private)?,
+			codecs::audio::FLAC => flac_headers(private)?,
+			codec => return Err(error!("codec '{}' cannot be stored in ogg", codec)),
+		};
+
+		let mut mapping = Self::detect(&headers[0])?
+			.ok_or_else(|| error!("stream {} has no valid {} header", stream.index, stream.codec))?;
+		if headers.len() != mapping.header_count {
+			return Err(error!("{} needs {} ogg headers", stream.codec, mapping.header_count));
+		}
+		for (index, header) in headers.iter().enumerate().skip(1) {
+			mapping.add_header(index, header)?;
+		}
+		Ok((mapping, headers))
+	}
+
+	/// Feeds a header packet after the first one.
+	pub fn add_header(&mut self, index: usize, packet: &[u8]) -> Result<()> {
+		if let CodecState::Vorbis { modes, .. } = &mut self.state
+			&& index == 2
+		{
+			*modes = vorbis_modes(packet)?;
+		}
+		Ok(())
+	}
+
+	/// The codec private data for the demuxed stream, in the layout other
+	/// containers use for the same codec.
+	pub fn codec_private(&self, headers: &[Vec<u8>]) -> Vec<u8> {
+		match self.state {
+			CodecState::Vorbis { .. } | CodecState::Theora { .. } => xiph_lace(headers),
+			CodecState::Opus { .. } => headers[0].clone(),
+			CodecState::Flac => headers[0][17..51].to_vec(),
+		}
+	}
+
+	/// Length of a data packet in stream time units. Must see every data
+	/// packet in order, since Vorbis durations depend on the previous one.
+	pub fn packet_duration(&mut self, packet: &[u8]) -> Option<i64> {
+		match &mut self.state {
+			CodecState::Vorbis { blocksizes, modes, previous } => {
+				let first = *packet.first()?;
+				if first & 1 != 0 || modes.is_empty() {
+					return None;
+				}
+				let bits = usize::BITS - (modes.len() - 1).leading_zeros();
+				let mode = (first >> 1) as usize & ((1 << bits) - 1);
+				let blocksize = blocksizes[*modes.get(mode)? as usize];
+				let duration = previous.map_or(0, |previous| previous / 4 + blocksize / 4);
+				*previous = Some(blocksize);
+				Some(duration)
+			}
+			CodecState::Opus { .. } => opus_packet_duration(packet),
+			CodecState::Flac => FrameHeader::parse(packet).ok().map(|header| header.block_size as i64),
+			CodecState::Theora { .. } => Some(1),
+		}
+	}
+
+	/// Converts the granule position of a page into the stream time right
+	/// after the last packet completed on it.
+	pub fn granule_end(&self, granule: i64) -> i64 {
+		match self.state {
+			CodecState::Opus { pre_skip } => granule - pre_skip,
+			CodecState::Theora { shift, offset, .. } => {
+				let keyframe = granule >> shift;
+				keyframe + (granule - (keyframe << shift)) - offset + 1
+			}
+			_ => granule,
+		}
+	}
+
+	/// Granule position for a page whose last completed packet starts at
+	/// `pts`. Must see every data packet in order.
+	pub fn packet_granule(&mut self, pts: i64, packet: &[u8], keyframe: bool) -> i64 {
+		let duration = self.packet_duration(packet).unwrap_or(0);
+		match &mut self.state {
+			CodecState::Opus { pre_skip } => pts + duration + *pre_skip,
+			CodecState::Theora { shift, offset, keyframe: last } => {
+				let frame = pts + *offset;
+				if keyframe {
+					*last = frame;
+				}
+				(*last << *shift) | (frame - *last)
+			}
+			_ => pts + duration,
+		}
+	}
+
+	pub fn is_keyframe(&self, packet: &[u8]) -> bool {
+		match self.state {
+			CodecState::Theora { .. } => packet.first().is_some_and(|byte| byte & 0x40 == 0),
+			_ => true,
+		}
+	}
+}
+
+/// Mode block flags from the end of a Vorbis setup header. The mode count
+/// sits before the modes, so they are found by walking back from the framing
+/// bit and checking which count matches.
+fn vorbis_modes(packet: &[u8]) -> Result<Vec<bool>> {
+	// reading the reversed bytes msb first walks the lsb first bitstream
+	// backwards
+	let reversed: Vec<u8> = packet.iter().rev().copied().collect();
+	let mut reader = BitReader::new(&reversed);
+	fn skip_to_framing(reader: &mut BitReader) -> Result<()> {
+		for _ in 0..8 {
+			if reader.read_bit()? {
+				return Ok(());
+			}
+		}
+		Err(error!("vorbis setup header has no framing bit"))
+	}
+
+	skip_to_framing(&mut reader)?;
+	let mut count = 0;
+	let mut modes = 0;
+	while reader.bits_left() >= 97 {
+		if reader.read(8)? > 63 || reader.read(16)? != 0 || reader.read(16)? != 0 {
+			break;
+		}
+		reader.skip(1)?;
+		count += 1;
+		if count > 64 {
+			break;
+		}
+		let position = reader.position();
+		if reader.read(6)? as usize + 1 == count {
+			modes = count;
+		}
+		reader.set_position(position);
+	}
+	if modes == 0 {
+		return Err(error!("vorbis setup header has no modes"));
+	}
+
+	let mut reader = BitReader::new(&reversed);
+	skip_to_framing(&mut reader)?;
+	let mut flags = vec![false; modes];
+	for flag in flags.iter_mut().rev() {
+		reader.skip(40)?;
+		*flag = reader.read_bit()?;
+	}
+	Ok(flags)
+}
+
+/// Samples at 48 kHz in an Opus packet, from its TOC byte.
+fn opus_packet_duration(packet: &[u8]) -> Option<i64> {
+	let toc = *packet.first()?;
+	let config = toc >> 3;
+	let frame_size = match config {
+		0..12 => [480, 960, 1920, 2880][config as usize & 3],
+		12..16 => [480, 960][config as usize & 1],
+		_ => [120, 240, 480, 960][config as usize & 3],
+	};
+	let frames = match toc & 3 {
+		0 => 1,
+		1 | 2 => 2,
+		_ => (*packet.get(1)? & 0x3F) as i64,
+	};
+	Some(frames * frame_size)
+}
+
+fn opus_tags() -> Vec<u8> {
+	let mut tags = b"OpusTags".to_vec();
+	tags.extend_from_slice(&(VENDOR.len() as u32).to_le_bytes());
+	tags.extend_from_slice(VENDOR);
+	tags.extend_from_slice(&0u32.to_le_bytes());
+	tags
+}
+
+/// The mapping header wrapping STREAMINFO, then the mandatory vorbis
+/// comment block.
+fn flac_headers(streaminfo: &[u8]) -> Result<Vec<Vec<u8>>> {
+	StreamInfo::parse(streaminfo)?;
+	let mut mapping = b"\x7FFLAC\x01\x00".to_vec();
+	mapping.extend_from_slice(&1u16.to_be_bytes());
+	mapping.extend_from_slice(b"fLaC");
+	mapping.extend_from_slice(&[0, 0, 0, 34]);
+	mapping.extend_from_slice(&streaminfo[..34]);
+
+	let mut comment = Vec::new();
+	comment.extend_from_slice(&(VENDOR.len() as u32).to_le_bytes());
+	comment.extend_from_slice(VENDOR);
+	comment.extend_from_slice(&0u32.to_le_bytes());
+	let size = (comment.len() as u32).to_be_bytes();
+	let mut block = vec![0x84, size[1], size[2], size[3]];
+	block.extend_from_slice(&comment);
+	Ok(vec![mapping, block])
+}
+
+/// Packs header packets as a count minus one, the sizes of all but the last
+/// in 255-runs, then the data.
+pub fn xiph_lace(packets: &[Vec<u8>]) -> Vec<u8> {
+	let mut out = vec![packets.len().saturating_sub(1) as u8];
+	for packet in packets.iter().take(packets.len().saturating_sub(1)) {
+		out.extend(std::iter::repeat_n(255, packet.len() / 255));
+		out.push((packet.len() % 255) as u8);
+	}
+	for packet in packets {
+		out.extend_from_slice(packet);
+	}
+	out
+}
+
+pub fn xiph_unlace(data: &[u8]) -> Result<Vec<Vec<u8>>> {
+	let count = *data.first().ok_or_else(|| error!("codec private data is empty"))? as usize + 1;
+	let mut offset = 1;
+	let mut sizes = Vec::with_capacity(count);
+	for _ in 1..count {
+		let mut size = 0;
+		loop {
+			let lacing = *data.get(offset).ok_or_else(|| error!("xiph lacing is truncated"))?;
+			offset += 1;
+			size += lacing as usize;
+			if lacing < 255 {
+				break;
+			}
+		}
+		sizes.push(size);
+	}
+
+	let total: usize = sizes.iter().sum();
+	if offset + total > data.len() {
+		return Err(error!("xiph lacing is truncated"));
+	}
+	sizes.push(data.len() - offset - total);
+
+	let mut packets = Vec::with_capacity(count);
+	for size in sizes {
+		packets.push(data[offset..offset + size].to_vec());
+		offset += size;
+	}
+	Ok(packets)
+}
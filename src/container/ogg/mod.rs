@@ -0,0 +1,10 @@
+pub mod crc;
+pub mod demuxer;
+pub mod mapping;
+pub mod muxer;
+pub mod page;
+
+pub use demuxer::OggDemuxer;
+pub use mapping::Mapping;
+pub use muxer::OggMuxer;
+pub use page::Page;
@@ -0,0 +1,173 @@
+use super::mapping::Mapping;
+use super::page::{FLAG_CONTINUED, FLAG_FIRST, FLAG_LAST, MAX_SEGMENTS, NO_GRANULE, Page};
+use crate::core::Muxer;
+use crate::core::packet::Packet;
+use crate::core::stream::{self, Stream};
+use crate::io::{MediaWrite, WritePrimitives};
+use crate::{error, message::Result};
+
+struct OutputStream {
+	serial: u32,
+	mapping: Mapping,
+	headers: Vec<Vec<u8>>,
+	page: Page,
+	granule: i64,
+	/// Time of the first packet on the page, in the time of the mapping.
+	page_start: Option<i64>,
+}
+
+pub struct OggMuxer<W: MediaWrite> {
+	writer: W,
+	streams: stream::Streams,
+	outputs: Vec<OutputStream>,
+	header_written: bool,
+}
+
+impl<W: MediaWrite> OggMuxer<W> {
+	// pages are closed once their body reaches this size or their packets
+	// span this many seconds
+	const PAGE_SIZE: usize = 4096;
+	const PAGE_DURATION: f64 = 1.0;
+	const FIRST_SERIAL: u32 = 0x5F3A_11C1;
+
+	pub fn new(writer: W, streams: stream::Streams) -> Result<Self> {
+		let mut outputs = Vec::new();
+		let mut muxed = stream::Streams::new_empty();
+		for stream in streams.all() {
+			let (mapping, headers) = Mapping::from_stream(stream)?;
+			let serial = Self::FIRST_SERIAL.wrapping_add(stream.index as u32);
+			let id = outputs.len() as u32;
+			let codec = stream.codec.clone();
+			let output = Stream::new(id, id as usize, stream.kind, codec, mapping.time)
+				.with_codec_private(stream.codec_private.clone());
+			muxed.add(output);
+			outputs.push(OutputStream {
+				serial,
+				mapping,
+				headers,
+				page: Page::new(serial, 0),
+				granule: 0,
+				page_start: None,
+			});
+		}
+		if outputs.is_empty() {
+			return Err(error!("ogg output needs at least one stream"));
+		}
+
+		Ok(Self { writer, streams: muxed, outputs, header_written: false })
+	}
+
+	fn write_header(&mut self) -> Result<()> {
+		for index in 0..self.outputs.len() {
+			let output = &mut self.outputs[index];
+			output.page.flags = FLAG_FIRST;
+			let header = output.headers[0].clone();
+			self.push_packet(index, &header, 0)?;
+			self.flush_page(index)?;
+		}
+
+		// data packets have to start on a fresh page
+		for index in 0..self.outputs.len() {
+			for header in self.outputs[index].headers.clone().iter().skip(1) {
+				self.push_packet(index, header, 0)?;
+			}
+			if !self.outputs[index].page.is_empty() {
+				self.flush_page(index)?;
+			}
+		}
+		self.header_written = true;
+		Ok(())
+	}
+
+	pub fn write_packet(&mut self, packet: Packet) -> Result<()> {
+		if !self.header_written {
+			self.write_header()?;
+		}
+
+		let index = packet.stream_id as usize;
+		let output = self.outputs.get_mut(index).ok_or_else(|| error!("no ogg stream {}", index))?;
+		let pts = output.mapping.time.scale_pts(packet.pts, packet.time);
+		let granule = output.mapping.packet_granule(pts, &packet.data, packet.keyframe);
+		let start = *output.page_start.get_or_insert(pts);
+		self.push_packet(index, &packet.data, granule)?;
+
+		let output = &self.outputs[index];
+		let full = output.page.data.len() >= Self::PAGE_SIZE;
+		let long = pts - start >= output.mapping.time.from_seconds(Self::PAGE_DURATION);
+		if full || long {
+			self.flush_pages(index)?;
+		}
+		Ok(())
+	}
+
+	/// Writes the page of stream `index` along with the pages the other
+	/// streams have started, so no stream lags behind the others in the
+	/// file.
+	fn flush_pages(&mut self, index: usize) -> Result<()> {
+		self.flush_page(index)?;
+		for other in 0..self.outputs.len() {
+			if other != index && !self.outputs[other].page.is_empty() {
+				self.flush_page(other)?;
+			}
+		}
+		Ok(())
+	}
+
+	fn push_packet(&mut self, index: usize, data: &[u8], granule: i64) -> Result<()> {
+		if self.outputs[index].page.lacing.len() == MAX_SEGMENTS {
+			self.flush_page(index)?;
+		}
+
+		let mut remaining = data;
+		loop {
+			let output = &mut self.outputs[index];
+			let (taken, complete) = output.page.push(remaining);
+			remaining = &remaining[taken..];
+			if complete {
+				output.page.granule = granule;
+				output.granule = granule;
+				return Ok(());
+			}
+			self.flush_page(index)?;
+			self.outputs[index].page.flags |= FLAG_CONTINUED;
+		}
+	}
+
+	fn flush_page(&mut self, index: usize) -> Result<()> {
+		let output = &mut self.outputs[index];
+		output.page_start = None;
+		let next = Page::new(output.serial, output.page.sequence.wrapping_add(1));
+		let page = std::mem::replace(&mut output.page, next);
+		self.writer.write_all(&page.to_bytes())
+	}
+
+	pub fn finalize(&mut self) -> Result<()> {
+		if !self.header_written {
+			self.write_header()?;
+		}
+
+		for index in 0..self.outputs.len() {
+			let output = &mut self.outputs[index];
+			output.page.flags |= FLAG_LAST;
+			if output.page.granule == NO_GRANULE {
+				output.page.granule = output.granule;
+			}
+			self.flush_page(index)?;
+		}
+		self.writer.flush()
+	}
+}
+
+impl<W: MediaWrite> Muxer for OggMuxer<W> {
+	fn streams(&self) -> &stream::Streams {
+		&self.streams
+	}
+
+	fn write(&mut self, packet: Packet) -> Result<()> {
+		self.write_packet(packet)
+	}
+
+	fn finalize(&mut self) -> Result<()> {
+		self.finalize()
+	}
+}
@@ -0,0 +1,136 @@
+use super::crc::crc32;
+use crate::{error, message::Result};
+
+pub const CAPTURE_PATTERN: &[u8; 4] = b"OggS";
+pub const HEADER_SIZE: usize = 27;
+pub const MAX_SEGMENTS: usize = 255;
+
+pub const FLAG_CONTINUED: u8 = 0x01;
+pub const FLAG_FIRST: u8 = 0x02;
+pub const FLAG_LAST: u8 = 0x04;
+
+/// Granule position of a page on which no packet ends.
+pub const NO_GRANULE: i64 = -1;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Page {
+	pub flags: u8,
+	pub granule: i64,
+	pub serial: u32,
+	pub sequence: u32,
+	pub lacing: Vec<u8>,
+	pub data: Vec<u8>,
+}
+
+impl Page {
+	pub fn new(serial: u32, sequence: u32) -> Self {
+		Self { flags: 0, granule: NO_GRANULE, serial, sequence, lacing: Vec::new(), data: Vec::new() }
+	}
+
+	/// Total size of the page whose header and segment table start `data`,
+	/// or `None` while fewer bytes than that are available.
+	pub fn size(data: &[u8]) -> Option<usize> {
+		let segments = *data.get(HEADER_SIZE - 1)? as usize;
+		let lacing = data.get(HEADER_SIZE..HEADER_SIZE + segments)?;
+		Some(HEADER_SIZE + segments + lacing.iter().map(|&l| l as usize).sum::<usize>())
+	}
+
+	pub fn parse(data: &[u8]) -> Result<Self> {
+		if data.len() < HEADER_SIZE || &data[..4] != CAPTURE_PATTERN {
+			return Err(error!("missing ogg capture pattern"));
+		}
+		if data[4] != 0 {
+			return Err(error!("unsupported ogg version {}", data[4]));
+		}
+		let size = Self::size(data).ok_or_else(|| error!("ogg page is truncated"))?;
+		if data.len() < size {
+			return Err(error!("ogg page is truncated"));
+		}
+
+		let stored = u32::from_le_bytes([data[22], data[23], data[24], data[25]]);
+		let mut checked = data[..size].to_vec();
+		checked[22..26].fill(0);
+		if crc32(&checked) != stored {
+			return Err(error!("ogg page checksum mismatch"));
+		}
+
+		let segments = data[26] as usize;
+		Ok(Self {
+			flags: data[5],
+			granule: i64::from_le_bytes(data[6..14].try_into().unwrap()),
+			serial: u32::from_le_bytes(data[14..18].try_into().unwrap()),
+			sequence: u32::from_le_bytes(data[18..22].try_into().unwrap()),
+			lacing: data[HEADER_SIZE..HEADER_SIZE + segments].to_vec(),
+			data: data[HEADER_SIZE + segments..size].to_vec(),
+		})
+	}
+
+	pub fn to_bytes(&self) -> Vec<u8> {
+		let mut out = Vec::with_capacity(HEADER_SIZE + self.lacing.len() + self.data.len());
+		out.extend_from_slice(CAPTURE_PATTERN);
+		out.push(0);
+		out.push(self.flags);
+		out.extend_from_slice(&self.granule.to_le_bytes());
+		out.extend_from_slice(&self.serial.to_le_bytes());
+		out.extend_from_slice(&self.sequence.to_le_bytes());
+		out.extend_from_slice(&[0; 4]);
+		out.push(self.lacing.len() as u8);
+		out.extend_from_slice(&self.lacing);
+		out.extend_from_slice(&self.data);
+
+		let crc = crc32(&out);
+		out[22..26].copy_from_slice(&crc.to_le_bytes());
+		out
+	}
+
+	pub fn is_continued(&self) -> bool {
+		self.flags & FLAG_CONTINUED != 0
+	}
+
+	pub fn is_first(&self) -> bool {
+		self.flags & FLAG_FIRST != 0
+	}
+
+	pub fn is_last(&self) -> bool {
+		self.flags & FLAG_LAST != 0
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.lacing.is_empty()
+	}
+
+	/// Splits the body into packet pieces, each paired with whether the
+	/// packet ends on this page.
+	pub fn packets(&self) -> Vec<(&[u8], bool)> {
+		let mut packets = Vec::new();
+		let (mut start, mut end) = (0, 0);
+		for &lacing in &self.lacing {
+			end += lacing as usize;
+			if lacing < 255 {
+				packets.push((&self.data[start..end], true));
+				start = end;
+			}
+		}
+		if self.lacing.last() == Some(&255) {
+			packets.push((&self.data[start..end], false));
+		}
+		packets
+	}
+
+	/// Appends as much of `data` as the segment table allows, returning the
+	/// number of bytes taken and whether the packet ended on this page.
+	pub fn push(&mut self, data: &[u8]) -> (usize, bool) {
+		let free = MAX_SEGMENTS - self.lacing.len();
+		if data.len() / 255 < free {
+			self.lacing.extend(std::iter::repeat_n(255, data.len() / 255));
+			self.lacing.push((data.len() % 255) as u8);
+			self.data.extend_from_slice(data);
+			return (data.len(), true);
+		}
+
+		let taken = free * 255;
+		self.lacing.extend(std::iter::repeat_n(255, free));
+		self.data.extend_from_slice(&data[..taken]);
+		(taken, false)
+	}
+}
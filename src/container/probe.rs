@@ -0,0 +1,80 @@
+/// Bytes from the start of an input the probes look at.
+pub const PROBE_SIZE: usize = 65536;
+/// Score of a probe that is sure the input is its container.
+pub const SCORE_MAX: u8 = 100;
+
+/// Bytes of an ID3v2 tag header.
+pub const ID3V2_HEADER_SIZE: usize = 10;
+// how far past ID3v2 tags the first frame is looked for
+const SYNC_WINDOW: usize = 4096;
+
+/// A container that can be told apart by the first bytes of its input.
+pub struct Probe {
+	pub name: &'static str,
+	/// How likely the bytes are this container, from 0 to `SCORE_MAX`.
+	pub score: fn(&[u8]) -> u8,
+}
+
+/// The container of an input starting with `data`, among `probes` in the
+/// order ties go to. The `extension` of its name settles ties, and names
+/// the container when no probe knows the data, as for raw PCM.
+pub fn detect<'a>(probes: &[Probe], data: &[u8], extension: Option<&'a str>) -> Option<&'a str> {
+	let mut best: Option<(&'static str, u8)> = None;
+	for probe in probes {
+		let score = (probe.score)(data);
+		let ahead = best.is_none_or(|(_, best)| score > best);
+		let named = best.is_some_and(|(_, best)| score == best) && extension == Some(probe.name);
+		if score > 0 && (ahead || named) {
+			best = Some((probe.name, score));
+		}
+	}
+	match best {
+		Some((name, _)) => Some(name),
+		None => extension,
+	}
+}
+
+/// The bytes after the ID3v2 tags at the start of `data`, or `None` when
+/// the tags run past its end.
+pub fn skip_id3v2(mut data: &[u8]) -> Option<&[u8]> {
+	while let Some(size) = id3v2_size(data) {
+		data = data.get(size..)?;
+	}
+	Some(data)
+}
+
+/// Size of the ID3v2 tag whose header starts `data`, header and footer
+/// included, or `None` when `data` starts with no tag header.
+pub fn id3v2_size(data: &[u8]) -> Option<usize> {
+	let header = data.get(..ID3V2_HEADER_SIZE).filter(|header| &header[..3] == b"ID3")?;
+	// the size is syncsafe, seven bits to a byte
+	let size = header[6..10].iter().fold(0usize, |acc, &b| (acc << 7) | (b & 0x7F) as usize);
+	let footer = if header[5] & 0x10 != 0 { 10 } else { 0 };
+	Some(ID3V2_HEADER_SIZE + size + footer)
+}
+
+/// Scores a stream of self-delimiting frames, where `frame_size` gives the
+/// size of a frame starting at the slice or `None` when none does. Behind
+/// ID3v2 tags the first frame may come after some junk.
+pub fn score_frames(data: &[u8], frame_size: impl Fn(&[u8]) -> Option<usize>) -> u8 {
+	let Some(frames) = skip_id3v2(data) else {
+		return 0;
+	};
+	let window = if frames.len() < data.len() { frames.len().min(SYNC_WINDOW) } else { 1 };
+	let mut scores = (0..window).map(|offset| score_frame_pair(&frames[offset..], &frame_size));
+	scores.find(|&score| score > 0).unwrap_or(0)
+}
+
+/// A second frame right after the first makes it certain.
+fn score_frame_pair(data: &[u8], frame_size: &impl Fn(&[u8]) -> Option<usize>) -> u8 {
+	let Some(size) = frame_size(data) else {
+		return 0;
+	};
+	match data.get(size..) {
+		Some(next) if frame_size(next).is_some() => SCORE_MAX,
+		// the first frame fills the probe data
+		Some(next) if next.len() < 16 => SCORE_MAX / 2,
+		None => SCORE_MAX / 2,
+		Some(_) => 0,
+	}
+}
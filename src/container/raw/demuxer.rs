@@ -43,7 +43,8 @@ impl<R: MediaRead> RawPcmDemuxer<R> {
 		}
 
 		let time = time::Time::new(1, self.format.sample_rate);
-		let packet = Packet::new(data, 0, time).with_pts(self.sample_position as i64);
+		let position = self.sample_position as i64;
+		let packet = Packet::new(data, 0, time).with_pts(position).with_dts(position);
 
 		self.sample_position += (bytes_read / self.format.bytes_per_frame()) as u64;
 		self.packet_count += 1;
@@ -1,4 +1,5 @@
 use crate::codecs;
+use crate::codecs::audio::g711::G711Law;
 use crate::core::frame::{AudioFormat, Channels};
 
 #[derive(Debug, Clone, Copy)]
@@ -6,12 +7,14 @@ pub struct RawPcmFormat {
 	pub channels: Channels,
 	pub sample_rate: u32,
 	pub bit_depth: u16,
+	/// Companding law of 8 bit G.711 samples.
+	pub g711: Option<G711Law>,
 }
 
 impl Default for RawPcmFormat {
 	fn default() -> Self {
 		// default is pcm_16, stereo, 44.1kHz
-		Self { channels: Channels::Stereo, sample_rate: 44100, bit_depth: 16 }
+		Self { channels: Channels::Stereo, sample_rate: 44100, bit_depth: 16, g711: None }
 	}
 }
 
@@ -21,6 +24,12 @@ impl RawPcmFormat {
 			codecs::audio::PCM_S16LE => Ok(Self::default()),
 			codecs::audio::PCM_S24LE => Ok(Self { bit_depth: 24, ..Self::default() }),
 			codecs::audio::PCM_F32LE => Ok(Self { bit_depth: 32, ..Self::default() }),
+			codecs::audio::PCM_MULAW => {
+				Ok(Self { bit_depth: 8, g711: Some(G711Law::MuLaw), ..Self::default() })
+			}
+			codecs::audio::PCM_ALAW => {
+				Ok(Self { bit_depth: 8, g711: Some(G711Law::ALaw), ..Self::default() })
+			}
 			_ => Err(format!("raw codec '{}' is not supported", codec)),
 		}
 	}
@@ -45,6 +54,9 @@ impl RawPcmFormat {
 	}
 
 	pub fn audio_format(&self) -> AudioFormat {
+		if let Some(law) = self.g711 {
+			return law.audio_format();
+		}
 		match self.bit_depth {
 			16 => AudioFormat::PCM16,
 			24 => AudioFormat::PCM24,
@@ -54,6 +66,9 @@ impl RawPcmFormat {
 	}
 
 	pub fn to_codec_string(&self) -> &'static str {
+		if let Some(law) = self.g711 {
+			return law.codec();
+		}
 		match self.bit_depth {
 			16 => codecs::audio::PCM_S16LE,
 			24 => codecs::audio::PCM_S24LE,
@@ -64,9 +79,11 @@ impl RawPcmFormat {
 
 	pub fn apply_codec(&mut self, codec: &str) -> Result<(), String> {
 		match codec {
-			codecs::audio::PCM_S16LE => self.bit_depth = 16,
-			codecs::audio::PCM_S24LE => self.bit_depth = 24,
-			codecs::audio::PCM_F32LE => self.bit_depth = 32,
+			codecs::audio::PCM_S16LE => (self.bit_depth, self.g711) = (16, None),
+			codecs::audio::PCM_S24LE => (self.bit_depth, self.g711) = (24, None),
+			codecs::audio::PCM_F32LE => (self.bit_depth, self.g711) = (32, None),
+			codecs::audio::PCM_MULAW => (self.bit_depth, self.g711) = (8, Some(G711Law::MuLaw)),
+			codecs::audio::PCM_ALAW => (self.bit_depth, self.g711) = (8, Some(G711Law::ALaw)),
 			_ => return Err(format!("raw codec '{}' is not supported", codec)),
 		}
 		Ok(())
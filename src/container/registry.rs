@@ -0,0 +1,248 @@
+use crate::container::probe::{self, Probe};
+use crate::container::{self, aac, aiff, au, flac, mkv, mp3, mp4, ogg, voc, wav};
+use crate::{codecs, error, message::Result};
+
+/// The codecs a container holds, by kind of stream.
+#[derive(Debug)]
+pub struct Codecs {
+	pub audio: &'static [&'static str],
+	pub video: &'static [&'static str],
+	pub subtitle: &'static [&'static str],
+}
+
+impl Codecs {
+	const fn audio(audio: &'static [&'static str]) -> Self {
+		Self { audio, video: &[], subtitle: &[] }
+	}
+}
+
+/// What the library knows about a container: how to recognize it and what
+/// it holds.
+#[derive(Debug)]
+pub struct Container {
+	pub name: &'static str,
+	/// How likely the first bytes of an input are this container, from 0
+	/// to `SCORE_MAX`; `None` when only its name tells it.
+	pub probe: Option<fn(&[u8]) -> u8>,
+	pub codecs: Codecs,
+}
+
+impl Container {
+	pub fn assert_audio(&self, codec: &str) -> Result<()> {
+		self.assert_codec(self.codecs.audio, codec)
+	}
+
+	pub fn assert_video(&self, codec: &str) -> Result<()> {
+		self.assert_codec(self.codecs.video, codec)
+	}
+
+	pub fn assert_subtitle(&self, format: &str) -> Result<()> {
+		if self.codecs.subtitle.contains(&format) {
+			return Ok(());
+		}
+		Err(error!("format '{}' not supported in '{}'", format, self.name))
+	}
+
+	fn assert_codec(&self, codecs: &[&str], codec: &str) -> Result<()> {
+		if codecs.contains(&codec) {
+			return Ok(());
+		}
+		Err(error!("codec '{}' not supported in '{}'", codec, self.name))
+	}
+}
+
+const WAV_CODECS: Codecs = Codecs::audio(&[
+	codecs::audio::PCM_U8,
+	codecs::audio::PCM_S16LE,
+	codecs::audio::PCM_S24LE,
+	codecs::audio::PCM_S32LE,
+	codecs::audio::PCM_F32LE,
+	codecs::audio::PCM_F64LE,
+	codecs::audio::PCM_MULAW,
+	codecs::audio::PCM_ALAW,
+	codecs::audio::ADPCM_IMA_WAV,
+	codecs::audio::ADPCM_MS,
+]);
+
+const AIFF_CODECS: Codecs = Codecs::audio(&[
+	codecs::audio::PCM_S8,
+	codecs::audio::PCM_U8,
+	codecs::audio::PCM_S16BE,
+	codecs::audio::PCM_S24BE,
+	codecs::audio::PCM_S32BE,
+	codecs::audio::PCM_F32BE,
+	codecs::audio::PCM_F64BE,
+	codecs::audio::PCM_S16LE,
+	codecs::audio::PCM_S24LE,
+	codecs::audio::PCM_S32LE,
+	codecs::audio::PCM_MULAW,
+	codecs::audio::PCM_ALAW,
+]);
+
+const AU_CODECS: Codecs = Codecs::audio(&[
+	codecs::audio::PCM_S8,
+	codecs::audio::PCM_S16BE,
+	codecs::audio::PCM_S24BE,
+	codecs::audio::PCM_S32BE,
+	codecs::audio::PCM_F32BE,
+	codecs::audio::PCM_F64BE,
+	codecs::audio::PCM_MULAW,
+	codecs::audio::PCM_ALAW,
+]);
+
+const RAW_CODECS: Codecs = Codecs::audio(&[
+	codecs::audio::PCM_S16LE,
+	codecs::audio::PCM_S24LE,
+	codecs::audio::PCM_F32LE,
+	codecs::audio::PCM_MULAW,
+	codecs::audio::PCM_ALAW,
+]);
+
+const MP4_CODECS: Codecs = Codecs {
+	audio: &[codecs::audio::AAC, codecs::audio::MP3],
+	video: &[codecs::video::H264, codecs::video::H265],
+	subtitle: &[codecs::subtitle::MOV_TEXT],
+};
+
+// containers sharing a probe come in the order ties go to
+pub const CONTAINERS: &[Container] = &[
+	Container { name: container::WAV, probe: Some(wav::demuxer::probe), codecs: WAV_CODECS },
+	Container { name: container::AIFF, probe: Some(aiff::demuxer::probe), codecs: AIFF_CODECS },
+	Container { name: container::AIF, probe: Some(aiff::demuxer::probe), codecs: AIFF_CODECS },
+	Container { name: container::AIFC, probe: Some(aiff::demuxer::probe), codecs: AIFF_CODECS },
+	Container { name: container::AU, probe: Some(au::demuxer::probe), codecs: AU_CODECS },
+	Container { name: container::SND, probe: Some(au::demuxer::probe), codecs: AU_CODECS },
+	Container {
+		name: container::VOC,
+		probe: Some(voc::demuxer::probe),
+		codecs: Codecs::audio(&[
+			codecs::audio::PCM_U8,
+			codecs::audio::PCM_S16LE,
+			codecs::audio::PCM_MULAW,
+			codecs::audio::PCM_ALAW,
+		]),
+	},
+	Container {
+		name: container::FLAC,
+		probe: Some(flac::demuxer::probe),
+		codecs: Codecs::audio(&[codecs::audio::FLAC]),
+	},
+	Container {
+		name: container::OGG,
+		probe: Some(ogg::demuxer::probe),
+		codecs: Codecs::audio(&[codecs::audio::VORBIS, codecs::audio::OPUS, codecs::audio::FLAC]),
+	},
+	Container {
+		name: container::OPUS,
+		probe: Some(ogg::demuxer::probe_opus),
+		codecs: Codecs::audio(&[codecs::audio::OPUS]),
+	},
+	Container {
+		name: container::MKV,
+		probe: Some(mkv::demuxer::probe),
+		codecs: Codecs {
+			audio: &[
+				codecs::audio::AAC,
+				codecs::audio::VORBIS,
+				codecs::audio::OPUS,
+				codecs::audio::MP3,
+				codecs::audio::FLAC,
+				codecs::audio::PCM_S16LE,
+				codecs::audio::PCM_S24LE,
+				codecs::audio::PCM_F32LE,
+			],
+			video: &[codecs::video::H264, codecs::video::H265, codecs::video::VP9, codecs::video::AV1],
+			subtitle: &[codecs::subtitle::SRT, codecs::subtitle::ASS, codecs::subtitle::VTT],
+		},
+	},
+	Container {
+		name: container::WEBM,
+		probe: Some(mkv::demuxer::probe_webm),
+		codecs: Codecs {
+			audio: &[codecs::audio::VORBIS, codecs::audio::OPUS],
+			video: &[codecs::video::VP8, codecs::video::VP9, codecs::video::AV1],
+			subtitle: &[codecs::subtitle::VTT],
+		},
+	},
+	Container { name: container::MP4, probe: Some(mp4::demuxer::probe), codecs: MP4_CODECS },
+	Container {
+		name: container::M4A,
+		probe: Some(mp4::demuxer::probe),
+		codecs: Codecs::audio(&[codecs::audio::AAC, codecs::audio::ALAC]),
+	},
+	Container { name: container::MOV, probe: Some(mp4::demuxer::probe), codecs: MP4_CODECS },
+	Container {
+		name: container::MP3,
+		probe: Some(mp3::demuxer::probe),
+		codecs: Codecs::audio(&[codecs::audio::MP3]),
+	},
+	Container {
+		name: container::AAC,
+		probe: Some(aac::demuxer::probe),
+		codecs: Codecs::audio(&[codecs::audio::AAC]),
+	},
+	Container { name: container::RAW, probe: None, codecs: RAW_CODECS },
+	Container { name: container::PCM, probe: None, codecs: RAW_CODECS },
+	Container { name: container::ALAC, probe: None, codecs: Codecs::audio(&[codecs::audio::ALAC]) },
+	Container {
+		name: container::AVI,
+		probe: None,
+		codecs: Codecs {
+			audio: &[codecs::audio::MP3, codecs::audio::AAC],
+			video: &[codecs::video::MPEG4, codecs::video::H264],
+			subtitle: &[],
+		},
+	},
+	Container {
+		name: container::OGV,
+		probe: None,
+		codecs: Codecs {
+			audio: &[codecs::audio::VORBIS],
+			video: &[codecs::video::THEORA],
+			subtitle: &[],
+		},
+	},
+	Container {
+		name: container::FLV,
+		probe: None,
+		codecs: Codecs {
+			audio: &[codecs::audio::MP3],
+			video: &[codecs::video::H264, codecs::video::VP6],
+			subtitle: &[],
+		},
+	},
+	Container {
+		name: container::MXF,
+		probe: None,
+		codecs: Codecs {
+			audio: &[codecs::audio::PCM_S16LE, codecs::audio::AAC],
+			video: &[codecs::video::MPEG2, codecs::video::H264, codecs::video::H265],
+			subtitle: &[],
+		},
+	},
+	Container {
+		name: container::TS,
+		probe: None,
+		codecs: Codecs {
+			audio: &[codecs::audio::AAC, codecs::audio::MP2],
+			video: &[codecs::video::H264, codecs::video::H265, codecs::video::MPEG2],
+			subtitle: &[],
+		},
+	},
+];
+
+pub fn find(name: &str) -> Result<&'static Container> {
+	let found = CONTAINERS.iter().find(|container| container.name == name);
+	found.ok_or_else(|| error!("'{}' is not supported", name))
+}
+
+/// The container of an input starting with `data`, from its content first
+/// and the `extension` of its name after.
+pub fn detect(data: &[u8], extension: Option<&str>) -> Option<&'static Container> {
+	let probes: Vec<Probe> = CONTAINERS
+		.iter()
+		.filter_map(|container| Some(Probe { name: container.name, score: container.probe? }))
+		.collect();
+	let name = probe::detect(&probes, data, extension)?;
+	CONTAINERS.iter().find(|container| container.name == name)
+}
@@ -0,0 +1,323 @@
+use std::collections::VecDeque;
+
+use super::VocFormat;
+use super::muxer::SIGNATURE;
+use crate::container::probe::SCORE_MAX;
+use crate::core::frame::{AudioFormat, Channels};
+use crate::core::packet::Packet;
+use crate::core::{Demuxer, stream, time};
+use crate::io::{MediaRead, MediaSeek, ReadPrimitives, SeekFrom};
+use crate::{error, message::Result};
+
+// the repeat count of a loop that never ends
+const ENDLESS: u16 = 0xFFFF;
+// signature, data offset, version and check
+const HEADER_SIZE: u64 = 26;
+
+type SeekFn<R> = fn(&mut R, SeekFrom) -> Result<u64>;
+
+/// What a block of the file holds, once its header has been read.
+enum Block {
+	/// Audio in this layout follows.
+	Sound(VocFormat),
+	/// More audio in the layout of the block before.
+	Continue,
+	Silence {
+		frames: u64,
+		sample_rate: u32,
+	},
+	/// Where the repeated blocks start, when the input can go back to
+	/// them, and how many more times they play.
+	RepeatStart(Option<u64>, u16),
+	RepeatEnd,
+	End,
+}
+
+/// Reads Creative Voice files. Silence blocks are played out as samples
+/// and repeated blocks are read again, so the packets hold the sound as
+/// it is heard; input that cannot seek plays them once.
+pub struct VocDemuxer<R: MediaRead> {
+	reader: R,
+	seek: Option<SeekFn<R>>,
+	format: VocFormat,
+	streams: stream::Streams,
+	/// Audio bytes left in the current block.
+	block_remaining: u64,
+	/// Frames of silence still to hand out.
+	silence_remaining: u64,
+	/// Layout from an extended block, for the sound block after it.
+	extended: Option<VocFormat>,
+	/// Where the repeated blocks start, and how many more times they play.
+	repeat: Option<(u64, u16)>,
+	/// Blocks before the first sound block, played once its layout is known.
+	pending: VecDeque<Block>,
+	sample_position: u64,
+}
+
+impl<R: MediaRead + MediaSeek> VocDemuxer<R> {
+	pub fn new(reader: R) -> Result<Self> {
+		Self::build(reader, Some(<R as MediaSeek>::seek))
+	}
+}
+
+impl<R: MediaRead> VocDemuxer<R> {
+	const CHUNK_SIZE_LIMIT: usize = 65536;
+
+	/// A demuxer for input that cannot seek, such as a pipe.
+	pub fn new_stream(reader: R) -> Result<Self> {
+		Self::build(reader, None)
+	}
+
+	fn build(mut reader: R, seek: Option<SeekFn<R>>) -> Result<Self> {
+		let mut signature = [0u8; 20];
+		reader.read_exact(&mut signature)?;
+		if &signature != SIGNATURE {
+			return Err(error!("not a creative voice file"));
+		}
+		let data_start = reader.read_u16_le()? as u64;
+		let _version = reader.read_u16_le()?;
+		let _check = reader.read_u16_le()?;
+		if data_start < HEADER_SIZE {
+			return Err(error!("invalid voc data offset {}", data_start));
+		}
+		Self::skip_bytes(&mut reader, data_start - HEADER_SIZE)?;
+
+		let mut demuxer = Self {
+			reader,
+			seek,
+			format: VocFormat::default(),
+			streams: stream::Streams::new_empty(),
+			block_remaining: 0,
+			silence_remaining: 0,
+			extended: None,
+			repeat: None,
+			pending: VecDeque::new(),
+			sample_position: 0,
+		};
+
+		// the stream takes the layout of the first sound block
+		demuxer.format = loop {
+			match demuxer.read_block()? {
+				Block::Sound(format) => break format,
+				Block::Continue => demuxer.skip_block_data()?,
+				Block::End => return Err(error!("voc file has no sound data")),
+				block => demuxer.pending.push_back(block),
+			}
+		};
+
+		let format = demuxer.format;
+		let codec_name = format.to_codec_string().to_string();
+		let time = time::Time::new(1, format.sample_rate);
+		let stream = stream::Stream::new(0, 0, stream::StreamKind::Audio, codec_name, time);
+		demuxer.streams = stream::Streams::new(vec![stream]);
+
+		Ok(demuxer)
+	}
+
+	/// Reads blocks up to the next one that matters to the audio, and
+	/// the header of its sound data.
+	fn read_block(&mut self) -> Result<Block> {
+		loop {
+			let mut kind = [0u8; 1];
+			if self.reader.read(&mut kind)? == 0 || kind[0] == 0 {
+				return Ok(Block::End);
+			}
+			let mut size = [0u8; 4];
+			self.reader.read_exact(&mut size[..3])?;
+			let size = u32::from_le_bytes(size) as u64;
+
+			// the sound data is left for the packets, the rest of any
+			// other block is skipped past what its header holds
+			let (block, header_size) = match kind[0] {
+				1 if size >= 2 => {
+					self.block_remaining = size - 2;
+					return Ok(Block::Sound(self.read_sound_header()?));
+				}
+				2 => {
+					self.block_remaining = size;
+					return Ok(Block::Continue);
+				}
+				9 if size >= 12 => {
+					self.block_remaining = size - 12;
+					return Ok(Block::Sound(self.read_new_sound_header()?));
+				}
+				3 if size >= 3 => {
+					let frames = self.reader.read_u16_le()? as u64 + 1;
+					let sample_rate = Self::divisor_rate(self.reader.read_u8()?)?;
+					(Some(Block::Silence { frames, sample_rate }), 3)
+				}
+				6 if size >= 2 => {
+					let count = self.reader.read_u16_le()?;
+					let start = match self.seek {
+						Some(seek) => Some(seek(&mut self.reader, SeekFrom::Current(0))? + size - 2),
+						None => None,
+					};
+					(Some(Block::RepeatStart(start, count)), 2)
+				}
+				7 => (Some(Block::RepeatEnd), 0),
+				8 if size >= 4 => {
+					self.extended = Some(self.read_extended_header()?);
+					(None, 4)
+				}
+				// markers, text and anything newer
+				_ => (None, 0),
+			};
+			Self::skip_bytes(&mut self.reader, size - header_size)?;
+			if let Some(block) = block {
+				return Ok(block);
+			}
+		}
+	}
+
+	/// The original sound block: a rate divisor and a codec, both
+	/// overridden by an extended block right before it.
+	fn read_sound_header(&mut self) -> Result<VocFormat> {
+		let divisor = self.reader.read_u8()?;
+		let codec = self.reader.read_u8()?;
+		if let Some(extended) = self.extended.take() {
+			return Ok(extended);
+		}
+		Ok(VocFormat {
+			channels: Channels::Mono,
+			sample_rate: Self::divisor_rate(divisor)?,
+			format: Self::codec_format(codec as u16)?,
+		})
+	}
+
+	fn read_extended_header(&mut self) -> Result<VocFormat> {
+		let time_constant = self.reader.read_u16_le()?;
+		let codec = self.reader.read_u8()?;
+		let channels = match self.reader.read_u8()? {
+			0 => 1,
+			1 => 2,
+			mode => return Err(error!("voc channel mode {} is not supported", mode)),
+		};
+		let sample_rate = 256_000_000 / ((65536 - time_constant as u32) * channels);
+		Ok(VocFormat {
+			channels: Channels::from_count(channels as u8),
+			sample_rate,
+			format: Self::codec_format(codec as u16)?,
+		})
+	}
+
+	fn read_new_sound_header(&mut self) -> Result<VocFormat> {
+		let sample_rate = self.reader.read_u32_le()?;
+		let bits = self.reader.read_u8()?;
+		let channels = self.reader.read_u8()?;
+		let codec = self.reader.read_u16_le()?;
+		let _reserved = self.reader.read_u32_le()?;
+		let format = Self::codec_format(codec)?;
+		if channels == 0 || sample_rate == 0 {
+			return Err(error!("invalid voc sound block"));
+		}
+		let format = VocFormat { channels: Channels::from_count(channels), sample_rate, format };
+		if format.bit_depth() != bits {
+			return Err(error!("voc codec {} with {} bit samples is not supported", codec, bits));
+		}
+		Ok(format)
+	}
+
+	fn codec_format(codec: u16) -> Result<AudioFormat> {
+		VocFormat::from_codec_id(codec).ok_or_else(|| error!("voc codec {} is not supported", codec))
+	}
+
+	fn divisor_rate(divisor: u8) -> Result<u32> {
+		if divisor == 0 {
+			return Err(error!("invalid voc sample rate divisor 0"));
+		}
+		Ok(1_000_000 / (256 - divisor as u32))
+	}
+
+	fn skip_block_data(&mut self) -> Result<()> {
+		Self::skip_bytes(&mut self.reader, self.block_remaining)?;
+		self.block_remaining = 0;
+		Ok(())
+	}
+
+	fn skip_bytes(reader: &mut R, size: u64) -> Result<()> {
+		let mut chunk = [0u8; 8192];
+		let mut left = size;
+		while left > 0 {
+			let count = left.min(chunk.len() as u64) as usize;
+			reader.read_exact(&mut chunk[..count])?;
+			left -= count as u64;
+		}
+		Ok(())
+	}
+
+	pub fn read_packet(&mut self) -> Result<Option<Packet>> {
+		let frame_size = self.format.bytes_per_frame() as u64;
+		let max_frames = Self::CHUNK_SIZE_LIMIT as u64 / frame_size;
+
+		loop {
+			if self.silence_remaining > 0 {
+				let frames = self.silence_remaining.min(max_frames);
+				self.silence_remaining -= frames;
+				let data = vec![self.format.silence(); (frames * frame_size) as usize];
+				return Ok(Some(self.packet(data)));
+			}
+
+			// blocks before the first sound block play before its data
+			let pending = self.pending.pop_front();
+			if pending.is_none() && self.block_remaining > 0 {
+				let chunk_size = self.block_remaining.min(max_frames * frame_size) as usize;
+				let mut data = vec![0u8; chunk_size];
+				self.reader.read_exact(&mut data)?;
+				self.block_remaining -= chunk_size as u64;
+				return Ok(Some(self.packet(data)));
+			}
+
+			let block = match pending {
+				Some(block) => block,
+				None => self.read_block()?,
+			};
+			match block {
+				Block::Sound(format) if format != self.format => {
+					return Err(error!("voc files that change format midway are not supported"));
+				}
+				Block::Sound(_) | Block::Continue => {}
+				Block::Silence { frames, sample_rate } => {
+					let rate = self.format.sample_rate as u64;
+					self.silence_remaining = frames * rate / sample_rate as u64;
+				}
+				Block::RepeatStart(start, count) => self.repeat = start.map(|start| (start, count)),
+				// an endless loop plays once
+				Block::RepeatEnd => match (self.repeat, self.seek) {
+					(Some((start, count)), Some(seek)) if count > 0 && count != ENDLESS => {
+						self.repeat = Some((start, count - 1));
+						seek(&mut self.reader, SeekFrom::Start(start))?;
+					}
+					_ => self.repeat = None,
+				},
+				Block::End => return Ok(None),
+			}
+		}
+	}
+
+	fn packet(&mut self, data: Vec<u8>) -> Packet {
+		let frames = data.len() as u64 / self.format.bytes_per_frame() as u64;
+		let time = time::Time::new(1, self.format.sample_rate);
+		let position = self.sample_position as i64;
+		let packet = Packet::new(data, 0, time).with_pts(position).with_dts(position);
+		self.sample_position += frames;
+		packet
+	}
+
+	pub fn format(&self) -> VocFormat {
+		self.format
+	}
+}
+
+impl<R: MediaRead> Demuxer for VocDemuxer<R> {
+	fn streams(&self) -> &stream::Streams {
+		&self.streams
+	}
+	fn read_packet(&mut self) -> Result<Option<Packet>> {
+		self.read_packet()
+	}
+}
+
+/// Scores how likely `data` is the start of a Creative Voice file.
+pub fn probe(data: &[u8]) -> u8 {
+	if data.starts_with(SIGNATURE) { SCORE_MAX } else { 0 }
+}
@@ -0,0 +1,86 @@
+use crate::codecs;
+use crate::codecs::audio::g711::G711Law;
+use crate::codecs::audio::pcm::layout;
+use crate::container::wav::WavFormat;
+use crate::core::frame::{AudioFormat, Channels};
+
+// codec ids of the sound data blocks, with their sample size
+const CODECS: [(u16, u8, AudioFormat); 4] = [
+	(0, 8, AudioFormat::PCM8),
+	(4, 16, AudioFormat::PCM16),
+	(6, 8, AudioFormat::ALAW),
+	(7, 8, AudioFormat::MULAW),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VocFormat {
+	pub channels: Channels,
+	pub sample_rate: u32,
+	pub format: AudioFormat,
+}
+
+impl Default for VocFormat {
+	fn default() -> Self {
+		Self { channels: Channels::Mono, sample_rate: 22050, format: AudioFormat::PCM8 }
+	}
+}
+
+impl VocFormat {
+	pub fn from_codec_id(codec: u16) -> Option<AudioFormat> {
+		CODECS.iter().find(|(id, ..)| *id == codec).map(|(.., format)| *format)
+	}
+
+	pub fn codec_id(&self) -> u16 {
+		CODECS.iter().find(|(.., format)| *format == self.format).map_or(0, |(id, ..)| *id)
+	}
+
+	pub fn bit_depth(&self) -> u8 {
+		CODECS.iter().find(|(.., format)| *format == self.format).map_or(8, |(_, bits, _)| *bits)
+	}
+
+	/// The VOC layout closest to the decoded `format`.
+	pub fn from_audio_format(format: AudioFormat, channels: Channels, sample_rate: u32) -> Self {
+		let format = match format {
+			AudioFormat::PCM8 | AudioFormat::MULAW | AudioFormat::ALAW => format,
+			_ => AudioFormat::PCM16,
+		};
+		Self { channels, sample_rate, format }
+	}
+
+	pub fn bytes_per_frame(&self) -> usize {
+		self.format.bytes_per_sample().unwrap_or(1) * self.channels.count() as usize
+	}
+
+	/// The byte one sample of silence is stored as.
+	pub fn silence(&self) -> u8 {
+		match self.format {
+			AudioFormat::PCM8 => 0x80,
+			AudioFormat::MULAW => 0xFF,
+			AudioFormat::ALAW => 0xD5,
+			_ => 0,
+		}
+	}
+
+	pub fn to_codec_string(&self) -> &'static str {
+		layout::codec_for_format(self.format).unwrap_or(codecs::audio::PCM_U8)
+	}
+
+	/// The decoded PCM layout, as the decoders take it.
+	pub fn to_wav_format(&self) -> WavFormat {
+		let mut format = WavFormat::from_audio_format(self.format, self.channels, self.sample_rate);
+		if let Some(law) = G711Law::from_codec(self.to_codec_string()) {
+			(format.bit_depth, format.format_code) = (8, law.format_code());
+		}
+		format
+	}
+
+	pub fn apply_codec(&mut self, codec: &str) -> Result<(), String> {
+		match layout::format_for_codec(codec) {
+			Some(format) if CODECS.iter().any(|(.., supported)| *supported == format) => {
+				self.format = format;
+				Ok(())
+			}
+			_ => Err(format!("voc codec '{}' is not supported", codec)),
+		}
+	}
+}
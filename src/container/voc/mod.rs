@@ -0,0 +1,7 @@
+pub mod demuxer;
+pub mod formater;
+pub mod muxer;
+
+pub use demuxer::VocDemuxer;
+pub use formater::VocFormat;
+pub use muxer::VocMuxer;
@@ -0,0 +1,98 @@
+use super::VocFormat;
+use crate::core::Muxer;
+use crate::core::packet::Packet;
+use crate::core::stream::{self, Stream, StreamKind};
+use crate::core::time::Time;
+use crate::io::{MediaWrite, WritePrimitives};
+use crate::message::Result;
+
+pub const SIGNATURE: &[u8; 20] = b"Creative Voice File\x1A";
+// the header size, and version 1.20 which brought sound blocks of any layout
+const HEADER_SIZE: u16 = 26;
+const VERSION: u16 = 0x0114;
+// block sizes are 24 bits
+const MAX_BLOCK_SIZE: usize = 0xFFFFFF;
+
+/// Writes Creative Voice files as one sound block followed by a
+/// continuation block per packet, so the output never needs to seek.
+pub struct VocMuxer<W: MediaWrite> {
+	writer: W,
+	format: VocFormat,
+	streams: stream::Streams,
+	sound_started: bool,
+}
+
+impl<W: MediaWrite> VocMuxer<W> {
+	pub fn new(mut writer: W, format: VocFormat) -> Result<Self> {
+		let codec_name = format.to_codec_string().to_string();
+		let time = Time::new(1, format.sample_rate);
+		let mut streams = stream::Streams::new_empty();
+		let stream = Stream::new(0, 0, StreamKind::Audio, codec_name, time);
+
+		streams.add(stream);
+
+		writer.write_all(SIGNATURE)?;
+		writer.write_u16_le(HEADER_SIZE)?;
+		writer.write_u16_le(VERSION)?;
+		writer.write_u16_le((!VERSION).wrapping_add(0x1234))?;
+
+		Ok(Self { writer, format, streams, sound_started: false })
+	}
+
+	pub fn write_packet(&mut self, packet: Packet) -> Result<()> {
+		// blocks hold whole frames, so a reader never splits one
+		let frame_size = self.format.bytes_per_frame();
+		let block_limit = (MAX_BLOCK_SIZE - 12) / frame_size * frame_size;
+		for data in packet.data.chunks(block_limit) {
+			self.write_block(data)?;
+		}
+		Ok(())
+	}
+
+	fn write_block(&mut self, data: &[u8]) -> Result<()> {
+		if self.sound_started {
+			self.writer.write_u8(2)?;
+			self.write_block_size(data.len())?;
+			self.writer.write_all(data)?;
+			return Ok(());
+		}
+
+		self.writer.write_u8(9)?;
+		self.write_block_size(12 + data.len())?;
+		self.writer.write_u32_le(self.format.sample_rate)?;
+		self.writer.write_u8(self.format.bit_depth())?;
+		self.writer.write_u8(self.format.channels.count())?;
+		self.writer.write_u16_le(self.format.codec_id())?;
+		self.writer.write_u32_le(0)?;
+		self.writer.write_all(data)?;
+		self.sound_started = true;
+		Ok(())
+	}
+
+	fn write_block_size(&mut self, size: usize) -> Result<()> {
+		self.writer.write_all(&(size as u32).to_le_bytes()[..3])?;
+		Ok(())
+	}
+
+	pub fn finalize(&mut self) -> Result<()> {
+		// an empty sound block still records the layout
+		if !self.sound_started {
+			self.write_block(&[])?;
+		}
+		self.writer.write_u8(0)?;
+		self.writer.flush()?;
+		Ok(())
+	}
+}
+
+impl<W: MediaWrite> Muxer for VocMuxer<W> {
+	fn streams(&self) -> &stream::Streams {
+		&self.streams
+	}
+	fn write(&mut self, packet: Packet) -> Result<()> {
+		self.write_packet(packet)
+	}
+	fn finalize(&mut self) -> Result<()> {
+		self.finalize()
+	}
+}
@@ -0,0 +1,342 @@
+use crate::{error, message::Result};
+
+// size of the fixed part of a bext chunk, before the coding history
+const BEXT_SIZE: usize = 602;
+const CUE_POINT_SIZE: usize = 24;
+const SAMPLER_SIZE: usize = 36;
+const SAMPLE_LOOP_SIZE: usize = 24;
+
+/// Broadcast extension of a BWF file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Bext {
+	pub description: String,
+	pub originator: String,
+	pub originator_reference: String,
+	/// yyyy-mm-dd
+	pub origination_date: String,
+	/// hh:mm:ss
+	pub origination_time: String,
+	/// Samples since midnight of the first sample.
+	pub time_reference: u64,
+	pub version: u16,
+	pub umid: [u8; 64],
+	/// Loudness fields of version 2, in hundredths of LUFS, LU or dBTP.
+	pub loudness_value: i16,
+	pub loudness_range: i16,
+	pub max_true_peak_level: i16,
+	pub max_momentary_loudness: i16,
+	pub max_short_term_loudness: i16,
+	pub coding_history: String,
+}
+
+impl Default for Bext {
+	fn default() -> Self {
+		Self {
+			description: String::new(),
+			originator: String::new(),
+			originator_reference: String::new(),
+			origination_date: String::new(),
+			origination_time: String::new(),
+			time_reference: 0,
+			version: 0,
+			umid: [0; 64],
+			loudness_value: 0,
+			loudness_range: 0,
+			max_true_peak_level: 0,
+			max_momentary_loudness: 0,
+			max_short_term_loudness: 0,
+			coding_history: String::new(),
+		}
+	}
+}
+
+impl Bext {
+	pub fn parse(data: &[u8]) -> Result<Self> {
+		if data.len() < BEXT_SIZE {
+			return Err(error!("bext chunk too small"));
+		}
+		let mut offset = 0;
+		let mut bext = Self {
+			description: read_text(data, &mut offset, 256)?,
+			originator: read_text(data, &mut offset, 32)?,
+			originator_reference: read_text(data, &mut offset, 32)?,
+			origination_date: read_text(data, &mut offset, 10)?,
+			origination_time: read_text(data, &mut offset, 8)?,
+			time_reference: read_u64(data, &mut offset)?,
+			version: read_u16(data, &mut offset)?,
+			..Self::default()
+		};
+		bext.umid.copy_from_slice(read_bytes(data, &mut offset, 64)?);
+		bext.loudness_value = read_u16(data, &mut offset)? as i16;
+		bext.loudness_range = read_u16(data, &mut offset)? as i16;
+		bext.max_true_peak_level = read_u16(data, &mut offset)? as i16;
+		bext.max_momentary_loudness = read_u16(data, &mut offset)? as i16;
+		bext.max_short_term_loudness = read_u16(data, &mut offset)? as i16;
+		offset = BEXT_SIZE;
+		bext.coding_history = read_text(data, &mut offset, data.len() - BEXT_SIZE)?;
+		Ok(bext)
+	}
+
+	pub fn to_bytes(&self) -> Vec<u8> {
+		let mut out = Vec::with_capacity(BEXT_SIZE + self.coding_history.len());
+		put_text(&mut out, &self.description, 256);
+		put_text(&mut out, &self.originator, 32);
+		put_text(&mut out, &self.originator_reference, 32);
+		put_text(&mut out, &self.origination_date, 10);
+		put_text(&mut out, &self.origination_time, 8);
+		out.extend_from_slice(&self.time_reference.to_le_bytes());
+		out.extend_from_slice(&self.version.to_le_bytes());
+		out.extend_from_slice(&self.umid);
+		for value in [
+			self.loudness_value,
+			self.loudness_range,
+			self.max_true_peak_level,
+			self.max_momentary_loudness,
+			self.max_short_term_loudness,
+		] {
+			out.extend_from_slice(&value.to_le_bytes());
+		}
+		out.resize(BEXT_SIZE, 0);
+		out.extend_from_slice(self.coding_history.as_bytes());
+		out
+	}
+}
+
+/// A marker from the cue chunk, with its label, note and region length
+/// from the adtl list.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CuePoint {
+	pub id: u32,
+	/// Position in samples from the start of the data.
+	pub position: u32,
+	pub label: Option<String>,
+	pub note: Option<String>,
+	/// Length in samples of the region that starts here, zero for a marker.
+	pub length: u32,
+}
+
+pub fn parse_cue(data: &[u8]) -> Result<Vec<CuePoint>> {
+	let mut offset = 0;
+	let count = read_u32(data, &mut offset)? as usize;
+	if data.len() < 4 + count * CUE_POINT_SIZE {
+		return Err(error!("cue chunk holds fewer points than it declares"));
+	}
+	let mut cues = Vec::with_capacity(count);
+	for _ in 0..count {
+		let id = read_u32(data, &mut offset)?;
+		// play order position, data chunk id, chunk start and block start
+		offset += 16;
+		let position = read_u32(data, &mut offset)?;
+		cues.push(CuePoint { id, position, ..CuePoint::default() });
+	}
+	Ok(cues)
+}
+
+/// Attaches the labels, notes and regions of an adtl list to `cues`.
+pub fn parse_adtl(data: &[u8], cues: &mut [CuePoint]) -> Result<()> {
+	let mut offset = 0;
+	while offset + 8 <= data.len() {
+		let id = read_bytes(data, &mut offset, 4)?;
+		let id = [id[0], id[1], id[2], id[3]];
+		let size = read_u32(data, &mut offset)? as usize;
+		let body = read_bytes(data, &mut offset, size)?;
+		offset += size % 2;
+		if size < 4 {
+			continue;
+		}
+		let cue_id = u32::from_le_bytes([body[0], body[1], body[2], body[3]]);
+		let Some(cue) = cues.iter_mut().find(|cue| cue.id == cue_id) else {
+			continue;
+		};
+		match &id {
+			b"labl" => cue.label = Some(read_text(body, &mut 4, size - 4)?),
+			b"note" => cue.note = Some(read_text(body, &mut 4, size - 4)?),
+			b"ltxt" if size >= 8 => cue.length = read_u32(body, &mut 4)?,
+			_ => {}
+		}
+	}
+	Ok(())
+}
+
+pub fn cue_to_bytes(cues: &[CuePoint]) -> Vec<u8> {
+	let mut out = Vec::with_capacity(4 + cues.len() * CUE_POINT_SIZE);
+	out.extend_from_slice(&(cues.len() as u32).to_le_bytes());
+	for cue in cues {
+		out.extend_from_slice(&cue.id.to_le_bytes());
+		out.extend_from_slice(&cue.position.to_le_bytes());
+		out.extend_from_slice(b"data");
+		out.extend_from_slice(&[0; 8]);
+		out.extend_from_slice(&cue.position.to_le_bytes());
+	}
+	out
+}
+
+/// The payload of a LIST chunk of type adtl, `None` when no cue carries
+/// a label, note or region.
+pub fn adtl_to_bytes(cues: &[CuePoint]) -> Option<Vec<u8>> {
+	let mut out = b"adtl".to_vec();
+	for cue in cues {
+		if cue.length > 0 {
+			let mut body = cue.id.to_le_bytes().to_vec();
+			body.extend_from_slice(&cue.length.to_le_bytes());
+			// purpose, then country, language, dialect and code page
+			body.extend_from_slice(b"rgn ");
+			body.extend_from_slice(&[0; 8]);
+			put_sub_chunk(&mut out, b"ltxt", &body);
+		}
+		for (id, text) in [(b"labl", &cue.label), (b"note", &cue.note)] {
+			if let Some(text) = text {
+				let mut body = cue.id.to_le_bytes().to_vec();
+				body.extend_from_slice(text.as_bytes());
+				body.push(0);
+				put_sub_chunk(&mut out, id, &body);
+			}
+		}
+	}
+	if out.len() == 4 { None } else { Some(out) }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SampleLoop {
+	pub id: u32,
+	/// 0 forward, 1 alternating, 2 backward.
+	pub kind: u32,
+	/// First and last sample of the loop, both played.
+	pub start: u32,
+	pub end: u32,
+	pub fraction: u32,
+	/// Zero loops forever.
+	pub play_count: u32,
+}
+
+/// Sampler settings and loop points of the smpl chunk.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Sampler {
+	pub manufacturer: u32,
+	pub product: u32,
+	/// Nanoseconds per sample.
+	pub sample_period: u32,
+	pub midi_unity_note: u32,
+	pub midi_pitch_fraction: u32,
+	pub smpte_format: u32,
+	pub smpte_offset: u32,
+	pub loops: Vec<SampleLoop>,
+	/// Data specific to the sampler, kept as is.
+	pub sampler_data: Vec<u8>,
+}
+
+impl Sampler {
+	pub fn parse(data: &[u8]) -> Result<Self> {
+		if data.len() < SAMPLER_SIZE {
+			return Err(error!("smpl chunk too small"));
+		}
+		let mut offset = 0;
+		let mut sampler = Self {
+			manufacturer: read_u32(data, &mut offset)?,
+			product: read_u32(data, &mut offset)?,
+			sample_period: read_u32(data, &mut offset)?,
+			midi_unity_note: read_u32(data, &mut offset)?,
+			midi_pitch_fraction: read_u32(data, &mut offset)?,
+			smpte_format: read_u32(data, &mut offset)?,
+			smpte_offset: read_u32(data, &mut offset)?,
+			..Self::default()
+		};
+		let count = read_u32(data, &mut offset)? as usize;
+		let data_size = read_u32(data, &mut offset)? as usize;
+		if data.len() < SAMPLER_SIZE + count * SAMPLE_LOOP_SIZE {
+			return Err(error!("smpl chunk holds fewer loops than it declares"));
+		}
+		for _ in 0..count {
+			sampler.loops.push(SampleLoop {
+				id: read_u32(data, &mut offset)?,
+				kind: read_u32(data, &mut offset)?,
+				start: read_u32(data, &mut offset)?,
+				end: read_u32(data, &mut offset)?,
+				fraction: read_u32(data, &mut offset)?,
+				play_count: read_u32(data, &mut offset)?,
+			});
+		}
+		// writers disagree on the sampler data size, take what is there
+		let data_size = data_size.min(data.len() - offset);
+		sampler.sampler_data = read_bytes(data, &mut offset, data_size)?.to_vec();
+		Ok(sampler)
+	}
+
+	pub fn to_bytes(&self) -> Vec<u8> {
+		let mut out = Vec::with_capacity(SAMPLER_SIZE + self.loops.len() * SAMPLE_LOOP_SIZE);
+		for value in [
+			self.manufacturer,
+			self.product,
+			self.sample_period,
+			self.midi_unity_note,
+			self.midi_pitch_fraction,
+			self.smpte_format,
+			self.smpte_offset,
+			self.loops.len() as u32,
+			self.sampler_data.len() as u32,
+		] {
+			out.extend_from_slice(&value.to_le_bytes());
+		}
+		for sample_loop in &self.loops {
+			for value in [
+				sample_loop.id,
+				sample_loop.kind,
+				sample_loop.start,
+				sample_loop.end,
+				sample_loop.fraction,
+				sample_loop.play_count,
+			] {
+				out.extend_from_slice(&value.to_le_bytes());
+			}
+		}
+		out.extend_from_slice(&self.sampler_data);
+		out
+	}
+}
+
+fn put_sub_chunk(out: &mut Vec<u8>, id: &[u8; 4], body: &[u8]) {
+	out.extend_from_slice(id);
+	out.extend_from_slice(&(body.len() as u32).to_le_bytes());
+	out.extend_from_slice(body);
+	if body.len() % 2 == 1 {
+		out.push(0);
+	}
+}
+
+/// Writes `text` into a field of `size` bytes, cut or padded with zeros.
+fn put_text(out: &mut Vec<u8>, text: &str, size: usize) {
+	let bytes = text.as_bytes();
+	let length = bytes.len().min(size);
+	out.extend_from_slice(&bytes[..length]);
+	out.resize(out.len() + size - length, 0);
+}
+
+fn read_bytes<'a>(data: &'a [u8], offset: &mut usize, size: usize) -> Result<&'a [u8]> {
+	let bytes = data.get(*offset..*offset + size).ok_or_else(|| error!("truncated wav chunk"))?;
+	*offset += size;
+	Ok(bytes)
+}
+
+/// Reads a text field of `size` bytes, which ends at its first zero.
+fn read_text(data: &[u8], offset: &mut usize, size: usize) -> Result<String> {
+	let bytes = read_bytes(data, offset, size)?;
+	let end = bytes.iter().position(|&byte| byte == 0).unwrap_or(bytes.len());
+	Ok(String::from_utf8_lossy(&bytes[..end]).to_string())
+}
+
+fn read_u16(data: &[u8], offset: &mut usize) -> Result<u16> {
+	let bytes = read_bytes(data, offset, 2)?;
+	Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+fn read_u32(data: &[u8], offset: &mut usize) -> Result<u32> {
+	let bytes = read_bytes(data, offset, 4)?;
+	Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+fn read_u64(data: &[u8], offset: &mut usize) -> Result<u64> {
+	let bytes = read_bytes(data, offset, 8)?;
+	let mut value = [0u8; 8];
+	value.copy_from_slice(bytes);
+	Ok(u64::from_le_bytes(value))
+}
@@ -1,26 +1,49 @@
 use super::utils;
+use crate::core::frame::{AudioFormat, FrameAudio};
 use crate::{container::wav::WavFormat, error, message};
 
 pub fn to_f32(data: &[u8], format: &WavFormat) -> message::Result<Vec<f32>> {
-	match format.bit_depth {
-		16 => from_pcm16(data),
-		24 => from_pcm24(data),
-		32 => from_pcm32(data),
-		_ => Err(error!("unsupported bit depth")),
+	match format.audio_format() {
+		AudioFormat::PCM8 => Ok(data.iter().map(|&b| utils::normalize_pcm8(b)).collect()),
+		AudioFormat::PCM16 => from_pcm16(data),
+		AudioFormat::PCM24 => from_pcm24(data),
+		AudioFormat::PCM32 => from_pcm32(data),
+		AudioFormat::PCM32I => from_pcm32_int(data),
+		AudioFormat::PCM64 => from_pcm64(data),
+		other => Err(error!("unsupported sample format {:?}", other)),
 	}
 }
 
 pub fn from_f32(samples: &[f32], format: &WavFormat) -> message::Result<Vec<u8>> {
-	match format.bit_depth {
-		16 => to_pcm16(samples),
-		24 => to_pcm24(samples),
-		32 => to_pcm32(samples),
-		_ => Err(error!("unsupported bit depth")),
+	match format.audio_format() {
+		AudioFormat::PCM8 => Ok(samples.iter().map(|&s| utils::denormalize_pcm8(s)).collect()),
+		AudioFormat::PCM16 => to_pcm16(samples),
+		AudioFormat::PCM24 => to_pcm24(samples),
+		AudioFormat::PCM32 => to_pcm32(samples),
+		AudioFormat::PCM32I => to_pcm32_int(samples),
+		AudioFormat::PCM64 => to_pcm64(samples),
+		other => Err(error!("unsupported sample format {:?}", other)),
 	}
 }
 
+pub fn audio_to_f32(audio: &FrameAudio) -> message::Result<Vec<f32>> {
+	if audio.is_compressed() {
+		return Err(error!("expected pcm audio, found {:?}", audio.format));
+	}
+	let format = WavFormat::from_audio_format(audio.format, audio.channels, audio.sample_rate);
+	to_f32(&audio.data, &format)
+}
+
+pub fn audio_from_f32(samples: &[f32], audio: &FrameAudio) -> message::Result<Vec<u8>> {
+	if audio.is_compressed() {
+		return Err(error!("expected pcm audio, found {:?}", audio.format));
+	}
+	let format = WavFormat::from_audio_format(audio.format, audio.channels, audio.sample_rate);
+	from_f32(samples, &format)
+}
+
 fn from_pcm16(data: &[u8]) -> message::Result<Vec<f32>> {
-	if data.len() % 2 != 0 {
+	if !data.len().is_multiple_of(2) {
 		return Err(error!("invalid pcm16 length"));
 	}
 
@@ -31,7 +54,7 @@ fn from_pcm16(data: &[u8]) -> message::Result<Vec<f32>> {
 }
 
 fn from_pcm24(data: &[u8]) -> message::Result<Vec<f32>> {
-	if data.len() % 3 != 0 {
+	if !data.len().is_multiple_of(3) {
 		return Err(error!("invalid pcm24 length"));
 	}
 	let mut result = Vec::with_capacity(data.len() / 3);
@@ -43,7 +66,7 @@ fn from_pcm24(data: &[u8]) -> message::Result<Vec<f32>> {
 }
 
 fn from_pcm32(data: &[u8]) -> message::Result<Vec<f32>> {
-	if data.len() % 4 != 0 {
+	if !data.len().is_multiple_of(4) {
 		return Err(error!("invalid pcm32 length"));
 	}
 	let iter = data.chunks_exact(4);
@@ -52,6 +75,26 @@ fn from_pcm32(data: &[u8]) -> message::Result<Vec<f32>> {
 	Ok(value.map(utils::normalize_pcm32).collect())
 }
 
+fn from_pcm32_int(data: &[u8]) -> message::Result<Vec<f32>> {
+	if !data.len().is_multiple_of(4) {
+		return Err(error!("invalid pcm32 length"));
+	}
+	let iter = data.chunks_exact(4);
+	let value = iter.map(|b| i32::from_le_bytes([b[0], b[1], b[2], b[3]]));
+
+	Ok(value.map(utils::normalize_pcm32_int).collect())
+}
+
+fn from_pcm64(data: &[u8]) -> message::Result<Vec<f32>> {
+	if !data.len().is_multiple_of(8) {
+		return Err(error!("invalid pcm64 length"));
+	}
+	let iter = data.chunks_exact(8);
+	let value = iter.map(|b| f64::from_le_bytes([b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]]));
+
+	Ok(value.map(utils::normalize_pcm64).collect())
+}
+
 fn to_pcm16(samples: &[f32]) -> message::Result<Vec<u8>> {
 	Ok(samples.iter().flat_map(|&s| utils::denormalize_pcm16(s).to_le_bytes()).collect())
 }
@@ -71,3 +114,11 @@ fn to_pcm24(samples: &[f32]) -> message::Result<Vec<u8>> {
 fn to_pcm32(samples: &[f32]) -> message::Result<Vec<u8>> {
 	Ok(samples.iter().flat_map(|&s| utils::denormalize_pcm32(s).to_le_bytes()).collect())
 }
+
+fn to_pcm32_int(samples: &[f32]) -> message::Result<Vec<u8>> {
+	Ok(samples.iter().flat_map(|&s| utils::denormalize_pcm32_int(s).to_le_bytes()).collect())
+}
+
+fn to_pcm64(samples: &[f32]) -> message::Result<Vec<u8>> {
+	Ok(samples.iter().flat_map(|&s| utils::denormalize_pcm64(s).to_le_bytes()).collect())
+}
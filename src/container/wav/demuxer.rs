@@ -1,29 +1,81 @@
-use super::header::WavHeader;
+use std::any::Any;
+
+use super::bwf::{self, Bext, Sampler};
+use super::header::{EXTENSIBLE_SIZE, FORMAT_EXTENSIBLE, SUBFORMAT_SUFFIX, WavHeader};
 use super::{WavFormat, WavMetadata};
+use crate::container::probe::SCORE_MAX;
 use crate::core::frame::Channels;
 use crate::core::packet::Packet;
 use crate::core::{Demuxer, stream, time};
-use crate::io::{MediaRead, ReadPrimitives};
+use crate::io::{MediaRead, MediaSeek, ReadPrimitives, SeekFrom};
 use crate::{error, message::Result};
 
+type SeekFn<R> = fn(&mut R, SeekFrom) -> Result<u64>;
+
+/// Reads WAV, RF64 and BW64. Chunks after the audio are read up front when
+/// the input can seek, and once the audio has been read when it cannot.
 pub struct WavDemuxer<R: MediaRead> {
 	reader: R,
 	format: WavFormat,
 	streams: stream::Streams,
 	metadata: WavMetadata,
-	data_remaining: u64,
+	/// Audio bytes left, `None` when the writer never set the size and the
+	/// audio runs to the end of the input.
+	data_remaining: Option<u64>,
+	/// The data chunk has an odd size, so a pad byte follows it.
+	data_padded: bool,
+	/// Cue labels still waiting for their cue chunk.
+	adtl: Option<Vec<u8>>,
+	tail_read: bool,
 	packet_count: u64,
 	sample_position: u64,
+	/// Samples per channel the fact chunk gives compressed audio, which
+	/// leaves out the padding of the last block.
+	sample_count: Option<u64>,
+}
+
+impl<R: MediaRead + MediaSeek> WavDemuxer<R> {
+	pub fn new(reader: R) -> Result<Self> {
+		Self::build(reader, Some(<R as MediaSeek>::seek))
+	}
 }
 
 impl<R: MediaRead> WavDemuxer<R> {
 	const CHUNK_SIZE_LIMIT: usize = 65536;
 
-	pub fn new(mut reader: R) -> Result<Self> {
-		let (header, metadata, data_size) = Self::read_wav_and_find_data(&mut reader)?;
+	/// A demuxer for input that cannot seek, such as a pipe. Metadata after
+	/// the audio shows up once the last packet has been read.
+	pub fn new_stream(reader: R) -> Result<Self> {
+		Self::build(reader, None)
+	}
+
+	fn build(mut reader: R, seek: Option<SeekFn<R>>) -> Result<Self> {
+		let (mut adtl, mut sample_count) = (None, None);
+		let (header, mut metadata, mut data_size) =
+			Self::read_wav_and_find_data(&mut reader, &mut adtl, &mut sample_count)?;
 		header.validate()?;
+		let data_padded = data_size.is_some_and(|size| size % 2 == 1);
+
+		if let Some(seek) = seek {
+			let data_start = seek(&mut reader, SeekFrom::Current(0))?;
+			let end = seek(&mut reader, SeekFrom::End(0))?;
+			let size = data_size.unwrap_or(u64::MAX).min(end - data_start);
+			if data_start + size < end {
+				let tail = data_start + size + data_padded as u64;
+				seek(&mut reader, SeekFrom::Start(tail))?;
+				// a damaged tail must not cost the audio before it
+				let left = end.saturating_sub(tail);
+				let _ = Self::read_trailing_chunks(&mut reader, &mut metadata, &mut adtl, left);
+			}
+			seek(&mut reader, SeekFrom::Start(data_start))?;
+			data_size = Some(size);
+		}
+		Self::attach_labels(&mut metadata, &mut adtl)?;
 
 		let format = header.to_format();
+		if !format.is_compressed() {
+			sample_count = None;
+		}
 
 		let codec_name = format.to_codec_string().to_string();
 		let time = time::Time::new(1, header.sample_rate);
@@ -36,13 +88,26 @@ impl<R: MediaRead> WavDemuxer<R> {
 			streams,
 			metadata,
 			data_remaining: data_size,
+			data_padded,
+			adtl,
+			tail_read: seek.is_some(),
 			packet_count: 0,
 			sample_position: 0,
+			sample_count,
 		})
 	}
 
-	fn read_wav_and_find_data(reader: &mut R) -> Result<(WavHeader, WavMetadata, u64)> {
-		Self::check_fourcc(reader, "RIFF")?;
+	/// Reads the chunks up to the audio. The data size is `None` when it is
+	/// 0 or 0xFFFFFFFF, which writers that cannot seek leave behind.
+	fn read_wav_and_find_data(
+		reader: &mut R,
+		adtl: &mut Option<Vec<u8>>,
+		sample_count: &mut Option<u64>,
+	) -> Result<(WavHeader, WavMetadata, Option<u64>)> {
+		let riff_id = Self::read_fourcc(reader)?;
+		if !matches!(riff_id.as_str(), "RIFF" | "RF64" | "BW64") {
+			return Err(error!("expected RIFF, RF64 or BW64, found {}", riff_id));
+		}
 		let _file_size = reader.read_u32_le()?;
 		Self::check_fourcc(reader, "WAVE")?;
 
@@ -53,20 +118,136 @@ impl<R: MediaRead> WavDemuxer<R> {
 			block_align: 0,
 			bits_per_sample: 0,
 			format_code: 0,
+			channel_mask: None,
 		};
 		let mut metadata = WavMetadata::new();
+		// 64 bit sizes of RF64 chunks whose 32 bit size is 0xFFFFFFFF
+		let mut sizes = Vec::new();
 
 		loop {
-			let chunk_id = Self::read_fourcc(reader)?;
-			let chunk_size = reader.read_u32_le()? as u64;
+			let (chunk_id, chunk_size) = Self::read_chunk_header(reader, &sizes)?
+				.ok_or_else(|| error!("wav file has no data chunk"))?;
 
 			match chunk_id.as_str() {
+				"ds64" if riff_id != "RIFF" => sizes = Self::read_ds64_chunk(reader, chunk_size)?,
 				"fmt " => Self::read_fmt_chunk(reader, chunk_size, &mut header)?,
-				"LIST" => Self::read_list_chunk(reader, chunk_size, &mut metadata)?,
-				"data" => return Ok((header, metadata, chunk_size)),
-				_ => Self::skip_bytes(reader, chunk_size)?,
+				"fact" if chunk_size >= 4 => {
+					let count = reader.read_u32_le()? as u64;
+					Self::skip_bytes(reader, chunk_size - 4)?;
+					// RF64 keeps a count that does not fit in ds64
+					let large = sizes.iter().find(|(id, _)| id == "fact").map(|(_, count)| *count);
+					*sample_count = if count == u32::MAX as u64 { large } else { Some(count) };
+					// writers that never came back to the count leave it at 0
+					*sample_count = sample_count.filter(|count| *count > 0);
+				}
+				"data" => {
+					let known = chunk_size != 0 && chunk_size != u32::MAX as u64;
+					return Ok((header, metadata, known.then_some(chunk_size)));
+				}
+				_ => Self::read_metadata_chunk(reader, &chunk_id, chunk_size, &mut metadata, adtl)?,
+			}
+			Self::skip_padding(reader, chunk_size)?;
+		}
+	}
+
+	/// Reads the chunks after the audio up to the end of the input, which
+	/// is `left` bytes away when it is known.
+	fn read_trailing_chunks(
+		reader: &mut R,
+		metadata: &mut WavMetadata,
+		adtl: &mut Option<Vec<u8>>,
+		mut left: u64,
+	) -> Result<()> {
+		while let Some((chunk_id, chunk_size)) = Self::read_chunk_header(reader, &[])? {
+			left = left.saturating_sub(8);
+			if chunk_size > left {
+				return Err(error!("wav chunk '{}' runs past the end of the input", chunk_id));
+			}
+			left -= chunk_size;
+			Self::read_metadata_chunk(reader, &chunk_id, chunk_size, metadata, adtl)?;
+			Self::skip_padding(reader, chunk_size)?;
+			left = left.saturating_sub(chunk_size % 2);
+		}
+		Ok(())
+	}
+
+	/// The id and size of the next chunk, `None` at the end of the input.
+	fn read_chunk_header(reader: &mut R, sizes: &[(String, u64)]) -> Result<Option<(String, u64)>> {
+		let mut buf = [0u8; 8];
+		let mut filled = 0;
+		while filled < buf.len() {
+			let read = reader.read(&mut buf[filled..])?;
+			if read == 0 {
+				// a few stray bytes at the end are not a chunk
+				return Ok(None);
+			}
+			filled += read;
+		}
+		let chunk_id = String::from_utf8_lossy(&buf[..4]).to_string();
+		let mut chunk_size = u32::from_le_bytes([buf[4], buf[5], buf[6], buf[7]]) as u64;
+		if chunk_size == u32::MAX as u64
+			&& let Some((_, size)) = sizes.iter().find(|(id, _)| *id == chunk_id)
+		{
+			chunk_size = *size;
+		}
+		Ok(Some((chunk_id, chunk_size)))
+	}
+
+	fn read_metadata_chunk(
+		reader: &mut R,
+		chunk_id: &str,
+		chunk_size: u64,
+		metadata: &mut WavMetadata,
+		adtl: &mut Option<Vec<u8>>,
+	) -> Result<()> {
+		match chunk_id {
+			"LIST" => Self::read_list_chunk(reader, chunk_size, metadata, adtl)?,
+			"bext" => metadata.bext = Some(Bext::parse(&Self::read_bytes(reader, chunk_size)?)?),
+			"iXML" => {
+				let data = Self::read_bytes(reader, chunk_size)?;
+				let xml = String::from_utf8_lossy(&data).trim_end_matches('\0').to_string();
+				metadata.ixml = Some(xml);
 			}
+			"cue " => metadata.cues = bwf::parse_cue(&Self::read_bytes(reader, chunk_size)?)?,
+			"smpl" => metadata.sampler = Some(Sampler::parse(&Self::read_bytes(reader, chunk_size)?)?),
+			_ => Self::skip_bytes(reader, chunk_size)?,
+		}
+		Ok(())
+	}
+
+	/// Gives the cue points the labels of an adtl list, once both were read.
+	fn attach_labels(metadata: &mut WavMetadata, adtl: &mut Option<Vec<u8>>) -> Result<()> {
+		if metadata.cues.is_empty() {
+			return Ok(());
+		}
+		match adtl.take() {
+			Some(adtl) => bwf::parse_adtl(&adtl, &mut metadata.cues),
+			None => Ok(()),
+		}
+	}
+
+	/// Reads the 64 bit sizes of an RF64 file, keyed by the chunk they
+	/// belong to.
+	fn read_ds64_chunk(reader: &mut R, chunk_size: u64) -> Result<Vec<(String, u64)>> {
+		if chunk_size < 28 {
+			return Err(error!("ds64 chunk too small"));
+		}
+		let _riff_size = reader.read_u64_le()?;
+		let data_size = reader.read_u64_le()?;
+		let sample_count = reader.read_u64_le()?;
+		let table_length = reader.read_u32_le()? as u64;
+		if chunk_size < 28 + table_length * 12 {
+			return Err(error!("ds64 table exceeds its chunk"));
+		}
+
+		// the sample count goes with the sizes, for a fact chunk to find
+		let mut sizes = vec![("data".to_string(), data_size), ("fact".to_string(), sample_count)];
+		for _ in 0..table_length {
+			let id = Self::read_fourcc(reader)?;
+			sizes.push((id, reader.read_u64_le()?));
 		}
+		Self::skip_bytes(reader, chunk_size - 28 - table_length * 12)?;
+		Ok(sizes)
 	}
 
 	fn read_fmt_chunk(reader: &mut R, chunk_size: u64, header: &mut WavHeader) -> Result<()> {
@@ -82,19 +263,43 @@ impl<R: MediaRead> WavDemuxer<R> {
 		header.block_align = reader.read_u16_le()?;
 		header.bits_per_sample = reader.read_u16_le()?;
 
-		let remaining = chunk_size - 16;
+		let mut remaining = chunk_size - 16;
+		if header.format_code == FORMAT_EXTENSIBLE {
+			if remaining < 2 + EXTENSIBLE_SIZE as u64 || reader.read_u16_le()? < EXTENSIBLE_SIZE {
+				return Err(error!("WAVE_FORMAT_EXTENSIBLE fmt chunk too small"));
+			}
+			// the container size in bits_per_sample is what the data holds
+			let _valid_bits = reader.read_u16_le()?;
+			header.channel_mask = Some(reader.read_u32_le()?);
+			let mut guid = [0u8; 16];
+			reader.read_exact(&mut guid)?;
+			if guid[2..] != SUBFORMAT_SUFFIX {
+				return Err(error!("WAVE_FORMAT_EXTENSIBLE sub format is not supported"));
+			}
+			header.format_code = u16::from_le_bytes([guid[0], guid[1]]);
+			remaining -= 2 + EXTENSIBLE_SIZE as u64;
+		}
 		if remaining > 0 {
 			Self::skip_bytes(reader, remaining)?;
 		}
 		Ok(())
 	}
 
-	fn read_list_chunk(reader: &mut R, chunk_size: u64, metadata: &mut WavMetadata) -> Result<()> {
+	fn read_list_chunk(
+		reader: &mut R,
+		chunk_size: u64,
+		metadata: &mut WavMetadata,
+		adtl: &mut Option<Vec<u8>>,
+	) -> Result<()> {
 		if chunk_size < 4 {
-			return Ok(());
+			return Self::skip_bytes(reader, chunk_size);
 		}
 
 		let form_type = Self::read_fourcc(reader)?;
+		if form_type == "adtl" {
+			*adtl = Some(Self::read_bytes(reader, chunk_size - 4)?);
+			return Ok(());
+		}
 		if form_type != "INFO" {
 			return Self::skip_bytes(reader, chunk_size - 4);
 		}
@@ -126,6 +331,9 @@ impl<R: MediaRead> WavDemuxer<R> {
 				position += 1;
 			}
 		}
+		if position < chunk_size {
+			Self::skip_bytes(reader, chunk_size - position)?;
+		}
 		Ok(())
 	}
 
@@ -143,40 +351,92 @@ impl<R: MediaRead> WavDemuxer<R> {
 		Ok(())
 	}
 
+	// the buffer grows with what is read, not with the size a chunk claims
 	fn read_bytes(reader: &mut R, size: u64) -> Result<Vec<u8>> {
-		let mut buf = vec![0u8; size as usize];
-		reader.read_exact(&mut buf)?;
-		Ok(buf)
+		let mut data = Vec::new();
+		let mut chunk = [0u8; 8192];
+		while (data.len() as u64) < size {
+			let count = (size - data.len() as u64).min(chunk.len() as u64) as usize;
+			reader.read_exact(&mut chunk[..count])?;
+			data.extend_from_slice(&chunk[..count]);
+		}
+		Ok(data)
 	}
 
 	fn skip_bytes(reader: &mut R, size: u64) -> Result<()> {
-		let mut buf = vec![0u8; size as usize];
-		reader.read_exact(&mut buf)?;
+		let mut chunk = [0u8; 8192];
+		let mut left = size;
+		while left > 0 {
+			let count = left.min(chunk.len() as u64) as usize;
+			reader.read_exact(&mut chunk[..count])?;
+			left -= count as u64;
+		}
+		Ok(())
+	}
+
+	/// Chunks start on even offsets; the pad byte may be missing at the end.
+	fn skip_padding(reader: &mut R, chunk_size: u64) -> Result<()> {
+		if chunk_size % 2 == 1 {
+			reader.read(&mut [0u8; 1])?;
+		}
 		Ok(())
 	}
 
+	/// Reads what follows the audio of an input that cannot seek.
+	fn read_tail(&mut self) -> Result<()> {
+		if self.tail_read {
+			return Ok(());
+		}
+		self.tail_read = true;
+		if self.data_padded {
+			self.reader.read(&mut [0u8; 1])?;
+		}
+		let (reader, metadata, adtl) = (&mut self.reader, &mut self.metadata, &mut self.adtl);
+		let _ = Self::read_trailing_chunks(reader, metadata, adtl, u64::MAX);
+		Self::attach_labels(&mut self.metadata, &mut self.adtl)
+	}
+
 	pub fn read_packet(&mut self) -> Result<Option<Packet>> {
-		if self.data_remaining == 0 {
+		let remaining = self.data_remaining.unwrap_or(u64::MAX);
+		if remaining == 0 {
+			self.read_tail()?;
 			return Ok(None);
 		}
 
 		let block_align = self.format.block_align() as u64;
 		let max_chunk = (Self::CHUNK_SIZE_LIMIT as u64 / block_align) * block_align;
-		let chunk_size = std::cmp::min(self.data_remaining, max_chunk) as usize;
+		let chunk_size = std::cmp::min(remaining, max_chunk) as usize;
 		let mut data = vec![0u8; chunk_size];
-		let bytes_read = self.reader.read(&mut data)?;
+		// short reads would split blocks, so fill the whole chunk
+		let mut bytes_read = 0;
+		while bytes_read < chunk_size {
+			let read = self.reader.read(&mut data[bytes_read..])?;
+			if read == 0 {
+				break;
+			}
+			bytes_read += read;
+		}
 
 		if bytes_read == 0 {
+			// the input ends before the data chunk does
+			self.data_remaining = Some(0);
+			self.tail_read = true;
 			return Ok(None);
 		}
 
 		data.truncate(bytes_read);
-		self.data_remaining -= bytes_read as u64;
+		self.data_remaining = self.data_remaining.map(|remaining| remaining - bytes_read as u64);
 
 		let time = time::Time::new(1, self.format.sample_rate);
-		let packet = Packet::new(data, 0, time).with_pts(self.sample_position as i64);
+		let position = self.sample_position as i64;
+		let mut packet = Packet::new(data, 0, time).with_pts(position).with_dts(position);
 
-		self.sample_position += (bytes_read / self.format.bytes_per_frame()) as u64;
+		let samples = self.format.samples_in(bytes_read as u64);
+		if let Some(count) = self.sample_count {
+			let duration = samples.min(count.saturating_sub(self.sample_position));
+			packet = packet.with_duration(duration as i64);
+		}
+		self.sample_position += samples;
 		self.packet_count += 1;
 
 		Ok(Some(packet))
@@ -201,4 +461,13 @@ impl<R: MediaRead> Demuxer for WavDemuxer<R> {
 	fn read_packet(&mut self) -> Result<Option<Packet>> {
 		self.read_packet()
 	}
+	fn container_metadata(&self) -> Option<&dyn Any> {
+		Some(&self.metadata)
+	}
+}
+
+/// Scores how likely `data` is the start of a WAV file.
+pub fn probe(data: &[u8]) -> u8 {
+	let riff = data.len() >= 12 && matches!(&data[..4], b"RIFF" | b"RF64" | b"BW64");
+	if riff && &data[8..12] == b"WAVE" { SCORE_MAX } else { 0 }
 }
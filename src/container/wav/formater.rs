@@ -1,4 +1,6 @@
 use crate::codecs;
+use crate::codecs::audio::adpcm::AdpcmKind;
+use crate::codecs::audio::g711::G711Law;
 use crate::container::raw;
 pub use crate::container::wav::demuxer::WavDemuxer;
 pub use crate::container::wav::metadata::WavMetadata;
@@ -12,12 +14,23 @@ pub struct WavFormat {
 	pub sample_rate: u32,
 	pub bit_depth: u16,
 	pub format_code: u16,
+	/// Samples per channel in a block of ADPCM, zero for PCM.
+	pub samples_per_block: u16,
+	/// Speaker positions of WAVE_FORMAT_EXTENSIBLE, which is written when set.
+	pub channel_mask: Option<u32>,
 }
 
 impl Default for WavFormat {
 	fn default() -> Self {
 		// defaut is pcm_16
-		Self { channels: Channels::Stereo, sample_rate: 44100, bit_depth: 16, format_code: 1 }
+		Self {
+			channels: Channels::Stereo,
+			sample_rate: 44100,
+			bit_depth: 16,
+			format_code: 1,
+			samples_per_block: 0,
+			channel_mask: None,
+		}
 	}
 }
 
@@ -27,15 +40,60 @@ impl WavFormat {
 			codecs::audio::PCM_S16LE => Ok(Self::default()),
 			codecs::audio::PCM_S24LE => Ok(Self { bit_depth: 24, ..Self::default() }),
 			codecs::audio::PCM_F32LE => Ok(Self { bit_depth: 32, format_code: 3, ..Self::default() }),
-			_ => Err(format!("wav codec '{}' is not supported", codec)),
+			_ => {
+				let mut format = Self::default();
+				format.apply_codec(codec)?;
+				Ok(format)
+			}
 		}
 	}
 
+	pub fn from_audio_format(format: AudioFormat, channels: Channels, sample_rate: u32) -> Self {
+		let (bit_depth, format_code) = match format {
+			AudioFormat::PCM8 => (8, 1),
+			AudioFormat::PCM24 => (24, 1),
+			AudioFormat::PCM32 => (32, 3),
+			AudioFormat::PCM32I => (32, 1),
+			AudioFormat::PCM64 => (64, 3),
+			_ => (16, 1),
+		};
+		Self { channels, sample_rate, bit_depth, format_code, samples_per_block: 0, channel_mask: None }
+	}
+
+	/// Raw layout of the samples, as decoded from ADPCM.
 	pub fn to_raw_format(&self) -> raw::RawPcmFormat {
+		let bit_depth = if self.adpcm().is_some() { 16 } else { self.bit_depth };
 		raw::RawPcmFormat {
 			channels: self.channels,
 			sample_rate: self.sample_rate,
-			bit_depth: self.bit_depth,
+			bit_depth,
+			g711: self.g711(),
+		}
+	}
+
+	pub fn adpcm(&self) -> Option<AdpcmKind> {
+		AdpcmKind::from_format_code(self.format_code)
+	}
+
+	pub fn g711(&self) -> Option<G711Law> {
+		G711Law::from_format_code(self.format_code)
+	}
+
+	/// Whether samples are coded rather than stored as plain PCM.
+	pub fn is_compressed(&self) -> bool {
+		self.adpcm().is_some() || self.g711().is_some()
+	}
+
+	/// Samples per channel held by `bytes` of data.
+	pub fn samples_in(&self, bytes: u64) -> u64 {
+		let channels = self.channels.count() as usize;
+		match self.adpcm() {
+			Some(kind) => {
+				let block_align = self.block_align() as u64;
+				let remainder = kind.samples_per_block(channels, (bytes % block_align) as usize);
+				bytes / block_align * self.samples_per_block as u64 + remainder as u64
+			}
+			None => bytes / self.bytes_per_frame().max(1) as u64,
 		}
 	}
 
@@ -48,6 +106,10 @@ impl WavFormat {
 	}
 
 	pub fn byte_rate(&self) -> u32 {
+		if self.adpcm().is_some() {
+			let bytes = self.sample_rate as u64 * self.block_align() as u64;
+			return (bytes / (self.samples_per_block as u64).max(1)) as u32;
+		}
 		self
 			.sample_rate
 			.saturating_mul(self.channels.count() as u32)
@@ -55,37 +117,71 @@ impl WavFormat {
 	}
 
 	pub fn block_align(&self) -> u16 {
+		if let Some(kind) = self.adpcm() {
+			let channels = self.channels.count() as usize;
+			return kind.block_size(channels, self.samples_per_block as usize) as u16;
+		}
 		self.channels.count() as u16 * (self.bit_depth / 8)
 	}
 
 	pub fn audio_format(&self) -> AudioFormat {
-		match self.bit_depth {
-			16 => AudioFormat::PCM16,
-			24 => AudioFormat::PCM24,
-			32 => AudioFormat::PCM32,
+		if self.adpcm().is_some() {
+			return AudioFormat::ADPCM;
+		}
+		if let Some(law) = self.g711() {
+			return law.audio_format();
+		}
+		match (self.format_code, self.bit_depth) {
+			(3, 64) => AudioFormat::PCM64,
+			(3, _) => AudioFormat::PCM32,
+			(_, 8) => AudioFormat::PCM8,
+			(_, 24) => AudioFormat::PCM24,
+			(_, 32) => AudioFormat::PCM32I,
 			_ => AudioFormat::PCM16,
 		}
 	}
 
 	pub fn to_codec_string(&self) -> &'static str {
-		match self.bit_depth {
-			16 => codecs::audio::PCM_S16LE,
-			24 => codecs::audio::PCM_S24LE,
-			32 => codecs::audio::PCM_F32LE,
+		if let Some(kind) = self.adpcm() {
+			return kind.codec();
+		}
+		match self.audio_format() {
+			AudioFormat::PCM8 => codecs::audio::PCM_U8,
+			AudioFormat::PCM24 => codecs::audio::PCM_S24LE,
+			AudioFormat::PCM32 => codecs::audio::PCM_F32LE,
+			AudioFormat::PCM32I => codecs::audio::PCM_S32LE,
+			AudioFormat::PCM64 => codecs::audio::PCM_F64LE,
+			AudioFormat::MULAW => codecs::audio::PCM_MULAW,
+			AudioFormat::ALAW => codecs::audio::PCM_ALAW,
 			_ => codecs::audio::PCM_S16LE,
 		}
 	}
 
 	pub fn apply_codec(&mut self, codec: &str) -> Result<(), String> {
 		match codec {
-			codecs::audio::PCM_S16LE => self.bit_depth = 16,
-			codecs::audio::PCM_S24LE => self.bit_depth = 24,
-			codecs::audio::PCM_F32LE => {
-				self.bit_depth = 32;
-				self.format_code = 3;
+			codecs::audio::PCM_U8 => self.set_pcm(8, 1),
+			codecs::audio::PCM_S16LE => self.set_pcm(16, 1),
+			codecs::audio::PCM_S24LE => self.set_pcm(24, 1),
+			codecs::audio::PCM_S32LE => self.set_pcm(32, 1),
+			codecs::audio::PCM_F32LE => self.set_pcm(32, 3),
+			codecs::audio::PCM_F64LE => self.set_pcm(64, 3),
+			codecs::audio::PCM_MULAW => self.set_pcm(8, G711Law::MuLaw.format_code()),
+			codecs::audio::PCM_ALAW => self.set_pcm(8, G711Law::ALaw.format_code()),
+			_ => {
+				let kind = AdpcmKind::from_codec(codec)
+					.ok_or_else(|| format!("wav codec '{}' is not supported", codec))?;
+				let channels = self.channels.count() as usize;
+				self.bit_depth = 4;
+				self.format_code = kind.format_code();
+				self.samples_per_block = kind.default_samples_per_block(channels) as u16;
 			}
-			_ => return Err(format!("wav codec '{}' is not supported", codec)),
 		}
 		Ok(())
 	}
+
+	fn set_pcm(&mut self, bit_depth: u16, format_code: u16) {
+		self.bit_depth = bit_depth;
+		self.format_code = format_code;
+		self.samples_per_block = 0;
+	}
 }
@@ -1,5 +1,13 @@
+use crate::codecs::audio::adpcm::AdpcmKind;
 use crate::{container::wav::WavFormat, core::frame::Channels, error, message::Result};
 
+pub const FORMAT_EXTENSIBLE: u16 = 0xFFFE;
+// size of the WAVE_FORMAT_EXTENSIBLE fields after cbSize
+pub const EXTENSIBLE_SIZE: u16 = 22;
+// a SubFormat GUID is the format code followed by these bytes
+pub const SUBFORMAT_SUFFIX: [u8; 14] =
+	[0x00, 0x00, 0x00, 0x00, 0x10, 0x00, 0x80, 0x00, 0x00, 0xAA, 0x00, 0x38, 0x9B, 0x71];
+
 #[derive(Debug)]
 pub struct WavHeader {
 	pub channels: Channels,
@@ -7,16 +15,26 @@ pub struct WavHeader {
 	pub byte_rate: u32,
 	pub block_align: u16,
 	pub bits_per_sample: u16,
+	/// The format code, taken from the SubFormat of WAVE_FORMAT_EXTENSIBLE.
 	pub format_code: u16,
+	pub channel_mask: Option<u32>,
 }
 
 impl WavHeader {
 	pub fn to_format(&self) -> WavFormat {
+		let samples_per_block = match AdpcmKind::from_format_code(self.format_code) {
+			Some(kind) => {
+				kind.samples_per_block(self.channels.count() as usize, self.block_align as usize)
+			}
+			None => 0,
+		};
 		WavFormat {
 			channels: self.channels,
 			sample_rate: self.sample_rate,
 			bit_depth: self.bits_per_sample,
 			format_code: self.format_code,
+			samples_per_block: samples_per_block as u16,
+			channel_mask: self.channel_mask,
 		}
 	}
 
@@ -29,7 +47,10 @@ impl WavHeader {
 		}
 
 		match self.format_code {
-			1 | 3 => self.validate_pcm_bits(),
+			1 => self.validate_pcm_bits(),
+			3 => self.validate_float_bits(),
+			0x02 => self.validate_ms_adpcm(),
+			0x06 | 0x07 => self.validate_g711(),
 			0x11 => self.validate_ima_adpcm(),
 			code => Err(error!("audio format code {} is not supported", code)),
 		}
@@ -39,9 +60,19 @@ impl WavHeader {
 		if self.bits_per_sample == 0 {
 			return Err(error!("bits per sample must be non-zero"));
 		}
-		if self.bits_per_sample % 8 != 0 {
+		if !self.bits_per_sample.is_multiple_of(8) {
 			return Err(error!("bits per sample must be multiple of 8"));
 		}
+		if self.bits_per_sample > 32 {
+			return Err(error!("{} bit integer PCM is not supported", self.bits_per_sample));
+		}
+		Ok(())
+	}
+
+	pub fn validate_float_bits(&self) -> Result<()> {
+		if self.bits_per_sample != 32 && self.bits_per_sample != 64 {
+			return Err(error!("float PCM must have 32 or 64 bits per sample"));
+		}
 		Ok(())
 	}
 
@@ -49,6 +80,34 @@ impl WavHeader {
 		if self.bits_per_sample != 4 {
 			return Err(error!("IMA ADPCM must have 4 bits per sample"));
 		}
+		self.validate_adpcm_block(AdpcmKind::Ima)
+	}
+
+	pub fn validate_g711(&self) -> Result<()> {
+		if self.bits_per_sample != 8 {
+			return Err(error!("G.711 must have 8 bits per sample"));
+		}
+		Ok(())
+	}
+
+	pub fn validate_ms_adpcm(&self) -> Result<()> {
+		if self.bits_per_sample != 4 {
+			return Err(error!("MS ADPCM must have 4 bits per sample"));
+		}
+		self.validate_adpcm_block(AdpcmKind::Microsoft)
+	}
+
+	/// Blocks must hold whole samples, so that packets of `block_align` bytes
+	/// line up with them.
+	fn validate_adpcm_block(&self, kind: AdpcmKind) -> Result<()> {
+		let channels = self.channels.count() as usize;
+		let samples = kind.samples_per_block(channels, self.block_align as usize);
+		if samples == 0
+			|| samples > u16::MAX as usize
+			|| kind.block_size(channels, samples) != self.block_align as usize
+		{
+			return Err(error!("ADPCM block align {} does not hold whole samples", self.block_align));
+		}
 		Ok(())
 	}
 }
@@ -1,13 +1,19 @@
+use super::bwf::{Bext, CuePoint, Sampler};
 use std::collections::HashMap;
 
 #[derive(Debug, Clone)]
 pub struct WavMetadata {
+	/// LIST INFO text fields.
 	pub fields: HashMap<String, String>,
+	pub bext: Option<Bext>,
+	pub ixml: Option<String>,
+	pub cues: Vec<CuePoint>,
+	pub sampler: Option<Sampler>,
 }
 
 impl WavMetadata {
 	pub fn new() -> Self {
-		Self { fields: HashMap::new() }
+		Self { fields: HashMap::new(), bext: None, ixml: None, cues: Vec::new(), sampler: None }
 	}
 
 	pub fn set(&mut self, key: &str, value: String) {
@@ -40,6 +46,10 @@ impl WavMetadata {
 
 	pub fn is_empty(&self) -> bool {
 		self.fields.is_empty()
+			&& self.bext.is_none()
+			&& self.ixml.is_none()
+			&& self.cues.is_empty()
+			&& self.sampler.is_none()
 	}
 }
 
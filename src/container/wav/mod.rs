@@ -1,3 +1,4 @@
+pub mod bwf;
 pub mod converter;
 pub mod demuxer;
 pub mod formater;
@@ -1,3 +1,9 @@
+use std::any::Any;
+
+use crate::codecs::audio::adpcm::AdpcmKind;
+use crate::codecs::audio::adpcm::ms::COEFFICIENTS;
+use crate::container::wav::bwf;
+use crate::container::wav::header::{EXTENSIBLE_SIZE, FORMAT_EXTENSIBLE, SUBFORMAT_SUFFIX};
 use crate::container::wav::{WavFormat, WavMetadata};
 use crate::core::Muxer;
 use crate::core::packet::Packet;
@@ -6,22 +12,42 @@ use crate::core::time::Time;
 use crate::io::{MediaSeek, MediaWrite, SeekFrom, WritePrimitives};
 use crate::message::Result;
 
+// riff size, data size and sample count, then an empty chunk size table
+const DS64_SIZE: u32 = 28;
+
+// LIST INFO ids of the metadata fields, in the order they are written
+const INFO_FIELDS: [(&str, &[u8; 4]); 7] = [
+	("title", b"INAM"),
+	("artist", b"IART"),
+	("comment", b"ICOM"),
+	("copyright", b"ICOP"),
+	("software", b"ISFT"),
+	("genre", b"IGNR"),
+	("track", b"ITRK"),
+];
+
 pub struct WavMuxer<W: MediaWrite + MediaSeek> {
 	writer: W,
-	#[allow(dead_code)]
 	format: WavFormat,
 	streams: stream::Streams,
 	metadata: Option<WavMetadata>,
-	data_size: u32,
+	data_size: u64,
+	/// End of the samples packets gave a duration for, which leaves out the
+	/// padding of a last ADPCM block.
+	samples_end: Option<u64>,
 	data_size_pos: u64,
 	file_size_pos: u64,
+	/// Where the JUNK chunk that becomes ds64 for RF64 starts.
+	junk_pos: u64,
+	/// Where the sample count of the fact chunk goes, for ADPCM.
+	fact_pos: Option<u64>,
+	/// The header waits for the first packet, so metadata that belongs
+	/// before the data can still be set.
+	header_written: bool,
 }
 
 impl<W: MediaWrite + MediaSeek> WavMuxer<W> {
-	pub fn new(mut writer: W, format: WavFormat) -> Result<Self> {
-		let (file_size_pos, data_size_pos) = Self::write_header(&mut writer, &format)?;
-		writer.flush()?;
-
+	pub fn new(writer: W, format: WavFormat) -> Result<Self> {
 		let codec_name = format.to_codec_string().to_string();
 		let time = Time::new(1, format.sample_rate);
 		let mut streams = stream::Streams::new_empty();
@@ -29,114 +55,232 @@ impl<W: MediaWrite + MediaSeek> WavMuxer<W> {
 
 		streams.add(stream);
 
-		Ok(Self { writer, format, streams, metadata: None, data_size: 0, data_size_pos, file_size_pos })
+		Ok(Self {
+			writer,
+			format,
+			streams,
+			metadata: None,
+			data_size: 0,
+			samples_end: None,
+			data_size_pos: 0,
+			file_size_pos: 0,
+			junk_pos: 0,
+			fact_pos: None,
+			header_written: false,
+		})
 	}
 
 	pub fn with_metadata(&mut self, metadata: Option<WavMetadata>) {
 		self.metadata = metadata;
 	}
 
-	fn write_header(writer: &mut W, format: &WavFormat) -> Result<(u64, u64)> {
+	fn ensure_header(&mut self) -> Result<()> {
+		if self.header_written {
+			return Ok(());
+		}
+		let metadata = self.metadata.as_ref();
+		let (file_size_pos, junk_pos, fact_pos, data_size_pos) =
+			Self::write_header(&mut self.writer, &self.format, metadata)?;
+		self.file_size_pos = file_size_pos;
+		self.junk_pos = junk_pos;
+		self.fact_pos = fact_pos;
+		self.data_size_pos = data_size_pos;
+		self.header_written = true;
+		Ok(())
+	}
+
+	fn write_header(
+		writer: &mut W,
+		format: &WavFormat,
+		metadata: Option<&WavMetadata>,
+	) -> Result<(u64, u64, Option<u64>, u64)> {
 		writer.write_all(b"RIFF")?;
 		let file_size_pos = writer.stream_position()?;
 		writer.write_u32_le(0)?;
 		writer.write_all(b"WAVE")?;
+
+		// room for a ds64 chunk, should the file outgrow 32 bit sizes
+		let junk_pos = writer.stream_position()?;
+		writer.write_all(b"JUNK")?;
+		writer.write_u32_le(DS64_SIZE)?;
+		writer.write_all(&[0; DS64_SIZE as usize])?;
+
+		// broadcast wave readers expect these before the audio
+		if let Some(metadata) = metadata {
+			if let Some(bext) = &metadata.bext {
+				Self::write_chunk(writer, b"bext", &bext.to_bytes())?;
+			}
+			if let Some(ixml) = &metadata.ixml {
+				Self::write_chunk(writer, b"iXML", ixml.as_bytes())?;
+			}
+		}
 		writer.write_all(b"fmt ")?;
 
-		let fmt_size = match format.format_code {
-			3 => 18,
-			0x11 => 20,
-			_ => 16,
+		// a channel mask needs WAVE_FORMAT_EXTENSIBLE, which only wraps PCM
+		let extensible = format.channel_mask.is_some() && matches!(format.format_code, 1 | 3);
+
+		let fmt_size = match format.adpcm() {
+			_ if extensible => 18 + EXTENSIBLE_SIZE as u32,
+			Some(AdpcmKind::Ima) => 20,
+			Some(AdpcmKind::Microsoft) => 22 + 4 * COEFFICIENTS.len() as u32,
+			// formats other than integer PCM carry an empty extension
+			None if format.format_code == 1 => 16,
+			None => 18,
 		};
 		writer.write_u32_le(fmt_size)?;
-		writer.write_u16_le(format.format_code)?;
+		writer.write_u16_le(if extensible { FORMAT_EXTENSIBLE } else { format.format_code })?;
 		writer.write_u16_le(format.channels.count() as u16)?;
 		writer.write_u32_le(format.sample_rate)?;
 		writer.write_u32_le(format.byte_rate())?;
 		writer.write_u16_le(format.block_align())?;
 		writer.write_u16_le(format.bit_depth)?;
 
-		if format.format_code == 3 {
-			writer.write_u16_le(0)?;
-		} else if format.format_code == 0x11 {
-			writer.write_u16_le(4)?;
-			let spb = ((512 - 4 * format.channels.count() as usize) * 2 + 1) as u16;
-			writer.write_u16_le(spb)?;
+		match format.adpcm() {
+			_ if extensible => {
+				writer.write_u16_le(EXTENSIBLE_SIZE)?;
+				writer.write_u16_le(format.bit_depth)?;
+				writer.write_u32_le(format.channel_mask.unwrap_or_default())?;
+				writer.write_u16_le(format.format_code)?;
+				writer.write_all(&SUBFORMAT_SUFFIX)?;
+			}
+			Some(AdpcmKind::Ima) => {
+				writer.write_u16_le(2)?;
+				writer.write_u16_le(format.samples_per_block)?;
+			}
+			Some(AdpcmKind::Microsoft) => {
+				writer.write_u16_le(fmt_size as u16 - 18)?;
+				writer.write_u16_le(format.samples_per_block)?;
+				writer.write_u16_le(COEFFICIENTS.len() as u16)?;
+				for (coefficient1, coefficient2) in COEFFICIENTS {
+					writer.write_u16_le(coefficient1 as i16 as u16)?;
+					writer.write_u16_le(coefficient2 as i16 as u16)?;
+				}
+			}
+			None if format.format_code == 1 => {}
+			None => writer.write_u16_le(0)?,
+		}
+
+		// compressed formats carry their length in samples
+		let mut fact_pos = None;
+		if format.is_compressed() {
+			writer.write_all(b"fact")?;
+			writer.write_u32_le(4)?;
+			fact_pos = Some(writer.stream_position()?);
+			writer.write_u32_le(0)?;
 		}
 
 		writer.write_all(b"data")?;
 		let data_size_pos = writer.stream_position()?;
 		writer.write_u32_le(0)?;
-		Ok((file_size_pos, data_size_pos))
+		Ok((file_size_pos, junk_pos, fact_pos, data_size_pos))
 	}
 
 	pub fn write_packet(&mut self, packet: Packet) -> Result<()> {
+		self.ensure_header()?;
 		self.writer.write_all(&packet.data)?;
-		self.data_size += packet.data.len() as u32;
+		self.data_size += packet.data.len() as u64;
+		if packet.duration > 0 {
+			let end = (packet.pts + packet.duration).max(0) as u64;
+			self.samples_end = Some(self.samples_end.map_or(end, |samples| samples.max(end)));
+		}
 		Ok(())
 	}
 
 	pub fn finalize(&mut self) -> Result<()> {
-		self.writer.seek(SeekFrom::Start(self.data_size_pos))?;
-		self.writer.write_u32_le(self.data_size)?;
+		self.ensure_header()?;
+		// chunks start on even offsets
+		if self.data_size % 2 == 1 {
+			self.writer.write_u8(0)?;
+		}
 
-		let mut file_size = self.data_size + 36;
+		if let Some(metadata) = &self.metadata {
+			Self::write_trailing_chunks(&mut self.writer, metadata)?;
+		}
+		let file_size = self.writer.stream_position()? - self.file_size_pos - 4;
+		let samples = self.samples_end.unwrap_or_else(|| self.format.samples_in(self.data_size));
 
-		if let Some(meta) = &self.metadata {
-			if !meta.is_empty() {
-				file_size += Self::calc_list_size(meta) as u32;
-				self.writer.seek(SeekFrom::End(0))?;
-				Self::write_list_chunk(&mut self.writer, meta)?;
-			}
+		if file_size > u32::MAX as u64 {
+			return self.finalize_rf64(file_size, samples);
+		}
+
+		self.writer.seek(SeekFrom::Start(self.data_size_pos))?;
+		self.writer.write_u32_le(self.data_size as u32)?;
+
+		if let Some(fact_pos) = self.fact_pos {
+			self.writer.seek(SeekFrom::Start(fact_pos))?;
+			self.writer.write_u32_le(samples as u32)?;
 		}
 
 		self.writer.seek(SeekFrom::Start(self.file_size_pos))?;
-		self.writer.write_u32_le(file_size)?;
+		self.writer.write_u32_le(file_size as u32)?;
 		self.writer.flush()?;
 		Ok(())
 	}
 
-	fn calc_list_size(metadata: &WavMetadata) -> u64 {
-		metadata.all_fields().values().fold(8, |acc, v| {
-			let mut size = acc + 8 + v.len() as u64 + 1;
-			if (v.len() + 1) % 2 == 1 {
-				size += 1;
-			}
-			size
-		})
+	/// Turns the file into RF64: the JUNK chunk becomes ds64 and holds the
+	/// sizes, which are 0xFFFFFFFF everywhere else.
+	fn finalize_rf64(&mut self, file_size: u64, samples: u64) -> Result<()> {
+		self.writer.seek(SeekFrom::Start(self.junk_pos))?;
+		self.writer.write_all(b"ds64")?;
+		self.writer.write_u32_le(DS64_SIZE)?;
+		self.writer.write_u64_le(file_size)?;
+		self.writer.write_u64_le(self.data_size)?;
+		self.writer.write_u64_le(samples)?;
+		self.writer.write_u32_le(0)?;
+
+		self.writer.seek(SeekFrom::Start(self.data_size_pos))?;
+		self.writer.write_u32_le(u32::MAX)?;
+
+		if let Some(fact_pos) = self.fact_pos {
+			self.writer.seek(SeekFrom::Start(fact_pos))?;
+			self.writer.write_u32_le(u32::MAX)?;
+		}
+
+		self.writer.seek(SeekFrom::Start(self.file_size_pos - 4))?;
+		self.writer.write_all(b"RF64")?;
+		self.writer.write_u32_le(u32::MAX)?;
+		self.writer.flush()?;
+		Ok(())
 	}
 
-	fn write_list_chunk(writer: &mut W, metadata: &WavMetadata) -> Result<()> {
-		if metadata.is_empty() {
-			return Ok(());
+	/// Cue points, loops and text, which go after the audio.
+	fn write_trailing_chunks(writer: &mut W, metadata: &WavMetadata) -> Result<()> {
+		if !metadata.cues.is_empty() {
+			Self::write_chunk(writer, b"cue ", &bwf::cue_to_bytes(&metadata.cues))?;
+			if let Some(adtl) = bwf::adtl_to_bytes(&metadata.cues) {
+				Self::write_chunk(writer, b"LIST", &adtl)?;
+			}
+		}
+		if let Some(sampler) = &metadata.sampler {
+			Self::write_chunk(writer, b"smpl", &sampler.to_bytes())?;
 		}
+		Self::write_list_chunk(writer, metadata)
+	}
 
-		let list_size = Self::calc_list_size(metadata) - 8;
-		writer.write_all(b"LIST")?;
-		writer.write_u32_le(list_size as u32)?;
-		writer.write_all(b"INFO")?;
-
-		for (field, value) in metadata.all_fields() {
-			let id: &[u8; 4] = match field.as_str() {
-				"artist" => b"IART",
-				"title" => b"INAM",
-				"comment" => b"ICOM",
-				"copyright" => b"ICOP",
-				"software" => b"ISFT",
-				"genre" => b"IGNR",
-				"track" => b"ITRK",
-				_ => continue,
+	fn write_list_chunk(writer: &mut W, metadata: &WavMetadata) -> Result<()> {
+		let mut list = b"INFO".to_vec();
+		for (field, id) in INFO_FIELDS {
+			let Some(value) = metadata.get(field) else {
+				continue;
 			};
-			Self::write_info_chunk(writer, id, value)?;
+			let data = format!("{}\0", value);
+			list.extend_from_slice(id);
+			list.extend_from_slice(&(data.len() as u32).to_le_bytes());
+			list.extend_from_slice(data.as_bytes());
+			if data.len() % 2 == 1 {
+				list.push(0);
+			}
 		}
-		Ok(())
+		if list.len() == 4 {
+			return Ok(());
+		}
+		Self::write_chunk(writer, b"LIST", &list)
 	}
 
-	fn write_info_chunk(writer: &mut W, id: &[u8; 4], value: &str) -> Result<()> {
-		let data = format!("{}\0", value);
+	fn write_chunk(writer: &mut W, id: &[u8; 4], data: &[u8]) -> Result<()> {
 		writer.write_all(id)?;
 		writer.write_u32_le(data.len() as u32)?;
-		writer.write_all(data.as_bytes())?;
+		writer.write_all(data)?;
 		if data.len() % 2 == 1 {
 			writer.write_u8(0)?;
 		}
@@ -154,4 +298,10 @@ impl<W: MediaWrite + MediaSeek> Muxer for WavMuxer<W> {
 	fn finalize(&mut self) -> Result<()> {
 		self.finalize()
 	}
+	fn update_container_metadata(&mut self, metadata: &dyn Any) -> Result<()> {
+		if let Some(metadata) = metadata.downcast_ref::<WavMetadata>() {
+			self.with_metadata(Some(metadata.clone()));
+		}
+		Ok(())
+	}
 }
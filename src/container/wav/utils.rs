@@ -36,6 +36,14 @@
 // 	}
 // }
 
+pub fn normalize_pcm8(sample: u8) -> f32 {
+	(sample as f32 - 128.0) / 128.0
+}
+
+pub fn denormalize_pcm8(normalized: f32) -> u8 {
+	(normalized * 128.0 + 128.0).clamp(0.0, 255.0) as u8
+}
+
 pub fn normalize_pcm16(sample: i16) -> f32 {
 	sample as f32 / 32768.0
 }
@@ -59,3 +67,19 @@ pub fn normalize_pcm32(sample: f32) -> f32 {
 pub fn denormalize_pcm32(normalized: f32) -> f32 {
 	normalized.clamp(-1.0, 1.0)
 }
+
+pub fn normalize_pcm32_int(sample: i32) -> f32 {
+	(sample as f64 / 2147483648.0) as f32
+}
+
+pub fn denormalize_pcm32_int(normalized: f32) -> i32 {
+	(normalized as f64 * 2147483648.0).clamp(-2147483648.0, 2147483647.0) as i32
+}
+
+pub fn normalize_pcm64(sample: f64) -> f32 {
+	sample.clamp(-1.0, 1.0) as f32
+}
+
+pub fn denormalize_pcm64(normalized: f32) -> f64 {
+	normalized.clamp(-1.0, 1.0) as f64
+}
@@ -0,0 +1,4 @@
+/// WebM is a Matroska profile (VP8/VP9/AV1, Vorbis/Opus and WebVTT), which
+/// the Matroska demuxer reads as is, reporting the "webm" document type.
+/// The muxer writes it after `with_doc_type("webm")`.
+pub use crate::container::mkv::{MkvDemuxer as WebmDemuxer, MkvMuxer as WebmMuxer};
@@ -1,12 +1,23 @@
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum AudioFormat {
+	PCM8,  // unsigned
+	PCM8S, // signed
 	PCM16,
 	PCM24,
-	PCM32,
+	PCM32,  // float
+	PCM32I, // signed integer
+	PCM64,  // float
+	PCM16BE,
+	PCM24BE,
+	PCM32BE,  // float
+	PCM32IBE, // signed integer
+	PCM64BE,  // float
 	FLAC,
 	AAC,
 	OPUS,
 	ADPCM,
+	MULAW,
+	ALAW,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -58,10 +69,32 @@ impl Channels {
 impl AudioFormat {
 	pub fn bytes_per_sample(&self) -> Option<usize> {
 		match self {
-			AudioFormat::PCM16 => Some(2),
-			AudioFormat::PCM24 => Some(3),
-			AudioFormat::PCM32 => Some(4),
-			AudioFormat::FLAC | AudioFormat::AAC | AudioFormat::OPUS | AudioFormat::ADPCM => None,
+			AudioFormat::PCM8 | AudioFormat::PCM8S => Some(1),
+			AudioFormat::PCM16 | AudioFormat::PCM16BE => Some(2),
+			AudioFormat::PCM24 | AudioFormat::PCM24BE => Some(3),
+			AudioFormat::PCM32 | AudioFormat::PCM32I => Some(4),
+			AudioFormat::PCM32BE | AudioFormat::PCM32IBE => Some(4),
+			AudioFormat::PCM64 | AudioFormat::PCM64BE => Some(8),
+			AudioFormat::FLAC
+			| AudioFormat::AAC
+			| AudioFormat::OPUS
+			| AudioFormat::ADPCM
+			| AudioFormat::MULAW
+			| AudioFormat::ALAW => None,
+		}
+	}
+
+	/// The layout frames carry samples stored as `self` in: little-endian,
+	/// with 8 bit samples unsigned.
+	pub fn native(&self) -> AudioFormat {
+		match self {
+			AudioFormat::PCM8S => AudioFormat::PCM8,
+			AudioFormat::PCM16BE => AudioFormat::PCM16,
+			AudioFormat::PCM24BE => AudioFormat::PCM24,
+			AudioFormat::PCM32BE => AudioFormat::PCM32,
+			AudioFormat::PCM32IBE => AudioFormat::PCM32I,
+			AudioFormat::PCM64BE => AudioFormat::PCM64,
+			other => *other,
 		}
 	}
 }
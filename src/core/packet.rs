@@ -5,6 +5,8 @@ pub struct Packet {
 	pub data: Vec<u8>,
 	pub pts: i64,
 	pub dts: i64,
+	/// Length in `time` units, 0 when the packet does not tell.
+	pub duration: i64,
 	pub time: Time,
 	pub stream_id: u32,
 	pub keyframe: bool,
@@ -13,7 +15,7 @@ pub struct Packet {
 
 impl Packet {
 	pub fn new(data: Vec<u8>, stream_id: u32, time: Time) -> Self {
-		Self { data, pts: 0, dts: 0, time, stream_id, keyframe: false, discard: false }
+		Self { data, pts: 0, dts: 0, duration: 0, time, stream_id, keyframe: false, discard: false }
 	}
 
 	pub fn with_pts(mut self, pts: i64) -> Self {
@@ -26,6 +28,11 @@ impl Packet {
 		self
 	}
 
+	pub fn with_duration(mut self, duration: i64) -> Self {
+		self.duration = duration;
+		self
+	}
+
 	pub fn with_keyframe(mut self, keyframe: bool) -> Self {
 		self.keyframe = keyframe;
 		self
@@ -97,7 +97,7 @@ impl Display for Stream {
 impl Display for Streams {
 	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
 		for stream in &self.inner {
-			write!(f, "{}\n", stream)?;
+			writeln!(f, "{}", stream)?;
 		}
 		Ok(())
 	}
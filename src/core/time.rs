@@ -1,3 +1,5 @@
+use std::cmp::Ordering;
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct Time {
 	pub num: u32,
@@ -18,8 +20,25 @@ impl Time {
 		((seconds * self.den as f64) / self.num as f64) as i64
 	}
 	pub fn scale_pts(&self, pts: i64, target: Time) -> i64 {
-		(pts as i128 * target.num as i128 / target.den as i128 * self.den as i128 / self.num as i128)
-			as i64
+		let num = target.num as i128 * self.den as i128;
+		let den = target.den as i128 * self.num as i128;
+		(pts as i128 * num / den) as i64
+	}
+
+	/// Like `scale_pts`, to the nearest unit rather than toward zero.
+	pub fn round_pts(&self, pts: i64, target: Time) -> i64 {
+		let num = target.num as i128 * self.den as i128;
+		let den = target.den as i128 * self.num as i128;
+		let value = pts as i128 * num;
+		((value + value.signum() * den / 2) / den) as i64
+	}
+
+	/// Orders `ts` on this time base against `other_ts` on `other`, without
+	/// the rounding of a rescale.
+	pub fn compare(&self, ts: i64, other: Time, other_ts: i64) -> Ordering {
+		let left = ts as i128 * self.num as i128 * other.den as i128;
+		let right = other_ts as i128 * other.num as i128 * self.den as i128;
+		left.cmp(&right)
 	}
 
 	pub fn gcd(&self) -> u32 {
@@ -1,3 +1,5 @@
+use std::any::Any;
+
 use crate::core::packet::Packet;
 use crate::core::stream::Streams;
 use crate::message::Result;
@@ -6,6 +8,12 @@ pub trait Demuxer {
 	fn streams(&self) -> &Streams;
 	fn read_packet(&mut self) -> Result<Option<Packet>>;
 
+	/// What the container holds beyond its streams, for a muxer of the same
+	/// container; complete once every packet is read.
+	fn container_metadata(&self) -> Option<&dyn Any> {
+		None
+	}
+
 	fn read_audio_packet(&mut self) -> Result<Option<Packet>> {
 		while let Some(packet) = self.read_packet()? {
 			let stream = self.streams().get(packet.stream_id);
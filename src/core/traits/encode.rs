@@ -5,4 +5,17 @@ use crate::message::Result;
 pub trait Encoder {
 	fn encode(&mut self, frame: Frame) -> Result<Option<Packet>>;
 	fn flush(&mut self) -> Result<Option<Packet>>;
+
+	/// Drains packets queued by the last `encode` call, for encoders that can
+	/// produce more than one packet from a single frame.
+	fn receive(&mut self) -> Result<Option<Packet>> {
+		Ok(None)
+	}
+
+	/// Codec configuration for the output stream (e.g. FLAC STREAMINFO).
+	/// Encoders may refine it while encoding, so muxers should read it again
+	/// after `flush`.
+	fn codec_private(&self) -> Option<Vec<u8>> {
+		None
+	}
 }
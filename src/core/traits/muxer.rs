@@ -1,3 +1,5 @@
+use std::any::Any;
+
 use crate::core::packet::Packet;
 use crate::core::stream::Streams;
 use crate::message::Result;
@@ -6,4 +8,38 @@ pub trait Muxer {
 	fn streams(&self) -> &Streams;
 	fn write(&mut self, packet: Packet) -> Result<()>;
 	fn finalize(&mut self) -> Result<()>;
+
+	/// Takes the codec configuration an encoder settled on at flush, for
+	/// muxers that write it once the stream is done.
+	fn update_codec_private(&mut self, _stream_id: u32, _codec_private: &[u8]) -> Result<()> {
+		Ok(())
+	}
+
+	/// Takes what a demuxer of the same container found beyond its streams,
+	/// for muxers that write it once the streams are done.
+	fn update_container_metadata(&mut self, _metadata: &dyn Any) -> Result<()> {
+		Ok(())
+	}
+}
+
+impl<M: Muxer + ?Sized> Muxer for Box<M> {
+	fn streams(&self) -> &Streams {
+		(**self).streams()
+	}
+
+	fn write(&mut self, packet: Packet) -> Result<()> {
+		(**self).write(packet)
+	}
+
+	fn finalize(&mut self) -> Result<()> {
+		(**self).finalize()
+	}
+
+	fn update_codec_private(&mut self, stream_id: u32, codec_private: &[u8]) -> Result<()> {
+		(**self).update_codec_private(stream_id, codec_private)
+	}
+
+	fn update_container_metadata(&mut self, metadata: &dyn Any) -> Result<()> {
+		(**self).update_container_metadata(metadata)
+	}
 }
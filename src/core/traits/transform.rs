@@ -3,4 +3,14 @@ use crate::{core::frame::Frame, message::Result};
 pub trait Transform: Send {
 	fn apply(&mut self, frame: Frame) -> Result<Frame>;
 	fn name(&self) -> &'static str;
+
+	/// Whether the transform must see the whole stream through `analyze`
+	/// before `apply` is called (e.g. two-pass normalization).
+	fn needs_analysis(&self) -> bool {
+		false
+	}
+
+	fn analyze(&mut self, _frame: &Frame) -> Result<()> {
+		Ok(())
+	}
 }
@@ -0,0 +1,235 @@
+use crate::{error, message::Result};
+
+/// MSB-first bit reader over a byte slice.
+pub struct BitReader<'a> {
+	data: &'a [u8],
+	pos: usize,
+}
+
+impl<'a> BitReader<'a> {
+	#[inline]
+	pub const fn new(data: &'a [u8]) -> Self {
+		Self { data, pos: 0 }
+	}
+
+	#[inline]
+	pub const fn position(&self) -> usize {
+		self.pos
+	}
+
+	#[inline]
+	pub fn set_position(&mut self, pos: usize) {
+		self.pos = pos;
+	}
+
+	#[inline]
+	pub const fn byte_position(&self) -> usize {
+		self.pos.div_ceil(8)
+	}
+
+	#[inline]
+	pub const fn bits_left(&self) -> usize {
+		(self.data.len() * 8).saturating_sub(self.pos)
+	}
+
+	#[inline]
+	pub const fn is_aligned(&self) -> bool {
+		self.pos.is_multiple_of(8)
+	}
+
+	#[inline]
+	pub fn align(&mut self) {
+		self.pos = self.pos.div_ceil(8) * 8;
+	}
+
+	#[inline]
+	pub fn data(&self) -> &'a [u8] {
+		self.data
+	}
+
+	pub fn read_bit(&mut self) -> Result<bool> {
+		if self.pos >= self.data.len() * 8 {
+			return Err(error!("unexpected end of bitstream"));
+		}
+		let bit = (self.data[self.pos >> 3] >> (7 - (self.pos & 7))) & 1;
+		self.pos += 1;
+		Ok(bit == 1)
+	}
+
+	/// Reads up to 32 bits as an unsigned value.
+	pub fn read(&mut self, bits: u32) -> Result<u32> {
+		debug_assert!(bits <= 32);
+		if bits == 0 {
+			return Ok(0);
+		}
+		if self.bits_left() < bits as usize {
+			return Err(error!("unexpected end of bitstream"));
+		}
+		let value = self.peek_unchecked(bits);
+		self.pos += bits as usize;
+		Ok(value)
+	}
+
+	pub fn read_u64(&mut self, bits: u32) -> Result<u64> {
+		debug_assert!(bits <= 64);
+		if bits <= 32 {
+			return Ok(self.read(bits)? as u64);
+		}
+		let high = self.read(bits - 32)? as u64;
+		let low = self.read(32)? as u64;
+		Ok((high << 32) | low)
+	}
+
+	pub fn read_signed(&mut self, bits: u32) -> Result<i32> {
+		if bits == 0 {
+			return Ok(0);
+		}
+		let value = self.read(bits)?;
+		let shift = 32 - bits;
+		Ok(((value << shift) as i32) >> shift)
+	}
+
+	/// Peeks up to 32 bits, padding with zeros past the end of the data.
+	pub fn peek(&self, bits: u32) -> u32 {
+		if bits == 0 {
+			return 0;
+		}
+		self.peek_unchecked(bits)
+	}
+
+	pub fn skip(&mut self, bits: usize) -> Result<()> {
+		if self.bits_left() < bits {
+			return Err(error!("unexpected end of bitstream"));
+		}
+		self.pos += bits;
+		Ok(())
+	}
+
+	/// Counts zero bits up to the next one bit and consumes them all.
+	pub fn read_unary(&mut self) -> Result<u32> {
+		let mut count = 0;
+		loop {
+			let byte_index = self.pos >> 3;
+			if byte_index >= self.data.len() {
+				return Err(error!("unexpected end of bitstream"));
+			}
+			let offset = (self.pos & 7) as u32;
+			let byte = self.data[byte_index] << offset;
+			if byte == 0 {
+				count += 8 - offset;
+				self.pos += (8 - offset) as usize;
+				continue;
+			}
+			let zeros = byte.leading_zeros();
+			count += zeros;
+			self.pos += zeros as usize + 1;
+			return Ok(count);
+		}
+	}
+
+	fn peek_unchecked(&self, bits: u32) -> u32 {
+		let mut value: u64 = 0;
+		let start = self.pos >> 3;
+		for i in 0..5 {
+			let byte = self.data.get(start + i).copied().unwrap_or(0);
+			value = (value << 8) | byte as u64;
+		}
+		let offset = (self.pos & 7) as u32;
+		((value << (24 + offset)) >> (64 - bits)) as u32
+	}
+}
+
+/// MSB-first bit writer into a growable buffer.
+#[derive(Debug, Default, Clone)]
+pub struct BitWriter {
+	data: Vec<u8>,
+	acc: u64,
+	bits: u32,
+}
+
+impl BitWriter {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn with_capacity(capacity: usize) -> Self {
+		Self { data: Vec::with_capacity(capacity), acc: 0, bits: 0 }
+	}
+
+	#[inline]
+	pub fn bit_len(&self) -> usize {
+		self.data.len() * 8 + self.bits as usize
+	}
+
+	#[inline]
+	pub fn is_aligned(&self) -> bool {
+		self.bits.is_multiple_of(8)
+	}
+
+	pub fn write_bit(&mut self, bit: bool) {
+		self.write(bit as u32, 1);
+	}
+
+	/// Writes the low `bits` bits of `value`, up to 32 bits.
+	pub fn write(&mut self, value: u32, bits: u32) {
+		debug_assert!(bits <= 32);
+		if bits == 0 {
+			return;
+		}
+		let mask = if bits == 32 { u32::MAX } else { (1 << bits) - 1 };
+		self.acc = (self.acc << bits) | (value & mask) as u64;
+		self.bits += bits;
+		while self.bits >= 8 {
+			self.bits -= 8;
+			self.data.push((self.acc >> self.bits) as u8);
+		}
+	}
+
+	pub fn write_u64(&mut self, value: u64, bits: u32) {
+		if bits > 32 {
+			self.write((value >> 32) as u32, bits - 32);
+			self.write(value as u32, 32);
+		} else {
+			self.write(value as u32, bits);
+		}
+	}
+
+	pub fn write_signed(&mut self, value: i32, bits: u32) {
+		self.write(value as u32, bits);
+	}
+
+	/// Writes `count` zero bits followed by a one bit.
+	pub fn write_unary(&mut self, mut count: u32) {
+		while count >= 32 {
+			self.write(0, 32);
+			count -= 32;
+		}
+		self.write(1, count + 1);
+	}
+
+	pub fn write_bytes(&mut self, bytes: &[u8]) {
+		if self.is_aligned() {
+			self.data.extend_from_slice(bytes);
+			return;
+		}
+		for &byte in bytes {
+			self.write(byte as u32, 8);
+		}
+	}
+
+	/// Pads with zero bits up to the next byte boundary.
+	pub fn align(&mut self) {
+		let pad = (8 - self.bits % 8) % 8;
+		self.write(0, pad);
+	}
+
+	/// Bytes completed so far; a partial trailing byte is not included.
+	pub fn bytes(&self) -> &[u8] {
+		&self.data
+	}
+
+	pub fn finish(mut self) -> Vec<u8> {
+		self.align();
+		self.data
+	}
+}
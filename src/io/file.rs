@@ -13,8 +13,12 @@ impl File {
 		Ok(Self { file })
 	}
 
+	/// Creates or truncates a file, open for reading back as well, which
+	/// muxers that rearrange their output need.
 	pub fn create(path: &str) -> Result<Self> {
-		let file = mapper_error(std::fs::File::create(path), path)?;
+		let mut options = std::fs::OpenOptions::new();
+		options.read(true).write(true).create(true).truncate(true);
+		let file = mapper_error(options.open(path), path)?;
 		Ok(Self { file })
 	}
 }
@@ -1,3 +1,4 @@
+mod bits;
 mod cursor;
 mod file;
 mod reader;
@@ -5,6 +6,7 @@ mod seek;
 pub mod stdio;
 mod writer;
 
+pub use bits::{BitReader, BitWriter};
 pub use cursor::Cursor;
 pub use file::File;
 pub use reader::{
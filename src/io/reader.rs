@@ -176,7 +176,7 @@ impl<R> StdReadAdapter<R> {
 impl<R: std::io::Read> MediaRead for StdReadAdapter<R> {
 	#[inline]
 	fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
-		self.inner.read(buf).map_err(|e| crate::message::Message::from(e))
+		self.inner.read(buf).map_err(crate::message::Message::from)
 	}
 }
 
@@ -82,7 +82,7 @@ impl<S> StdSeekAdapter<S> {
 impl<S: std::io::Seek> MediaSeek for StdSeekAdapter<S> {
 	#[inline]
 	fn seek(&mut self, pos: SeekFrom) -> Result<u64> {
-		self.inner.seek(pos.into()).map_err(|e| crate::message::Message::from(e))
+		self.inner.seek(pos.into()).map_err(crate::message::Message::from)
 	}
 }
 
@@ -1,3 +1,4 @@
+use crate::io::{Cursor, Error, ErrorKind, File, MediaRead, MediaSeek, SeekFrom};
 use crate::message::Result;
 use std::io::{Read, Write};
 
@@ -9,9 +10,15 @@ impl StdinAdapter {
 	}
 }
 
+impl Default for StdinAdapter {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
 impl crate::io::MediaRead for StdinAdapter {
 	fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
-		std::io::stdin().read(buf).map_err(|e| crate::message::Message::from(e))
+		std::io::stdin().read(buf).map_err(crate::message::Message::from)
 	}
 }
 
@@ -23,29 +30,83 @@ impl StdoutAdapter {
 	}
 }
 
+impl Default for StdoutAdapter {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
 impl crate::io::MediaWrite for StdoutAdapter {
 	fn write(&mut self, buf: &[u8]) -> Result<usize> {
-		std::io::stdout().write(buf).map_err(|e| crate::message::Message::from(e))
+		std::io::stdout().write(buf).map_err(crate::message::Message::from)
 	}
 
 	fn flush(&mut self) -> Result<()> {
-		std::io::stdout().flush().map_err(|e| crate::message::Message::from(e))
+		std::io::stdout().flush().map_err(crate::message::Message::from)
 	}
 }
 
+/// An input read from a file, or from standard input when its path is `-`.
 pub enum StdioSource {
-	Stdin(StdinAdapter),
-	File(std::fs::File),
+	/// Standard input, with the bytes peeked at it to be read again first.
+	Stdin(StdinAdapter, Cursor<Vec<u8>>),
+	File(File),
 }
 
-impl crate::io::MediaRead for StdioSource {
-	fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+impl StdioSource {
+	pub fn open(path: &str) -> Result<Self> {
+		match path {
+			"-" => Ok(StdioSource::Stdin(StdinAdapter::new(), Cursor::new(Vec::new()))),
+			_ => Ok(StdioSource::File(File::open(path)?)),
+		}
+	}
+
+	pub fn is_seekable(&self) -> bool {
+		matches!(self, StdioSource::File(_))
+	}
+
+	/// Up to `size` bytes from the start of the input, which are read again
+	/// by the next reads. Standard input keeps them in memory.
+	pub fn peek(&mut self, size: usize) -> Result<Vec<u8>> {
+		let mut data = vec![0u8; size];
+		let mut filled = 0;
+		while filled < size {
+			let read = self.read(&mut data[filled..])?;
+			if read == 0 {
+				break;
+			}
+			filled += read;
+		}
+		data.truncate(filled);
 		match self {
-			StdioSource::Stdin(stdin) => stdin.read(buf),
+			StdioSource::Stdin(_, replay) => *replay = Cursor::new(data.clone()),
 			StdioSource::File(file) => {
-				use std::io::Read;
-				file.read(buf).map_err(|e| crate::message::Message::from(e))
+				MediaSeek::seek(file, SeekFrom::Start(0))?;
+			}
+		}
+		Ok(data)
+	}
+}
+
+impl MediaRead for StdioSource {
+	fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+		match self {
+			StdioSource::Stdin(stdin, replay) => match replay.read(buf)? {
+				0 => stdin.read(buf),
+				read => Ok(read),
+			},
+			StdioSource::File(file) => MediaRead::read(file, buf),
+		}
+	}
+}
+
+impl MediaSeek for StdioSource {
+	fn seek(&mut self, position: SeekFrom) -> Result<u64> {
+		match self {
+			StdioSource::Stdin(..) => {
+				Err(Error::with_message(ErrorKind::NotSeekable, "standard input cannot seek").into())
 			}
+			StdioSource::File(file) => MediaSeek::seek(file, position),
 		}
 	}
 }
@@ -61,7 +122,7 @@ impl crate::io::MediaWrite for StdioSink {
 			StdioSink::Stdout(stdout) => stdout.write(buf),
 			StdioSink::File(file) => {
 				use std::io::Write;
-				file.write(buf).map_err(|e| crate::message::Message::from(e))
+				file.write(buf).map_err(crate::message::Message::from)
 			}
 		}
 	}
@@ -71,7 +132,7 @@ impl crate::io::MediaWrite for StdioSink {
 			StdioSink::Stdout(stdout) => stdout.flush(),
 			StdioSink::File(file) => {
 				use std::io::Write;
-				file.flush().map_err(|e| crate::message::Message::from(e))
+				file.flush().map_err(crate::message::Message::from)
 			}
 		}
 	}
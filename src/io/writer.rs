@@ -142,12 +142,12 @@ impl<W> StdWriteAdapter<W> {
 impl<W: std::io::Write> MediaWrite for StdWriteAdapter<W> {
 	#[inline]
 	fn write(&mut self, buf: &[u8]) -> Result<usize> {
-		self.inner.write(buf).map_err(|e| Message::from(e))
+		self.inner.write(buf).map_err(Message::from)
 	}
 
 	#[inline]
 	fn flush(&mut self) -> Result<()> {
-		self.inner.flush().map_err(|e| Message::from(e))
+		self.inner.flush().map_err(Message::from)
 	}
 }
 
@@ -0,0 +1,96 @@
+use crate::core::Transform;
+use crate::core::frame::Frame;
+use crate::message::Result;
+
+/// Ordered list of transforms applied to every decoded frame.
+///
+/// Transforms that need the whole stream (see `Transform::needs_analysis`)
+/// split the chain into passes: frames are buffered until `flush`, where
+/// each pass analyzes the buffered frames before the next one applies.
+#[derive(Default)]
+pub struct TransformChain {
+	transforms: Vec<Box<dyn Transform>>,
+	pending: Vec<Frame>,
+}
+
+impl TransformChain {
+	pub fn new() -> Self {
+		Self::default()
+	}
+
+	pub fn push(&mut self, transform: Box<dyn Transform>) {
+		self.transforms.push(transform);
+	}
+
+	pub fn with(mut self, transform: Box<dyn Transform>) -> Self {
+		self.push(transform);
+		self
+	}
+
+	pub fn len(&self) -> usize {
+		self.transforms.len()
+	}
+
+	pub fn is_empty(&self) -> bool {
+		self.transforms.is_empty()
+	}
+
+	pub fn names(&self) -> Vec<&'static str> {
+		self.transforms.iter().map(|t| t.name()).collect()
+	}
+
+	pub fn needs_analysis(&self) -> bool {
+		self.analysis_index(0).is_some()
+	}
+
+	/// Runs a frame through the chain. Returns nothing while frames are being
+	/// buffered for an analysis pass.
+	pub fn process(&mut self, frame: Frame) -> Result<Vec<Frame>> {
+		let Some(index) = self.analysis_index(0) else {
+			return Ok(vec![self.apply_range(frame, 0, self.transforms.len())?]);
+		};
+
+		let frame = self.apply_range(frame, 0, index)?;
+		self.transforms[index].analyze(&frame)?;
+		self.pending.push(frame);
+		Ok(Vec::new())
+	}
+
+	pub fn flush(&mut self) -> Result<Vec<Frame>> {
+		let mut frames = std::mem::take(&mut self.pending);
+		let Some(mut start) = self.analysis_index(0) else {
+			return Ok(frames);
+		};
+
+		loop {
+			let next = self.analysis_index(start + 1);
+			let end = next.unwrap_or(self.transforms.len());
+
+			let mut processed = Vec::with_capacity(frames.len());
+			for frame in frames {
+				processed.push(self.apply_range(frame, start, end)?);
+			}
+			frames = processed;
+
+			let Some(next) = next else {
+				return Ok(frames);
+			};
+
+			for frame in &frames {
+				self.transforms[next].analyze(frame)?;
+			}
+			start = next;
+		}
+	}
+
+	fn analysis_index(&self, from: usize) -> Option<usize> {
+		(from..self.transforms.len()).find(|&i| self.transforms[i].needs_analysis())
+	}
+
+	fn apply_range(&mut self, mut frame: Frame, start: usize, end: usize) -> Result<Frame> {
+		for transform in &mut self.transforms[start..end] {
+			frame = transform.apply(frame)?;
+		}
+		Ok(frame)
+	}
+}
@@ -1,5 +1,7 @@
+pub mod chain;
 pub mod normalize;
 pub mod volume;
 
-pub use normalize::Normalize;
+pub use chain::TransformChain;
+pub use normalize::{Normalize, NormalizeMode};
 pub use volume::Volume;
@@ -1,18 +1,128 @@
+use crate::container::wav::converter;
 use crate::core::Transform;
 use crate::core::frame::Frame;
 use crate::message::Result;
 
-pub struct Normalize {}
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NormalizeMode {
+	Peak,
+	Rms,
+}
+
+/// Two-pass normalizer: every frame of the stream goes through `analyze`
+/// first, then `apply` scales all frames by the same gain. When nothing was
+/// analyzed, each frame is normalized on its own.
+pub struct Normalize {
+	mode: NormalizeMode,
+	target: f32,
+	peak: f32,
+	sum_squares: f64,
+	count: u64,
+}
+
+impl Normalize {
+	pub const DEFAULT_PEAK: f32 = 1.0;
+	pub const DEFAULT_RMS: f32 = 0.1;
+
+	pub fn new(target: f32) -> Self {
+		Self::with_mode(NormalizeMode::Peak, target)
+	}
+
+	pub fn rms(target: f32) -> Self {
+		Self::with_mode(NormalizeMode::Rms, target)
+	}
+
+	pub fn with_mode(mode: NormalizeMode, target: f32) -> Self {
+		Self { mode, target, peak: 0.0, sum_squares: 0.0, count: 0 }
+	}
+
+	pub fn mode(&self) -> NormalizeMode {
+		self.mode
+	}
+
+	pub fn target(&self) -> f32 {
+		self.target
+	}
+
+	pub fn peak(&self) -> f32 {
+		self.peak
+	}
+
+	pub fn rms_level(&self) -> f32 {
+		if self.count == 0 {
+			return 0.0;
+		}
+		(self.sum_squares / self.count as f64).sqrt() as f32
+	}
+
+	pub fn reset(&mut self) {
+		self.peak = 0.0;
+		self.sum_squares = 0.0;
+		self.count = 0;
+	}
 
-impl Normalize {}
+	/// Gain derived from the analyzed stream. RMS gain is limited so the
+	/// loudest sample never clips.
+	pub fn gain(&self) -> f32 {
+		if self.peak <= 0.0 {
+			return 1.0;
+		}
+		match self.mode {
+			NormalizeMode::Peak => self.target / self.peak,
+			NormalizeMode::Rms => {
+				let rms = self.rms_level();
+				if rms <= 0.0 {
+					return 1.0;
+				}
+				(self.target / rms).min(1.0 / self.peak)
+			}
+		}
+	}
+
+	fn accumulate(&mut self, samples: &[f32]) {
+		for &sample in samples {
+			self.peak = self.peak.max(sample.abs());
+			self.sum_squares += (sample as f64) * (sample as f64);
+		}
+		self.count += samples.len() as u64;
+	}
+}
 
 impl Transform for Normalize {
 	fn apply(&mut self, mut frame: Frame) -> Result<Frame> {
-		if let Some(_audio_frame) = frame.audio_mut() {}
+		let analyzed = self.count > 0;
+		if let Some(audio) = frame.audio_mut() {
+			let mut samples = converter::audio_to_f32(audio)?;
+			if !analyzed {
+				self.accumulate(&samples);
+			}
+
+			let gain = self.gain();
+			for sample in samples.iter_mut() {
+				*sample = (*sample * gain).clamp(-1.0, 1.0);
+			}
+			audio.data = converter::audio_from_f32(&samples, audio)?;
+
+			if !analyzed {
+				self.reset();
+			}
+		}
 		Ok(frame)
 	}
 
 	fn name(&self) -> &'static str {
 		"normalize"
 	}
+
+	fn needs_analysis(&self) -> bool {
+		true
+	}
+
+	fn analyze(&mut self, frame: &Frame) -> Result<()> {
+		if let Some(audio) = frame.audio() {
+			let samples = converter::audio_to_f32(audio)?;
+			self.accumulate(&samples);
+		}
+		Ok(())
+	}
 }
@@ -1,8 +1,8 @@
+use crate::container::wav::converter;
 use crate::core::Transform;
 use crate::core::frame::Frame;
 use crate::message::Result;
 
-#[allow(dead_code)]
 pub struct Volume {
 	factor: f32,
 }
@@ -11,11 +11,21 @@ impl Volume {
 	pub fn new(factor: f32) -> Self {
 		Self { factor }
 	}
+
+	pub fn factor(&self) -> f32 {
+		self.factor
+	}
 }
 
 impl Transform for Volume {
 	fn apply(&mut self, mut frame: Frame) -> Result<Frame> {
-		if let Some(_audio_frame) = frame.audio_mut() {}
+		if let Some(audio) = frame.audio_mut() {
+			let mut samples = converter::audio_to_f32(audio)?;
+			for sample in samples.iter_mut() {
+				*sample = (*sample * self.factor).clamp(-1.0, 1.0);
+			}
+			audio.data = converter::audio_from_f32(&samples, audio)?;
+		}
 		Ok(frame)
 	}
 
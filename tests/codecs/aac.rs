@@ -0,0 +1,15 @@
+use tempfile::tempdir;
+
+use crate::common::{assert_close, data, read_raw, read_wav, run};
+
+// the reference is symphonia's decode of the same stream
+#[test]
+fn aac_decode_matches_reference() {
+	let dir = tempdir().unwrap();
+	let decoded = dir.path().join("tone.wav");
+	run(&data("tone.aac"), &decoded, &[]);
+
+	let output = read_wav(&decoded);
+	assert_eq!((output.sample_rate, output.channels), (44100, 2));
+	assert_close(&output.samples, &read_raw("tone_aac.pcm"), 1);
+}
@@ -0,0 +1,145 @@
+use std::f64::consts::PI;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use ffmpreg::cli::{Cli, executor};
+
+/// Integer PCM with its layout.
+pub struct Pcm {
+	pub sample_rate: u32,
+	pub channels: u16,
+	pub bits: u16,
+	/// Interleaved samples, sign extended from `bits`.
+	pub samples: Vec<i32>,
+}
+
+impl Pcm {
+	pub fn frames(&self) -> usize {
+		self.samples.len() / self.channels as usize
+	}
+
+	pub fn channel(&self, index: usize) -> Vec<i32> {
+		self.samples.iter().skip(index).step_by(self.channels as usize).copied().collect()
+	}
+}
+
+/// A file of the test data.
+pub fn data(name: &str) -> PathBuf {
+	Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data").join(name)
+}
+
+/// Raw little endian 16 bit samples of the test data.
+pub fn read_raw(name: &str) -> Vec<i32> {
+	let bytes = fs::read(data(name)).unwrap();
+	bytes.chunks_exact(2).map(|pair| i16::from_le_bytes([pair[0], pair[1]]) as i32).collect()
+}
+
+/// Runs the command line from `input` to `output`.
+pub fn run(input: &Path, output: &Path, audio: &[&str]) {
+	let cli = Cli {
+		input: input.to_string_lossy().into_owned(),
+		output: output.to_string_lossy().into_owned(),
+		audio: audio.iter().map(|option| option.to_string()).collect(),
+		video: Vec::new(),
+		subtitle: Vec::new(),
+		apply: Vec::new(),
+	};
+	executor::execute(cli).unwrap();
+}
+
+/// A sweep with a second tone and some noise on each channel, at three
+/// quarters of full scale.
+pub fn signal(sample_rate: u32, channels: u16, bits: u16, frames: usize) -> Pcm {
+	let scale = 0.75 * ((1i64 << (bits - 1)) - 1) as f64;
+	let mut noise = 0x1234_5678u32;
+	let mut samples = Vec::with_capacity(frames * channels as usize);
+	for frame in 0..frames {
+		let time = frame as f64 / sample_rate as f64;
+		for channel in 0..channels {
+			noise ^= noise << 13;
+			noise ^= noise >> 17;
+			noise ^= noise << 5;
+			let sweep = (2.0 * PI * (200.0 * time + 2000.0 * time * time)).sin();
+			let tone = (2.0 * PI * (500.0 + 300.0 * channel as f64) * time).sin();
+			let dither = noise as f64 / u32::MAX as f64 - 0.5;
+			let value = 0.6 * sweep + 0.3 * tone + 0.1 * dither;
+			samples.push((value * scale).round() as i32);
+		}
+	}
+	Pcm { sample_rate, channels, bits, samples }
+}
+
+pub fn write_wav(path: &Path, pcm: &Pcm) {
+	let width = pcm.bits as usize / 8;
+	let mut body = Vec::with_capacity(pcm.samples.len() * width);
+	for sample in &pcm.samples {
+		body.extend_from_slice(&sample.to_le_bytes()[..width]);
+	}
+	let block_align = pcm.channels as u32 * width as u32;
+	let mut file = b"RIFF".to_vec();
+	file.extend_from_slice(&(36 + body.len() as u32).to_le_bytes());
+	file.extend_from_slice(b"WAVEfmt ");
+	file.extend_from_slice(&16u32.to_le_bytes());
+	file.extend_from_slice(&1u16.to_le_bytes());
+	file.extend_from_slice(&pcm.channels.to_le_bytes());
+	file.extend_from_slice(&pcm.sample_rate.to_le_bytes());
+	file.extend_from_slice(&(pcm.sample_rate * block_align).to_le_bytes());
+	file.extend_from_slice(&(block_align as u16).to_le_bytes());
+	file.extend_from_slice(&pcm.bits.to_le_bytes());
+	file.extend_from_slice(b"data");
+	file.extend_from_slice(&(body.len() as u32).to_le_bytes());
+	file.extend_from_slice(&body);
+	fs::write(path, file).unwrap();
+}
+
+/// Reads integer PCM from a wav file, skipping chunks other than fmt and
+/// data.
+pub fn read_wav(path: &Path) -> Pcm {
+	let file = fs::read(path).unwrap();
+	assert_eq!(&file[..4], b"RIFF");
+	assert_eq!(&file[8..12], b"WAVE");
+	let u16_at = |at: usize| u16::from_le_bytes([file[at], file[at + 1]]);
+	let u32_at = |at: usize| u32::from_le_bytes([file[at], file[at + 1], file[at + 2], file[at + 3]]);
+
+	let (mut layout, mut data) = (None, None);
+	let mut offset = 12;
+	while offset + 8 <= file.len() {
+		let size = u32_at(offset + 4) as usize;
+		let body = offset + 8;
+		match &file[offset..offset + 4] {
+			b"fmt " => layout = Some((u16_at(body + 2), u32_at(body + 4), u16_at(body + 14))),
+			b"data" => data = Some(&file[body..body + size]),
+			_ => {}
+		}
+		offset = body + size + size % 2;
+	}
+
+	let (channels, sample_rate, bits) = layout.expect("wav file has no fmt chunk");
+	let data = data.expect("wav file has no data chunk");
+	let width = bits as usize / 8;
+	let samples = data
+		.chunks_exact(width)
+		.map(|bytes| {
+			let mut value = [0u8; 4];
+			value[4 - width..].copy_from_slice(bytes);
+			i32::from_le_bytes(value) >> (32 - bits)
+		})
+		.collect();
+	Pcm { sample_rate, channels, bits, samples }
+}
+
+/// Checks that every sample is within `tolerance` of the reference.
+pub fn assert_close(actual: &[i32], expected: &[i32], tolerance: i32) {
+	assert_eq!(actual.len(), expected.len(), "sample counts differ");
+	let worst = actual.iter().zip(expected).enumerate().max_by_key(|(_, (a, e))| (*a - *e).abs());
+	if let Some((index, (actual, expected))) = worst {
+		let difference = (actual - expected).abs();
+		assert!(
+			difference <= tolerance,
+			"sample {} is {} where {} was expected",
+			index,
+			actual,
+			expected
+		);
+	}
+}
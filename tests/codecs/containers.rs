@@ -0,0 +1,70 @@
+use tempfile::tempdir;
+
+use crate::common::{Pcm, read_wav, run, signal, write_wav};
+
+/// The offset of `output` against `input` that lines them up best, within
+/// `range` samples either way.
+fn lag(input: &[i32], output: &[i32], range: i64) -> i64 {
+	let window = 8192.min(input.len() as i64 - 2 * range);
+	let start = range;
+	let correlation = |lag: i64| -> f64 {
+		(start..start + window)
+			.map(|index| input[index as usize] as f64 * output[(index + lag) as usize] as f64)
+			.sum()
+	};
+	(-range..=range).max_by(|a, b| correlation(*a).total_cmp(&correlation(*b))).unwrap()
+}
+
+fn round_trip(container: &str, audio: &[&str]) -> (Pcm, Pcm) {
+	let dir = tempdir().unwrap();
+	let source = dir.path().join("in.wav");
+	let muxed = dir.path().join(format!("out.{}", container));
+	let decoded = dir.path().join("out.wav");
+	// not a whole number of frames for any of the codecs
+	let pcm = signal(44100, 2, 16, 44101);
+	write_wav(&source, &pcm);
+
+	run(&source, &muxed, audio);
+	run(&muxed, &decoded, &[]);
+	(pcm, read_wav(&decoded))
+}
+
+fn assert_aligned(input: &Pcm, output: &Pcm) {
+	assert_eq!(output.frames(), input.frames());
+	for channel in 0..input.channels as usize {
+		assert_eq!(lag(&input.channel(channel), &output.channel(channel), 1200), 0);
+	}
+}
+
+#[test]
+fn mkv_keeps_copied_flac() {
+	let dir = tempdir().unwrap();
+	let source = dir.path().join("in.wav");
+	let (flac, muxed, decoded) =
+		(dir.path().join("in.flac"), dir.path().join("out.mkv"), dir.path().join("out.wav"));
+	let pcm = signal(44100, 2, 16, 44101);
+	write_wav(&source, &pcm);
+
+	run(&source, &flac, &[]);
+	run(&flac, &muxed, &["codec=copy"]);
+	run(&muxed, &decoded, &[]);
+	assert_eq!(read_wav(&decoded).samples, pcm.samples);
+}
+
+#[test]
+fn mkv_keeps_aac_length_and_offset() {
+	let (input, output) = round_trip("mkv", &["codec=aac"]);
+	assert_aligned(&input, &output);
+}
+
+#[test]
+fn mp4_keeps_aac_length_and_offset() {
+	let (input, output) = round_trip("mp4", &["codec=aac"]);
+	assert_aligned(&input, &output);
+}
+
+#[test]
+fn m4a_keeps_aac_length_and_offset() {
+	let (input, output) = round_trip("m4a", &["codec=aac"]);
+	assert_aligned(&input, &output);
+}
@@ -0,0 +1,31 @@
+use tempfile::tempdir;
+
+use crate::common::{read_wav, run, signal, write_wav};
+
+fn round_trip(bits: u16, channels: u16, level: &str) {
+	let dir = tempdir().unwrap();
+	let (source, flac, decoded) =
+		(dir.path().join("in.wav"), dir.path().join("out.flac"), dir.path().join("out.wav"));
+	let pcm = signal(44100, channels, bits, 30011);
+	write_wav(&source, &pcm);
+
+	run(&source, &flac, &[&format!("compression_level={}", level)]);
+	run(&flac, &decoded, &[]);
+
+	let output = read_wav(&decoded);
+	assert_eq!((output.sample_rate, output.channels, output.bits), (44100, channels, bits));
+	assert_eq!(output.samples, pcm.samples);
+}
+
+#[test]
+fn flac_round_trip_is_lossless() {
+	for level in ["0", "5", "8"] {
+		round_trip(16, 2, level);
+	}
+}
+
+#[test]
+fn flac_round_trip_24_bit() {
+	round_trip(24, 2, "5");
+	round_trip(24, 1, "8");
+}
@@ -0,0 +1,6 @@
+mod aac;
+mod common;
+mod containers;
+mod flac;
+mod mp3;
+mod opus;
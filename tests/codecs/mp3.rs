@@ -0,0 +1,15 @@
+use tempfile::tempdir;
+
+use crate::common::{assert_close, data, read_raw, read_wav, run};
+
+// the reference is minimp3's decode of the same stream
+#[test]
+fn mp3_decode_matches_reference() {
+	let dir = tempdir().unwrap();
+	let decoded = dir.path().join("tone.wav");
+	run(&data("tone.mp3"), &decoded, &[]);
+
+	let output = read_wav(&decoded);
+	assert_eq!((output.sample_rate, output.channels), (48000, 1));
+	assert_close(&output.samples, &read_raw("tone_mp3.pcm"), 1);
+}
@@ -0,0 +1,38 @@
+use std::fs;
+
+use ffmpreg::codecs::audio::opus::OpusDecoder;
+
+use crate::common::{assert_close, data, read_raw};
+
+// the streams are opus_demo bitstreams, each packet behind its length and
+// the encoder's final range, and the references libopus's decode of them
+fn decode(name: &str, channels: usize) -> Vec<i32> {
+	let stream = fs::read(data(name)).unwrap();
+	let mut decoder = OpusDecoder::new(channels);
+	let mut pcm = Vec::new();
+	let mut offset = 0;
+	while offset < stream.len() {
+		let word = |at: usize| u32::from_be_bytes(stream[at..at + 4].try_into().unwrap());
+		let (length, range) = (word(offset) as usize, word(offset + 4));
+		let packet = &stream[offset + 8..offset + 8 + length];
+		decoder.decode_packet(packet, &mut pcm).unwrap();
+		assert_eq!(decoder.final_range(), range, "range differs after byte {}", offset);
+		offset += 8 + length;
+	}
+	pcm.iter().map(|sample| (sample * 32768.0).round().clamp(-32768.0, 32767.0) as i32).collect()
+}
+
+#[test]
+fn opus_silk_matches_reference() {
+	assert_close(&decode("opus_silk.bit", 1), &read_raw("opus_silk.pcm"), 1);
+}
+
+#[test]
+fn opus_hybrid_matches_reference() {
+	assert_close(&decode("opus_hybrid.bit", 2), &read_raw("opus_hybrid.pcm"), 1);
+}
+
+#[test]
+fn opus_celt_matches_reference() {
+	assert_close(&decode("opus_celt.bit", 2), &read_raw("opus_celt.pcm"), 1);
+}